@@ -0,0 +1,76 @@
+//! Compares the size of the client object sets with each `ObjectSetEncoding`.
+//!
+//! Run with `cargo bench -p steadyum-api-types --features dim3,pod-framing`.
+
+use std::time::Instant;
+use steadyum_api_types::objects::{ClientBodyObject, ClientBodyObjectSet};
+use steadyum_api_types::rapier::math::{Isometry, Real, Vector};
+use steadyum_api_types::serialization::{
+    deserialize_client_objects, serialize_client_objects, ObjectSetEncoding,
+};
+use steadyum_api_types::simulation::SimulationBounds;
+use uuid::Uuid;
+
+const NUM_BODIES: usize = 100_000;
+
+fn main() {
+    let region =
+        SimulationBounds::from_point(Vector::zeros().into(), SimulationBounds::DEFAULT_WIDTH);
+    let aabb = region.aabb();
+    let objects = (0..NUM_BODIES)
+        .map(|i| {
+            let t = i as Real;
+            let local =
+                Vector::new(t.sin(), (t * 0.7).cos(), (t * 1.3).sin()) * 0.5 + Vector::repeat(0.5);
+            let translation = aabb.mins.coords + aabb.extents().component_mul(&local);
+            ClientBodyObject {
+                uuid: Uuid::new_v4(),
+                position: Isometry::new(translation, Vector::new(t, t * 0.3, t * 0.1)),
+                linvel: Vector::new(t.cos(), 0.0, t.sin()),
+                angvel: Vector::new(0.0, t.cos(), 0.0),
+                shape_hash: i as u64,
+                sleep_start_frame: None,
+                epoch: 0,
+            }
+        })
+        .collect();
+    let set = ClientBodyObjectSet {
+        timestamp: 0,
+        objects,
+        cold_objects: vec![],
+        ghosts: vec![],
+        partial: false,
+    };
+
+    for encoding in [
+        ObjectSetEncoding::Full,
+        ObjectSetEncoding::Quantized,
+        ObjectSetEncoding::Pod,
+    ] {
+        let t0 = Instant::now();
+        let bytes = serialize_client_objects(set.clone(), &region, encoding).unwrap();
+        let encode_time = t0.elapsed().as_secs_f32() * 1000.0;
+
+        let t0 = Instant::now();
+        let decoded = deserialize_client_objects(&bytes, &region).unwrap();
+        let decode_time = t0.elapsed().as_secs_f32() * 1000.0;
+
+        let max_error = set
+            .objects
+            .iter()
+            .zip(decoded.objects.iter())
+            .map(|(a, b)| (a.position.translation.vector - b.position.translation.vector).norm())
+            .fold(0.0, Real::max);
+
+        println!(
+            "{:?}: {} bodies, {} bytes ({:.2} bytes/body), encode: {:.2}ms, decode: {:.2}ms, max translation error: {}",
+            encoding,
+            NUM_BODIES,
+            bytes.len(),
+            bytes.len() as f32 / NUM_BODIES as f32,
+            encode_time,
+            decode_time,
+            max_error
+        );
+    }
+}
@@ -0,0 +1,133 @@
+//! The checks of the bodies inserted in a region, so a malformed one can’t poison its
+//! simulation, see [`AdmissionPolicy`].
+
+use crate::messages::BodyAssignment;
+use rapier::math::{AngVector, Isometry, Real, Vector};
+use uuid::Uuid;
+
+/// Why a runner refused to simulate a body.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum RejectionReason {
+    /// Its position or rotation has a NaN or infinite component.
+    NonFinitePosition,
+    /// Its velocities aren’t finite, or exceed the maximums of the scene.
+    InvalidVelocity,
+    /// Its shape has no extent, or an infinite one.
+    DegenerateShape,
+    /// The density of a dynamic body isn’t finite and positive.
+    InvalidDensity,
+}
+
+/// A body refused by the region it was assigned to.
+#[derive(Copy, Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RejectedBody {
+    pub uuid: Uuid,
+    pub reason: RejectionReason,
+}
+
+/// How the runners of a scene handle the malformed bodies assigned to their regions, instead
+/// of handing them to Rapier.
+///
+/// The bodies that can’t be fixed are rejected: the runner drops them, and reports them as
+/// [`crate::messages::SimulationEvent::BodyRejected`] events.
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AdmissionPolicy {
+    /// The fastest linear velocity accepted.
+    pub max_linvel: Real,
+    /// The fastest angular velocity accepted, in radians per second.
+    pub max_angvel: Real,
+    /// Clamp the velocities above the maximums, and zero the ones that aren’t finite, instead
+    /// of rejecting the body.
+    pub clamp_velocities: bool,
+    /// Move the bodies whose position isn’t finite to the origin instead of rejecting them.
+    pub snap_nan_to_origin: bool,
+}
+
+impl Default for AdmissionPolicy {
+    fn default() -> Self {
+        Self {
+            max_linvel: 1.0e4,
+            max_angvel: 1.0e4,
+            clamp_velocities: false,
+            snap_nan_to_origin: false,
+        }
+    }
+}
+
+impl AdmissionPolicy {
+    /// Checks `body` before it is inserted in a region, fixing what this policy allows, or
+    /// returns why it must be rejected.
+    pub fn admit(&self, body: &mut BodyAssignment) -> Result<(), RejectionReason> {
+        let extents = body.cold.shape.compute_local_aabb().extents();
+        if !extents.iter().all(|e| e.is_finite()) || extents.max() <= 0.0 {
+            return Err(RejectionReason::DegenerateShape);
+        }
+
+        if body.cold.body_type.is_dynamic()
+            && !(body.cold.density.is_finite() && body.cold.density > 0.0)
+        {
+            return Err(RejectionReason::InvalidDensity);
+        }
+
+        let warm = &mut body.warm;
+        if !warm.position.to_homogeneous().iter().all(|e| e.is_finite()) {
+            if !self.snap_nan_to_origin {
+                return Err(RejectionReason::NonFinitePosition);
+            }
+            warm.position = Isometry::identity();
+        }
+
+        let valid_linvel =
+            warm.linvel.iter().all(|e| e.is_finite()) && warm.linvel.norm() <= self.max_linvel;
+        let valid_angvel =
+            angvel_is_finite(&warm.angvel) && angvel_norm(&warm.angvel) <= self.max_angvel;
+        if !(valid_linvel && valid_angvel) {
+            if !self.clamp_velocities {
+                return Err(RejectionReason::InvalidVelocity);
+            }
+
+            if warm.linvel.iter().all(|e| e.is_finite()) {
+                warm.linvel = warm.linvel.cap_magnitude(self.max_linvel);
+            } else {
+                warm.linvel = Vector::zeros();
+            }
+            if angvel_is_finite(&warm.angvel) {
+                warm.angvel = cap_angvel(warm.angvel, self.max_angvel);
+            } else {
+                warm.angvel = Default::default();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "dim2")]
+fn angvel_is_finite(angvel: &AngVector<Real>) -> bool {
+    angvel.is_finite()
+}
+
+#[cfg(feature = "dim3")]
+fn angvel_is_finite(angvel: &AngVector<Real>) -> bool {
+    angvel.iter().all(|e| e.is_finite())
+}
+
+#[cfg(feature = "dim2")]
+fn angvel_norm(angvel: &AngVector<Real>) -> Real {
+    angvel.abs()
+}
+
+#[cfg(feature = "dim3")]
+fn angvel_norm(angvel: &AngVector<Real>) -> Real {
+    angvel.norm()
+}
+
+#[cfg(feature = "dim2")]
+fn cap_angvel(angvel: AngVector<Real>, max: Real) -> AngVector<Real> {
+    angvel.clamp(-max, max)
+}
+
+#[cfg(feature = "dim3")]
+fn cap_angvel(angvel: AngVector<Real>, max: Real) -> AngVector<Real> {
+    angvel.cap_magnitude(max)
+}
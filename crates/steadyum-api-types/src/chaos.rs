@@ -0,0 +1,53 @@
+//! Fault injection for the chaos mode, enabled with `CHAOS=true`.
+//!
+//! It randomly kills runners, delays acks and drops zenoh messages to check that the cluster
+//! recovers from these failures.
+
+use crate::env::{Config, CONFIG};
+use rand::Rng;
+use std::time::Duration;
+
+/// Decides which faults to inject. Never injects anything unless the chaos mode is enabled.
+pub struct FaultInjector {
+    enabled: bool,
+    kill_rate: f64,
+    max_ack_delay: Duration,
+    drop_rate: f64,
+}
+
+impl FaultInjector {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            enabled: config.chaos,
+            kill_rate: config.chaos_kill_rate.clamp(0.0, 1.0),
+            max_ack_delay: Duration::from_millis(config.chaos_max_ack_delay_ms),
+            drop_rate: config.chaos_drop_rate.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Whether a runner should be killed, given the time elapsed since it was last considered.
+    pub fn kill_runner(&self, elapsed: Duration) -> bool {
+        // `kill_rate` is a probability per second.
+        let probability = 1.0 - (1.0 - self.kill_rate).powf(elapsed.as_secs_f64());
+        self.enabled && rand::thread_rng().gen_bool(probability.clamp(0.0, 1.0))
+    }
+
+    /// How long to wait before handling an ack, if it should be delayed.
+    pub fn ack_delay(&self) -> Option<Duration> {
+        (self.enabled && !self.max_ack_delay.is_zero())
+            .then(|| self.max_ack_delay.mul_f64(rand::thread_rng().gen()))
+    }
+
+    /// Whether a zenoh message should be dropped instead of published.
+    pub fn drop_message(&self) -> bool {
+        self.enabled && rand::thread_rng().gen_bool(self.drop_rate)
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref FAULTS: FaultInjector = FaultInjector::from_config(&CONFIG);
+}
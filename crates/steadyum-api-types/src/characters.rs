@@ -0,0 +1,75 @@
+//! Kinematic characters moved by the clients, e.g., the players of a game, see
+//! [`CharacterParams`].
+
+use crate::messages::BodyAssignment;
+use crate::objects::{ColdBodyObject, WarmBodyObject};
+use rapier::dynamics::RigidBodyType;
+use rapier::geometry::SharedShape;
+use rapier::math::{Isometry, Real};
+use uuid::Uuid;
+
+/// A capsule moved by the runner simulating it with Rapier’s character controller, following
+/// the [`crate::messages::BodyInput::Move`] intents of a client.
+///
+/// The character slides along the obstacles, climbs slopes and steps, and falls under the
+/// gravity of the scene. It is handed off to the neighbor region when it crosses a border,
+/// like the dynamic bodies.
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CharacterParams {
+    pub radius: Real,
+    /// Half the length of the segment of the capsule.
+    pub half_height: Real,
+    /// The gap kept between the character and the obstacles.
+    pub offset: Real,
+    /// The steepest slope the character can climb, in radians.
+    pub max_slope_climb_angle: Real,
+    /// The gentlest slope the character slides down, in radians.
+    pub min_slope_slide_angle: Real,
+    /// The highest step climbed automatically, if any.
+    pub autostep_height: Option<Real>,
+    /// The distance up to which the character sticks to the ground, e.g., going down stairs.
+    pub snap_to_ground: Option<Real>,
+}
+
+impl Default for CharacterParams {
+    fn default() -> Self {
+        Self {
+            radius: 0.3,
+            half_height: 0.6,
+            offset: 0.01,
+            max_slope_climb_angle: 45.0f32.to_radians() as Real,
+            min_slope_slide_angle: 30.0f32.to_radians() as Real,
+            autostep_height: Some(0.3),
+            snap_to_ground: Some(0.2),
+        }
+    }
+}
+
+impl CharacterParams {
+    pub fn shape(&self) -> SharedShape {
+        SharedShape::capsule_y(self.half_height, self.radius)
+    }
+
+    /// The body of a character at `position`, to insert like the other bodies.
+    pub fn body(&self, uuid: Uuid, position: Isometry<Real>) -> BodyAssignment {
+        BodyAssignment {
+            uuid,
+            warm: WarmBodyObject {
+                position,
+                ..Default::default()
+            },
+            cold: ColdBodyObject {
+                body_type: RigidBodyType::KinematicPositionBased,
+                density: 1.0,
+                shape: self.shape(),
+                animations: Default::default(),
+                user_data: vec![],
+                is_sensor: false,
+                collision_layer: None,
+                character: Some(*self),
+                projectile: None,
+            },
+            epoch: 0,
+        }
+    }
+}
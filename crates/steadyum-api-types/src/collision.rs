@@ -0,0 +1,82 @@
+/// The collision layers of a scene, and the pairs of layers whose bodies interact.
+///
+/// Bodies without layer interact with every body. The layers not part of any allowed pair only
+/// interact with the bodies without layer.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CollisionMatrix {
+    /// The layer names. The layer of a body is the index of its name in this list.
+    pub layers: Vec<String>,
+    /// The pairs of layers, by name, whose bodies interact.
+    pub allowed_pairs: Vec<(String, String)>,
+}
+
+impl CollisionMatrix {
+    /// The maximum number of layers of a scene.
+    pub const MAX_LAYERS: usize = 29;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a layer named `name`.
+    pub fn with_layer(mut self, name: impl Into<String>) -> Self {
+        self.layers.push(name.into());
+        self
+    }
+
+    /// Lets the bodies of the layers `layer1` and `layer2` interact.
+    pub fn allow(mut self, layer1: impl Into<String>, layer2: impl Into<String>) -> Self {
+        self.allowed_pairs.push((layer1.into(), layer2.into()));
+        self
+    }
+
+    /// The index of the layer named `name`, to be set as the layer of the bodies.
+    pub fn layer(&self, name: &str) -> Option<u32> {
+        self.layers
+            .iter()
+            .position(|layer| layer == name)
+            .map(|i| i as u32)
+    }
+
+    /// The layers interacting with `layer`, as a mask where bit `i` stands for the `i`-th layer.
+    pub fn interacting_layers(&self, layer: u32) -> u32 {
+        let mut mask = 0;
+
+        for (layer1, layer2) in &self.allowed_pairs {
+            let (Some(layer1), Some(layer2)) = (self.layer(layer1), self.layer(layer2)) else {
+                continue;
+            };
+
+            if layer1 == layer {
+                mask |= 1 << layer2;
+            }
+            if layer2 == layer {
+                mask |= 1 << layer1;
+            }
+        }
+
+        mask
+    }
+
+    /// Checks that there aren’t too many layers and that the allowed pairs only name known
+    /// layers.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.layers.len() > Self::MAX_LAYERS {
+            anyhow::bail!(
+                "too many collision layers: {} > {}",
+                self.layers.len(),
+                Self::MAX_LAYERS
+            );
+        }
+
+        for (layer1, layer2) in &self.allowed_pairs {
+            for name in [layer1, layer2] {
+                if self.layer(name).is_none() {
+                    anyhow::bail!("unknown collision layer: {name}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
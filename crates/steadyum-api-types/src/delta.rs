@@ -0,0 +1,43 @@
+//! Binary deltas between two versions of an executable, so the updaters only download what
+//! changed.
+//!
+//! A delta is the new executable compressed by zstd with the old one as its dictionary, with a
+//! window large enough for the matches to reach anywhere in the old executable.
+
+/// Slower than the payloads codec, but deltas are computed once per pair of versions.
+const DELTA_LEVEL: i32 = 12;
+
+/// The zstd window, in bits, covering both `base_len` and `target_len` bytes.
+fn window_log(base_len: usize, target_len: usize) -> u32 {
+    let len = base_len.max(target_len).max(1) as u64;
+    (u64::BITS - (len - 1).leading_zeros() + 1).clamp(10, 31)
+}
+
+/// The delta turning `base` into `target`.
+pub fn diff(base: &[u8], target: &[u8]) -> anyhow::Result<Vec<u8>> {
+    use zstd::stream::raw::CParameter;
+
+    let mut compressor = zstd::bulk::Compressor::with_dictionary(DELTA_LEVEL, base)?;
+    compressor.set_parameter(CParameter::WindowLog(window_log(base.len(), target.len())))?;
+    compressor.set_parameter(CParameter::EnableLongDistanceMatching(true))?;
+    Ok(compressor.compress(target)?)
+}
+
+/// Applies the `delta` computed by [`diff`] to `base`, to get back its target of `target_len`
+/// bytes.
+pub fn patch(base: &[u8], delta: &[u8], target_len: usize) -> anyhow::Result<Vec<u8>> {
+    use zstd::stream::raw::DParameter;
+
+    let mut decompressor = zstd::bulk::Decompressor::with_dictionary(base)?;
+    decompressor.set_parameter(DParameter::WindowLogMax(window_log(base.len(), target_len)))?;
+    let target = decompressor.decompress(delta, target_len)?;
+
+    if target.len() != target_len {
+        anyhow::bail!(
+            "The patched executable has {} bytes instead of {target_len}.",
+            target.len()
+        );
+    }
+
+    Ok(target)
+}
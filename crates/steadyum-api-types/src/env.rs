@@ -1,4 +1,106 @@
 use envconfig::Envconfig;
+use std::str::FromStr;
+
+/// How the `step`, `ack` and `client_input` messages are sent to the partitionners.
+///
+/// The administrative endpoints are always served over REST.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ControlPlane {
+    /// One HTTP request per message.
+    #[default]
+    Rest,
+    /// Messages published on zenoh, without waiting for any answer.
+    Zenoh,
+}
+
+impl FromStr for ControlPlane {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "rest" => Ok(Self::Rest),
+            "zenoh" => Ok(Self::Zenoh),
+            _ => Err(format!("Unknown control plane: {s}.")),
+        }
+    }
+}
+
+/// Where the runners and partitionners keep their shared state.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StateStoreKind {
+    /// In the memory of each process.
+    #[default]
+    Memory,
+    /// On the Redis server at `REDIS_ADDR`. Requires the `redis` feature.
+    Redis,
+    /// In the `S3_BUCKET` bucket of an S3-compatible storage. Requires the `s3` feature.
+    S3,
+}
+
+impl FromStr for StateStoreKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "memory" => Ok(Self::Memory),
+            "redis" => Ok(Self::Redis),
+            "s3" => Ok(Self::S3),
+            _ => Err(format!("Unknown state store: {s}.")),
+        }
+    }
+}
+
+/// How a child partitionner launches its runners.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunnerLauncherKind {
+    /// A child process running `RUNNER_EXE`.
+    #[default]
+    Process,
+    /// A sibling container of the `RUNNER_IMAGE` image, with the `docker` command.
+    Docker,
+    /// A pod of the `RUNNER_IMAGE` image in the `RUNNER_NAMESPACE` namespace, with the `kubectl`
+    /// command.
+    Kubernetes,
+}
+
+impl FromStr for RunnerLauncherKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "process" => Ok(Self::Process),
+            "docker" => Ok(Self::Docker),
+            "kubernetes" | "k8s" => Ok(Self::Kubernetes),
+            _ => Err(format!("Unknown runner launcher: {s}.")),
+        }
+    }
+}
+
+/// How a partitionner derives the step limit of a scene from the progress of its clients.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StepLimitPolicy {
+    /// The simulation runs ahead of its furthest client, the others may lag behind.
+    #[default]
+    Furthest,
+    /// The simulation waits for its slowest client.
+    Slowest,
+}
+
+impl FromStr for StepLimitPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "furthest" => Ok(Self::Furthest),
+            "slowest" => Ok(Self::Slowest),
+            _ => Err(format!("Unknown step limit policy: {s}.")),
+        }
+    }
+}
 
 #[derive(Envconfig, serde::Deserialize, Debug, Clone)]
 pub struct Config {
@@ -11,11 +113,197 @@ pub struct Config {
     #[envconfig(from = "RUNNER_EXE", default = "steadyum-runner.exe")]
     pub runner_exe: String,
 
+    #[envconfig(from = "RUNNER_LAUNCHER", default = "process")]
+    pub runner_launcher: RunnerLauncherKind,
+
+    /// The image of the runner containers, whose entrypoint is the runner executable.
+    #[envconfig(from = "RUNNER_IMAGE", default = "steadyum-runner")]
+    pub runner_image: String,
+
+    /// The docker network the runner containers join, sharing the network of their host by
+    /// default.
+    #[envconfig(from = "RUNNER_NETWORK", default = "host")]
+    pub runner_network: String,
+
+    /// The kubernetes namespace of the runner pods.
+    #[envconfig(from = "RUNNER_NAMESPACE", default = "default")]
+    pub runner_namespace: String,
+
+    /// Comma-separated names of the environment variables of the partitionner passed on to the
+    /// runner containers.
+    #[envconfig(
+        from = "RUNNER_ENV",
+        default = "ZENOH_ROUTER,PARTITIONNER_ADDR,PARTITIONNER_PORT,CONTROL_PLANE,STATE_STORE,REDIS_ADDR,S3_BUCKET,S3_REGION,S3_ENDPOINT,OTLP_ENDPOINT"
+    )]
+    pub runner_env: String,
+
     #[envconfig(from = "PRIV_NET_INT", default = "ens4")]
     pub priv_net_int: String,
 
     #[envconfig(from = "ZENOH_ROUTER", default = "tcp/162.19.70.139:7447")]
     pub zenoh_router: String,
+
+    /// Comma-separated endpoints (e.g. `http://10.0.2.154:3536`) tried in order when the
+    /// master partitionner is unreachable. These are the children able to take over, listening
+    /// on their `FAILOVER_PORT`, in registration order.
+    #[envconfig(from = "PARTITIONNER_CANDIDATES", default = "")]
+    pub partitionner_candidates: String,
+
+    /// Port a child partitionner serves the master endpoints on once it took over.
+    #[envconfig(from = "FAILOVER_PORT", default = "3536")]
+    pub failover_port: u16,
+
+    #[envconfig(from = "CONTROL_PLANE", default = "rest")]
+    pub control_plane: ControlPlane,
+
+    #[envconfig(from = "STATE_STORE", default = "memory")]
+    pub state_store: StateStoreKind,
+
+    #[envconfig(from = "REDIS_ADDR", default = "redis://127.0.0.1/")]
+    pub redis_addr: String,
+
+    #[envconfig(from = "S3_BUCKET", default = "steadyum")]
+    pub s3_bucket: String,
+
+    #[envconfig(from = "S3_REGION", default = "us-east-1")]
+    pub s3_region: String,
+
+    /// Endpoint of the S3-compatible storage. The credentials are read from the usual
+    /// `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` variables.
+    #[envconfig(from = "S3_ENDPOINT", default = "http://localhost:9000")]
+    pub s3_endpoint: String,
+
+    /// Comma-separated origins (e.g. `https://viewer.example.com`) of the web viewers allowed
+    /// to use the partitionner as their gateway, or `*` for any origin.
+    #[envconfig(from = "CORS_ORIGINS", default = "")]
+    pub cors_origins: String,
+
+    /// Enables the fault injection of the chaos mode, see [`crate::chaos`].
+    #[envconfig(from = "CHAOS", default = "false")]
+    pub chaos: bool,
+
+    /// With `CHAOS`, probability for each runner to be killed every second.
+    #[envconfig(from = "CHAOS_KILL_RATE", default = "0.01")]
+    pub chaos_kill_rate: f64,
+
+    /// With `CHAOS`, maximum delay, in milliseconds, before an ack is handled.
+    #[envconfig(from = "CHAOS_MAX_ACK_DELAY_MS", default = "200")]
+    pub chaos_max_ack_delay_ms: u64,
+
+    /// With `CHAOS`, probability for each zenoh message to be dropped.
+    #[envconfig(from = "CHAOS_DROP_RATE", default = "0.01")]
+    pub chaos_drop_rate: f64,
+
+    /// Maximum number of client input requests per second accepted by a partitionner from
+    /// each client, the others are rejected with a `429`. Zero disables the limit.
+    #[envconfig(from = "CLIENT_INPUT_RATE_LIMIT", default = "1000")]
+    pub client_input_rate_limit: u32,
+
+    /// Which clients of a scene the simulation keeps up with.
+    #[envconfig(from = "STEP_LIMIT_POLICY", default = "furthest")]
+    pub step_limit_policy: StepLimitPolicy,
+
+    /// A client that sent no input for this long, in milliseconds, is ignored by the
+    /// `STEP_LIMIT_POLICY` until it sends one again.
+    #[envconfig(from = "CLIENT_TIMEOUT_MS", default = "5000")]
+    pub client_timeout_ms: u64,
+
+    /// The OTLP collector the spans are exported to, e.g. `http://localhost:4317`. Spans are
+    /// only logged if empty.
+    #[envconfig(from = "OTLP_ENDPOINT", default = "")]
+    pub otlp_endpoint: String,
+
+    /// A region whose steps take longer than this, in milliseconds, is a hot spot.
+    #[envconfig(from = "HOT_REGION_STEP_MS", default = "1000")]
+    pub hot_region_step_ms: f32,
+
+    /// The maximum number of bodies generated by a single request to spawn templates, the
+    /// larger ones are rejected with a `413`.
+    #[envconfig(from = "MAX_TEMPLATE_BODIES", default = "1000000")]
+    pub max_template_bodies: usize,
+
+    /// A region simulating more bodies than this is a hot spot.
+    #[envconfig(from = "HOT_REGION_BODIES", default = "50000")]
+    pub hot_region_bodies: usize,
+
+    /// A region whose steps take this many times longer than the median region of its scene
+    /// is a hot spot.
+    #[envconfig(from = "HOT_REGION_IMBALANCE", default = "4")]
+    pub hot_region_imbalance: f32,
+
+    /// Checks the invariants of the simulation on the client objects streamed by every region,
+    /// and reports the violations in the scene statuses. This makes all the regions stream
+    /// their objects, for debugging only.
+    #[envconfig(from = "CONSISTENCY_CHECKS", default = "false")]
+    pub consistency_checks: bool,
+
+    /// JSON file listing the tenants sharing the cluster, with their API key and quotas. Every
+    /// request is accepted, without quotas, if empty.
+    #[envconfig(from = "TENANTS_FILE", default = "")]
+    pub tenants_file: String,
+
+    /// The API key sent by the clients to identify their tenant, if any.
+    #[envconfig(from = "API_KEY", default = "")]
+    pub api_key: String,
+
+    /// The secret shared by the partitionners and runners of the cluster, required by the
+    /// endpoints only they call, and by every endpoint of the child partitionners. Required
+    /// with `TENANTS_FILE`, and every request is accepted if empty.
+    #[envconfig(from = "CLUSTER_KEY", default = "")]
+    pub cluster_key: String,
+
+    /// The version of the executables served by the master to the updaters. Defaults to the
+    /// start of their checksum if empty.
+    #[envconfig(from = "RELEASE_VERSION", default = "")]
+    pub release_version: String,
+
+    /// The number of nodes updated at once by a staged rollout, everyone at once if zero.
+    #[envconfig(from = "ROLLOUT_BATCH_SIZE", default = "0")]
+    pub rollout_batch_size: usize,
+
+    /// The time an updated node has to answer its heartbeats again before the rollout halts.
+    #[envconfig(from = "ROLLOUT_HEALTH_TIMEOUT_SECS", default = "120")]
+    pub rollout_health_timeout_secs: u64,
+
+    /// The hex-encoded ed25519 key the updaters check the signatures of the executables with.
+    /// Outside of their dev mode, they refuse to run executables not signed with it.
+    #[envconfig(from = "RELEASE_PUBLIC_KEY", default = "")]
+    pub release_public_key: String,
+}
+
+impl Config {
+    /// The endpoints of [`Self::partitionner_candidates`], as `(addr, port)` pairs.
+    pub fn partitionner_candidates(&self) -> Vec<(String, u16)> {
+        self.partitionner_candidates
+            .split(',')
+            .map(str::trim)
+            .filter(|candidate| !candidate.is_empty())
+            .filter_map(|candidate| {
+                let (addr, port) = candidate.rsplit_once(':')?;
+                Some((addr.to_string(), port.parse().ok()?))
+            })
+            .collect()
+    }
+
+    /// The variables of [`Self::runner_env`], as `NAME=value`, skipping those that aren’t set.
+    pub fn runner_env(&self) -> Vec<String> {
+        self.runner_env
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .filter_map(|name| Some(format!("{name}={}", std::env::var(name).ok()?)))
+            .collect()
+    }
+
+    /// The origins of [`Self::cors_origins`].
+    pub fn cors_origins(&self) -> Vec<String> {
+        self.cors_origins
+            .split(',')
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
 }
 
 pub fn get_config() -> Config {
@@ -0,0 +1,50 @@
+//! The particle fluid of a scene, simulated by each region next to its rigid bodies, see
+//! [`FluidParams`].
+
+use rapier::math::{Point, Real, Vector, DIM};
+
+/// The parameters of the particle fluid of a scene, simulated with SPH (Smoothed-Particle
+/// Hydrodynamics) by the runners.
+///
+/// The particles are pushed out of the colliders, and push the dynamic bodies back. Like the
+/// bodies, they are handed off to the neighbor region when they cross a border, and the
+/// particles close to a border are sent to the neighbors with the watch sets.
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FluidParams {
+    pub particle_radius: Real,
+    /// The density of the fluid at rest, in kg per cubic meter (square meter in 2D).
+    pub rest_density: Real,
+    /// How strongly the particles push each other apart when the fluid is compressed.
+    pub stiffness: Real,
+    pub viscosity: Real,
+}
+
+impl Default for FluidParams {
+    fn default() -> Self {
+        Self {
+            particle_radius: 0.1,
+            rest_density: 1000.0,
+            stiffness: 200.0,
+            viscosity: 0.05,
+        }
+    }
+}
+
+impl FluidParams {
+    /// The distance up to which the particles interact with each other.
+    pub fn smoothing_radius(&self) -> Real {
+        self.particle_radius * 4.0
+    }
+
+    /// The mass of a particle, filling a cube (a square in 2D) of the fluid at rest.
+    pub fn particle_mass(&self) -> Real {
+        self.rest_density * (self.particle_radius * 2.0).powi(DIM as i32)
+    }
+}
+
+/// A particle of the fluid of a scene.
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FluidParticle {
+    pub position: Point<Real>,
+    pub velocity: Vector<Real>,
+}
@@ -0,0 +1,81 @@
+//! Force fields applied to the bodies of a scene, see [`ForceGenerator`].
+
+use rapier::geometry::Aabb;
+use rapier::math::{Point, Real, Vector};
+
+/// A force field applied by the runners to the dynamic bodies in its range, at each internal
+/// step, in addition to the gravity of the scene.
+///
+/// The fields are given as accelerations, so they move the light and heavy bodies alike.
+/// Sleeping bodies aren’t woken up by them.
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum ForceGenerator {
+    /// Attracts the bodies toward `center`, e.g., a planet. The acceleration is `strength` up
+    /// to `radius` from the center, and decreases with the squared distance beyond it.
+    PointGravity {
+        center: Point<Real>,
+        radius: Real,
+        strength: Real,
+        /// The bodies farther than `range` from the center aren’t attracted.
+        range: Real,
+    },
+    /// Drags the bodies inside `aabb` toward the velocity `velocity`, `drag` being the
+    /// fraction of the velocity difference caught up per second.
+    Wind {
+        aabb: Aabb,
+        velocity: Vector<Real>,
+        drag: Real,
+    },
+    /// Pushes the bodies below the plane through `point` back along its `normal`, with the
+    /// acceleration `strength`, and slows them down with `drag` like a fluid.
+    Buoyancy {
+        point: Point<Real>,
+        normal: Vector<Real>,
+        strength: Real,
+        drag: Real,
+    },
+}
+
+impl ForceGenerator {
+    /// The acceleration of a body at `position` moving at `linvel`.
+    pub fn acceleration(&self, position: &Point<Real>, linvel: &Vector<Real>) -> Vector<Real> {
+        match self {
+            Self::PointGravity {
+                center,
+                radius,
+                strength,
+                range,
+            } => {
+                let dir = center - position;
+                let dist = dir.norm();
+                if dist > *range || dist == 0.0 {
+                    return Vector::zeros();
+                }
+                let falloff = (*radius / dist).min(1.0);
+                dir / dist * *strength * falloff * falloff
+            }
+            Self::Wind {
+                aabb,
+                velocity,
+                drag,
+            } => {
+                if !aabb.contains_local_point(position) {
+                    return Vector::zeros();
+                }
+                (velocity - linvel) * *drag
+            }
+            Self::Buoyancy {
+                point,
+                normal,
+                strength,
+                drag,
+            } => {
+                let normal = normal.normalize();
+                if (position - point).dot(&normal) >= 0.0 {
+                    return Vector::zeros();
+                }
+                normal * *strength - linvel * *drag
+            }
+        }
+    }
+}
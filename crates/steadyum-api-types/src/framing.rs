@@ -0,0 +1,268 @@
+//! Plain-old-data framing of the object sets and watch sets, read back without deserializing
+//! their bodies. Only built with the `pod-framing` feature, since the peers built before it
+//! can't read these payloads.
+
+use crate::fluids::FluidParticle;
+use crate::objects::{ClientBodyObject, WarmBodyObject, WatchedBodyObject, WatchedObjects};
+use crate::serialization::{deserialize_auto, serialize_compressed, Codec};
+use bytemuck::{Pod, Zeroable};
+use rapier::geometry::{Aabb, ColliderShape};
+use rapier::math::{AngVector, Isometry, Real, Vector, ANG_DIM, DIM};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use uuid::Uuid;
+
+/// Header byte of the payloads written by [`serialize_framed`], distinct from the [`Codec`]s.
+const FRAMED_HEADER: u8 = 0x80;
+/// The plain-old-data of a framed payload starts at this offset, after its header and length.
+const FRAME_OFFSET: usize = 16;
+
+/// Serializes `pods` as their raw bytes, followed by `rest` serialized with
+/// [`serialize_compressed`].
+///
+/// [`deserialize_framed`] reads `pods` back without copying them if the payload is aligned
+/// for `T`, and with a single copy otherwise. The bytes are in the native endianness, little
+/// endian on every platform we run on.
+pub fn serialize_framed<T: Pod>(
+    pods: &[T],
+    rest: &impl Serialize,
+    codec: Codec,
+) -> anyhow::Result<Vec<u8>> {
+    let bytes: &[u8] = bytemuck::cast_slice(pods);
+    let rest = serialize_compressed(rest, codec)?;
+    let mut result = Vec::with_capacity(FRAME_OFFSET + bytes.len() + rest.len());
+    result.push(FRAMED_HEADER);
+    result.resize(FRAME_OFFSET - 8, 0);
+    result.extend_from_slice(&(pods.len() as u64).to_le_bytes());
+    result.extend_from_slice(bytes);
+    result.extend_from_slice(&rest);
+    Ok(result)
+}
+
+/// Is `value` a payload of [`serialize_framed`]?
+pub fn is_framed(value: &[u8]) -> bool {
+    value.first() == Some(&FRAMED_HEADER)
+}
+
+/// Deserializes a payload of [`serialize_framed`].
+pub fn deserialize_framed<T: Pod, Rest: DeserializeOwned>(
+    value: &[u8],
+) -> anyhow::Result<(Cow<[T]>, Rest)> {
+    if !is_framed(value) || value.len() < FRAME_OFFSET {
+        anyhow::bail!("Not a framed payload.");
+    }
+
+    let len = u64::from_le_bytes(value[FRAME_OFFSET - 8..FRAME_OFFSET].try_into()?);
+    let end = usize::try_from(len)
+        .ok()
+        .and_then(|len| len.checked_mul(std::mem::size_of::<T>()))
+        .and_then(|size| size.checked_add(FRAME_OFFSET))
+        .filter(|end| *end <= value.len())
+        .ok_or_else(|| anyhow::anyhow!("Truncated framed payload."))?;
+    let bytes = &value[FRAME_OFFSET..end];
+    let pods = match bytemuck::try_cast_slice(bytes) {
+        Ok(pods) => Cow::Borrowed(pods),
+        // Misaligned, e.g., the payload was received in a buffer shared with other messages.
+        Err(_) => Cow::Owned(bytemuck::pod_collect_to_vec(bytes)),
+    };
+
+    Ok((pods, deserialize_auto(&value[end..])?))
+}
+
+/// The pose and velocities of a body, as plain-old-data.
+#[derive(Copy, Clone, Zeroable, Pod)]
+#[repr(C)]
+pub struct PodMotion {
+    pub translation: [Real; DIM],
+    /// The coordinates of the unit complex, in 2D, or quaternion, in 3D.
+    #[cfg(feature = "dim2")]
+    pub rotation: [Real; 2],
+    #[cfg(feature = "dim3")]
+    pub rotation: [Real; 4],
+    pub linvel: [Real; DIM],
+    pub angvel: [Real; ANG_DIM],
+}
+
+impl PodMotion {
+    pub fn new(position: &Isometry<Real>, linvel: &Vector<Real>, angvel: &AngVector<Real>) -> Self {
+        #[cfg(feature = "dim2")]
+        let (rotation, angvel) = {
+            let rotation = position.rotation.complex();
+            ([rotation.re, rotation.im], [*angvel])
+        };
+        #[cfg(feature = "dim3")]
+        let (rotation, angvel) = (position.rotation.coords.into(), (*angvel).into());
+
+        Self {
+            translation: position.translation.vector.into(),
+            rotation,
+            linvel: (*linvel).into(),
+            angvel,
+        }
+    }
+
+    pub fn position(&self) -> Isometry<Real> {
+        #[cfg(feature = "dim2")]
+        let rotation = rapier::na::UnitComplex::new_unchecked(rapier::na::Complex::new(
+            self.rotation[0],
+            self.rotation[1],
+        ));
+        #[cfg(feature = "dim3")]
+        let rotation = rapier::na::UnitQuaternion::new_unchecked(rapier::na::Quaternion::from(
+            rapier::na::Vector4::from(self.rotation),
+        ));
+
+        Isometry::from_parts(Vector::from(self.translation).into(), rotation)
+    }
+
+    pub fn linvel(&self) -> Vector<Real> {
+        self.linvel.into()
+    }
+
+    pub fn angvel(&self) -> AngVector<Real> {
+        #[cfg(feature = "dim2")]
+        return self.angvel[0];
+        #[cfg(feature = "dim3")]
+        return self.angvel.into();
+    }
+}
+
+/// A [`ClientBodyObject`] as plain-old-data, see
+/// [`ObjectSetEncoding::Pod`](crate::serialization::ObjectSetEncoding::Pod).
+#[derive(Copy, Clone, Zeroable, Pod)]
+#[repr(C)]
+pub struct PodClientBodyObject {
+    pub uuid: [u8; 16],
+    pub shape_hash: u64,
+    /// `u64::MAX` if the body isn’t sleeping.
+    pub sleep_start_frame: u64,
+    pub motion: PodMotion,
+    pub epoch: u32,
+}
+
+impl From<&ClientBodyObject> for PodClientBodyObject {
+    fn from(object: &ClientBodyObject) -> Self {
+        Self {
+            uuid: object.uuid.into_bytes(),
+            shape_hash: object.shape_hash,
+            sleep_start_frame: object.sleep_start_frame.unwrap_or(u64::MAX),
+            motion: PodMotion::new(&object.position, &object.linvel, &object.angvel),
+            epoch: object.epoch,
+        }
+    }
+}
+
+impl From<&PodClientBodyObject> for ClientBodyObject {
+    fn from(object: &PodClientBodyObject) -> Self {
+        Self {
+            uuid: Uuid::from_bytes(object.uuid),
+            position: object.motion.position(),
+            linvel: object.motion.linvel(),
+            angvel: object.motion.angvel(),
+            shape_hash: object.shape_hash,
+            sleep_start_frame: (object.sleep_start_frame != u64::MAX)
+                .then_some(object.sleep_start_frame),
+            epoch: object.epoch,
+        }
+    }
+}
+
+/// The data of a [`WatchedBodyObject`] besides its shape, as plain-old-data.
+#[derive(Copy, Clone, Zeroable, Pod)]
+#[repr(C)]
+pub struct PodWatchedBodyObject {
+    pub uuid: [u8; 16],
+    pub timestamp: u64,
+    pub aabb_mins: [Real; DIM],
+    pub aabb_maxs: [Real; DIM],
+    pub motion: PodMotion,
+    pub epoch: u32,
+    /// `u32::MAX` if the body has no collision layer.
+    pub collision_layer: u32,
+    pub padding: u32,
+}
+
+/// The rest of a [`WatchedObjects`] framed by [`serialize_watched_objects`].
+#[derive(Serialize, Deserialize)]
+struct FramedWatchedObjects {
+    step_id: u64,
+    version: u64,
+    unchanged: bool,
+    /// The shape of each framed object.
+    shapes: Vec<ColliderShape>,
+    joint_bodies: Vec<(Uuid, WarmBodyObject)>,
+    #[serde(default)]
+    particles: Vec<FluidParticle>,
+}
+
+/// Serializes a watch set, framing the data of its bodies besides their shapes as
+/// plain-old-data.
+pub fn serialize_watched_objects(watched: &WatchedObjects) -> anyhow::Result<Vec<u8>> {
+    let pods: Vec<_> = watched
+        .objects
+        .iter()
+        .map(|object| PodWatchedBodyObject {
+            uuid: object.uuid.into_bytes(),
+            timestamp: object.warm.timestamp,
+            aabb_mins: object.aabb.mins.coords.into(),
+            aabb_maxs: object.aabb.maxs.coords.into(),
+            motion: PodMotion::new(
+                &object.warm.position,
+                &object.warm.linvel,
+                &object.warm.angvel,
+            ),
+            epoch: object.epoch,
+            collision_layer: object.collision_layer.unwrap_or(u32::MAX),
+            padding: 0,
+        })
+        .collect();
+    let rest = FramedWatchedObjects {
+        step_id: watched.step_id,
+        version: watched.version,
+        unchanged: watched.unchanged,
+        shapes: watched
+            .objects
+            .iter()
+            .map(|object| object.shape.clone())
+            .collect(),
+        joint_bodies: watched.joint_bodies.clone(),
+        particles: watched.particles.clone(),
+    };
+    serialize_framed(&pods, &rest, Codec::Lz4)
+}
+
+/// Deserializes a watch set serialized by [`serialize_watched_objects`].
+pub fn deserialize_watched_objects(value: &[u8]) -> anyhow::Result<WatchedObjects> {
+    let (pods, rest) = deserialize_framed::<PodWatchedBodyObject, FramedWatchedObjects>(value)?;
+    if pods.len() != rest.shapes.len() {
+        anyhow::bail!("Mismatched watched bodies and shapes.");
+    }
+
+    let objects = pods
+        .iter()
+        .zip(rest.shapes)
+        .map(|(pod, shape)| WatchedBodyObject {
+            uuid: Uuid::from_bytes(pod.uuid),
+            aabb: Aabb::new(pod.aabb_mins.into(), pod.aabb_maxs.into()),
+            warm: WarmBodyObject {
+                timestamp: pod.timestamp,
+                position: pod.motion.position(),
+                linvel: pod.motion.linvel(),
+                angvel: pod.motion.angvel(),
+            },
+            shape,
+            collision_layer: (pod.collision_layer != u32::MAX).then_some(pod.collision_layer),
+            epoch: pod.epoch,
+        })
+        .collect();
+
+    Ok(WatchedObjects {
+        step_id: rest.step_id,
+        version: rest.version,
+        unchanged: rest.unchanged,
+        objects,
+        joint_bodies: rest.joint_bodies,
+        particles: rest.particles,
+    })
+}
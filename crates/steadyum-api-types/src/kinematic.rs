@@ -1,49 +1,233 @@
 use rapier::math::{AngVector, Isometry, Real, Rotation, Vector};
-use std::ops::{Add, Mul};
+
+/// A value that can be animated by a [`KinematicCurve`].
+pub trait Keyframeable: Copy {
+    /// The type of the derivative of the animated value.
+    type Velocity;
+
+    fn interpolate(&self, other: &Self, t: Real) -> Self;
+    /// The constant velocity needed to go from `self` to `other` in `dt` seconds.
+    fn velocity(&self, other: &Self, dt: Real) -> Self::Velocity;
+}
+
+impl Keyframeable for Real {
+    type Velocity = Real;
+
+    fn interpolate(&self, other: &Self, t: Real) -> Self {
+        *self * (1.0 - t) + *other * t
+    }
+
+    fn velocity(&self, other: &Self, dt: Real) -> Self::Velocity {
+        (*other - *self) / dt
+    }
+}
+
+impl Keyframeable for Vector<Real> {
+    type Velocity = Vector<Real>;
+
+    fn interpolate(&self, other: &Self, t: Real) -> Self {
+        self.lerp(other, t)
+    }
+
+    fn velocity(&self, other: &Self, dt: Real) -> Self::Velocity {
+        (*other - *self) / dt
+    }
+}
+
+impl Keyframeable for Rotation<Real> {
+    type Velocity = AngVector<Real>;
+
+    fn interpolate(&self, other: &Self, t: Real) -> Self {
+        self.slerp(other, t)
+    }
+
+    fn velocity(&self, other: &Self, dt: Real) -> Self::Velocity {
+        #[cfg(feature = "dim2")]
+        return (other * self.inverse()).angle() / dt;
+        #[cfg(feature = "dim3")]
+        return (other * self.inverse()).scaled_axis() / dt;
+    }
+}
+
+/// How the interpolation parameter progresses between two keyframes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Starts slowly, then accelerates.
+    EaseIn,
+    /// Starts fast, then decelerates.
+    EaseOut,
+    /// Starts and ends slowly.
+    EaseInOut,
+    /// Keeps the value of the first keyframe until the next one is reached.
+    Step,
+}
+
+impl Easing {
+    /// Maps the linear progress `t` in `[0, 1]` to the eased progress.
+    pub fn apply(self, t: Real) -> Real {
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t * t,
+            Self::EaseOut => t * (2.0 - t),
+            Self::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Self::Step => 0.0,
+        }
+    }
+
+    /// The derivative of [`Self::apply`] with respect to `t`.
+    pub fn derivative(self, t: Real) -> Real {
+        match self {
+            Self::Linear => 1.0,
+            Self::EaseIn => 2.0 * t,
+            Self::EaseOut => 2.0 - 2.0 * t,
+            Self::EaseInOut => 6.0 * t * (1.0 - t),
+            Self::Step => 0.0,
+        }
+    }
+}
+
+/// What happens once the last keyframe of a curve is reached.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum LoopMode {
+    /// Stay at the last keyframe.
+    #[default]
+    Once,
+    /// Start again from the first keyframe.
+    Loop,
+    /// Run the curve backward, then forward again, and so on.
+    PingPong,
+}
+
+#[derive(Copy, Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Keyframe<T> {
+    /// Time of this keyframe, relative to the start of the curve.
+    pub time: Real,
+    pub value: T,
+    /// The easing used to go from this keyframe to the next one.
+    pub easing: Easing,
+}
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct KinematicCurve<T> {
-    pub control_points: Vec<T>,
+    /// The keyframes, sorted by increasing time.
+    pub keyframes: Vec<Keyframe<T>>,
     pub t0: Real,
-    pub total_time: Real,
-    pub loop_back: bool,
-}
-
-impl<T> KinematicCurve<T> {
-    pub fn eval(&self, t: Real) -> T
-    where
-        T: Copy + Mul<Real, Output = T> + Add<T, Output = T>,
-    {
-        if t < self.t0 {
-            self.control_points[0]
-        } else if t > self.total_time && !self.loop_back {
-            *self.control_points.last().unwrap()
-        } else {
-            let t = t - self.t0;
-            let loop_id = (t / self.total_time).floor() as i32;
-            let rel_t = if loop_id % 2 == 1 {
-                (1.0 - (t / self.total_time).fract()) * self.total_time
-            } else {
-                (t / self.total_time).fract() * self.total_time
-            };
+    pub mode: LoopMode,
+}
+
+impl<T: Keyframeable> KinematicCurve<T> {
+    /// A curve going linearly through evenly spaced `control_points` in `total_time` seconds.
+    pub fn uniform(control_points: Vec<T>, t0: Real, total_time: Real, mode: LoopMode) -> Self {
+        let time_slices = total_time / (control_points.len().max(2) - 1) as Real;
+        let keyframes = control_points
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| Keyframe {
+                time: i as Real * time_slices,
+                value,
+                easing: Easing::Linear,
+            })
+            .collect();
+
+        Self {
+            keyframes,
+            t0,
+            mode,
+        }
+    }
+
+    /// Appends the keyframes of `next` so they start right after the last keyframe of `self`.
+    pub fn then(mut self, next: Self) -> Self {
+        let offset = self.duration();
+        self.keyframes
+            .extend(next.keyframes.into_iter().map(|key| Keyframe {
+                time: key.time + offset,
+                ..key
+            }));
+        self
+    }
+
+    /// Time between the first and the last keyframes.
+    pub fn duration(&self) -> Real {
+        self.keyframes.last().map(|key| key.time).unwrap_or(0.0)
+    }
+
+    pub fn eval(&self, t: Real) -> T {
+        let (segment, s, _) = self.locate(t);
+        let key = &self.keyframes[segment];
+        match self.keyframes.get(segment + 1) {
+            Some(next) => key.value.interpolate(&next.value, key.easing.apply(s)),
+            None => key.value,
+        }
+    }
+
+    /// The derivative of [`Self::eval`] with respect to `t`.
+    pub fn eval_derivative(&self, t: Real) -> T::Velocity {
+        let (segment, s, sign) = self.locate(t);
+        let key = &self.keyframes[segment];
+        match self.keyframes.get(segment + 1) {
+            Some(next) if sign != 0.0 => {
+                let dt = (next.time - key.time) / (key.easing.derivative(s) * sign);
+                key.value.velocity(&next.value, dt)
+            }
+            _ => key.value.velocity(&key.value, 1.0),
+        }
+    }
 
-            let time_slices = self.total_time / (self.control_points.len() - 1) as Real;
-            let curr_time_slice = (rel_t / time_slices).floor() as usize;
-            let rel_slice_t = (rel_t / time_slices).fract();
+    /// Finds the segment containing the time `t`.
+    ///
+    /// Returns the index of the segment’s first keyframe, the relative position in
+    /// that segment, and the direction the curve is being run (`0.0` if it is not moving).
+    fn locate(&self, t: Real) -> (usize, Real, Real) {
+        let duration = self.duration();
+        let t = t - self.t0;
 
-            self.control_points[curr_time_slice] * (1.0 - rel_slice_t)
-                + self.control_points[curr_time_slice + 1] * rel_slice_t
+        if t <= 0.0 || duration <= 0.0 || self.keyframes.len() < 2 {
+            return (0, 0.0, 0.0);
         }
+
+        let loop_id = (t / duration).floor() as u64;
+        let (rel_t, sign) = match self.mode {
+            LoopMode::Once if t >= duration => return (self.keyframes.len() - 1, 0.0, 0.0),
+            LoopMode::PingPong if loop_id % 2 == 1 => {
+                ((1.0 - (t / duration).fract()) * duration, -1.0)
+            }
+            _ => ((t / duration).fract() * duration, 1.0),
+        };
+
+        let segment = self
+            .keyframes
+            .windows(2)
+            .position(|keys| rel_t < keys[1].time)
+            .unwrap_or(self.keyframes.len() - 2);
+        let start = self.keyframes[segment].time;
+        let end = self.keyframes[segment + 1].time;
+        let s = if end > start {
+            ((rel_t - start) / (end - start)).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+
+        (segment, s, sign)
     }
 }
 
 #[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
 pub struct KinematicAnimations {
     pub linear: Option<KinematicCurve<Vector<Real>>>,
+    /// Rotation given as a rotation vector. Ignored if `rotation` is set.
     pub angular: Option<KinematicCurve<AngVector<Real>>>,
+    /// Rotation keyframes, interpolated with slerp.
+    pub rotation: Option<KinematicCurve<Rotation<Real>>>,
 }
 
 impl KinematicAnimations {
+    pub fn is_empty(&self) -> bool {
+        self.linear.is_none() && self.angular.is_none() && self.rotation.is_none()
+    }
+
     pub fn eval(&self, t: Real, base: Isometry<Real>) -> Isometry<Real> {
         let mut result = base;
 
@@ -51,10 +235,35 @@ impl KinematicAnimations {
             result.translation.vector = linear.eval(t);
         }
 
-        if let Some(angular) = &self.angular {
+        if let Some(rotation) = &self.rotation {
+            result.rotation = rotation.eval(t);
+        } else if let Some(angular) = &self.angular {
             result.rotation = Rotation::new(angular.eval(t));
         }
 
         result
     }
+
+    /// The linear and angular velocities of the animation at time `t`.
+    ///
+    /// This is what drives velocity-based kinematic bodies.
+    pub fn eval_velocities(&self, t: Real) -> (Vector<Real>, AngVector<Real>) {
+        let linvel = self
+            .linear
+            .as_ref()
+            .map(|linear| linear.eval_derivative(t))
+            .unwrap_or_else(nalgebra::zero);
+        let angvel = if let Some(rotation) = &self.rotation {
+            rotation.eval_derivative(t)
+        } else {
+            // NOTE: this assumes the rotation axis doesn’t change much between two
+            //       control points.
+            self.angular
+                .as_ref()
+                .map(|angular| angular.eval_derivative(t))
+                .unwrap_or_else(nalgebra::zero)
+        };
+
+        (linvel, angvel)
+    }
 }
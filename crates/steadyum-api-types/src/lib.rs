@@ -3,11 +3,25 @@ pub extern crate rapier2d as rapier;
 #[cfg(feature = "dim3")]
 pub extern crate rapier3d as rapier;
 
+pub mod admission;
+pub mod chaos;
+pub mod characters;
+pub mod collision;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod delta;
+pub mod fluids;
+pub mod forces;
 pub mod kinematic;
 pub mod messages;
 pub mod objects;
+pub mod projectiles;
 pub mod queries;
 pub mod simulation;
+pub mod store;
+pub mod telemetry;
+pub mod templates;
+pub mod trajectory;
+pub mod vehicles;
 
 pub mod region_db;
 #[cfg(feature = "zenoh")]
@@ -15,5 +29,8 @@ pub mod zenoh;
 
 pub(crate) mod array_ser;
 pub mod env;
+#[cfg(feature = "pod-framing")]
+pub mod framing;
 pub mod partitionner;
 pub mod serialization;
+pub mod signing;
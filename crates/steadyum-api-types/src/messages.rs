@@ -1,13 +1,150 @@
+use crate::admission::{RejectedBody, RejectionReason};
+use crate::fluids::FluidParticle;
+use crate::forces::ForceGenerator;
 use crate::objects::{ColdBodyObject, WarmBodyObject};
-use crate::partitionner::SceneUuid;
+use crate::partitionner::{FrozenVolume, SceneSettings, SceneUuid, SimulationParams, TimeScale};
+use crate::serialization::{Codec, ObjectSetEncoding};
 use crate::simulation::SimulationBounds;
+use crate::vehicles::{VehicleControls, VehicleParams};
 use rapier::dynamics::GenericJoint;
 use rapier::geometry::Aabb;
-use rapier::math::{Isometry, Real};
+use rapier::math::{Isometry, Point, Real, Vector};
+use std::time::Duration;
 use uuid::Uuid;
 
 pub const PARTITIONNER_QUEUE: &str = "partitionner";
 
+/// Interests not refreshed for this long are considered gone.
+pub const CLIENT_INTEREST_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often viewers refresh their interest.
+pub const CLIENT_INTEREST_REFRESH_PERIOD: Duration = Duration::from_secs(1);
+
+/// The part of a scene a viewer is looking at.
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, Debug)]
+pub struct ClientInterest {
+    pub view: Aabb,
+    /// If set, the regions far from the center of `view` report their objects less often.
+    #[serde(default)]
+    pub lod: Option<ClientLod>,
+}
+
+impl ClientInterest {
+    /// Every how many auxiliary steps `region` must report its objects to this client.
+    pub fn report_period(&self, region: &SimulationBounds) -> u64 {
+        match &self.lod {
+            Some(lod) if lod.is_far(&self.view.center(), region) => lod.far_period.max(1),
+            _ => 1,
+        }
+    }
+}
+
+/// The level of detail of the objects reported to a viewer, to save bandwidth on large scenes.
+///
+/// The viewer interpolates between the sparser positions of the far regions.
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct ClientLod {
+    /// The regions closer than this to the camera report at every auxiliary step.
+    pub near_distance: Real,
+    /// The other regions only report every `far_period` auxiliary steps.
+    pub far_period: u64,
+}
+
+impl Default for ClientLod {
+    fn default() -> Self {
+        Self {
+            near_distance: SimulationBounds::DEFAULT_WIDTH as Real * 3.0,
+            far_period: 4,
+        }
+    }
+}
+
+impl ClientLod {
+    /// Is `region` further than `near_distance` from `camera`?
+    pub fn is_far(&self, camera: &Point<Real>, region: &SimulationBounds) -> bool {
+        let aabb = region.aabb();
+        let closest = camera.coords.sup(&aabb.mins.coords).inf(&aabb.maxs.coords);
+        (closest - camera.coords).norm() > self.near_distance
+    }
+}
+
+/// Bodies that just started being simulated by `region`, published by its runner.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct BodyOwnershipUpdate {
+    pub scene: SceneUuid,
+    pub region: SimulationBounds,
+    pub bodies: Vec<Uuid>,
+    /// The bodies assigned to `region` and refused by its runner, see
+    /// [`crate::admission::AdmissionPolicy`].
+    #[serde(default)]
+    pub rejected: Vec<RejectedBody>,
+}
+
+/// Bodies spawned by a client, published on the spawn stream of the region containing them.
+///
+/// The runner simulating `region` inserts the dynamic bodies at its next step. The partitionner
+/// inserts the static bodies, and the dynamic ones if no runner simulates `region` yet.
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct SpawnBodies {
+    pub scene: SceneUuid,
+    pub region: SimulationBounds,
+    pub bodies: Vec<BodyAssignment>,
+}
+
+/// Bodies removed by a client, published on the despawn stream of their scene.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct DespawnBodies {
+    pub scene: SceneUuid,
+    pub bodies: Vec<Uuid>,
+}
+
+/// Something that happened to the bodies of a region during a step.
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SimulationEvent {
+    /// `body` started (or stopped) intersecting the sensor body `sensor`.
+    Intersection {
+        sensor: Uuid,
+        body: Uuid,
+        started: bool,
+    },
+    /// The projectile `projectile` hit `body`, or a static body if `None`. See
+    /// [`crate::projectiles::ProjectileParams`].
+    ProjectileHit {
+        projectile: Uuid,
+        body: Option<Uuid>,
+    },
+    /// The body `body` was refused by the region, see [`crate::admission::AdmissionPolicy`].
+    BodyRejected { body: Uuid, reason: RejectionReason },
+    /// The body `body` left the [`crate::partitionner::SimulationDomain`] of the scene, and the
+    /// policy of the domain was applied to it.
+    LeftDomain { body: Uuid },
+}
+
+/// An action of a client on a single body, e.g., dragged by the mouse in the viewer.
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, Debug)]
+pub enum BodyInput {
+    /// Applies a force at the center of mass of the body during the next step.
+    Force(Vector<Real>),
+    /// Moves the body to a new position, and stops it.
+    Teleport(Isometry<Real>),
+    /// Locks, or unlocks, all the degrees of freedom of the body. A frozen body stays in place
+    /// but the others still collide with it.
+    Freeze(bool),
+    /// Sets the velocity at which a character walks, until the next `Move`. Ignored by the
+    /// bodies that aren’t characters, see [`crate::characters::CharacterParams`].
+    Move(Vector<Real>),
+    /// Sets the controls of the vehicle with this chassis, until the next `Drive`. Ignored by
+    /// the bodies that aren’t the chassis of a vehicle.
+    Drive(VehicleControls),
+}
+
+/// The events of a region for one step, published by its runner when there are any.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct RegionEvents {
+    pub region: SimulationBounds,
+    pub step_id: u64,
+    pub events: Vec<SimulationEvent>,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct ObjectAssignment {
     pub uuid: Uuid,
@@ -16,6 +153,15 @@ pub struct ObjectAssignment {
     pub dynamic: bool,
 }
 
+/// A vehicle assigned to a region as one unit: its chassis, its wheels and the last controls
+/// of its driver.
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct VehicleAssignment {
+    pub chassis: BodyAssignment,
+    pub params: VehicleParams,
+    pub controls: VehicleControls,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Copy, Clone, Debug)]
 pub struct ImpulseJointAssignment {
     pub body1: Uuid,
@@ -28,6 +174,11 @@ pub struct BodyAssignment {
     pub uuid: Uuid,
     pub warm: WarmBodyObject,
     pub cold: ColdBodyObject, // TODO: don’t send the cold object?
+    /// The ownership epoch of the body, incremented every time it migrates to another region.
+    /// A copy of the body with an older epoch is stale, e.g., left behind by a region that
+    /// crashed before removing it.
+    #[serde(default)]
+    pub epoch: u32,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
@@ -83,7 +234,15 @@ pub enum PartitionnerMessage {
 
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
 pub enum RunnerMessage {
+    /// Binds an idle runner from the warm pool to `scene`.
+    Adopt {
+        scene: SceneUuid,
+        client_objects_encoding: ObjectSetEncoding,
+        #[serde(default)]
+        settings: SceneSettings,
+    },
     AssignStaticBodies {
+        scene: SceneUuid,
         bodies: Vec<BodyAssignment>,
     },
     AssignIsland {
@@ -92,10 +251,122 @@ pub enum RunnerMessage {
         bodies: Vec<BodyAssignment>,
         impulse_joints: Vec<ImpulseJointAssignment>,
     },
+    /// Adds fluid particles to `region`, inserted by a client or handed off by a neighbor.
+    AssignParticles {
+        scene: SceneUuid,
+        region: SimulationBounds,
+        particles: Vec<FluidParticle>,
+    },
+    /// Adds vehicles to `region`, inserted by a client or handed off by a neighbor.
+    AssignVehicles {
+        scene: SceneUuid,
+        region: SimulationBounds,
+        vehicles: Vec<VehicleAssignment>,
+    },
     Step {
+        scene: SceneUuid,
+        step_id: u64,
+    },
+    SyncClientObjects {
+        scene: SceneUuid,
+    },
+    /// Asks every region of `scene` to publish its bodies and joints under the key given
+    /// by `zenoh::scene_export_key`.
+    Export {
+        scene: SceneUuid,
+        request: Uuid,
+    },
+    /// Replaces the WASM module run by every region of `scene` at each step. An empty module
+    /// removes the script.
+    SetScript {
+        scene: SceneUuid,
+        module: Vec<u8>,
+    },
+    /// Changes the time scale of `scene`, streamed to the viewers with its regions.
+    SetTimeScale {
+        scene: SceneUuid,
+        time_scale: TimeScale,
+    },
+    /// Changes the solver options of `scene`, from the next step of its regions.
+    SetSimulationParams {
+        scene: SceneUuid,
+        params: SimulationParams,
+    },
+    /// Replaces the frozen volumes of `scene`, see [`crate::partitionner::FreezeVolumeRequest`].
+    SetFrozenVolumes {
+        scene: SceneUuid,
+        volumes: Vec<FrozenVolume>,
+    },
+    /// Replaces the force generators of `scene`, see
+    /// [`crate::partitionner::SetForceGeneratorsRequest`].
+    SetForceGenerators {
+        scene: SceneUuid,
+        generators: Vec<ForceGenerator>,
+    },
+    /// Replaces the regions of `scene` far from every viewer, stepped with the
+    /// [`crate::partitionner::RegionLod::far_params`] of the scene.
+    SetFarRegions {
+        scene: SceneUuid,
+        regions: Vec<SimulationBounds>,
+    },
+    /// Starts, or stops if `traced` is `false`, publishing the trajectories of `bodies` under
+    /// the key given by `zenoh::trajectory_key`.
+    TraceBodies {
+        scene: SceneUuid,
+        bodies: Vec<Uuid>,
+        traced: bool,
+    },
+    /// Applies `input` to `body`. Sent to every region of `scene`, only the one simulating
+    /// the body applies it.
+    BodyInput {
+        scene: SceneUuid,
+        body: Uuid,
+        input: BodyInput,
+    },
+    /// Sent by a region thread once it is done with the step `step_id`.
+    Ack {
+        scene: SceneUuid,
+        region: SimulationBounds,
         step_id: u64,
     },
-    SyncClientObjects,
-    Ack,
-    Exit,
+    /// Stops simulating `region`, and publishes its bodies and joints under the key given by
+    /// `zenoh::scene_export_key`, for the master to hand them to the runner `target`. The
+    /// islands sent to the region afterwards are forwarded to `target`.
+    HandOver {
+        scene: SceneUuid,
+        region: SimulationBounds,
+        request: Uuid,
+        target: Uuid,
+    },
+    /// Starts simulating `region`, handed over by another runner, even if this runner handed it
+    /// over before.
+    TakeOver {
+        scene: SceneUuid,
+        region: SimulationBounds,
+        bodies: Vec<BodyAssignment>,
+        impulse_joints: Vec<ImpulseJointAssignment>,
+    },
+    /// Removes all the regions of `scene` from the runner.
+    Exit {
+        scene: SceneUuid,
+    },
+    /// Does nothing: acknowledged like any other command, it tells whether the runner is alive.
+    Ping,
+    /// Stops the runner process entirely.
+    Shutdown,
+}
+
+impl RunnerMessage {
+    /// The codec used to compress this message before sending it to another process.
+    pub fn codec(&self) -> Codec {
+        match self {
+            // Islands can be large and may be handed off to a runner on another node.
+            Self::AssignIsland { .. }
+            | Self::AssignParticles { .. }
+            | Self::TakeOver { .. }
+            | Self::AssignStaticBodies { .. }
+            | Self::SetScript { .. } => Codec::Zstd,
+            _ => Codec::Lz4,
+        }
+    }
 }
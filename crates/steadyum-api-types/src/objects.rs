@@ -1,25 +1,97 @@
+use crate::characters::CharacterParams;
+use crate::fluids::FluidParticle;
 use crate::kinematic::KinematicAnimations;
-use crate::partitionner::SceneUuid;
+use crate::partitionner::{BodyCounts, PhysicsStats, SceneUuid, TimeScale};
+use crate::projectiles::ProjectileParams;
+use crate::serialization::EncodedClientBodyObjectSet;
 use crate::simulation::SimulationBounds;
 use rapier::math::{AngVector, Isometry, Real, Vector};
 use rapier::prelude::{Aabb, Collider, ColliderShape, RigidBody, RigidBodyType};
+use std::sync::Arc;
 use uuid::Uuid;
 
-#[derive(Clone, serde::Serialize, serde::Deserialize)]
+/// The per-step data of a body, as seen by the clients.
+///
+/// The shape and other cold data are only sent once through [`ClientColdBodyObject`].
+#[derive(Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ClientBodyObject {
     pub uuid: Uuid,
     pub position: Isometry<Real>,
-    // TODO: a bit sad to always re-send the shape.
-    //       Needs to be benchmarked to determine if it’s to slow (probably will be whenever we don’t use a complex shape).
+    /// Velocities used by clients to extrapolate the position while waiting for the next one.
+    pub linvel: Vector<Real>,
+    pub angvel: AngVector<Real>,
+    pub shape_hash: u64,
+    pub sleep_start_frame: Option<u64>,
+    /// See [`crate::messages::BodyAssignment::epoch`]. If two regions report the same body,
+    /// the one with the latest epoch owns it.
+    #[serde(default)]
+    pub epoch: u32,
+}
+
+/// The data of a body that (almost) never change, as seen by the clients.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct ClientColdBodyObject {
+    pub uuid: Uuid,
+    pub shape_hash: u64,
+    /// The timestamp of the first step where the body had this cold data in its region.
+    pub timestamp: u64,
     pub shape: ColliderShape,
     pub body_type: RigidBodyType,
-    pub sleep_start_frame: Option<u64>,
+    /// See [`ColdBodyObject::user_data`].
+    #[serde(default)]
+    pub user_data: Vec<u8>,
+    /// See [`ColdBodyObject::is_sensor`].
+    #[serde(default)]
+    pub is_sensor: bool,
+}
+
+impl ClientColdBodyObject {
+    /// Hash identifying the cold data of a body, so clients know when to refresh their cache.
+    pub fn compute_shape_hash(shape: &ColliderShape, body_type: RigidBodyType) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        bincode::serialize(shape)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        (body_type as u32).hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// A body that just left a region.
+///
+/// The region keeps reporting it for a few steps so clients don’t lose track of the body
+/// until its new owner reports it.
+#[derive(Copy, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ClientGhostBodyObject {
+    pub object: ClientBodyObject,
+    /// The region now owning the body.
+    pub new_region: SimulationBounds,
 }
 
 #[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct ClientBodyObjectSet {
     pub timestamp: u64,
     pub objects: Vec<ClientBodyObject>,
+    /// The cold data of the bodies that the client might not know about yet.
+    pub cold_objects: Vec<ClientColdBodyObject>,
+    /// The bodies recently handed off to another region.
+    pub ghosts: Vec<ClientGhostBodyObject>,
+    /// If `true`, only the bodies that moved significantly since the previous set are listed.
+    /// The other bodies of the region are still there, at the position last reported.
+    #[serde(default)]
+    pub partial: bool,
+}
+
+/// The client objects of a single region, as streamed by the runners after each step.
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct RegionClientBodyObjectSet {
+    pub region: SimulationBounds,
+    pub set: EncodedClientBodyObjectSet,
+    /// The time scale of the scene when the region was stepped, for the viewers to play it back.
+    pub time_scale: TimeScale,
+    /// The energy and momentum of the bodies simulated by the region.
+    pub stats: PhysicsStats,
 }
 
 #[derive(Copy, Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
@@ -47,6 +119,24 @@ pub struct ColdBodyObject {
     pub density: Real,
     pub shape: ColliderShape,
     pub animations: KinematicAnimations,
+    /// Opaque data attached by the creator of the body, e.g., to identify the game entity it
+    /// belongs to. It follows the body from region to region, up to the clients.
+    #[serde(default)]
+    pub user_data: Vec<u8>,
+    /// Sensors don’t generate contact forces, they only report the bodies intersecting them
+    /// through [`crate::messages::RegionEvents`].
+    #[serde(default)]
+    pub is_sensor: bool,
+    /// The index of the layer of the body in the [`crate::collision::CollisionMatrix`] of its
+    /// scene. Bodies without layer interact with every body.
+    #[serde(default)]
+    pub collision_layer: Option<u32>,
+    /// If set, the body is a kinematic character moved by the clients.
+    #[serde(default)]
+    pub character: Option<CharacterParams>,
+    /// If set, the body is a projectile removed by the runners.
+    #[serde(default)]
+    pub projectile: Option<ProjectileParams>,
 }
 
 impl ColdBodyObject {
@@ -56,21 +146,170 @@ impl ColdBodyObject {
             density: collider.density(),
             shape: collider.shared_shape().clone(),
             animations: KinematicAnimations::default(),
+            user_data: vec![],
+            is_sensor: collider.is_sensor(),
+            collision_layer: None,
+            character: None,
+            projectile: None,
         }
     }
+
+    /// Is the body simulated by a single region, and handed off from region to region? The
+    /// other bodies, static or animated, are known by every region they touch.
+    pub fn moves_between_regions(&self) -> bool {
+        self.body_type.is_dynamic() || self.character.is_some()
+    }
+}
+
+/// A body near the border of its region, as seen by the neighbor regions.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct WatchedBodyObject {
+    pub uuid: Uuid,
+    /// The AABB swept by the body until the next watch set is published.
+    pub aabb: Aabb,
+    pub warm: WarmBodyObject,
+    pub shape: ColliderShape,
+    /// See [`ColdBodyObject::collision_layer`].
+    #[serde(default)]
+    pub collision_layer: Option<u32>,
+    /// See [`crate::messages::BodyAssignment::epoch`].
+    #[serde(default)]
+    pub epoch: u32,
 }
 
 #[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct WatchedObjects {
-    pub objects: Vec<(Uuid, Aabb)>,
+    /// The step after which this watch set was computed.
+    pub step_id: u64,
+    /// Incremented each time the watched bodies move, appear or disappear.
+    #[serde(default)]
+    pub version: u64,
+    /// If `true`, the bodies aren’t sent: they didn’t change since the watch set with the
+    /// same `version`. See [`Self::unchanged`].
+    #[serde(default)]
+    pub unchanged: bool,
+    pub objects: Vec<WatchedBodyObject>,
+    /// Bodies attached by a joint to a body simulated by another region.
+    pub joint_bodies: Vec<(Uuid, WarmBodyObject)>,
+    /// The fluid particles close enough to the borders to interact with the particles of the
+    /// neighbors.
+    #[serde(default)]
+    pub particles: Vec<FluidParticle>,
+}
+
+impl WatchedObjects {
+    /// Are the bodies of `self` the same as those of `other`, at the same positions and with
+    /// the same velocities? The timestamps are ignored.
+    pub fn same_bodies(&self, other: &Self) -> bool {
+        fn same_warm(a: &WarmBodyObject, b: &WarmBodyObject) -> bool {
+            a.position == b.position && a.linvel == b.linvel && a.angvel == b.angvel
+        }
+
+        self.objects.len() == other.objects.len()
+            && self.joint_bodies.len() == other.joint_bodies.len()
+            && self.objects.iter().zip(&other.objects).all(|(a, b)| {
+                a.uuid == b.uuid
+                    && a.aabb == b.aabb
+                    && same_warm(&a.warm, &b.warm)
+                    && Arc::ptr_eq(&a.shape.0, &b.shape.0)
+                    && a.collision_layer == b.collision_layer
+                    && a.epoch == b.epoch
+            })
+            && self
+                .joint_bodies
+                .iter()
+                .zip(&other.joint_bodies)
+                .all(|(a, b)| a.0 == b.0 && same_warm(&a.1, &b.1))
+            && self.particles == other.particles
+    }
+
+    /// The watch set sent instead of `self` to the neighbors that already received the
+    /// bodies of its version.
+    pub fn unchanged(&self) -> Self {
+        Self {
+            step_id: self.step_id,
+            version: self.version,
+            unchanged: true,
+            objects: vec![],
+            joint_bodies: vec![],
+            particles: vec![],
+        }
+    }
 }
 
 #[derive(Clone, serde::Serialize, serde::Deserialize, Default, Debug)]
 pub struct RegionList {
     pub bounds: Vec<SimulationBounds>,
+    /// The occupancy of the regions of `bounds` that answered their last profile query, in no
+    /// particular order.
+    #[serde(default)]
+    pub activity: Vec<RegionActivity>,
+}
+
+impl RegionList {
+    /// The occupancy of `region`, if it is known.
+    pub fn activity(&self, region: &SimulationBounds) -> Option<&RegionActivity> {
+        self.activity
+            .iter()
+            .find(|activity| activity.region == *region)
+    }
+}
+
+/// The bodies of a region and how recently they moved, so the clients can skip the regions
+/// that are empty, or didn’t change since they last fetched them.
+#[derive(Copy, Clone, serde::Serialize, serde::Deserialize, Debug)]
+pub struct RegionActivity {
+    pub region: SimulationBounds,
+    /// The last step of the region.
+    pub step_id: u64,
+    /// The bodies of the region at the end of `step_id`, the static bodies excluded.
+    pub bodies: BodyCounts,
+    /// The last step where a body of the region moved, or was added or removed.
+    pub last_active_step: u64,
+}
+
+impl RegionActivity {
+    /// Did the bodies of the region stay the same, and not move, since the step `step_id`?
+    pub fn unchanged_since(&self, step_id: u64) -> bool {
+        self.last_active_step <= step_id
+    }
 }
 
 #[derive(Clone, serde::Serialize, serde::Deserialize, Default, Debug)]
 pub struct SceneList {
-    pub scenes: Vec<SceneUuid>,
+    pub scenes: Vec<SceneInfo>,
+}
+
+/// A scene listed by the partitionner.
+#[derive(Clone, serde::Serialize, serde::Deserialize, Default, Debug)]
+pub struct SceneInfo {
+    pub uuid: SceneUuid,
+    /// See [`SceneSettings::name`](crate::partitionner::SceneSettings::name).
+    pub name: String,
+    /// The bodies currently in the scene, static ones included.
+    pub num_bodies: usize,
+    /// When the scene was created, in seconds since the Unix epoch.
+    pub created_at: u64,
+    pub running: bool,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize, Default, Debug)]
+pub struct SceneSaveList {
+    pub saves: Vec<SceneSaveInfo>,
+}
+
+/// A named save of a scene, see [`SceneSave`](crate::partitionner::SceneSave).
+#[derive(Clone, serde::Serialize, serde::Deserialize, Default, Debug)]
+pub struct SceneSaveInfo {
+    pub name: String,
+    /// The scene the save was taken from.
+    pub scene: SceneUuid,
+    /// The bodies of the save, static ones included.
+    pub num_bodies: usize,
+    /// When the save was taken, in seconds since the Unix epoch.
+    pub saved_at: u64,
+    /// The tenant the save belongs to, see `TENANTS_FILE`. Without tenants, everyone sees
+    /// every save.
+    #[serde(default)]
+    pub tenant: Option<String>,
 }
@@ -1,14 +1,29 @@
-use crate::messages::BodyAssignment;
+use crate::admission::AdmissionPolicy;
+use crate::collision::CollisionMatrix;
+use crate::fluids::{FluidParams, FluidParticle};
+use crate::forces::ForceGenerator;
+use crate::messages::{
+    BodyAssignment, BodyInput, ClientLod, ImpulseJointAssignment, VehicleAssignment,
+};
+use crate::objects::SceneSaveInfo;
 use crate::region_db::AsyncPartitionnerServer;
-use crate::simulation::SimulationBounds;
+use crate::serialization::ObjectSetEncoding;
+use crate::simulation::{MigrationHysteresis, SimulationBounds, WatchMargins, WatchMode};
+use crate::templates::SceneTemplate;
+use rapier::dynamics::IntegrationParameters;
 use rapier::geometry::Aabb;
+use rapier::math::{Isometry, Real, Vector};
+use rapier::parry::bounding_volume::BoundingVolume;
+use rapier::prelude::{RigidBody, RigidBodyType};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use uuid::Uuid;
 
 pub const NUM_INTERNAL_STEPS: u64 = 10;
 
 pub const SHUTDOWN: &str = "/shutdown";
 pub const GET_EXES: &str = "/getbins";
+pub const GET_EXES_DELTA_ENDPOINT: &str = "/getbins_delta";
 pub const HEARTBEAT: &str = "/heartbeat";
 pub const RUNNER_INITIALIZED_ENDPOINT: &str = "/initialized";
 pub const ASSIGN_RUNNER_ENDPOINT: &str = "/region";
@@ -22,10 +37,66 @@ pub const REGISTER_CHILD_ENDPOINT: &str = "/register_child";
 pub const ACK_ENDPOINT: &str = "/ack";
 pub const STEP_ENDPOINT: &str = "/step";
 pub const CLIENT_INPUT_ENDPOINT: &str = "/input";
+pub const CLIENT_INPUTS_ENDPOINT: &str = "/inputs";
+pub const STREAM_CLIENT_OBJECTS_ENDPOINT: &str = "/stream_client_objects";
+pub const SPAWN_TEMPLATES_ENDPOINT: &str = "/spawn_templates";
+pub const BEGIN_INSERT_ENDPOINT: &str = "/begin_insert";
+pub const INSERT_CHUNK_ENDPOINT: &str = "/insert_chunk";
+pub const INSERT_PROGRESS_ENDPOINT: &str = "/insert_progress";
+pub const EXPORT_SCENE_ENDPOINT: &str = "/export_scene";
+pub const REPLICATE_ENDPOINT: &str = "/replicate";
+pub const BODY_OWNER_ENDPOINT: &str = "/body_owner";
+pub const SET_SCRIPT_ENDPOINT: &str = "/set_script";
+pub const BODY_INPUT_ENDPOINT: &str = "/body_input";
+pub const REMOVE_BODIES_ENDPOINT: &str = "/remove_bodies";
+pub const PROFILE_SCENE_ENDPOINT: &str = "/profile_scene";
+pub const SET_TIME_SCALE_ENDPOINT: &str = "/set_time_scale";
+pub const STEP_N_ENDPOINT: &str = "/step_n";
+pub const TRACE_BODIES_ENDPOINT: &str = "/trace_bodies";
+pub const TRAJECTORIES_ENDPOINT: &str = "/trajectories";
+pub const SCENE_STATS_ENDPOINT: &str = "/scene_stats";
+pub const SET_SIMULATION_PARAMS_ENDPOINT: &str = "/set_simulation_params";
+pub const SCENE_STATUS_ENDPOINT: &str = "/scene_status";
+pub const FULL_SYNC_ENDPOINT: &str = "/full_sync";
+pub const SAVE_SCENE_ENDPOINT: &str = "/save_scene";
+pub const LOAD_SAVE_ENDPOINT: &str = "/load_save";
+pub const LIST_SAVES_ENDPOINT: &str = "/list_saves";
+pub const UPDATE_CHECK_ENDPOINT: &str = "/update_check";
+pub const TRANSFER_REGIONS_ENDPOINT: &str = "/transfer_regions";
+pub const DRAIN_NODE_ENDPOINT: &str = "/drain_node";
+pub const FREEZE_VOLUME_ENDPOINT: &str = "/freeze_volume";
+pub const SET_FORCE_GENERATORS_ENDPOINT: &str = "/set_force_generators";
+pub const INSERT_PARTICLES_ENDPOINT: &str = "/insert_particles";
+pub const INSERT_VEHICLES_ENDPOINT: &str = "/insert_vehicles";
+
+/// Maximum number of chunks of an insertion session waiting to be processed by the
+/// partitionner before it stops accepting new ones.
+pub const MAX_PENDING_INSERT_CHUNKS: usize = 4;
+
+/// Content type of request bodies serialized with [`crate::serialization::serialize`]
+/// instead of JSON.
+pub const BINCODE_CONTENT_TYPE: &str = "application/x-bincode";
+
+/// Header carrying the API key identifying the tenant of a request, see `TENANTS_FILE`.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Header carrying the `CLUSTER_KEY` shared by the partitionners and runners of a cluster.
+pub const CLUSTER_KEY_HEADER: &str = "x-cluster-key";
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct SceneUuid(pub Uuid);
 
+impl SceneUuid {
+    /// The deterministic id of the body identified by `key` in this scene, a UUID v5 in the
+    /// namespace of the scene.
+    ///
+    /// Running a procedural generator again gives its bodies the same ids: the partitionner
+    /// skips those already in the scene, and the clients can reference them by stable ids.
+    pub fn body_uuid(&self, key: &str) -> Uuid {
+        Uuid::new_v5(&self.0, key.as_bytes())
+    }
+}
+
 impl Default for SceneUuid {
     fn default() -> Self {
         SceneUuid(Uuid::new_v4())
@@ -34,7 +105,6 @@ impl Default for SceneUuid {
 
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct RunnerInitializedRequest {
-    pub scene: SceneUuid,
     pub uuid: Uuid,
 }
 
@@ -44,6 +114,19 @@ pub struct AssignRunnerRequest {
     pub region: SimulationBounds,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BodyOwnerRequest {
+    pub body: Uuid,
+}
+
+/// Where a dynamic body is currently simulated.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BodyOwner {
+    pub scene: SceneUuid,
+    pub region: SimulationBounds,
+    pub runner: Uuid,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct AssignRunnerResponse {
     pub scene: SceneUuid,
@@ -54,6 +137,10 @@ pub struct AssignRunnerResponse {
 #[derive(Clone, Serialize, Deserialize)]
 pub struct AckRequest {
     pub scene: SceneUuid,
+    /// The step being acknowledged.
+    pub step_id: u64,
+    /// The runner or partitionner sending the ack.
+    pub origin: Uuid,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -65,16 +152,424 @@ pub struct StepRequest {
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ClientInputRequest {
     pub scene: SceneUuid,
+    /// Identifies the client, whose progress is tracked separately from the other ones.
+    pub client: Uuid,
     pub step_id: u64,
     pub input: usize,
 }
 
+/// The latest client inputs of a viewer, at most one per scene.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ClientInputBatch {
+    pub inputs: Vec<ClientInputRequest>,
+}
+
+/// Sent (as a text message) through the client objects WebSocket to start streaming the
+/// objects of the regions intersecting `view`. Can be sent again to update the view.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StreamClientObjectsRequest {
+    pub scene: SceneUuid,
+    pub view: Aabb,
+    #[serde(default)]
+    pub lod: Option<ClientLod>,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct InsertObjectsRequest {
     pub scene: SceneUuid,
     pub bodies: Vec<BodyAssignment>,
 }
 
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct BeginInsertRequest {
+    pub scene: SceneUuid,
+    /// The total number of bodies that will be sent through this insertion session.
+    pub total: usize,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct BeginInsertResponse {
+    pub session: Uuid,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InsertChunkRequest {
+    pub session: Uuid,
+    pub bodies: Vec<BodyAssignment>,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct InsertProgressRequest {
+    pub session: Uuid,
+}
+
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct InsertProgress {
+    /// Number of bodies received by the partitionner. Some may still be queued, waiting for
+    /// the previous chunks to be sent to their runners.
+    pub received: usize,
+    /// Number of bodies sent to their runners, which acknowledged them.
+    pub inserted: usize,
+    pub total: usize,
+}
+
+impl InsertProgress {
+    pub fn is_done(&self) -> bool {
+        self.inserted >= self.total
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpawnTemplatesRequest {
+    pub scene: SceneUuid,
+    pub templates: Vec<SceneTemplate>,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct ExportSceneRequest {
+    pub scene: SceneUuid,
+}
+
+/// The current state of every body, joint, fluid particle and vehicle of a scene.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct SceneExport {
+    pub bodies: Vec<BodyAssignment>,
+    pub impulse_joints: Vec<ImpulseJointAssignment>,
+    #[serde(default)]
+    pub particles: Vec<FluidParticle>,
+    /// The vehicles, with their chassis. The chassis aren’t part of `bodies`.
+    #[serde(default)]
+    pub vehicles: Vec<VehicleAssignment>,
+}
+
+impl SceneExport {
+    /// Adds the bodies and joints of `other` that aren’t part of `self` yet.
+    ///
+    /// Static bodies are simulated by every region, and joints between two regions are
+    /// simulated by both, so the exports of different regions overlap.
+    pub fn merge(&mut self, other: SceneExport) {
+        let mut bodies: HashSet<_> = self.bodies.iter().map(|body| body.uuid).collect();
+        let mut joints: HashSet<_> = self
+            .impulse_joints
+            .iter()
+            .map(|joint| (joint.body1, joint.body2))
+            .collect();
+
+        self.bodies.extend(
+            other
+                .bodies
+                .into_iter()
+                .filter(|body| bodies.insert(body.uuid)),
+        );
+        self.impulse_joints.extend(
+            other
+                .impulse_joints
+                .into_iter()
+                .filter(|joint| joints.insert((joint.body1, joint.body2))),
+        );
+        // Each particle, and each vehicle, is simulated by a single region.
+        self.particles.extend(other.particles);
+        self.vehicles.extend(other.vehicles);
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct ProfileSceneRequest {
+    pub scene: SceneUuid,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct SceneStatsRequest {
+    pub scene: SceneUuid,
+}
+
+/// How long, in seconds, each phase of the last step of a region took.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StepTimings {
+    pub num_bodies: usize,
+    /// Waiting for the partitionner to start the step.
+    pub waiting_acks: f32,
+    /// Fetching the watch sets of the neighbor regions.
+    pub read_watch_sets: f32,
+    pub apply_watch_sets: f32,
+    pub resolve_assignments: f32,
+    /// The whole physics step, including the broad-phase, narrow-phase and solver below.
+    pub simulation_step: f32,
+    pub broad_phase: f32,
+    pub narrow_phase: f32,
+    pub solver: f32,
+    pub connected_components: f32,
+    /// Publishing the client objects and the watch set of the region.
+    pub data_and_watch_list: f32,
+    /// Sending the bodies leaving the region to their new region.
+    pub release_reassign: f32,
+    pub ack: f32,
+    pub loop_time: f32,
+    /// Was the auxiliary work skipped during this step? See
+    /// [`SceneSettings::auxiliary_work_period`].
+    pub auxiliary_work_skipped: bool,
+}
+
+impl StepTimings {
+    /// The name and duration of every phase of the step, summing up to `loop_time`.
+    pub fn phases(&self) -> [(&'static str, f32); 12] {
+        let other_physics =
+            (self.simulation_step - self.broad_phase - self.narrow_phase - self.solver).max(0.0);
+        [
+            ("waiting_acks", self.waiting_acks),
+            ("read_watch_sets", self.read_watch_sets),
+            ("apply_watch_sets", self.apply_watch_sets),
+            ("resolve_assignments", self.resolve_assignments),
+            ("broad_phase", self.broad_phase),
+            ("narrow_phase", self.narrow_phase),
+            ("solver", self.solver),
+            ("other_physics", other_physics),
+            ("connected_components", self.connected_components),
+            ("data_and_watch_list", self.data_and_watch_list),
+            ("release_reassign", self.release_reassign),
+            ("ack", self.ack),
+        ]
+    }
+
+    /// The phase that took the longest, ignoring the wait for the step to start.
+    pub fn slowest_phase(&self) -> (&'static str, f32) {
+        self.phases()
+            .into_iter()
+            .skip(1)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap_or_default()
+    }
+}
+
+/// The timings of the last step of a region.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct RegionProfile {
+    pub region: SimulationBounds,
+    pub runner: Uuid,
+    pub step_id: u64,
+    pub timings: StepTimings,
+    /// The energy and momentum of the region at the end of that step.
+    pub stats: PhysicsStats,
+    /// The bodies simulated by the region at the end of that step, see [`BodyCounts`].
+    #[serde(default)]
+    pub bodies: BodyCounts,
+    /// The last step where a body of the region moved, or was added or removed.
+    #[serde(default)]
+    pub last_active_step: u64,
+}
+
+/// The total kinetic energy and linear momentum of a set of dynamic bodies.
+///
+/// Without external forces, they should only drift because of the handoffs between regions,
+/// and of the solver.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PhysicsStats {
+    pub num_bodies: usize,
+    pub kinetic_energy: Real,
+    pub linear_momentum: Vector<Real>,
+}
+
+impl PhysicsStats {
+    /// Adds `body` to the totals, unless it isn’t dynamic.
+    pub fn add_body(&mut self, body: &RigidBody) {
+        if body.is_dynamic() {
+            self.num_bodies += 1;
+            self.kinetic_energy += body.kinetic_energy();
+            self.linear_momentum += body.linvel() * body.mass();
+        }
+    }
+
+    pub fn merge(&mut self, other: &PhysicsStats) {
+        self.num_bodies += other.num_bodies;
+        self.kinetic_energy += other.kinetic_energy;
+        self.linear_momentum += other.linear_momentum;
+    }
+}
+
+/// The number of bodies of each type of a region, or of a whole scene.
+///
+/// The regions only count the bodies moving between regions: the dynamic bodies and the
+/// characters. The static bodies, shared by all the regions, are added by the partitionner to
+/// the counts of the whole scene.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BodyCounts {
+    pub dynamic: usize,
+    /// The dynamic bodies asleep, or frozen.
+    pub sleeping: usize,
+    pub kinematic: usize,
+    pub fixed: usize,
+}
+
+impl BodyCounts {
+    /// The number of bodies, sleeping or not.
+    pub fn total(&self) -> usize {
+        self.dynamic + self.kinematic + self.fixed
+    }
+
+    pub fn add(&mut self, body_type: RigidBodyType, sleeping: bool) {
+        match body_type {
+            RigidBodyType::Dynamic => {
+                self.dynamic += 1;
+                self.sleeping += sleeping as usize;
+            }
+            RigidBodyType::Fixed => self.fixed += 1,
+            RigidBodyType::KinematicPositionBased | RigidBodyType::KinematicVelocityBased => {
+                self.kinematic += 1
+            }
+        }
+    }
+
+    pub fn add_body(&mut self, body: &RigidBody) {
+        self.add(body.body_type(), body.is_sleeping());
+    }
+
+    pub fn merge(&mut self, other: &BodyCounts) {
+        self.dynamic += other.dynamic;
+        self.sleeping += other.sleeping;
+        self.kinematic += other.kinematic;
+        self.fixed += other.fixed;
+    }
+}
+
+/// The bodies simulated by a region, see [`SceneStats::regions`].
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct RegionBodyCounts {
+    pub region: SimulationBounds,
+    pub bodies: BodyCounts,
+}
+
+/// The energy, momentum and bodies of a whole scene, summed over its regions.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SceneStats {
+    pub num_regions: usize,
+    /// The oldest and latest steps the regions were at. The totals mix steps if they differ.
+    pub min_step_id: u64,
+    pub max_step_id: u64,
+    pub total: PhysicsStats,
+    /// The bodies of the whole scene, its static bodies included.
+    #[serde(default)]
+    pub bodies: BodyCounts,
+    /// The bodies simulated by each region, the most populated first.
+    #[serde(default)]
+    pub regions: Vec<RegionBodyCounts>,
+}
+
+/// The timings of the last step of every region of a scene, slowest first.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SceneProfile {
+    pub regions: Vec<RegionProfile>,
+}
+
+impl SceneProfile {
+    /// The region whose last step took the longest, excluding the wait for the step to start.
+    pub fn slowest(&self) -> Option<&RegionProfile> {
+        self.regions.first()
+    }
+}
+
+/// Why a region was flagged as a hot spot, see `HOT_REGION_STEP_MS`, `HOT_REGION_BODIES` and
+/// `HOT_REGION_IMBALANCE`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HotRegionReason {
+    /// Its steps take too long.
+    SlowStep,
+    /// It simulates too many bodies.
+    TooManyBodies,
+    /// Its steps take much longer than the ones of the median region of the scene.
+    Imbalance,
+}
+
+/// A region flagged by the hot spot detection of the partitionner.
+///
+/// Hot regions should be split, but the regions are a fixed grid for now: the partitionner
+/// only reports them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HotRegion {
+    /// The profile of the last step of the region when it was checked.
+    pub profile: RegionProfile,
+    pub reasons: Vec<HotRegionReason>,
+    /// The number of consecutive checks the region was hot.
+    pub num_checks: u32,
+}
+
+/// An invariant of the simulation broken by the client objects streamed by a region, see
+/// `CONSISTENCY_CHECKS`.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ConsistencyViolationKind {
+    /// The body was also simulated by the region `other` at the same timestamp.
+    DuplicateOwner { other: SimulationBounds },
+    /// The body moved further than its velocity allows since it was last reported.
+    Teleport { distance: Real, max_distance: Real },
+    /// The region reported an older timestamp than its previous one.
+    TimestampRegression { previous: u64 },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConsistencyViolation {
+    pub region: SimulationBounds,
+    pub timestamp: u64,
+    /// The body concerned, if any.
+    pub body: Option<Uuid>,
+    pub kind: ConsistencyViolationKind,
+}
+
+/// The state of a scene, as last checked by the partitionner.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SceneStatus {
+    pub step_id: u64,
+    pub running: bool,
+    /// The hot regions of the last check, slowest first.
+    pub hot_regions: Vec<HotRegion>,
+    /// The latest consistency violations found, oldest first. Always empty if
+    /// `CONSISTENCY_CHECKS` is disabled.
+    #[serde(default)]
+    pub violations: Vec<ConsistencyViolation>,
+    /// See [`SceneSettings::force_generators`].
+    #[serde(default)]
+    pub force_generators: Vec<ForceGenerator>,
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct SceneStatusRequest {
+    pub scene: SceneUuid,
+}
+
+/// Asks for all the client objects of `region`, including its sleeping bodies.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct FullSyncRequest {
+    pub scene: SceneUuid,
+    pub region: SimulationBounds,
+}
+
+/// Saves the current state of `scene` under `name`, replacing any previous save of that name.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SaveSceneRequest {
+    pub scene: SceneUuid,
+    pub name: String,
+}
+
+/// Creates the new scene `scene` from the save `name`.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct LoadSaveRequest {
+    pub name: String,
+    pub scene: SceneUuid,
+}
+
+/// Can `name` name a save, as part of its key in the state store? Same rules as
+/// [`is_valid_version`].
+pub fn is_valid_save_name(name: &str) -> bool {
+    is_valid_version(name)
+}
+
+/// A named save of a scene, stored in the state store of the cluster.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SceneSave {
+    pub info: SceneSaveInfo,
+    /// The request recreating the scene, with the uuid of the saved scene.
+    pub scene: CreateSceneRequest,
+    pub export: SceneExport,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ListRegionsRequest {
     pub scene: SceneUuid,
@@ -86,7 +581,21 @@ pub struct StartStopRequest {
     pub running: bool,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+/// Runs exactly `steps` steps of the paused `scene` from its current step, whether its clients
+/// asked for them or not.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug)]
+pub struct StepNRequest {
+    pub scene: SceneUuid,
+    pub steps: u64,
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize, Debug)]
+pub struct StepNResponse {
+    /// The step the simulation will stop at, unless its clients let it go further.
+    pub step_limit: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct ChildPartitionner {
     pub addr: String,
     pub port: u16,
@@ -95,12 +604,252 @@ pub struct ChildPartitionner {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct RegisterChildRequest {
     pub child: ChildPartitionner,
+    #[serde(default)]
+    pub capabilities: NodeCapabilities,
+}
+
+/// Moves regions of `scene` from the child `from` to the child `to`.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TransferRegionsRequest {
+    pub scene: SceneUuid,
+    pub from: ChildPartitionner,
+    pub to: ChildPartitionner,
+    /// The regions to move. If empty, all the regions of `from` move, and its new regions are
+    /// given to `to` too, so `from` can be decommissioned.
+    #[serde(default)]
+    pub regions: Vec<SimulationBounds>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TransferRegionsResponse {
+    /// The regions moved to `to`.
+    pub regions: Vec<SimulationBounds>,
+}
+
+/// Moves all the regions of the child `child` to the other children, then deregisters it.
+///
+/// Sent by the master to the child too once it is deregistered, so it stops stepping.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DrainNodeRequest {
+    pub child: ChildPartitionner,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DrainNodeResponse {
+    /// The number of regions moved to other children, over all the scenes.
+    pub regions: usize,
+}
+
+/// The hardware of a child partitionner’s node, sent when it registers.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct NodeCapabilities {
+    /// Zero if unknown.
+    pub cores: u32,
+    /// Zero if unknown.
+    pub memory_bytes: u64,
+    pub gpus: u32,
+}
+
+impl NodeCapabilities {
+    /// The share of the scenes given to this node, relative to the other children. The runners
+    /// simulate on the CPU, so this is the number of cores.
+    pub fn weight(&self) -> Real {
+        self.cores.max(1) as Real
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct CreateSceneRequest {
     pub scene: SceneUuid,
     pub bounds: Aabb,
+    #[serde(default)]
+    pub client_objects_encoding: ObjectSetEncoding,
+    /// Seed of everything random in the scene, so two runs of the same scene are identical.
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub settings: SceneSettings,
+}
+
+/// The per-scene simulation settings, set when the scene is created.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct SceneSettings {
+    /// A human-readable name, displayed in the scene lists.
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub collision_matrix: CollisionMatrix,
+    #[serde(default)]
+    pub watch_margins: WatchMargins,
+    #[serde(default)]
+    pub watch_mode: WatchMode,
+    #[serde(default)]
+    pub migration_hysteresis: MigrationHysteresis,
+    #[serde(default)]
+    pub time_scale: TimeScale,
+    #[serde(default)]
+    pub simulation_params: SimulationParams,
+    /// The number of internal steps between two runs of the connected components, the region
+    /// reassignments, the watch sets and the client objects of each region, see
+    /// [`Self::auxiliary_work_period`].
+    #[serde(default)]
+    pub auxiliary_work_interval: u64,
+    /// The volumes whose bodies are suspended, see [`FreezeVolumeRequest`].
+    #[serde(default)]
+    pub frozen_volumes: Vec<FrozenVolume>,
+    /// The uniform gravity of the scene, `-9.81` along `y` if `None`. Scenes around planets
+    /// set it to zero and rely on [`ForceGenerator::PointGravity`].
+    #[serde(default)]
+    pub gravity: Option<Vector<Real>>,
+    /// The force fields applied to the bodies in addition to the gravity, see
+    /// [`SetForceGeneratorsRequest`].
+    #[serde(default)]
+    pub force_generators: Vec<ForceGenerator>,
+    /// The particle fluid of the scene, if it has one, see [`InsertParticlesRequest`].
+    #[serde(default)]
+    pub fluid: Option<FluidParams>,
+    /// If set, the regions far from every viewer step with cheaper solver options.
+    #[serde(default)]
+    pub region_lod: Option<RegionLod>,
+    /// How the runners check, and fix, the bodies assigned to their regions.
+    #[serde(default)]
+    pub admission: AdmissionPolicy,
+    /// If set, the bodies can’t go further than this, e.g., falling forever below the world.
+    #[serde(default)]
+    pub domain: Option<SimulationDomain>,
+}
+
+impl SceneSettings {
+    /// The number of control steps between two runs of the auxiliary work of the regions.
+    ///
+    /// The auxiliary work runs between control steps, so `auxiliary_work_interval` is rounded
+    /// up to a multiple of [`NUM_INTERNAL_STEPS`]. Anything up to it runs it at every control
+    /// step. Longer periods delay the bodies crossing borders, and the updates of the viewers.
+    pub fn auxiliary_work_period(&self) -> u64 {
+        self.auxiliary_work_interval
+            .div_ceil(NUM_INTERNAL_STEPS)
+            .max(1)
+    }
+}
+
+/// How fast a scene is simulated compared to real time: below one is slow motion, above one
+/// fast-forward.
+///
+/// Each step still simulates the same duration, the scale changes how many steps are run per
+/// real second, both by the pacing of the partitionner and by the playback of the viewers.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TimeScale(pub f32);
+
+impl TimeScale {
+    pub const MIN: Self = Self(0.01);
+    pub const MAX: Self = Self(100.0);
+
+    /// Is this scale within [`Self::MIN`] and [`Self::MAX`]?
+    pub fn is_valid(self) -> bool {
+        self.0 >= Self::MIN.0 && self.0 <= Self::MAX.0
+    }
+}
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// The options of the Rapier solver of a scene, trading accuracy for speed.
+///
+/// Rapier 0.17 only has a PGS solver: stiffer stacks need more iterations, or more substeps.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimulationParams {
+    pub max_velocity_iterations: usize,
+    pub max_velocity_friction_iterations: usize,
+    pub max_stabilization_iterations: usize,
+    /// The number of physics steps each internal step is split into, with a timestep divided
+    /// accordingly.
+    pub substeps: usize,
+}
+
+impl SimulationParams {
+    /// The maximum of each iteration count, and of the number of substeps.
+    pub const MAX_ITERATIONS: usize = 100;
+
+    /// Are all the iteration counts and the number of substeps between 1 and
+    /// [`Self::MAX_ITERATIONS`]?
+    pub fn is_valid(&self) -> bool {
+        [
+            self.max_velocity_iterations,
+            self.max_velocity_friction_iterations,
+            self.max_stabilization_iterations,
+            self.substeps,
+        ]
+        .iter()
+        .all(|n| (1..=Self::MAX_ITERATIONS).contains(n))
+    }
+
+    /// The parameters of each substep, given those of a whole internal step.
+    pub fn substep_params(&self, step_params: &IntegrationParameters) -> IntegrationParameters {
+        IntegrationParameters {
+            dt: step_params.dt / self.substeps as Real,
+            max_velocity_iterations: self.max_velocity_iterations,
+            max_velocity_friction_iterations: self.max_velocity_friction_iterations,
+            max_stabilization_iterations: self.max_stabilization_iterations,
+            ..*step_params
+        }
+    }
+}
+
+impl Default for SimulationParams {
+    fn default() -> Self {
+        let params = IntegrationParameters::default();
+        Self {
+            max_velocity_iterations: params.max_velocity_iterations,
+            max_velocity_friction_iterations: params.max_velocity_friction_iterations,
+            max_stabilization_iterations: params.max_stabilization_iterations,
+            substeps: 1,
+        }
+    }
+}
+
+/// The level of detail of the physics of the regions, to save CPU on the parts of large scenes
+/// nobody is looking at.
+///
+/// The partitionner compares the regions to the views of the viewers, and tells the runners
+/// which regions are far. Those step with [`Self::far_params`] instead of the
+/// [`SceneSettings::simulation_params`] of the scene, until a viewer comes closer than
+/// [`Self::near_distance`]: the full solver options are restored before the region is in view.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RegionLod {
+    /// The regions closer than this to the view of a viewer are simulated with the full solver
+    /// options.
+    pub near_distance: Real,
+    /// The solver options of the other regions.
+    pub far_params: SimulationParams,
+}
+
+impl Default for RegionLod {
+    fn default() -> Self {
+        Self {
+            near_distance: SimulationBounds::DEFAULT_WIDTH as Real,
+            far_params: SimulationParams {
+                max_velocity_iterations: 1,
+                max_velocity_friction_iterations: 1,
+                max_stabilization_iterations: 1,
+                substeps: 1,
+            },
+        }
+    }
+}
+
+impl RegionLod {
+    /// Is `region` further than `near_distance` from every view of `views`?
+    pub fn is_far<'a>(
+        &self,
+        region: &SimulationBounds,
+        mut views: impl Iterator<Item = &'a Aabb>,
+    ) -> bool {
+        let aabb = region.aabb().loosened(self.near_distance);
+        !views.any(|view| aabb.intersects(view))
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -108,13 +857,351 @@ pub struct CreateSceneResponse {
     pub runner: Uuid,
 }
 
+/// Sets the WASM module called by the runners at each step of `scene`.
+///
+/// An empty module removes the script of the scene.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SetScriptRequest {
+    pub scene: SceneUuid,
+    pub module: Vec<u8>,
+}
+
+/// Changes the [`SimulationParams`] of `scene`, from its next step.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug)]
+pub struct SetSimulationParamsRequest {
+    pub scene: SceneUuid,
+    pub params: SimulationParams,
+}
+
+/// Changes the [`TimeScale`] of `scene`.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug)]
+pub struct SetTimeScaleRequest {
+    pub scene: SceneUuid,
+    pub time_scale: TimeScale,
+}
+
+/// Applies `input` to the body `body` of `scene`, wherever it is simulated.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug)]
+pub struct BodyInputRequest {
+    pub scene: SceneUuid,
+    pub body: Uuid,
+    pub input: BodyInput,
+}
+
+/// Removes `bodies` from `scene`, wherever they are simulated.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RemoveBodiesRequest {
+    pub scene: SceneUuid,
+    pub bodies: Vec<Uuid>,
+}
+
+/// Freezes the dynamic bodies of `scene` with their center inside `aabb`, or unfreezes the
+/// volume `volume` if `aabb` is `None`.
+///
+/// Frozen bodies are put to sleep and skipped by the steps until their volume is unfrozen.
+/// The bodies entering a frozen volume are frozen too.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug)]
+pub struct FreezeVolumeRequest {
+    pub scene: SceneUuid,
+    pub volume: Uuid,
+    pub aabb: Option<Aabb>,
+}
+
+/// Replaces the force generators of `scene`, from the next step of its regions.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SetForceGeneratorsRequest {
+    pub scene: SceneUuid,
+    pub generators: Vec<ForceGenerator>,
+}
+
+/// Adds fluid particles to `scene`, simulated by the regions they are in. Ignored by the
+/// runners if the scene has no [`SceneSettings::fluid`].
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct InsertParticlesRequest {
+    pub scene: SceneUuid,
+    pub particles: Vec<FluidParticle>,
+}
+
+/// Adds vehicles to `scene`, simulated by the regions their chassis are in.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InsertVehiclesRequest {
+    pub scene: SceneUuid,
+    pub vehicles: Vec<VehicleAssignment>,
+}
+
+/// The volume the bodies of a scene are simulated in, so those escaping it don’t keep creating
+/// new regions.
+///
+/// The region simulating a body that leaves `aabb` applies `policy` to it, and reports it as a
+/// [`crate::messages::SimulationEvent::LeftDomain`] event.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug)]
+pub struct SimulationDomain {
+    pub aabb: Aabb,
+    #[serde(default)]
+    pub policy: OutOfDomainPolicy,
+}
+
+/// What happens to a body leaving the [`SimulationDomain`] of its scene.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub enum OutOfDomainPolicy {
+    #[default]
+    Despawn,
+    /// Moves the body to this position, and stops it.
+    Teleport(Isometry<Real>),
+    /// Suspends the body where it left the domain, like the bodies of the frozen volumes. The
+    /// characters can’t be frozen, they are despawned instead.
+    Freeze,
+}
+
+/// A volume frozen by a [`FreezeVolumeRequest`].
+#[derive(Copy, Clone, Serialize, Deserialize, Debug)]
+pub struct FrozenVolume {
+    pub uuid: Uuid,
+    pub aabb: Aabb,
+    /// The first step the bodies of the volume are frozen at, the same for every region.
+    pub since_step: u64,
+}
+
+/// Starts, or stops if `traced` is `false`, recording the trajectories of `bodies`.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TraceBodiesRequest {
+    pub scene: SceneUuid,
+    pub bodies: Vec<Uuid>,
+    pub traced: bool,
+}
+
+/// The recorded trajectories of `bodies`, or of every traced body of `scene` if it is empty.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TrajectoriesRequest {
+    pub scene: SceneUuid,
+    #[serde(default)]
+    pub bodies: Vec<Uuid>,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct RemoveSceneRequest {
     pub scene: SceneUuid,
 }
 
+/// The scene metadata of the master partitionner, replicated to its children so one of them
+/// can take over if the master dies.
+#[derive(Clone, Serialize, Deserialize, Default)]
+pub struct ReplicatedState {
+    /// The children of the master, in registration order. This is also the election order.
+    pub children: Vec<ChildPartitionner>,
+    /// The capabilities of `children`, in the same order.
+    #[serde(default)]
+    pub children_capabilities: Vec<NodeCapabilities>,
+    pub running: bool,
+    pub scenes: Vec<ReplicatedScene>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReplicatedScene {
+    pub scene: SceneUuid,
+    /// The part of the scene handled by each child, in the same order as the children.
+    pub children_bounds: Vec<Aabb>,
+    pub client_objects_encoding: ObjectSetEncoding,
+    /// The runner hosting the scene on each child, in the same order as the children.
+    pub runners: Vec<Uuid>,
+    /// The runner simulating each region.
+    pub assigned: Vec<(SimulationBounds, Uuid)>,
+    pub static_bodies: Vec<BodyAssignment>,
+    pub step_id: u64,
+    pub step_limit: u64,
+    pub time_scale: TimeScale,
+    /// The tenant that created the scene, if any.
+    #[serde(default)]
+    pub tenant: Option<String>,
+    /// The bodies inserted into the scene, counted against the quota of its tenant.
+    #[serde(default)]
+    pub num_bodies: usize,
+    /// See [`SceneSettings::name`].
+    #[serde(default)]
+    pub name: String,
+    /// See [`SceneInfo::created_at`](crate::objects::SceneInfo::created_at).
+    #[serde(default)]
+    pub created_at: u64,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct GetExesResponse {
     pub runner: Vec<u8>,
     pub partitionner: Vec<u8>,
+    /// `RELEASE_VERSION`, or the start of the checksum of both executables.
+    pub version: String,
+    /// The hex-encoded SHA-256 of `runner`.
+    pub runner_sha256: String,
+    /// The hex-encoded SHA-256 of `partitionner`.
+    pub partitionner_sha256: String,
+    /// The ed25519 signature of `runner`, see [`crate::signing`].
+    pub runner_signature: Option<String>,
+    /// The ed25519 signature of `partitionner`.
+    pub partitionner_signature: Option<String>,
+}
+
+impl GetExesResponse {
+    pub fn new(runner: Vec<u8>, partitionner: Vec<u8>, version: Option<String>) -> Self {
+        let runner_sha256 = sha256(&runner);
+        let partitionner_sha256 = sha256(&partitionner);
+        let version = version.unwrap_or_else(|| {
+            sha256(format!("{runner_sha256}{partitionner_sha256}"))[..12].to_string()
+        });
+        Self {
+            runner,
+            partitionner,
+            version,
+            runner_sha256,
+            partitionner_sha256,
+            runner_signature: None,
+            partitionner_signature: None,
+        }
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.runner_signature.is_some() && self.partitionner_signature.is_some()
+    }
+
+    /// Checks that both executables are signed by the owner of `public_key`.
+    pub fn verify_signatures(&self, public_key: &str) -> anyhow::Result<()> {
+        let (Some(runner_signature), Some(partitionner_signature)) =
+            (&self.runner_signature, &self.partitionner_signature)
+        else {
+            anyhow::bail!("The executables of version {} aren’t signed.", self.version);
+        };
+
+        crate::signing::verify(public_key, &self.runner, runner_signature)
+            .map_err(|e| anyhow::anyhow!("Invalid signature of the runner executable: {e}"))?;
+        crate::signing::verify(public_key, &self.partitionner, partitionner_signature).map_err(
+            |e| anyhow::anyhow!("Invalid signature of the partitionner executable: {e}"),
+        )?;
+        Ok(())
+    }
+
+    /// Checks that the executables match their checksums, and that the version can name a
+    /// directory.
+    pub fn verify(&self) -> anyhow::Result<()> {
+        if !is_valid_version(&self.version) {
+            anyhow::bail!("Invalid version: {:?}.", self.version);
+        }
+        if sha256(&self.runner) != self.runner_sha256 {
+            anyhow::bail!("The runner executable doesn’t match its checksum.");
+        }
+        if sha256(&self.partitionner) != self.partitionner_sha256 {
+            anyhow::bail!("The partitionner executable doesn’t match its checksum.");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetExesDeltaRequest {
+    /// The version the node has downloaded already.
+    pub from: String,
+}
+
+/// The executables of `version`, as deltas from the ones of `from`.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetExesDeltaResponse {
+    pub from: String,
+    pub version: String,
+    pub runner: ExeDelta,
+    pub partitionner: ExeDelta,
+    pub runner_sha256: String,
+    pub partitionner_sha256: String,
+    pub runner_signature: Option<String>,
+    pub partitionner_signature: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ExeDelta {
+    pub delta: Vec<u8>,
+    /// The size of the patched executable.
+    pub len: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ExeDelta {
+    fn new(base: &[u8], target: &[u8]) -> anyhow::Result<Self> {
+        Ok(Self {
+            delta: crate::delta::diff(base, target)?,
+            len: target.len(),
+        })
+    }
+
+    fn apply(&self, base: &[u8]) -> anyhow::Result<Vec<u8>> {
+        crate::delta::patch(base, &self.delta, self.len)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl GetExesDeltaResponse {
+    /// The deltas turning the executables of `base` into the ones of `target`.
+    pub fn new(base: &GetExesResponse, target: &GetExesResponse) -> anyhow::Result<Self> {
+        Ok(Self {
+            from: base.version.clone(),
+            version: target.version.clone(),
+            runner: ExeDelta::new(&base.runner, &target.runner)?,
+            partitionner: ExeDelta::new(&base.partitionner, &target.partitionner)?,
+            runner_sha256: target.runner_sha256.clone(),
+            partitionner_sha256: target.partitionner_sha256.clone(),
+            runner_signature: target.runner_signature.clone(),
+            partitionner_signature: target.partitionner_signature.clone(),
+        })
+    }
+
+    /// Patches the executables of `base`, which must be of version `self.from`.
+    ///
+    /// The result should be [verified](GetExesResponse::verify) before being run.
+    pub fn apply(&self, base: &GetExesResponse) -> anyhow::Result<GetExesResponse> {
+        if base.version != self.from {
+            anyhow::bail!(
+                "The delta applies to version {}, not {}.",
+                self.from,
+                base.version
+            );
+        }
+
+        Ok(GetExesResponse {
+            runner: self.runner.apply(&base.runner)?,
+            partitionner: self.partitionner.apply(&base.partitionner)?,
+            version: self.version.clone(),
+            runner_sha256: self.runner_sha256.clone(),
+            partitionner_sha256: self.partitionner_sha256.clone(),
+            runner_signature: self.runner_signature.clone(),
+            partitionner_signature: self.partitionner_signature.clone(),
+        })
+    }
+}
+
+fn sha256(bytes: impl AsRef<[u8]>) -> String {
+    use sha2::Digest;
+    format!("{:x}", sha2::Sha256::digest(bytes))
+}
+
+/// Is `version` made of ASCII letters, digits, `.`, `-` and `_` only, so it can name a directory?
+pub fn is_valid_version(version: &str) -> bool {
+    !version.is_empty()
+        && !version.starts_with('.')
+        && version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+/// Sent periodically by the updater of each node, to know which version it should run.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct UpdateCheckRequest {
+    /// Identifies the node across restarts of its updater.
+    pub node: String,
+    /// The version the node runs, if any.
+    pub version: Option<String>,
+    /// Does the partitionner of the node answer its heartbeats?
+    pub healthy: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct UpdateCheckResponse {
+    /// The version the node should run now: the one it already runs while it waits for its turn
+    /// in a staged rollout.
+    pub version: String,
 }
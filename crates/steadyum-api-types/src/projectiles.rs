@@ -0,0 +1,54 @@
+//! Short-lived bodies removed by the runners, e.g., bullets or debris, see
+//! [`ProjectileParams`].
+
+use crate::messages::BodyAssignment;
+use crate::objects::{ColdBodyObject, WarmBodyObject};
+use rapier::dynamics::RigidBodyType;
+use rapier::geometry::SharedShape;
+use rapier::math::{Isometry, Point, Real, Vector};
+use uuid::Uuid;
+
+/// Makes a dynamic body a projectile, removed by the region simulating it after `lifetime`
+/// steps, or when it first hits another body if `despawn_on_hit` is set.
+///
+/// Its hits are reported as [`crate::messages::SimulationEvent::ProjectileHit`]. The remaining
+/// lifetime follows the projectile from region to region, so no debris is left behind.
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ProjectileParams {
+    /// The number of steps left before the projectile is removed.
+    pub lifetime: u64,
+    pub despawn_on_hit: bool,
+}
+
+impl ProjectileParams {
+    /// A ball fired from `origin` at `linvel`.
+    pub fn ball(
+        &self,
+        uuid: Uuid,
+        radius: Real,
+        density: Real,
+        origin: Point<Real>,
+        linvel: Vector<Real>,
+    ) -> BodyAssignment {
+        BodyAssignment {
+            uuid,
+            warm: WarmBodyObject {
+                position: Isometry::from(origin.coords),
+                linvel,
+                ..Default::default()
+            },
+            cold: ColdBodyObject {
+                body_type: RigidBodyType::Dynamic,
+                density,
+                shape: SharedShape::ball(radius),
+                animations: Default::default(),
+                user_data: vec![],
+                is_sensor: false,
+                collision_layer: None,
+                character: None,
+                projectile: Some(*self),
+            },
+            epoch: 0,
+        }
+    }
+}
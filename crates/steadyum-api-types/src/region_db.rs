@@ -1,38 +1,325 @@
 use crate::env::CONFIG;
-use crate::messages::BodyAssignment;
-use crate::objects::{RegionList, SceneList};
+use crate::fluids::FluidParticle;
+use crate::forces::ForceGenerator;
+use crate::messages::{BodyAssignment, BodyInput, VehicleAssignment};
+use crate::objects::{ClientBodyObjectSet, RegionList, SceneList, SceneSaveInfo, SceneSaveList};
 use crate::partitionner::{
-    AckRequest, ClientInputRequest, CreateSceneRequest, CreateSceneResponse, StepRequest,
-    CLIENT_INPUT_ENDPOINT, CREATE_SCENE_ENDPOINT, LIST_SCENES_ENDPOINT, STEP_ENDPOINT,
+    AckRequest, ClientInputBatch, ClientInputRequest, CreateSceneRequest, CreateSceneResponse,
+    StepRequest, CLIENT_INPUTS_ENDPOINT, CLIENT_INPUT_ENDPOINT, CREATE_SCENE_ENDPOINT,
+    LIST_SCENES_ENDPOINT, STEP_ENDPOINT,
 };
 use crate::partitionner::{
     AssignRunnerRequest, AssignRunnerResponse, ChildPartitionner, GetExesResponse,
     InsertObjectsRequest, ListRegionsRequest, RegisterChildRequest, RemoveSceneRequest,
     RunnerInitializedRequest, SceneUuid, StartStopRequest, ACK_ENDPOINT, ASSIGN_RUNNER_ENDPOINT,
     GET_EXES, HEARTBEAT, INSERT_OBJECTS_ENDPOINT, LIST_REGIONS_ENDPOINT, REGISTER_CHILD_ENDPOINT,
-    REMOVE_SCENE_ENDPOINT, RUNNER_INITIALIZED_ENDPOINT, SHUTDOWN, START_STOP_ENDPOINT,
+    REMOVE_SCENE_ENDPOINT, RUNNER_INITIALIZED_ENDPOINT, SHUTDOWN, SPAWN_TEMPLATES_ENDPOINT,
+    START_STOP_ENDPOINT, STREAM_CLIENT_OBJECTS_ENDPOINT,
 };
-use crate::serialization::deserialize;
+use crate::partitionner::{
+    BeginInsertRequest, BeginInsertResponse, ExportSceneRequest, InsertChunkRequest,
+    InsertProgress, InsertProgressRequest, SceneExport, SpawnTemplatesRequest, API_KEY_HEADER,
+    BEGIN_INSERT_ENDPOINT, BINCODE_CONTENT_TYPE, CLUSTER_KEY_HEADER, EXPORT_SCENE_ENDPOINT,
+    INSERT_CHUNK_ENDPOINT, INSERT_PROGRESS_ENDPOINT,
+};
+use crate::partitionner::{
+    BodyInputRequest, BodyOwner, BodyOwnerRequest, FullSyncRequest, ProfileSceneRequest,
+    RemoveBodiesRequest, ReplicatedState, SceneProfile, SceneSettings, SceneStats,
+    SceneStatsRequest, SceneStatus, SceneStatusRequest, SetScriptRequest,
+    SetSimulationParamsRequest, SetTimeScaleRequest, SimulationParams, StepNRequest, StepNResponse,
+    TimeScale, TraceBodiesRequest, TrajectoriesRequest, BODY_INPUT_ENDPOINT, BODY_OWNER_ENDPOINT,
+    FULL_SYNC_ENDPOINT, PROFILE_SCENE_ENDPOINT, REMOVE_BODIES_ENDPOINT, REPLICATE_ENDPOINT,
+    SCENE_STATS_ENDPOINT, SCENE_STATUS_ENDPOINT, SET_SCRIPT_ENDPOINT,
+    SET_SIMULATION_PARAMS_ENDPOINT, SET_TIME_SCALE_ENDPOINT, STEP_N_ENDPOINT,
+    TRACE_BODIES_ENDPOINT, TRAJECTORIES_ENDPOINT,
+};
+use crate::partitionner::{
+    DrainNodeRequest, DrainNodeResponse, FreezeVolumeRequest, GetExesDeltaRequest,
+    GetExesDeltaResponse, LoadSaveRequest, NodeCapabilities, SaveSceneRequest,
+    TransferRegionsRequest, TransferRegionsResponse, UpdateCheckRequest, UpdateCheckResponse,
+    DRAIN_NODE_ENDPOINT, FREEZE_VOLUME_ENDPOINT, GET_EXES_DELTA_ENDPOINT, LIST_SAVES_ENDPOINT,
+    LOAD_SAVE_ENDPOINT, SAVE_SCENE_ENDPOINT, TRANSFER_REGIONS_ENDPOINT, UPDATE_CHECK_ENDPOINT,
+};
+use crate::partitionner::{
+    InsertParticlesRequest, InsertVehiclesRequest, SetForceGeneratorsRequest,
+    INSERT_PARTICLES_ENDPOINT, INSERT_VEHICLES_ENDPOINT, SET_FORCE_GENERATORS_ENDPOINT,
+};
+use crate::serialization::{deserialize, deserialize_client_objects, serialize, ObjectSetEncoding};
 use crate::simulation::SimulationBounds;
+use crate::telemetry::{StepCorrelation, STEP_HEADER};
+use crate::templates::SceneTemplate;
+use crate::trajectory::Trajectories;
+use instant::Instant;
 use rapier::prelude::Aabb;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{RequestBuilder, Response};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::Duration;
 use uuid::Uuid;
 
+/// How requests to the partitionner are retried when they fail.
+#[derive(Copy, Clone, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of times a single request is sent again after failing.
+    pub max_retries: u32,
+    /// Delay before the first retry. It doubles after each retry.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Number of consecutive failed requests after which the partitionner is
+    /// assumed gone and requests fail immediately.
+    pub circuit_breaker_threshold: u32,
+    /// How long requests fail immediately once the circuit breaker tripped.
+    pub circuit_breaker_cooldown: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(2),
+            circuit_breaker_threshold: 5,
+            circuit_breaker_cooldown: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Error returned by all the requests of an [`AsyncPartitionnerServer`].
+///
+/// It can be retrieved from an `anyhow::Error` with `downcast_ref`.
+#[derive(Debug)]
+pub enum PartitionnerError {
+    /// The partitionner couldn’t be reached, even after retrying.
+    Unreachable(reqwest::Error),
+    /// Too many requests failed in a row, the request wasn’t sent.
+    CircuitOpen,
+    /// The request failed but could succeed later (timeout, server error).
+    Temporary(reqwest::Error),
+    /// The partitionner refused the request.
+    Rejected(reqwest::Error),
+}
+
+impl PartitionnerError {
+    fn classify(error: reqwest::Error) -> Self {
+        // NOTE: the browser doesn’t tell connection failures apart, assume any failed fetch
+        //       without a response didn’t reach the partitionner.
+        #[cfg(target_arch = "wasm32")]
+        let is_connect = error.is_request() && error.status().is_none();
+        #[cfg(not(target_arch = "wasm32"))]
+        let is_connect = error.is_connect();
+
+        if is_connect {
+            Self::Unreachable(error)
+        } else if error.status().map(|s| s.is_client_error()) == Some(true) {
+            Self::Rejected(error)
+        } else {
+            Self::Temporary(error)
+        }
+    }
+
+    /// Is the partitionner likely gone, as opposed to temporarily failing?
+    pub fn is_gone(&self) -> bool {
+        matches!(self, Self::Unreachable(_) | Self::CircuitOpen)
+    }
+
+    /// Returns `true` if `error` is a [`PartitionnerError`] for which [`Self::is_gone`] is `true`.
+    pub fn is_gone_error(error: &anyhow::Error) -> bool {
+        error
+            .downcast_ref::<Self>()
+            .map(|e| e.is_gone())
+            .unwrap_or(false)
+    }
+}
+
+impl fmt::Display for PartitionnerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unreachable(e) => write!(f, "partitionner unreachable: {e}"),
+            Self::CircuitOpen => write!(f, "partitionner unreachable: too many failed requests"),
+            Self::Temporary(e) => write!(f, "partitionner request failed: {e}"),
+            Self::Rejected(e) => write!(f, "partitionner rejected the request: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for PartitionnerError {}
+
+/// Tracks consecutive failures of an endpoint, shared by all the clones of an
+/// [`AsyncPartitionnerServer`].
+#[derive(Default)]
+struct CircuitBreaker {
+    consecutive_failures: AtomicU32,
+    open_until: Mutex<Option<Instant>>,
+}
+
+impl CircuitBreaker {
+    fn is_open(&self) -> bool {
+        let open_until = self.open_until.lock().unwrap();
+        open_until.map(|t| Instant::now() < t).unwrap_or(false)
+    }
+
+    fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::SeqCst);
+        *self.open_until.lock().unwrap() = None;
+    }
+
+    fn record_failure(&self, policy: &RetryPolicy) {
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::SeqCst) + 1;
+        if failures >= policy.circuit_breaker_threshold {
+            let open_until = Instant::now() + policy.circuit_breaker_cooldown;
+            *self.open_until.lock().unwrap() = Some(open_until);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn sleep(duration: Duration) {
+    gloo_timers::future::sleep(duration).await
+}
+
+/// Can a request to `endpoint` be sent twice without changing the outcome?
+fn is_idempotent(endpoint: &str) -> bool {
+    [
+        HEARTBEAT,
+        GET_EXES,
+        GET_EXES_DELTA_ENDPOINT,
+        ACK_ENDPOINT,
+        LIST_REGIONS_ENDPOINT,
+        LIST_SCENES_ENDPOINT,
+        INSERT_PROGRESS_ENDPOINT,
+        EXPORT_SCENE_ENDPOINT,
+        START_STOP_ENDPOINT,
+        REMOVE_SCENE_ENDPOINT,
+        REPLICATE_ENDPOINT,
+        BODY_OWNER_ENDPOINT,
+        SET_SCRIPT_ENDPOINT,
+        REMOVE_BODIES_ENDPOINT,
+        PROFILE_SCENE_ENDPOINT,
+        SET_TIME_SCALE_ENDPOINT,
+        TRACE_BODIES_ENDPOINT,
+        TRAJECTORIES_ENDPOINT,
+        SCENE_STATS_ENDPOINT,
+        SET_SIMULATION_PARAMS_ENDPOINT,
+        SCENE_STATUS_ENDPOINT,
+        FULL_SYNC_ENDPOINT,
+        SAVE_SCENE_ENDPOINT,
+        LIST_SAVES_ENDPOINT,
+        UPDATE_CHECK_ENDPOINT,
+        FREEZE_VOLUME_ENDPOINT,
+        SET_FORCE_GENERATORS_ENDPOINT,
+    ]
+    .contains(&endpoint)
+}
+
 #[derive(Clone)]
 pub struct AsyncPartitionnerServer {
     client: reqwest::Client,
     addr: String,
     port: u16,
+    /// Send request bodies as bincode instead of JSON.
+    binary_bodies: bool,
+    retry_policy: RetryPolicy,
+    default_timeout: Duration,
+    timeouts: HashMap<&'static str, Duration>,
+    /// Endpoints tried in order once `addr:port` is unreachable.
+    candidates: Arc<RwLock<Vec<(String, u16)>>>,
+    /// Index of the endpoint requests are sent to: 0 for `addr:port`, `i` for the
+    /// candidate `i - 1`.
+    active: Arc<AtomicUsize>,
+    circuit_breakers: Arc<Mutex<HashMap<String, Arc<CircuitBreaker>>>>,
+    /// Sent in the [`API_KEY_HEADER`] of every request.
+    api_key: Option<String>,
+    /// Sent in the [`CLUSTER_KEY_HEADER`] of every request.
+    cluster_key: Option<String>,
 }
 
 impl AsyncPartitionnerServer {
+    /// Connects to the master partitionner, failing over to the candidates of the config.
     pub fn new() -> anyhow::Result<Self> {
-        Self::with_endpoint(CONFIG.partitionner_addr.clone(), CONFIG.partitionner_port)
+        Ok(
+            Self::with_endpoint(CONFIG.partitionner_addr.clone(), CONFIG.partitionner_port)?
+                .candidates(CONFIG.partitionner_candidates()),
+        )
     }
 
     pub fn with_endpoint(addr: String, port: u16) -> anyhow::Result<Self> {
         let client = reqwest::Client::new();
-        Ok(Self { client, addr, port })
+        let timeouts = [
+            (HEARTBEAT, Duration::from_secs(2)),
+            (GET_EXES, Duration::from_secs(2)),
+            // The partitionner delays its answer until it has room for the chunk.
+            (INSERT_CHUNK_ENDPOINT, Duration::from_secs(120)),
+            // The regions only answer in between two steps.
+            (EXPORT_SCENE_ENDPOINT, Duration::from_secs(60)),
+        ];
+        Ok(Self {
+            client,
+            addr,
+            port,
+            binary_bodies: true,
+            retry_policy: RetryPolicy::default(),
+            default_timeout: Duration::from_secs(10),
+            timeouts: timeouts.into_iter().collect(),
+            candidates: Arc::new(RwLock::new(vec![])),
+            active: Arc::new(AtomicUsize::new(0)),
+            circuit_breakers: Arc::new(Mutex::new(HashMap::new())),
+            api_key: Some(CONFIG.api_key.clone()).filter(|api_key| !api_key.is_empty()),
+            cluster_key: Some(CONFIG.cluster_key.clone())
+                .filter(|cluster_key| !cluster_key.is_empty()),
+        })
+    }
+
+    /// Sets the endpoints, as `(addr, port)`, tried in order when the current one is
+    /// unreachable.
+    pub fn candidates(self, candidates: Vec<(String, u16)>) -> Self {
+        self.set_candidates(candidates);
+        self
+    }
+
+    /// Replaces the failover endpoints of this client and all its clones.
+    pub fn set_candidates(&self, candidates: Vec<(String, u16)>) {
+        *self.candidates.write().unwrap() = candidates;
+    }
+
+    /// Sets how failed requests are retried.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Sets the timeout of the requests to endpoints without a specific timeout.
+    pub fn default_timeout(mut self, timeout: Duration) -> Self {
+        self.default_timeout = timeout;
+        self
+    }
+
+    /// Sets the timeout of each request to `endpoint`, e.g., [`HEARTBEAT`].
+    pub fn endpoint_timeout(mut self, endpoint: &'static str, timeout: Duration) -> Self {
+        self.timeouts.insert(endpoint, timeout);
+        self
+    }
+
+    /// Sets whether request bodies are sent as bincode (the default) or as JSON.
+    ///
+    /// JSON is slower and larger but makes the traffic easier to inspect.
+    pub fn binary_bodies(mut self, enabled: bool) -> Self {
+        self.binary_bodies = enabled;
+        self
+    }
+
+    /// Sets the API key identifying the tenant of the requests, instead of `API_KEY`.
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
     }
 
     pub fn local() -> anyhow::Result<Self> {
@@ -40,37 +327,77 @@ impl AsyncPartitionnerServer {
     }
 
     pub async fn shutdown(&self) -> anyhow::Result<()> {
-        self.client.get(self.endpoint(SHUTDOWN)).send().await?;
+        self.get(SHUTDOWN).await?;
         Ok(())
     }
 
     pub async fn heartbeat(&self) -> anyhow::Result<()> {
-        self.client
-            .get(self.endpoint(HEARTBEAT))
-            .timeout(Duration::from_secs(2))
-            .send()
-            .await?;
+        self.get(HEARTBEAT).await?;
         Ok(())
     }
 
     pub async fn get_exes(&self) -> anyhow::Result<GetExesResponse> {
-        let raw_response = self
-            .client
-            .get(self.endpoint(GET_EXES))
-            .timeout(Duration::from_secs(2))
-            .send()
-            .await?;
+        let raw_response = self.get(GET_EXES).await?;
         let response = deserialize(&raw_response.bytes().await?)?;
         Ok(response)
     }
 
-    pub async fn put_runner_initialized(&self, scene: SceneUuid, uuid: Uuid) -> anyhow::Result<()> {
-        let body = RunnerInitializedRequest { scene, uuid };
-        self.client
-            .post(self.endpoint(RUNNER_INITIALIZED_ENDPOINT))
-            .json(&body)
-            .send()
-            .await?;
+    /// The executables of the version served by the master, as deltas from the ones of `from`.
+    ///
+    /// Fails if the master no longer knows `from`, the caller should then fall back to
+    /// [`Self::get_exes`].
+    pub async fn get_exes_delta(&self, from: String) -> anyhow::Result<GetExesDeltaResponse> {
+        let body = GetExesDeltaRequest { from };
+        let raw_response = self.post(GET_EXES_DELTA_ENDPOINT, &body).await?;
+        let response = deserialize(&raw_response.bytes().await?)?;
+        Ok(response)
+    }
+
+    /// Moves the `regions` of `scene`, or all of them if empty, from the child `from` to the
+    /// child `to`.
+    pub async fn transfer_regions(
+        &self,
+        scene: SceneUuid,
+        from: ChildPartitionner,
+        to: ChildPartitionner,
+        regions: Vec<SimulationBounds>,
+    ) -> anyhow::Result<TransferRegionsResponse> {
+        let body = TransferRegionsRequest {
+            scene,
+            from,
+            to,
+            regions,
+        };
+        let raw_response = self.post(TRANSFER_REGIONS_ENDPOINT, &body).await?;
+        Ok(raw_response.json().await?)
+    }
+
+    /// Moves all the regions of the child `child` to the other children, then deregisters it.
+    pub async fn drain_node(&self, child: ChildPartitionner) -> anyhow::Result<DrainNodeResponse> {
+        let body = DrainNodeRequest { child };
+        let raw_response = self.post(DRAIN_NODE_ENDPOINT, &body).await?;
+        Ok(raw_response.json().await?)
+    }
+
+    /// The version the node `node`, currently running `version`, should run.
+    pub async fn update_check(
+        &self,
+        node: String,
+        version: Option<String>,
+        healthy: bool,
+    ) -> anyhow::Result<UpdateCheckResponse> {
+        let body = UpdateCheckRequest {
+            node,
+            version,
+            healthy,
+        };
+        let raw_response = self.post(UPDATE_CHECK_ENDPOINT, &body).await?;
+        Ok(raw_response.json().await?)
+    }
+
+    pub async fn put_runner_initialized(&self, uuid: Uuid) -> anyhow::Result<()> {
+        let body = RunnerInitializedRequest { uuid };
+        self.post(RUNNER_INITIALIZED_ENDPOINT, &body).await?;
         Ok(())
     }
 
@@ -80,12 +407,7 @@ impl AsyncPartitionnerServer {
         region: SimulationBounds,
     ) -> anyhow::Result<Uuid> {
         let body = AssignRunnerRequest { scene, region };
-        let raw_response = self
-            .client
-            .post(self.endpoint(ASSIGN_RUNNER_ENDPOINT))
-            .json(&body)
-            .send()
-            .await?;
+        let raw_response = self.post(ASSIGN_RUNNER_ENDPOINT, &body).await?;
         let response: AssignRunnerResponse = raw_response.json().await?;
         Ok(response.uuid)
     }
@@ -96,51 +418,314 @@ impl AsyncPartitionnerServer {
         bodies: Vec<BodyAssignment>,
     ) -> anyhow::Result<()> {
         let body = InsertObjectsRequest { scene, bodies };
-        self.client
-            .post(self.endpoint(INSERT_OBJECTS_ENDPOINT))
-            .json(&body)
-            .send()
+        self.post(INSERT_OBJECTS_ENDPOINT, &body).await?;
+        Ok(())
+    }
+
+    /// Inserts a large amount of bodies by sending them in chunks of `chunk_size` bodies.
+    ///
+    /// Each chunk is sent only once the partitionner is ready to accept it. `on_progress`
+    /// is called after each chunk. Returns the insertion session, to be used with
+    /// [`Self::insert_progress`] to know when all the bodies reached their runners.
+    pub async fn insert_objects_chunked(
+        &self,
+        scene: SceneUuid,
+        bodies: Vec<BodyAssignment>,
+        chunk_size: usize,
+        mut on_progress: impl FnMut(InsertProgress),
+    ) -> anyhow::Result<Uuid> {
+        let body = BeginInsertRequest {
+            scene,
+            total: bodies.len(),
+        };
+        let response: BeginInsertResponse = self
+            .post(BEGIN_INSERT_ENDPOINT, &body)
+            .await?
+            .json()
             .await?;
+
+        for chunk in bodies.chunks(chunk_size.max(1)) {
+            let body = InsertChunkRequest {
+                session: response.session,
+                bodies: chunk.to_vec(),
+            };
+            // NOTE: the partitionner doesn’t reply until it has room for this chunk.
+            let progress: InsertProgress = self
+                .post(INSERT_CHUNK_ENDPOINT, &body)
+                .await?
+                .json()
+                .await?;
+            on_progress(progress);
+        }
+
+        Ok(response.session)
+    }
+
+    pub async fn insert_progress(&self, session: Uuid) -> anyhow::Result<InsertProgress> {
+        let body = InsertProgressRequest { session };
+        let raw_response = self.post(INSERT_PROGRESS_ENDPOINT, &body).await?;
+        Ok(raw_response.json().await?)
+    }
+
+    /// Downloads the current state of every body and joint of `scene`.
+    pub async fn export_scene(&self, scene: SceneUuid) -> anyhow::Result<SceneExport> {
+        let body = ExportSceneRequest { scene };
+        let raw_response = self.post(EXPORT_SCENE_ENDPOINT, &body).await?;
+        Ok(raw_response.json().await?)
+    }
+
+    /// The timings of the last step of every region of `scene`, slowest first.
+    pub async fn profile_scene(&self, scene: SceneUuid) -> anyhow::Result<SceneProfile> {
+        let body = ProfileSceneRequest { scene };
+        let raw_response = self.post(PROFILE_SCENE_ENDPOINT, &body).await?;
+        Ok(raw_response.json().await?)
+    }
+
+    /// The energy and momentum of `scene`, summed over its regions.
+    pub async fn scene_stats(&self, scene: SceneUuid) -> anyhow::Result<SceneStats> {
+        let body = SceneStatsRequest { scene };
+        let raw_response = self.post(SCENE_STATS_ENDPOINT, &body).await?;
+        Ok(raw_response.json().await?)
+    }
+
+    /// The step of `scene`, and its hot regions as last checked by the partitionner.
+    pub async fn scene_status(&self, scene: SceneUuid) -> anyhow::Result<SceneStatus> {
+        let body = SceneStatusRequest { scene };
+        let raw_response = self.post(SCENE_STATUS_ENDPOINT, &body).await?;
+        Ok(raw_response.json().await?)
+    }
+
+    /// All the client objects of `region`, including its sleeping bodies and the cold data of
+    /// every body.
+    pub async fn full_sync(
+        &self,
+        scene: SceneUuid,
+        region: SimulationBounds,
+    ) -> anyhow::Result<ClientBodyObjectSet> {
+        let body = FullSyncRequest { scene, region };
+        let raw_response = self.post(FULL_SYNC_ENDPOINT, &body).await?;
+        deserialize_client_objects(&raw_response.bytes().await?, &region)
+    }
+
+    /// Asks the partitionner to generate the bodies described by `templates` and insert
+    /// them into `scene`.
+    pub async fn spawn_templates(
+        &self,
+        scene: SceneUuid,
+        templates: Vec<SceneTemplate>,
+    ) -> anyhow::Result<()> {
+        let body = SpawnTemplatesRequest { scene, templates };
+        self.post(SPAWN_TEMPLATES_ENDPOINT, &body).await?;
         Ok(())
     }
 
     pub async fn list_regions(&self, scene: SceneUuid) -> anyhow::Result<RegionList> {
         let body = ListRegionsRequest { scene };
         let raw_response = self
-            .client
-            .get(self.endpoint(LIST_REGIONS_ENDPOINT))
-            .json(&body)
-            .send()
+            .send(LIST_REGIONS_ENDPOINT, |url| {
+                self.client.get(url).json(&body)
+            })
             .await?;
         Ok(raw_response.json().await?)
     }
 
     pub async fn list_scenes(&self) -> anyhow::Result<SceneList> {
-        let raw_response = self
-            .client
-            .get(self.endpoint(LIST_SCENES_ENDPOINT))
-            .send()
-            .await?;
+        let raw_response = self.get(LIST_SCENES_ENDPOINT).await?;
+        Ok(raw_response.json().await?)
+    }
+
+    /// Saves the current state of `scene` under `name` in the state store of the cluster.
+    pub async fn save_scene(
+        &self,
+        scene: SceneUuid,
+        name: String,
+    ) -> anyhow::Result<SceneSaveInfo> {
+        let body = SaveSceneRequest { scene, name };
+        let raw_response = self.post(SAVE_SCENE_ENDPOINT, &body).await?;
+        Ok(raw_response.json().await?)
+    }
+
+    /// Creates the new scene `scene` from the save `name`.
+    pub async fn load_save(
+        &self,
+        name: String,
+        scene: SceneUuid,
+    ) -> anyhow::Result<CreateSceneResponse> {
+        let body = LoadSaveRequest { name, scene };
+        let raw_response = self.post(LOAD_SAVE_ENDPOINT, &body).await?;
+        Ok(raw_response.json().await?)
+    }
+
+    pub async fn list_saves(&self) -> anyhow::Result<SceneSaveList> {
+        let raw_response = self.get(LIST_SAVES_ENDPOINT).await?;
         Ok(raw_response.json().await?)
     }
 
     pub async fn set_running(&self, scene: SceneUuid, running: bool) -> anyhow::Result<()> {
         let body = StartStopRequest { scene, running };
-        self.client
-            .post(self.endpoint(START_STOP_ENDPOINT))
-            .json(&body)
-            .send()
-            .await?;
+        self.post(START_STOP_ENDPOINT, &body).await?;
         Ok(())
     }
 
-    pub async fn register_child(&self, child: ChildPartitionner) -> anyhow::Result<()> {
-        let body = RegisterChildRequest { child };
-        self.client
-            .post(self.endpoint(REGISTER_CHILD_ENDPOINT))
-            .json(&body)
-            .send()
-            .await?;
+    /// Runs `steps` more steps of `scene`, e.g., to step through a paused simulation.
+    pub async fn step_n(&self, scene: SceneUuid, steps: u64) -> anyhow::Result<StepNResponse> {
+        let body = StepNRequest { scene, steps };
+        let raw_response = self.post(STEP_N_ENDPOINT, &body).await?;
+        Ok(raw_response.json().await?)
+    }
+
+    /// Finds the scene, region and runner currently simulating the dynamic body `body`.
+    ///
+    /// Fails if the partitionner doesn’t know that body.
+    pub async fn body_owner(&self, body: Uuid) -> anyhow::Result<BodyOwner> {
+        let body = BodyOwnerRequest { body };
+        let raw_response = self.post(BODY_OWNER_ENDPOINT, &body).await?;
+        Ok(raw_response.json().await?)
+    }
+
+    /// Sets the WASM module run next to the physics at each step of `scene`.
+    ///
+    /// See the `scripting` module of the runner for the functions available to the module.
+    pub async fn set_script(&self, scene: SceneUuid, module: Vec<u8>) -> anyhow::Result<()> {
+        let body = SetScriptRequest { scene, module };
+        self.post(SET_SCRIPT_ENDPOINT, &body).await?;
+        Ok(())
+    }
+
+    /// Slows down or speeds up the simulation of `scene`, see [`TimeScale`].
+    pub async fn set_time_scale(
+        &self,
+        scene: SceneUuid,
+        time_scale: TimeScale,
+    ) -> anyhow::Result<()> {
+        let body = SetTimeScaleRequest { scene, time_scale };
+        self.post(SET_TIME_SCALE_ENDPOINT, &body).await?;
+        Ok(())
+    }
+
+    /// Changes the solver options of `scene`, see [`SimulationParams`].
+    pub async fn set_simulation_params(
+        &self,
+        scene: SceneUuid,
+        params: SimulationParams,
+    ) -> anyhow::Result<()> {
+        let body = SetSimulationParamsRequest { scene, params };
+        self.post(SET_SIMULATION_PARAMS_ENDPOINT, &body).await?;
+        Ok(())
+    }
+
+    /// Freezes the bodies of `scene` inside `aabb`, or unfreezes `volume` if `aabb` is `None`,
+    /// see [`FreezeVolumeRequest`].
+    pub async fn freeze_volume(
+        &self,
+        scene: SceneUuid,
+        volume: Uuid,
+        aabb: Option<Aabb>,
+    ) -> anyhow::Result<()> {
+        let body = FreezeVolumeRequest {
+            scene,
+            volume,
+            aabb,
+        };
+        self.post(FREEZE_VOLUME_ENDPOINT, &body).await?;
+        Ok(())
+    }
+
+    /// Replaces the force generators of `scene`, see [`ForceGenerator`].
+    pub async fn set_force_generators(
+        &self,
+        scene: SceneUuid,
+        generators: Vec<ForceGenerator>,
+    ) -> anyhow::Result<()> {
+        let body = SetForceGeneratorsRequest { scene, generators };
+        self.post(SET_FORCE_GENERATORS_ENDPOINT, &body).await?;
+        Ok(())
+    }
+
+    /// Adds fluid particles to `scene`, see [`crate::fluids::FluidParams`].
+    pub async fn insert_particles(
+        &self,
+        scene: SceneUuid,
+        particles: Vec<FluidParticle>,
+    ) -> anyhow::Result<()> {
+        let body = InsertParticlesRequest { scene, particles };
+        self.post(INSERT_PARTICLES_ENDPOINT, &body).await?;
+        Ok(())
+    }
+
+    /// Adds vehicles to `scene`, see [`crate::vehicles::VehicleParams`].
+    pub async fn insert_vehicles(
+        &self,
+        scene: SceneUuid,
+        vehicles: Vec<VehicleAssignment>,
+    ) -> anyhow::Result<()> {
+        let body = InsertVehiclesRequest { scene, vehicles };
+        self.post(INSERT_VEHICLES_ENDPOINT, &body).await?;
+        Ok(())
+    }
+
+    /// Applies `input` to `body`, in whatever region simulates it.
+    pub async fn body_input(
+        &self,
+        scene: SceneUuid,
+        body: Uuid,
+        input: BodyInput,
+    ) -> anyhow::Result<()> {
+        let body = BodyInputRequest { scene, body, input };
+        self.post(BODY_INPUT_ENDPOINT, &body).await?;
+        Ok(())
+    }
+
+    /// Removes `bodies` from `scene`.
+    pub async fn remove_bodies(&self, scene: SceneUuid, bodies: Vec<Uuid>) -> anyhow::Result<()> {
+        let body = RemoveBodiesRequest { scene, bodies };
+        self.post(REMOVE_BODIES_ENDPOINT, &body).await?;
+        Ok(())
+    }
+
+    /// Starts, or stops if `traced` is `false`, recording the trajectories of `bodies`.
+    pub async fn trace_bodies(
+        &self,
+        scene: SceneUuid,
+        bodies: Vec<Uuid>,
+        traced: bool,
+    ) -> anyhow::Result<()> {
+        let body = TraceBodiesRequest {
+            scene,
+            bodies,
+            traced,
+        };
+        self.post(TRACE_BODIES_ENDPOINT, &body).await?;
+        Ok(())
+    }
+
+    /// The recorded trajectories of `bodies`, or of every traced body of `scene` if it is empty.
+    pub async fn trajectories(
+        &self,
+        scene: SceneUuid,
+        bodies: Vec<Uuid>,
+    ) -> anyhow::Result<Trajectories> {
+        let body = TrajectoriesRequest { scene, bodies };
+        let raw_response = self.post(TRAJECTORIES_ENDPOINT, &body).await?;
+        Ok(raw_response.json().await?)
+    }
+
+    /// Sends the scene metadata of the master to one of its children.
+    pub async fn replicate(&self, state: &ReplicatedState) -> anyhow::Result<()> {
+        self.post(REPLICATE_ENDPOINT, state).await?;
+        Ok(())
+    }
+
+    pub async fn register_child(
+        &self,
+        child: ChildPartitionner,
+        capabilities: NodeCapabilities,
+    ) -> anyhow::Result<()> {
+        let body = RegisterChildRequest {
+            child,
+            capabilities,
+        };
+        self.post(REGISTER_CHILD_ENDPOINT, &body).await?;
         Ok(())
     }
 
@@ -148,84 +733,282 @@ impl AsyncPartitionnerServer {
         &self,
         scene: SceneUuid,
         bounds: Aabb,
+        client_objects_encoding: ObjectSetEncoding,
+        seed: u64,
+        settings: SceneSettings,
     ) -> anyhow::Result<CreateSceneResponse> {
-        let body = CreateSceneRequest { scene, bounds };
-        let raw_response = self
-            .client
-            .post(self.endpoint(CREATE_SCENE_ENDPOINT))
-            .json(&body)
-            .send()
-            .await?;
+        settings.collision_matrix.validate()?;
+        let body = CreateSceneRequest {
+            scene,
+            bounds,
+            client_objects_encoding,
+            seed,
+            settings,
+        };
+        let raw_response = self.post(CREATE_SCENE_ENDPOINT, &body).await?;
         Ok(raw_response.json().await?)
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn create_scene_blocking(
         &self,
         scene: SceneUuid,
         bounds: Aabb,
+        client_objects_encoding: ObjectSetEncoding,
+        seed: u64,
+        settings: SceneSettings,
     ) -> anyhow::Result<CreateSceneResponse> {
         tokio::runtime::Builder::new_current_thread()
             .build()?
-            .block_on(self.create_scene(scene, bounds))
+            .block_on(self.create_scene(scene, bounds, client_objects_encoding, seed, settings))
     }
 
     pub async fn remove_scene(&self, scene: SceneUuid) -> anyhow::Result<()> {
         let body = RemoveSceneRequest { scene };
-        self.client
-            .post(self.endpoint(REMOVE_SCENE_ENDPOINT))
-            .json(&body)
-            .send()
-            .await?;
+        self.post(REMOVE_SCENE_ENDPOINT, &body).await?;
         Ok(())
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn remove_scene_blocking(&self, scene: SceneUuid) -> anyhow::Result<()> {
         tokio::runtime::Builder::new_current_thread()
             .build()?
             .block_on(self.remove_scene(scene))
     }
 
-    pub async fn client_input(&self, scene: SceneUuid, step_id: u64) -> anyhow::Result<()> {
+    pub async fn client_input(
+        &self,
+        scene: SceneUuid,
+        client: Uuid,
+        step_id: u64,
+    ) -> anyhow::Result<()> {
         let body = ClientInputRequest {
             scene,
+            client,
             step_id,
             input: 0,
         };
-        self.client
-            .post(self.endpoint(CLIENT_INPUT_ENDPOINT))
-            .json(&body)
-            .send()
-            .await?;
+        self.post(CLIENT_INPUT_ENDPOINT, &body).await?;
         Ok(())
     }
 
-    pub fn client_input_blocking(&self, scene: SceneUuid, step_id: u64) -> anyhow::Result<()> {
+    /// Sends several client inputs in a single request.
+    pub async fn client_inputs(&self, inputs: Vec<ClientInputRequest>) -> anyhow::Result<()> {
+        let body = ClientInputBatch { inputs };
+        self.post(CLIENT_INPUTS_ENDPOINT, &body).await?;
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn client_input_blocking(
+        &self,
+        scene: SceneUuid,
+        client: Uuid,
+        step_id: u64,
+    ) -> anyhow::Result<()> {
         tokio::runtime::Builder::new_current_thread()
             .build()?
-            .block_on(self.client_input(scene, step_id))
+            .block_on(self.client_input(scene, client, step_id))
     }
 
-    pub async fn ack(&self, scene: SceneUuid) -> anyhow::Result<()> {
-        let body = AckRequest { scene };
-        self.client
-            .post(self.endpoint(ACK_ENDPOINT))
-            .json(&body)
-            .send()
+    pub async fn ack(&self, scene: SceneUuid, step_id: u64, origin: Uuid) -> anyhow::Result<()> {
+        let body = AckRequest {
+            scene,
+            step_id,
+            origin,
+        };
+        let correlation = StepCorrelation::current(scene, step_id);
+        self.post_with_step(ACK_ENDPOINT, &body, Some(correlation))
             .await?;
         Ok(())
     }
 
     pub async fn step(&self, scene: SceneUuid, step_id: u64) -> anyhow::Result<()> {
         let body = StepRequest { scene, step_id };
-        self.client
-            .post(self.endpoint(STEP_ENDPOINT))
-            .json(&body)
-            .send()
+        let correlation = StepCorrelation::current(scene, step_id);
+        self.post_with_step(STEP_ENDPOINT, &body, Some(correlation))
             .await?;
         Ok(())
     }
 
+    /// The WebSocket url for streaming client objects, see [`StreamClientObjectsRequest`].
+    ///
+    /// [`StreamClientObjectsRequest`]: crate::partitionner::StreamClientObjectsRequest
+    pub fn client_objects_stream_url(&self) -> String {
+        self.endpoint(STREAM_CLIENT_OBJECTS_ENDPOINT)
+            .replacen("http", "ws", 1)
+    }
+
+    async fn get(&self, endpoint: &'static str) -> anyhow::Result<Response> {
+        self.send(endpoint, |url| self.client.get(url)).await
+    }
+
+    async fn post(
+        &self,
+        endpoint: &'static str,
+        body: &impl Serialize,
+    ) -> anyhow::Result<Response> {
+        self.post_with_step(endpoint, body, None).await
+    }
+
+    /// Like `post`, tagging the request with the [`STEP_HEADER`] of `correlation`.
+    async fn post_with_step(
+        &self,
+        endpoint: &'static str,
+        body: &impl Serialize,
+        correlation: Option<StepCorrelation>,
+    ) -> anyhow::Result<Response> {
+        let binary_body = if self.binary_bodies {
+            Some(serialize(body)?)
+        } else {
+            None
+        };
+
+        self.send(endpoint, |url| {
+            let mut request = self.client.post(url);
+            if let Some(correlation) = &correlation {
+                request = request.header(STEP_HEADER, correlation.header_value());
+            }
+            match &binary_body {
+                Some(binary_body) => request
+                    .header(CONTENT_TYPE, BINCODE_CONTENT_TYPE)
+                    .body(binary_body.clone()),
+                None => request.json(body),
+            }
+        })
+        .await
+    }
+
+    /// Sends the request built by `build` from the url of `endpoint`, failing over to the
+    /// next candidates while the partitionner is gone.
+    ///
+    /// The returned error is always a [`PartitionnerError`].
+    async fn send(
+        &self,
+        endpoint: &'static str,
+        build: impl Fn(String) -> RequestBuilder,
+    ) -> anyhow::Result<Response> {
+        let num_endpoints = 1 + self.candidates.read().unwrap().len();
+        let first = self.active.load(Ordering::SeqCst) % num_endpoints;
+        let mut last_error = None;
+
+        for i in 0..num_endpoints {
+            let id = (first + i) % num_endpoints;
+            let Some(base_url) = self.base_url(id) else {
+                continue;
+            };
+
+            let mut request = build(format!("{base_url}{endpoint}"));
+            if let Some(api_key) = &self.api_key {
+                request = request.header(API_KEY_HEADER, api_key);
+            }
+            if let Some(cluster_key) = &self.cluster_key {
+                request = request.header(CLUSTER_KEY_HEADER, cluster_key);
+            }
+            match self.send_to(&base_url, endpoint, request).await {
+                Ok(response) => {
+                    if id != first {
+                        log::warn!("Partitionner unreachable, failed over to {base_url}.");
+                        self.active.store(id, Ordering::SeqCst);
+                    }
+                    return Ok(response);
+                }
+                Err(e) if PartitionnerError::is_gone_error(&e) => last_error = Some(e),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| PartitionnerError::CircuitOpen.into()))
+    }
+
+    /// Sends `request` to `base_url`, retrying it according to the retry policy.
+    async fn send_to(
+        &self,
+        base_url: &str,
+        endpoint: &'static str,
+        request: RequestBuilder,
+    ) -> anyhow::Result<Response> {
+        let circuit_breaker = self
+            .circuit_breakers
+            .lock()
+            .unwrap()
+            .entry(base_url.to_string())
+            .or_default()
+            .clone();
+
+        if circuit_breaker.is_open() {
+            return Err(PartitionnerError::CircuitOpen.into());
+        }
+
+        // NOTE: requests can’t time out in the browser.
+        #[cfg(not(target_arch = "wasm32"))]
+        let request = request.timeout(self.timeout(endpoint));
+        let mut backoff = self.retry_policy.initial_backoff;
+        let mut attempt = 0;
+
+        loop {
+            let to_send = request
+                .try_clone()
+                .expect("Request bodies are never streamed.");
+            let result = match to_send.send().await {
+                Ok(response) => response.error_for_status(),
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(response) => {
+                    circuit_breaker.record_success();
+                    return Ok(response);
+                }
+                Err(e) => {
+                    let error = PartitionnerError::classify(e);
+                    // NOTE: unreachable errors happen before the request is delivered,
+                    //       so retrying is always safe. Other failures are only retried
+                    //       if applying the request twice is harmless.
+                    let can_retry = match &error {
+                        PartitionnerError::Unreachable(_) => true,
+                        PartitionnerError::Temporary(_) => is_idempotent(endpoint),
+                        _ => false,
+                    };
+
+                    if !can_retry || attempt >= self.retry_policy.max_retries {
+                        if !matches!(error, PartitionnerError::Rejected(_)) {
+                            circuit_breaker.record_failure(&self.retry_policy);
+                        }
+                        return Err(error.into());
+                    }
+
+                    log::debug!("Request to {endpoint} failed ({error}), retrying in {backoff:?}.");
+                    sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.retry_policy.max_backoff);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    fn timeout(&self, endpoint: &str) -> Duration {
+        self.timeouts
+            .get(endpoint)
+            .copied()
+            .unwrap_or(self.default_timeout)
+    }
+
     fn endpoint(&self, endpoint: &str) -> String {
-        format!("{}:{}{endpoint}", self.addr, self.port)
+        let id = self.active.load(Ordering::SeqCst);
+        let base_url = self.base_url(id).or_else(|| self.base_url(0)).unwrap();
+        format!("{base_url}{endpoint}")
+    }
+
+    /// The url of the endpoint `id`, numbered like `active`.
+    fn base_url(&self, id: usize) -> Option<String> {
+        if id == 0 {
+            Some(format!("{}:{}", self.addr, self.port))
+        } else {
+            let candidates = self.candidates.read().unwrap();
+            let (addr, port) = candidates.get(id - 1)?;
+            Some(format!("{addr}:{port}"))
+        }
     }
 }
@@ -1,4 +1,15 @@
+use crate::fluids::FluidParticle;
+#[cfg(feature = "pod-framing")]
+use crate::framing::{deserialize_framed, is_framed, serialize_framed, PodClientBodyObject};
+use crate::objects::{
+    ClientBodyObject, ClientBodyObjectSet, ClientColdBodyObject, ClientGhostBodyObject,
+    RegionClientBodyObjectSet, WatchedObjects,
+};
+use crate::simulation::SimulationBounds;
+use rapier::math::{AngVector, Isometry, Point, Real, Vector, DIM};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 pub fn serialize(value: &impl Serialize) -> anyhow::Result<Vec<u8>> {
     Ok(lz4_flex::compress_prepend_size(&bincode::serialize(value)?))
@@ -9,3 +20,432 @@ pub fn deserialize<Out: for<'a> Deserialize<'a>>(value: &[u8]) -> anyhow::Result
         value,
     )?)?)
 }
+
+#[cfg(not(target_arch = "wasm32"))]
+const ZSTD_LEVEL: i32 = 3;
+
+/// The compression codec of a payload, stored in its first byte.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Codec {
+    None = 0,
+    /// Fast, for payloads sent very frequently.
+    Lz4 = 1,
+    /// Slower but smaller, for large payloads that may cross a WAN.
+    Zstd = 2,
+}
+
+/// Serializes `value` and compresses it with `codec`, prepending a header byte
+/// identifying the codec so it can be read back with [`deserialize_auto`].
+pub fn serialize_compressed(value: &impl Serialize, codec: Codec) -> anyhow::Result<Vec<u8>> {
+    let raw = bincode::serialize(value)?;
+    let mut result = vec![codec as u8];
+
+    match codec {
+        Codec::None => result.extend_from_slice(&raw),
+        Codec::Lz4 => result.extend_from_slice(&lz4_flex::compress_prepend_size(&raw)),
+        #[cfg(not(target_arch = "wasm32"))]
+        Codec::Zstd => result.extend_from_slice(&zstd::stream::encode_all(&raw[..], ZSTD_LEVEL)?),
+        #[cfg(target_arch = "wasm32")]
+        Codec::Zstd => anyhow::bail!("The zstd codec isn’t supported on wasm32."),
+    }
+
+    Ok(result)
+}
+
+/// Deserializes a payload created by [`serialize_compressed`], whatever codec it used.
+pub fn deserialize_auto<Out: for<'a> Deserialize<'a>>(value: &[u8]) -> anyhow::Result<Out> {
+    let Some((header, payload)) = value.split_first() else {
+        anyhow::bail!("Cannot deserialize an empty payload.");
+    };
+
+    match *header {
+        h if h == Codec::None as u8 => Ok(bincode::deserialize(payload)?),
+        h if h == Codec::Lz4 as u8 => Ok(bincode::deserialize(
+            &lz4_flex::decompress_size_prepended(payload)?,
+        )?),
+        #[cfg(not(target_arch = "wasm32"))]
+        h if h == Codec::Zstd as u8 => {
+            Ok(bincode::deserialize(&zstd::stream::decode_all(payload)?)?)
+        }
+        #[cfg(target_arch = "wasm32")]
+        h if h == Codec::Zstd as u8 => anyhow::bail!("The zstd codec isn’t supported on wasm32."),
+        h => anyhow::bail!("Unknown payload codec: {h}."),
+    }
+}
+
+/// The wire format used for the client objects of a scene.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ObjectSetEncoding {
+    /// Positions are sent with full floating-point precision.
+    #[default]
+    Full,
+    /// Positions are sent as fixed-point values relative to their region, and rotations are packed.
+    Quantized,
+    /// The objects are sent as raw plain-old-data, framed by [`serialize_framed`], and read back
+    /// without deserializing them. Larger than [`Self::Quantized`], but the cheapest to encode
+    /// and decode.
+    #[cfg(feature = "pod-framing")]
+    Pod,
+}
+
+/// The encoding of the client objects the runners stream to the partitionners, which re-encode
+/// them for the viewers.
+#[cfg(feature = "pod-framing")]
+pub const STREAMED_OBJECT_SET_ENCODING: ObjectSetEncoding = ObjectSetEncoding::Pod;
+#[cfg(not(feature = "pod-framing"))]
+pub const STREAMED_OBJECT_SET_ENCODING: ObjectSetEncoding = ObjectSetEncoding::Full;
+
+/// Translations are quantized within this many region widths around the region center.
+const QUANTIZATION_RANGE: Real = 2.0;
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct QuantizedClientBodyObject {
+    pub uuid: Uuid,
+    pub translation: [i16; DIM],
+    #[cfg(feature = "dim2")]
+    pub rotation: i16,
+    #[cfg(feature = "dim3")]
+    pub rotation: u32,
+    pub linvel: Vector<Real>,
+    pub angvel: AngVector<Real>,
+    pub shape_hash: u64,
+    pub sleep_start_frame: Option<u64>,
+    #[serde(default)]
+    pub epoch: u32,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct QuantizedClientBodyObjectSet {
+    pub timestamp: u64,
+    pub objects: Vec<QuantizedClientBodyObject>,
+    /// Objects too far from their region to be quantized.
+    pub unquantized: Vec<ClientBodyObject>,
+    pub cold_objects: Vec<ClientColdBodyObject>,
+    pub ghosts: Vec<ClientGhostBodyObject>,
+    #[serde(default)]
+    pub partial: bool,
+}
+
+/// A [`ClientBodyObjectSet`] whose objects are framed as plain-old-data.
+#[cfg(feature = "pod-framing")]
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct PodClientBodyObjectSet {
+    pub timestamp: u64,
+    /// Not serialized, framed separately by [`serialize_framed`].
+    #[serde(skip)]
+    pub objects: Vec<PodClientBodyObject>,
+    pub cold_objects: Vec<ClientColdBodyObject>,
+    pub ghosts: Vec<ClientGhostBodyObject>,
+    pub partial: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum EncodedClientBodyObjectSet {
+    Full(ClientBodyObjectSet),
+    Quantized(QuantizedClientBodyObjectSet),
+    #[cfg(feature = "pod-framing")]
+    Pod(PodClientBodyObjectSet),
+}
+
+impl Default for EncodedClientBodyObjectSet {
+    fn default() -> Self {
+        Self::Full(ClientBodyObjectSet::default())
+    }
+}
+
+impl EncodedClientBodyObjectSet {
+    pub fn encode(
+        set: ClientBodyObjectSet,
+        region: &SimulationBounds,
+        encoding: ObjectSetEncoding,
+    ) -> Self {
+        match encoding {
+            ObjectSetEncoding::Full => Self::Full(set),
+            ObjectSetEncoding::Quantized => {
+                let (center, range) = quantization_frame(region);
+                let mut result = QuantizedClientBodyObjectSet {
+                    timestamp: set.timestamp,
+                    objects: Vec::with_capacity(set.objects.len()),
+                    unquantized: vec![],
+                    cold_objects: set.cold_objects,
+                    ghosts: set.ghosts,
+                    partial: set.partial,
+                };
+
+                for object in set.objects {
+                    let local = (object.position.translation.vector - center) / range;
+
+                    if local.iter().any(|x| x.abs() > 1.0) {
+                        result.unquantized.push(object);
+                        continue;
+                    }
+
+                    result.objects.push(QuantizedClientBodyObject {
+                        uuid: object.uuid,
+                        translation: std::array::from_fn(|i| {
+                            (local[i] * i16::MAX as Real).round() as i16
+                        }),
+                        rotation: pack_rotation(&object.position),
+                        linvel: object.linvel,
+                        angvel: object.angvel,
+                        shape_hash: object.shape_hash,
+                        sleep_start_frame: object.sleep_start_frame,
+                        epoch: object.epoch,
+                    });
+                }
+
+                Self::Quantized(result)
+            }
+            #[cfg(feature = "pod-framing")]
+            ObjectSetEncoding::Pod => Self::Pod(PodClientBodyObjectSet {
+                timestamp: set.timestamp,
+                objects: set.objects.iter().map(PodClientBodyObject::from).collect(),
+                cold_objects: set.cold_objects,
+                ghosts: set.ghosts,
+                partial: set.partial,
+            }),
+        }
+    }
+
+    pub fn decode(self, region: &SimulationBounds) -> ClientBodyObjectSet {
+        match self {
+            Self::Full(set) => set,
+            Self::Quantized(set) => {
+                let (center, range) = quantization_frame(region);
+                let mut objects = set.unquantized;
+                objects.extend(set.objects.into_iter().map(|object| {
+                    let local =
+                        Vector::from_fn(|i, _| object.translation[i] as Real / i16::MAX as Real);
+                    let mut position = unpack_rotation(object.rotation);
+                    position.translation.vector = center + local * range;
+
+                    ClientBodyObject {
+                        uuid: object.uuid,
+                        position,
+                        linvel: object.linvel,
+                        angvel: object.angvel,
+                        shape_hash: object.shape_hash,
+                        sleep_start_frame: object.sleep_start_frame,
+                        epoch: object.epoch,
+                    }
+                }));
+
+                ClientBodyObjectSet {
+                    timestamp: set.timestamp,
+                    objects,
+                    cold_objects: set.cold_objects,
+                    ghosts: set.ghosts,
+                    partial: set.partial,
+                }
+            }
+            #[cfg(feature = "pod-framing")]
+            Self::Pod(set) => ClientBodyObjectSet {
+                timestamp: set.timestamp,
+                objects: set.objects.iter().map(ClientBodyObject::from).collect(),
+                cold_objects: set.cold_objects,
+                ghosts: set.ghosts,
+                partial: set.partial,
+            },
+        }
+    }
+
+    /// Serializes `value`, which contains this set, framing the objects of this set as
+    /// plain-old-data if it is [`Self::Pod`].
+    fn serialize_within(&self, value: &impl Serialize) -> anyhow::Result<Vec<u8>> {
+        #[cfg(feature = "pod-framing")]
+        if let Self::Pod(set) = self {
+            return serialize_framed(&set.objects, value, Codec::Lz4);
+        }
+
+        serialize_compressed(value, Codec::Lz4)
+    }
+
+    /// Deserializes a payload of [`Self::serialize_within`], `set` giving the set contained by
+    /// its value.
+    #[cfg_attr(not(feature = "pod-framing"), allow(unused_variables))]
+    fn deserialize_within<Out: DeserializeOwned>(
+        value: &[u8],
+        set: impl FnOnce(&mut Out) -> &mut Self,
+    ) -> anyhow::Result<Out> {
+        #[cfg(feature = "pod-framing")]
+        if is_framed(value) {
+            let (objects, mut result) = deserialize_framed::<PodClientBodyObject, Out>(value)?;
+            match set(&mut result) {
+                Self::Pod(set) => set.objects = objects.into_owned(),
+                _ => anyhow::bail!("Framed objects outside of a plain-old-data object set."),
+            }
+            return Ok(result);
+        }
+
+        deserialize_auto(value)
+    }
+}
+
+pub fn serialize_client_objects(
+    set: ClientBodyObjectSet,
+    region: &SimulationBounds,
+    encoding: ObjectSetEncoding,
+) -> anyhow::Result<Vec<u8>> {
+    let encoded = EncodedClientBodyObjectSet::encode(set, region, encoding);
+    encoded.serialize_within(&encoded)
+}
+
+pub fn deserialize_client_objects(
+    value: &[u8],
+    region: &SimulationBounds,
+) -> anyhow::Result<ClientBodyObjectSet> {
+    Ok(EncodedClientBodyObjectSet::deserialize_within(value, |set| set)?.decode(region))
+}
+
+/// Serializes the client objects streamed by a region, whatever the encoding of its set.
+pub fn serialize_region_client_objects(
+    streamed: &RegionClientBodyObjectSet,
+) -> anyhow::Result<Vec<u8>> {
+    streamed.set.serialize_within(streamed)
+}
+
+/// Deserializes a payload of [`serialize_region_client_objects`].
+pub fn deserialize_region_client_objects(
+    value: &[u8],
+) -> anyhow::Result<RegionClientBodyObjectSet> {
+    EncodedClientBodyObjectSet::deserialize_within(
+        value,
+        |streamed: &mut RegionClientBodyObjectSet| &mut streamed.set,
+    )
+}
+
+/// Serializes a watch set, framing the data of its bodies besides their shapes as
+/// plain-old-data with the `pod-framing` feature.
+pub fn serialize_watched_objects(watched: &WatchedObjects) -> anyhow::Result<Vec<u8>> {
+    #[cfg(feature = "pod-framing")]
+    return crate::framing::serialize_watched_objects(watched);
+    #[cfg(not(feature = "pod-framing"))]
+    return serialize_compressed(watched, Codec::Lz4);
+}
+
+/// Deserializes a watch set serialized by [`serialize_watched_objects`].
+pub fn deserialize_watched_objects(value: &[u8]) -> anyhow::Result<WatchedObjects> {
+    #[cfg(feature = "pod-framing")]
+    if is_framed(value) {
+        return crate::framing::deserialize_watched_objects(value);
+    }
+
+    deserialize_auto(value)
+}
+
+/// The positions of the fluid particles of a region, streamed to the viewers.
+///
+/// Like the [`ObjectSetEncoding::Quantized`] objects, the positions are fixed-point values
+/// relative to the region. The velocities aren’t sent.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct QuantizedParticleSet {
+    pub timestamp: u64,
+    pub particle_radius: Real,
+    pub positions: Vec<[i16; DIM]>,
+    /// Particles too far from their region to be quantized.
+    pub unquantized: Vec<Point<Real>>,
+}
+
+impl QuantizedParticleSet {
+    pub fn encode(
+        particles: &[FluidParticle],
+        particle_radius: Real,
+        region: &SimulationBounds,
+        timestamp: u64,
+    ) -> Self {
+        let (center, range) = quantization_frame(region);
+        let mut result = Self {
+            timestamp,
+            particle_radius,
+            positions: Vec::with_capacity(particles.len()),
+            unquantized: vec![],
+        };
+
+        for particle in particles {
+            let local = (particle.position.coords - center) / range;
+
+            if local.iter().any(|x| x.abs() > 1.0) {
+                result.unquantized.push(particle.position);
+            } else {
+                result.positions.push(std::array::from_fn(|i| {
+                    (local[i] * i16::MAX as Real).round() as i16
+                }));
+            }
+        }
+
+        result
+    }
+
+    pub fn decode(&self, region: &SimulationBounds) -> Vec<Point<Real>> {
+        let (center, range) = quantization_frame(region);
+        let mut positions = self.unquantized.clone();
+        positions.extend(self.positions.iter().map(|position| {
+            let local = Vector::from_fn(|i, _| position[i] as Real / i16::MAX as Real);
+            Point::from(center + local * range)
+        }));
+        positions
+    }
+}
+
+fn quantization_frame(region: &SimulationBounds) -> (Vector<Real>, Real) {
+    let aabb = region.aabb();
+    (
+        aabb.center().coords,
+        aabb.extents().max() * QUANTIZATION_RANGE,
+    )
+}
+
+#[cfg(feature = "dim2")]
+fn pack_rotation(pos: &Isometry<Real>) -> i16 {
+    (pos.rotation.angle() / std::f32::consts::PI as Real * i16::MAX as Real).round() as i16
+}
+
+#[cfg(feature = "dim2")]
+fn unpack_rotation(rotation: i16) -> Isometry<Real> {
+    Isometry::rotation(rotation as Real / i16::MAX as Real * std::f32::consts::PI as Real)
+}
+
+/// Packs a rotation with the “smallest three” scheme: the index of the largest quaternion
+/// component on 2 bits, followed by the three other components on 10 bits each.
+#[cfg(feature = "dim3")]
+fn pack_rotation(pos: &Isometry<Real>) -> u32 {
+    const MAX: Real = 1023.0;
+    let mut coords = pos.rotation.coords;
+    let largest = coords.iamax();
+
+    if coords[largest] < 0.0 {
+        coords = -coords;
+    }
+
+    let mut packed = (largest as u32) << 30;
+    let mut shift = 20;
+    for i in (0..4).filter(|i| *i != largest) {
+        let normalized = (coords[i] / std::f32::consts::FRAC_1_SQRT_2 as Real) * 0.5 + 0.5;
+        packed |= ((normalized.clamp(0.0, 1.0) * MAX).round() as u32) << shift;
+        shift -= 10;
+    }
+
+    packed
+}
+
+#[cfg(feature = "dim3")]
+fn unpack_rotation(rotation: u32) -> Isometry<Real> {
+    use rapier::na::{Quaternion, UnitQuaternion, Vector4};
+    const MAX: Real = 1023.0;
+
+    let largest = (rotation >> 30) as usize;
+    let mut coords = Vector4::zeros();
+    let mut shift = 20;
+    for i in (0..4).filter(|i| *i != largest) {
+        let normalized = ((rotation >> shift) & 0x3ff) as Real / MAX;
+        coords[i] = (normalized - 0.5) * 2.0 * std::f32::consts::FRAC_1_SQRT_2 as Real;
+        shift -= 10;
+    }
+    coords[largest] = (1.0 - coords.norm_squared()).max(0.0).sqrt();
+
+    Isometry::from_parts(
+        Vector::zeros().into(),
+        UnitQuaternion::new_normalize(Quaternion::from(coords)),
+    )
+}
@@ -0,0 +1,36 @@
+//! Ed25519 signatures of the executables served to the updaters.
+//!
+//! Keys and signatures are hex-encoded: the secret key is only needed by whoever builds the
+//! releases, the updaters only know the public key.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+
+/// A new `(secret key, public key)` pair.
+pub fn generate_key() -> (String, String) {
+    let key = SigningKey::generate(&mut rand::rngs::OsRng);
+    (
+        hex::encode(key.to_bytes()),
+        hex::encode(key.verifying_key().to_bytes()),
+    )
+}
+
+/// The signature of `message` with the secret key `secret_key`.
+pub fn sign(secret_key: &str, message: &[u8]) -> anyhow::Result<String> {
+    let key = SigningKey::from_bytes(&decode_array(secret_key, "secret key")?);
+    Ok(hex::encode(key.sign(message).to_bytes()))
+}
+
+/// Checks that `signature` is the signature of `message` by the owner of `public_key`.
+pub fn verify(public_key: &str, message: &[u8], signature: &str) -> anyhow::Result<()> {
+    let key = VerifyingKey::from_bytes(&decode_array(public_key, "public key")?)?;
+    let signature = Signature::from_bytes(&decode_array(signature, "signature")?);
+    key.verify(message, &signature)?;
+    Ok(())
+}
+
+fn decode_array<const N: usize>(hex_value: &str, what: &str) -> anyhow::Result<[u8; N]> {
+    let bytes = hex::decode(hex_value.trim())?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("The {what} must be {N} hex-encoded bytes."))
+}
@@ -1,7 +1,6 @@
 use crate::array_ser;
 use crate::partitionner::SceneUuid;
-use crate::zenoh::zenoh_storage_key;
-use rapier::geometry::Aabb;
+use rapier::geometry::{Aabb, SharedShape};
 use rapier::math::{Point, Real, DIM};
 use rapier::na::vector;
 use rapier::parry::bounding_volume::BoundingVolume;
@@ -222,6 +221,23 @@ impl SimulationBounds {
         )
     }
 
+    #[cfg(feature = "dim2")]
+    pub fn from_str(str: &str) -> Option<Self> {
+        use std::str::FromStr;
+
+        let mut elts = str.split('_');
+        let mins = [
+            i64::from_str(elts.next()?).ok()?,
+            i64::from_str(elts.next()?).ok()?,
+        ];
+        elts.next()?;
+        let maxs = [
+            i64::from_str(elts.next()?).ok()?,
+            i64::from_str(elts.next()?).ok()?,
+        ];
+        Some(Self { mins, maxs })
+    }
+
     #[cfg(feature = "dim3")]
     pub fn from_str(str: &str) -> Option<Self> {
         use std::str::FromStr;
@@ -245,21 +261,64 @@ impl SimulationBounds {
         format!("runner/{:?}/{}", scene.0, self.to_string())
     }
 
-    pub fn watch_kvs_key(&self, node: Uuid) -> String {
-        format!("steadyum/watch/{:?}?{}", node, self.to_string())
+    /// The key to query the watch set of this region from the runner `node`.
+    ///
+    /// If `known_version` is set and the watch set didn’t change since this version, the reply
+    /// doesn’t contain its bodies.
+    pub fn watch_kvs_key(
+        &self,
+        scene: SceneUuid,
+        node: Uuid,
+        known_version: Option<u64>,
+    ) -> String {
+        let key = format!(
+            "steadyum/watch/{:?}?{:?}&{}",
+            node,
+            scene.0,
+            self.to_string()
+        );
+        match known_version {
+            Some(version) => format!("{key}&{version}"),
+            None => key,
+        }
     }
 
     pub fn runner_key(&self, scene: SceneUuid) -> String {
         self.zenoh_queue_key(scene)
     }
 
-    pub fn runner_client_objects_key(&self, scene: SceneUuid, step_id: u64) -> String {
-        format!(
+    /// The key of the client objects of this region that changed since the step `step_id`.
+    ///
+    /// If `view` is set, only the bodies with their center inside it are replied.
+    pub fn runner_client_objects_key(
+        &self,
+        scene: SceneUuid,
+        step_id: u64,
+        view: Option<&Aabb>,
+    ) -> String {
+        let key = format!(
             "steadyum/client_bodies/{:?}?{}&{}",
             scene.0,
             self.to_string(),
             step_id
-        )
+        );
+        with_view_param(key, view)
+    }
+
+    /// The key of the complete current client objects of this region, regardless of the sleep
+    /// state of its bodies, for the clients that just discovered it.
+    pub fn runner_full_client_objects_key(&self, scene: SceneUuid, view: Option<&Aabb>) -> String {
+        let key = format!(
+            "steadyum/client_bodies/{:?}?{}&0&full",
+            scene.0,
+            self.to_string()
+        );
+        with_view_param(key, view)
+    }
+
+    /// The key of the fluid particles of this region, as last streamed to the clients.
+    pub fn runner_particles_key(&self, scene: SceneUuid) -> String {
+        format!("steadyum/particles/{:?}?{}", scene.0, self.to_string())
     }
 
     #[cfg(feature = "dim2")]
@@ -338,6 +397,37 @@ impl SimulationBounds {
         result
     }
 
+    #[cfg(feature = "dim2")]
+    pub fn all_neighbors(&self) -> [Self; 8] {
+        let mut result = [*self; 8];
+        let mut curr = 0;
+
+        for i in -1..=1 {
+            for j in -1..=1 {
+                if i == 0 && j == 0 {
+                    continue; // Exclude self.
+                }
+
+                let width = [
+                    (self.maxs[0] - self.mins[0]) * i,
+                    (self.maxs[1] - self.mins[1]) * j,
+                ];
+
+                let adj_region = Self {
+                    mins: [self.mins[0] + width[0], self.mins[1] + width[1]],
+                    maxs: [self.maxs[0] + width[0], self.maxs[1] + width[1]],
+                };
+
+                result[curr] = adj_region;
+                curr += 1;
+            }
+        }
+
+        assert_eq!(curr, 8);
+
+        result
+    }
+
     #[cfg(feature = "dim3")]
     pub fn all_neighbors(&self) -> [Self; 26] {
         let mut result = [*self; 26];
@@ -380,3 +470,91 @@ impl SimulationBounds {
         result
     }
 }
+
+fn with_view_param(key: String, view: Option<&Aabb>) -> String {
+    match view {
+        Some(view) => format!("{key}&view={}", aabb_param(view)),
+        None => key,
+    }
+}
+
+/// Formats `aabb` for the parameters of a query key, see [`parse_aabb_param`].
+pub fn aabb_param(aabb: &Aabb) -> String {
+    aabb.mins
+        .iter()
+        .chain(aabb.maxs.iter())
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Parses an AABB formatted by [`aabb_param`].
+pub fn parse_aabb_param(param: &str) -> Option<Aabb> {
+    let coords: Vec<Real> = param
+        .split('_')
+        .map(|x| x.parse().ok())
+        .collect::<Option<_>>()?;
+    if coords.len() != 2 * DIM {
+        return None;
+    }
+
+    Some(Aabb::new(
+        Point::from_slice(&coords[..DIM]),
+        Point::from_slice(&coords[DIM..]),
+    ))
+}
+
+/// How far around its bodies a region looks for bodies of the neighbor regions.
+///
+/// Larger margins let fast bodies collide across region borders, at the cost of larger watch
+/// sets. The default margins only cover the motion of the bodies during the next step.
+#[derive(Copy, Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct WatchMargins {
+    /// Distance added around every body.
+    pub absolute: Real,
+    /// Time, in seconds, the velocity of a body is scaled by to get the distance added around it.
+    pub velocity_scaled: Real,
+}
+
+impl WatchMargins {
+    /// The radius of the ball, centered on a body with the given shape, that detects the
+    /// bodies close to it.
+    pub fn watch_radius(&self, shape: &SharedShape) -> Real {
+        shape.compute_local_bounding_sphere().radius * 1.1 + self.absolute
+    }
+}
+
+/// The neighbor regions a region watches.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum WatchMode {
+    /// Only the next region along each axis. Cheap, but bodies meeting across the corner of
+    /// a region, or coming from a smaller region, may only collide one step late.
+    #[default]
+    Greater,
+    /// All the adjacent regions, diagonals included. Contacts across region borders are never
+    /// missed, at the cost of larger watch traffic.
+    All,
+}
+
+/// When a body leaving a region for a smaller one is actually sent to it.
+///
+/// Bodies resting on the border of a smaller region would otherwise move back and forth
+/// between both regions. Longer delays and distances avoid this ping-pong, at the cost of
+/// bodies staying longer in a region they left, e.g., on scenes with very small bodies.
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MigrationHysteresis {
+    /// Number of region assignments a body must spend in the smaller region before it moves.
+    pub delay: u32,
+    /// Distance the maxs of the AABB of a body, which give its region, must be past the
+    /// border before it moves.
+    pub border_distance: Real,
+}
+
+impl Default for MigrationHysteresis {
+    fn default() -> Self {
+        Self {
+            delay: 50,
+            border_distance: 0.0,
+        }
+    }
+}
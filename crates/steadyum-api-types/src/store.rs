@@ -0,0 +1,244 @@
+use crate::env::{Config, StateStoreKind};
+use crate::partitionner::SceneUuid;
+use crate::serialization::{deserialize_auto, serialize_compressed, Codec};
+use crate::simulation::SimulationBounds;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Key of the scene metadata of the master partitionner.
+pub const SCENE_METADATA_KEY: &str = "partitionner/metadata";
+
+/// Key of the [`SceneSaveList`](crate::objects::SceneSaveList) of all the named scene saves.
+pub const SCENE_SAVES_KEY: &str = "saves";
+
+/// Key of the named scene save `name`.
+pub fn scene_save_key(name: &str) -> String {
+    format!("saves/{name}")
+}
+
+/// Key of the scenes, runners and region assignments of the partitionner `node`, persisted so
+/// it re-adopts its runners after a restart.
+pub fn partitionner_runners_key(node: &str) -> String {
+    format!("partitionner/runners/{node}")
+}
+
+/// Key of the last watch set computed by `region`.
+pub fn watch_set_key(scene: SceneUuid, region: &SimulationBounds) -> String {
+    format!("{:?}/watch/{}", scene.0, region.to_string())
+}
+
+/// Key of the last client objects computed by `region`.
+pub fn client_objects_key(scene: SceneUuid, region: &SimulationBounds) -> String {
+    format!("{:?}/client_objects/{}", scene.0, region.to_string())
+}
+
+/// Key of the last fluid particles streamed by `region`, see
+/// [`crate::serialization::QuantizedParticleSet`].
+pub fn particles_key(scene: SceneUuid, region: &SimulationBounds) -> String {
+    format!("{:?}/particles/{}", scene.0, region.to_string())
+}
+
+/// Prefix of all the keys of `scene`.
+pub fn scene_prefix(scene: SceneUuid) -> String {
+    format!("{:?}/", scene.0)
+}
+
+/// A key-value store for the state shared by the runners and partitionners.
+///
+/// The remote backends are only meant for deployments where the state must outlive the
+/// processes, the in-memory one is the fastest.
+#[async_trait]
+pub trait StateStore: Send + Sync {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+    async fn put(&self, key: &str, value: Vec<u8>) -> anyhow::Result<()>;
+    async fn remove(&self, key: &str) -> anyhow::Result<()>;
+    /// Removes all the keys starting with `prefix`.
+    async fn remove_prefix(&self, prefix: &str) -> anyhow::Result<()>;
+
+    /// Is this store local to the process?
+    ///
+    /// The processes keep the state they read often in their own typed maps, and only write
+    /// it to the store if it isn’t local.
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+impl dyn StateStore {
+    pub async fn get_value<T: for<'a> Deserialize<'a>>(
+        &self,
+        key: &str,
+    ) -> anyhow::Result<Option<T>> {
+        self.get(key)
+            .await?
+            .map(|bytes| deserialize_auto(&bytes))
+            .transpose()
+    }
+
+    pub async fn put_value(
+        &self,
+        key: &str,
+        value: &(impl Serialize + Sync),
+    ) -> anyhow::Result<()> {
+        self.put(key, serialize_compressed(value, Codec::Lz4)?)
+            .await
+    }
+}
+
+/// Opens the store selected by `config`.
+pub async fn open_state_store(config: &Config) -> anyhow::Result<Arc<dyn StateStore>> {
+    match config.state_store {
+        StateStoreKind::Memory => Ok(Arc::new(MemoryStore::default())),
+        #[cfg(feature = "redis")]
+        StateStoreKind::Redis => Ok(Arc::new(RedisStore::new(&config.redis_addr).await?)),
+        #[cfg(feature = "s3")]
+        StateStoreKind::S3 => Ok(Arc::new(S3Store::new(
+            &config.s3_bucket,
+            &config.s3_region,
+            &config.s3_endpoint,
+        )?)),
+        #[allow(unreachable_patterns)]
+        kind => anyhow::bail!("The {kind:?} state store isn’t enabled in this build."),
+    }
+}
+
+/// A store local to the process.
+#[derive(Default)]
+pub struct MemoryStore {
+    values: RwLock<HashMap<String, Vec<u8>>>,
+}
+
+// NOTE: the lock is never held across an `.await`, so the std one is fine.
+#[async_trait]
+impl StateStore for MemoryStore {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self.values.read().unwrap().get(key).cloned())
+    }
+
+    async fn put(&self, key: &str, value: Vec<u8>) -> anyhow::Result<()> {
+        self.values.write().unwrap().insert(key.to_string(), value);
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> anyhow::Result<()> {
+        self.values.write().unwrap().remove(key);
+        Ok(())
+    }
+
+    async fn remove_prefix(&self, prefix: &str) -> anyhow::Result<()> {
+        self.values
+            .write()
+            .unwrap()
+            .retain(|key, _| !key.starts_with(prefix));
+        Ok(())
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+/// A store backed by a Redis server.
+#[cfg(feature = "redis")]
+pub struct RedisStore {
+    // NOTE: the multiplexed connection is cheap to clone, and the clones share the same socket.
+    connection: redis::aio::MultiplexedConnection,
+}
+
+#[cfg(feature = "redis")]
+impl RedisStore {
+    pub async fn new(addr: &str) -> anyhow::Result<Self> {
+        let connection = redis::Client::open(addr)?
+            .get_multiplexed_tokio_connection()
+            .await?;
+        Ok(Self { connection })
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl StateStore for RedisStore {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        use redis::AsyncCommands;
+        Ok(self.connection.clone().get(key).await?)
+    }
+
+    async fn put(&self, key: &str, value: Vec<u8>) -> anyhow::Result<()> {
+        use redis::AsyncCommands;
+        Ok(self.connection.clone().set(key, value).await?)
+    }
+
+    async fn remove(&self, key: &str) -> anyhow::Result<()> {
+        use redis::AsyncCommands;
+        Ok(self.connection.clone().del(key).await?)
+    }
+
+    async fn remove_prefix(&self, prefix: &str) -> anyhow::Result<()> {
+        use redis::AsyncCommands;
+        let mut connection = self.connection.clone();
+        let mut keys: Vec<String> = vec![];
+        {
+            let mut iter = connection.scan_match(format!("{prefix}*")).await?;
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+        }
+        if !keys.is_empty() {
+            connection.del::<_, ()>(keys).await?;
+        }
+        Ok(())
+    }
+}
+
+/// A store backed by an S3-compatible object storage.
+#[cfg(feature = "s3")]
+pub struct S3Store {
+    bucket: s3::Bucket,
+}
+
+#[cfg(feature = "s3")]
+impl S3Store {
+    pub fn new(bucket: &str, region: &str, endpoint: &str) -> anyhow::Result<Self> {
+        let region = s3::Region::Custom {
+            region: region.to_string(),
+            endpoint: endpoint.to_string(),
+        };
+        let credentials = s3::creds::Credentials::default()?;
+        let bucket = s3::Bucket::new(bucket, region, credentials)?.with_path_style();
+        Ok(Self { bucket })
+    }
+}
+
+#[cfg(feature = "s3")]
+#[async_trait]
+impl StateStore for S3Store {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let response = self.bucket.get_object(key).await?;
+        match response.status_code() {
+            404 => Ok(None),
+            200 => Ok(Some(response.bytes().to_vec())),
+            code => anyhow::bail!("Failed to get {key} from S3: status {code}."),
+        }
+    }
+
+    async fn put(&self, key: &str, value: Vec<u8>) -> anyhow::Result<()> {
+        self.bucket.put_object(key, &value).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> anyhow::Result<()> {
+        self.bucket.delete_object(key).await?;
+        Ok(())
+    }
+
+    async fn remove_prefix(&self, prefix: &str) -> anyhow::Result<()> {
+        for page in self.bucket.list(prefix.to_string(), None).await? {
+            for object in page.contents {
+                self.bucket.delete_object(&object.key).await?;
+            }
+        }
+        Ok(())
+    }
+}
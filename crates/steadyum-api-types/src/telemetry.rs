@@ -0,0 +1,183 @@
+//! Structured tracing, and the correlation of a simulation step across the cluster.
+//!
+//! A step goes from the master partitionner to its children, then to the runners and their
+//! region threads. Each of them opens its spans in the trace given by
+//! [`StepCorrelation::trace_id`], derived from the scene and the step id, and parents them
+//! to the span of the process that sent the step. That span travels in the [`STEP_HEADER`]
+//! of the HTTP requests, and in the [`Traced`] envelopes of the zenoh messages.
+
+use crate::partitionner::SceneUuid;
+use serde::{Deserialize, Serialize};
+use tracing::Span;
+use uuid::Uuid;
+
+/// The HTTP header identifying the step a request belongs to, see
+/// [`StepCorrelation::header_value`].
+pub const STEP_HEADER: &str = "x-steadyum-step";
+
+/// Identifies a step of a scene, and the span that triggered its handling.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepCorrelation {
+    pub scene: SceneUuid,
+    pub step_id: u64,
+    /// The OpenTelemetry id of the span of the sender, zero if it isn’t exported.
+    pub parent_span: u64,
+}
+
+impl StepCorrelation {
+    /// The correlation of a step nobody sent us, e.g. started by the master partitionner.
+    pub fn root(scene: SceneUuid, step_id: u64) -> Self {
+        Self {
+            scene,
+            step_id,
+            parent_span: 0,
+        }
+    }
+
+    /// The correlation of a step sent from the current span.
+    pub fn current(scene: SceneUuid, step_id: u64) -> Self {
+        Self {
+            scene,
+            step_id,
+            parent_span: current_span_id(),
+        }
+    }
+
+    /// The id of the trace gathering all the spans of this step, whatever their process.
+    pub fn trace_id(&self) -> u128 {
+        (self.scene.0.as_u128() ^ self.step_id as u128).max(1)
+    }
+
+    /// The value of the [`STEP_HEADER`]: `scene/step_id/parent_span`.
+    pub fn header_value(&self) -> String {
+        format!(
+            "{}/{}/{:016x}",
+            self.scene.0, self.step_id, self.parent_span
+        )
+    }
+
+    /// Parses a value of the [`STEP_HEADER`].
+    pub fn parse_header(value: &str) -> Option<Self> {
+        let mut parts = value.splitn(3, '/');
+        let scene = Uuid::parse_str(parts.next()?).ok()?;
+        let step_id = parts.next()?.parse().ok()?;
+        let parent_span = u64::from_str_radix(parts.next()?, 16).ok()?;
+        Some(Self {
+            scene: SceneUuid(scene),
+            step_id,
+            parent_span,
+        })
+    }
+
+    /// Opens a span named `name` for handling this step.
+    ///
+    /// Without a parent, the span is attached to a virtual root span derived from the step id,
+    /// so that it still ends up in the trace of the step.
+    pub fn span(&self, name: &'static str) -> Span {
+        let span = tracing::info_span!(
+            "step",
+            otel.name = name,
+            scene = %self.scene.0,
+            step_id = self.step_id
+        );
+
+        #[cfg(feature = "telemetry")]
+        {
+            use opentelemetry::trace::{
+                SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+            };
+            use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+            let parent_span = if self.parent_span == 0 {
+                self.step_id | (1 << 63)
+            } else {
+                self.parent_span
+            };
+            let parent = SpanContext::new(
+                TraceId::from_bytes(self.trace_id().to_be_bytes()),
+                SpanId::from_bytes(parent_span.to_be_bytes()),
+                TraceFlags::SAMPLED,
+                true,
+                TraceState::default(),
+            );
+            span.set_parent(opentelemetry::Context::new().with_remote_span_context(parent));
+        }
+
+        span
+    }
+}
+
+/// A message published on zenoh, tagged with the step it belongs to, if any.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Traced<T> {
+    pub correlation: Option<StepCorrelation>,
+    pub message: T,
+}
+
+impl<T> Traced<T> {
+    pub fn new(correlation: Option<StepCorrelation>, message: T) -> Self {
+        Self {
+            correlation,
+            message,
+        }
+    }
+}
+
+#[cfg(feature = "telemetry")]
+fn current_span_id() -> u64 {
+    use opentelemetry::trace::TraceContextExt;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let context = Span::current().context();
+    let span_id = context.span().span_context().span_id();
+    u64::from_be_bytes(span_id.to_bytes())
+}
+
+#[cfg(not(feature = "telemetry"))]
+fn current_span_id() -> u64 {
+    0
+}
+
+/// Installs the tracing subscriber of the process, filtered by `RUST_LOG`.
+///
+/// The events of the `log` crate are forwarded to it. If `OTLP_ENDPOINT` is set, the spans
+/// are also exported there, as coming from the service `service_name`.
+#[cfg(feature = "telemetry")]
+pub fn init(service_name: &'static str) -> anyhow::Result<()> {
+    use crate::env::CONFIG;
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::Resource;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+    use tracing_subscriber::EnvFilter;
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let otlp = if CONFIG.otlp_endpoint.is_empty() {
+        None
+    } else {
+        let exporter = opentelemetry_otlp::new_exporter()
+            .tonic()
+            .with_endpoint(CONFIG.otlp_endpoint.clone());
+        let resource = Resource::new([KeyValue::new("service.name", service_name)]);
+        let tracer = opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(exporter)
+            .with_trace_config(opentelemetry_sdk::trace::config().with_resource(resource))
+            .install_batch(opentelemetry_sdk::runtime::TokioCurrentThread)?;
+        Some(tracing_opentelemetry::layer().with_tracer(tracer))
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(otlp)
+        .try_init()?;
+    Ok(())
+}
+
+/// Exports the spans that weren’t sent yet. Must be called before the process exits.
+#[cfg(feature = "telemetry")]
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
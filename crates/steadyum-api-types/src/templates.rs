@@ -0,0 +1,188 @@
+use crate::kinematic::KinematicAnimations;
+use crate::messages::BodyAssignment;
+use crate::objects::{ColdBodyObject, WarmBodyObject};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rapier::dynamics::RigidBodyType;
+use rapier::geometry::{Aabb, Cuboid, SharedShape};
+use rapier::math::{Isometry, Point, Real, Rotation, Vector};
+use uuid::Uuid;
+
+/// Description of a set of bodies generated by the partitionner itself, so clients
+/// don’t have to upload every single body.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub enum SceneTemplate {
+    /// A fixed cuboid covering `aabb`.
+    Ground { aabb: Aabb },
+    /// A grid of pyramids made of cubes.
+    Pyramids {
+        origin: Point<Real>,
+        /// Number of pyramids along the `x` (and `z` in 3D) axes.
+        grid: [u32; 2],
+        /// Distance between the origins of two adjacent pyramids.
+        spacing: Real,
+        /// Number of cubes along each side of a pyramid base.
+        base_size: u32,
+        cube_half_extent: Real,
+    },
+    /// Dynamic boxes with random positions inside of `aabb`.
+    ///
+    /// Their positions and sizes only depend on `seed`, not on the seed of the scene.
+    RandomBoxes {
+        aabb: Aabb,
+        count: u32,
+        min_half_extent: Real,
+        max_half_extent: Real,
+        seed: u64,
+    },
+}
+
+impl SceneTemplate {
+    /// The number of bodies [`Self::bodies`] will output, or `None` if it doesn’t fit in a
+    /// `usize`.
+    pub fn num_bodies(&self) -> Option<usize> {
+        match self {
+            Self::Ground { .. } => Some(1),
+            Self::Pyramids {
+                grid, base_size, ..
+            } => {
+                // 1 + 2 + … + n cubes in 2D, 1² + 2² + … + n² in 3D.
+                let n = *base_size as u64;
+                let per_pyramid = if cfg!(feature = "dim2") {
+                    n.checked_mul(n + 1)? / 2
+                } else {
+                    n.checked_mul(n + 1)?.checked_mul(2 * n + 1)? / 6
+                };
+                let num_pyramids = (grid[0] as u64).checked_mul(grid[1] as u64)?;
+                usize::try_from(num_pyramids.checked_mul(per_pyramid)?).ok()
+            }
+            Self::RandomBoxes { count, .. } => usize::try_from(*count).ok(),
+        }
+    }
+
+    /// The bodies of this template, with uuids drawn from `rng`.
+    ///
+    /// They are generated lazily, so the large templates can be inserted chunk by chunk.
+    pub fn bodies(&self, mut rng: StdRng) -> Box<dyn Iterator<Item = BodyAssignment> + Send> {
+        match *self {
+            Self::Ground { aabb } => {
+                let shape = cuboid(aabb.half_extents());
+                Box::new(std::iter::once(body(
+                    &mut rng,
+                    RigidBodyType::Fixed,
+                    shape,
+                    aabb.center(),
+                )))
+            }
+            Self::Pyramids {
+                origin,
+                grid,
+                spacing,
+                base_size,
+                cube_half_extent,
+            } => {
+                // Don’t go through every empty pyramid of the grid.
+                if base_size == 0 {
+                    return Box::new(std::iter::empty());
+                }
+
+                let shape = cuboid(Vector::repeat(cube_half_extent));
+                let positions = (0..grid[0])
+                    .flat_map(move |gx| (0..grid[1]).map(move |gz| [gx, gz]))
+                    .flat_map(move |id| {
+                        let pyramid_origin = pyramid_origin(origin, grid, id, spacing);
+                        pyramid_cubes(pyramid_origin, base_size, cube_half_extent)
+                    });
+                let bodies = positions
+                    .map(move |pos| body(&mut rng, RigidBodyType::Dynamic, shape.clone(), pos));
+                Box::new(bodies)
+            }
+            Self::RandomBoxes {
+                aabb,
+                count,
+                min_half_extent,
+                max_half_extent,
+                seed,
+            } => {
+                let mut boxes_rng = StdRng::seed_from_u64(seed);
+                let max_half_extent = max_half_extent.max(min_half_extent);
+
+                Box::new((0..count).map(move |_| {
+                    let half_extents = Vector::from_fn(|_, _| {
+                        boxes_rng.gen_range(min_half_extent..=max_half_extent)
+                    });
+                    let pos = Point::from(Vector::from_fn(|i, _| {
+                        boxes_rng.gen_range(aabb.mins[i]..=aabb.maxs[i].max(aabb.mins[i]))
+                    }));
+                    let shape = cuboid(half_extents);
+                    body(&mut rng, RigidBodyType::Dynamic, shape, pos)
+                }))
+            }
+        }
+    }
+}
+
+/// The centers of the cubes of a pyramid, layer by layer.
+fn pyramid_cubes(
+    origin: Point<Real>,
+    base_size: u32,
+    cube_half_extent: Real,
+) -> impl Iterator<Item = Point<Real>> {
+    let width = cube_half_extent * 2.0;
+
+    (0..base_size).flat_map(move |layer| {
+        let n = base_size - layer;
+        let shift = layer as Real * cube_half_extent;
+        let y = cube_half_extent + layer as Real * width;
+
+        (0..n).flat_map(move |i| {
+            let x = shift + i as Real * width;
+
+            #[cfg(feature = "dim2")]
+            return std::iter::once(origin + Vector::new(x, y));
+            #[cfg(feature = "dim3")]
+            return (0..n).map(move |k| origin + Vector::new(x, y, shift + k as Real * width));
+        })
+    })
+}
+
+#[cfg_attr(feature = "dim3", allow(unused_variables))]
+fn pyramid_origin(origin: Point<Real>, grid: [u32; 2], id: [u32; 2], spacing: Real) -> Point<Real> {
+    // NOTE: in 2D, all the pyramids are placed along the `x` axis.
+    #[cfg(feature = "dim2")]
+    return origin + Vector::x() * (id[0] as u64 * grid[1] as u64 + id[1] as u64) as Real * spacing;
+    #[cfg(feature = "dim3")]
+    return origin + Vector::new(id[0] as Real * spacing, 0.0, id[1] as Real * spacing);
+}
+
+fn cuboid(half_extents: Vector<Real>) -> SharedShape {
+    SharedShape::new(Cuboid::new(half_extents))
+}
+
+fn body(
+    rng: &mut impl Rng,
+    body_type: RigidBodyType,
+    shape: SharedShape,
+    center: Point<Real>,
+) -> BodyAssignment {
+    BodyAssignment {
+        uuid: Uuid::from_u128(rng.gen()),
+        warm: WarmBodyObject {
+            timestamp: 0,
+            position: Isometry::from_parts(center.coords.into(), Rotation::identity()),
+            ..Default::default()
+        },
+        cold: ColdBodyObject {
+            body_type,
+            density: 1.0,
+            shape,
+            animations: KinematicAnimations::default(),
+            user_data: vec![],
+            is_sensor: false,
+            collision_layer: None,
+            character: None,
+            projectile: None,
+        },
+        epoch: 0,
+    }
+}
@@ -0,0 +1,101 @@
+//! The trajectories of the bodies traced through the `TRACE_BODIES_ENDPOINT`.
+//!
+//! The runners publish the pose of the traced bodies they simulate after each step, and the
+//! partitionner gathers them into one [`BodyTrajectory`] per body, whatever the regions it
+//! went through.
+
+use crate::partitionner::SceneUuid;
+use crate::simulation::SimulationBounds;
+use rapier::math::{AngVector, Isometry, Real, Vector, DIM};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use uuid::Uuid;
+
+/// The pose and velocities of a body at the end of a step.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct TrajectoryPoint {
+    pub timestamp: u64,
+    pub position: Isometry<Real>,
+    pub linvel: Vector<Real>,
+    pub angvel: AngVector<Real>,
+}
+
+/// The points of the traced bodies simulated by `region`, published after each of its steps.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrajectoryUpdate {
+    pub scene: SceneUuid,
+    pub region: SimulationBounds,
+    pub points: Vec<(Uuid, TrajectoryPoint)>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BodyTrajectory {
+    pub body: Uuid,
+    /// Sorted by timestamp.
+    pub points: Vec<TrajectoryPoint>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Trajectories {
+    pub bodies: Vec<BodyTrajectory>,
+}
+
+/// The file formats [`Trajectories`] can be written to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TrajectoryFormat {
+    /// One row per point, see [`Trajectories::write_csv`].
+    Csv,
+    /// The [`Trajectories`] themselves, serialized as JSON.
+    Json,
+}
+
+impl Trajectories {
+    pub fn write(&self, format: TrajectoryFormat, writer: impl Write) -> anyhow::Result<()> {
+        match format {
+            TrajectoryFormat::Csv => self.write_csv(writer),
+            TrajectoryFormat::Json => Ok(serde_json::to_writer_pretty(writer, self)?),
+        }
+    }
+
+    /// Writes one row per point, with the uuid of the body, the timestamp, the translation,
+    /// the rotation (an angle in 2D, a `(x, y, z, w)` quaternion in 3D), the linear velocity and
+    /// the angular velocity.
+    pub fn write_csv(&self, mut writer: impl Write) -> anyhow::Result<()> {
+        const AXES: [&str; 3] = ["x", "y", "z"];
+        #[cfg(feature = "dim2")]
+        const ROTATION: &[&str] = &["angle"];
+        #[cfg(feature = "dim3")]
+        const ROTATION: &[&str] = &["qx", "qy", "qz", "qw"];
+        #[cfg(feature = "dim2")]
+        const ANGVEL: &[&str] = &["angvel"];
+        #[cfg(feature = "dim3")]
+        const ANGVEL: &[&str] = &["angvel_x", "angvel_y", "angvel_z"];
+
+        let mut header = vec!["body".to_string(), "timestamp".to_string()];
+        header.extend(AXES[..DIM].iter().map(|axis| axis.to_string()));
+        header.extend(ROTATION.iter().map(|column| column.to_string()));
+        header.extend(AXES[..DIM].iter().map(|axis| format!("linvel_{axis}")));
+        header.extend(ANGVEL.iter().map(|column| column.to_string()));
+        writeln!(writer, "{}", header.join(","))?;
+
+        for trajectory in &self.bodies {
+            for point in &trajectory.points {
+                let mut row = vec![trajectory.body.to_string(), point.timestamp.to_string()];
+                let translation = point.position.translation.vector;
+                row.extend(translation.iter().map(Real::to_string));
+                #[cfg(feature = "dim2")]
+                row.push(point.position.rotation.angle().to_string());
+                #[cfg(feature = "dim3")]
+                row.extend(point.position.rotation.coords.iter().map(Real::to_string));
+                row.extend(point.linvel.iter().map(Real::to_string));
+                #[cfg(feature = "dim2")]
+                row.push(point.angvel.to_string());
+                #[cfg(feature = "dim3")]
+                row.extend(point.angvel.iter().map(Real::to_string));
+                writeln!(writer, "{}", row.join(","))?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+//! Ray-cast vehicles driven by the clients, see [`VehicleParams`].
+
+use rapier::math::{Point, Real, Vector};
+
+/// A wheel of a vehicle, simulated as a ray cast from the chassis, with a suspension.
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct WheelParams {
+    /// Where the suspension is attached, in the local frame of the chassis.
+    pub connection: Point<Real>,
+    /// The direction of the suspension, in the local frame of the chassis, usually down.
+    pub direction: Vector<Real>,
+    /// The axle of the wheel, in the local frame of the chassis.
+    pub axle: Vector<Real>,
+    pub radius: Real,
+    pub suspension_rest_length: Real,
+    pub suspension_stiffness: Real,
+    pub max_suspension_travel: Real,
+    /// How much the wheel grips the ground before it slips.
+    pub friction_slip: Real,
+    /// Is the wheel turned by the steering controls?
+    pub steered: bool,
+    /// Is the wheel driven by the engine?
+    pub driven: bool,
+}
+
+/// A vehicle made of a dynamic chassis and ray-cast wheels, stepped by the runner simulating
+/// its chassis with Rapier’s ray-cast vehicle controller.
+///
+/// Its driver sends [`VehicleControls`] through the body input channel. The chassis, its wheels
+/// and the last controls are handed off as one unit, see
+/// [`crate::messages::VehicleAssignment`].
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct VehicleParams {
+    pub wheels: Vec<WheelParams>,
+    /// The force of the engine on each driven wheel, at full throttle.
+    pub max_engine_force: Real,
+    /// The brake force on each wheel, fully braking.
+    pub max_brake: Real,
+    /// The angle of the steered wheels, in radians, fully turning.
+    pub max_steering_angle: Real,
+}
+
+#[cfg(feature = "dim3")]
+impl VehicleParams {
+    /// A car with a wheel at each bottom corner of a chassis of `half_extents`, facing the
+    /// `+x` axis. The front wheels are steered and the rear ones driven.
+    pub fn car(half_extents: Vector<Real>) -> Self {
+        let wheel = |x: Real, z: Real| WheelParams {
+            connection: Point::new(x, -half_extents.y, z),
+            direction: -Vector::y(),
+            axle: Vector::z(),
+            radius: half_extents.y,
+            suspension_rest_length: half_extents.y,
+            suspension_stiffness: 20.0,
+            max_suspension_travel: half_extents.y,
+            friction_slip: 10.5,
+            steered: x > 0.0,
+            driven: x < 0.0,
+        };
+
+        Self {
+            wheels: vec![
+                wheel(half_extents.x, -half_extents.z),
+                wheel(half_extents.x, half_extents.z),
+                wheel(-half_extents.x, -half_extents.z),
+                wheel(-half_extents.x, half_extents.z),
+            ],
+            max_engine_force: 30.0,
+            max_brake: 5.0,
+            max_steering_angle: 0.5,
+        }
+    }
+}
+
+/// The controls of a vehicle, kept until its driver sends new ones.
+#[derive(Copy, Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct VehicleControls {
+    /// From `-1` (full reverse) to `1` (full throttle).
+    pub throttle: Real,
+    /// From `-1` (fully right) to `1` (fully left).
+    pub steering: Real,
+    /// From `0` to `1` (fully braking).
+    pub brake: Real,
+}
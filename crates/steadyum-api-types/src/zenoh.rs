@@ -1,11 +1,17 @@
+use crate::chaos::FAULTS;
 use crate::env::CONFIG;
+use crate::messages::{BodyAssignment, DespawnBodies, RunnerMessage, SpawnBodies};
 use crate::partitionner::SceneUuid;
-use crate::serialization::serialize;
+use crate::serialization::{serialize_compressed, Codec};
 use crate::simulation::SimulationBounds;
+use crate::telemetry::Traced;
+use futures::lock::Mutex as AsyncMutex;
 use log::warn;
-use serde::Serialize;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use uuid::Uuid;
 use zenoh::config::{ConnectConfig, EndPoint, PluginLoad, WhatAmI};
 use zenoh::plugins::PluginsManager;
@@ -13,8 +19,56 @@ use zenoh::prelude::r#async::*;
 use zenoh::publication::Publisher;
 use zenoh::runtime::Runtime;
 
+/// How long a runner command waits for the runner to acknowledge it before being sent again.
+const COMMAND_ACK_TIMEOUT: Duration = Duration::from_secs(2);
+/// How many times a runner command is sent before giving up.
+const COMMAND_MAX_ATTEMPTS: u32 = 5;
+
 pub struct ZenohContext {
     pub session: Session,
+    /// Identifies the runner commands sent through this session.
+    commands_sender: Uuid,
+    /// The sequence number of the last command sent to each runner.
+    ///
+    /// Its lock is held until the runner acknowledges the command, so the commands sent to a
+    /// given runner are delivered one at a time, in order.
+    commands_seqs: Mutex<HashMap<Uuid, Arc<AsyncMutex<u64>>>>,
+}
+
+/// A [`RunnerMessage`] sent with [`ZenohContext::send_runner_command`].
+#[derive(Serialize, Deserialize)]
+pub struct RunnerCommand {
+    pub sender: Uuid,
+    /// Starts at 1, and increases by one with each command sent to the same runner.
+    pub seq: u64,
+    pub message: Traced<RunnerMessage>,
+}
+
+/// Drops the runner commands received more than once, after their ack was lost.
+#[derive(Default)]
+pub struct RunnerCommandDedup {
+    last_seqs: HashMap<Uuid, u64>,
+}
+
+impl RunnerCommandDedup {
+    /// Returns `false` if `command` was already received.
+    pub fn accept(&mut self, command: &RunnerCommand) -> bool {
+        match self.last_seqs.get(&command.sender).copied() {
+            Some(last_seq) if command.seq <= last_seq => return false,
+            Some(last_seq) if command.seq > last_seq + 1 => {
+                warn!(
+                    "Runner commands {}..{} from {:?} were lost.",
+                    last_seq + 1,
+                    command.seq,
+                    command.sender
+                );
+            }
+            _ => {}
+        }
+
+        self.last_seqs.insert(command.sender, command.seq);
+        true
+    }
 }
 
 impl ZenohContext {
@@ -59,10 +113,83 @@ impl ZenohContext {
 
         let session = zenoh::open(config.clone()).res().await.unwrap();
         load_zenoh_plugins(config, session.runtime()).await;
-        Ok(Self { session })
+        Ok(Self {
+            session,
+            commands_sender: Uuid::new_v4(),
+            commands_seqs: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Sends `message` to the runner `runner`, and waits for it to acknowledge it.
+    ///
+    /// The command is a zenoh query, sent again if the runner doesn’t reply in time. Its
+    /// sequence number lets the runner ignore the copies it already received.
+    pub async fn send_runner_command(
+        &self,
+        runner: Uuid,
+        message: Traced<RunnerMessage>,
+    ) -> anyhow::Result<()> {
+        let seqs = self
+            .commands_seqs
+            .lock()
+            .unwrap()
+            .entry(runner)
+            .or_default()
+            .clone();
+        let mut last_seq = seqs.lock().await;
+        // NOTE: the sequence number is consumed even if the command is never acknowledged,
+        //       so that the next one isn’t mistaken for a copy.
+        *last_seq += 1;
+        let codec = message.message.codec();
+        let command = RunnerCommand {
+            sender: self.commands_sender,
+            seq: *last_seq,
+            message,
+        };
+        let data = serialize_compressed(&command, codec)?;
+        let key = runner_zenoh_commands_key(runner);
+
+        for attempt in 1..=COMMAND_MAX_ATTEMPTS {
+            if FAULTS.drop_message() {
+                warn!(
+                    "Chaos: dropping the runner command {} to {runner}.",
+                    command.seq
+                );
+                continue;
+            }
+
+            let replies = self
+                .session
+                .get(&key)
+                .with_value(data.clone())
+                .timeout(COMMAND_ACK_TIMEOUT)
+                .res()
+                .await
+                .map_err(|e| anyhow::anyhow!("{e}"))?;
+            while let Ok(reply) = replies.recv_async().await {
+                if reply.sample.is_ok() {
+                    return Ok(());
+                }
+            }
+
+            warn!(
+                "Runner {runner} didn’t acknowledge the command {} (attempt {attempt}).",
+                command.seq
+            );
+        }
+
+        anyhow::bail!(
+            "Runner {runner} didn’t acknowledge the command {} after {COMMAND_MAX_ATTEMPTS} attempts.",
+            command.seq
+        )
     }
 
     pub async fn put(&self, queue: &str, elt: &impl Serialize) -> anyhow::Result<()> {
+        if FAULTS.drop_message() {
+            warn!("Chaos: dropping a message published on {queue}.");
+            return Ok(());
+        }
+
         let publisher = self
             .session
             .declare_publisher(queue)
@@ -72,10 +199,56 @@ impl ZenohContext {
             .unwrap();
         put(&publisher, elt).await
     }
+
+    /// Spawns `bodies` through the spawn streams of the regions containing them, without going
+    /// through the partitionner when these regions are already simulated.
+    pub async fn spawn_bodies(
+        &self,
+        scene: SceneUuid,
+        bodies: Vec<BodyAssignment>,
+    ) -> anyhow::Result<()> {
+        let mut region_to_bodies: HashMap<SimulationBounds, Vec<BodyAssignment>> = HashMap::new();
+
+        for body in bodies {
+            let aabb = body.cold.shape.compute_aabb(&body.warm.position);
+            let region = SimulationBounds::from_aabb(&aabb, SimulationBounds::DEFAULT_WIDTH);
+            region_to_bodies.entry(region).or_default().push(body);
+        }
+
+        for (region, bodies) in region_to_bodies {
+            let spawn = SpawnBodies {
+                scene,
+                region,
+                bodies,
+            };
+            self.put(&spawn_key(scene, &region), &spawn).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes `bodies` from the regions simulating them.
+    pub async fn despawn_bodies(&self, scene: SceneUuid, bodies: Vec<Uuid>) -> anyhow::Result<()> {
+        self.put(&despawn_key(scene), &DespawnBodies { scene, bodies })
+            .await
+    }
 }
 
 pub async fn put(publisher: &Publisher<'_>, elt: &impl Serialize) -> anyhow::Result<()> {
-    let data = serialize(elt)?;
+    put_serialized(publisher, serialize_compressed(elt, Codec::Lz4)?).await
+}
+
+/// Publishes `data`, already serialized like [`put`] does, e.g., in parallel with other
+/// payloads.
+pub async fn put_serialized(publisher: &Publisher<'_>, data: Vec<u8>) -> anyhow::Result<()> {
+    if FAULTS.drop_message() {
+        warn!(
+            "Chaos: dropping a message published on {}.",
+            publisher.key_expr()
+        );
+        return Ok(());
+    }
+
     publisher.put(data).res().await.expect("F");
     Ok(())
 }
@@ -88,6 +261,134 @@ pub fn runner_zenoh_ack_key(scene: SceneUuid, region: &SimulationBounds) -> Stri
     format!("ack/{}/{}", scene.0, region.to_string())
 }
 
+/// Key where `region` pushes its watch set after each step.
+pub fn watch_stream_key(scene: SceneUuid, region: &SimulationBounds) -> String {
+    format!("steadyum/watch_stream/{:?}/{}", scene.0, region.to_string())
+}
+
+/// Key where `region` publishes the bodies it started simulating.
+pub fn body_ownership_key(scene: SceneUuid, region: &SimulationBounds) -> String {
+    format!("steadyum/ownership/{:?}/{}", scene.0, region.to_string())
+}
+
+/// Key expression matching the ownership updates of every region, for every scene.
+pub fn body_ownership_key_expr() -> String {
+    "steadyum/ownership/*/*".to_string()
+}
+
+/// Key where clients publish the bodies they spawn in `region`.
+pub fn spawn_key(scene: SceneUuid, region: &SimulationBounds) -> String {
+    format!("steadyum/spawn/{:?}/{}", scene.0, region.to_string())
+}
+
+/// Key expression matching the bodies spawned in every region, for every scene.
+pub fn spawn_key_expr() -> String {
+    "steadyum/spawn/*/*".to_string()
+}
+
+/// Key where clients publish the bodies they despawn from `scene`.
+pub fn despawn_key(scene: SceneUuid) -> String {
+    format!("steadyum/despawn/{:?}", scene.0)
+}
+
+/// Key expression matching the bodies despawned from every scene.
+pub fn despawn_key_expr() -> String {
+    "steadyum/despawn/*".to_string()
+}
+
+/// Key where `region` publishes the events that happened during each step.
+pub fn events_key(scene: SceneUuid, region: &SimulationBounds) -> String {
+    format!("steadyum/events/{:?}/{}", scene.0, region.to_string())
+}
+
+/// Key expression matching the events published by every region of `scene`.
+pub fn events_key_expr(scene: SceneUuid) -> String {
+    format!("steadyum/events/{:?}/*", scene.0)
+}
+
+/// Key where `region` publishes its part of the export `request` of a scene.
+pub fn scene_export_key(request: Uuid, region: &SimulationBounds) -> String {
+    format!("steadyum/export/{:?}/{}", request, region.to_string())
+}
+
+/// Key expression matching the parts of the export `request` published by every region.
+pub fn scene_export_key_expr(request: Uuid) -> String {
+    format!("steadyum/export/{:?}/*", request)
+}
+
+/// Key where `region` publishes the poses of the traced bodies it simulates after each step.
+pub fn trajectory_key(scene: SceneUuid, region: &SimulationBounds) -> String {
+    format!("steadyum/trajectory/{:?}/{}", scene.0, region.to_string())
+}
+
+/// Key expression matching the trajectory updates of every region, for every scene.
+pub fn trajectory_key_expr() -> String {
+    "steadyum/trajectory/*/*".to_string()
+}
+
+/// Key where the runner of `region` answers with the timings of its last step.
+pub fn region_profile_key(scene: SceneUuid, region: &SimulationBounds) -> String {
+    format!("steadyum/profile/{:?}/{}", scene.0, region.to_string())
+}
+
+/// Key expression matching the step timings of every region of `scene`.
+pub fn scene_profile_key_expr(scene: SceneUuid) -> String {
+    format!("steadyum/profile/{:?}/*", scene.0)
+}
+
+/// Key expression matching the step timings of every region, for every scene.
+pub fn region_profiles_key_expr() -> String {
+    "steadyum/profile/*/*".to_string()
+}
+
+pub fn client_objects_stream_key(scene: SceneUuid, region: &SimulationBounds) -> String {
+    format!(
+        "steadyum/client_bodies_stream/{:?}/{}",
+        scene.0,
+        region.to_string()
+    )
+}
+
+/// Key expression matching the client objects streamed by every region of `scene`.
+pub fn client_objects_stream_key_expr(scene: SceneUuid) -> String {
+    format!("steadyum/client_bodies_stream/{:?}/*", scene.0)
+}
+
+/// Key expression matching the client objects streamed by every region of every scene.
+pub fn all_client_objects_stream_key_expr() -> String {
+    "steadyum/client_bodies_stream/*/*".to_string()
+}
+
+/// Key where the viewer `client` publishes the part of `scene` it is looking at.
+pub fn client_interest_key(scene: SceneUuid, client: Uuid) -> String {
+    format!("steadyum/interest/{:?}/{:?}", scene.0, client)
+}
+
+/// Key expression matching the interests of every viewer, for every scene.
+pub fn client_interests_key_expr() -> String {
+    "steadyum/interest/*/*".to_string()
+}
+
+/// Key where the master partitionner publishes the step requests of every scene.
+pub fn control_step_key() -> String {
+    "steadyum/control/step".to_string()
+}
+
+/// Key where the child partitionners publish their step acks.
+pub fn control_ack_key() -> String {
+    "steadyum/control/ack".to_string()
+}
+
+/// Key where the viewers publish their client inputs.
+pub fn control_client_input_key() -> String {
+    "steadyum/control/client_input".to_string()
+}
+
+/// Key expression matching every control plane message.
+pub fn control_key_expr() -> String {
+    "steadyum/control/*".to_string()
+}
+
 pub fn zenoh_storage_key(key: &str) -> String {
     format!("steadyum/kv/{key}")
 }
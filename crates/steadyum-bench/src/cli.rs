@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+#[derive(clap::Parser, Debug, Clone)]
+#[command(author, version, about, long_about = None)]
+pub struct CliArgs {
+    /// Number of dynamic bodies, laid out on a grid above a fixed ground.
+    #[arg(long, default_value_t = 10_000)]
+    pub bodies: usize,
+    /// Distance between the centers of two neighbor bodies of the grid. Smaller values make
+    /// denser scenes.
+    #[arg(long, default_value_t = 2.0)]
+    pub spacing: f32,
+    /// Radius of the balls.
+    #[arg(long, default_value_t = 0.5)]
+    pub radius: f32,
+    /// Number of steps to record before removing the scene.
+    #[arg(long, default_value_t = 1000)]
+    pub steps: u64,
+    /// Seed of the scene, for reproducible runs.
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+    /// The CSV file the per-step measurements are written to.
+    #[arg(short, long, default_value = "steadyum-bench.csv")]
+    pub output: PathBuf,
+    /// Don’t remove the scene once the benchmark is done, e.g., to inspect it with the viewer.
+    #[arg(long, default_value_t = false)]
+    pub keep_scene: bool,
+}
@@ -0,0 +1,155 @@
+mod cli;
+mod recorder;
+
+use crate::cli::CliArgs;
+use crate::recorder::StepRecorder;
+use clap::Parser;
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::Duration;
+use steadyum_api_types::messages::BodyAssignment;
+use steadyum_api_types::objects::{ColdBodyObject, WarmBodyObject};
+use steadyum_api_types::partitionner::{SceneSettings, SceneUuid, NUM_INTERNAL_STEPS};
+use steadyum_api_types::rapier::math::{Point, Real, Vector, DIM};
+use steadyum_api_types::rapier::prelude::{
+    Aabb, Collider, ColliderBuilder, RigidBody, RigidBodyBuilder,
+};
+use steadyum_api_types::serialization::ObjectSetEncoding;
+use steadyum_client::{SteadyumClient, SubscriptionEvent};
+use uuid::Uuid;
+
+/// Number of bodies sent to the partitionner per request while building the scene.
+const INSERT_CHUNK_SIZE: usize = 1024;
+/// Half-thickness of the ground the bodies fall on.
+const GROUND_HALF_THICKNESS: Real = 1.0;
+/// How long to wait for the bodies to reach their runners.
+const INSERT_POLL_PERIOD: Duration = Duration::from_millis(200);
+/// The benchmark gives up if no region was received for this long.
+const STREAM_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    init_log();
+
+    let args = CliArgs::parse();
+    let client = SteadyumClient::new()?;
+    let scene = SceneUuid(Uuid::new_v4());
+    let (bounds, bodies) = build_scene(&args);
+
+    log::info!(
+        "Creating the scene {:?} with {} bodies.",
+        scene.0,
+        bodies.len()
+    );
+    client
+        .create_scene(
+            scene,
+            bounds,
+            ObjectSetEncoding::Full,
+            args.seed,
+            SceneSettings::default(),
+        )
+        .await?;
+    let session = client
+        .insert_bodies_chunked(scene, bodies, INSERT_CHUNK_SIZE, |_| {})
+        .await?;
+    while !client
+        .partitionner()
+        .insert_progress(session)
+        .await?
+        .is_done()
+    {
+        tokio::time::sleep(INSERT_POLL_PERIOD).await;
+    }
+
+    // Stream every region, whatever its position.
+    let view = Aabb::from_half_extents(Point::origin(), Vector::repeat(1.0e9));
+    let mut subscription = client.subscribe(scene, view).await?;
+    let output = BufWriter::new(File::create(&args.output)?);
+    let mut recorder = StepRecorder::new(output)?;
+
+    // Let the cluster step as fast as it can until the last recorded step.
+    client.set_running(scene, true).await?;
+    client
+        .client_input(scene, (args.steps + 1) * NUM_INTERNAL_STEPS)
+        .await?;
+
+    log::info!("Recording {} steps into {:?}.", args.steps, args.output);
+    while recorder.num_recorded_steps() < args.steps {
+        match subscription.next(STREAM_TIMEOUT).await? {
+            SubscriptionEvent::Region(region) => recorder.record(&region)?,
+            SubscriptionEvent::Idle => anyhow::bail!("The cluster stopped stepping."),
+            SubscriptionEvent::Closed => anyhow::bail!("The partitionner closed the stream."),
+        }
+    }
+    recorder.finish()?;
+
+    client.set_running(scene, false).await?;
+    if !args.keep_scene {
+        client.remove_scene(scene).await?;
+    }
+
+    Ok(())
+}
+
+/// A grid of `args.bodies` balls above a fixed ground, and the bounds of the scene.
+fn build_scene(args: &CliArgs) -> (Aabb, Vec<BodyAssignment>) {
+    let side = (args.bodies as f64).powf(1.0 / DIM as f64).ceil() as usize;
+    let width = side as Real * args.spacing;
+    let mut bodies = Vec::with_capacity(args.bodies + 1);
+
+    for i in 0..args.bodies {
+        let mut position = Vector::zeros();
+        let mut index = i;
+        for coord in position.iter_mut() {
+            *coord = (index % side) as Real * args.spacing;
+            index /= side;
+        }
+        // Center the grid horizontally, and keep it above the ground.
+        position -= Vector::repeat(width / 2.0);
+        position.y += width / 2.0 + args.radius;
+
+        let body = RigidBodyBuilder::dynamic().translation(position).build();
+        let collider = ColliderBuilder::ball(args.radius).build();
+        bodies.push(assignment(&body, &collider));
+    }
+
+    let mut ground_half_extents = Vector::repeat(width);
+    ground_half_extents.y = GROUND_HALF_THICKNESS;
+    let mut ground_position = Vector::zeros();
+    ground_position.y = -GROUND_HALF_THICKNESS;
+    let ground = RigidBodyBuilder::fixed()
+        .translation(ground_position)
+        .build();
+    #[cfg(feature = "dim2")]
+    let collider = ColliderBuilder::cuboid(ground_half_extents.x, ground_half_extents.y).build();
+    #[cfg(feature = "dim3")]
+    let collider = ColliderBuilder::cuboid(
+        ground_half_extents.x,
+        ground_half_extents.y,
+        ground_half_extents.z,
+    )
+    .build();
+    bodies.push(assignment(&ground, &collider));
+
+    let mut maxs = Vector::repeat(width);
+    maxs.y = width + args.radius * 2.0;
+    let mut mins = -Vector::repeat(width);
+    mins.y = -GROUND_HALF_THICKNESS * 2.0;
+    (Aabb::new(mins.into(), maxs.into()), bodies)
+}
+
+fn assignment(body: &RigidBody, collider: &Collider) -> BodyAssignment {
+    BodyAssignment {
+        uuid: Uuid::new_v4(),
+        warm: WarmBodyObject::from_body(body, 0),
+        cold: ColdBodyObject::from_body_collider(body, collider),
+        epoch: 0,
+    }
+}
+
+fn init_log() {
+    let mut builder = env_logger::Builder::from_default_env();
+    builder.filter_level(log::LevelFilter::Info);
+    builder.init();
+}
@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::time::Instant;
+use steadyum_api_types::partitionner::NUM_INTERNAL_STEPS;
+use steadyum_api_types::simulation::SimulationBounds;
+use steadyum_client::RegionObjects;
+use uuid::Uuid;
+
+/// Writes one CSV row per step, once every region streamed it.
+pub struct StepRecorder<W: Write> {
+    output: W,
+    start: Instant,
+    /// The latest step streamed by each region.
+    region_steps: HashMap<SimulationBounds, u64>,
+    /// The region each body was last streamed by.
+    owners: HashMap<Uuid, SimulationBounds>,
+    /// Number of bodies that changed region at each step not recorded yet.
+    migrations: BTreeMap<u64, usize>,
+    last_step: Option<u64>,
+    last_step_time: Instant,
+    num_recorded_steps: u64,
+}
+
+impl<W: Write> StepRecorder<W> {
+    pub fn new(mut output: W) -> anyhow::Result<Self> {
+        writeln!(output, "step,time_ms,latency_ms,regions,migrations")?;
+        let now = Instant::now();
+        Ok(Self {
+            output,
+            start: now,
+            region_steps: HashMap::new(),
+            owners: HashMap::new(),
+            migrations: BTreeMap::new(),
+            last_step: None,
+            last_step_time: now,
+            num_recorded_steps: 0,
+        })
+    }
+
+    pub fn num_recorded_steps(&self) -> u64 {
+        self.num_recorded_steps
+    }
+
+    pub fn record(&mut self, region: &RegionObjects) -> anyhow::Result<()> {
+        let step = region.objects.timestamp / NUM_INTERNAL_STEPS;
+        self.region_steps.insert(region.region, step);
+
+        for object in &region.objects.objects {
+            if let Some(owner) = self.owners.insert(object.uuid, region.region) {
+                if owner != region.region {
+                    *self.migrations.entry(step).or_default() += 1;
+                }
+            }
+        }
+
+        // A step is complete once the slowest region ran it.
+        let Some(completed) = self.region_steps.values().min().copied() else {
+            return Ok(());
+        };
+        let Some(last_step) = self.last_step else {
+            // The first step only starts the clock.
+            self.last_step = Some(completed);
+            self.last_step_time = Instant::now();
+            return Ok(());
+        };
+        if completed <= last_step {
+            return Ok(());
+        }
+
+        // Spread the elapsed time over the steps completed at once.
+        let now = Instant::now();
+        let latency =
+            now.duration_since(self.last_step_time).as_secs_f64() / (completed - last_step) as f64;
+        for step in last_step + 1..=completed {
+            let migrations = self.migrations.remove(&step).unwrap_or(0);
+            writeln!(
+                self.output,
+                "{},{:.3},{:.3},{},{}",
+                step,
+                now.duration_since(self.start).as_secs_f64() * 1000.0,
+                latency * 1000.0,
+                self.region_steps.len(),
+                migrations
+            )?;
+            self.num_recorded_steps += 1;
+        }
+        self.migrations.retain(|step, _| *step > completed);
+        self.last_step = Some(completed);
+        self.last_step_time = now;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> anyhow::Result<()> {
+        self.output.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,404 @@
+use crate::subscription::ObjectSetSubscription;
+use steadyum_api_types::characters::CharacterParams;
+use steadyum_api_types::fluids::FluidParticle;
+use steadyum_api_types::forces::ForceGenerator;
+use steadyum_api_types::messages::{BodyAssignment, BodyInput, VehicleAssignment};
+use steadyum_api_types::objects::{
+    ClientBodyObjectSet, RegionList, SceneList, SceneSaveInfo, SceneSaveList,
+};
+use steadyum_api_types::partitionner::{
+    ClientInputRequest, CreateSceneResponse, InsertProgress, SceneExport, SceneProfile,
+    SceneSettings, SceneStats, SceneStatus, SceneUuid, SimulationParams, StepNResponse,
+    StreamClientObjectsRequest, TimeScale,
+};
+use steadyum_api_types::rapier::math::{Isometry, Real, Vector};
+use steadyum_api_types::rapier::prelude::Aabb;
+use steadyum_api_types::region_db::AsyncPartitionnerServer;
+use steadyum_api_types::serialization::ObjectSetEncoding;
+use steadyum_api_types::simulation::SimulationBounds;
+use steadyum_api_types::trajectory::{Trajectories, TrajectoryFormat};
+use steadyum_api_types::vehicles::VehicleControls;
+use uuid::Uuid;
+
+#[cfg(feature = "zenoh")]
+use std::sync::Arc;
+#[cfg(feature = "zenoh")]
+use steadyum_api_types::zenoh::{control_client_input_key, ZenohContext};
+
+/// A connection to the partitionner of a cluster.
+///
+/// Cloning it is cheap and shares the connection.
+#[derive(Clone)]
+pub struct SteadyumClient {
+    partitionner: AsyncPartitionnerServer,
+    /// Identifies this client in its inputs, shared by its clones.
+    id: Uuid,
+    /// The session client inputs are published on, if the control plane goes through zenoh.
+    #[cfg(feature = "zenoh")]
+    control_plane: Option<Arc<ZenohContext>>,
+}
+
+impl SteadyumClient {
+    /// Connects to the partitionner configured by `PARTITIONNER_ADDR` and `PARTITIONNER_PORT`.
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self::from_partitionner(AsyncPartitionnerServer::new()?))
+    }
+
+    /// Connects to the partitionner at `addr:port`, e.g. `http://10.0.2.154` and `3535`.
+    pub fn with_endpoint(addr: String, port: u16) -> anyhow::Result<Self> {
+        Ok(Self::from_partitionner(
+            AsyncPartitionnerServer::with_endpoint(addr, port)?,
+        ))
+    }
+
+    /// Wraps an already configured partitionner client, e.g. with a custom retry policy.
+    pub fn from_partitionner(partitionner: AsyncPartitionnerServer) -> Self {
+        Self {
+            partitionner,
+            id: Uuid::new_v4(),
+            #[cfg(feature = "zenoh")]
+            control_plane: None,
+        }
+    }
+
+    /// Publishes the client inputs on `zenoh` instead of sending them to the partitionner.
+    ///
+    /// This is what the cluster expects when it runs with `CONTROL_PLANE=zenoh`.
+    #[cfg(feature = "zenoh")]
+    pub fn with_control_plane(mut self, zenoh: Arc<ZenohContext>) -> Self {
+        self.control_plane = Some(zenoh);
+        self
+    }
+
+    /// The id the partitionner tracks the progress of this client with.
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    /// The underlying partitionner client, for the endpoints this client doesn’t wrap.
+    pub fn partitionner(&self) -> &AsyncPartitionnerServer {
+        &self.partitionner
+    }
+
+    pub async fn create_scene(
+        &self,
+        scene: SceneUuid,
+        bounds: Aabb,
+        client_objects_encoding: ObjectSetEncoding,
+        seed: u64,
+        settings: SceneSettings,
+    ) -> anyhow::Result<CreateSceneResponse> {
+        self.partitionner
+            .create_scene(scene, bounds, client_objects_encoding, seed, settings)
+            .await
+    }
+
+    pub async fn list_scenes(&self) -> anyhow::Result<SceneList> {
+        self.partitionner.list_scenes().await
+    }
+
+    pub async fn remove_scene(&self, scene: SceneUuid) -> anyhow::Result<()> {
+        self.partitionner.remove_scene(scene).await
+    }
+
+    /// Downloads the current state of `scene`.
+    pub async fn export_scene(&self, scene: SceneUuid) -> anyhow::Result<SceneExport> {
+        self.partitionner.export_scene(scene).await
+    }
+
+    /// Saves the current state of `scene` under `name`, on the cluster, for [`Self::load_save`].
+    pub async fn save_scene(
+        &self,
+        scene: SceneUuid,
+        name: String,
+    ) -> anyhow::Result<SceneSaveInfo> {
+        self.partitionner.save_scene(scene, name).await
+    }
+
+    /// Creates the new scene `scene` from the save `name`, with the same bounds and settings as
+    /// the saved scene.
+    pub async fn load_save(
+        &self,
+        name: String,
+        scene: SceneUuid,
+    ) -> anyhow::Result<CreateSceneResponse> {
+        self.partitionner.load_save(name, scene).await
+    }
+
+    pub async fn list_saves(&self) -> anyhow::Result<SceneSaveList> {
+        self.partitionner.list_saves().await
+    }
+
+    /// Starts or pauses the simulation of `scene`.
+    pub async fn set_running(&self, scene: SceneUuid, running: bool) -> anyhow::Result<()> {
+        self.partitionner.set_running(scene, running).await
+    }
+
+    /// Runs `steps` steps of the paused `scene`, from its current step. Fails if it is running.
+    pub async fn step_n(&self, scene: SceneUuid, steps: u64) -> anyhow::Result<StepNResponse> {
+        self.partitionner.step_n(scene, steps).await
+    }
+
+    /// Slows down or speeds up the simulation of `scene`, see [`TimeScale`].
+    pub async fn set_time_scale(
+        &self,
+        scene: SceneUuid,
+        time_scale: TimeScale,
+    ) -> anyhow::Result<()> {
+        self.partitionner.set_time_scale(scene, time_scale).await
+    }
+
+    /// Changes the solver options of `scene`, e.g., more iterations for heavy stacks.
+    pub async fn set_simulation_params(
+        &self,
+        scene: SceneUuid,
+        params: SimulationParams,
+    ) -> anyhow::Result<()> {
+        self.partitionner.set_simulation_params(scene, params).await
+    }
+
+    /// Replaces the force fields of `scene`, e.g., the gravity of planets.
+    pub async fn set_force_generators(
+        &self,
+        scene: SceneUuid,
+        generators: Vec<ForceGenerator>,
+    ) -> anyhow::Result<()> {
+        self.partitionner
+            .set_force_generators(scene, generators)
+            .await
+    }
+
+    /// Adds fluid particles to `scene`, if it was created with a fluid.
+    pub async fn insert_particles(
+        &self,
+        scene: SceneUuid,
+        particles: Vec<FluidParticle>,
+    ) -> anyhow::Result<()> {
+        self.partitionner.insert_particles(scene, particles).await
+    }
+
+    /// Suspends the bodies of `scene` inside `aabb`, e.g., far from every player. Returns the
+    /// volume to give to [`Self::unfreeze_volume`].
+    pub async fn freeze_volume(&self, scene: SceneUuid, aabb: Aabb) -> anyhow::Result<Uuid> {
+        let volume = Uuid::new_v4();
+        self.partitionner
+            .freeze_volume(scene, volume, Some(aabb))
+            .await?;
+        Ok(volume)
+    }
+
+    /// Resumes the bodies suspended by [`Self::freeze_volume`].
+    pub async fn unfreeze_volume(&self, scene: SceneUuid, volume: Uuid) -> anyhow::Result<()> {
+        self.partitionner.freeze_volume(scene, volume, None).await
+    }
+
+    /// The regions of `scene` currently simulated by a runner.
+    pub async fn list_regions(&self, scene: SceneUuid) -> anyhow::Result<RegionList> {
+        self.partitionner.list_regions(scene).await
+    }
+
+    /// The timings of the last step of every region of `scene`, slowest first.
+    pub async fn profile_scene(&self, scene: SceneUuid) -> anyhow::Result<SceneProfile> {
+        self.partitionner.profile_scene(scene).await
+    }
+
+    /// The energy and momentum of `scene`, summed over its regions.
+    pub async fn scene_stats(&self, scene: SceneUuid) -> anyhow::Result<SceneStats> {
+        self.partitionner.scene_stats(scene).await
+    }
+
+    /// The step of `scene`, and its hot regions as last checked by the partitionner.
+    pub async fn scene_status(&self, scene: SceneUuid) -> anyhow::Result<SceneStatus> {
+        self.partitionner.scene_status(scene).await
+    }
+
+    /// All the objects of `region`, including the sleeping bodies a subscription only reports
+    /// when they fall asleep or wake up. Useful when a subscription starts reporting a new region.
+    pub async fn full_sync(
+        &self,
+        scene: SceneUuid,
+        region: SimulationBounds,
+    ) -> anyhow::Result<ClientBodyObjectSet> {
+        self.partitionner.full_sync(scene, region).await
+    }
+
+    pub async fn insert_bodies(
+        &self,
+        scene: SceneUuid,
+        bodies: Vec<BodyAssignment>,
+    ) -> anyhow::Result<()> {
+        self.partitionner.insert_objects(scene, bodies).await
+    }
+
+    /// Inserts a large amount of bodies in chunks of `chunk_size`, see
+    /// [`AsyncPartitionnerServer::insert_objects_chunked`].
+    pub async fn insert_bodies_chunked(
+        &self,
+        scene: SceneUuid,
+        bodies: Vec<BodyAssignment>,
+        chunk_size: usize,
+        on_progress: impl FnMut(InsertProgress),
+    ) -> anyhow::Result<Uuid> {
+        self.partitionner
+            .insert_objects_chunked(scene, bodies, chunk_size, on_progress)
+            .await
+    }
+
+    pub async fn remove_bodies(&self, scene: SceneUuid, bodies: Vec<Uuid>) -> anyhow::Result<()> {
+        self.partitionner.remove_bodies(scene, bodies).await
+    }
+
+    /// Updates a body of `scene`: its velocity, a force to apply, etc.
+    pub async fn update_body(
+        &self,
+        scene: SceneUuid,
+        body: Uuid,
+        input: BodyInput,
+    ) -> anyhow::Result<()> {
+        self.partitionner.body_input(scene, body, input).await
+    }
+
+    /// Inserts a character at `position` in `scene`, and returns its uuid. Move it with
+    /// [`Self::move_character`].
+    pub async fn create_character(
+        &self,
+        scene: SceneUuid,
+        params: CharacterParams,
+        position: Isometry<Real>,
+    ) -> anyhow::Result<Uuid> {
+        let character = Uuid::new_v4();
+        self.insert_bodies(scene, vec![params.body(character, position)])
+            .await?;
+        Ok(character)
+    }
+
+    /// Sets the velocity at which `character` walks, until the next call. Called whenever the
+    /// intent of the agent controlling it changes, e.g., at each step.
+    pub async fn move_character(
+        &self,
+        scene: SceneUuid,
+        character: Uuid,
+        velocity: Vector<Real>,
+    ) -> anyhow::Result<()> {
+        self.update_body(scene, character, BodyInput::Move(velocity))
+            .await
+    }
+
+    /// Inserts vehicles in `scene`. Drive them with [`Self::drive_vehicle`].
+    pub async fn insert_vehicles(
+        &self,
+        scene: SceneUuid,
+        vehicles: Vec<VehicleAssignment>,
+    ) -> anyhow::Result<()> {
+        self.partitionner.insert_vehicles(scene, vehicles).await
+    }
+
+    /// Sets the controls of the vehicle with the chassis `vehicle`, until the next call.
+    pub async fn drive_vehicle(
+        &self,
+        scene: SceneUuid,
+        vehicle: Uuid,
+        controls: VehicleControls,
+    ) -> anyhow::Result<()> {
+        self.update_body(scene, vehicle, BodyInput::Drive(controls))
+            .await
+    }
+
+    /// Fires projectiles in `scene`, e.g., made with [`ProjectileParams::ball`]. They are sent
+    /// directly to the regions containing them if the control plane goes through zenoh.
+    ///
+    /// Their hits are reported as [`SimulationEvent::ProjectileHit`] events.
+    ///
+    /// [`ProjectileParams::ball`]: steadyum_api_types::projectiles::ProjectileParams::ball
+    /// [`SimulationEvent::ProjectileHit`]: steadyum_api_types::messages::SimulationEvent::ProjectileHit
+    pub async fn fire_projectiles(
+        &self,
+        scene: SceneUuid,
+        projectiles: Vec<BodyAssignment>,
+    ) -> anyhow::Result<()> {
+        #[cfg(feature = "zenoh")]
+        if let Some(zenoh) = &self.control_plane {
+            return zenoh.spawn_bodies(scene, projectiles).await;
+        }
+
+        self.insert_bodies(scene, projectiles).await
+    }
+
+    /// Starts, or stops if `traced` is `false`, recording the trajectories of `bodies`.
+    pub async fn trace_bodies(
+        &self,
+        scene: SceneUuid,
+        bodies: Vec<Uuid>,
+        traced: bool,
+    ) -> anyhow::Result<()> {
+        self.partitionner.trace_bodies(scene, bodies, traced).await
+    }
+
+    /// The trajectories recorded for `bodies`, or for every body traced so far if it is empty.
+    pub async fn trajectories(
+        &self,
+        scene: SceneUuid,
+        bodies: Vec<Uuid>,
+    ) -> anyhow::Result<Trajectories> {
+        self.partitionner.trajectories(scene, bodies).await
+    }
+
+    /// Downloads the trajectories of `bodies`, see [`Self::trajectories`], and writes them to
+    /// `writer` in the given `format`.
+    pub async fn download_trajectories(
+        &self,
+        scene: SceneUuid,
+        bodies: Vec<Uuid>,
+        format: TrajectoryFormat,
+        writer: impl std::io::Write,
+    ) -> anyhow::Result<()> {
+        self.trajectories(scene, bodies)
+            .await?
+            .write(format, writer)
+    }
+
+    /// Tells the cluster this client displayed the step `step_id` of `scene`.
+    pub async fn client_input(&self, scene: SceneUuid, step_id: u64) -> anyhow::Result<()> {
+        #[cfg(feature = "zenoh")]
+        if let Some(zenoh) = &self.control_plane {
+            let request = ClientInputRequest {
+                scene,
+                client: self.id,
+                step_id,
+                input: 0,
+            };
+            return zenoh.put(&control_client_input_key(), &request).await;
+        }
+
+        self.partitionner
+            .client_input(scene, self.id, step_id)
+            .await
+    }
+
+    /// Sends several client inputs at once, see [`ClientInputBatcher`](crate::ClientInputBatcher).
+    pub async fn client_inputs(&self, inputs: Vec<ClientInputRequest>) -> anyhow::Result<()> {
+        #[cfg(feature = "zenoh")]
+        if let Some(zenoh) = &self.control_plane {
+            for input in &inputs {
+                zenoh.put(&control_client_input_key(), input).await?;
+            }
+            return Ok(());
+        }
+
+        self.partitionner.client_inputs(inputs).await
+    }
+
+    /// Streams the objects of the regions of `scene` intersecting `view`.
+    pub async fn subscribe(
+        &self,
+        scene: SceneUuid,
+        view: Aabb,
+    ) -> anyhow::Result<ObjectSetSubscription> {
+        let request = StreamClientObjectsRequest {
+            scene,
+            view,
+            lod: None,
+        };
+        ObjectSetSubscription::open(&self.partitionner.client_objects_stream_url(), request).await
+    }
+}
@@ -0,0 +1,80 @@
+use crate::SteadyumClient;
+use instant::Instant;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use steadyum_api_types::partitionner::{ClientInputRequest, SceneUuid};
+
+/// Coalesces the client inputs of a viewer calling [`Self::push`] every frame.
+///
+/// Only the latest step of each scene is kept, and it is sent at most once per `period`, and
+/// never while the previous inputs are still in flight.
+pub struct ClientInputBatcher {
+    period: Duration,
+    state: Mutex<BatcherState>,
+}
+
+#[derive(Default)]
+struct BatcherState {
+    pending: HashMap<SceneUuid, u64>,
+    last_sent: Option<Instant>,
+    in_flight: bool,
+}
+
+impl ClientInputBatcher {
+    pub fn new(period: Duration) -> Self {
+        Self {
+            period,
+            state: Mutex::new(BatcherState::default()),
+        }
+    }
+
+    /// Records that this client displayed the step `step_id` of `scene`.
+    pub fn push(&self, scene: SceneUuid, step_id: u64) {
+        let mut state = self.state.lock().unwrap();
+        let latest = state.pending.entry(scene).or_insert(step_id);
+        *latest = (*latest).max(step_id);
+    }
+
+    /// Sends the pending inputs with `client`, if they are due.
+    pub async fn flush(&self, client: &SteadyumClient) -> anyhow::Result<()> {
+        let inputs: Vec<_> = {
+            let mut state = self.state.lock().unwrap();
+            let is_due = state
+                .last_sent
+                .map(|date| date.elapsed() >= self.period)
+                .unwrap_or(true);
+
+            if state.in_flight || !is_due || state.pending.is_empty() {
+                return Ok(());
+            }
+
+            state.in_flight = true;
+            state.last_sent = Some(Instant::now());
+            state
+                .pending
+                .drain()
+                .map(|(scene, step_id)| ClientInputRequest {
+                    scene,
+                    client: client.id(),
+                    step_id,
+                    input: 0,
+                })
+                .collect()
+        };
+
+        let result = client.client_inputs(inputs.clone()).await;
+        let mut state = self.state.lock().unwrap();
+        state.in_flight = false;
+
+        if result.is_err() {
+            // Retry with the next flush, unless newer steps were pushed meanwhile.
+            for input in inputs {
+                let latest = state.pending.entry(input.scene).or_insert(input.step_id);
+                *latest = (*latest).max(input.step_id);
+            }
+        }
+
+        result
+    }
+}
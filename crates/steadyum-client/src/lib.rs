@@ -0,0 +1,14 @@
+//! Async client of a steadyum cluster.
+//!
+//! This is what the Bevy viewer uses to talk to the partitionner, and what other engines
+//! (Godot or Unity bridges, headless bots, etc.) can use to create scenes, edit their bodies
+//! and follow the simulation.
+
+pub use client::SteadyumClient;
+pub use inputs::ClientInputBatcher;
+pub use subscription::{ObjectSetSubscription, RegionObjects, SubscriptionEvent};
+
+mod client;
+mod inputs;
+mod socket;
+mod subscription;
@@ -0,0 +1,88 @@
+//! Native and browser implementations of the client objects WebSocket.
+
+use std::time::Duration;
+use steadyum_api_types::partitionner::StreamClientObjectsRequest;
+
+/// What [`ClientObjectsSocket::next`] got from the partitionner.
+pub(crate) enum SocketEvent {
+    Payload(Vec<u8>),
+    /// Nothing useful was received in time.
+    Idle,
+    Closed,
+}
+
+/// The WebSocket the partitionner streams the client objects on.
+pub(crate) struct ClientObjectsSocket {
+    #[cfg(not(target_arch = "wasm32"))]
+    socket: tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >,
+    #[cfg(target_arch = "wasm32")]
+    socket: gloo_net::websocket::futures::WebSocket,
+}
+
+impl ClientObjectsSocket {
+    pub(crate) async fn connect(url: &str) -> anyhow::Result<Self> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let (socket, _) = tokio_tungstenite::connect_async(url).await?;
+        #[cfg(target_arch = "wasm32")]
+        let socket = gloo_net::websocket::futures::WebSocket::open(url)
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+        Ok(Self { socket })
+    }
+
+    /// Subscribes to the client objects of a scene, replacing the previous subscription.
+    pub(crate) async fn subscribe(
+        &mut self,
+        request: &StreamClientObjectsRequest,
+    ) -> anyhow::Result<()> {
+        use futures::SinkExt;
+
+        let text = serde_json::to_string(request)?;
+        #[cfg(not(target_arch = "wasm32"))]
+        self.socket
+            .send(tokio_tungstenite::tungstenite::Message::Text(text))
+            .await?;
+        #[cfg(target_arch = "wasm32")]
+        self.socket
+            .send(gloo_net::websocket::Message::Text(text))
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+        Ok(())
+    }
+
+    /// Waits for the next payload, for at most `timeout`.
+    pub(crate) async fn next(&mut self, timeout: Duration) -> anyhow::Result<SocketEvent> {
+        use futures::StreamExt;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            use tokio_tungstenite::tungstenite::Message;
+
+            let Ok(message) = tokio::time::timeout(timeout, self.socket.next()).await else {
+                return Ok(SocketEvent::Idle);
+            };
+            match message {
+                Some(Ok(Message::Binary(payload))) => Ok(SocketEvent::Payload(payload)),
+                Some(Ok(_)) => Ok(SocketEvent::Idle),
+                Some(Err(e)) => Err(e.into()),
+                None => Ok(SocketEvent::Closed),
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            use futures::future::{select, Either};
+            use gloo_net::websocket::Message;
+
+            let delay = Box::pin(gloo_timers::future::sleep(timeout));
+            match select(self.socket.next(), delay).await {
+                Either::Left((Some(Ok(Message::Bytes(payload))), _)) => {
+                    Ok(SocketEvent::Payload(payload))
+                }
+                Either::Left((Some(Ok(_)), _)) | Either::Right(_) => Ok(SocketEvent::Idle),
+                Either::Left((Some(Err(e)), _)) => Err(anyhow::anyhow!("{e}")),
+                Either::Left((None, _)) => Ok(SocketEvent::Closed),
+            }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use crate::socket::{ClientObjectsSocket, SocketEvent};
+use std::time::Duration;
+use steadyum_api_types::messages::ClientLod;
+use steadyum_api_types::objects::ClientBodyObjectSet;
+use steadyum_api_types::partitionner::{
+    PhysicsStats, SceneUuid, StreamClientObjectsRequest, TimeScale,
+};
+use steadyum_api_types::rapier::prelude::Aabb;
+use steadyum_api_types::serialization::deserialize_region_client_objects;
+use steadyum_api_types::simulation::SimulationBounds;
+
+/// The objects of a region, as pushed by the partitionner.
+pub struct RegionObjects {
+    pub region: SimulationBounds,
+    /// The objects that changed since they were last sent, with the cold data of the new ones.
+    pub objects: ClientBodyObjectSet,
+    /// The time scale of the scene when the region was stepped.
+    pub time_scale: TimeScale,
+    /// The energy and momentum of the bodies simulated by the region.
+    pub stats: PhysicsStats,
+    /// Size of the message received, in bytes.
+    pub payload_len: usize,
+}
+
+/// What [`ObjectSetSubscription::next`] received.
+pub enum SubscriptionEvent {
+    Region(RegionObjects),
+    /// Nothing was received in time.
+    Idle,
+    /// The partitionner closed the connection.
+    Closed,
+}
+
+/// A stream of the objects of the regions intersecting a view, see
+/// [`SteadyumClient::subscribe`](crate::SteadyumClient::subscribe).
+pub struct ObjectSetSubscription {
+    socket: ClientObjectsSocket,
+    request: StreamClientObjectsRequest,
+}
+
+impl ObjectSetSubscription {
+    pub(crate) async fn open(
+        url: &str,
+        request: StreamClientObjectsRequest,
+    ) -> anyhow::Result<Self> {
+        let mut socket = ClientObjectsSocket::connect(url).await?;
+        socket.subscribe(&request).await?;
+        Ok(Self { socket, request })
+    }
+
+    pub fn scene(&self) -> SceneUuid {
+        self.request.scene
+    }
+
+    pub fn view(&self) -> Aabb {
+        self.request.view
+    }
+
+    /// Follows another scene, or another view of the same scene.
+    pub async fn update(&mut self, scene: SceneUuid, view: Aabb) -> anyhow::Result<()> {
+        let request = StreamClientObjectsRequest {
+            scene,
+            view,
+            lod: self.request.lod,
+        };
+        self.socket.subscribe(&request).await?;
+        self.request = request;
+        Ok(())
+    }
+
+    /// Lets the regions far from the view report their objects less often, or all of them at
+    /// every step if `lod` is `None`.
+    pub async fn set_lod(&mut self, lod: Option<ClientLod>) -> anyhow::Result<()> {
+        let request = StreamClientObjectsRequest {
+            lod,
+            ..self.request
+        };
+        self.socket.subscribe(&request).await?;
+        self.request = request;
+        Ok(())
+    }
+
+    /// Waits for the next region, for at most `timeout`.
+    pub async fn next(&mut self, timeout: Duration) -> anyhow::Result<SubscriptionEvent> {
+        let payload = match self.socket.next(timeout).await? {
+            SocketEvent::Payload(payload) => payload,
+            SocketEvent::Idle => return Ok(SubscriptionEvent::Idle),
+            SocketEvent::Closed => return Ok(SubscriptionEvent::Closed),
+        };
+
+        let data = deserialize_region_client_objects(&payload)?;
+        Ok(SubscriptionEvent::Region(RegionObjects {
+            region: data.region,
+            objects: data.set.decode(&data.region),
+            time_scale: data.time_scale,
+            stats: data.stats,
+            payload_len: payload.len(),
+        }))
+    }
+}
@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+use uuid::Uuid;
+
+#[derive(clap::Parser, Debug, Clone)]
+#[command(author, version, about, long_about = None)]
+pub struct CliArgs {
+    /// Address of the master partitionner, e.g. `http://10.0.2.154`, instead of
+    /// `PARTITIONNER_ADDR`.
+    #[arg(long)]
+    pub addr: Option<String>,
+    /// Port of the master partitionner, instead of `PARTITIONNER_PORT`.
+    #[arg(long)]
+    pub port: Option<u16>,
+    /// API key of the tenant, instead of `API_KEY`.
+    #[arg(long)]
+    pub api_key: Option<String>,
+    /// Print the answers of the partitionner as JSON, one document per line.
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Checks that the partitionner answers, and shows the step and hot regions of every scene.
+    Status,
+    /// Lists the scenes, newest first.
+    Scenes,
+    /// Lists the regions of a scene currently simulated by a runner.
+    Regions { scene: Uuid },
+    /// Creates a scene from a JSON scene file, and prints its uuid.
+    Create {
+        file: PathBuf,
+        /// Replaces the name of the scene file.
+        #[arg(long)]
+        name: Option<String>,
+        /// Starts the simulation once all the bodies reached their runners.
+        #[arg(long, default_value_t = false)]
+        start: bool,
+    },
+    /// Removes scenes.
+    Remove {
+        #[arg(required = true)]
+        scenes: Vec<Uuid>,
+    },
+    /// Starts the simulation.
+    Start { scene: Uuid },
+    /// Pauses the simulation.
+    Stop { scene: Uuid },
+    /// Runs more steps of a scene, even if it is paused.
+    Step { scene: Uuid, steps: u64 },
+    /// Prints the step, energy and slowest region of a scene periodically, until interrupted.
+    Metrics {
+        scene: Uuid,
+        /// In seconds.
+        #[arg(long, default_value_t = 1.0)]
+        period: f32,
+    },
+    /// Moves all the regions of a scene from a child partitionner to another, e.g., before
+    /// stopping the first one. Children are given as `IP[:PORT]`.
+    Transfer {
+        scene: Uuid,
+        from: String,
+        to: String,
+    },
+    /// Moves all the regions of a child partitionner to the other children, and deregisters it
+    /// so its node can be stopped. The child is given as `IP[:PORT]`.
+    Drain { child: String },
+    /// Generates the key pair signing the executables: writes the secret key to a file, and
+    /// prints the public key to set as `RELEASE_PUBLIC_KEY`.
+    Keygen { key: PathBuf },
+    /// Signs executables with a secret key, writing the signature of each next to it, with a
+    /// `.sig` extension.
+    Sign {
+        #[arg(long)]
+        key: PathBuf,
+        #[arg(required = true)]
+        exes: Vec<PathBuf>,
+    },
+}
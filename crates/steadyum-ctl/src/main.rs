@@ -0,0 +1,350 @@
+mod cli;
+
+use crate::cli::{CliArgs, Command};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use steadyum_api_types::env::CONFIG;
+use steadyum_api_types::messages::BodyAssignment;
+use steadyum_api_types::objects::SceneInfo;
+use steadyum_api_types::partitionner::{
+    ChildPartitionner, RegionProfile, SceneSettings, SceneStats, SceneStatus, SceneUuid,
+};
+use steadyum_api_types::rapier::prelude::Aabb;
+use steadyum_api_types::region_db::AsyncPartitionnerServer;
+use steadyum_api_types::serialization::ObjectSetEncoding;
+use steadyum_api_types::signing;
+use steadyum_api_types::templates::SceneTemplate;
+use uuid::Uuid;
+
+/// Number of bodies sent to the partitionner per request while creating a scene.
+const INSERT_CHUNK_SIZE: usize = 1024;
+/// How long to wait for the bodies to reach their runners.
+const INSERT_POLL_PERIOD: Duration = Duration::from_millis(200);
+
+/// A scene to create: its bounds and settings, and its initial bodies.
+#[derive(Deserialize)]
+struct SceneFile {
+    bounds: Aabb,
+    #[serde(default)]
+    client_objects_encoding: ObjectSetEncoding,
+    #[serde(default)]
+    seed: u64,
+    #[serde(default)]
+    settings: SceneSettings,
+    #[serde(default)]
+    bodies: Vec<BodyAssignment>,
+    /// Bodies generated by the partitionner, much shorter to write by hand.
+    #[serde(default)]
+    templates: Vec<SceneTemplate>,
+}
+
+#[derive(Serialize)]
+struct ClusterStatus {
+    scenes: Vec<ClusterSceneStatus>,
+}
+
+#[derive(Serialize)]
+struct ClusterSceneStatus {
+    scene: SceneInfo,
+    status: SceneStatus,
+}
+
+#[derive(Serialize)]
+struct MetricsSample {
+    stats: SceneStats,
+    slowest: Option<RegionProfile>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    init_log();
+
+    let args = CliArgs::parse();
+    let mut partitionner = match (&args.addr, args.port) {
+        (None, None) => AsyncPartitionnerServer::new()?,
+        (addr, port) => AsyncPartitionnerServer::with_endpoint(
+            addr.clone()
+                .unwrap_or_else(|| CONFIG.partitionner_addr.clone()),
+            port.unwrap_or(CONFIG.partitionner_port),
+        )?,
+    };
+    if let Some(api_key) = &args.api_key {
+        partitionner = partitionner.api_key(api_key.clone());
+    }
+
+    match args.command.clone() {
+        Command::Status => status(&partitionner, args.json).await,
+        Command::Scenes => {
+            let list = partitionner.list_scenes().await?;
+            if args.json {
+                print_json(&list)
+            } else {
+                for scene in &list.scenes {
+                    print_scene(scene);
+                }
+                Ok(())
+            }
+        }
+        Command::Regions { scene } => {
+            let list = partitionner.list_regions(SceneUuid(scene)).await?;
+            if args.json {
+                print_json(&list)
+            } else {
+                for region in &list.bounds {
+                    println!("{:?} {:?}", region.mins, region.maxs);
+                }
+                Ok(())
+            }
+        }
+        Command::Create { file, name, start } => {
+            let mut scene_file: SceneFile = serde_json::from_slice(&std::fs::read(&file)?)?;
+            if let Some(name) = name {
+                scene_file.settings.name = name;
+            }
+            let scene = create_scene(&partitionner, scene_file).await?;
+            if start {
+                partitionner.set_running(scene, true).await?;
+            }
+            println!("{}", scene.0);
+            Ok(())
+        }
+        Command::Remove { scenes } => {
+            for scene in scenes {
+                partitionner.remove_scene(SceneUuid(scene)).await?;
+            }
+            Ok(())
+        }
+        Command::Start { scene } => partitionner.set_running(SceneUuid(scene), true).await,
+        Command::Stop { scene } => partitionner.set_running(SceneUuid(scene), false).await,
+        Command::Step { scene, steps } => {
+            let response = partitionner.step_n(SceneUuid(scene), steps).await?;
+            if args.json {
+                print_json(&response)
+            } else {
+                println!("Stepping until step {}.", response.step_limit);
+                Ok(())
+            }
+        }
+        Command::Metrics { scene, period } => {
+            metrics(&partitionner, SceneUuid(scene), period, args.json).await
+        }
+        Command::Transfer { scene, from, to } => {
+            let response = partitionner
+                .transfer_regions(
+                    SceneUuid(scene),
+                    child_endpoint(&from)?,
+                    child_endpoint(&to)?,
+                    vec![],
+                )
+                .await?;
+            if args.json {
+                print_json(&response)
+            } else {
+                println!("Moved {} regions.", response.regions.len());
+                Ok(())
+            }
+        }
+        Command::Drain { child } => {
+            let response = partitionner.drain_node(child_endpoint(&child)?).await?;
+            if args.json {
+                print_json(&response)
+            } else {
+                println!(
+                    "Moved {} regions, the node can be stopped.",
+                    response.regions
+                );
+                Ok(())
+            }
+        }
+        Command::Keygen { key } => {
+            let (secret_key, public_key) = signing::generate_key();
+            std::fs::write(&key, secret_key)?;
+            #[cfg(target_family = "unix")]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&key, std::fs::Permissions::from_mode(0o600))?;
+            }
+            println!("{public_key}");
+            Ok(())
+        }
+        Command::Sign { key, exes } => {
+            let secret_key = std::fs::read_to_string(key)?;
+            for exe in exes {
+                let signature = signing::sign(&secret_key, &std::fs::read(&exe)?)?;
+                let mut path = exe.into_os_string();
+                path.push(".sig");
+                std::fs::write(path, signature)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The child partitionner at `IP[:PORT]`, as it registered to the master.
+fn child_endpoint(child: &str) -> anyhow::Result<ChildPartitionner> {
+    let (ip, port) = match child.split_once(':') {
+        Some((ip, port)) => (ip, port.parse()?),
+        None => (child, CONFIG.partitionner_port),
+    };
+    Ok(ChildPartitionner {
+        addr: format!("http://{ip}"),
+        port,
+    })
+}
+
+/// Creates the scene of `file`, and waits for all its bodies to reach their runners.
+async fn create_scene(
+    partitionner: &AsyncPartitionnerServer,
+    file: SceneFile,
+) -> anyhow::Result<SceneUuid> {
+    let scene = SceneUuid(Uuid::new_v4());
+    log::info!(
+        "Creating the scene {:?} with {} bodies and {} templates.",
+        scene.0,
+        file.bodies.len(),
+        file.templates.len()
+    );
+    partitionner
+        .create_scene(
+            scene,
+            file.bounds,
+            file.client_objects_encoding,
+            file.seed,
+            file.settings,
+        )
+        .await?;
+
+    if !file.templates.is_empty() {
+        partitionner.spawn_templates(scene, file.templates).await?;
+    }
+
+    if !file.bodies.is_empty() {
+        let session = partitionner
+            .insert_objects_chunked(scene, file.bodies, INSERT_CHUNK_SIZE, |progress| {
+                log::info!("Sent {}/{} bodies.", progress.received, progress.total)
+            })
+            .await?;
+        while !partitionner.insert_progress(session).await?.is_done() {
+            tokio::time::sleep(INSERT_POLL_PERIOD).await;
+        }
+    }
+
+    Ok(scene)
+}
+
+async fn status(partitionner: &AsyncPartitionnerServer, json: bool) -> anyhow::Result<()> {
+    partitionner.heartbeat().await?;
+
+    let mut result = ClusterStatus { scenes: vec![] };
+    for scene in partitionner.list_scenes().await?.scenes {
+        let status = partitionner.scene_status(scene.uuid).await?;
+        result.scenes.push(ClusterSceneStatus { scene, status });
+    }
+
+    if json {
+        return print_json(&result);
+    }
+
+    println!("The partitionner is up, {} scenes.", result.scenes.len());
+    for ClusterSceneStatus { scene, status } in &result.scenes {
+        print_scene(scene);
+        println!(
+            "    step {}, {}",
+            status.step_id,
+            if status.running { "running" } else { "paused" }
+        );
+        for hot in &status.hot_regions {
+            println!(
+                "    hot region {:?} {:?} ({:?}): {:.0} ms, {} bodies",
+                hot.profile.region.mins,
+                hot.profile.region.maxs,
+                hot.reasons,
+                hot.profile.timings.loop_time * 1000.0,
+                hot.profile.stats.num_bodies
+            );
+        }
+        for violation in &status.violations {
+            println!(
+                "    violation at {} in {:?} {:?}: {:?} {:?}",
+                violation.timestamp,
+                violation.region.mins,
+                violation.region.maxs,
+                violation.body,
+                violation.kind
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the stats and the slowest region of `scene` every `period` seconds.
+async fn metrics(
+    partitionner: &AsyncPartitionnerServer,
+    scene: SceneUuid,
+    period: f32,
+    json: bool,
+) -> anyhow::Result<()> {
+    let mut interval = tokio::time::interval(Duration::from_secs_f32(period));
+
+    loop {
+        interval.tick().await;
+
+        let sample = MetricsSample {
+            stats: partitionner.scene_stats(scene).await?,
+            slowest: partitionner.profile_scene(scene).await?.slowest().copied(),
+        };
+
+        if json {
+            print_json(&sample)?;
+            continue;
+        }
+
+        let stats = &sample.stats;
+        print!(
+            "steps {}..{}, {} regions, {} bodies ({} dynamic, {} sleeping, {} kinematic, {} fixed), \
+             kinetic energy {:.3}",
+            stats.min_step_id,
+            stats.max_step_id,
+            stats.num_regions,
+            stats.bodies.total(),
+            stats.bodies.dynamic,
+            stats.bodies.sleeping,
+            stats.bodies.kinematic,
+            stats.bodies.fixed,
+            stats.total.kinetic_energy
+        );
+        match &sample.slowest {
+            Some(slowest) => println!(
+                ", slowest region {:?} {:?}: {:.1} ms",
+                slowest.region.mins,
+                slowest.region.maxs,
+                slowest.timings.loop_time * 1000.0
+            ),
+            None => println!(),
+        }
+    }
+}
+
+fn print_scene(scene: &SceneInfo) {
+    println!(
+        "{}  {:<24}  {:>8} bodies  {}  created at {}",
+        scene.uuid.0,
+        scene.name,
+        scene.num_bodies,
+        if scene.running { "running" } else { "paused" },
+        scene.created_at
+    );
+}
+
+fn print_json(value: &impl Serialize) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+fn init_log() {
+    let mut builder = env_logger::Builder::from_default_env();
+    builder.filter_level(log::LevelFilter::Info);
+    builder.init();
+}
@@ -66,13 +66,14 @@ pub fn init_world() -> BuiltinScene {
                 .insert_with_parent(collider, ground_handle, &mut result.bodies);
 
             // let ground_animation = KinematicAnimations {
-            //     linear: Some(KinematicCurve {
-            //         control_points: vec![vector![x, y], vector![x, y + 1.0]],
-            //         t0: 0.0,
-            //         total_time: 16.0,
-            //         loop_back: true,
-            //     }),
+            //     linear: Some(KinematicCurve::uniform(
+            //         vec![vector![x, y], vector![x, y + 1.0]],
+            //         0.0,
+            //         16.0,
+            //         LoopMode::PingPong,
+            //     )),
             //     angular: None,
+            //     rotation: None,
             // };
             // animations.insert(ground_handle, ground_animation);
         }
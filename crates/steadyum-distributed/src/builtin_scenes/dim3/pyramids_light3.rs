@@ -3,7 +3,7 @@ use crate::utils::RapierContext;
 use na::Vector3;
 use rapier::prelude::*;
 use std::collections::HashMap;
-use steadyum_api_types::kinematic::{KinematicAnimations, KinematicCurve};
+use steadyum_api_types::kinematic::{KinematicAnimations, KinematicCurve, LoopMode};
 
 fn create_wall(
     bodies: &mut RigidBodySet,
@@ -107,12 +107,13 @@ pub fn init_world() -> BuiltinScene {
 
     let ground_animation = KinematicAnimations {
         linear: None,
-        angular: Some(KinematicCurve {
-            control_points: vec![vector![0.0, 0.0, 0.0], vector![0.0, 100.0, 0.0]],
-            t0: 0.0,
-            total_time: 400.0,
-            loop_back: true,
-        }),
+        angular: Some(KinematicCurve::uniform(
+            vec![vector![0.0, 0.0, 0.0], vector![0.0, 100.0, 0.0]],
+            0.0,
+            400.0,
+            LoopMode::PingPong,
+        )),
+        rotation: None,
     };
     animations.insert(ground_handle, ground_animation);
 
@@ -0,0 +1,250 @@
+use crate::block_on;
+use crate::storage::DbContext;
+use crate::styling::ColorGenerator;
+use crate::ui::UiState;
+use crate::utils::{PhysicsObject, Vect};
+use crate::{GizmoCamera, MainCamera};
+use bevy::core_pipeline::clear_color::ClearColorConfig;
+use bevy::prelude::*;
+use bevy::render::camera::{ScalingMode, Viewport};
+use bevy::render::view::RenderLayers;
+use bevy::utils::Uuid;
+use bevy::window::PrimaryWindow;
+use steadyum_api_types::simulation::SimulationBounds;
+
+#[cfg(feature = "dim2")]
+use bevy::prelude::Transform as CameraTarget;
+#[cfg(feature = "dim3")]
+use smooth_bevy_cameras::LookTransform as CameraTarget;
+
+/// The render layer only seen by the minimap camera.
+const MINIMAP_LAYER: u8 = 1;
+/// Width and height of the minimap, in physical pixels.
+const MINIMAP_SIZE: u32 = 256;
+/// Width of the world area shown by the minimap.
+const MINIMAP_EXTENT: f32 = SimulationBounds::DEFAULT_WIDTH as f32 * 16.0;
+/// Height of the minimap camera, looking down.
+#[cfg(feature = "dim3")]
+const MINIMAP_ALTITUDE: f32 = 10_000.0;
+/// Length of the view frustum edges drawn on the minimap.
+#[cfg(feature = "dim3")]
+const FRUSTUM_DRAW_DISTANCE: f32 = SimulationBounds::DEFAULT_WIDTH as f32 * 2.0;
+
+/// Moves the main camera to follow a body, or to a region.
+#[derive(Resource, Default)]
+pub struct CameraNavigation {
+    /// The body the camera stays centered on.
+    pub follow: Option<Uuid>,
+    /// The region to move the camera to on the next frame.
+    pub jump_to: Option<SimulationBounds>,
+}
+
+pub struct CameraNavigationPlugin;
+
+impl Plugin for CameraNavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraNavigation>()
+            .add_systems(Startup, setup_minimap)
+            .add_systems(Update, (jump_to_region, follow_body).chain())
+            .add_systems(Update, update_minimap);
+    }
+}
+
+#[cfg(feature = "dim2")]
+fn center_camera(camera: &mut CameraTarget, point: Vect) {
+    camera.translation.x = point.x;
+    camera.translation.y = point.y;
+}
+
+#[cfg(feature = "dim3")]
+fn center_camera(camera: &mut CameraTarget, point: Vect) {
+    // Keep the view direction and distance.
+    let offset = camera.eye - camera.target;
+    camera.target = point;
+    camera.eye = point + offset;
+}
+
+fn jump_to_region(
+    mut navigation: ResMut<CameraNavigation>,
+    mut cameras: Query<&mut CameraTarget, With<MainCamera>>,
+) {
+    let Some(region) = navigation.jump_to.take() else {
+        return;
+    };
+
+    // Jumping elsewhere stops following the body.
+    navigation.follow = None;
+    let center: Vect = region.aabb().center().coords.into();
+    for mut camera in cameras.iter_mut() {
+        center_camera(&mut camera, center);
+    }
+}
+
+fn follow_body(
+    navigation: Res<CameraNavigation>,
+    objects: Query<(&PhysicsObject, &Transform), Without<MainCamera>>,
+    mut cameras: Query<&mut CameraTarget, With<MainCamera>>,
+) {
+    let Some(uuid) = navigation.follow else {
+        return;
+    };
+    // The body may be out of the streamed area for a while, keep waiting for it.
+    let Some((_, transform)) = objects.iter().find(|(object, _)| object.uuid == uuid) else {
+        return;
+    };
+
+    #[cfg(feature = "dim2")]
+    let position = transform.translation.truncate();
+    #[cfg(feature = "dim3")]
+    let position = transform.translation;
+
+    for mut camera in cameras.iter_mut() {
+        center_camera(&mut camera, position);
+    }
+}
+
+fn setup_minimap(mut commands: Commands, mut gizmo_config: ResMut<GizmoConfig>) {
+    // The minimap only shows the gizmos: the visible regions and the view of the main camera.
+    gizmo_config.render_layers = RenderLayers::from_layers(&[0, MINIMAP_LAYER]);
+
+    let camera = Camera {
+        order: 1,
+        is_active: false,
+        ..default()
+    };
+    let clear_color = ClearColorConfig::Custom(Color::rgb(0.15, 0.15, 0.15));
+
+    #[cfg(feature = "dim2")]
+    let bundle = Camera2dBundle {
+        camera,
+        camera_2d: Camera2d { clear_color },
+        projection: OrthographicProjection {
+            scaling_mode: ScalingMode::FixedVertical(MINIMAP_EXTENT),
+            ..default()
+        },
+        ..default()
+    };
+    #[cfg(feature = "dim3")]
+    let bundle = Camera3dBundle {
+        camera,
+        camera_3d: Camera3d {
+            clear_color,
+            ..default()
+        },
+        projection: Projection::Orthographic(OrthographicProjection {
+            scaling_mode: ScalingMode::FixedVertical(MINIMAP_EXTENT),
+            far: MINIMAP_ALTITUDE * 2.0,
+            ..default()
+        }),
+        ..default()
+    };
+
+    commands
+        .spawn(bundle)
+        .insert(GizmoCamera)
+        .insert(RenderLayers::layer(MINIMAP_LAYER));
+}
+
+/// Places the minimap in the bottom-right corner of the window, above the main camera, and
+/// draws the visible regions and the view of the main camera.
+fn update_minimap(
+    ui_state: Res<UiState>,
+    db: Res<DbContext>,
+    mut colors: ResMut<ColorGenerator>,
+    mut gizmos: Gizmos,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    main_cameras: Query<(&Camera, &GlobalTransform), (With<MainCamera>, Without<GizmoCamera>)>,
+    mut minimaps: Query<(&mut Camera, &mut Transform), With<GizmoCamera>>,
+) {
+    let (Ok(window), Ok((main_camera, main_transform))) =
+        (windows.get_single(), main_cameras.get_single())
+    else {
+        return;
+    };
+
+    let size = MINIMAP_SIZE
+        .min(window.physical_width())
+        .min(window.physical_height());
+    let eye = main_transform.translation();
+    for (mut camera, mut transform) in minimaps.iter_mut() {
+        camera.is_active = ui_state.minimap;
+        camera.viewport = Some(Viewport {
+            physical_position: UVec2::new(
+                window.physical_width() - size,
+                window.physical_height() - size,
+            ),
+            physical_size: UVec2::new(size, size),
+            ..default()
+        });
+
+        #[cfg(feature = "dim2")]
+        {
+            transform.translation = eye.truncate().extend(transform.translation.z);
+        }
+        #[cfg(feature = "dim3")]
+        {
+            *transform = Transform::from_xyz(eye.x, MINIMAP_ALTITUDE, eye.z)
+                .looking_to(Vec3::NEG_Y, Vec3::NEG_Z);
+        }
+    }
+
+    if !ui_state.minimap {
+        return;
+    }
+
+    let regions: Vec<_> = block_on(db.region_stats.read()).keys().copied().collect();
+    for region in regions {
+        let aabb = region.aabb();
+        let center: Vect = aabb.center().coords.into();
+        let extents: Vect = aabb.extents().into();
+        let color = colors.gen_region_color(region);
+
+        #[cfg(feature = "dim2")]
+        gizmos.rect_2d(center, 0.0, extents, color);
+        #[cfg(feature = "dim3")]
+        gizmos.rect(
+            Vec3::new(center.x, aabb.maxs.y, center.z),
+            Quat::from_rotation_x(std::f32::consts::FRAC_PI_2),
+            Vec2::new(extents.x, extents.z),
+            color,
+        );
+    }
+
+    // The view of the main camera: its visible rectangle in 2D, the edges of its frustum in 3D.
+    let Some(viewport_size) = main_camera.logical_viewport_size() else {
+        return;
+    };
+    let corners = [
+        Vec2::ZERO,
+        Vec2::new(viewport_size.x, 0.0),
+        viewport_size,
+        Vec2::new(0.0, viewport_size.y),
+    ];
+
+    #[cfg(feature = "dim2")]
+    {
+        let corners: Option<Vec<Vec2>> = corners
+            .iter()
+            .map(|corner| main_camera.viewport_to_world_2d(main_transform, *corner))
+            .collect();
+        if let Some(corners) = corners {
+            gizmos.linestrip_2d(corners.iter().chain(&corners[..1]).copied(), Color::WHITE);
+        }
+    }
+    #[cfg(feature = "dim3")]
+    {
+        let corners: Option<Vec<Vec3>> = corners
+            .iter()
+            .map(|corner| {
+                let ray = main_camera.viewport_to_world(main_transform, *corner)?;
+                Some(ray.get_point(FRUSTUM_DRAW_DISTANCE))
+            })
+            .collect();
+        if let Some(corners) = corners {
+            for corner in &corners {
+                gizmos.line(eye, *corner, Color::WHITE);
+            }
+            gizmos.linestrip(corners.iter().chain(&corners[..1]).copied(), Color::WHITE);
+        }
+    }
+}
@@ -24,6 +24,16 @@ pub struct CliArgs {
     pub lower_graphics: bool,
     #[arg(long, default_value_t = false)]
     pub dev: bool,
+    /// Receive object positions through the partitionner’s WebSocket stream instead
+    /// of polling each region.
+    #[arg(long, default_value_t = false)]
+    pub stream: bool,
+    /// Ask the runners to send quantized object positions, trading precision for bandwidth.
+    #[arg(long, default_value_t = false)]
+    pub quantize: bool,
+    /// Seed of everything random in the simulated scene.
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
 }
 
 impl CliArgs {
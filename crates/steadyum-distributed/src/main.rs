@@ -22,6 +22,8 @@ use bevy::utils::HashSet;
 use bevy::winit::WinitWindows;
 use clap::Parser;
 use rapier::math::Real;
+use steadyum_api_types::partitionner::TimeScale;
+use steadyum_api_types::serialization::ObjectSetEncoding;
 use steadyum_api_types::simulation::SimulationBounds;
 
 mod camera;
@@ -50,6 +52,10 @@ pub struct PhysicsProgress {
     pub progress_limit: usize,
     pub required_progress: u64,
     pub known_regions: HashSet<SimulationBounds>,
+    /// Number of steps played back per frame, see [`TimeScale`].
+    pub time_scale: TimeScale,
+    /// The part of a step accumulated by the frames of a slow motion.
+    pub step_fraction: Real,
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
@@ -91,7 +97,15 @@ fn main() {
         .add_systems(Startup, setup_graphics)
         .add_plugins(storage::StoragePlugin {
             local_dev_mode: args.dev,
+            stream_client_objects: args.stream,
+            client_objects_encoding: if args.quantize {
+                ObjectSetEncoding::Quantized
+            } else {
+                ObjectSetEncoding::Full
+            },
+            seed: args.seed,
         });
+    app.add_plugins(camera::CameraNavigationPlugin);
 
     app.run();
 }
@@ -217,6 +231,7 @@ pub fn look_at(camera: &mut OrbitCamera, eye: Vec3, at: Vec3) {
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 fn block_on<Fut: Future>(f: Fut) -> Fut::Output {
     tokio::runtime::Builder::new_current_thread()
         .enable_all()
@@ -224,3 +239,10 @@ fn block_on<Fut: Future>(f: Fut) -> Fut::Output {
         .unwrap()
         .block_on(f)
 }
+
+/// The browser can’t block: only use this on futures that complete without waiting on the
+/// network, like taking the locks of the `DbContext`.
+#[cfg(target_arch = "wasm32")]
+fn block_on<Fut: Future>(f: Fut) -> Fut::Output {
+    futures::executor::block_on(f)
+}
@@ -2,20 +2,60 @@ use crate::render::{ColliderRender, ColliderRenderShape};
 use bevy::prelude::*;
 use bevy::render::mesh::{Indices, VertexAttributeValues};
 use na::{point, UnitQuaternion};
-use rapier::math::{Point, Real, Vector};
+use rapier::math::{Point, Real, Vector, DIM};
 
 use crate::cli::CliArgs;
 #[cfg(feature = "dim2")]
 use bevy::sprite::MaterialMesh2dBundle;
 use bevy_egui::egui::ahash::HashMap;
 use rapier::geometry::ColliderShape;
-use rapier::parry::shape::Cuboid;
 use rapier::prelude::{Collider, TypedShape};
 
+/// The meshes and materials shared by the colliders, so Bevy batches the draws of the bodies
+/// with the same shape and color.
 #[derive(Resource, Default, Clone)]
 pub struct CollisionShapeMeshInstances {
-    cuboid_to_mesh: Vec<(Cuboid, Handle<Mesh>)>, // TODO: make this work for all collider types.
-    color_to_material: Vec<(Color, Handle<StandardMaterial>)>,
+    primitive_meshes: HashMap<PrimitiveMeshKey, Handle<Mesh>>,
+    /// The materials, by the bits of the RGBA components of their color.
+    color_to_material: HashMap<[u32; 4], Handle<StandardMaterial>>,
+}
+
+/// The dimensions of a primitive shape, identifying the shapes with the same mesh.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+enum PrimitiveMeshKey {
+    Cuboid([u32; DIM]),
+    Ball(u32),
+    /// The endpoints of the segment of the capsule, and its radius.
+    Capsule([u32; DIM], [u32; DIM], u32),
+    /// The half-height and radius of the cylinder.
+    #[cfg(feature = "dim3")]
+    Cylinder(u32, u32),
+    /// The half-height and radius of the cone.
+    #[cfg(feature = "dim3")]
+    Cone(u32, u32),
+}
+
+impl PrimitiveMeshKey {
+    /// The key of the mesh of `shape`, or `None` if its mesh isn’t shared.
+    fn new(shape: &ColliderShape) -> Option<Self> {
+        let bits = |v: &Vector<Real>| std::array::from_fn(|i| v[i].to_bits());
+        match shape.as_typed_shape() {
+            TypedShape::Cuboid(s) => Some(Self::Cuboid(bits(&s.half_extents))),
+            TypedShape::Ball(s) => Some(Self::Ball(s.radius.to_bits())),
+            TypedShape::Capsule(s) => Some(Self::Capsule(
+                bits(&s.segment.a.coords),
+                bits(&s.segment.b.coords),
+                s.radius.to_bits(),
+            )),
+            #[cfg(feature = "dim3")]
+            TypedShape::Cylinder(s) => {
+                Some(Self::Cylinder(s.half_height.to_bits(), s.radius.to_bits()))
+            }
+            #[cfg(feature = "dim3")]
+            TypedShape::Cone(s) => Some(Self::Cone(s.half_height.to_bits(), s.radius.to_bits())),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Component, Copy, Clone)]
@@ -48,10 +88,9 @@ pub fn create_collider_renders_system(
 
             let material_handle = instances
                 .color_to_material
-                .iter()
-                .find(|(c, _)| *c == render.color)
-                .map(|(_, m)| m.clone())
-                .unwrap_or_else(|| materials.add(material));
+                .entry(render.color.as_rgba_f32().map(f32::to_bits))
+                .or_insert_with(|| materials.add(material))
+                .clone();
 
             // println!("Rendering with color: {:?}", render.color);
 
@@ -106,24 +145,13 @@ fn generate_collision_shape_render_mesh(
 ) -> Option<Handle<Mesh>> {
     const NSUB: u32 = 20;
 
-    let ((vertices, indices), flat_normals) = match shape.as_typed_shape() {
-        TypedShape::Cuboid(s) => {
-            if let Some((_, mesh)) = instances
-                .cuboid_to_mesh
-                .iter()
-                .find(|(cuboid, _)| cuboid == s)
-            {
-                return Some(mesh.clone());
-            }
-
-            let (vertices, indices) = s.to_trimesh();
-            let mesh = gen_bevy_mesh(&vertices, &indices, true);
-            let handle = meshes.add(mesh);
-            instances.cuboid_to_mesh.push((s.clone(), handle.clone()));
-            return Some(handle);
+    let key = PrimitiveMeshKey::new(shape);
+    if let Some(mesh) = key.and_then(|key| instances.primitive_meshes.get(&key)) {
+        return Some(mesh.clone());
+    }
 
-            // (s.to_trimesh(), true)
-        }
+    let ((vertices, indices), flat_normals) = match shape.as_typed_shape() {
+        TypedShape::Cuboid(s) => (s.to_trimesh(), true),
         TypedShape::Ball(s) => (s.to_trimesh(NSUB, NSUB / 2), false),
         TypedShape::Cylinder(s) => {
             let (mut vtx, mut idx) = s.to_trimesh(NSUB);
@@ -185,17 +213,26 @@ fn generate_collision_shape_render_mesh(
         _ => todo!(),
     };
 
-    let mesh = gen_bevy_mesh(&vertices, &indices, flat_normals);
-    Some(meshes.add(mesh))
+    let mesh = meshes.add(gen_bevy_mesh(&vertices, &indices, flat_normals));
+    if let Some(key) = key {
+        instances.primitive_meshes.insert(key, mesh.clone());
+    }
+    Some(mesh)
 }
 
 #[cfg(feature = "dim2")]
 fn generate_collision_shape_render_mesh(
     shape: &ColliderShape,
     meshes: &mut Assets<Mesh>,
+    instances: &mut CollisionShapeMeshInstances,
 ) -> Option<Handle<Mesh>> {
     const NSUB: u32 = 20;
 
+    let key = PrimitiveMeshKey::new(shape);
+    if let Some(mesh) = key.and_then(|key| instances.primitive_meshes.get(&key)) {
+        return Some(mesh.clone());
+    }
+
     let (vertices, indices) = match shape.as_typed_shape() {
         TypedShape::Cuboid(s) => (s.to_polyline(), None),
         TypedShape::Ball(s) => (s.to_polyline(NSUB), None),
@@ -209,8 +246,11 @@ fn generate_collision_shape_render_mesh(
         _ => todo!(),
     };
 
-    let mesh = gen_bevy_mesh(&vertices, indices);
-    Some(meshes.add(mesh))
+    let mesh = meshes.add(gen_bevy_mesh(&vertices, indices));
+    if let Some(key) = key {
+        instances.primitive_meshes.insert(key, mesh.clone());
+    }
+    Some(mesh)
 }
 
 #[cfg(feature = "dim2")]
@@ -0,0 +1,137 @@
+//! Draws the colliders of the bodies with Rapier’s debug render pipeline, on top of their
+//! meshes, see [`DebugRenderOptions`].
+
+use crate::render::ColliderRenderShape;
+use crate::ui::{DebugRenderOptions, UiState};
+use crate::utils::{transform_to_iso, PhysicsObject, Vect};
+use bevy::prelude::*;
+use rapier::dynamics::RigidBodyType;
+use rapier::geometry::{ColliderBuilder, ColliderHandle};
+use rapier::math::{Point, Real};
+use rapier::pipeline::{
+    DebugRenderBackend, DebugRenderMode, DebugRenderObject, DebugRenderPipeline, DebugRenderStyle,
+};
+use steadyum_api_types::simulation::WatchMargins;
+
+const AABB_COLOR: Color = Color::rgb(0.9, 0.5, 0.1);
+const WATCH_RADIUS_COLOR: Color = Color::rgba(1.0, 1.0, 0.0, 0.3);
+
+/// The pipeline drawing the debug render of the colliders.
+#[derive(Resource)]
+pub struct DebugRender(DebugRenderPipeline);
+
+impl Default for DebugRender {
+    fn default() -> Self {
+        Self(DebugRenderPipeline::new(
+            DebugRenderStyle::default(),
+            DebugRenderMode::COLLIDER_SHAPES,
+        ))
+    }
+}
+
+/// Draws the lines of the debug render pipeline with the gizmos.
+struct GizmosBackend<'a, 's> {
+    gizmos: &'a mut Gizmos<'s>,
+}
+
+impl DebugRenderBackend for GizmosBackend<'_, '_> {
+    fn draw_line(
+        &mut self,
+        _object: DebugRenderObject,
+        a: Point<Real>,
+        b: Point<Real>,
+        color: [f32; 4],
+    ) {
+        let a: Vect = a.coords.into();
+        let b: Vect = b.coords.into();
+        #[cfg(feature = "dim2")]
+        self.gizmos.line_2d(a, b, hsla(color));
+        #[cfg(feature = "dim3")]
+        self.gizmos.line(a, b, hsla(color));
+    }
+}
+
+/// The colors of the debug render are in HSLA.
+fn hsla(color: [f32; 4]) -> Color {
+    Color::hsla(color[0], color[1], color[2], color[3])
+}
+
+/// Draws the categories of the debug render enabled from the UI, for the visible bodies.
+pub fn draw_debug_render(
+    ui_state: Res<UiState>,
+    mut debug_render: ResMut<DebugRender>,
+    mut gizmos: Gizmos,
+    bodies: Query<(
+        &PhysicsObject,
+        &Transform,
+        &ColliderRenderShape,
+        &InheritedVisibility,
+    )>,
+) {
+    let options: DebugRenderOptions = ui_state.debug_render;
+    if !options.any() {
+        return;
+    }
+
+    let pipeline = &mut debug_render.0;
+    let style = pipeline.style;
+    let watch_margins = WatchMargins::default();
+
+    for (object, transform, shape, visibility) in bodies.iter() {
+        if !visibility.get() {
+            continue;
+        }
+
+        let position = transform_to_iso(transform, 1.0);
+
+        if options.outlines {
+            let mut color = match object.body_type {
+                RigidBodyType::Dynamic => style.collider_dynamic_color,
+                RigidBodyType::Fixed => style.collider_fixed_color,
+                _ => style.collider_kinematic_color,
+            };
+            if options.sleeping && object.sleeping {
+                for (c, multiplier) in color.iter_mut().zip(style.sleep_color_multiplier) {
+                    *c *= multiplier;
+                }
+            }
+
+            // NOTE: the viewer has no collider set, this collider only gives the shape to the
+            //       pipeline.
+            let collider = ColliderBuilder::new(shape.shape.clone())
+                .position(position)
+                .build();
+            pipeline.render_shape(
+                DebugRenderObject::Collider(ColliderHandle::invalid(), &collider),
+                &mut GizmosBackend {
+                    gizmos: &mut gizmos,
+                },
+                &*shape.shape,
+                &position,
+                color,
+            );
+        }
+
+        if options.aabbs {
+            let aabb = shape.shape.compute_aabb(&position);
+            let center: Vect = aabb.center().coords.into();
+            let extents: Vect = aabb.extents().into();
+            #[cfg(feature = "dim2")]
+            gizmos.rect_2d(center, 0.0, extents, AABB_COLOR);
+            #[cfg(feature = "dim3")]
+            gizmos.cuboid(
+                Transform::from_translation(center).with_scale(extents),
+                AABB_COLOR,
+            );
+        }
+
+        if options.watch_radii && object.body_type == RigidBodyType::Dynamic {
+            let center: Vect = position.translation.vector.into();
+            let radius = watch_margins.watch_radius(&shape.shape);
+            #[cfg(feature = "dim2")]
+            gizmos.circle_2d(center, radius, WATCH_RADIUS_COLOR);
+            #[cfg(feature = "dim3")]
+            gizmos.sphere(center, Quat::IDENTITY, radius, WATCH_RADIUS_COLOR);
+        }
+    }
+}
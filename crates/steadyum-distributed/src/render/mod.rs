@@ -1,10 +1,12 @@
 pub use self::collision_shape_render::*;
 pub use self::components::*;
+pub use self::debug_render::*;
 // pub use self::joint_render::*;
 pub use self::plugins::*;
 
 // mod collision_shape_render;
 mod components;
+mod debug_render;
 // mod joint_render;
 mod collision_shape_render;
 mod plugins;
@@ -1,4 +1,4 @@
-use crate::render::CollisionShapeMeshInstances;
+use crate::render::{CollisionShapeMeshInstances, DebugRender};
 use bevy::prelude::*;
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
@@ -34,6 +34,7 @@ impl Plugin for RapierRenderPlugin {
 
         app // .add_plugins(bevy_prototype_debug_lines::DebugLinesPlugin::with_depth_test(false))
             .init_resource::<CollisionShapeMeshInstances>()
+            .init_resource::<DebugRender>()
             .add_systems(
                 Update,
                 apply_deferred
@@ -43,7 +44,8 @@ impl Plugin for RapierRenderPlugin {
             .add_systems(
                 Update, // SteadyumStages::RenderStage,
                 super::create_collider_renders_system.in_set(RenderSystems::CreateColliderRenders),
-            );
+            )
+            .add_systems(Update, super::draw_debug_render);
 
         // .add_systems(
         //     CoreStage::Update,
@@ -1,32 +1,75 @@
+use super::gateway::{self, ClusterEndpoint, Runtime};
 use crate::cli::CliArgs;
 use crate::rapier::dynamics::RigidBodyHandle;
 use crate::utils::Vect;
 use bevy::prelude::Resource;
 use bevy::utils::Uuid;
-use dashmap::DashMap;
 use futures::{stream, StreamExt};
 use rapier::geometry::HalfSpace;
-use rapier::math::Vector;
+use rapier::math::{Point, Real, Vector};
 use rapier::parry::bounding_volume::{Aabb, BoundingVolume};
-use sled::Atomic;
+use rapier::parry::query::PointQuery;
 use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::sync::atomic::{AtomicBool, AtomicUsize};
 use std::sync::Arc;
-use steadyum_api_types::env::CONFIG;
-use steadyum_api_types::messages::{BodyAssignment, ImpulseJointAssignment};
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use steadyum_api_types::env::{ControlPlane, CONFIG};
+use steadyum_api_types::forces::ForceGenerator;
+use steadyum_api_types::messages::{
+    BodyAssignment, ClientInterest, ClientLod, ImpulseJointAssignment,
+    CLIENT_INTEREST_REFRESH_PERIOD,
+};
 use steadyum_api_types::objects::{
-    ClientBodyObject, ClientBodyObjectSet, ColdBodyObject, RegionList, WarmBodyObject,
+    ClientBodyObject, ClientBodyObjectSet, ClientColdBodyObject, ColdBodyObject, RegionList,
+    SceneInfo, WarmBodyObject,
+};
+use steadyum_api_types::partitionner::{SceneSettings, SceneUuid, TimeScale, NUM_INTERNAL_STEPS};
+use steadyum_api_types::serialization::ObjectSetEncoding;
+#[cfg(not(target_arch = "wasm32"))]
+use steadyum_api_types::serialization::{
+    deserialize_auto, deserialize_client_objects, QuantizedParticleSet,
 };
-use steadyum_api_types::partitionner::SceneUuid;
-use steadyum_api_types::region_db::AsyncPartitionnerServer;
-use steadyum_api_types::serialization::deserialize;
 use steadyum_api_types::simulation::SimulationBounds;
-use steadyum_api_types::zenoh::ZenohContext;
+#[cfg(not(target_arch = "wasm32"))]
+use steadyum_api_types::zenoh::{client_interest_key, ZenohContext};
+use steadyum_client::{
+    ClientInputBatcher, ObjectSetSubscription, SteadyumClient, SubscriptionEvent,
+};
 use tokio::sync::RwLock;
+#[cfg(not(target_arch = "wasm32"))]
 use zenoh::config::WhatAmI;
+#[cfg(not(target_arch = "wasm32"))]
 use zenoh::prelude::r#async::AsyncResolve;
+#[cfg(not(target_arch = "wasm32"))]
 use zenoh::prelude::SplitBuffer;
 
+/// Minimum delay between two client inputs sent to the partitionner.
+const CLIENT_INPUT_PERIOD: Duration = Duration::from_millis(50);
+/// How often the connection to the partitionner is checked while it is up.
+const CONNECTION_CHECK_PERIOD: Duration = Duration::from_secs(2);
+/// The delay before the first reconnection attempt, doubled after each failed attempt.
+const RECONNECT_MIN_DELAY: Duration = Duration::from_millis(250);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// The distance beyond which the regions aren’t fetched.
+const VIEW_DISTANCE: Real = 750.0;
+/// Half the angle of the view cone of the camera, in radians. Wider than its field of view so
+/// the regions are fetched before they come into view when the camera turns.
+const VIEW_HALF_ANGLE: Real = 1.2;
+/// Maximum number of regions fetched by an iteration of the polling loop. The farthest ones
+/// are fetched at a later iteration.
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_REGIONS_PER_POLL: usize = 64;
+/// Maximum number of region queries in flight at once in the polling loop.
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_CONCURRENT_REGION_QUERIES: usize = 16;
+/// Delay after which a region query is abandoned. The region is queried again at the next
+/// iteration of the polling loop.
+#[cfg(not(target_arch = "wasm32"))]
+const REGION_QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
 pub struct NewObjectCommand {
     pub uuid: Uuid,
     // TODO: keep this?
@@ -44,6 +87,46 @@ pub struct LatestBodyData {
     pub bounds: SimulationBounds,
     pub timestamp: u64,
     pub data: ClientBodyObject,
+    pub cold: ClientColdBodyObject,
+    /// Is this body reported by the region it just left instead of its actual owner?
+    pub ghost: bool,
+}
+
+/// Stats of a visible region, displayed by the region overlay.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct RegionStats {
+    pub num_bodies: usize,
+    /// The step of the last objects read from this region.
+    pub timestamp: u64,
+}
+
+/// The state of the connection of the viewer to its cluster, displayed by the main menu.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    #[default]
+    Connected,
+    /// The partitionner is unreachable, or the viewer is switching to another endpoint.
+    Reconnecting { attempt: u32 },
+}
+
+/// The client of the cluster, replaced when the viewer reconnects or switches to another
+/// endpoint.
+#[derive(Clone)]
+pub struct SharedClient(Arc<std::sync::RwLock<Arc<SteadyumClient>>>);
+
+impl SharedClient {
+    fn new(client: SteadyumClient) -> Self {
+        Self(Arc::new(std::sync::RwLock::new(Arc::new(client))))
+    }
+
+    /// The current client. Requests already sent keep using the previous one.
+    pub fn get(&self) -> Arc<SteadyumClient> {
+        self.0.read().unwrap().clone()
+    }
+
+    fn set(&self, client: SteadyumClient) {
+        *self.0.write().unwrap() = Arc::new(client);
+    }
 }
 
 #[derive(Copy, Clone, Debug, Default)]
@@ -60,6 +143,44 @@ impl CameraPos {
 
         SimulationBounds::intersecting_aabb(camera_aabb, SimulationBounds::DEFAULT_WIDTH)
     }
+
+    /// The box containing every region the camera can see.
+    pub fn view_aabb(&self) -> Aabb {
+        Aabb::from_half_extents(self.position.into(), Vector::repeat(VIEW_DISTANCE))
+    }
+
+    /// The distance between the camera and the closest point of the region.
+    pub fn distance_to_region(&self, bounds: &SimulationBounds) -> Real {
+        bounds
+            .aabb()
+            .distance_to_local_point(&self.position.into(), true)
+    }
+
+    /// Whether the region intersects the view cone of the camera, up to [`VIEW_DISTANCE`].
+    ///
+    /// The region is approximated by its bounding sphere, so a few regions just outside of the
+    /// cone are seen too. Without a view direction, e.g., in 2D, every region close enough is
+    /// seen.
+    pub fn sees_region(&self, bounds: &SimulationBounds) -> bool {
+        let aabb = bounds.aabb();
+        let radius = aabb.half_extents().norm();
+        let position: Point<Real> = self.position.into();
+        let to_region = aabb.center() - position;
+        let distance = to_region.norm();
+
+        if distance <= radius {
+            return true;
+        }
+        if distance - radius > VIEW_DISTANCE {
+            return false;
+        }
+
+        let dir: Vector<Real> = self.dir.into();
+        let Some(dir) = dir.try_normalize(1.0e-6) else {
+            return true;
+        };
+        to_region.angle(&dir) <= VIEW_HALF_ANGLE + (radius / distance).asin()
+    }
 }
 
 #[derive(Default, Debug)]
@@ -68,6 +189,40 @@ pub struct DbStats {
     pub num_visible_regions: AtomicUsize,
     pub num_objects_read: AtomicUsize,
     pub total_db_read_time_ms: AtomicUsize,
+    pub num_objects_inserted: AtomicUsize,
+    pub num_objects_to_insert: AtomicUsize,
+    /// Time, in microseconds, spent waiting for the objects of the regions during the last read.
+    pub query_latency_us: AtomicUsize,
+    pub bytes_per_second: AtomicUsize,
+    /// Number of regions queried during the last read.
+    pub num_regions_polled: AtomicUsize,
+}
+
+/// Measures the download throughput of the viewer over one-second windows.
+struct ThroughputMeter {
+    bytes: usize,
+    since: instant::Instant,
+}
+
+impl ThroughputMeter {
+    fn new() -> Self {
+        Self {
+            bytes: 0,
+            since: instant::Instant::now(),
+        }
+    }
+
+    fn record(&mut self, bytes: usize, stats: &DbStats) {
+        self.bytes += bytes;
+        let elapsed = self.since.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            let rate = self.bytes as f64 / elapsed.as_secs_f64();
+            stats
+                .bytes_per_second
+                .store(rate as usize, std::sync::atomic::Ordering::SeqCst);
+            *self = Self::new();
+        }
+    }
 }
 
 #[derive(Resource)]
@@ -76,34 +231,165 @@ pub struct DbContext {
     pub camera: Arc<RwLock<CameraPos>>,
     pub uuid2body: Arc<RwLock<Option<HashMap<Uuid, LatestBodyData>>>>,
     pub region_list: Arc<RwLock<RegionList>>,
-    pub partitionner: Arc<AsyncPartitionnerServer>,
+    pub region_stats: Arc<RwLock<HashMap<SimulationBounds, RegionStats>>>,
+    /// The scenes known by the partitionner, as of the last time the list was reloaded.
+    pub network_scenes: Arc<RwLock<Vec<SceneInfo>>>,
+    /// The force generators of the current scene, fetched when the force overlay is enabled.
+    pub force_generators: Arc<RwLock<Vec<ForceGenerator>>>,
+    /// The fluid particles of the visible regions, and their radius.
+    pub particles: Arc<RwLock<HashMap<SimulationBounds, (Real, Vec<Point<Real>>)>>>,
+    pub client: SharedClient,
+    /// The endpoint the viewer should be connected to. Changing it switches the viewer to
+    /// another cluster.
+    pub endpoint: Arc<RwLock<ClusterEndpoint>>,
+    pub connection_status: Arc<RwLock<ConnectionStatus>>,
+    /// Coalesces the client inputs emitted every frame.
+    pub client_inputs: Arc<ClientInputBatcher>,
     pub scene: Arc<RwLock<SceneUuid>>,
     pub read_new_region: Arc<AtomicBool>,
     pub stats: Arc<DbStats>,
     pub is_running: bool,
-    pub runtime: tokio::runtime::Runtime,
+    /// The time scale last requested by this viewer.
+    pub time_scale: TimeScale,
+    /// The time scale of the current scene, as last streamed with its regions or set by this
+    /// viewer.
+    pub scene_time_scale: Arc<RwLock<TimeScale>>,
+    pub runtime: Runtime,
+}
+
+impl DbContext {
+    /// Runs a request to the partitionner.
+    ///
+    /// Native viewers wait for it to complete. The browser can’t block, so it runs in the
+    /// background on wasm.
+    pub fn request(&self, task: impl Future<Output = anyhow::Result<()>> + 'static) {
+        let task = async move {
+            if let Err(e) = task.await {
+                log::error!("Request to the partitionner failed: {e}");
+            }
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        crate::block_on(task);
+        #[cfg(target_arch = "wasm32")]
+        self.runtime.spawn(task);
+    }
 }
 
-pub fn spawn_db_thread(local_dev_mode: bool) -> DbContext {
+#[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+pub fn spawn_db_thread(
+    local_dev_mode: bool,
+    stream_client_objects: bool,
+    client_objects_encoding: ObjectSetEncoding,
+    seed: u64,
+) -> DbContext {
     let (commands_snd, commands_rcv) = async_channel::unbounded();
     let camera = Arc::new(RwLock::new(CameraPos::default()));
     let uuid2body = Arc::new(RwLock::new(None));
     let region_list = Arc::new(RwLock::new(RegionList::default()));
-    let partitionner = Arc::new(AsyncPartitionnerServer::new().unwrap());
+    let region_stats = Arc::new(RwLock::new(HashMap::new()));
+    let particles = Arc::new(RwLock::new(HashMap::new()));
+    let endpoint = ClusterEndpoint::from_env().unwrap();
     let scene = Arc::new(RwLock::new(SceneUuid(Uuid::new_v4())));
-    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let runtime = Runtime::new().unwrap();
     let read_new_region = Arc::new(AtomicBool::new(true));
     let stats = Arc::new(DbStats::default());
+    let scene_time_scale = Arc::new(RwLock::new(TimeScale::default()));
+    #[cfg(not(target_arch = "wasm32"))]
+    let whatami = if local_dev_mode {
+        WhatAmI::Peer
+    } else {
+        WhatAmI::Client
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    let client = runtime.block_on(connect(&endpoint, whatami)).unwrap();
+    #[cfg(target_arch = "wasm32")]
+    let client = gateway::connect_client(&endpoint).unwrap();
+    let client = SharedClient::new(client);
+    let endpoint = Arc::new(RwLock::new(endpoint));
+    let connection_status = Arc::new(RwLock::new(ConnectionStatus::Connected));
 
     {
-        let partitionner = partitionner.clone();
+        let client = client.clone();
+        let endpoint = endpoint.clone();
+        let connection_status = connection_status.clone();
+
+        runtime.spawn(async move {
+            /*
+             * Connection loop.
+             */
+            let mut connected_to = endpoint.read().await.clone();
+            let mut attempt = 0;
+            let mut delay = RECONNECT_MIN_DELAY;
+
+            loop {
+                gateway::sleep(if attempt == 0 {
+                    CONNECTION_CHECK_PERIOD
+                } else {
+                    delay
+                })
+                .await;
+
+                let requested = endpoint.read().await.clone();
+                if requested == connected_to {
+                    let current = client.get();
+                    if current.partitionner().heartbeat().await.is_ok() {
+                        if attempt > 0 {
+                            log::info!("Reconnected to {}.", requested.partitionner_addr);
+                        }
+                        attempt = 0;
+                        delay = RECONNECT_MIN_DELAY;
+                        *connection_status.write().await = ConnectionStatus::Connected;
+                        continue;
+                    }
+                }
+
+                attempt += 1;
+                *connection_status.write().await = ConnectionStatus::Reconnecting { attempt };
+
+                #[cfg(not(target_arch = "wasm32"))]
+                let new_client = connect(&requested, whatami).await;
+                #[cfg(target_arch = "wasm32")]
+                let new_client = gateway::connect_client(&requested);
+
+                match new_client {
+                    Ok(new_client) if new_client.partitionner().heartbeat().await.is_ok() => {
+                        log::info!("Connected to {}.", requested.partitionner_addr);
+                        client.set(new_client);
+                        connected_to = requested;
+                        attempt = 0;
+                        delay = RECONNECT_MIN_DELAY;
+                        *connection_status.write().await = ConnectionStatus::Connected;
+                    }
+                    Ok(_) => {
+                        log::warn!(
+                            "{} is unreachable, retrying in {delay:?}.",
+                            requested.partitionner_addr
+                        );
+                        delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+                    }
+                    Err(e) => {
+                        log::error!("Failed to connect to {}: {e}", requested.partitionner_addr);
+                        delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+                    }
+                }
+            }
+        });
+    }
+
+    {
+        let client = client.clone();
         let scene = scene.clone();
+        let stats = stats.clone();
+        let scene_time_scale = scene_time_scale.clone();
 
         runtime.spawn(async move {
             /*
              * Command loop.
              */
             while let Ok(command) = commands_rcv.recv().await {
+                let client = client.get();
+
                 match command {
                     DbCommand::NewScene { objects } => {
                         let scene_uuid = *scene.read().await;
@@ -121,24 +407,50 @@ pub fn spawn_db_thread(local_dev_mode: bool) -> DbContext {
                             scene_aabb.merge(&obj_aabb);
                         }
 
-                        partitionner
-                            .create_scene(scene_uuid, scene_aabb)
+                        let settings = SceneSettings {
+                            time_scale: *scene_time_scale.read().await,
+                            ..SceneSettings::default()
+                        };
+                        if let Err(e) = client
+                            .create_scene(
+                                scene_uuid,
+                                scene_aabb,
+                                client_objects_encoding,
+                                seed,
+                                settings,
+                            )
                             .await
-                            .unwrap();
-                        for objects in objects.chunks(1024) {
-                            let bodies_to_insert: Vec<_> = objects
-                                .iter()
-                                .map(|obj| BodyAssignment {
-                                    uuid: obj.uuid,
-                                    cold: obj.cold_object.clone(),
-                                    warm: obj.warm_object.clone(),
-                                })
-                                .collect();
-                            dbg!("Sending objects query to the partitionner!");
-                            partitionner
-                                .insert_objects(scene_uuid, bodies_to_insert)
-                                .await
-                                .unwrap();
+                        {
+                            log::error!("Failed to create the scene: {e}");
+                            continue;
+                        }
+
+                        let bodies_to_insert: Vec<_> = objects
+                            .iter()
+                            .map(|obj| BodyAssignment {
+                                uuid: obj.uuid,
+                                cold: obj.cold_object.clone(),
+                                warm: obj.warm_object.clone(),
+                                epoch: 0,
+                            })
+                            .collect();
+                        stats
+                            .num_objects_inserted
+                            .store(0, std::sync::atomic::Ordering::SeqCst);
+                        stats
+                            .num_objects_to_insert
+                            .store(bodies_to_insert.len(), std::sync::atomic::Ordering::SeqCst);
+
+                        dbg!("Sending objects query to the partitionner!");
+                        if let Err(e) = client
+                            .insert_bodies_chunked(scene_uuid, bodies_to_insert, 1024, |progress| {
+                                stats
+                                    .num_objects_inserted
+                                    .store(progress.inserted, std::sync::atomic::Ordering::SeqCst);
+                            })
+                            .await
+                        {
+                            log::error!("Failed to insert the bodies: {e}");
                         }
                     }
                 }
@@ -148,30 +460,209 @@ pub fn spawn_db_thread(local_dev_mode: bool) -> DbContext {
         });
     }
 
-    {
+    // The browser can only reach the cluster through the WebSocket of the partitionner.
+    let stream_client_objects = stream_client_objects || cfg!(target_arch = "wasm32");
+    if stream_client_objects {
+        let region_list = region_list.clone();
+        let region_stats = region_stats.clone();
+        let client = client.clone();
+        let scene = scene.clone();
+        let uuid2body = uuid2body.clone();
+        let mut fetched_uuid2body = HashMap::new();
+        let camera = camera.clone();
+        let read_new_region = read_new_region.clone();
+        let stats = stats.clone();
+        let scene_time_scale = scene_time_scale.clone();
+
+        runtime.spawn(async move {
+            let mut known_region_timestamps = HashMap::new();
+            let mut cold_cache = HashMap::new();
+            let mut subscription: Option<ObjectSetSubscription> = None;
+            // The client the subscription was opened with.
+            let mut subscribed_with: Option<Arc<SteadyumClient>> = None;
+            let mut throughput = ThroughputMeter::new();
+
+            /*
+             * Position streaming loop.
+             */
+            loop {
+                let t0 = instant::Instant::now();
+
+                // Subscribe again once the viewer reconnected, or switched to another endpoint.
+                let client = client.get();
+                if !subscribed_with
+                    .as_ref()
+                    .map(|prev| Arc::ptr_eq(prev, &client))
+                    .unwrap_or(false)
+                {
+                    subscription = None;
+                    subscribed_with = Some(client.clone());
+                }
+
+                let scene = *scene.read().await;
+                let camera_pos = camera.read().await.clone();
+                let view_aabb = camera_pos.view_aabb();
+
+                // Update the subscription if we changed scene, or if the camera moved too much.
+                let needs_update = match &subscription {
+                    Some(sub) => {
+                        sub.scene() != scene
+                            || (sub.view().center() - view_aabb.center()).norm()
+                                > SimulationBounds::DEFAULT_WIDTH as Real
+                    }
+                    None => true,
+                };
+
+                if needs_update {
+                    if subscription.as_ref().map(|sub| sub.scene()) != Some(scene) {
+                        fetched_uuid2body.clear();
+                        known_region_timestamps.clear();
+                        cold_cache.clear();
+                    }
+
+                    let subscribed = async {
+                        match subscription.as_mut() {
+                            Some(sub) => sub.update(scene, view_aabb).await,
+                            None => {
+                                let mut sub = client.subscribe(scene, view_aabb).await?;
+                                sub.set_lod(Some(ClientLod::default())).await?;
+                                subscription = Some(sub);
+                                Ok(())
+                            }
+                        }
+                    }
+                    .await;
+
+                    if let Err(e) = subscribed {
+                        log::error!("Failed to subscribe to the client objects: {e}");
+                        subscription = None;
+                        gateway::sleep(RECONNECT_MIN_DELAY).await;
+                        continue;
+                    }
+                }
+
+                let Some(sub) = subscription.as_mut() else {
+                    continue;
+                };
+
+                // Don’t wait for too long so the subscription keeps up with the camera.
+                let data = match sub.next(Duration::from_millis(100)).await {
+                    Ok(SubscriptionEvent::Region(data)) => data,
+                    Ok(SubscriptionEvent::Idle) => continue,
+                    Ok(SubscriptionEvent::Closed) => {
+                        log::warn!("The client objects stream closed, subscribing again.");
+                        subscription = None;
+                        gateway::sleep(RECONNECT_MIN_DELAY).await;
+                        continue;
+                    }
+                    Err(e) => {
+                        log::error!("The client objects stream failed: {e}");
+                        subscription = None;
+                        gateway::sleep(RECONNECT_MIN_DELAY).await;
+                        continue;
+                    }
+                };
+
+                stats.query_latency_us.store(
+                    t0.elapsed().as_micros() as usize,
+                    std::sync::atomic::Ordering::SeqCst,
+                );
+                throughput.record(data.payload_len, &stats);
+                *scene_time_scale.write().await = data.time_scale;
+
+                let set = data.objects;
+                // The partitionner pushes the regions one at a time.
+                stats
+                    .num_regions_polled
+                    .store(1, std::sync::atomic::Ordering::SeqCst);
+
+                if known_region_timestamps
+                    .insert(data.region, set.timestamp)
+                    .is_none()
+                {
+                    read_new_region.store(true, std::sync::atomic::Ordering::SeqCst);
+
+                    // The stream only reports the sleeping bodies when they fall asleep, so
+                    // fetch everything the region already has.
+                    match client.full_sync(scene, data.region).await {
+                        Ok(full) => {
+                            insert_client_objects(
+                                &mut fetched_uuid2body,
+                                &mut cold_cache,
+                                data.region,
+                                full,
+                            );
+                        }
+                        Err(e) => log::error!("Failed to sync region {:?}: {e}", data.region),
+                    }
+                }
+
+                stats
+                    .num_objects_read
+                    .store(set.objects.len(), std::sync::atomic::Ordering::SeqCst);
+                // NOTE: the partitionner sends the cold objects we don’t know about, so there
+                //       shouldn’t be anything missing here.
+                insert_client_objects(&mut fetched_uuid2body, &mut cold_cache, data.region, set);
+
+                let view_aabb = sub.view();
+                known_region_timestamps.retain(|region, _| region.intersects_aabb(&view_aabb));
+                retain_up_to_date_bodies(&mut fetched_uuid2body, &known_region_timestamps);
+
+                stats.total_num_regions.store(
+                    known_region_timestamps.len(),
+                    std::sync::atomic::Ordering::SeqCst,
+                );
+                stats.num_visible_regions.store(
+                    known_region_timestamps.len(),
+                    std::sync::atomic::Ordering::SeqCst,
+                );
+
+                *uuid2body.write().await = Some(fetched_uuid2body.clone());
+                region_list.write().await.bounds =
+                    known_region_timestamps.keys().copied().collect();
+                *region_stats.write().await = compute_region_stats(
+                    known_region_timestamps.keys(),
+                    &fetched_uuid2body,
+                    &known_region_timestamps,
+                );
+
+                stats.total_db_read_time_ms.store(
+                    t0.elapsed().as_millis() as usize,
+                    std::sync::atomic::Ordering::SeqCst,
+                );
+            }
+        });
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if !stream_client_objects {
         let region_list = region_list.clone();
-        let partitionner = partitionner.clone();
+        let region_stats = region_stats.clone();
+        let particles = particles.clone();
+        let client = client.clone();
         let scene = scene.clone();
         let uuid2body = uuid2body.clone();
         let mut fetched_uuid2body = HashMap::new();
         let camera = camera.clone();
         let read_new_region = read_new_region.clone();
         let stats = stats.clone();
+        let endpoint = endpoint.clone();
 
         // let camera = camera.clone();
         runtime.spawn(async move {
             let mut prev_region_list = HashSet::new();
             let mut known_region_timestamps = HashMap::new();
+            let mut cold_cache = HashMap::new();
+            let mut last_scene = None;
+            let client_uuid = Uuid::new_v4();
+            let mut last_interest_refresh: Option<std::time::Instant> = None;
+            let mut throughput = ThroughputMeter::new();
 
             /*
              * Init S3
              */
-            let whatami = if local_dev_mode {
-                WhatAmI::Peer
-            } else {
-                WhatAmI::Client
-            };
-            let mut zenoh = ZenohContext::new(whatami, Some(CONFIG.zenoh_router.clone()), false)
+            let mut zenoh_router = endpoint.read().await.zenoh_router.clone();
+            let mut zenoh = ZenohContext::new(whatami, Some(zenoh_router.clone()), false)
                 .await
                 .unwrap();
 
@@ -181,11 +672,33 @@ pub fn spawn_db_thread(local_dev_mode: bool) -> DbContext {
             loop {
                 let t0 = std::time::Instant::now();
 
+                let client = client.get();
+                let requested_router = endpoint.read().await.zenoh_router.clone();
+                if requested_router != zenoh_router {
+                    match ZenohContext::new(whatami, Some(requested_router.clone()), false).await {
+                        Ok(new_zenoh) => {
+                            zenoh = new_zenoh;
+                            zenoh_router = requested_router;
+                        }
+                        Err(e) => {
+                            log::error!("Failed to connect to {requested_router}: {e}");
+                            gateway::sleep(RECONNECT_MIN_DELAY).await;
+                            continue;
+                        }
+                    }
+                }
+
                 let scene = *scene.read().await;
 
+                if last_scene != Some(scene) {
+                    known_region_timestamps.clear();
+                    cold_cache.clear();
+                    last_scene = Some(scene);
+                }
+
                 // TODO: we should be able to query the scene with an AABB or something.
                 let mut new_region_list: RegionList =
-                    partitionner.list_regions(scene).await.unwrap_or_default();
+                    client.list_regions(scene).await.unwrap_or_default();
 
                 stats.total_num_regions.store(
                     new_region_list.bounds.len(),
@@ -193,66 +706,137 @@ pub fn spawn_db_thread(local_dev_mode: bool) -> DbContext {
                 );
 
                 let camera_pos = camera.read().await.clone();
-                let view_aabb =
-                    Aabb::from_half_extents(camera_pos.position.into(), Vector::repeat(750.0));
+                let view_aabb = camera_pos.view_aabb();
                 new_region_list
                     .bounds
-                    .retain(|bounds| bounds.intersects_aabb(&view_aabb));
+                    .retain(|bounds| camera_pos.sees_region(bounds));
+
+                // Let the runners know what we are looking at.
+                if last_interest_refresh
+                    .map(|t| t.elapsed() >= CLIENT_INTEREST_REFRESH_PERIOD)
+                    .unwrap_or(true)
+                {
+                    let interest = ClientInterest {
+                        view: view_aabb,
+                        lod: Some(ClientLod::default()),
+                    };
+                    if let Err(e) = zenoh
+                        .put(&client_interest_key(scene, client_uuid), &interest)
+                        .await
+                    {
+                        log::error!("Failed to publish the client interest: {e}");
+                    }
+                    last_interest_refresh = Some(std::time::Instant::now());
+                }
 
                 stats.num_visible_regions.store(
                     new_region_list.bounds.len(),
                     std::sync::atomic::Ordering::SeqCst,
                 );
 
-                let replies: Vec<_> = stream::iter(new_region_list.bounds.iter())
-                    .then(|bounds| async {
-                        let storage_key = bounds.runner_client_objects_key(
-                            scene,
-                            known_region_timestamps.get(bounds).copied().unwrap_or(0),
-                        );
-                        zenoh.session.get(&storage_key).res_async().await
+                // Skip the regions whose bodies didn’t change since we last fetched them, and fetch
+                // the closest ones first.
+                let mut polled: Vec<_> = new_region_list
+                    .bounds
+                    .iter()
+                    .filter(|bounds| {
+                        match (
+                            new_region_list.activity(bounds),
+                            known_region_timestamps.get(bounds),
+                        ) {
+                            (Some(activity), Some(timestamp)) => {
+                                !activity.unchanged_since(*timestamp / NUM_INTERNAL_STEPS)
+                            }
+                            _ => true,
+                        }
                     })
-                    .collect()
-                    .await;
+                    .copied()
+                    .collect();
+                polled.sort_by(|a, b| {
+                    camera_pos
+                        .distance_to_region(a)
+                        .total_cmp(&camera_pos.distance_to_region(b))
+                });
+                polled.truncate(MAX_REGIONS_PER_POLL);
 
-                let mut num_objects_read = 0;
-                for (reply, bounds) in replies.into_iter().zip(new_region_list.bounds.iter()) {
-                    let Ok(reply) = reply else { continue };
+                let t_query = std::time::Instant::now();
+                let queries: Vec<_> = polled
+                    .iter()
+                    .map(|bounds| {
+                        let storage_key = match known_region_timestamps.get(bounds) {
+                            Some(timestamp) => bounds.runner_client_objects_key(
+                                scene,
+                                *timestamp,
+                                Some(&view_aabb),
+                            ),
+                            None => bounds.runner_full_client_objects_key(scene, Some(&view_aabb)),
+                        };
+                        (*bounds, storage_key)
+                    })
+                    .collect();
+                let mut replies = stream::iter(queries)
+                    .map(|(bounds, storage_key)| {
+                        query_region(&zenoh, bounds, storage_key, move |payload| {
+                            deserialize_client_objects(payload, &bounds)
+                        })
+                    })
+                    .buffer_unordered(MAX_CONCURRENT_REGION_QUERIES);
 
-                    while let Ok(reply) = reply.recv() {
-                        let Ok(sample) = reply.sample else { continue };
-                        let payload = sample.value.payload.contiguous();
-                        let data: ClientBodyObjectSet = deserialize(&payload).unwrap();
+                // NOTE: the objects of a region are inserted while the other regions are still
+                //       being queried.
+                let mut num_objects_read = 0;
+                while let Some(reply) = replies.next().await {
+                    let Some(reply) = reply else { continue };
+                    throughput.record(reply.num_bytes, &stats);
 
-                        known_region_timestamps.insert(*bounds, data.timestamp);
+                    let mut complete = reply.complete;
+                    for data in reply.values {
+                        known_region_timestamps.insert(reply.bounds, data.timestamp);
                         num_objects_read += data.objects.len();
 
-                        for object in data.objects {
-                            let uuid = object.uuid;
-                            let data = LatestBodyData {
-                                bounds: *bounds,
-                                timestamp: data.timestamp,
-                                data: object,
-                            };
+                        complete &= insert_client_objects(
+                            &mut fetched_uuid2body,
+                            &mut cold_cache,
+                            reply.bounds,
+                            data,
+                        );
+                    }
 
-                            fetched_uuid2body.insert(uuid, data);
-                        }
+                    if !complete {
+                        // We are missing some objects, ask for all of them next time.
+                        known_region_timestamps.insert(reply.bounds, 0);
                     }
                 }
 
-                fetched_uuid2body.retain(|_, body| {
-                    if let Some(actual_timestamp) = known_region_timestamps.get(&body.bounds) {
-                        if body.data.sleep_start_frame.is_some() {
-                            /* Keep the body, just update the timestamp, it is sleeping so we don’t get new updates. */
-                            body.timestamp = *actual_timestamp;
-                        }
-
-                        body.timestamp >= *actual_timestamp
-                    } else {
-                        false
+                // NOTE: only the regions of scenes with a fluid reply.
+                let mut particle_replies = stream::iter(new_region_list.bounds.iter().copied())
+                    .map(|bounds| {
+                        let storage_key = bounds.runner_particles_key(scene);
+                        query_region(&zenoh, bounds, storage_key, move |payload| {
+                            let set = deserialize_auto::<QuantizedParticleSet>(payload)?;
+                            Ok((set.particle_radius, set.decode(&bounds)))
+                        })
+                    })
+                    .buffer_unordered(MAX_CONCURRENT_REGION_QUERIES);
+                let mut fetched_particles = HashMap::new();
+                while let Some(reply) = particle_replies.next().await {
+                    let Some(reply) = reply else { continue };
+                    throughput.record(reply.num_bytes, &stats);
+                    if let Some(particles) = reply.values.into_iter().last() {
+                        fetched_particles.insert(reply.bounds, particles);
                     }
-                });
+                }
+                *particles.write().await = fetched_particles;
 
+                retain_up_to_date_bodies(&mut fetched_uuid2body, &known_region_timestamps);
+
+                stats.query_latency_us.store(
+                    t_query.elapsed().as_micros() as usize,
+                    std::sync::atomic::Ordering::SeqCst,
+                );
+                stats
+                    .num_regions_polled
+                    .store(polled.len(), std::sync::atomic::Ordering::SeqCst);
                 stats
                     .num_objects_read
                     .store(num_objects_read, std::sync::atomic::Ordering::SeqCst);
@@ -268,14 +852,17 @@ pub fn spawn_db_thread(local_dev_mode: bool) -> DbContext {
                 prev_region_list.extend(new_region_list.bounds.iter().copied());
 
                 // println!("Apply async time: {}", t0.elapsed().as_secs_f32());
-                known_region_timestamps.retain(|region, _| {
-                    region.intersects_aabb(&view_aabb)
-                });
+                known_region_timestamps.retain(|region, _| prev_region_list.contains(region));
                 fetched_uuid2body.retain(|_, body| {
                     known_region_timestamps.contains_key(&body.bounds)
                 });
 
                 *uuid2body.write().await = Some(fetched_uuid2body.clone());
+                *region_stats.write().await = compute_region_stats(
+                    new_region_list.bounds.iter(),
+                    &fetched_uuid2body,
+                    &known_region_timestamps,
+                );
                 *region_list.write().await = new_region_list;
 
                 stats.total_db_read_time_ms.store(
@@ -292,10 +879,227 @@ pub fn spawn_db_thread(local_dev_mode: bool) -> DbContext {
         uuid2body,
         camera,
         region_list,
+        region_stats,
+        network_scenes: Arc::new(RwLock::new(vec![])),
+        force_generators: Arc::new(RwLock::new(vec![])),
+        particles,
         read_new_region,
-        partitionner,
+        client,
+        endpoint,
+        connection_status,
+        client_inputs: Arc::new(ClientInputBatcher::new(CLIENT_INPUT_PERIOD)),
         is_running: false,
+        time_scale: TimeScale::default(),
+        scene_time_scale,
         runtime,
         stats,
     }
 }
+
+/// Connects to the cluster at `endpoint`, publishing the client inputs on zenoh if it is the
+/// control plane.
+#[cfg(not(target_arch = "wasm32"))]
+async fn connect(endpoint: &ClusterEndpoint, whatami: WhatAmI) -> anyhow::Result<SteadyumClient> {
+    let client = gateway::connect_client(endpoint)?;
+
+    if CONFIG.control_plane == ControlPlane::Zenoh {
+        let zenoh = ZenohContext::new(whatami, Some(endpoint.zenoh_router.clone()), false).await?;
+        Ok(client.with_control_plane(Arc::new(zenoh)))
+    } else {
+        Ok(client)
+    }
+}
+
+/// The decoded replies to the query of a region.
+#[cfg(not(target_arch = "wasm32"))]
+struct RegionReplies<T> {
+    bounds: SimulationBounds,
+    values: Vec<T>,
+    /// The size of the replies, before decoding.
+    num_bytes: usize,
+    /// `false` if some replies couldn’t be decoded.
+    complete: bool,
+}
+
+/// Queries the storage key of a region and decodes its replies as they arrive, so the replies
+/// of the other regions queried concurrently are received meanwhile.
+///
+/// Returns `None` if the query failed, or didn’t complete within [`REGION_QUERY_TIMEOUT`].
+#[cfg(not(target_arch = "wasm32"))]
+async fn query_region<T>(
+    zenoh: &ZenohContext,
+    bounds: SimulationBounds,
+    storage_key: String,
+    decode: impl Fn(&[u8]) -> anyhow::Result<T>,
+) -> Option<RegionReplies<T>> {
+    let query = async {
+        let receiver = zenoh.session.get(&storage_key).res_async().await.ok()?;
+        let mut replies = RegionReplies {
+            bounds,
+            values: vec![],
+            num_bytes: 0,
+            complete: true,
+        };
+
+        // NOTE: the objects of dense regions are split across several replies.
+        while let Ok(reply) = receiver.recv_async().await {
+            let Ok(sample) = reply.sample else { continue };
+            let payload = sample.value.payload.contiguous();
+            replies.num_bytes += payload.len();
+            match decode(&payload) {
+                Ok(value) => replies.values.push(value),
+                Err(e) => {
+                    log::error!("Failed to decode a reply of region {bounds:?}: {e}");
+                    replies.complete = false;
+                }
+            }
+        }
+
+        Some(replies)
+    };
+
+    match tokio::time::timeout(REGION_QUERY_TIMEOUT, query).await {
+        Ok(replies) => replies,
+        Err(_) => {
+            log::warn!("Timed out querying {storage_key}.");
+            None
+        }
+    }
+}
+
+/// Inserts the objects read from a region, joining them with their cached cold data.
+///
+/// Returns `false` if the cold data of some objects is unknown. These objects are skipped.
+fn insert_client_objects(
+    fetched_uuid2body: &mut HashMap<Uuid, LatestBodyData>,
+    cold_cache: &mut HashMap<Uuid, ClientColdBodyObject>,
+    bounds: SimulationBounds,
+    data: ClientBodyObjectSet,
+) -> bool {
+    let mut complete = true;
+
+    // The bodies missing from a partial set didn’t move, they are still up to date.
+    if data.partial {
+        for body in fetched_uuid2body.values_mut() {
+            if body.bounds == bounds && !body.ghost {
+                body.timestamp = data.timestamp;
+            }
+        }
+    }
+
+    for cold in data.cold_objects {
+        cold_cache.insert(cold.uuid, cold);
+    }
+
+    for object in data.objects {
+        let uuid = object.uuid;
+
+        // Ignore the stale copies of the bodies that migrated since, e.g., left behind by a
+        // region that crashed.
+        if let Some(body) = fetched_uuid2body.get(&uuid) {
+            if body.bounds != bounds && body.data.epoch > object.epoch {
+                continue;
+            }
+        }
+
+        let Some(cold) = cold_cache
+            .get(&uuid)
+            .filter(|cold| cold.shape_hash == object.shape_hash)
+        else {
+            complete = false;
+            continue;
+        };
+
+        let data = LatestBodyData {
+            bounds,
+            timestamp: data.timestamp,
+            data: object,
+            cold: cold.clone(),
+            ghost: false,
+        };
+
+        fetched_uuid2body.insert(uuid, data);
+    }
+
+    for ghost in data.ghosts {
+        let uuid = ghost.object.uuid;
+
+        // Prefer the data from the authoritative owner of the body if we have it.
+        if let Some(body) = fetched_uuid2body.get(&uuid) {
+            if !body.ghost
+                && (body.bounds == ghost.new_region || body.data.epoch > ghost.object.epoch)
+            {
+                continue;
+            }
+        }
+
+        // NOTE: don’t report ghosts with unknown cold data as incomplete, they
+        //       will be reported by their new owner soon anyway.
+        let Some(cold) = cold_cache
+            .get(&uuid)
+            .filter(|cold| cold.shape_hash == ghost.object.shape_hash)
+        else {
+            continue;
+        };
+
+        let data = LatestBodyData {
+            bounds,
+            timestamp: data.timestamp,
+            data: ghost.object,
+            cold: cold.clone(),
+            ghost: true,
+        };
+
+        fetched_uuid2body.insert(uuid, data);
+    }
+
+    complete
+}
+
+/// Removes the bodies that are no longer part of the latest data read from their region.
+fn compute_region_stats<'a>(
+    regions: impl Iterator<Item = &'a SimulationBounds>,
+    fetched_uuid2body: &HashMap<Uuid, LatestBodyData>,
+    known_region_timestamps: &HashMap<SimulationBounds, u64>,
+) -> HashMap<SimulationBounds, RegionStats> {
+    let mut stats: HashMap<_, _> = regions
+        .map(|region| {
+            let timestamp = known_region_timestamps.get(region).copied().unwrap_or(0);
+            (
+                *region,
+                RegionStats {
+                    num_bodies: 0,
+                    timestamp,
+                },
+            )
+        })
+        .collect();
+
+    for body in fetched_uuid2body.values() {
+        if !body.ghost {
+            if let Some(region_stats) = stats.get_mut(&body.bounds) {
+                region_stats.num_bodies += 1;
+            }
+        }
+    }
+
+    stats
+}
+
+fn retain_up_to_date_bodies(
+    fetched_uuid2body: &mut HashMap<Uuid, LatestBodyData>,
+    known_region_timestamps: &HashMap<SimulationBounds, u64>,
+) {
+    fetched_uuid2body.retain(|_, body| {
+        if let Some(actual_timestamp) = known_region_timestamps.get(&body.bounds) {
+            if body.data.sleep_start_frame.is_some() {
+                /* Keep the body, just update the timestamp, it is sleeping so we don’t get new updates. */
+                body.timestamp = *actual_timestamp;
+            }
+
+            body.timestamp >= *actual_timestamp
+        } else {
+            false
+        }
+    });
+}
@@ -0,0 +1,98 @@
+//! The parts of the networking of the viewer that differ between native and wasm builds.
+//!
+//! In the browser, the viewer can’t use zenoh or block on requests: it only talks to the
+//! partitionner, acting as a gateway, over HTTP and the client objects WebSocket.
+
+use std::time::Duration;
+use steadyum_api_types::env::CONFIG;
+use steadyum_client::SteadyumClient;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use tokio::runtime::Runtime;
+
+/// Runs the background tasks of the viewer on the event loop of the browser.
+#[cfg(target_arch = "wasm32")]
+pub struct Runtime;
+
+#[cfg(target_arch = "wasm32")]
+impl Runtime {
+    pub fn new() -> std::io::Result<Self> {
+        Ok(Self)
+    }
+
+    pub fn spawn<T: 'static>(&self, task: impl std::future::Future<Output = T> + 'static) {
+        wasm_bindgen_futures::spawn_local(async move {
+            let _ = task.await;
+        });
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+#[cfg(target_arch = "wasm32")]
+pub async fn sleep(duration: Duration) {
+    gloo_timers::future::sleep(duration).await
+}
+
+/// Where the viewer reaches its cluster.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClusterEndpoint {
+    pub partitionner_addr: String,
+    pub partitionner_port: u16,
+    /// Only used by native viewers.
+    pub zenoh_router: String,
+}
+
+impl ClusterEndpoint {
+    /// The endpoint configured by the environment.
+    ///
+    /// On wasm, the url of the partitionner can be given with the `gateway` parameter of the
+    /// page, e.g. `?gateway=https://cluster.example.com:3535`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        #[cfg_attr(not(target_arch = "wasm32"), allow(unused_mut))]
+        let mut result = Self {
+            partitionner_addr: CONFIG.partitionner_addr.clone(),
+            partitionner_port: CONFIG.partitionner_port,
+            zenoh_router: CONFIG.zenoh_router.clone(),
+        };
+
+        #[cfg(target_arch = "wasm32")]
+        if let Some(gateway) = page_parameter("gateway") {
+            let (addr, port) = match gateway.rsplit_once(':') {
+                Some((addr, port)) if !port.starts_with('/') => (addr.to_string(), port.parse()?),
+                _ => (gateway, CONFIG.partitionner_port),
+            };
+            result.partitionner_addr = addr;
+            result.partitionner_port = port;
+        }
+
+        Ok(result)
+    }
+}
+
+/// The client of the partitionner at `endpoint`.
+///
+/// The partitionner candidates of the environment are only tried for the endpoint it configures.
+pub fn connect_client(endpoint: &ClusterEndpoint) -> anyhow::Result<SteadyumClient> {
+    if endpoint.partitionner_addr == CONFIG.partitionner_addr
+        && endpoint.partitionner_port == CONFIG.partitionner_port
+    {
+        return SteadyumClient::new();
+    }
+
+    SteadyumClient::with_endpoint(
+        endpoint.partitionner_addr.clone(),
+        endpoint.partitionner_port,
+    )
+}
+
+#[cfg(target_arch = "wasm32")]
+fn page_parameter(name: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    web_sys::UrlSearchParams::new_with_str(&search)
+        .ok()?
+        .get(name)
+}
@@ -1,11 +1,16 @@
-#[cfg(not(target_arch = "wasm32"))]
-pub use db::{DbCommand, DbContext, DbStats, NewObjectCommand};
+pub use db::{ConnectionStatus, DbCommand, DbContext, DbStats, NewObjectCommand};
+pub use gateway::ClusterEndpoint;
+pub use picking::Picking;
+pub use spawner::{SpawnShape, Spawner};
 
 pub use plugin::{SaveFileData, StoragePlugin};
+pub use position_interpolation::PositionInterpolation;
 
-#[cfg(not(target_arch = "wasm32"))]
 mod db;
+mod gateway;
+mod picking;
 mod plugin;
 mod position_interpolation;
-#[cfg(not(target_arch = "wasm32"))]
+mod region_overlay;
+mod spawner;
 mod systems;
@@ -0,0 +1,198 @@
+use crate::render::ColliderRenderShape;
+use crate::storage::db::DbContext;
+use crate::storage::position_interpolation::PositionInterpolation;
+use crate::utils::{transform_to_iso, PhysicsObject, Vect};
+use crate::MainCamera;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::EguiContexts;
+use instant::Instant;
+use rapier::math::{Isometry, Point, Real};
+use rapier::prelude::Ray;
+use std::time::Duration;
+use steadyum_api_types::messages::BodyInput;
+
+/// Minimum delay between two inputs sent while dragging a body.
+const DRAG_INPUT_PERIOD: Duration = Duration::from_millis(50);
+/// Stiffness, per unit of mass, of the spring pulling the dragged body toward the cursor.
+const DRAG_STIFFNESS: Real = 25.0;
+/// Damping, per unit of mass, of the spring pulling the dragged body toward the cursor.
+const DRAG_DAMPING: Real = 10.0;
+
+/// The body selected in the viewer, and the mouse spring dragging it.
+#[derive(Resource, Default)]
+pub struct Picking {
+    pub selected: Option<Entity>,
+    drag: Option<Drag>,
+}
+
+struct Drag {
+    /// The grabbed point, in the local frame of the body.
+    local_anchor: Point<Real>,
+    /// Distance between the camera and the grabbed point, along the cursor ray.
+    depth: Real,
+}
+
+/// The ray from the camera through the cursor, and the maximum time of impact of the shapes
+/// it picks.
+#[cfg(feature = "dim3")]
+fn cursor_ray(
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<(Ray, Real)> {
+    let ray = camera.viewport_to_world(camera_transform, window.cursor_position()?)?;
+    Some((Ray::new(ray.origin.into(), ray.direction.into()), Real::MAX))
+}
+
+/// The ray from the camera through the cursor, and the maximum time of impact of the shapes
+/// it picks.
+#[cfg(feature = "dim2")]
+fn cursor_ray(
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<(Ray, Real)> {
+    let point = camera.viewport_to_world_2d(camera_transform, window.cursor_position()?)?;
+    // Only pick the shapes containing the cursor.
+    Some((Ray::new(point.into(), rapier::math::Vector::x()), 0.0))
+}
+
+/// Selects the body under the cursor on Ctrl + left click, and starts dragging it.
+///
+/// The shapes are tested against the positions currently displayed, so what is picked is
+/// what the user sees.
+pub fn pick_body(
+    mouse: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    mut ui_context: EguiContexts,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    objects: Query<(Entity, &Transform, &ColliderRenderShape), With<PhysicsObject>>,
+    mut picking: ResMut<Picking>,
+) {
+    if !mouse.just_pressed(MouseButton::Left)
+        || !keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+        || ui_context.ctx_mut().wants_pointer_input()
+    {
+        return;
+    }
+
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single())
+    else {
+        return;
+    };
+    let Some((ray, max_toi)) = cursor_ray(window, camera, camera_transform) else {
+        return;
+    };
+
+    let mut best_hit = None;
+    for (entity, transform, shape) in objects.iter() {
+        let pos = transform_to_iso(transform, 1.0);
+        if let Some(toi) = shape.shape.cast_ray(&pos, &ray, max_toi, true) {
+            if best_hit
+                .map(|(_, best_toi, _)| toi < best_toi)
+                .unwrap_or(true)
+            {
+                best_hit = Some((entity, toi, pos));
+            }
+        }
+    }
+
+    picking.selected = best_hit.map(|(entity, ..)| entity);
+    picking.drag = best_hit.map(|(_, toi, pos)| Drag {
+        local_anchor: pos.inverse_transform_point(&ray.point_at(toi)),
+        depth: toi,
+    });
+}
+
+/// Pulls the dragged body toward the cursor with a spring, or teleports it under the cursor
+/// while Shift is pressed.
+pub fn drag_body(
+    db: Res<DbContext>,
+    mouse: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    objects: Query<(
+        &Transform,
+        &PhysicsObject,
+        &PositionInterpolation,
+        &ColliderRenderShape,
+    )>,
+    mut picking: ResMut<Picking>,
+    mut last_input: Local<Option<Instant>>,
+) {
+    if !mouse.pressed(MouseButton::Left) {
+        picking.drag = None;
+    }
+
+    let (Some(drag), Some(selected)) = (&picking.drag, picking.selected) else {
+        return;
+    };
+    let Ok((transform, object, interpolation, shape)) = objects.get(selected) else {
+        // The body was removed from the scene.
+        picking.drag = None;
+        return;
+    };
+
+    if last_input.is_some_and(|last| last.elapsed() < DRAG_INPUT_PERIOD) {
+        return;
+    }
+
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single())
+    else {
+        return;
+    };
+    let Some((ray, _)) = cursor_ray(window, camera, camera_transform) else {
+        return;
+    };
+
+    let target = ray.point_at(drag.depth);
+    let pos = transform_to_iso(transform, 1.0);
+    let input = if keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        let translation = target - pos.rotation * drag.local_anchor.coords;
+        BodyInput::Teleport(Isometry::from_parts(translation.into(), pos.rotation))
+    } else {
+        // NOTE: the viewer doesn’t know the density of the bodies, assume it is 1.
+        let mass = shape.shape.mass_properties(1.0).mass();
+        let spring = (target - pos * drag.local_anchor) * DRAG_STIFFNESS
+            - interpolation.final_linvel() * DRAG_DAMPING;
+        BodyInput::Force(spring * mass)
+    };
+    *last_input = Some(Instant::now());
+
+    let client = db.client.get();
+    let scene = db.scene.clone();
+    let body = object.uuid;
+    db.runtime.spawn(async move {
+        let scene = *scene.read().await;
+        if let Err(e) = client.update_body(scene, body, input).await {
+            log::error!("Failed to send the drag input: {e}");
+        }
+    });
+}
+
+/// Draws the bounding box of the selected body.
+pub fn highlight_selected(
+    picking: Res<Picking>,
+    objects: Query<(&Transform, &ColliderRenderShape)>,
+    mut gizmos: Gizmos,
+) {
+    let Some((transform, shape)) = picking.selected.and_then(|entity| objects.get(entity).ok())
+    else {
+        return;
+    };
+
+    let aabb = shape.shape.compute_aabb(&transform_to_iso(transform, 1.0));
+    let center: Vect = aabb.center().coords.into();
+    let extents: Vect = aabb.extents().into();
+
+    #[cfg(feature = "dim2")]
+    gizmos.rect_2d(center, 0.0, extents, Color::YELLOW);
+    #[cfg(feature = "dim3")]
+    gizmos.cuboid(
+        Transform::from_translation(center).with_scale(extents),
+        Color::YELLOW,
+    );
+}
@@ -3,31 +3,46 @@ use crate::operation::clear_scene;
 use crate::render::RenderSystems;
 use bevy::prelude::*;
 use rapier::prelude::{GenericJoint, RigidBodyHandle};
+use std::collections::HashMap;
 use steadyum_api_types::objects::{ColdBodyObject, WarmBodyObject};
+use steadyum_api_types::partitionner::SceneExport;
+use steadyum_api_types::serialization::ObjectSetEncoding;
 use uuid::Uuid;
 
 pub struct StoragePlugin {
     pub local_dev_mode: bool,
+    pub stream_client_objects: bool,
+    pub client_objects_encoding: ObjectSetEncoding,
+    pub seed: u64,
 }
 
-#[cfg(target_arch = "wasm32")]
-impl Plugin for StoragePlugin {
-    fn build(&self, app: &mut App) {}
-}
-
-#[cfg(not(target_arch = "wasm32"))]
 impl Plugin for StoragePlugin {
     fn build(&self, app: &mut App) {
-        use super::systems;
+        use super::{picking, region_overlay, spawner, systems};
 
-        let context = super::db::spawn_db_thread(self.local_dev_mode);
+        let context = super::db::spawn_db_thread(
+            self.local_dev_mode,
+            self.stream_client_objects,
+            self.client_objects_encoding,
+            self.seed,
+        );
         app.insert_resource(context)
+            .init_resource::<super::Picking>()
+            .init_resource::<super::Spawner>()
             .add_systems(PreUpdate, systems::read_object_positions_from_kvs)
             .add_systems(PreUpdate, systems::update_start_stop)
+            .add_systems(PreUpdate, systems::update_time_scale)
+            .add_systems(PreUpdate, systems::run_debug_steps)
             .add_systems(Update, systems::update_camera_pos)
             .add_systems(Update, systems::step_interpolations)
             .add_systems(Update, systems::update_physics_progress)
             .add_systems(Update, systems::integrate_kinematic_animations)
+            .add_systems(Update, (picking::pick_body, picking::drag_body).chain())
+            .add_systems(Update, picking::highlight_selected)
+            .add_systems(Update, spawner::spawn_bodies)
+            .add_systems(Update, region_overlay::draw_region_overlay)
+            .add_systems(Update, region_overlay::draw_force_overlay)
+            .add_systems(Update, region_overlay::draw_fluid_particles)
             .add_systems(Last, systems::emit_client_inputs)
             .add_systems(Last, systems::remove_scene_on_exit)
             .add_systems(
@@ -72,3 +87,30 @@ impl From<BuiltinScene> for SaveFileData {
         result
     }
 }
+
+impl From<SceneExport> for SaveFileData {
+    fn from(export: SceneExport) -> Self {
+        let mut result = SaveFileData::default();
+        let mut uuid2handle = HashMap::new();
+
+        // NOTE: the vehicles are loaded as plain bodies.
+        let chassis = export.vehicles.into_iter().map(|vehicle| vehicle.chassis);
+        for (i, body) in export.bodies.into_iter().chain(chassis).enumerate() {
+            let handle = RigidBodyHandle::from_raw_parts(i as u32, 0);
+            uuid2handle.insert(body.uuid, handle);
+            result.objects.push((handle, body.cold, body.warm));
+        }
+
+        for joint in export.impulse_joints {
+            if let (Some(handle1), Some(handle2)) =
+                (uuid2handle.get(&joint.body1), uuid2handle.get(&joint.body2))
+            {
+                result
+                    .impulse_joints
+                    .push((*handle1, *handle2, joint.joint));
+            }
+        }
+
+        result
+    }
+}
@@ -1,33 +1,77 @@
 use bevy::prelude::Component;
-use rapier::math::{Isometry, Real};
+use rapier::math::{AngVector, Isometry, Real, Rotation, Vector};
 use std::collections::VecDeque;
 
+/// We don’t extrapolate positions further than this time (in seconds) after the last known one.
+pub const MAX_EXTRAPOLATION_TIME: Real = 0.25;
+/// Fraction of the correction that remains after each frame once an authoritative position
+/// arrived while we were extrapolating.
+const CORRECTION_DECAY: Real = 0.8;
+
 #[derive(Copy, Clone, Debug, Default)]
 struct PositionInterpolationPoint {
     pub pos: Isometry<Real>,
+    pub linvel: Vector<Real>,
+    pub angvel: AngVector<Real>,
     pub timestamp: u64,
 }
 
+impl PositionInterpolationPoint {
+    fn extrapolate(&self, dt: Real) -> Isometry<Real> {
+        let translation = self.pos.translation.vector + self.linvel * dt;
+        let rotation = Rotation::new(self.angvel * dt) * self.pos.rotation;
+        Isometry::from_parts(translation.into(), rotation)
+    }
+}
+
 #[derive(Clone, Debug, Component)]
 pub struct PositionInterpolation {
     current: PositionInterpolationPoint,
     targets: VecDeque<PositionInterpolationPoint>,
+    /// Position estimated from the velocities, while no authoritative position is known yet.
+    extrapolated: Option<Isometry<Real>>,
+    /// Offset between the position displayed before an authoritative correction arrived
+    /// and the corrected position. It vanishes progressively to avoid popping.
+    correction_translation: Vector<Real>,
+    correction_rotation: Rotation<Real>,
 }
 
 impl PositionInterpolation {
-    pub fn new(pos: Isometry<Real>, timestamp: u64) -> Self {
+    pub fn new(
+        pos: Isometry<Real>,
+        linvel: Vector<Real>,
+        angvel: AngVector<Real>,
+        timestamp: u64,
+    ) -> Self {
         Self {
-            current: PositionInterpolationPoint { pos, timestamp },
+            current: PositionInterpolationPoint {
+                pos,
+                linvel,
+                angvel,
+                timestamp,
+            },
             targets: VecDeque::new(),
+            extrapolated: None,
+            correction_translation: Vector::zeros(),
+            correction_rotation: Rotation::identity(),
         }
     }
 }
 
 impl PositionInterpolation {
-    pub fn step(&mut self, timestamp: u64) {
+    /// Advances to `timestamp`.
+    ///
+    /// If `extrapolate` is `true` and `timestamp` is past the last known position, the position
+    /// is estimated from the last known velocities, `dt` being the duration of one timestep.
+    pub fn step(&mut self, timestamp: u64, dt: Real, extrapolate: bool) {
+        let prev_pos = self.current_pos();
+        let was_extrapolating = self.extrapolated.is_some();
+        let mut got_new_data = false;
+
         while !self.targets.is_empty() {
             if self.targets[0].timestamp <= timestamp {
                 self.current = self.targets.pop_front().unwrap();
+                got_new_data = true;
             } else {
                 break;
             }
@@ -40,11 +84,36 @@ impl PositionInterpolation {
                 / (target.timestamp as Real - self.current.timestamp as Real);
             self.current.pos = self.current.pos.lerp_slerp(&target.pos, t);
             self.current.timestamp = timestamp;
+            self.extrapolated = None;
+        } else if extrapolate && timestamp > self.current.timestamp {
+            // No data yet for this timestamp, use dead reckoning.
+            let elapsed = (timestamp - self.current.timestamp) as Real * dt;
+            self.extrapolated = Some(
+                self.current
+                    .extrapolate(elapsed.min(MAX_EXTRAPOLATION_TIME)),
+            );
+        } else {
+            self.extrapolated = None;
+        }
+
+        if was_extrapolating && got_new_data {
+            // Start from where we were displayed, and converge toward the authoritative position.
+            let new_pos = self.extrapolated.unwrap_or(self.current.pos);
+            self.correction_translation = prev_pos.translation.vector - new_pos.translation.vector;
+            self.correction_rotation = prev_pos.rotation * new_pos.rotation.inverse();
+        } else {
+            self.correction_translation *= CORRECTION_DECAY;
+            self.correction_rotation =
+                Rotation::identity().slerp(&self.correction_rotation, CORRECTION_DECAY);
         }
     }
 
     pub fn current_pos(&self) -> Isometry<Real> {
-        self.current.pos
+        let pos = self.extrapolated.unwrap_or(self.current.pos);
+        Isometry::from_parts(
+            (pos.translation.vector + self.correction_translation).into(),
+            self.correction_rotation * pos.rotation,
+        )
     }
 
     pub fn final_pos(&self) -> &Isometry<Real> {
@@ -54,6 +123,18 @@ impl PositionInterpolation {
             .unwrap_or(&self.current.pos)
     }
 
+    pub fn final_linvel(&self) -> Vector<Real> {
+        self.targets
+            .back()
+            .map(|p| p.linvel)
+            .unwrap_or(self.current.linvel)
+    }
+
+    /// Number of known positions not reached yet.
+    pub fn buffered_points(&self) -> usize {
+        self.targets.len()
+    }
+
     pub fn max_known_timestep(&self) -> u64 {
         self.targets
             .back()
@@ -61,10 +142,20 @@ impl PositionInterpolation {
             .unwrap_or(self.current.timestamp)
     }
 
-    pub fn add_interpolation_point(&mut self, pos: Isometry<Real>, timestamp: u64) {
+    pub fn add_interpolation_point(
+        &mut self,
+        pos: Isometry<Real>,
+        linvel: Vector<Real>,
+        angvel: AngVector<Real>,
+        timestamp: u64,
+    ) {
         // TODO: don’t accumulate interpolation point with equal positions, or with
         //       position that could be part of the interpolation.
-        self.targets
-            .push_back(PositionInterpolationPoint { pos, timestamp });
+        self.targets.push_back(PositionInterpolationPoint {
+            pos,
+            linvel,
+            angvel,
+            timestamp,
+        });
     }
 }
@@ -0,0 +1,135 @@
+use crate::block_on;
+use crate::storage::db::DbContext;
+use crate::styling::ColorGenerator;
+use crate::ui::UiState;
+use crate::utils::Vect;
+use crate::MainCamera;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use steadyum_api_types::forces::ForceGenerator;
+
+/// Length of the normals drawn for the buoyancy planes.
+const FORCE_NORMAL_LENGTH: f32 = 5.0;
+
+/// Draws the wireframe of the visible regions, with their stats as floating labels.
+pub fn draw_region_overlay(
+    ui_state: Res<UiState>,
+    db: Res<DbContext>,
+    mut colors: ResMut<ColorGenerator>,
+    mut gizmos: Gizmos,
+    mut ui_context: EguiContexts,
+    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+) {
+    if !ui_state.region_overlay {
+        return;
+    }
+
+    let region_stats = block_on(db.region_stats.read()).clone();
+    let camera = cameras.get_single().ok();
+    let painter = ui_context
+        .ctx_mut()
+        .layer_painter(egui::LayerId::background());
+
+    for (region, stats) in region_stats {
+        let aabb = region.aabb();
+        let center: Vect = aabb.center().coords.into();
+        let extents: Vect = aabb.extents().into();
+        let color = colors.gen_region_color(region);
+
+        #[cfg(feature = "dim2")]
+        gizmos.rect_2d(center, 0.0, extents, color);
+        #[cfg(feature = "dim3")]
+        gizmos.cuboid(
+            Transform::from_translation(center).with_scale(extents),
+            color,
+        );
+
+        #[cfg(feature = "dim2")]
+        let center = center.extend(0.0);
+        let Some(label_pos) = camera.and_then(|(camera, camera_transform)| {
+            camera.world_to_viewport(camera_transform, center)
+        }) else {
+            continue;
+        };
+
+        let [r, g, b, _] = color.as_rgba_u8();
+        painter.text(
+            egui::pos2(label_pos.x, label_pos.y),
+            egui::Align2::CENTER_CENTER,
+            format!("{} bodies\nstep {}", stats.num_bodies, stats.timestamp),
+            egui::FontId::proportional(14.0),
+            egui::Color32::from_rgb(r, g, b),
+        );
+    }
+}
+
+/// Draws the force generators of the current scene: the range of the gravity wells, the
+/// volume and direction of the wind, and the normal of the buoyancy planes.
+pub fn draw_force_overlay(ui_state: Res<UiState>, db: Res<DbContext>, mut gizmos: Gizmos) {
+    if !ui_state.force_overlay {
+        return;
+    }
+
+    for generator in block_on(db.force_generators.read()).iter() {
+        match generator {
+            ForceGenerator::PointGravity {
+                center,
+                radius,
+                range,
+                ..
+            } => {
+                let center: Vect = center.coords.into();
+                #[cfg(feature = "dim2")]
+                {
+                    gizmos.circle_2d(center, *radius, Color::ORANGE);
+                    gizmos.circle_2d(center, *range, Color::ORANGE.with_a(0.3));
+                }
+                #[cfg(feature = "dim3")]
+                {
+                    gizmos.sphere(center, Quat::IDENTITY, *radius, Color::ORANGE);
+                    gizmos.sphere(center, Quat::IDENTITY, *range, Color::ORANGE.with_a(0.3));
+                }
+            }
+            ForceGenerator::Wind { aabb, velocity, .. } => {
+                let center: Vect = aabb.center().coords.into();
+                let extents: Vect = aabb.extents().into();
+                let velocity: Vect = (*velocity).into();
+                #[cfg(feature = "dim2")]
+                {
+                    gizmos.rect_2d(center, 0.0, extents, Color::CYAN);
+                    gizmos.ray_2d(center, velocity, Color::CYAN);
+                }
+                #[cfg(feature = "dim3")]
+                {
+                    gizmos.cuboid(
+                        Transform::from_translation(center).with_scale(extents),
+                        Color::CYAN,
+                    );
+                    gizmos.ray(center, velocity, Color::CYAN);
+                }
+            }
+            ForceGenerator::Buoyancy { point, normal, .. } => {
+                let point: Vect = point.coords.into();
+                let normal: Vect = (*normal).into();
+                #[cfg(feature = "dim2")]
+                gizmos.ray_2d(point, normal * FORCE_NORMAL_LENGTH, Color::BLUE);
+                #[cfg(feature = "dim3")]
+                gizmos.ray(point, normal * FORCE_NORMAL_LENGTH, Color::BLUE);
+            }
+        }
+    }
+}
+
+pub fn draw_fluid_particles(db: Res<DbContext>, mut gizmos: Gizmos) {
+    for (radius, points) in block_on(db.particles.read()).values() {
+        for point in points {
+            let center: Vect = point.coords.into();
+            #[cfg(feature = "dim2")]
+            gizmos.circle_2d(center, *radius, Color::rgb(0.2, 0.5, 1.0));
+            #[cfg(feature = "dim3")]
+            gizmos
+                .sphere(center, Quat::IDENTITY, *radius, Color::rgb(0.2, 0.5, 1.0))
+                .circle_segments(4);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use crate::storage::db::DbContext;
+use bevy::prelude::*;
+use bevy::utils::Uuid;
+use rapier::math::{Real, Vector};
+use rapier::prelude::{ColliderBuilder, RigidBodyBuilder};
+use steadyum_api_types::messages::BodyAssignment;
+use steadyum_api_types::objects::{ColdBodyObject, WarmBodyObject};
+
+/// The maximum number of bodies streamed in a single frame, so a low frame rate doesn’t
+/// result in huge insertion bursts.
+const MAX_STREAMED_PER_FRAME: usize = 100;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpawnShape {
+    Cuboid,
+    Ball,
+    Capsule,
+    #[cfg(feature = "dim3")]
+    Cylinder,
+}
+
+impl SpawnShape {
+    pub const ALL: &'static [Self] = &[
+        Self::Cuboid,
+        Self::Ball,
+        Self::Capsule,
+        #[cfg(feature = "dim3")]
+        Self::Cylinder,
+    ];
+
+    /// A collider of this shape fitting in a box of width `size`.
+    fn collider(self, size: Real) -> ColliderBuilder {
+        let half = size / 2.0;
+        match self {
+            #[cfg(feature = "dim2")]
+            Self::Cuboid => ColliderBuilder::cuboid(half, half),
+            #[cfg(feature = "dim3")]
+            Self::Cuboid => ColliderBuilder::cuboid(half, half, half),
+            Self::Ball => ColliderBuilder::ball(half),
+            Self::Capsule => ColliderBuilder::capsule_y(half / 2.0, half / 2.0),
+            #[cfg(feature = "dim3")]
+            Self::Cylinder => ColliderBuilder::cylinder(half, half),
+        }
+    }
+}
+
+/// The bodies spawned from the viewer, in front of the camera.
+#[derive(Resource)]
+pub struct Spawner {
+    pub shape: SpawnShape,
+    pub size: Real,
+    pub density: Real,
+    /// Speed of the bodies along the camera direction.
+    pub speed: Real,
+    /// Number of bodies to spawn on the next frame.
+    pub requested: usize,
+    /// Continuously spawns `stream_rate` bodies per second, for stress testing.
+    pub stream: bool,
+    pub stream_rate: f32,
+    stream_budget: f32,
+}
+
+impl Default for Spawner {
+    fn default() -> Self {
+        Self {
+            shape: SpawnShape::Cuboid,
+            size: 1.0,
+            density: 1.0,
+            speed: 20.0,
+            requested: 0,
+            stream: false,
+            stream_rate: 10.0,
+            stream_budget: 0.0,
+        }
+    }
+}
+
+/// Inserts the bodies requested from the spawn toolbar, and the ones of the spawn stream.
+pub fn spawn_bodies(db: Res<DbContext>, time: Res<Time>, mut spawner: ResMut<Spawner>) {
+    let mut count = std::mem::take(&mut spawner.requested);
+
+    if db.is_running && spawner.stream {
+        spawner.stream_budget += spawner.stream_rate * time.delta_seconds();
+        let streamed = spawner.stream_budget.floor();
+        spawner.stream_budget -= streamed;
+        count += (streamed as usize).min(MAX_STREAMED_PER_FRAME);
+    } else {
+        spawner.stream_budget = 0.0;
+    }
+
+    if count == 0 {
+        return;
+    }
+
+    let shape = spawner.shape;
+    let size = spawner.size;
+    let density = spawner.density;
+    let speed = spawner.speed;
+    let camera = db.camera.clone();
+    let client = db.client.get();
+    let scene = db.scene.clone();
+
+    db.runtime.spawn(async move {
+        let camera = camera.read().await.clone();
+        let origin: Vector<Real> = camera.position.into();
+        let linvel: Vector<Real> = (camera.dir * speed).into();
+
+        let bodies = (0..count)
+            .map(|i| {
+                // Scatter the bodies of a same batch so they don’t start overlapping.
+                let jitter = if i == 0 {
+                    Vector::zeros()
+                } else {
+                    Vector::from_fn(|_, _| (rand::random::<Real>() - 0.5) * size * 4.0)
+                };
+                let body = RigidBodyBuilder::dynamic()
+                    .translation(origin + jitter)
+                    .linvel(linvel)
+                    .build();
+                let collider = shape.collider(size).density(density).friction(1.0).build();
+                BodyAssignment {
+                    uuid: Uuid::new_v4(),
+                    warm: WarmBodyObject::from_body(&body, 0),
+                    cold: ColdBodyObject::from_body_collider(&body, &collider),
+                    epoch: 0,
+                }
+            })
+            .collect();
+
+        let scene = *scene.read().await;
+        if let Err(e) = client.insert_bodies(scene, bodies).await {
+            log::error!("Failed to spawn bodies: {e}");
+        }
+    });
+}
@@ -1,39 +1,67 @@
 use crate::operation::{Operation, Operations};
 use crate::render::{ColliderRender, ColliderRenderShape};
 use crate::storage::db::{CameraPos, DbContext};
-use crate::storage::position_interpolation::PositionInterpolation;
+use crate::storage::position_interpolation::{PositionInterpolation, MAX_EXTRAPOLATION_TIME};
 use crate::styling::ColorGenerator;
 use crate::ui::UiState;
-use crate::utils::{iso_to_transform, transform_to_iso, MissingDataPoints, PhysicsObject, Vect};
+use crate::utils::{
+    iso_to_transform, transform_to_iso, MissingDataPoints, PhysicsObject, PooledBody, Vect,
+};
 use crate::utils::{KinematicAnimationsComponent, RapierContext};
 use crate::{block_on, MainCamera, PhysicsProgress};
 use bevy::app::AppExit;
 use bevy::prelude::*;
 use bevy::utils::{HashMap, Uuid};
 use rapier::dynamics::{RigidBodyBuilder, RigidBodyType};
-use rapier::math::Real;
+use rapier::math::{Isometry, Point, Real, Rotation};
 use rapier::prelude::ColliderBuilder;
 use std::collections::HashSet;
 use std::sync::atomic::Ordering;
-use steadyum_api_types::messages::BodyAssignment;
+use steadyum_api_types::messages::{BodyAssignment, ClientLod};
 use steadyum_api_types::objects::{ColdBodyObject, WarmBodyObject};
-use steadyum_api_types::partitionner::SceneUuid;
+use steadyum_api_types::partitionner::{SceneUuid, TimeScale};
 
 pub fn update_start_stop(mut db: ResMut<DbContext>, ui: Res<UiState>) {
     let db = &mut *db;
     if db.is_running != ui.running {
         dbg!("Update start stop.");
-        block_on(async {
-            let scene = *db.scene.read().await;
-            db.is_running = ui.running;
-            db.partitionner
-                .set_running(scene, db.is_running)
-                .await
-                .unwrap();
+        db.is_running = ui.running;
+        let scene = *block_on(db.scene.read());
+        let client = db.client.get();
+        let running = db.is_running;
+        db.request(async move { client.set_running(scene, running).await });
+    }
+}
+
+/// Runs the steps requested by the play button of the paused simulation.
+pub fn run_debug_steps(db: Res<DbContext>, mut ui: ResMut<UiState>) {
+    if ui.single_step {
+        ui.single_step = false;
+        let scene = *block_on(db.scene.read());
+        let client = db.client.get();
+        let steps = ui.num_debug_steps;
+        db.request(async move {
+            client.step_n(scene, steps).await?;
+            Ok(())
         });
     }
 }
 
+pub fn update_time_scale(mut db: ResMut<DbContext>, ui: Res<UiState>) {
+    let db = &mut *db;
+    let time_scale = TimeScale(ui.time_scale);
+    if db.time_scale != time_scale && time_scale.is_valid() {
+        db.time_scale = time_scale;
+        block_on(async { *db.scene_time_scale.write().await = time_scale });
+        let scene = *block_on(db.scene.read());
+        let client = db.client.get();
+        db.request(async move { client.set_time_scale(scene, time_scale).await });
+    }
+}
+
+/// Maximum number of hidden body entities kept for reuse, see [`PooledBody`].
+const MAX_POOLED_BODIES: usize = 10_000;
+
 pub fn read_object_positions_from_kvs(
     mut commands: Commands,
     db: Res<DbContext>,
@@ -48,15 +76,23 @@ pub fn read_object_positions_from_kvs(
         &mut ColliderRender,
         &mut Visibility,
     )>,
+    pooled: Query<Entity, With<PooledBody>>,
 ) {
+    let mut pooled: Vec<_> = pooled.iter().collect();
+    let mut num_pooled = pooled.len();
     let mut new_progress_limit = u64::MAX;
 
     let mut min_progress_limit = u64::MAX;
     let mut max_progress_limit = 0;
+    // The far regions report less often, don’t let them hold back the near ones.
+    let mut min_far_progress_limit = u64::MAX;
+    let camera: Point<Real> = block_on(db.camera.read()).position.into();
+    let lod = ClientLod::default();
 
     let t0 = instant::Instant::now();
 
     let got_new_region = db.read_new_region.swap(false, Ordering::SeqCst);
+    progress.time_scale = *block_on(db.scene_time_scale.read());
     let mut uuid_is_rendered = HashSet::new();
 
     let Some(uuid2body) = block_on(db.uuid2body.write()).take() else {
@@ -69,10 +105,19 @@ pub fn read_object_positions_from_kvs(
         bodies.iter_mut()
     {
         if let Some(data) = uuid2body.get(&object.uuid) {
-            interpolation.add_interpolation_point(data.data.position, data.timestamp);
+            interpolation.add_interpolation_point(
+                data.data.position,
+                data.data.linvel,
+                data.data.angvel,
+                data.timestamp,
+            );
             object.sleeping = data.data.sleep_start_frame.is_some();
+            object.body_type = data.cold.body_type;
+            object.region = data.bounds;
 
-            let region_color = if data.data.body_type == RigidBodyType::Dynamic {
+            let region_color = if data.cold.is_sensor {
+                colors.sensor_color()
+            } else if data.cold.body_type == RigidBodyType::Dynamic {
                 colors.gen_region_color(data.bounds)
             } else {
                 colors.static_object_color()
@@ -83,7 +128,12 @@ pub fn read_object_positions_from_kvs(
                 color.color = region_color;
             }
 
-            min_progress_limit = min_progress_limit.min(interpolation.max_known_timestep());
+            if lod.is_far(&camera, &data.bounds) {
+                min_far_progress_limit =
+                    min_far_progress_limit.min(interpolation.max_known_timestep());
+            } else {
+                min_progress_limit = min_progress_limit.min(interpolation.max_known_timestep());
+            }
             max_progress_limit = max_progress_limit.max(interpolation.max_known_timestep());
 
             missing.0 = 0;
@@ -96,7 +146,23 @@ pub fn read_object_positions_from_kvs(
 
             if missing.0 > 5 {
                 *visible = Visibility::Hidden;
-                commands.entity(entity).despawn_recursive();
+
+                // NOTE: the pooled entity keeps its mesh and material, reused if the next body
+                //       has the same shape and color.
+                if num_pooled < MAX_POOLED_BODIES {
+                    num_pooled += 1;
+                    commands
+                        .entity(entity)
+                        .remove::<(
+                            PhysicsObject,
+                            PositionInterpolation,
+                            MissingDataPoints,
+                            ColliderRenderShape,
+                        )>()
+                        .insert(PooledBody);
+                } else {
+                    commands.entity(entity).despawn_recursive();
+                }
             }
         }
     }
@@ -108,25 +174,46 @@ pub fn read_object_positions_from_kvs(
                 progress.required_progress = progress.required_progress.max(object.timestamp);
             }
 
-            let entity = commands.spawn((
-                SpatialBundle::default(),
+            let body = (
                 PhysicsObject {
                     uuid: object.data.uuid,
                     sleeping: object.data.sleep_start_frame.is_some(),
+                    body_type: object.cold.body_type,
+                    region: object.bounds,
+                    user_data: object.cold.user_data,
                 },
-                PositionInterpolation::new(object.data.position, object.timestamp),
+                PositionInterpolation::new(
+                    object.data.position,
+                    object.data.linvel,
+                    object.data.angvel,
+                    object.timestamp,
+                ),
                 ColliderRender::default(),
                 MissingDataPoints(0),
                 ColliderRenderShape {
-                    shape: object.data.shape,
+                    shape: object.cold.shape,
                 },
-            ));
-
-            // if object.timestamp <
-            // entity.insert(Visibility::Hidden);
+            );
+
+            match pooled.pop() {
+                Some(entity) => {
+                    commands.entity(entity).remove::<PooledBody>().insert((
+                        body,
+                        iso_to_transform(&object.data.position, 1.0),
+                        Visibility::Inherited,
+                    ));
+                }
+                None => {
+                    commands.spawn((SpatialBundle::default(), body));
+                }
+            }
         }
     }
 
+    if min_progress_limit == u64::MAX {
+        min_progress_limit = min_far_progress_limit;
+    }
+
     progress.calculated_progress_limits_range = [min_progress_limit, max_progress_limit];
 
     if min_progress_limit != u64::MAX {
@@ -146,6 +233,7 @@ pub fn read_object_positions_from_kvs(
 pub fn step_interpolations(
     ui_state: Res<UiState>,
     progress: Res<PhysicsProgress>,
+    context: Res<RapierContext>,
     camera: Query<&GlobalTransform, With<MainCamera>>,
     mut objects: Query<(&mut PositionInterpolation, &mut Transform, &mut Visibility)>,
 ) {
@@ -159,7 +247,11 @@ pub fn step_interpolations(
     let camera = camera.single();
 
     for (mut interpolation, mut transform, mut visibility) in objects.iter_mut() {
-        interpolation.step(progress.simulated_steps as u64);
+        interpolation.step(
+            progress.simulated_steps as u64,
+            context.integration_parameters.dt,
+            ui_state.extrapolation,
+        );
 
         let current_pos = if ui_state.interpolation {
             iso_to_transform(&interpolation.current_pos(), 1.0)
@@ -195,11 +287,26 @@ pub fn step_interpolations(
 
 pub fn integrate_kinematic_animations(
     progress: Res<PhysicsProgress>,
+    mut last_simulated_time: Local<Real>,
     mut objects: Query<(&mut Transform, &KinematicAnimationsComponent)>,
 ) {
+    // NOTE: the simulated time goes backward when the scene is cleared.
+    let dt = (progress.simulated_time - *last_simulated_time).max(0.0);
+    *last_simulated_time = progress.simulated_time;
+
     for (mut transform, animations) in objects.iter_mut() {
         let base = transform_to_iso(&*transform, 1.0);
-        let pos = animations.0.eval(progress.simulated_time, base);
+        let pos = if animations.body_type == RigidBodyType::KinematicVelocityBased {
+            let (linvel, angvel) = animations
+                .animations
+                .eval_velocities(progress.simulated_time);
+            Isometry::from_parts(
+                (base.translation.vector + linvel * dt).into(),
+                Rotation::new(angvel * dt) * base.rotation,
+            )
+        } else {
+            animations.animations.eval(progress.simulated_time, base)
+        };
         *transform = iso_to_transform(&pos, 1.0);
     }
 }
@@ -226,8 +333,20 @@ pub fn update_physics_progress(
         //     "sim steps: {}, limit: {}",
         //     progress.simulated_steps, progress.progress_limit
         // );
-        if progress.simulated_steps <= progress.progress_limit {
-            let mut progress_delta = 1;
+        // When extrapolating, keep going a bit further than the latest known positions
+        // instead of stalling while waiting for them.
+        let progress_limit = if ui_state.extrapolation {
+            progress.progress_limit
+                + (MAX_EXTRAPOLATION_TIME / context.integration_parameters.dt) as usize
+        } else {
+            progress.progress_limit
+        };
+
+        if progress.simulated_steps <= progress_limit {
+            // Play back `time_scale` steps per frame, keeping the remainder for the next ones.
+            let steps = progress.step_fraction + progress.time_scale.0;
+            let mut progress_delta = steps as usize;
+            progress.step_fraction = steps.fract();
 
             if progress.required_progress as usize > progress.simulated_steps {
                 progress_delta = progress.required_progress as usize - progress.simulated_steps;
@@ -252,13 +371,15 @@ pub fn handle_scene_reset(
     for op in operations.iter() {
         if let Operation::ClearScene = op {
             dbg!(">>>>>>>>>>>>>>>>>>>>>>>>> Clearing scene.");
-            block_on(async {
+            let scene = block_on(async {
                 let scene = *db.scene.read().await;
-                db.partitionner.remove_scene(scene).await.unwrap();
                 let new_scene_uuid = Uuid::new_v4();
                 db.scene.write().await.0 = new_scene_uuid;
                 *db.uuid2body.write().await = None;
+                scene
             });
+            let client = db.client.get();
+            db.request(async move { client.remove_scene(scene).await });
 
             *progress = PhysicsProgress::default();
             ui_state.running = false;
@@ -289,10 +410,9 @@ pub fn open_existing_scene(
 pub fn remove_scene_on_exit(mut exit: EventReader<AppExit>, db: ResMut<DbContext>) {
     for _ in exit.read() {
         dbg!("Bevy is exiting.");
-        block_on(async {
-            let scene = *db.scene.read().await;
-            db.partitionner.remove_scene(scene).await.unwrap();
-        });
+        let scene = *block_on(db.scene.read());
+        let client = db.client.get();
+        db.request(async move { client.remove_scene(scene).await });
     }
 }
 
@@ -302,41 +422,35 @@ pub fn emit_client_inputs(
     keyboard_input: Res<Input<KeyCode>>,
 ) {
     if db.is_running {
-        block_on(async {
-            let scene = *db.scene.read().await;
-
-            let t0 = std::time::Instant::now();
-            db.partitionner
-                .client_input(scene, progress.simulated_steps as u64)
-                .await
-                .unwrap();
-
-            if keyboard_input.just_released(KeyCode::Space) {
-                let camera = db.camera.read().await.clone();
-                let body = RigidBodyBuilder::dynamic()
-                    .translation(camera.position.into())
-                    .linvel((camera.dir * 100.0).into())
-                    .build();
-                let collider = ColliderBuilder::cuboid(
-                    0.5 + rand::random::<f32>(),
-                    0.5 + rand::random::<f32>(),
-                    0.5 + rand::random::<f32>(),
-                )
-                .density(5.0)
-                .friction(1.0)
+        let scene = *block_on(db.scene.read());
+        let client = db.client.get();
+        let client_inputs = db.client_inputs.clone();
+        client_inputs.push(scene, progress.simulated_steps as u64);
+        db.request(async move { client_inputs.flush(&client).await });
+
+        if keyboard_input.just_released(KeyCode::Space) {
+            let camera = block_on(db.camera.read()).clone();
+            let body = RigidBodyBuilder::dynamic()
+                .translation(camera.position.into())
+                .linvel((camera.dir * 100.0).into())
                 .build();
-
-                let assignment = BodyAssignment {
-                    uuid: Uuid::new_v4(),
-                    warm: WarmBodyObject::from_body(&body, 0),
-                    cold: ColdBodyObject::from_body_collider(&body, &collider),
-                };
-                db.partitionner
-                    .insert_objects(scene, vec![assignment])
-                    .await
-                    .unwrap();
-            }
-            // println!(">>>>>>>>> TIME: {}", t0.elapsed().as_secs_f32());
-        });
+            let collider = ColliderBuilder::cuboid(
+                0.5 + rand::random::<f32>(),
+                0.5 + rand::random::<f32>(),
+                0.5 + rand::random::<f32>(),
+            )
+            .density(5.0)
+            .friction(1.0)
+            .build();
+
+            let assignment = BodyAssignment {
+                uuid: Uuid::new_v4(),
+                warm: WarmBodyObject::from_body(&body, 0),
+                cold: ColdBodyObject::from_body_collider(&body, &collider),
+                epoch: 0,
+            };
+            let client = db.client.get();
+            db.request(async move { client.insert_bodies(scene, vec![assignment]).await });
+        }
     }
 }
@@ -46,4 +46,9 @@ impl ColorGenerator {
     pub fn static_object_color(&self) -> Color {
         Color::DARK_GREEN
     }
+
+    /// Sensors are translucent so the bodies inside them remain visible.
+    pub fn sensor_color(&self) -> Color {
+        Color::rgba(1.0, 0.8, 0.0, 0.3)
+    }
 }
@@ -0,0 +1,81 @@
+use crate::camera::CameraNavigation;
+use crate::render::ColliderRenderShape;
+use crate::storage::{DbContext, Picking};
+use crate::utils::PhysicsObject;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use steadyum_api_types::messages::BodyInput;
+use uuid::Uuid;
+
+pub(super) fn ui(
+    ui_context: &mut EguiContexts,
+    db: &DbContext,
+    picking: &mut Picking,
+    navigation: &mut CameraNavigation,
+    objects: &Query<(&PhysicsObject, &Transform, &ColliderRenderShape)>,
+) {
+    let Some(selected) = picking.selected else {
+        return;
+    };
+    let Ok((object, transform, shape)) = objects.get(selected) else {
+        // The body was removed from the scene.
+        picking.selected = None;
+        return;
+    };
+
+    let mut open = true;
+    egui::Window::new("🔍 Selected body")
+        .open(&mut open)
+        .resizable(false)
+        .show(ui_context.ctx_mut(), |ui| {
+            ui.label(format!("UUID: {}", object.uuid));
+            ui.label(format!("Shape: {:?}", shape.shape.shape_type()));
+            ui.label(format!("Position: {}", transform.translation));
+            ui.label(format!("Sleeping: {}", object.sleeping));
+            ui.label(format!("User data: {} bytes", object.user_data.len()));
+
+            let mut follow = navigation.follow == Some(object.uuid);
+            if ui.checkbox(&mut follow, "Follow with the camera").changed() {
+                navigation.follow = follow.then_some(object.uuid);
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Freeze").clicked() {
+                    send_body_input(db, object.uuid, BodyInput::Freeze(true));
+                }
+                if ui.button("Unfreeze").clicked() {
+                    send_body_input(db, object.uuid, BodyInput::Freeze(false));
+                }
+                if ui.button("❌ Despawn").clicked() {
+                    despawn(db, object.uuid);
+                }
+            });
+            ui.separator();
+            ui.label("Ctrl + drag to pull it, with Shift to teleport it.");
+        });
+
+    if !open {
+        picking.selected = None;
+    }
+}
+
+fn send_body_input(db: &DbContext, body: Uuid, input: BodyInput) {
+    let client = db.client.get();
+    let scene = db.scene.clone();
+    db.runtime.spawn(async move {
+        let scene = *scene.read().await;
+        if let Err(e) = client.update_body(scene, body, input).await {
+            log::error!("Failed to send the body input: {e}");
+        }
+    });
+}
+
+fn despawn(db: &DbContext, body: Uuid) {
+    let client = db.client.get();
+    let scene = db.scene.clone();
+    db.runtime.spawn(async move {
+        let scene = *scene.read().await;
+        if let Err(e) = client.remove_bodies(scene, vec![body]).await {
+            log::error!("Failed to despawn the body: {e}");
+        }
+    });
+}
@@ -0,0 +1,51 @@
+use crate::block_on;
+use crate::storage::{ConnectionStatus, DbContext};
+use crate::ui::UiState;
+use bevy_egui::{egui, EguiContexts};
+
+pub(super) fn ui(ui_context: &mut EguiContexts, ui_state: &mut UiState, db: &DbContext) {
+    if !ui_state.connection_open {
+        ui_state.endpoint_form = None;
+        return;
+    }
+
+    let status = *block_on(db.connection_status.read());
+    let form = ui_state
+        .endpoint_form
+        .get_or_insert_with(|| block_on(db.endpoint.read()).clone());
+
+    egui::Window::new("🔌 Connection")
+        .open(&mut ui_state.connection_open)
+        .resizable(false)
+        .show(ui_context.ctx_mut(), |ui| {
+            match status {
+                ConnectionStatus::Connected => ui.label("Connected."),
+                ConnectionStatus::Reconnecting { attempt } => ui.colored_label(
+                    egui::Color32::LIGHT_RED,
+                    format!("Reconnecting… (attempt {attempt})"),
+                ),
+            };
+
+            egui::Grid::new("endpoint").num_columns(2).show(ui, |ui| {
+                ui.label("Partitionner");
+                ui.text_edit_singleline(&mut form.partitionner_addr);
+                ui.end_row();
+
+                ui.label("Port");
+                ui.add(egui::DragValue::new(&mut form.partitionner_port));
+                ui.end_row();
+
+                // The browser only talks to the partitionner.
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    ui.label("Zenoh router");
+                    ui.text_edit_singleline(&mut form.zenoh_router);
+                    ui.end_row();
+                }
+            });
+
+            if ui.button("Connect").clicked() {
+                *block_on(db.endpoint.write()) = form.clone();
+            }
+        });
+}
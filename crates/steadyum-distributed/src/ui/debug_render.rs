@@ -0,0 +1,23 @@
+use crate::ui::UiState;
+use bevy_egui::{egui, EguiContexts};
+
+pub(super) fn ui(ui_context: &mut EguiContexts, ui_state: &mut UiState) {
+    if !ui_state.debug_render_open {
+        return;
+    }
+
+    let options = &mut ui_state.debug_render;
+
+    egui::Window::new("🐞 Debug render")
+        .open(&mut ui_state.debug_render_open)
+        .resizable(false)
+        .show(ui_context.ctx_mut(), |ui| {
+            ui.checkbox(&mut options.outlines, "Collider outlines");
+            ui.add_enabled(
+                options.outlines,
+                egui::Checkbox::new(&mut options.sleeping, "Color the sleeping bodies"),
+            );
+            ui.checkbox(&mut options.aabbs, "AABBs");
+            ui.checkbox(&mut options.watch_radii, "Watch radii");
+        });
+}
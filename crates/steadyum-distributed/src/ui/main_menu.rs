@@ -1,5 +1,5 @@
 use crate::operation::{Operation, Operations};
-use crate::storage::{DbContext, SaveFileData};
+use crate::storage::{ConnectionStatus, DbContext, SaveFileData};
 use crate::styling::Theme;
 use crate::ui::UiState;
 use crate::{block_on, builtin_scenes};
@@ -7,11 +7,11 @@ use bevy::app::AppExit;
 use bevy::prelude::*;
 use bevy::window::Window;
 use bevy_egui::{egui, EguiContexts};
+use steadyum_api_types::objects::SceneInfo;
 
 use crate::utils::RapierContext;
 #[cfg(not(target_arch = "wasm32"))]
 use native_dialog::FileDialog;
-use steadyum_api_types::region_db::AsyncPartitionnerServer;
 
 pub(super) fn ui(
     _window: &Window,
@@ -19,7 +19,7 @@ pub(super) fn ui(
     ui_context: &mut EguiContexts,
     ui_state: &mut UiState,
     operations: &mut Operations,
-    partitionner: &AsyncPartitionnerServer,
+    db: &DbContext,
     mut exit: EventWriter<AppExit>,
 ) {
     egui::Window::new("main menu")
@@ -41,6 +41,13 @@ pub(super) fn ui(
                         }
                     }
 
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if ui.button("💾 Export scene…").clicked() {
+                        if let Err(e) = export_data(db) {
+                            error!("Failed to export scene: {:?}", e);
+                        }
+                    }
+
                     ui.menu_button("📂 Built-in scenes", |ui| {
                         for (name, builder) in builtin_scenes::builders() {
                             if ui.button(name).clicked() {
@@ -52,27 +59,77 @@ pub(super) fn ui(
                     });
 
                     ui.menu_button("Network scenes", |ui| {
-                        for uuid in &ui_state.network_scenes {
-                            if ui.button(format!("{}", uuid.0)).clicked() {
-                                operations.push(Operation::LoadNetworkScene(*uuid));
+                        let network_scenes = block_on(db.network_scenes.read()).clone();
+                        for scene in &network_scenes {
+                            let name = if scene.name.is_empty() {
+                                format!("{}", scene.uuid.0)
+                            } else {
+                                scene.name.clone()
+                            };
+                            let state = if scene.running { "running" } else { "paused" };
+                            let label = format!("{name} ({} bodies, {state})", scene.num_bodies);
+                            if ui
+                                .button(label)
+                                .on_hover_text(scene_tooltip(scene))
+                                .clicked()
+                            {
+                                operations.push(Operation::LoadNetworkScene(scene.uuid));
                             }
                         }
 
                         if ui.button("Reload list…").clicked() {
-                            block_on(async {
-                                if let Ok(list) = partitionner.list_scenes().await {
-                                    ui_state.network_scenes = list.scenes;
-                                }
+                            let client = db.client.get();
+                            let network_scenes = db.network_scenes.clone();
+                            db.request(async move {
+                                let list = client.list_scenes().await?;
+                                *network_scenes.write().await = list.scenes;
+                                Ok(())
                             });
                         }
                     });
 
                     ui.checkbox(&mut theme.dark_mode, "Dark mode");
+                    ui.checkbox(&mut ui_state.region_overlay, "Show regions");
+                    if ui
+                        .checkbox(&mut ui_state.force_overlay, "Show force fields")
+                        .changed()
+                        && ui_state.force_overlay
+                    {
+                        let client = db.client.get();
+                        let scene = *block_on(db.scene.read());
+                        let force_generators = db.force_generators.clone();
+                        db.request(async move {
+                            let status = client.scene_status(scene).await?;
+                            *force_generators.write().await = status.force_generators;
+                            Ok(())
+                        });
+                    }
+                    ui.checkbox(&mut ui_state.minimap, "Minimap");
 
                     if ui.button("ℹ Simulation infos…").clicked() {
                         ui_state.simulation_infos_open = true;
                         ui.close_menu();
                     }
+                    if ui.button("🌲 Scene graph…").clicked() {
+                        ui_state.scene_graph_open = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("🗺 Regions…").clicked() {
+                        ui_state.region_list_open = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("➕ Spawn bodies…").clicked() {
+                        ui_state.spawn_toolbar_open = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("🔌 Connection…").clicked() {
+                        ui_state.connection_open = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("🐞 Debug render…").clicked() {
+                        ui_state.debug_render_open = true;
+                        ui.close_menu();
+                    }
                     ui.separator();
                     if ui.button("❌ Clear scene").clicked() {
                         operations.push(Operation::ClearScene)
@@ -81,10 +138,32 @@ pub(super) fn ui(
                         exit.send(AppExit);
                     }
                 });
+
+                match *block_on(db.connection_status.read()) {
+                    ConnectionStatus::Connected => {}
+                    ConnectionStatus::Reconnecting { attempt } => {
+                        ui.colored_label(
+                            egui::Color32::LIGHT_RED,
+                            format!("Reconnecting… (attempt {attempt})"),
+                        );
+                    }
+                }
             })
         });
 }
 
+/// The uuid of `scene`, and how long ago it was created.
+fn scene_tooltip(scene: &SceneInfo) -> String {
+    // NOTE: the wall clock isn’t available through `std` in the browser.
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Ok(now) = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        let age_mins = now.as_secs().saturating_sub(scene.created_at) / 60;
+        return format!("{}\nCreated {age_mins} min ago.", scene.uuid.0);
+    }
+
+    format!("{}", scene.uuid.0)
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 fn import_data<T: serde::Serialize>() -> anyhow::Result<Option<SaveFileData>> {
     if let Some(path) = FileDialog::new()
@@ -97,3 +176,22 @@ fn import_data<T: serde::Serialize>() -> anyhow::Result<Option<SaveFileData>> {
         Ok(None)
     }
 }
+
+/// Downloads the current state of the distributed scene into a file that can be opened again.
+#[cfg(not(target_arch = "wasm32"))]
+fn export_data(db: &DbContext) -> anyhow::Result<()> {
+    let Some(path) = FileDialog::new()
+        .add_filter("Json", &["json"])
+        .show_save_single_file()?
+    else {
+        return Ok(());
+    };
+
+    let export = block_on(async {
+        let scene = *db.scene.read().await;
+        db.client.get().export_scene(scene).await
+    })?;
+    let data = SaveFileData::from(export);
+    std::fs::write(path, serde_json::to_vec(&data)?)?;
+    Ok(())
+}
@@ -9,20 +9,28 @@ use image::Progress;
 use strum_macros::EnumIter;
 
 pub use self::plugin::RapierUiPlugin;
+use crate::camera::CameraNavigation;
 use crate::cli::CliArgs;
 use crate::operation::Operations;
-use crate::storage::DbContext;
+use crate::render::ColliderRenderShape;
+use crate::storage::{DbContext, Picking, PositionInterpolation, Spawner};
 use crate::styling::Theme;
 use crate::utils::{PhysicsObject, RapierContext};
 use crate::PhysicsProgress;
-pub use ui_state::UiState;
+pub use ui_state::{DebugRenderOptions, UiState};
 
+mod body_infos;
+mod connection;
+mod debug_render;
 // mod gizmo;
 mod main_menu;
 mod play_stop;
 mod plugin;
 mod popup_menu;
+mod region_list;
+mod scene_graph;
 mod simulation_infos;
+mod spawn_toolbar;
 mod ui_state;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter)]
@@ -89,7 +97,14 @@ pub fn update_ui(
     db_ctxt: Res<DbContext>,
     exit: EventWriter<AppExit>,
     windows: Query<&Window, With<PrimaryWindow>>,
-    visible_objects: Query<&InheritedVisibility, With<PhysicsObject>>,
+    visible_objects: Query<(&InheritedVisibility, &PositionInterpolation), With<PhysicsObject>>,
+    (mut picking, mut spawner, mut navigation): (
+        ResMut<Picking>,
+        ResMut<Spawner>,
+        ResMut<CameraNavigation>,
+    ),
+    objects: Query<(&PhysicsObject, &Transform, &ColliderRenderShape)>,
+    scene_objects: Query<(Entity, &PhysicsObject)>,
 ) {
     if let Ok(window) = windows.get_single() {
         main_menu::ui(
@@ -98,7 +113,7 @@ pub fn update_ui(
             &mut ui_context,
             &mut ui_state,
             &mut *operations,
-            &db_ctxt.partitionner,
+            &db_ctxt,
             exit,
         );
         play_stop::ui(
@@ -110,14 +125,30 @@ pub fn update_ui(
         );
         popup_menu::ui(window, &mut ui_context, &mut *physics_context);
 
-        let num_visible_objects = visible_objects.iter().filter(|vis| vis.get()).count();
         simulation_infos::ui(
             &mut ui_context,
             &mut ui_state,
-            &*physics_context,
             &*progress,
             &db_ctxt.stats,
-            num_visible_objects,
+            &visible_objects,
         );
+        body_infos::ui(
+            &mut ui_context,
+            &db_ctxt,
+            &mut picking,
+            &mut navigation,
+            &objects,
+        );
+        scene_graph::ui(
+            &mut ui_context,
+            &mut ui_state,
+            &db_ctxt,
+            &mut picking,
+            &scene_objects,
+        );
+        region_list::ui(&mut ui_context, &mut ui_state, &db_ctxt, &mut navigation);
+        spawn_toolbar::ui(&mut ui_context, &mut ui_state, &mut spawner);
+        connection::ui(&mut ui_context, &mut ui_state, &db_ctxt);
+        debug_render::ui(&mut ui_context, &mut ui_state);
     }
 }
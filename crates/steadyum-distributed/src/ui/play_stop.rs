@@ -3,6 +3,7 @@ use crate::utils::RapierContext;
 use bevy::window::Window;
 use bevy_egui::egui::PointerButton;
 use bevy_egui::{egui, EguiContexts};
+use steadyum_api_types::partitionner::TimeScale;
 
 use super::{ButtonTexture, UiState};
 
@@ -13,11 +14,6 @@ pub(super) fn ui(
     ui_state: &mut UiState,
     _physics_context: &mut RapierContext,
 ) {
-    if ui_state.single_step {
-        ui_state.single_step = false;
-        ui_state.running = false;
-    }
-
     let button_sz = [40.0, 40.0];
     let num_buttons = 3;
     let pos = [
@@ -42,12 +38,30 @@ pub(super) fn ui(
                 let play_pause_button = ui.button(play_pause.rich_text());
                 if play_pause_button.clicked_by(PointerButton::Primary) {
                     ui_state.running = !ui_state.running;
-                } else if play_pause_button.clicked_by(PointerButton::Secondary) {
-                    ui_state.running = true;
+                } else if play_pause_button.clicked_by(PointerButton::Secondary)
+                    && !ui_state.running
+                {
+                    // Run the number of steps below while paused.
                     ui_state.single_step = true;
                 }
 
                 let _ = ui.button(ButtonTexture::Redo.rich_text());
+
+                ui.add(
+                    egui::DragValue::new(&mut ui_state.num_debug_steps)
+                        .clamp_range(1..=1000)
+                        .suffix(" steps"),
+                )
+                .on_hover_text("Number of steps run by a right click on the play button.");
+
+                ui.add(
+                    egui::Slider::new(
+                        &mut ui_state.time_scale,
+                        TimeScale::MIN.0..=TimeScale::MAX.0,
+                    )
+                    .logarithmic(true)
+                    .text("Speed"),
+                );
             })
         });
 }
@@ -0,0 +1,42 @@
+use crate::block_on;
+use crate::camera::CameraNavigation;
+use crate::storage::DbContext;
+use crate::ui::UiState;
+use bevy_egui::{egui, EguiContexts};
+
+pub(super) fn ui(
+    ui_context: &mut EguiContexts,
+    ui_state: &mut UiState,
+    db: &DbContext,
+    navigation: &mut CameraNavigation,
+) {
+    if !ui_state.region_list_open {
+        return;
+    }
+
+    let mut regions: Vec<_> = block_on(db.region_stats.read())
+        .clone()
+        .into_iter()
+        .collect();
+    regions.sort_by_key(|(region, _)| *region);
+
+    egui::Window::new("🗺 Regions")
+        .open(&mut ui_state.region_list_open)
+        .default_height(300.0)
+        .show(ui_context.ctx_mut(), |ui| {
+            ui.label(format!("{} visible regions", regions.len()));
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (region, stats) in &regions {
+                    ui.horizontal(|ui| {
+                        if ui.button("Go").clicked() {
+                            navigation.jump_to = Some(*region);
+                        }
+                        ui.label(format!(
+                            "{:?}..{:?}: {} bodies",
+                            region.mins, region.maxs, stats.num_bodies
+                        ));
+                    });
+                }
+            });
+        });
+}
@@ -0,0 +1,113 @@
+use crate::block_on;
+use crate::storage::{DbContext, Picking};
+use crate::ui::UiState;
+use crate::utils::PhysicsObject;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use rapier::dynamics::RigidBodyType;
+use steadyum_api_types::simulation::SimulationBounds;
+
+const BODY_TYPES: [RigidBodyType; 4] = [
+    RigidBodyType::Dynamic,
+    RigidBodyType::Fixed,
+    RigidBodyType::KinematicPositionBased,
+    RigidBodyType::KinematicVelocityBased,
+];
+
+/// The bodies listed by the scene graph panel. `None` matches everything.
+#[derive(Default)]
+pub struct SceneGraphFilter {
+    pub uuid_prefix: String,
+    pub body_type: Option<RigidBodyType>,
+    pub sleeping: Option<bool>,
+    pub region: Option<SimulationBounds>,
+}
+
+impl SceneGraphFilter {
+    fn matches(&self, object: &PhysicsObject) -> bool {
+        object.uuid.to_string().starts_with(self.uuid_prefix.trim())
+            && self.body_type.map_or(true, |ty| ty == object.body_type)
+            && self
+                .sleeping
+                .map_or(true, |sleeping| sleeping == object.sleeping)
+            && self.region.map_or(true, |region| region == object.region)
+    }
+}
+
+pub(super) fn ui(
+    ui_context: &mut EguiContexts,
+    ui_state: &mut UiState,
+    db: &DbContext,
+    picking: &mut Picking,
+    objects: &Query<(Entity, &PhysicsObject)>,
+) {
+    if !ui_state.scene_graph_open {
+        return;
+    }
+
+    let filter = &mut ui_state.scene_graph_filter;
+    let mut regions: Vec<_> = block_on(db.region_stats.read()).keys().copied().collect();
+    regions.sort();
+
+    egui::Window::new("🌲 Scene graph")
+        .open(&mut ui_state.scene_graph_open)
+        .default_height(400.0)
+        .show(ui_context.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("UUID");
+                ui.text_edit_singleline(&mut filter.uuid_prefix);
+            });
+            egui::ComboBox::from_label("Body type")
+                .selected_text(option_text(&filter.body_type))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut filter.body_type, None, "Any");
+                    for ty in BODY_TYPES {
+                        ui.selectable_value(&mut filter.body_type, Some(ty), format!("{ty:?}"));
+                    }
+                });
+            egui::ComboBox::from_label("Sleeping")
+                .selected_text(option_text(&filter.sleeping))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut filter.sleeping, None, "Any");
+                    ui.selectable_value(&mut filter.sleeping, Some(true), "true");
+                    ui.selectable_value(&mut filter.sleeping, Some(false), "false");
+                });
+            egui::ComboBox::from_label("Region")
+                .selected_text(filter.region.map_or("Any".to_string(), region_text))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut filter.region, None, "Any");
+                    for region in regions {
+                        ui.selectable_value(&mut filter.region, Some(region), region_text(region));
+                    }
+                });
+            ui.separator();
+
+            let mut matches: Vec<_> = objects
+                .iter()
+                .filter(|(_, object)| filter.matches(object))
+                .map(|(entity, object)| (entity, object.uuid))
+                .collect();
+            matches.sort_by_key(|(_, uuid)| *uuid);
+
+            ui.label(format!("{} matching bodies", matches.len()));
+            let row_height = ui.text_style_height(&egui::TextStyle::Body);
+            egui::ScrollArea::vertical().show_rows(ui, row_height, matches.len(), |ui, rows| {
+                for (entity, uuid) in &matches[rows] {
+                    let selected = picking.selected == Some(*entity);
+                    if ui.selectable_label(selected, uuid.to_string()).clicked() {
+                        picking.selected = Some(*entity);
+                    }
+                }
+            });
+        });
+}
+
+fn option_text<T: std::fmt::Debug>(value: &Option<T>) -> String {
+    value
+        .as_ref()
+        .map_or("Any".to_string(), |value| format!("{value:?}"))
+}
+
+fn region_text(region: SimulationBounds) -> String {
+    format!("{:?}..{:?}", region.mins, region.maxs)
+}
@@ -1,45 +1,104 @@
-use crate::storage::DbStats;
+use crate::storage::{DbStats, PositionInterpolation};
 use crate::ui::UiState;
-use crate::utils::RapierContext;
+use crate::utils::PhysicsObject;
 use crate::PhysicsProgress;
+use bevy::prelude::*;
 use bevy_egui::{egui, EguiContexts};
+use std::sync::atomic::Ordering;
+
+/// The last bucket of the interpolation buffer histogram counts the bodies with at least this
+/// many buffered positions.
+const MAX_BUFFER_DEPTH: usize = 4;
 
 pub(super) fn ui(
     ui_context: &mut EguiContexts,
     ui_state: &mut UiState,
-    physics: &RapierContext,
     progress: &PhysicsProgress,
     db_stats: &DbStats,
-    num_visible_objects: usize,
+    visible_objects: &Query<(&InheritedVisibility, &PositionInterpolation), With<PhysicsObject>>,
 ) {
+    if !ui_state.simulation_infos_open {
+        return;
+    }
+
+    let mut num_visible_objects = 0;
+    let mut buffer_depths = [0; MAX_BUFFER_DEPTH + 1];
+    for (visibility, interpolation) in visible_objects.iter() {
+        if visibility.get() {
+            num_visible_objects += 1;
+            buffer_depths[interpolation.buffered_points().min(MAX_BUFFER_DEPTH)] += 1;
+        }
+    }
+
+    let stat = |value: &std::sync::atomic::AtomicUsize| value.load(Ordering::SeqCst);
+    let latest_step = progress.calculated_progress_limits_range[1];
+    let step_lag = latest_step.saturating_sub(progress.simulated_steps as u64);
+
     egui::Window::new("ℹ Simulation infos")
         .open(&mut ui_state.simulation_infos_open)
         .resizable(false)
         .show(ui_context.ctx_mut(), |ui| {
-            ui.label(stats_string(
-                physics,
-                progress,
-                db_stats,
-                num_visible_objects,
-            ));
-        });
-}
+            egui::Grid::new("simulation_infos")
+                .striped(true)
+                .show(ui, |ui| {
+                    let mut row = |name: &str, value: String| {
+                        ui.label(name);
+                        ui.label(value);
+                        ui.end_row();
+                    };
 
-fn stats_string(
-    physics: &RapierContext,
-    progress: &PhysicsProgress,
-    db_stats: &DbStats,
-    num_visible_objects: usize,
-) -> String {
-    format!(
-        r#"Visible objects: {}
-Progress limits range: {:?}
-curr step: {}/{}
-{:#?}"#,
-        num_visible_objects,
-        progress.calculated_progress_limits_range,
-        progress.simulated_steps,
-        progress.progress_limit,
-        db_stats
-    )
+                    row("Visible objects", num_visible_objects.to_string());
+                    row(
+                        "Step",
+                        format!("{}/{}", progress.simulated_steps, progress.progress_limit),
+                    );
+                    row("Step lag", step_lag.to_string());
+                    row("Time scale", format!("{:.2}x", progress.time_scale.0));
+                    row(
+                        "Query latency",
+                        format!("{:.1} ms", stat(&db_stats.query_latency_us) as f32 / 1000.0),
+                    );
+                    row(
+                        "Download",
+                        format!(
+                            "{:.1} KiB/s",
+                            stat(&db_stats.bytes_per_second) as f32 / 1024.0
+                        ),
+                    );
+                    row(
+                        "Regions polled",
+                        format!(
+                            "{} ({} visible, {} total)",
+                            stat(&db_stats.num_regions_polled),
+                            stat(&db_stats.num_visible_regions),
+                            stat(&db_stats.total_num_regions)
+                        ),
+                    );
+                    row("Objects read", stat(&db_stats.num_objects_read).to_string());
+                    row(
+                        "Read time",
+                        format!("{} ms", stat(&db_stats.total_db_read_time_ms)),
+                    );
+                    row(
+                        "Inserted objects",
+                        format!(
+                            "{}/{}",
+                            stat(&db_stats.num_objects_inserted),
+                            stat(&db_stats.num_objects_to_insert)
+                        ),
+                    );
+                });
+
+            ui.separator();
+            ui.label("Interpolation buffer depth");
+            for (depth, count) in buffer_depths.iter().enumerate() {
+                let fraction = *count as f32 / num_visible_objects.max(1) as f32;
+                let label = if depth == MAX_BUFFER_DEPTH {
+                    format!("{depth}+: {count}")
+                } else {
+                    format!("{depth}: {count}")
+                };
+                ui.add(egui::ProgressBar::new(fraction).text(label));
+            }
+        });
 }
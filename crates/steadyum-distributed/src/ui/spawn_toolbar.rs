@@ -0,0 +1,34 @@
+use crate::storage::{SpawnShape, Spawner};
+use crate::ui::UiState;
+use bevy_egui::{egui, EguiContexts};
+
+pub(super) fn ui(ui_context: &mut EguiContexts, ui_state: &mut UiState, spawner: &mut Spawner) {
+    egui::Window::new("➕ Spawn")
+        .open(&mut ui_state.spawn_toolbar_open)
+        .resizable(false)
+        .show(ui_context.ctx_mut(), |ui| {
+            egui::ComboBox::from_label("Shape")
+                .selected_text(format!("{:?}", spawner.shape))
+                .show_ui(ui, |ui| {
+                    for shape in SpawnShape::ALL {
+                        ui.selectable_value(&mut spawner.shape, *shape, format!("{shape:?}"));
+                    }
+                });
+            ui.add(egui::Slider::new(&mut spawner.size, 0.1..=10.0).text("Size"));
+            ui.add(egui::Slider::new(&mut spawner.density, 0.1..=100.0).text("Density"));
+            ui.add(egui::Slider::new(&mut spawner.speed, 0.0..=200.0).text("Speed"));
+
+            if ui.button("Spawn").clicked() {
+                spawner.requested += 1;
+            }
+
+            ui.separator();
+            ui.checkbox(&mut spawner.stream, "Spawn stream");
+            ui.add_enabled(
+                spawner.stream,
+                egui::Slider::new(&mut spawner.stream_rate, 1.0..=1000.0)
+                    .logarithmic(true)
+                    .text("Bodies/s"),
+            );
+        });
+}
@@ -1,17 +1,49 @@
 use bevy::prelude::*;
 use bevy_egui::egui::TextureId;
-use steadyum_api_types::partitionner::SceneUuid;
+
+/// What the debug render draws over the meshes of the bodies.
+#[derive(Copy, Clone, Default)]
+pub struct DebugRenderOptions {
+    /// The outlines of the colliders, colored by body type.
+    pub outlines: bool,
+    /// Darken the outlines of the sleeping bodies.
+    pub sleeping: bool,
+    pub aabbs: bool,
+    /// The balls detecting the bodies of the neighbor regions around the dynamic bodies, with
+    /// the default watch margins.
+    pub watch_radii: bool,
+}
+
+impl DebugRenderOptions {
+    pub fn any(&self) -> bool {
+        self.outlines || self.aabbs || self.watch_radii
+    }
+}
 
 #[derive(Resource)]
 pub struct UiState {
     pub button_texture_handles: Vec<Handle<Image>>,
     pub button_textures: Vec<TextureId>,
-    pub network_scenes: Vec<SceneUuid>,
     pub debug_render_open: bool,
+    pub debug_render: DebugRenderOptions,
+    pub region_overlay: bool,
+    pub force_overlay: bool,
+    pub region_list_open: bool,
+    pub minimap: bool,
+    pub scene_graph_open: bool,
+    pub scene_graph_filter: SceneGraphFilter,
     pub simulation_infos_open: bool,
+    pub spawn_toolbar_open: bool,
+    pub connection_open: bool,
+    /// The endpoint being edited in the connection panel.
+    pub endpoint_form: Option<ClusterEndpoint>,
+    /// Set to run `num_debug_steps` steps of the paused simulation.
     pub single_step: bool,
+    pub num_debug_steps: u64,
     pub running: bool,
     pub interpolation: bool,
+    pub extrapolation: bool,
+    pub time_scale: f32,
 }
 
 impl Default for UiState {
@@ -19,12 +51,24 @@ impl Default for UiState {
         Self {
             button_texture_handles: vec![],
             button_textures: vec![],
-            network_scenes: vec![],
             debug_render_open: false,
+            debug_render: DebugRenderOptions::default(),
+            region_overlay: false,
+            force_overlay: false,
+            region_list_open: false,
+            minimap: false,
+            scene_graph_open: false,
+            scene_graph_filter: SceneGraphFilter::default(),
             simulation_infos_open: false,
+            spawn_toolbar_open: false,
+            connection_open: false,
+            endpoint_form: None,
             single_step: false,
+            num_debug_steps: 1,
             running: false,
             interpolation: true,
+            extrapolation: true,
+            time_scale: 1.0,
         }
     }
 }
@@ -1,5 +1,11 @@
 use bevy::prelude::Component;
+use rapier::dynamics::RigidBodyType;
 use steadyum_api_types::kinematic::KinematicAnimations;
 
 #[derive(Clone, Component)]
-pub struct KinematicAnimationsComponent(pub KinematicAnimations);
+pub struct KinematicAnimationsComponent {
+    pub animations: KinematicAnimations,
+    /// Velocity-based kinematic bodies follow the animation’s velocities instead
+    /// of its positions.
+    pub body_type: RigidBodyType,
+}
@@ -2,7 +2,9 @@ pub use self::animation::*;
 pub use self::bevy_mesh_conversion::*;
 pub use self::rapier_context::RapierContext;
 use bevy::prelude::{Component, Transform};
+use rapier::dynamics::RigidBodyType;
 use rapier::math::{Isometry, Real};
+use steadyum_api_types::simulation::SimulationBounds;
 use uuid::Uuid;
 
 mod animation;
@@ -18,11 +20,21 @@ pub type Vect = bevy::prelude::Vec3;
 pub struct PhysicsObject {
     pub uuid: Uuid,
     pub sleeping: bool,
+    pub body_type: RigidBodyType,
+    /// The region that last reported this body.
+    pub region: SimulationBounds,
+    /// The opaque data attached to the body by its creator.
+    pub user_data: Vec<u8>,
 }
 
 #[derive(Copy, Clone, Component)]
 pub struct MissingDataPoints(pub usize);
 
+/// A hidden entity of a body no longer simulated, kept to be reused by the next body fetched
+/// instead of spawning a new entity.
+#[derive(Copy, Clone, Component)]
+pub struct PooledBody;
+
 /// Converts a Rapier isometry to a Bevy transform.
 ///
 /// The translation is multiplied by the `physics_scale`.
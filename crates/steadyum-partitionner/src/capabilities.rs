@@ -0,0 +1,40 @@
+//! The hardware of this node, sent to the master when registering as a child, so it gets a
+//! share of the scenes proportional to its capacity.
+
+use steadyum_api_types::partitionner::NodeCapabilities;
+
+pub fn detect() -> NodeCapabilities {
+    NodeCapabilities {
+        cores: std::thread::available_parallelism()
+            .map(|cores| cores.get() as u32)
+            .unwrap_or(0),
+        memory_bytes: total_memory().unwrap_or(0),
+        gpus: num_gpus(),
+    }
+}
+
+/// The `MemTotal` of `/proc/meminfo`, in bytes.
+fn total_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|line| line.starts_with("MemTotal:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// The number of NVIDIA devices, e.g., `/dev/nvidia0`.
+fn num_gpus() -> u32 {
+    let Ok(entries) = std::fs::read_dir("/dev") else {
+        return 0;
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.strip_prefix("nvidia")
+                .map(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()))
+                .unwrap_or(false)
+        })
+        .count() as u32
+}
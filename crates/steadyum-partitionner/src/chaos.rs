@@ -0,0 +1,50 @@
+use crate::AppState;
+use std::time::Duration;
+use steadyum_api_types::chaos::FAULTS;
+use tracing::{error, warn};
+
+/// How often the runners are considered for being killed.
+const KILL_PERIOD: Duration = Duration::from_secs(1);
+
+/// Randomly kills the runner processes spawned by this partitionner, with the chaos mode.
+///
+/// The killed runners are left in the bookkeeping of the partitionner so it notices the
+/// failure the same way it would notice a crash.
+pub fn chaos_loop(state: AppState) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(KILL_PERIOD).await;
+
+            let mut victims = vec![];
+            let to_remove = {
+                let mut runners = state.data.runners.lock().await;
+                let runners = &mut *runners;
+                for runner in runners
+                    .per_node
+                    .values_mut()
+                    .flat_map(|r| r.iter_mut())
+                    .chain(runners.host.iter_mut())
+                    .chain(runners.idle.iter_mut())
+                {
+                    if runner.process.is_none() || !FAULTS.kill_runner(KILL_PERIOD) {
+                        continue;
+                    }
+                    if let Some(process) = runner.process.take() {
+                        victims.push((runner.uuid, process));
+                    }
+                }
+                runners.to_remove.clone()
+            };
+
+            // NOTE: the runners are killed without holding the `runners` lock.
+            for (uuid, process) in victims {
+                warn!("Chaos: killing the runner {:?}.", uuid);
+                if let Err(e) = process.kill().await {
+                    error!("Chaos: failed to kill the runner {:?}: {e}", uuid);
+                }
+                // Reap the process.
+                let _ = to_remove.try_send(process);
+            }
+        }
+    });
+}
@@ -1,3 +1,7 @@
+use std::time::Duration;
+use steadyum_api_types::partitionner::NUM_INTERNAL_STEPS;
+use steadyum_api_types::rapier::math::Real;
+use steadyum_api_types::rapier::prelude::IntegrationParameters;
 use steadyum_api_types::simulation::SimulationBounds;
 use uuid::Uuid;
 
@@ -8,4 +12,47 @@ pub struct CliArgs {
     pub runner: bool,
     #[arg(short, long, default_value_t = false)]
     pub dev: bool,
+    /// Number of idle runner processes kept ready to adopt the first scene of this node, 0
+    /// disables the warm pool. Values above 1 are capped: that first scene's runner then hosts
+    /// all the scenes and regions of the node.
+    #[arg(long, default_value_t = 1)]
+    pub warm_runners: u32,
+    /// How fast the master partitionner steps the simulation.
+    #[arg(long, value_enum, default_value_t = Pacing::MaxSpeed)]
+    pub pacing: Pacing,
+    /// Target number of steps per second with the real-time pacing. Defaults to the rate
+    /// matching the wall-clock time.
+    #[arg(long)]
+    pub steps_per_second: Option<f32>,
+    /// Simulation time, in milliseconds, of the first step. Forwarded to the spawned runners.
+    #[arg(long, default_value_t = 0)]
+    pub time_origin: u64,
+    /// Identifies this partitionner across its restarts, to re-adopt the runners it spawned.
+    /// Defaults to its type and port.
+    #[arg(long)]
+    pub node_id: Option<String>,
+}
+
+/// How fast the master partitionner steps the simulation.
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Pacing {
+    /// Start the next step as soon as the previous one was acked, e.g., for batch jobs.
+    MaxSpeed,
+    /// Run at most `--steps-per-second` steps per second, e.g., for interactive clients.
+    RealTime,
+}
+
+impl CliArgs {
+    /// The minimum duration between the starts of two consecutive steps, if any.
+    pub fn step_period(&self) -> Option<Duration> {
+        match self.pacing {
+            Pacing::MaxSpeed => None,
+            Pacing::RealTime => {
+                let steps_per_second = self.steps_per_second.unwrap_or_else(|| {
+                    1.0 / (IntegrationParameters::default().dt * NUM_INTERNAL_STEPS as Real)
+                });
+                Some(Duration::from_secs_f32(1.0 / steps_per_second))
+            }
+        }
+    }
 }
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use steadyum_api_types::env::StepLimitPolicy;
+use steadyum_api_types::partitionner::NUM_INTERNAL_STEPS;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+/// The progress of the clients of a scene, from which its step limit is derived.
+#[derive(Default)]
+pub struct ClientsProgress {
+    clients: HashMap<Uuid, ClientProgress>,
+}
+
+struct ClientProgress {
+    /// The furthest step displayed by the client.
+    step_id: u64,
+    last_input: Instant,
+}
+
+impl ClientProgress {
+    fn is_active(&self, now: Instant, timeout: Duration) -> bool {
+        now.duration_since(self.last_input) < timeout
+    }
+}
+
+impl ClientsProgress {
+    /// Records that `client` displayed the step `step_id`.
+    pub fn update(&mut self, client: Uuid, step_id: u64, now: Instant) {
+        let progress = self.clients.entry(client).or_insert(ClientProgress {
+            step_id,
+            last_input: now,
+        });
+        progress.step_id = progress.step_id.max(step_id);
+        progress.last_input = now;
+    }
+
+    /// Forgets the clients that sent no input for `timeout`, and returns `true` if there were any.
+    pub fn expire(&mut self, now: Instant, timeout: Duration) -> bool {
+        let num_clients = self.clients.len();
+        self.clients
+            .retain(|_, progress| progress.is_active(now, timeout));
+        self.clients.len() != num_clients
+    }
+
+    /// The step the simulation can run to, a few steps ahead of the clients selected by
+    /// `policy`, or `None` if no client is active.
+    pub fn step_limit(
+        &self,
+        policy: StepLimitPolicy,
+        now: Instant,
+        timeout: Duration,
+    ) -> Option<u64> {
+        let active = self
+            .clients
+            .values()
+            .filter(|progress| progress.is_active(now, timeout))
+            .map(|progress| progress.step_id);
+        let step_id = match policy {
+            StepLimitPolicy::Furthest => active.max(),
+            StepLimitPolicy::Slowest => active.min(),
+        }?;
+        Some(step_id / NUM_INTERNAL_STEPS + 2)
+    }
+}
@@ -0,0 +1,212 @@
+//! Debug service checking the invariants of the simulation on the client objects streamed by
+//! the regions, enabled with `CONSISTENCY_CHECKS`.
+
+use crate::AppState;
+use std::collections::{HashMap, VecDeque};
+use steadyum_api_types::messages::{ClientInterest, CLIENT_INTEREST_REFRESH_PERIOD};
+use steadyum_api_types::objects::ClientBodyObjectSet;
+use steadyum_api_types::partitionner::{ConsistencyViolation, ConsistencyViolationKind, SceneUuid};
+use steadyum_api_types::rapier::dynamics::IntegrationParameters;
+use steadyum_api_types::rapier::math::{Real, Vector};
+use steadyum_api_types::rapier::parry::bounding_volume::Aabb;
+use steadyum_api_types::serialization::deserialize_region_client_objects;
+use steadyum_api_types::simulation::SimulationBounds;
+use steadyum_api_types::zenoh::{all_client_objects_stream_key_expr, client_interest_key};
+use tracing::{error, warn};
+use uuid::Uuid;
+use zenoh::prelude::r#async::AsyncResolve;
+use zenoh::prelude::SplitBuffer;
+
+/// The number of violations kept for the status of each scene.
+const MAX_VIOLATIONS: usize = 100;
+/// How much further than its velocities allow a body can move between two reports, to account
+/// for its accelerations.
+const TELEPORT_TOLERANCE: Real = 2.0;
+/// Distance any body can move between two reports, e.g., when pushed while at rest.
+const TELEPORT_MARGIN: Real = 0.5;
+
+/// The last report of a body.
+struct BodyReport {
+    region: SimulationBounds,
+    timestamp: u64,
+    translation: Vector<Real>,
+    linvel: Vector<Real>,
+}
+
+/// The last reports of the bodies and regions of a scene.
+#[derive(Default)]
+struct SceneReports {
+    bodies: HashMap<Uuid, BodyReport>,
+    regions: HashMap<SimulationBounds, u64>,
+}
+
+impl SceneReports {
+    /// Checks the objects streamed by `region` against the previous reports, and records them.
+    fn check(
+        &mut self,
+        region: SimulationBounds,
+        set: &ClientBodyObjectSet,
+        dt: Real,
+    ) -> Vec<ConsistencyViolation> {
+        let mut violations = vec![];
+        let timestamp = set.timestamp;
+        let violation = |body, kind| ConsistencyViolation {
+            region,
+            timestamp,
+            body,
+            kind,
+        };
+
+        if let Some(previous) = self.regions.insert(region, timestamp) {
+            if timestamp < previous {
+                let kind = ConsistencyViolationKind::TimestampRegression { previous };
+                violations.push(violation(None, kind));
+            }
+        }
+
+        // NOTE: the ghosts are extrapolated by the regions that handed them off, they aren’t
+        //       simulated by them anymore.
+        for object in &set.objects {
+            let translation = object.position.translation.vector;
+
+            if let Some(report) = self.bodies.get(&object.uuid) {
+                if report.region != region && report.timestamp == timestamp {
+                    let kind = ConsistencyViolationKind::DuplicateOwner {
+                        other: report.region,
+                    };
+                    violations.push(violation(Some(object.uuid), kind));
+                } else if timestamp > report.timestamp {
+                    let elapsed = (timestamp - report.timestamp) as Real * dt;
+                    let speed = report.linvel.norm().max(object.linvel.norm());
+                    let max_distance = speed * elapsed * TELEPORT_TOLERANCE + TELEPORT_MARGIN;
+                    let distance = (translation - report.translation).norm();
+                    if distance > max_distance {
+                        let kind = ConsistencyViolationKind::Teleport {
+                            distance,
+                            max_distance,
+                        };
+                        violations.push(violation(Some(object.uuid), kind));
+                    }
+                }
+
+                if timestamp < report.timestamp {
+                    // An older report, e.g., from a region that is late.
+                    continue;
+                }
+            }
+
+            let report = BodyReport {
+                region,
+                timestamp,
+                translation,
+                linvel: object.linvel,
+            };
+            self.bodies.insert(object.uuid, report);
+        }
+
+        violations
+    }
+}
+
+/// The key of a streamed client object set: its scene and region.
+fn parse_stream_key(key: &str) -> Option<(SceneUuid, SimulationBounds)> {
+    let mut parts = key.rsplit('/');
+    let region = SimulationBounds::from_str(parts.next()?)?;
+    let scene = Uuid::parse_str(parts.next()?).ok()?;
+    Some((SceneUuid(scene), region))
+}
+
+/// Subscribes to the client objects streamed by every region, and records the violations of
+/// the invariants of the simulation in `violations`:
+/// - a body is simulated by a single region at a time.
+/// - a body doesn’t move further than its velocity allows between two reports.
+/// - the timestamps of a region never decrease.
+///
+/// The regions only stream their objects to interested clients, so the checker declares
+/// itself interested in the whole of every scene.
+pub fn consistency_loop(state: AppState) {
+    tokio::spawn(async move {
+        if let Err(e) = check_consistency(state).await {
+            error!("The consistency checks stopped: {e}");
+        }
+    });
+}
+
+async fn check_consistency(state: AppState) -> anyhow::Result<()> {
+    let checker = Uuid::new_v4();
+    let dt = IntegrationParameters::default().dt;
+    let subscriber = state
+        .data
+        .zenoh
+        .session
+        .declare_subscriber(all_client_objects_stream_key_expr())
+        .res_async()
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+    let mut interest_refresh = tokio::time::interval(CLIENT_INTEREST_REFRESH_PERIOD);
+    let mut reports: HashMap<SceneUuid, SceneReports> = HashMap::new();
+    let half_extents = Vector::repeat(Real::MAX / 4.0);
+    let interest = ClientInterest {
+        view: Aabb::new((-half_extents).into(), half_extents.into()),
+        lod: None,
+    };
+
+    loop {
+        tokio::select! {
+            _ = interest_refresh.tick() => {
+                let scenes: Vec<_> = {
+                    let runners = state.data.runners.lock().await;
+                    runners
+                        .per_node
+                        .keys()
+                        .filter(|scene| !runners.exited.contains(scene))
+                        .copied()
+                        .collect()
+                };
+
+                reports.retain(|scene, _| scenes.contains(scene));
+                state
+                    .data
+                    .violations
+                    .write()
+                    .await
+                    .retain(|scene, _| scenes.contains(scene));
+
+                for scene in scenes {
+                    let key = client_interest_key(scene, checker);
+                    if let Err(e) = state.data.zenoh.put(&key, &interest).await {
+                        warn!("Failed to publish the interest of the consistency checks: {e}");
+                    }
+                }
+            }
+            sample = subscriber.recv_async() => {
+                let sample = sample.map_err(|e| anyhow::anyhow!("{e}"))?;
+                let Some((scene, region)) = parse_stream_key(sample.key_expr.as_str()) else {
+                    continue;
+                };
+                let streamed =
+                    match deserialize_region_client_objects(&sample.value.payload.contiguous()) {
+                        Ok(streamed) => streamed,
+                        Err(e) => {
+                            warn!("Failed to decode the client objects of {region:?}: {e}");
+                            continue;
+                        }
+                    };
+                let set = streamed.set.decode(&region);
+                let new_violations = reports.entry(scene).or_default().check(region, &set, dt);
+
+                if !new_violations.is_empty() {
+                    let mut violations = state.data.violations.write().await;
+                    let violations = violations.entry(scene).or_insert_with(VecDeque::new);
+                    for violation in new_violations {
+                        warn!("Consistency violation in scene {scene:?}: {violation:?}");
+                        if violations.len() == MAX_VIOLATIONS {
+                            violations.pop_front();
+                        }
+                        violations.push_back(violation);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,459 @@
+use crate::negotiated::Negotiated;
+use crate::spawn::spawn_loop;
+use crate::tenants::{ClusterMember, SceneUsage};
+use crate::{
+    control_plane_loop, input_handling_loop, put_runner_message, router, start_stop_scene,
+    AppState, PartitionnerType, Runner, SceneAcks, SceneGeometry, SceneMetadata,
+};
+use axum::extract::State;
+use futures::future::join_all;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use steadyum_api_types::env::{ControlPlane, CONFIG};
+use steadyum_api_types::messages::RunnerMessage;
+use steadyum_api_types::partitionner::{
+    ChildPartitionner, ReplicatedScene, ReplicatedState, SceneSettings, StartStopRequest, TimeScale,
+};
+use steadyum_api_types::region_db::{AsyncPartitionnerServer, RetryPolicy};
+use steadyum_api_types::serialization::ObjectSetEncoding;
+use steadyum_api_types::store::{partitionner_runners_key, SCENE_METADATA_KEY};
+use tracing::{error, info, warn};
+
+/// How often the master sends its scene metadata to its children.
+const REPLICATION_PERIOD: Duration = Duration::from_secs(1);
+/// How often the children check that the master is alive.
+const MASTER_HEARTBEAT_PERIOD: Duration = Duration::from_secs(1);
+/// Number of consecutive missed heartbeats after which the master is assumed dead.
+const MAX_MISSED_HEARTBEATS: u32 = 3;
+/// How often a partitionner persists its runners and region assignments.
+const PERSISTENCE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Periodically sends the scene metadata of the master to all its children.
+pub fn replication_loop(state: AppState) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(REPLICATION_PERIOD).await;
+
+            let replica = snapshot(&state).await;
+            if let Err(e) = state
+                .data
+                .store
+                .put_value(SCENE_METADATA_KEY, &replica)
+                .await
+            {
+                warn!("Failed to store the scene metadata: {e}");
+            }
+
+            let children: Vec<_> = state.data.children.lock().await.iter().cloned().collect();
+
+            for child_partitionner in children {
+                if let Err(e) = child_partitionner.replicate(&replica).await {
+                    warn!("Failed to replicate the scene metadata: {e}");
+                }
+            }
+        }
+    });
+}
+
+/// The scene metadata needed by a child to take over as the master.
+async fn snapshot(state: &AppState) -> ReplicatedState {
+    let data = &state.data;
+    let children = data.children_endpoints.lock().await.clone();
+    let children_capabilities = data.children_capabilities.lock().await.clone();
+
+    let mut scenes: Vec<_> = {
+        let runners = data.runners.lock().await;
+        runners
+            .per_node
+            .iter()
+            .filter(|(scene, _)| !runners.exited.contains(scene))
+            .map(|(scene, scene_runners)| ReplicatedScene {
+                scene: *scene,
+                children_bounds: vec![],
+                client_objects_encoding: ObjectSetEncoding::default(),
+                runners: scene_runners.iter().map(|runner| runner.uuid).collect(),
+                assigned: data
+                    .assigned_runners
+                    .iter()
+                    .filter(|entry| entry.key().0 == *scene)
+                    .map(|entry| (entry.key().1, *entry.value()))
+                    .collect(),
+                static_bodies: vec![],
+                step_id: 0,
+                step_limit: 0,
+                time_scale: TimeScale::default(),
+                tenant: None,
+                num_bodies: 0,
+                name: String::new(),
+                created_at: 0,
+            })
+            .collect()
+    };
+    scenes.sort_by_key(|replicated| replicated.scene.0);
+
+    // NOTE: each lock is released before taking the next one, since other tasks take
+    //       them in different orders.
+    for replicated in &mut scenes {
+        let scene = replicated.scene;
+        if let Some(geometry) = data.scenes_geometries.read().await.get(&scene) {
+            replicated.children_bounds = geometry.children_bounds.clone();
+        }
+        if let Some(encoding) = data.client_objects_encodings.read().await.get(&scene) {
+            replicated.client_objects_encoding = *encoding;
+        }
+        if let Some(static_bodies) = data.static_bodies.read().await.get(&scene) {
+            replicated.static_bodies = static_bodies.clone();
+        }
+        if let Some(scene_acks) = data.scenes_acks.read().await.get(&scene) {
+            replicated.step_id = scene_acks.step_id.load(Ordering::SeqCst);
+            replicated.step_limit = scene_acks.step_limit.load(Ordering::SeqCst);
+            replicated.time_scale = *scene_acks.time_scale.read().await;
+        }
+        if let Some(usage) = data.scene_usages.read().await.get(&scene) {
+            replicated.tenant = Some(usage.tenant.clone());
+            replicated.num_bodies = usage.bodies;
+        }
+        if let Some(metadata) = data.scenes_metadata.read().await.get(&scene) {
+            replicated.name = metadata.settings.name.clone();
+            replicated.created_at = metadata.created_at;
+        }
+    }
+
+    ReplicatedState {
+        children,
+        children_capabilities,
+        running: data.running.load(Ordering::SeqCst),
+        scenes,
+    }
+}
+
+/// Stores the scene metadata sent by the master.
+pub async fn replicate(
+    State(state): State<AppState>,
+    _member: ClusterMember,
+    Negotiated(payload): Negotiated<ReplicatedState>,
+) {
+    // If the master dies, the children take over in registration order.
+    if let Some(parent_partitionner) = &state.data.parent_partitionner {
+        let candidates = payload.children.iter().map(failover_endpoint).collect();
+        parent_partitionner.set_candidates(candidates);
+    }
+
+    if let Err(e) = state
+        .data
+        .store
+        .put_value(SCENE_METADATA_KEY, &payload)
+        .await
+    {
+        warn!("Failed to store the replicated scene metadata: {e}");
+    }
+}
+
+/// The endpoint `child` serves the master endpoints on once it took over.
+fn failover_endpoint(child: &ChildPartitionner) -> (String, u16) {
+    (child.addr.clone(), CONFIG.failover_port)
+}
+
+/// Watches the master partitionner and elects a new one among its children if it dies.
+///
+/// The first child of the replicated metadata still alive is elected. If that is `me`, this
+/// partitionner takes over.
+pub fn master_watchdog_loop(state: AppState, me: ChildPartitionner) {
+    tokio::spawn(async move {
+        let mut master = (CONFIG.partitionner_addr.clone(), CONFIG.partitionner_port);
+        let mut missed_heartbeats = 0;
+
+        loop {
+            tokio::time::sleep(MASTER_HEARTBEAT_PERIOD).await;
+
+            // A decommissioned child isn’t part of the cluster anymore.
+            if state.data.decommissioned.load(Ordering::SeqCst) {
+                return;
+            }
+
+            if is_alive(&master).await {
+                missed_heartbeats = 0;
+                continue;
+            }
+
+            missed_heartbeats += 1;
+            if missed_heartbeats < MAX_MISSED_HEARTBEATS {
+                continue;
+            }
+            missed_heartbeats = 0;
+
+            let replica = match state
+                .data
+                .store
+                .get_value::<ReplicatedState>(SCENE_METADATA_KEY)
+                .await
+            {
+                Ok(Some(replica)) => replica,
+                Ok(None) => {
+                    warn!("Master partitionner unreachable, but no scene metadata was replicated.");
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Failed to read the replicated scene metadata: {e}");
+                    continue;
+                }
+            };
+
+            warn!(
+                "The master partitionner {}:{} is unreachable, electing a new one.",
+                master.0, master.1
+            );
+
+            for child in &replica.children {
+                if *child == me {
+                    promote(&state, replica.clone()).await;
+                    return;
+                }
+
+                if is_alive(&(child.addr.clone(), child.port)).await {
+                    info!("Elected {}:{} as the new master.", child.addr, child.port);
+                    master = failover_endpoint(child);
+                    break;
+                }
+            }
+        }
+    });
+}
+
+async fn is_alive((addr, port): &(String, u16)) -> bool {
+    let Ok(server) = AsyncPartitionnerServer::with_endpoint(addr.clone(), *port) else {
+        return false;
+    };
+    let policy = RetryPolicy {
+        max_retries: 0,
+        ..RetryPolicy::default()
+    };
+    server.retry_policy(policy).heartbeat().await.is_ok()
+}
+
+/// Starts serving the master endpoints on the failover port, from the replicated metadata.
+///
+/// This partitionner keeps acting as a child for its own runners.
+async fn promote(state: &AppState, replica: ReplicatedState) {
+    warn!("Taking over as the master partitionner.");
+
+    // The master never spawns runner processes itself.
+    let (to_remove, _) = async_channel::unbounded();
+    let master = AppState::new(
+        PartitionnerType::Master,
+        state.data.zenoh.clone(),
+        state.data.store.clone(),
+        state.data.step_period,
+        state.data.time_origin,
+        to_remove,
+    );
+    restore(&master, &replica).await;
+
+    input_handling_loop(master.clone());
+    replication_loop(master.clone());
+    spawn_loop(master.clone());
+    if CONFIG.control_plane == ControlPlane::Zenoh {
+        control_plane_loop(master.clone());
+    }
+
+    let app = router(master.clone());
+    tokio::spawn(async move {
+        let addr = format!("0.0.0.0:{}", CONFIG.failover_port).parse().unwrap();
+        if let Err(e) = axum::Server::bind(&addr)
+            .serve(app.into_make_service())
+            .await
+        {
+            error!("The master partitionner stopped: {e}");
+        }
+    });
+
+    if replica.running {
+        for scene in &replica.scenes {
+            let request = StartStopRequest {
+                scene: scene.scene,
+                running: true,
+            };
+            start_stop_scene(&master, request).await;
+        }
+    }
+}
+
+/// Fills the state of a new master from the metadata replicated by the previous one, or of a
+/// restarted partitionner from the metadata it persisted.
+async fn restore(state: &AppState, replica: &ReplicatedState) {
+    let data = &state.data;
+
+    for (id, child) in replica.children.iter().enumerate() {
+        let child_server =
+            AsyncPartitionnerServer::with_endpoint(child.addr.clone(), child.port).unwrap();
+        let capabilities = replica
+            .children_capabilities
+            .get(id)
+            .copied()
+            .unwrap_or_default();
+        data.children.lock().await.push(child_server);
+        data.children_endpoints.lock().await.push(child.clone());
+        data.children_capabilities.lock().await.push(capabilities);
+    }
+
+    for scene in &replica.scenes {
+        let geometry = SceneGeometry {
+            children_bounds: scene.children_bounds.clone(),
+            provisional: false,
+            redirects: HashMap::new(),
+        };
+        data.scenes_geometries
+            .write()
+            .await
+            .insert(scene.scene, geometry);
+        data.client_objects_encodings
+            .write()
+            .await
+            .insert(scene.scene, scene.client_objects_encoding);
+        // NOTE: the random generators aren’t replicated, so the templates spawned after
+        //       a failover differ from the ones of an uninterrupted run.
+        data.scenes_rngs
+            .lock()
+            .await
+            .insert(scene.scene, StdRng::from_entropy());
+        data.static_bodies
+            .write()
+            .await
+            .insert(scene.scene, scene.static_bodies.clone());
+
+        let mut scene_acks = SceneAcks::default();
+        scene_acks.step_id.store(scene.step_id, Ordering::SeqCst);
+        scene_acks
+            .step_limit
+            .store(scene.step_limit, Ordering::SeqCst);
+        *scene_acks.time_scale.get_mut() = scene.time_scale;
+        data.scenes_acks
+            .write()
+            .await
+            .insert(scene.scene, scene_acks);
+
+        let mut runners = data.runners.lock().await;
+        let scene_runners = scene
+            .runners
+            .iter()
+            .map(|uuid| Runner {
+                process: None,
+                uuid: *uuid,
+                is_new: false,
+            })
+            .collect();
+        runners.per_node.insert(scene.scene, scene_runners);
+        for (region, uuid) in &scene.assigned {
+            data.assigned_runners.insert((scene.scene, *region), *uuid);
+        }
+        if let Some(tenant) = &scene.tenant {
+            let usage = SceneUsage {
+                tenant: tenant.clone(),
+                runners: scene.runners.len(),
+                bodies: scene.num_bodies,
+            };
+            data.scene_usages.write().await.insert(scene.scene, usage);
+        }
+        // NOTE: the seed and the other settings aren’t replicated, the saves of this scene
+        //       will use the default ones.
+        let metadata = SceneMetadata {
+            created_at: scene.created_at,
+            seed: 0,
+            settings: SceneSettings {
+                name: scene.name.clone(),
+                time_scale: scene.time_scale,
+                ..SceneSettings::default()
+            },
+        };
+        data.scenes_metadata
+            .write()
+            .await
+            .insert(scene.scene, metadata);
+    }
+}
+
+/// Periodically persists the scenes, runners and region assignments of this partitionner,
+/// so it re-adopts its runners if it restarts.
+pub fn persistence_loop(state: AppState, node: String) {
+    tokio::spawn(async move {
+        let key = partitionner_runners_key(&node);
+
+        loop {
+            tokio::time::sleep(PERSISTENCE_PERIOD).await;
+
+            let snapshot = snapshot(&state).await;
+            if let Err(e) = state.data.store.put_value(&key, &snapshot).await {
+                warn!("Failed to persist the runners of this partitionner: {e}");
+            }
+        }
+    });
+}
+
+/// Re-adopts the runners persisted by a previous process of this partitionner, instead of
+/// spawning new ones for their scenes.
+///
+/// The scenes with a runner that no longer answers are dropped. The others are restored
+/// stopped, at the step they were at.
+pub async fn readopt_runners(state: &AppState, node: &str) {
+    let mut persisted = match state
+        .data
+        .store
+        .get_value::<ReplicatedState>(&partitionner_runners_key(node))
+        .await
+    {
+        Ok(Some(persisted)) => persisted,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Failed to read the persisted runners of this partitionner: {e}");
+            return;
+        }
+    };
+
+    let mut uuids: Vec<_> = persisted
+        .scenes
+        .iter()
+        .flat_map(|scene| scene.runners.iter().copied())
+        .collect();
+    uuids.sort();
+    uuids.dedup();
+    let alive: HashSet<_> = join_all(uuids.into_iter().map(|uuid| async move {
+        put_runner_message(&state.data.zenoh, uuid, RunnerMessage::Ping)
+            .await
+            .ok()
+            .map(|_| uuid)
+    }))
+    .await
+    .into_iter()
+    .flatten()
+    .collect();
+
+    persisted.scenes.retain(|scene| {
+        let is_alive = scene.runners.iter().all(|uuid| alive.contains(uuid));
+        if !is_alive {
+            warn!("Dropping scene {:?}: its runners are gone.", scene.scene);
+        }
+        is_alive
+    });
+
+    // All the scenes of a node are hosted by the same runner process.
+    if state.data.my_type != PartitionnerType::Master {
+        let host = persisted
+            .scenes
+            .iter()
+            .find_map(|scene| scene.runners.first().copied());
+        if let Some(uuid) = host {
+            info!("Re-adopting the host runner {uuid:?}.");
+            state.data.runners.lock().await.host = Some(Runner {
+                process: None,
+                uuid,
+                is_new: false,
+            });
+        }
+    }
+
+    info!("Re-adopted {} scenes.", persisted.scenes.len());
+    restore(state, &persisted).await;
+}
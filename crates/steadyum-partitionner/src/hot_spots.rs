@@ -0,0 +1,134 @@
+use crate::{query_region_profiles, AppState};
+use std::collections::HashMap;
+use std::time::Duration;
+use steadyum_api_types::env::CONFIG;
+use steadyum_api_types::partitionner::{HotRegion, HotRegionReason, RegionProfile};
+use tracing::{info, warn};
+
+/// How often the regions of every scene are checked for hot spots.
+const HOT_SPOT_CHECK_PERIOD: Duration = Duration::from_secs(5);
+
+/// The time a region spent on its last step, excluding the wait for the step to start.
+fn busy_time(profile: &RegionProfile) -> f32 {
+    profile.timings.loop_time - profile.timings.waiting_acks
+}
+
+/// The thresholds above which a region is a hot spot.
+#[derive(Copy, Clone, Debug)]
+pub struct HotSpotPolicy {
+    /// In seconds.
+    pub max_step_time: f32,
+    pub max_bodies: usize,
+    /// Maximum ratio between the step time of a region and the median one of its scene.
+    pub max_imbalance: f32,
+}
+
+impl Default for HotSpotPolicy {
+    fn default() -> Self {
+        Self {
+            max_step_time: CONFIG.hot_region_step_ms / 1000.0,
+            max_bodies: CONFIG.hot_region_bodies,
+            max_imbalance: CONFIG.hot_region_imbalance,
+        }
+    }
+}
+
+impl HotSpotPolicy {
+    /// The hot regions among `profiles`, the profiles of all the regions of a scene, slowest
+    /// first.
+    pub fn hot_regions(&self, profiles: Vec<RegionProfile>) -> Vec<HotRegion> {
+        let mut busy_times: Vec<_> = profiles.iter().map(busy_time).collect();
+        busy_times.sort_by(f32::total_cmp);
+        let median = busy_times.get(busy_times.len() / 2).copied().unwrap_or(0.0);
+
+        let mut result: Vec<_> = profiles
+            .into_iter()
+            .filter_map(|profile| {
+                let mut reasons = vec![];
+                if busy_time(&profile) > self.max_step_time {
+                    reasons.push(HotRegionReason::SlowStep);
+                }
+                if profile.stats.num_bodies > self.max_bodies {
+                    reasons.push(HotRegionReason::TooManyBodies);
+                }
+                // NOTE: the median is zero while the scene is stopped.
+                if median > 0.0 && busy_time(&profile) > median * self.max_imbalance {
+                    reasons.push(HotRegionReason::Imbalance);
+                }
+
+                (!reasons.is_empty()).then_some(HotRegion {
+                    profile,
+                    reasons,
+                    num_checks: 1,
+                })
+            })
+            .collect();
+        result.sort_by(|a, b| busy_time(&b.profile).total_cmp(&busy_time(&a.profile)));
+        result
+    }
+}
+
+/// Periodically checks the profiles of the regions of every scene for hot spots, and warns
+/// about the regions that just became hot.
+pub fn hot_spot_loop(state: AppState) {
+    tokio::spawn(async move {
+        let policy = HotSpotPolicy::default();
+
+        loop {
+            tokio::time::sleep(HOT_SPOT_CHECK_PERIOD).await;
+
+            let scenes: Vec<_> = {
+                let runners = state.data.runners.lock().await;
+                runners
+                    .per_node
+                    .keys()
+                    .filter(|scene| !runners.exited.contains(scene))
+                    .copied()
+                    .collect()
+            };
+
+            for scene in scenes {
+                let profiles = match query_region_profiles(&state, scene).await {
+                    Ok(profiles) => profiles,
+                    Err(e) => {
+                        warn!("Failed to profile scene {scene:?}: {e}");
+                        continue;
+                    }
+                };
+                let mut hot_regions = policy.hot_regions(profiles);
+
+                let mut all_hot_regions = state.data.hot_regions.write().await;
+                let previous: HashMap<_, _> = all_hot_regions
+                    .remove(&scene)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|hot| (hot.profile.region, hot.num_checks))
+                    .collect();
+
+                for hot in &mut hot_regions {
+                    let region = hot.profile.region;
+                    match previous.get(&region) {
+                        Some(num_checks) => hot.num_checks += num_checks,
+                        None => warn!(
+                            "Region {region:?} of scene {scene:?} is hot ({:?}): {:.0} ms per \
+                             step, {} bodies.",
+                            hot.reasons,
+                            busy_time(&hot.profile) * 1000.0,
+                            hot.profile.stats.num_bodies
+                        ),
+                    }
+                }
+
+                for region in previous.keys() {
+                    if !hot_regions.iter().any(|hot| hot.profile.region == *region) {
+                        info!("Region {region:?} of scene {scene:?} cooled down.");
+                    }
+                }
+
+                if !hot_regions.is_empty() {
+                    all_hot_regions.insert(scene, hot_regions);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use steadyum_api_types::messages::BodyAssignment;
+use steadyum_api_types::rapier::math::Real;
+use steadyum_api_types::rapier::parry::bounding_volume::{Aabb, BoundingVolume};
+use steadyum_api_types::rapier::parry::partitioning::Qbvh;
+use steadyum_api_types::simulation::SimulationBounds;
+
+/// Margin added to the AABBs of the inserted bodies to find the ones touching each other.
+const CONTACT_MARGIN: Real = 0.01;
+
+/// Groups the given dynamic bodies by region, keeping bodies touching each other in the
+/// same region.
+///
+/// Like the runners do after each step, an island is assigned to the largest region of its
+/// bodies. Doing it at insertion time avoids migrating most of the bodies during the first step.
+pub fn group_islands_by_region(
+    bodies: Vec<BodyAssignment>,
+) -> HashMap<SimulationBounds, Vec<BodyAssignment>> {
+    let aabbs: Vec<Aabb> = bodies
+        .iter()
+        .map(|body| body.cold.shape.compute_aabb(&body.warm.position))
+        .collect();
+
+    let mut tree = Qbvh::new();
+    tree.clear_and_rebuild(aabbs.iter().copied().enumerate(), 0.0);
+
+    let mut islands = UnionFind::new(bodies.len());
+    let mut intersections = vec![];
+
+    for (i, aabb) in aabbs.iter().enumerate() {
+        tree.intersect_aabb(&aabb.loosened(CONTACT_MARGIN), &mut intersections);
+        for j in intersections.drain(..) {
+            islands.union(i, j);
+        }
+    }
+
+    let mut island_regions: HashMap<usize, SimulationBounds> = HashMap::new();
+
+    for (i, aabb) in aabbs.iter().enumerate() {
+        let region = SimulationBounds::from_aabb(aabb, SimulationBounds::DEFAULT_WIDTH);
+        let island_region = island_regions.entry(islands.find(i)).or_insert(region);
+        *island_region = (*island_region).max(region);
+    }
+
+    let mut result = HashMap::new();
+
+    for (i, body) in bodies.into_iter().enumerate() {
+        let region = island_regions[&islands.find(i)];
+        result.entry(region).or_insert_with(Vec::new).push(body);
+    }
+
+    result
+}
+
+struct UnionFind {
+    parents: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        Self {
+            parents: (0..len).collect(),
+        }
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parents[i] != i {
+            // Path halving.
+            self.parents[i] = self.parents[self.parents[i]];
+            i = self.parents[i];
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let root_a = self.find(a);
+        let root_b = self.find(b);
+        self.parents[root_a] = root_b;
+    }
+}
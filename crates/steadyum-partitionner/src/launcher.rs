@@ -0,0 +1,320 @@
+//! How a child partitionner launches its runners: as child processes, or as containers next to
+//! its own, selected by `RUNNER_LAUNCHER`.
+
+use crate::{AppState, Runner};
+use anyhow::Context;
+use async_channel::Sender;
+use axum::async_trait;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use steadyum_api_types::env::{RunnerLauncherKind, CONFIG};
+use tokio::process::{Child, Command};
+use tokio::sync::Mutex;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+/// How often the runners launched by this partitionner are checked for being alive.
+const HEALTH_CHECK_PERIOD: Duration = Duration::from_secs(5);
+
+/// Starts the runners of this node.
+#[async_trait]
+pub trait RunnerLauncher: Send + Sync {
+    /// Starts the runner `uuid`, with the command-line arguments `args`.
+    async fn launch(&self, uuid: Uuid, args: Vec<String>) -> anyhow::Result<Arc<dyn RunnerHandle>>;
+}
+
+/// A runner started by a [`RunnerLauncher`].
+///
+/// The handles are shared so they can be used without holding the `runners` lock: `docker`
+/// and `kubectl` can take a while to answer.
+#[async_trait]
+pub trait RunnerHandle: Send + Sync {
+    async fn is_alive(&self) -> anyhow::Result<bool>;
+    /// Stops the runner, without waiting for it to exit.
+    async fn kill(&self) -> anyhow::Result<()>;
+    /// Waits for the runner to exit, and releases what it was using.
+    async fn wait(&self) -> anyhow::Result<()>;
+}
+
+/// The launcher selected by `kind`.
+pub fn runner_launcher(kind: RunnerLauncherKind) -> Box<dyn RunnerLauncher> {
+    match kind {
+        RunnerLauncherKind::Process => Box::new(ProcessLauncher),
+        RunnerLauncherKind::Docker => Box::new(DockerLauncher),
+        RunnerLauncherKind::Kubernetes => Box::new(KubernetesLauncher),
+    }
+}
+
+/// Runs `program` with `args`, and returns its trimmed standard output if it succeeded.
+async fn run(program: &str, args: &[String]) -> anyhow::Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run {program}."))?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "{program} {} failed: {}",
+            args.first().map(String::as_str).unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The name of the container or pod of the runner `uuid`.
+fn container_name(uuid: Uuid) -> String {
+    format!("steadyum-runner-{uuid}")
+}
+
+/// Runs `RUNNER_EXE` as a child process.
+struct ProcessLauncher;
+
+#[async_trait]
+impl RunnerLauncher for ProcessLauncher {
+    async fn launch(
+        &self,
+        _uuid: Uuid,
+        args: Vec<String>,
+    ) -> anyhow::Result<Arc<dyn RunnerHandle>> {
+        let child = Command::new(&CONFIG.runner_exe)
+            .args(args)
+            .spawn()
+            .with_context(|| format!("Failed to run {}.", CONFIG.runner_exe))?;
+        Ok(Arc::new(Mutex::new(child)))
+    }
+}
+
+#[async_trait]
+impl RunnerHandle for Mutex<Child> {
+    async fn is_alive(&self) -> anyhow::Result<bool> {
+        Ok(self.lock().await.try_wait()?.is_none())
+    }
+
+    async fn kill(&self) -> anyhow::Result<()> {
+        Ok(self.lock().await.start_kill()?)
+    }
+
+    async fn wait(&self) -> anyhow::Result<()> {
+        self.lock().await.wait().await?;
+        Ok(())
+    }
+}
+
+/// Runs a container of `RUNNER_IMAGE` in the `RUNNER_NETWORK` network.
+///
+/// The partitionner must be able to run `docker`, e.g., by mounting the docker socket in its
+/// own container.
+struct DockerLauncher;
+
+#[async_trait]
+impl RunnerLauncher for DockerLauncher {
+    async fn launch(&self, uuid: Uuid, args: Vec<String>) -> anyhow::Result<Arc<dyn RunnerHandle>> {
+        let name = container_name(uuid);
+        let mut docker_args = vec![
+            "run".to_string(),
+            "--detach".to_string(),
+            format!("--name={name}"),
+            format!("--network={}", CONFIG.runner_network),
+        ];
+        docker_args.extend(
+            CONFIG
+                .runner_env()
+                .into_iter()
+                .map(|var| format!("--env={var}")),
+        );
+        docker_args.push(CONFIG.runner_image.clone());
+        docker_args.extend(args);
+
+        run("docker", &docker_args).await?;
+        Ok(Arc::new(DockerContainer { name }))
+    }
+}
+
+struct DockerContainer {
+    name: String,
+}
+
+#[async_trait]
+impl RunnerHandle for DockerContainer {
+    async fn is_alive(&self) -> anyhow::Result<bool> {
+        let args = [
+            "inspect".to_string(),
+            "--format={{.State.Running}}".to_string(),
+            self.name.clone(),
+        ];
+        Ok(run("docker", &args).await? == "true")
+    }
+
+    async fn kill(&self) -> anyhow::Result<()> {
+        run("docker", &["kill".to_string(), self.name.clone()]).await?;
+        Ok(())
+    }
+
+    async fn wait(&self) -> anyhow::Result<()> {
+        run("docker", &["wait".to_string(), self.name.clone()]).await?;
+        run("docker", &["rm".to_string(), self.name.clone()]).await?;
+        Ok(())
+    }
+}
+
+/// Runs a pod of `RUNNER_IMAGE` in the `RUNNER_NAMESPACE` namespace.
+///
+/// The partitionner must be able to run `kubectl`, e.g., with a service account allowed to
+/// manage the pods of that namespace.
+struct KubernetesLauncher;
+
+#[async_trait]
+impl RunnerLauncher for KubernetesLauncher {
+    async fn launch(&self, uuid: Uuid, args: Vec<String>) -> anyhow::Result<Arc<dyn RunnerHandle>> {
+        let name = container_name(uuid);
+        let mut kubectl_args = vec![
+            "run".to_string(),
+            name.clone(),
+            format!("--namespace={}", CONFIG.runner_namespace),
+            format!("--image={}", CONFIG.runner_image),
+            "--restart=Never".to_string(),
+            "--labels=app=steadyum-runner".to_string(),
+        ];
+        kubectl_args.extend(
+            CONFIG
+                .runner_env()
+                .into_iter()
+                .map(|var| format!("--env={var}")),
+        );
+        kubectl_args.push("--".to_string());
+        kubectl_args.extend(args);
+
+        run("kubectl", &kubectl_args).await?;
+        Ok(Arc::new(KubernetesPod { name }))
+    }
+}
+
+struct KubernetesPod {
+    name: String,
+}
+
+impl KubernetesPod {
+    fn args(&self, command: &[&str]) -> Vec<String> {
+        command
+            .iter()
+            .map(|arg| arg.to_string())
+            .chain([
+                self.name.clone(),
+                format!("--namespace={}", CONFIG.runner_namespace),
+            ])
+            .collect()
+    }
+}
+
+#[async_trait]
+impl RunnerHandle for KubernetesPod {
+    async fn is_alive(&self) -> anyhow::Result<bool> {
+        let phase = run(
+            "kubectl",
+            &self.args(&["get", "pod", "--output=jsonpath={.status.phase}"]),
+        )
+        .await?;
+        Ok(phase == "Pending" || phase == "Running")
+    }
+
+    async fn kill(&self) -> anyhow::Result<()> {
+        run("kubectl", &self.args(&["delete", "pod", "--wait=false"])).await?;
+        Ok(())
+    }
+
+    async fn wait(&self) -> anyhow::Result<()> {
+        // The pods aren’t removed when their runner exits by itself.
+        run(
+            "kubectl",
+            &self.args(&["delete", "pod", "--ignore-not-found"]),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Periodically checks that the runners launched by this partitionner are still alive.
+///
+/// The dead runners of the warm pool are forgotten, so the pool is refilled. The death of a
+/// runner hosting scenes is only reported: the partitionner can’t recover its regions.
+pub fn runner_health_loop(state: AppState) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(HEALTH_CHECK_PERIOD).await;
+
+            // NOTE: the runners are checked without holding the `runners` lock.
+            let processes: Vec<_> = {
+                let runners = state.data.runners.lock().await;
+                runners
+                    .per_node
+                    .values()
+                    .flatten()
+                    .chain(runners.host.iter())
+                    .chain(runners.uninitialized.values())
+                    .chain(runners.idle.iter())
+                    .filter_map(|runner| Some((runner.uuid, runner.process.clone()?)))
+                    .collect()
+            };
+
+            let mut dead = HashSet::new();
+            for (uuid, process) in processes {
+                match process.is_alive().await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        dead.insert(uuid);
+                    }
+                    Err(e) => warn!("Failed to check the health of the runner {uuid:?}: {e}"),
+                }
+            }
+
+            if dead.is_empty() {
+                continue;
+            }
+
+            let mut runners = state.data.runners.lock().await;
+            let runners = &mut *runners;
+            let to_remove = runners.to_remove.clone();
+            runners
+                .idle
+                .retain_mut(|runner| !reap_dead(runner, &dead, &to_remove));
+            runners
+                .uninitialized
+                .retain(|_, runner| !reap_dead(runner, &dead, &to_remove));
+
+            for runner in runners
+                .per_node
+                .values_mut()
+                .flat_map(|r| r.iter_mut())
+                .chain(runners.host.iter_mut())
+            {
+                if reap_dead(runner, &dead, &to_remove) {
+                    error!("The runner {:?} hosting scenes exited.", runner.uuid);
+                }
+            }
+        }
+    });
+}
+
+/// Is `runner` one of the `dead` runners? Its handle is then given to `to_remove` to tear it
+/// down, so it is reported only once.
+fn reap_dead(
+    runner: &mut Runner,
+    dead: &HashSet<Uuid>,
+    to_remove: &Sender<Arc<dyn RunnerHandle>>,
+) -> bool {
+    if !dead.contains(&runner.uuid) {
+        return false;
+    }
+
+    let Some(process) = runner.process.take() else {
+        return false;
+    };
+
+    warn!("The runner {:?} exited.", runner.uuid);
+    let _ = to_remove.try_send(process);
+    true
+}
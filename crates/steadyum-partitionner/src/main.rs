@@ -1,52 +1,144 @@
+mod capabilities;
+mod chaos;
 mod cli;
+mod clients;
+mod consistency;
+mod failover;
+mod hot_spots;
+mod islands;
+mod launcher;
+mod negotiated;
+mod rate_limit;
+mod region_lod;
+mod rollout;
+mod saves;
+mod spawn;
 mod storage;
+mod subdivision;
+mod tenants;
+mod transfers;
 
 #[macro_use]
 extern crate dotenv_codegen;
 
+use crate::chaos::chaos_loop;
 use crate::cli::CliArgs;
+use crate::clients::ClientsProgress;
+use crate::consistency::consistency_loop;
+use crate::failover::{
+    master_watchdog_loop, persistence_loop, readopt_runners, replicate, replication_loop,
+};
+use crate::hot_spots::hot_spot_loop;
+use crate::islands::group_islands_by_region;
+use crate::launcher::{runner_health_loop, runner_launcher, RunnerHandle, RunnerLauncher};
+use crate::negotiated::Negotiated;
+use crate::rate_limit::RateLimiter;
+use crate::region_lod::region_lod_loop;
+use crate::rollout::{get_exes, get_exes_delta, update_check, Release, Rollout};
+use crate::saves::{list_saves, load_save, save_scene};
+use crate::spawn::spawn_loop;
 use crate::storage::start_storage_thread;
+use crate::subdivision::subdivide_domain;
+use crate::tenants::{ClusterMember, SceneUsage, Tenant, Tenants};
+use crate::transfers::{drain_node, transfer_regions, Drain};
 use async_channel::{Receiver, Sender};
-use axum::extract::State;
-use axum::http::StatusCode;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{DefaultBodyLimit, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Response;
 use axum::routing::get;
 use axum::{routing::post, Json, Router};
 use clap::Parser;
-use log::{error, info};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::collections::{HashMap, HashSet, VecDeque};
-use std::process::{Child, Command};
-use std::sync::atomic::{AtomicBool, AtomicIsize, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Condvar};
 use std::time::Duration;
-use steadyum_api_types::env::CONFIG;
-use steadyum_api_types::messages::{BodyAssignment, RunnerMessage};
-use steadyum_api_types::objects::{ClientBodyObjectSet, RegionList, SceneList, WatchedObjects};
+use steadyum_api_types::chaos::FAULTS;
+use steadyum_api_types::env::{ControlPlane, CONFIG};
+use steadyum_api_types::fluids::FluidParticle;
+use steadyum_api_types::messages::{
+    BodyAssignment, BodyOwnershipUpdate, ClientInterest, ImpulseJointAssignment, RunnerMessage,
+    VehicleAssignment, CLIENT_INTEREST_REFRESH_PERIOD,
+};
+use steadyum_api_types::objects::{
+    ClientBodyObjectSet, ClientColdBodyObject, RegionActivity, RegionClientBodyObjectSet,
+    RegionList, SceneInfo, SceneList, WatchedObjects,
+};
 use steadyum_api_types::partitionner::{
-    AckRequest, AssignRunnerRequest, AssignRunnerResponse, ChildPartitionner, ClientInputRequest,
-    CreateSceneRequest, CreateSceneResponse, GetExesResponse, InsertObjectsRequest,
-    ListRegionsRequest, RegisterChildRequest, RemoveSceneRequest, RunnerInitializedRequest,
-    SceneUuid, StartStopRequest, StepRequest, ACK_ENDPOINT, ASSIGN_RUNNER_ENDPOINT,
-    CLIENT_INPUT_ENDPOINT, CREATE_SCENE_ENDPOINT, GET_EXES, HEARTBEAT, INSERT_OBJECTS_ENDPOINT,
-    LIST_REGIONS_ENDPOINT, LIST_SCENES_ENDPOINT, NUM_INTERNAL_STEPS, REGISTER_CHILD_ENDPOINT,
-    REMOVE_SCENE_ENDPOINT, RUNNER_INITIALIZED_ENDPOINT, SHUTDOWN, START_STOP_ENDPOINT,
-    STEP_ENDPOINT,
+    AckRequest, AssignRunnerRequest, AssignRunnerResponse, BeginInsertRequest, BeginInsertResponse,
+    BodyInputRequest, BodyOwner, BodyOwnerRequest, ChildPartitionner, ClientInputBatch,
+    ClientInputRequest, ConsistencyViolation, CreateSceneRequest, CreateSceneResponse,
+    ExportSceneRequest, FreezeVolumeRequest, FrozenVolume, FullSyncRequest, HotRegion,
+    InsertChunkRequest, InsertObjectsRequest, InsertParticlesRequest, InsertProgress,
+    InsertProgressRequest, InsertVehiclesRequest, ListRegionsRequest, NodeCapabilities,
+    ProfileSceneRequest, RegionBodyCounts, RegionProfile, RegisterChildRequest,
+    RemoveBodiesRequest, RemoveSceneRequest, RunnerInitializedRequest, SceneExport, SceneProfile,
+    SceneSettings, SceneStats, SceneStatsRequest, SceneStatus, SceneStatusRequest, SceneUuid,
+    SetForceGeneratorsRequest, SetScriptRequest, SetSimulationParamsRequest, SetTimeScaleRequest,
+    SpawnTemplatesRequest, StartStopRequest, StepNRequest, StepNResponse, StepRequest,
+    StreamClientObjectsRequest, TimeScale, TraceBodiesRequest, TrajectoriesRequest, ACK_ENDPOINT,
+    ASSIGN_RUNNER_ENDPOINT, BEGIN_INSERT_ENDPOINT, BODY_INPUT_ENDPOINT, BODY_OWNER_ENDPOINT,
+    CLIENT_INPUTS_ENDPOINT, CLIENT_INPUT_ENDPOINT, CREATE_SCENE_ENDPOINT, DRAIN_NODE_ENDPOINT,
+    EXPORT_SCENE_ENDPOINT, FREEZE_VOLUME_ENDPOINT, FULL_SYNC_ENDPOINT, GET_EXES,
+    GET_EXES_DELTA_ENDPOINT, HEARTBEAT, INSERT_CHUNK_ENDPOINT, INSERT_OBJECTS_ENDPOINT,
+    INSERT_PARTICLES_ENDPOINT, INSERT_PROGRESS_ENDPOINT, INSERT_VEHICLES_ENDPOINT,
+    LIST_REGIONS_ENDPOINT, LIST_SAVES_ENDPOINT, LIST_SCENES_ENDPOINT, LOAD_SAVE_ENDPOINT,
+    MAX_PENDING_INSERT_CHUNKS, PROFILE_SCENE_ENDPOINT, REGISTER_CHILD_ENDPOINT,
+    REMOVE_BODIES_ENDPOINT, REMOVE_SCENE_ENDPOINT, REPLICATE_ENDPOINT, RUNNER_INITIALIZED_ENDPOINT,
+    SAVE_SCENE_ENDPOINT, SCENE_STATS_ENDPOINT, SCENE_STATUS_ENDPOINT,
+    SET_FORCE_GENERATORS_ENDPOINT, SET_SCRIPT_ENDPOINT, SET_SIMULATION_PARAMS_ENDPOINT,
+    SET_TIME_SCALE_ENDPOINT, SHUTDOWN, SPAWN_TEMPLATES_ENDPOINT, START_STOP_ENDPOINT,
+    STEP_ENDPOINT, STEP_N_ENDPOINT, STREAM_CLIENT_OBJECTS_ENDPOINT, TRACE_BODIES_ENDPOINT,
+    TRAJECTORIES_ENDPOINT, TRANSFER_REGIONS_ENDPOINT, UPDATE_CHECK_ENDPOINT,
 };
+use steadyum_api_types::rapier::math::Real;
 use steadyum_api_types::rapier::parry::bounding_volume::{Aabb, BoundingVolume};
 use steadyum_api_types::rapier::parry::query::PointQuery;
 use steadyum_api_types::rapier::parry::shape::Cuboid;
 use steadyum_api_types::region_db::AsyncPartitionnerServer;
-use steadyum_api_types::serialization::serialize;
+use steadyum_api_types::serialization::{
+    deserialize_auto, deserialize_client_objects, deserialize_region_client_objects,
+    serialize_region_client_objects, EncodedClientBodyObjectSet, ObjectSetEncoding,
+};
 use steadyum_api_types::simulation::SimulationBounds;
-use steadyum_api_types::zenoh::{runner_zenoh_commands_key, ZenohContext};
-use tokio::sync::{Mutex, OnceCell, RwLock};
+use steadyum_api_types::store::{open_state_store, StateStore};
+use steadyum_api_types::telemetry::{StepCorrelation, Traced, STEP_HEADER};
+use steadyum_api_types::trajectory::{
+    BodyTrajectory, Trajectories, TrajectoryPoint, TrajectoryUpdate,
+};
+use steadyum_api_types::zenoh::{
+    body_ownership_key_expr, client_interest_key, client_objects_stream_key_expr, control_ack_key,
+    control_client_input_key, control_key_expr, control_step_key, scene_export_key_expr,
+    scene_profile_key_expr, trajectory_key_expr, ZenohContext,
+};
+use tokio::sync::{Mutex, Notify, OnceCell, RwLock};
+use tokio::task::JoinHandle;
 use tokio::time::Instant;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tracing::{error, info, warn, Instrument};
 use uuid::Uuid;
 use zenoh::config::WhatAmI;
 use zenoh::prelude::r#async::AsyncResolve;
-use zenoh::prelude::CongestionControl;
+use zenoh::prelude::ConsolidationMode;
+use zenoh::prelude::SplitBuffer;
 use zenoh::publication::Publisher;
 
 const MAX_PENDING_RUNNERS: u32 = 10;
+/// The maximum size of the WASM modules accepted by the script endpoint.
+const MAX_SCRIPT_SIZE: usize = 16 * 1024 * 1024;
+/// The maximum number of points recorded per traced body. The oldest ones are dropped first.
+const MAX_TRAJECTORY_LEN: usize = 100_000;
+/// The number of bodies generated at once from a template, see `spawn_templates`.
+const TEMPLATE_CHUNK_SIZE: usize = 4096;
+/// How long an insertion session waits for its next chunk before it is dropped, and how long
+/// the progress of a complete one can still be queried.
+const INSERT_SESSION_TIMEOUT: Duration = Duration::from_secs(300);
+/// How long the first scene of a node waits for a runner to initialize before failing.
+const RUNNER_INIT_TIMEOUT: Duration = Duration::from_secs(30);
 
 // static ZENOH: OnceCell<ZenohContext> = OnceCell::const_new();
 //
@@ -72,7 +164,7 @@ pub enum PartitionnerType {
 }
 
 pub struct Runner {
-    pub process: Option<Child>,
+    pub process: Option<Arc<dyn RunnerHandle>>,
     pub uuid: Uuid,
     pub is_new: bool,
 }
@@ -80,18 +172,25 @@ pub struct Runner {
 pub struct LiveRunners {
     pub next_port_id: u32,
     pub exited: HashSet<SceneUuid>,
-    pub assigned: HashMap<(SceneUuid, SimulationBounds), Uuid>,
     pub per_node: HashMap<SceneUuid, Vec<Runner>>,
-    pub to_remove: Sender<Child>,
+    /// The runner process hosting the regions of every scene on this node.
+    pub host: Option<Runner>,
+    /// Runner processes spawned for the warm pool that didn’t signal they are ready yet.
+    pub uninitialized: HashMap<Uuid, Runner>,
+    /// Warm pool of idle runner processes, ready to adopt a scene.
+    pub idle: Vec<Runner>,
+    pub to_remove: Sender<Arc<dyn RunnerHandle>>,
 }
 
 impl LiveRunners {
-    fn default(to_remove: Sender<Child>) -> Self {
+    fn default(to_remove: Sender<Arc<dyn RunnerHandle>>) -> Self {
         Self {
             exited: HashSet::default(),
             next_port_id: 10_000,
-            assigned: HashMap::default(),
             per_node: HashMap::default(),
+            host: None,
+            uninitialized: HashMap::default(),
+            idle: vec![],
             to_remove,
         }
     }
@@ -101,40 +200,174 @@ struct SceneGeometry {
     // TODO: this doesn’t support children added dynamically
     //       after the scene is created.
     children_bounds: Vec<Aabb>,
+    /// Are `children_bounds` still split by volume, waiting for the first bodies?
+    provisional: bool,
+    /// The child the new regions of each child go to instead, once all its regions moved
+    /// there.
+    redirects: HashMap<usize, usize>,
+}
+
+/// What the scene lists and saves need about a scene, besides its live state.
+#[derive(Clone, Default)]
+struct SceneMetadata {
+    /// In seconds since the Unix epoch.
+    created_at: u64,
+    seed: u64,
+    /// The settings the scene was created with, and its latest solver options.
+    settings: SceneSettings,
+}
+
+/// The current time, in seconds since the Unix epoch.
+fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// The acks received for a single step of a scene.
+#[derive(Default)]
+struct StepAcks {
+    step_id: u64,
+    expected: usize,
+    received: HashSet<Uuid>,
+}
+
+impl StepAcks {
+    fn remaining(&self) -> usize {
+        self.expected.saturating_sub(self.received.len())
+    }
 }
 
 struct SceneAcks {
-    pending_acks: AtomicIsize,
+    acks: Mutex<StepAcks>,
     step_id: AtomicU64,
+    /// The last step the simulation is allowed to run.
     step_limit: AtomicU64,
+    /// The step limit requested through the `STEP_N_ENDPOINT`, the clients can’t lower it.
+    debug_step_limit: AtomicU64,
+    clients: Mutex<ClientsProgress>,
+    /// Divides the pacing period of the steps, if the simulation is paced.
+    time_scale: RwLock<TimeScale>,
     date: RwLock<Instant>,
+    /// Holds back the steps while regions move between children.
+    drain: Mutex<Drain>,
 }
 
 impl Default for SceneAcks {
     fn default() -> Self {
         Self {
-            pending_acks: Default::default(),
+            acks: Mutex::new(StepAcks::default()),
             step_id: Default::default(),
             step_limit: Default::default(),
+            debug_step_limit: Default::default(),
+            clients: Mutex::new(ClientsProgress::default()),
+            time_scale: RwLock::new(TimeScale::default()),
             date: RwLock::new(Instant::now()),
+            drain: Mutex::new(Drain::default()),
+        }
+    }
+}
+
+impl SceneAcks {
+    /// Sets the step limit derived from the progress of the clients, and returns the previous
+    /// one.
+    fn set_clients_step_limit(&self, step_limit: u64) -> u64 {
+        let step_limit = step_limit.max(self.debug_step_limit.load(Ordering::SeqCst));
+        self.step_limit.swap(step_limit, Ordering::SeqCst)
+    }
+}
+
+/// A chunked insertion of bodies into a scene.
+///
+/// The task inserting the chunks stops once the session is dropped, so it must not hold it.
+struct InsertSession {
+    scene: SceneUuid,
+    total: usize,
+    received: AtomicUsize,
+    inserted: Arc<AtomicUsize>,
+    /// When the session received its last chunk, or started.
+    last_chunk: std::sync::Mutex<Instant>,
+    chunks_snd: Sender<Vec<BodyAssignment>>,
+}
+
+impl InsertSession {
+    fn touch(&self) {
+        *self.last_chunk.lock().unwrap() = Instant::now();
+    }
+
+    fn progress(&self) -> InsertProgress {
+        InsertProgress {
+            received: self.received.load(Ordering::SeqCst),
+            inserted: self.inserted.load(Ordering::SeqCst),
+            total: self.total,
         }
     }
 }
 
 struct SharedState {
+    /// Identifies this partitionner when it acks its parent.
+    uuid: Uuid,
     runners: Mutex<LiveRunners>,
-    zenoh: ZenohContext,
+    /// Serializes the adoptions of the host runner, see `host_runner`.
+    host_adoption: Mutex<()>,
+    /// Wakes up the scenes waiting for a runner whenever one acks its initialization.
+    runner_initialized: Notify,
+    zenoh: Arc<ZenohContext>,
     running: AtomicBool,
+    /// Set on a child once the master deregistered it, so it stops stepping.
+    decommissioned: AtomicBool,
     my_type: PartitionnerType,
     children: Mutex<Vec<AsyncPartitionnerServer>>,
+    /// The endpoints of `children`, in the same order.
+    children_endpoints: Mutex<Vec<ChildPartitionner>>,
+    /// The capabilities of `children`, in the same order.
+    children_capabilities: Mutex<Vec<NodeCapabilities>>,
     next_child: AtomicUsize,
     scenes_acks: RwLock<HashMap<SceneUuid, SceneAcks>>,
     scenes_geometries: RwLock<HashMap<SceneUuid, SceneGeometry>>,
+    client_objects_encodings: RwLock<HashMap<SceneUuid, ObjectSetEncoding>>,
+    /// Source of everything random in each scene, seeded by its creator.
+    scenes_rngs: Mutex<HashMap<SceneUuid, StdRng>>,
     static_bodies: RwLock<HashMap<SceneUuid, Vec<BodyAssignment>>>,
+    /// The scene and region currently simulating each dynamic body.
+    body_owners: RwLock<HashMap<Uuid, (SceneUuid, SimulationBounds)>>,
+    /// The points recorded for each traced body of each scene.
+    trajectories: RwLock<HashMap<SceneUuid, HashMap<Uuid, VecDeque<TrajectoryPoint>>>>,
     parent_partitionner: Option<AsyncPartitionnerServer>,
-    assign_runner_lock: Mutex<()>,
-    inputs_snd: Sender<ClientInputRequest>,
-    inputs_rcv: Receiver<ClientInputRequest>,
+    /// The runner simulating each region, read without locking `runners` and written by
+    /// `assign_runner` while holding it.
+    assigned_runners: DashMap<(SceneUuid, SimulationBounds), Uuid>,
+    /// The furthest step displayed by each client of each scene, not handled yet.
+    pending_inputs: Mutex<HashMap<(SceneUuid, Uuid), u64>>,
+    /// Wakes up the input handling loop when inputs are pending.
+    inputs_notify: Notify,
+    /// Limits the client input requests accepted over REST, per client.
+    inputs_rate_limiter: RateLimiter<Uuid>,
+    insert_sessions: RwLock<HashMap<Uuid, Arc<InsertSession>>>,
+    /// The final progress of the complete insertion sessions, with their scene and when they
+    /// completed, until they expire.
+    complete_inserts: RwLock<HashMap<Uuid, (SceneUuid, InsertProgress, Instant)>>,
+    /// The minimum duration of a step, if the simulation is paced to run in real time.
+    step_period: Option<Duration>,
+    /// Simulation time, in milliseconds, of the first step of the runners we spawn.
+    time_origin: u64,
+    /// Holds the latest scene metadata of the master, replicated to its children.
+    store: Arc<dyn StateStore>,
+    launcher: Box<dyn RunnerLauncher>,
+    tenants: Tenants,
+    /// The tenant and quota usage of each scene created by a tenant.
+    scene_usages: RwLock<HashMap<SceneUuid, SceneUsage>>,
+    /// The hot regions of each scene found by the last check of `hot_spot_loop`.
+    hot_regions: RwLock<HashMap<SceneUuid, Vec<HotRegion>>>,
+    /// The latest violations of each scene found by `consistency_loop`, oldest first.
+    violations: RwLock<HashMap<SceneUuid, VecDeque<ConsistencyViolation>>>,
+    scenes_metadata: RwLock<HashMap<SceneUuid, SceneMetadata>>,
+    /// Serializes the updates of the list of scene saves of the state store.
+    saves_lock: Mutex<()>,
+    /// The executables served to the updaters, and their rollout to the nodes.
+    release: Mutex<Release>,
+    rollout: Mutex<Rollout>,
 }
 
 #[derive(Clone)]
@@ -143,28 +376,89 @@ pub struct AppState {
 }
 
 impl AppState {
-    pub async fn with_type(my_type: PartitionnerType, to_remove: Sender<Child>) -> Self {
-        let (inputs_snd, inputs_rcv) = async_channel::unbounded();
+    /// Queues a client input, only keeping the furthest step of each client.
+    async fn push_client_input(&self, input: ClientInputRequest) {
+        let mut pending = self.data.pending_inputs.lock().await;
+        let step_id = pending
+            .entry((input.scene, input.client))
+            .or_insert(input.step_id);
+        *step_id = (*step_id).max(input.step_id);
+        self.data.inputs_notify.notify_one();
+    }
+
+    pub async fn with_type(
+        my_type: PartitionnerType,
+        step_period: Option<Duration>,
+        time_origin: u64,
+        to_remove: Sender<Arc<dyn RunnerHandle>>,
+    ) -> Self {
+        let zenoh = if my_type == PartitionnerType::Dev {
+            ZenohContext::new(WhatAmI::Peer, None, true).await.unwrap()
+        } else {
+            ZenohContext::new(WhatAmI::Router, None, true)
+                .await
+                .unwrap()
+        };
+        let store = open_state_store(&CONFIG).await.unwrap();
+        Self::new(
+            my_type,
+            Arc::new(zenoh),
+            store,
+            step_period,
+            time_origin,
+            to_remove,
+        )
+    }
+
+    /// A partitionner sharing the zenoh session and state store of another one, e.g., the
+    /// master run by a child that took over.
+    pub fn new(
+        my_type: PartitionnerType,
+        zenoh: Arc<ZenohContext>,
+        store: Arc<dyn StateStore>,
+        step_period: Option<Duration>,
+        time_origin: u64,
+        to_remove: Sender<Arc<dyn RunnerHandle>>,
+    ) -> Self {
         Self {
             data: Arc::new(SharedState {
+                uuid: Uuid::new_v4(),
                 my_type,
-                zenoh: if my_type == PartitionnerType::Dev {
-                    ZenohContext::new(WhatAmI::Peer, None, true).await.unwrap()
-                } else {
-                    ZenohContext::new(WhatAmI::Router, None, true)
-                        .await
-                        .unwrap()
-                },
+                zenoh,
                 runners: Mutex::new(LiveRunners::default(to_remove)),
+                host_adoption: Mutex::new(()),
+                runner_initialized: Notify::new(),
                 running: AtomicBool::new(false),
+                decommissioned: AtomicBool::new(false),
                 children: Mutex::new(vec![]),
+                children_endpoints: Mutex::new(vec![]),
+                children_capabilities: Mutex::new(vec![]),
                 next_child: AtomicUsize::new(0),
                 scenes_acks: RwLock::new(HashMap::new()),
                 scenes_geometries: RwLock::new(HashMap::new()),
+                client_objects_encodings: RwLock::new(HashMap::new()),
+                scenes_rngs: Mutex::new(HashMap::new()),
                 static_bodies: RwLock::new(HashMap::new()),
-                assign_runner_lock: Mutex::new(()),
-                inputs_snd,
-                inputs_rcv,
+                body_owners: RwLock::new(HashMap::new()),
+                trajectories: RwLock::new(HashMap::new()),
+                assigned_runners: DashMap::new(),
+                pending_inputs: Mutex::new(HashMap::new()),
+                inputs_notify: Notify::new(),
+                inputs_rate_limiter: RateLimiter::new(CONFIG.client_input_rate_limit),
+                insert_sessions: RwLock::new(HashMap::new()),
+                complete_inserts: RwLock::new(HashMap::new()),
+                step_period,
+                time_origin,
+                store,
+                launcher: runner_launcher(CONFIG.runner_launcher),
+                tenants: Tenants::load(&CONFIG.tenants_file).unwrap(),
+                scene_usages: RwLock::new(HashMap::new()),
+                hot_regions: RwLock::new(HashMap::new()),
+                violations: RwLock::new(HashMap::new()),
+                scenes_metadata: RwLock::new(HashMap::new()),
+                saves_lock: Mutex::new(()),
+                release: Mutex::new(Release::default()),
+                rollout: Mutex::new(Rollout::default()),
                 parent_partitionner: if my_type == PartitionnerType::Runner {
                     Some(AsyncPartitionnerServer::new().unwrap())
                 } else {
@@ -177,7 +471,7 @@ impl AppState {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    init_log();
+    steadyum_api_types::telemetry::init("steadyum-partitionner")?;
 
     let args = CliArgs::parse();
 
@@ -192,9 +486,18 @@ async fn main() -> anyhow::Result<()> {
     info!("Running partitionner as: {:?}", my_type);
 
     let (to_remove_snd, to_remove_rcv) = async_channel::unbounded();
-    let mut state = AppState::with_type(my_type, to_remove_snd).await;
+    let mut state =
+        AppState::with_type(my_type, args.step_period(), args.time_origin, to_remove_snd).await;
     let state_clone2 = state.clone();
 
+    let node_id = args
+        .node_id
+        .clone()
+        .unwrap_or_else(|| format!("{my_type:?}-{}", CONFIG.partitionner_port));
+    readopt_runners(&state, &node_id).await;
+    persistence_loop(state.clone(), node_id);
+    insert_session_expiry_loop(state.clone());
+
     if my_type == PartitionnerType::Runner {
         // Register this partitionner in the parent.
         let network_interfaces = local_ip_address::list_afinet_netifas()?;
@@ -214,7 +517,16 @@ async fn main() -> anyhow::Result<()> {
             addr: format!("http://{my_local_ip}"),
             port: CONFIG.partitionner_port,
         };
-        parent_server.register_child(me.clone()).await?;
+        let capabilities = capabilities::detect();
+        info!("My capabilities: {:?}", capabilities);
+        parent_server
+            .register_child(me.clone(), capabilities)
+            .await?;
+        master_watchdog_loop(state.clone(), me);
+    }
+
+    if my_type == PartitionnerType::Master {
+        replication_loop(state.clone());
     }
 
     if my_type != PartitionnerType::Master {
@@ -222,26 +534,67 @@ async fn main() -> anyhow::Result<()> {
             smol::block_on(runner_init_validation_loop(state_clone2));
         });
 
-        std::thread::spawn(move || runner_stopped_child_wait_loop(to_remove_rcv));
+        warm_pool_loop(state.clone(), args.warm_runners.min(MAX_PENDING_RUNNERS));
+
+        tokio::spawn(runner_stopped_child_wait_loop(to_remove_rcv));
+        runner_health_loop(state.clone());
+
+        if FAULTS.is_enabled() {
+            warn!("Chaos mode enabled: runners will be killed and messages lost.");
+            chaos_loop(state.clone());
+        }
     }
 
     if my_type != PartitionnerType::Runner {
         input_handling_loop(state.clone());
+        body_ownership_loop(state.clone());
+        trajectory_loop(state.clone());
+        spawn_loop(state.clone());
+        hot_spot_loop(state.clone());
+        region_lod_loop(state.clone());
+
+        if CONFIG.consistency_checks {
+            warn!("Consistency checks enabled: all the regions stream their client objects.");
+            consistency_loop(state.clone());
+        }
+    }
+
+    if CONFIG.control_plane == ControlPlane::Zenoh {
+        control_plane_loop(state.clone());
     }
 
     // if my_type != PartitionnerType::Runner {
     //     start_storage_thread(state.clone());
     // }
 
-    let app = Router::new()
+    let app = router(state);
+    axum::Server::bind(
+        &format!("0.0.0.0:{}", CONFIG.partitionner_port)
+            .parse()
+            .unwrap(),
+    )
+    .serve(app.into_make_service())
+    .await?;
+
+    Ok(())
+}
+
+fn router(state: AppState) -> Router {
+    Router::new()
         .route(SHUTDOWN, get(shutdown))
         .route(HEARTBEAT, get(heartbeat))
         .route(GET_EXES, get(get_exes))
+        .route(GET_EXES_DELTA_ENDPOINT, post(get_exes_delta))
         .route(ASSIGN_RUNNER_ENDPOINT, post(assign_runner))
         .route(RUNNER_INITIALIZED_ENDPOINT, post(runner_initialized))
         .route(INSERT_OBJECTS_ENDPOINT, post(insert_objects))
+        .route(SPAWN_TEMPLATES_ENDPOINT, post(spawn_templates))
+        .route(BEGIN_INSERT_ENDPOINT, post(begin_insert))
+        .route(INSERT_CHUNK_ENDPOINT, post(insert_chunk))
+        .route(INSERT_PROGRESS_ENDPOINT, post(insert_progress))
         .route(LIST_REGIONS_ENDPOINT, get(list_regions))
         .route(LIST_SCENES_ENDPOINT, get(list_scenes))
+        .route(EXPORT_SCENE_ENDPOINT, post(export_scene))
         .route(START_STOP_ENDPOINT, post(start_stop))
         .route(REGISTER_CHILD_ENDPOINT, post(register_child))
         .route(CREATE_SCENE_ENDPOINT, post(create_scene))
@@ -249,27 +602,303 @@ async fn main() -> anyhow::Result<()> {
         .route(ACK_ENDPOINT, post(ack))
         .route(STEP_ENDPOINT, post(step))
         .route(CLIENT_INPUT_ENDPOINT, post(handle_client_inputs))
-        .with_state(state);
-    axum::Server::bind(
-        &format!("0.0.0.0:{}", CONFIG.partitionner_port)
-            .parse()
-            .unwrap(),
-    )
-    .serve(app.into_make_service())
-    .await?;
+        .route(CLIENT_INPUTS_ENDPOINT, post(handle_client_input_batch))
+        .route(STREAM_CLIENT_OBJECTS_ENDPOINT, get(stream_client_objects))
+        .route(REPLICATE_ENDPOINT, post(replicate))
+        .route(BODY_OWNER_ENDPOINT, post(body_owner))
+        .route(
+            SET_SCRIPT_ENDPOINT,
+            post(set_script).layer(DefaultBodyLimit::max(MAX_SCRIPT_SIZE)),
+        )
+        .route(BODY_INPUT_ENDPOINT, post(body_input))
+        .route(REMOVE_BODIES_ENDPOINT, post(remove_bodies))
+        .route(PROFILE_SCENE_ENDPOINT, post(profile_scene))
+        .route(SCENE_STATS_ENDPOINT, post(scene_stats))
+        .route(SET_TIME_SCALE_ENDPOINT, post(set_time_scale))
+        .route(SET_SIMULATION_PARAMS_ENDPOINT, post(set_simulation_params))
+        .route(FREEZE_VOLUME_ENDPOINT, post(freeze_volume))
+        .route(SET_FORCE_GENERATORS_ENDPOINT, post(set_force_generators))
+        .route(INSERT_PARTICLES_ENDPOINT, post(insert_particles))
+        .route(INSERT_VEHICLES_ENDPOINT, post(insert_vehicles))
+        .route(STEP_N_ENDPOINT, post(step_n))
+        .route(TRACE_BODIES_ENDPOINT, post(trace_bodies))
+        .route(TRAJECTORIES_ENDPOINT, post(trajectories))
+        .route(SCENE_STATUS_ENDPOINT, post(scene_status))
+        .route(FULL_SYNC_ENDPOINT, post(full_sync))
+        .route(SAVE_SCENE_ENDPOINT, post(save_scene))
+        .route(LOAD_SAVE_ENDPOINT, post(load_save))
+        .route(LIST_SAVES_ENDPOINT, get(list_saves))
+        .route(UPDATE_CHECK_ENDPOINT, post(update_check))
+        .route(TRANSFER_REGIONS_ENDPOINT, post(transfer_regions))
+        .route(DRAIN_NODE_ENDPOINT, post(drain_node))
+        .layer(cors_layer())
+        .with_state(state)
+}
 
-    Ok(())
+/// Lets the web viewers of `CORS_ORIGINS` call the partitionner from the browser.
+fn cors_layer() -> CorsLayer {
+    let origins = CONFIG.cors_origins();
+    let allowed = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(origins.iter().filter_map(|origin| origin.parse().ok()))
+    };
+    CorsLayer::new()
+        .allow_origin(allowed)
+        .allow_methods(Any)
+        .allow_headers(Any)
 }
 
 async fn handle_client_inputs(
     State(state): State<AppState>,
-    Json(payload): Json<ClientInputRequest>,
-) {
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<ClientInputRequest>,
+) -> StatusCode {
+    if let Err(status) = tenant.check_scene(&state, payload.scene).await {
+        return status;
+    }
+
     // info!("Got clinet input.");
-    state.data.inputs_snd.send(payload).await.unwrap();
+    if !state.data.inputs_rate_limiter.try_acquire(payload.client) {
+        return StatusCode::TOO_MANY_REQUESTS;
+    }
+
+    state.push_client_input(payload).await;
+    StatusCode::OK
+}
+
+async fn handle_client_input_batch(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<ClientInputBatch>,
+) -> StatusCode {
+    for input in &payload.inputs {
+        if let Err(status) = tenant.check_scene(&state, input.scene).await {
+            return status;
+        }
+    }
+
+    // A batch counts as one request of each of its clients, usually a single viewer.
+    let clients: HashSet<_> = payload.inputs.iter().map(|input| input.client).collect();
+    if !clients
+        .into_iter()
+        .all(|client| state.data.inputs_rate_limiter.try_acquire(client))
+    {
+        return StatusCode::TOO_MANY_REQUESTS;
+    }
+
+    for input in payload.inputs {
+        state.push_client_input(input).await;
+    }
+    StatusCode::OK
+}
+
+async fn stream_client_objects(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Response {
+    ws.on_upgrade(move |socket| async move {
+        if let Err(e) = client_objects_stream_loop(socket, state, tenant).await {
+            error!("Client objects stream closed: {e}");
+        }
+    })
+}
+
+/// Forwards the client objects published by the runners to a viewer, filtering out the
+/// regions outside of its view.
+async fn client_objects_stream_loop(
+    mut socket: WebSocket,
+    state: AppState,
+    tenant: Tenant,
+) -> anyhow::Result<()> {
+    // The first message must tell us what to stream.
+    let mut request = loop {
+        match socket.recv().await {
+            Some(Ok(Message::Text(text))) => {
+                break serde_json::from_str::<StreamClientObjectsRequest>(&text)?
+            }
+            Some(Ok(Message::Close(_))) | None => return Ok(()),
+            Some(Ok(_)) => {}
+            Some(Err(e)) => return Err(e.into()),
+        }
+    };
+
+    // Publish the interest of the viewer on its behalf, so the runners keep building its objects.
+    let client = Uuid::new_v4();
+    let mut interest_refresh = tokio::time::interval(CLIENT_INTEREST_REFRESH_PERIOD);
+
+    loop {
+        if tenant.check_scene(&state, request.scene).await.is_err() {
+            anyhow::bail!("Unknown scene {:?}.", request.scene);
+        }
+
+        // The shape hash of every body this client already received the cold data of.
+        let mut sent_shape_hashes = HashMap::new();
+        let encoding = state
+            .data
+            .client_objects_encodings
+            .read()
+            .await
+            .get(&request.scene)
+            .copied()
+            .unwrap_or_default();
+        let subscriber = state
+            .data
+            .zenoh
+            .session
+            .declare_subscriber(client_objects_stream_key_expr(request.scene))
+            .res()
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+
+        // Stream until the client switches to another scene.
+        let new_scene_request = loop {
+            tokio::select! {
+                message = socket.recv() => {
+                    match message {
+                        Some(Ok(Message::Text(text))) => {
+                            let new_request: StreamClientObjectsRequest = serde_json::from_str(&text)?;
+                            if new_request.scene != request.scene {
+                                break new_request;
+                            }
+                            request = new_request;
+                        }
+                        Some(Ok(Message::Close(_))) | None => return Ok(()),
+                        Some(Ok(_)) => {}
+                        Some(Err(e)) => return Err(e.into()),
+                    }
+                }
+                _ = interest_refresh.tick() => {
+                    let interest = ClientInterest {
+                        view: request.view,
+                        lod: request.lod,
+                    };
+                    state
+                        .data
+                        .zenoh
+                        .put(&client_interest_key(request.scene, client), &interest)
+                        .await?;
+                }
+                sample = subscriber.recv_async() => {
+                    let sample = sample.map_err(|e| anyhow::anyhow!("{e}"))?;
+                    let Some(region) = sample
+                        .key_expr
+                        .as_str()
+                        .rsplit('/')
+                        .next()
+                        .and_then(SimulationBounds::from_str)
+                    else {
+                        continue;
+                    };
+
+                    if region.intersects_aabb(&request.view) {
+                        let streamed =
+                            deserialize_region_client_objects(&sample.value.payload.contiguous())?;
+                        let mut set = streamed.set.decode(&region);
+                        select_cold_objects_to_stream(
+                            &state.data.zenoh,
+                            request.scene,
+                            region,
+                            &mut set,
+                            &mut sent_shape_hashes,
+                        )
+                        .await?;
+                        let streamed = RegionClientBodyObjectSet {
+                            region,
+                            set: EncodedClientBodyObjectSet::encode(set, &region, encoding),
+                            time_scale: streamed.time_scale,
+                            stats: streamed.stats,
+                        };
+                        let payload = serialize_region_client_objects(&streamed)?;
+                        socket.send(Message::Binary(payload)).await?;
+                    }
+                }
+            }
+        };
+
+        request = new_scene_request;
+    }
+}
+
+/// Only keeps the cold objects the client didn’t receive yet.
+///
+/// The runners only stream the cold objects that changed during the last step, so the ones
+/// streamed before the client subscribed are fetched from the runner storage.
+async fn select_cold_objects_to_stream(
+    zenoh: &ZenohContext,
+    scene: SceneUuid,
+    region: SimulationBounds,
+    set: &mut ClientBodyObjectSet,
+    sent_shape_hashes: &mut HashMap<Uuid, u64>,
+) -> anyhow::Result<()> {
+    let mut available: HashMap<Uuid, ClientColdBodyObject> = set
+        .cold_objects
+        .drain(..)
+        .map(|cold| (cold.uuid, cold))
+        .collect();
+
+    let needs_fetch = set.objects.iter().any(|obj| {
+        sent_shape_hashes.get(&obj.uuid) != Some(&obj.shape_hash)
+            && available.get(&obj.uuid).map(|cold| cold.shape_hash) != Some(obj.shape_hash)
+    });
+
+    if needs_fetch {
+        let key = region.runner_client_objects_key(scene, 0, None);
+        let replies = zenoh
+            .session
+            .get(&key)
+            .res()
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+
+        while let Ok(reply) = replies.recv_async().await {
+            let Ok(sample) = reply.sample else { continue };
+            let full = deserialize_client_objects(&sample.value.payload.contiguous(), &region)?;
+            available.extend(full.cold_objects.into_iter().map(|cold| (cold.uuid, cold)));
+        }
+    }
+
+    for obj in &set.objects {
+        if sent_shape_hashes.get(&obj.uuid) == Some(&obj.shape_hash) {
+            continue;
+        }
+
+        let Some(cold) = available.remove(&obj.uuid) else {
+            continue;
+        };
+
+        if cold.shape_hash == obj.shape_hash {
+            sent_shape_hashes.insert(obj.uuid, obj.shape_hash);
+            set.cold_objects.push(cold);
+        }
+    }
+
+    Ok(())
+}
+
+async fn step(
+    State(state): State<AppState>,
+    _member: ClusterMember,
+    headers: HeaderMap,
+    Negotiated(payload): Negotiated<StepRequest>,
+) {
+    let correlation = step_correlation(&headers, payload.scene, payload.step_id);
+    traced_step(state, correlation, payload).await
+}
+
+/// Steps the scene of `payload` in the span of the step `correlation`.
+async fn traced_step(state: AppState, correlation: StepCorrelation, payload: StepRequest) {
+    handle_step(state, payload)
+        .instrument(correlation.span("partitionner.step"))
+        .await
 }
 
-async fn step(State(state): State<AppState>, Json(payload): Json<StepRequest>) {
+async fn handle_step(state: AppState, payload: StepRequest) {
+    if state.data.decommissioned.load(Ordering::SeqCst) {
+        return; // The master moved our regions to other children.
+    }
+
     if state.data.my_type != PartitionnerType::Runner && !state.data.running.load(Ordering::SeqCst)
     {
         info!("Could not step {:?}: simulation paused.", payload.scene);
@@ -307,16 +936,44 @@ async fn step(State(state): State<AppState>, Json(payload): Json<StepRequest>) {
                     children.iter().cloned().collect()
                 };
 
-                scene_acks
-                    .pending_acks
-                    .store(children_to_notify.len() as isize, Ordering::SeqCst);
-                scene_acks.step_id.store(payload.step_id, Ordering::SeqCst);
+                // Held until the acks expect this step, so a drain waits for its acks.
+                let mut drain = scene_acks.drain.lock().await;
+                if drain.active {
+                    info!(
+                        "Holding back step {} of {:?}: regions are moving.",
+                        payload.step_id, payload.scene
+                    );
+                    drain.withheld_step = Some(payload.step_id);
+                    return;
+                }
 
-                for child_partitionner in children_to_notify {
-                    child_partitionner
-                        .step(payload.scene, payload.step_id)
-                        .await
-                        .unwrap();
+                *scene_acks.acks.lock().await = StepAcks {
+                    step_id: payload.step_id,
+                    expected: children_to_notify.len(),
+                    received: HashSet::new(),
+                };
+                scene_acks.step_id.store(payload.step_id, Ordering::SeqCst);
+                drop(drain);
+
+                match CONFIG.control_plane {
+                    ControlPlane::Rest => {
+                        for child_partitionner in children_to_notify {
+                            if let Err(e) = child_partitionner
+                                .step(payload.scene, payload.step_id)
+                                .await
+                            {
+                                error!("Failed to step child partitionner: {e}");
+                            }
+                        }
+                    }
+                    ControlPlane::Zenoh => {
+                        // All the children get the same message, publish it once.
+                        let correlation = StepCorrelation::current(payload.scene, payload.step_id);
+                        let message = Traced::new(Some(correlation), &payload);
+                        if let Err(e) = state.data.zenoh.put(&control_step_key(), &message).await {
+                            error!("Failed to step child partitionners: {e}");
+                        }
+                    }
                 }
             }
             PartitionnerType::Runner | PartitionnerType::Dev => {
@@ -330,9 +987,11 @@ async fn step(State(state): State<AppState>, Json(payload): Json<StepRequest>) {
                         .collect()
                 };
 
-                scene_acks
-                    .pending_acks
-                    .store(runners_to_notify.len() as isize, Ordering::SeqCst);
+                *scene_acks.acks.lock().await = StepAcks {
+                    step_id: payload.step_id,
+                    expected: runners_to_notify.len(),
+                    received: HashSet::new(),
+                };
                 scene_acks.step_id.store(payload.step_id, Ordering::SeqCst);
 
                 info!("Stepping {} runners.", runners_to_notify.len());
@@ -340,9 +999,9 @@ async fn step(State(state): State<AppState>, Json(payload): Json<StepRequest>) {
                 if runners_to_notify.is_empty() {
                     // This child partitionner doesn’t have any active runner
                     // for this scene. Ack immediately.
-                    if let Some(parent_partitionner) = &state.data.parent_partitionner {
+                    if state.data.parent_partitionner.is_some() {
                         info!("No runner to wait on, acking the parent partitionner.");
-                        parent_partitionner.ack(payload.scene).await.unwrap();
+                        ack_parent(&state, payload.scene, payload.step_id).await;
                     }
                 }
 
@@ -351,6 +1010,7 @@ async fn step(State(state): State<AppState>, Json(payload): Json<StepRequest>) {
                         &state.data.zenoh,
                         uuid,
                         RunnerMessage::Step {
+                            scene: payload.scene,
                             step_id: payload.step_id,
                         },
                     )
@@ -362,68 +1022,174 @@ async fn step(State(state): State<AppState>, Json(payload): Json<StepRequest>) {
     }
 }
 
-async fn ack(State(state): State<AppState>, Json(payload): Json<AckRequest>) {
+async fn ack(
+    State(state): State<AppState>,
+    _member: ClusterMember,
+    headers: HeaderMap,
+    Negotiated(payload): Negotiated<AckRequest>,
+) {
+    let correlation = step_correlation(&headers, payload.scene, payload.step_id);
+    traced_ack(state, correlation, payload).await
+}
+
+/// Handles the ack `payload` in the span of the step `correlation`.
+async fn traced_ack(state: AppState, correlation: StepCorrelation, payload: AckRequest) {
+    handle_ack(state, payload)
+        .instrument(correlation.span("partitionner.ack"))
+        .await
+}
+
+/// The correlation sent in the [`STEP_HEADER`] of a request, or a new one if there isn’t any.
+fn step_correlation(headers: &HeaderMap, scene: SceneUuid, step_id: u64) -> StepCorrelation {
+    headers
+        .get(STEP_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(StepCorrelation::parse_header)
+        .unwrap_or_else(|| StepCorrelation::root(scene, step_id))
+}
+
+async fn handle_ack(state: AppState, payload: AckRequest) {
+    if let Some(delay) = FAULTS.ack_delay() {
+        tokio::time::sleep(delay).await;
+    }
+
     let scenes_acks = state.data.scenes_acks.read().await;
-    if let Some(scene_acks) = scenes_acks.get(&payload.scene) {
-        let val_before = scene_acks.pending_acks.fetch_add(-1, Ordering::SeqCst);
-
-        info!("Received ack, remaining: {}", val_before - 1);
-
-        // NOTE: if the value was 1, it’s now 0.
-        if val_before <= 1 {
-            // All the children acked for this scene.
-            // Notify the parent if we have one.
-            match state.data.my_type {
-                PartitionnerType::Master => {
-                    let new_step_id = scene_acks.step_id.fetch_add(1, Ordering::SeqCst) + 1;
-                    if new_step_id <= scene_acks.step_limit.load(Ordering::SeqCst) {
-                        step(
-                            State(state.clone()),
-                            Json(StepRequest {
-                                scene: payload.scene,
-                                step_id: new_step_id,
-                            }),
-                        )
-                        .await
-                    } else {
-                        state.data.running.store(false, Ordering::SeqCst);
-                    }
-                }
-                PartitionnerType::Runner => {
-                    // We are a leaf instance, send an ack to the parent partitionner.
-                    let parent_server = AsyncPartitionnerServer::new().unwrap();
-                    parent_server.ack(payload.scene).await.unwrap();
-                }
-                PartitionnerType::Dev => {
-                    let new_step_id = scene_acks.step_id.fetch_add(1, Ordering::SeqCst) + 1;
-                    if new_step_id <= scene_acks.step_limit.load(Ordering::SeqCst) {
-                        step(
-                            State(state.clone()),
-                            Json(StepRequest {
-                                scene: payload.scene,
-                                step_id: new_step_id,
-                            }),
-                        )
-                        .await
-                    } else {
-                        println!("################# Stopping runners ################");
-                        state.data.running.store(false, Ordering::SeqCst);
-                    }
-                }
+    let Some(scene_acks) = scenes_acks.get(&payload.scene) else {
+        return;
+    };
+
+    let all_acked = {
+        let mut acks = scene_acks.acks.lock().await;
+
+        if acks.step_id != payload.step_id {
+            warn!(
+                "Ignoring ack of step {} from {:?}, expected step {}.",
+                payload.step_id, payload.origin, acks.step_id
+            );
+            return;
+        }
+
+        if !acks.received.insert(payload.origin) {
+            warn!(
+                "Ignoring duplicate ack of step {} from {:?}.",
+                payload.step_id, payload.origin
+            );
+            return;
+        }
+
+        info!("Received ack, remaining: {}", acks.remaining());
+        acks.remaining() == 0
+    };
+
+    if !all_acked {
+        return;
+    }
+
+    // All the children acked for this scene.
+    if state.data.my_type == PartitionnerType::Runner {
+        drop(scenes_acks);
+        // We are a leaf instance, send an ack to the parent partitionner.
+        ack_parent(&state, payload.scene, payload.step_id).await;
+        return;
+    }
+
+    let new_step_id = scene_acks.step_id.fetch_add(1, Ordering::SeqCst) + 1;
+    if new_step_id > scene_acks.step_limit.load(Ordering::SeqCst) {
+        if state.data.my_type == PartitionnerType::Dev {
+            println!("################# Stopping runners ################");
+        }
+        state.data.running.store(false, Ordering::SeqCst);
+        return;
+    }
+
+    let next_step_date = next_step_date(scene_acks, state.data.step_period).await;
+    drop(scenes_acks);
+
+    // NOTE: the next step waits for its date from its own task, so the other scenes can still
+    //       be created or removed meanwhile.
+    tokio::spawn(async move {
+        if let Some(date) = next_step_date {
+            tokio::time::sleep_until(date).await;
+        }
+
+        traced_step(
+            state,
+            StepCorrelation::root(payload.scene, new_step_id),
+            StepRequest {
+                scene: payload.scene,
+                step_id: new_step_id,
+            },
+        )
+        .await
+    });
+}
+
+/// Tells the parent partitionner that this partitionner is done with the step `step_id`.
+async fn ack_parent(state: &AppState, scene: SceneUuid, step_id: u64) {
+    let result = match CONFIG.control_plane {
+        ControlPlane::Rest => match &state.data.parent_partitionner {
+            Some(parent_partitionner) => {
+                parent_partitionner
+                    .ack(scene, step_id, state.data.uuid)
+                    .await
             }
+            None => Ok(()),
+        },
+        ControlPlane::Zenoh => {
+            let ack = AckRequest {
+                scene,
+                step_id,
+                origin: state.data.uuid,
+            };
+            let correlation = StepCorrelation::current(scene, step_id);
+            let message = Traced::new(Some(correlation), ack);
+            state.data.zenoh.put(&control_ack_key(), &message).await
         }
+    };
+
+    if let Err(e) = result {
+        error!("Failed to ack the parent partitionner: {e}");
     }
 }
 
-async fn shutdown(State(state): State<AppState>) {
+/// When the next step of the scene is due, if the simulation is paced.
+async fn next_step_date(scene_acks: &SceneAcks, step_period: Option<Duration>) -> Option<Instant> {
+    let period = step_period?;
+    let time_scale = *scene_acks.time_scale.read().await;
+    Some(*scene_acks.date.read().await + period.div_f32(time_scale.0))
+}
+
+/// Stops the runners of this partitionner, then exits. Only the master partitionner, or the
+/// clients of a cluster without tenants, can shut it down.
+async fn shutdown(State(state): State<AppState>, tenant: Tenant) -> StatusCode {
+    if !tenant.is_unrestricted() {
+        return StatusCode::FORBIDDEN;
+    }
+
     let mut runners = state.data.runners.lock().await;
     let runners = &mut *runners;
-    for runner in runners.per_node.values_mut().flat_map(|r| r.iter_mut()) {
-        if let Some(proc) = &mut runner.process {
-            if let Err(e) = proc.kill() {
+
+    if let Some(host) = &runners.host {
+        if let Err(e) =
+            put_runner_message(&state.data.zenoh, host.uuid, RunnerMessage::Shutdown).await
+        {
+            error!("Failed to send shutdown message to runner: {e}");
+        }
+    }
+
+    for runner in runners
+        .per_node
+        .values_mut()
+        .flat_map(|r| r.iter_mut())
+        .chain(runners.host.iter_mut())
+        .chain(runners.uninitialized.values_mut())
+        .chain(runners.idle.iter_mut())
+    {
+        if let Some(proc) = &runner.process {
+            if let Err(e) = proc.kill().await {
                 error!("Failed to stop child runner.");
             }
-            if let Err(e) = proc.wait() {
+            if let Err(e) = proc.wait().await {
                 error!("Failed to wait for child runner termination.");
             }
         }
@@ -431,62 +1197,57 @@ async fn shutdown(State(state): State<AppState>) {
     std::process::abort();
 }
 
-async fn get_exes() -> bytes::Bytes {
-    info!("Getting exes.");
-    let runner = tokio::fs::read(&CONFIG.runner_exe)
-        .await
-        .expect("Failed to read runner exe");
-    let partitionner = tokio::fs::read("steadyum-partitionner")
-        .await
-        .expect("Failed to read partitionner exe");
-    let resp = GetExesResponse {
-        partitionner,
-        runner,
-    };
-
-    let result = serialize(&resp).unwrap();
-    result.into()
-}
-
 async fn heartbeat() {}
 
 async fn create_scene(
     State(state): State<AppState>,
-    Json(payload): Json<CreateSceneRequest>,
-) -> Json<CreateSceneResponse> {
-    fn split_aabb(aabb: Aabb) -> [Aabb; 2] {
-        let extents = aabb.extents();
-        let center = aabb.center();
-        let split_axis = extents.xz().imax() * 2;
-        let mut left = aabb;
-        let mut right = aabb;
-        left.maxs[split_axis] = center[split_axis];
-        right.mins[split_axis] = center[split_axis];
-        [left, right]
-    }
-
-    fn subdivide_domain(domain: Aabb, num_subdivs: usize) -> Vec<Aabb> {
-        let mut subdivisions = VecDeque::new();
-        subdivisions.push_back(domain);
-        for _ in 1..num_subdivs {
-            let to_sub = subdivisions.pop_front().unwrap();
-            let subs = split_aabb(to_sub);
-            subdivisions.push_back(subs[0]);
-            subdivisions.push_back(subs[1]);
-        }
-
-        subdivisions.into()
-    }
-
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<CreateSceneRequest>,
+) -> Result<Json<CreateSceneResponse>, StatusCode> {
     info!(
         "Creating scene {:?} with bounds {:?}.",
         payload.scene, payload.bounds
     );
 
     let num_child_partitioners = state.data.children.lock().await.len();
-    let children_bounds = subdivide_domain(payload.bounds, num_child_partitioners);
+    let num_runners = match state.data.my_type {
+        PartitionnerType::Master => num_child_partitioners,
+        _ => 1,
+    };
+    tenant.add_scene(
+        &mut *state.data.scene_usages.write().await,
+        payload.scene,
+        num_runners,
+    )?;
+
+    let scene = payload.scene;
+    match setup_scene(&state, payload).await {
+        Ok(response) => {
+            info!("Done creating scene {:?}", scene);
+            Ok(Json(response))
+        }
+        Err(status) => {
+            // The quotas only count the scenes created successfully.
+            if !tenant.is_unrestricted() {
+                state.data.scene_usages.write().await.remove(&scene);
+            }
+            Err(status)
+        }
+    }
+}
+
+/// Sets up the new scene on this node, and on the children if this is the master partitionner.
+async fn setup_scene(
+    state: &AppState,
+    payload: CreateSceneRequest,
+) -> Result<CreateSceneResponse, StatusCode> {
+    // The domain is split again once the first bodies are inserted, see
+    // `balance_children_bounds`.
+    let children_bounds = subdivide_domain(payload.bounds, &children_weights(state).await, &[]);
     let scene_geom = SceneGeometry {
         children_bounds: children_bounds.clone(),
+        provisional: true,
+        redirects: HashMap::new(),
     };
 
     state
@@ -495,18 +1256,42 @@ async fn create_scene(
         .write()
         .await
         .insert(payload.scene, scene_geom);
+    state
+        .data
+        .client_objects_encodings
+        .write()
+        .await
+        .insert(payload.scene, payload.client_objects_encoding);
+    state
+        .data
+        .scenes_rngs
+        .lock()
+        .await
+        .insert(payload.scene, StdRng::seed_from_u64(payload.seed));
     state
         .data
         .static_bodies
         .write()
         .await
         .insert(payload.scene, vec![]);
+    let metadata = SceneMetadata {
+        created_at: unix_time(),
+        seed: payload.seed,
+        settings: payload.settings.clone(),
+    };
     state
         .data
-        .scenes_acks
+        .scenes_metadata
         .write()
         .await
-        .insert(payload.scene, SceneAcks::default());
+        .insert(payload.scene, metadata);
+    state.data.scenes_acks.write().await.insert(
+        payload.scene,
+        SceneAcks {
+            time_scale: RwLock::new(payload.settings.time_scale),
+            ..SceneAcks::default()
+        },
+    );
 
     let response = match state.data.my_type {
         PartitionnerType::Master => {
@@ -520,9 +1305,18 @@ async fn create_scene(
                 children_to_notify.iter().zip(children_bounds.iter())
             {
                 let response = child_partitionner
-                    .create_scene(payload.scene, *child_bounds)
+                    .create_scene(
+                        payload.scene,
+                        *child_bounds,
+                        payload.client_objects_encoding,
+                        payload.seed,
+                        payload.settings.clone(),
+                    )
                     .await
-                    .unwrap();
+                    .map_err(|e| {
+                        error!("Failed to create scene {:?} on a child: {e}", payload.scene);
+                        StatusCode::BAD_GATEWAY
+                    })?;
                 runners_per_node.push(Runner {
                     process: None,
                     uuid: response.runner,
@@ -541,226 +1335,1751 @@ async fn create_scene(
             }
         }
         _ => {
-            // Spawn the runner and wait for it to become ready.
-            // NOTE: creating a scene is a one-time event, so it sounds acceptable for it to
-            //       take a bit of time (instead of having pre-spawned runners).
-            let uuid = Uuid::new_v4();
-            log::info!(
-                "Spawning new runner: {:?}, path : {}.",
-                uuid,
-                CONFIG.runner_exe
-            );
-            let mut locked_runners = state.data.runners.lock().await;
-            let mut args = vec![
-                "--uuid".to_string(),
-                format!("{}", uuid.to_u128_le()),
-                "--scene-uuid".to_string(),
-                format!("{}", payload.scene.0.to_u128_le()),
-            ];
+            // All the scenes of this node are hosted by the same runner process.
+            let uuid = host_runner(state).await?;
 
-            if state.data.my_type == PartitionnerType::Dev {
-                args.push("--dev".to_string());
+            let message = RunnerMessage::Adopt {
+                scene: payload.scene,
+                client_objects_encoding: payload.client_objects_encoding,
+                settings: payload.settings,
+            };
+            if let Err(e) = put_runner_message(&state.data.zenoh, uuid, message).await {
+                error!(
+                    "Failed to send scene {:?} to runner {uuid:?}: {e}",
+                    payload.scene
+                );
+                return Err(StatusCode::BAD_GATEWAY);
             }
 
-            let process = Command::new(&CONFIG.runner_exe).args(args).spawn().unwrap();
             let runner = Runner {
-                process: Some(process),
+                process: None,
                 uuid,
                 is_new: true,
             };
+            let mut locked_runners = state.data.runners.lock().await;
+            locked_runners.per_node.insert(payload.scene, vec![runner]);
+
+            CreateSceneResponse { runner: uuid }
+        }
+    };
+
+    Ok(response)
+}
+
+/// The runner hosting all the scenes of this node.
+///
+/// The first scene adopts it from the warm pool, or spawns it and waits for it to ack its
+/// initialization if the pool is empty. The `runners` lock isn’t held while spawning or waiting.
+async fn host_runner(state: &AppState) -> Result<Uuid, StatusCode> {
+    let _adoption = state.data.host_adoption.lock().await;
+    let deadline = Instant::now() + RUNNER_INIT_TIMEOUT;
+
+    loop {
+        // NOTE: created before checking the idle runners so a runner initialized in between
+        //       still wakes us up.
+        let initialized = state.data.runner_initialized.notified();
+
+        let any_pending = {
+            let mut runners = state.data.runners.lock().await;
+
+            if let Some(host) = &runners.host {
+                return Ok(host.uuid);
+            }
+
+            if let Some(idle) = runners.idle.pop() {
+                info!("Adopting runner {:?} from the warm pool.", idle.uuid);
+                let uuid = idle.uuid;
+                runners.host = Some(idle);
+                return Ok(uuid);
+            }
+
+            !runners.uninitialized.is_empty()
+        };
+
+        if !any_pending {
+            let runner = spawn_runner(state).await.map_err(|e| {
+                error!("Failed to spawn a runner: {e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+            let mut runners = state.data.runners.lock().await;
+            runners.uninitialized.insert(runner.uuid, runner);
+        }
+
+        let waited = tokio::time::timeout_at(deadline, initialized).await;
+        if waited.is_err() {
+            error!("No runner acked its initialization within {RUNNER_INIT_TIMEOUT:?}.");
+            return Err(StatusCode::SERVICE_UNAVAILABLE);
+        }
+    }
+}
+
+async fn remove_scene(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<RemoveSceneRequest>,
+) -> Result<(), StatusCode> {
+    {
+        let mut scene_usages = state.data.scene_usages.write().await;
+        if !tenant.owns(&scene_usages, payload.scene) {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        scene_usages.remove(&payload.scene);
+    }
+
+    info!("Removing scene: {:?}", payload.scene.0);
+    let mut runners = state.data.runners.lock().await;
+    let runners = &mut *runners;
+    runners.exited.insert(payload.scene);
+    state
+        .data
+        .insert_sessions
+        .write()
+        .await
+        .retain(|_, session| session.scene != payload.scene);
+    state
+        .data
+        .complete_inserts
+        .write()
+        .await
+        .retain(|_, (scene, _, _)| *scene != payload.scene);
+    state.data.scenes_rngs.lock().await.remove(&payload.scene);
+    state
+        .data
+        .body_owners
+        .write()
+        .await
+        .retain(|_, (scene, _)| *scene != payload.scene);
+    state.data.trajectories.write().await.remove(&payload.scene);
+    state.data.hot_regions.write().await.remove(&payload.scene);
+    state
+        .data
+        .scenes_metadata
+        .write()
+        .await
+        .remove(&payload.scene);
+
+    if let Some(node_runners) = runners.per_node.remove(&payload.scene) {
+        for mut runner in node_runners {
+            info!("Exiting runner: {:?}", runner.uuid);
+
+            put_runner_message(
+                &state.data.zenoh,
+                runner.uuid,
+                RunnerMessage::Exit {
+                    scene: payload.scene,
+                },
+            )
+            .await
+            .unwrap();
+
+            if let Some(child) = runner.process.take() {
+                runners.to_remove.send(child).await.unwrap();
+            }
+        }
+    }
+    state
+        .data
+        .assigned_runners
+        .retain(|(scene, _), _| *scene != payload.scene);
+
+    let children = state.data.children.lock().await;
+
+    for child_partitionner in children.iter() {
+        child_partitionner
+            .remove_scene(payload.scene)
+            .await
+            .unwrap();
+    }
+
+    Ok(())
+}
+
+/// Sends the script to the runners hosting the scene, so they load it before their next step.
+async fn set_script(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<SetScriptRequest>,
+) -> Result<(), StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+
+    let runners: Vec<_> = state
+        .data
+        .runners
+        .lock()
+        .await
+        .per_node
+        .get(&payload.scene)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .iter()
+        .map(|runner| runner.uuid)
+        .collect();
+
+    for runner in runners {
+        let message = RunnerMessage::SetScript {
+            scene: payload.scene,
+            module: payload.module.clone(),
+        };
+        if let Err(e) = put_runner_message(&state.data.zenoh, runner, message).await {
+            error!("Failed to send the script to runner {runner:?}: {e}");
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+    }
+
+    Ok(())
+}
+
+/// Changes the pacing of the steps of the scene, and tells its runners to stream the new time
+/// scale to the viewers.
+async fn set_time_scale(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<SetTimeScaleRequest>,
+) -> Result<(), StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+
+    if !payload.time_scale.is_valid() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let scenes_acks = state.data.scenes_acks.read().await;
+    let scene_acks = scenes_acks
+        .get(&payload.scene)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    *scene_acks.time_scale.write().await = payload.time_scale;
+    drop(scenes_acks);
+
+    let runners: Vec<_> = state
+        .data
+        .runners
+        .lock()
+        .await
+        .per_node
+        .get(&payload.scene)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .iter()
+        .map(|runner| runner.uuid)
+        .collect();
+
+    for runner in runners {
+        let message = RunnerMessage::SetTimeScale {
+            scene: payload.scene,
+            time_scale: payload.time_scale,
+        };
+        if let Err(e) = put_runner_message(&state.data.zenoh, runner, message).await {
+            error!("Failed to send the time scale to runner {runner:?}: {e}");
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+    }
+
+    Ok(())
+}
+
+/// Tells the runners of the scene to start, or stop, publishing the poses of the bodies after
+/// each step. The points already recorded are kept until the scene is removed.
+async fn trace_bodies(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<TraceBodiesRequest>,
+) -> Result<(), StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+
+    let runners: Vec<_> = state
+        .data
+        .runners
+        .lock()
+        .await
+        .per_node
+        .get(&payload.scene)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .iter()
+        .map(|runner| runner.uuid)
+        .collect();
+
+    if payload.traced {
+        let mut trajectories = state.data.trajectories.write().await;
+        let scene_trajectories = trajectories.entry(payload.scene).or_default();
+        for body in &payload.bodies {
+            scene_trajectories.entry(*body).or_default();
+        }
+    }
+
+    for runner in runners {
+        let message = RunnerMessage::TraceBodies {
+            scene: payload.scene,
+            bodies: payload.bodies.clone(),
+            traced: payload.traced,
+        };
+        if let Err(e) = put_runner_message(&state.data.zenoh, runner, message).await {
+            error!("Failed to send the traced bodies to runner {runner:?}: {e}");
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+    }
+
+    Ok(())
+}
+
+/// The points recorded for the requested bodies, or for every body traced so far.
+async fn trajectories(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<TrajectoriesRequest>,
+) -> Result<Json<Trajectories>, StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+
+    let trajectories = state.data.trajectories.read().await;
+    let scene_trajectories = trajectories
+        .get(&payload.scene)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let bodies: Vec<_> = if payload.bodies.is_empty() {
+        scene_trajectories.keys().copied().collect()
+    } else {
+        payload.bodies
+    };
+
+    let mut result = Trajectories::default();
+    for body in bodies {
+        let points = scene_trajectories.get(&body).ok_or(StatusCode::NOT_FOUND)?;
+        let mut points: Vec<_> = points.iter().copied().collect();
+        // The regions the body went through may have published their points out of order.
+        points.sort_by_key(|point| point.timestamp);
+        result.bodies.push(BodyTrajectory { body, points });
+    }
+
+    Ok(Json(result))
+}
+
+/// Sends the new solver options to the runners hosting the scene, used from their next step.
+async fn set_simulation_params(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<SetSimulationParamsRequest>,
+) -> Result<(), StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+
+    if !payload.params.is_valid() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let runners: Vec<_> = state
+        .data
+        .runners
+        .lock()
+        .await
+        .per_node
+        .get(&payload.scene)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .iter()
+        .map(|runner| runner.uuid)
+        .collect();
+
+    for runner in runners {
+        let message = RunnerMessage::SetSimulationParams {
+            scene: payload.scene,
+            params: payload.params,
+        };
+        if let Err(e) = put_runner_message(&state.data.zenoh, runner, message).await {
+            error!("Failed to send the solver options to runner {runner:?}: {e}");
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+    }
+
+    if let Some(metadata) = state
+        .data
+        .scenes_metadata
+        .write()
+        .await
+        .get_mut(&payload.scene)
+    {
+        metadata.settings.simulation_params = payload.params;
+    }
+
+    Ok(())
+}
+
+/// Sends the new force generators to the runners hosting the scene, used from their next step.
+async fn set_force_generators(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<SetForceGeneratorsRequest>,
+) -> Result<(), StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+
+    let runners: Vec<_> = state
+        .data
+        .runners
+        .lock()
+        .await
+        .per_node
+        .get(&payload.scene)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .iter()
+        .map(|runner| runner.uuid)
+        .collect();
+
+    for runner in runners {
+        let message = RunnerMessage::SetForceGenerators {
+            scene: payload.scene,
+            generators: payload.generators.clone(),
+        };
+        if let Err(e) = put_runner_message(&state.data.zenoh, runner, message).await {
+            error!("Failed to send the force generators to runner {runner:?}: {e}");
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+    }
+
+    if let Some(metadata) = state
+        .data
+        .scenes_metadata
+        .write()
+        .await
+        .get_mut(&payload.scene)
+    {
+        metadata.settings.force_generators = payload.generators;
+    }
+
+    Ok(())
+}
+
+/// Freezes or unfreezes a volume of the scene, and sends all its frozen volumes to the runners
+/// hosting it.
+async fn freeze_volume(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<FreezeVolumeRequest>,
+) -> Result<(), StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+
+    // NOTE: the step in flight may already run in some regions, and the next one may be sent
+    //       before the runners get the volumes. Freeze from the one after so every region
+    //       freezes the bodies at the same step.
+    let since_step = state
+        .data
+        .scenes_acks
+        .read()
+        .await
+        .get(&payload.scene)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .step_id
+        .load(Ordering::SeqCst)
+        + 2;
+
+    let volumes = {
+        let mut scenes_metadata = state.data.scenes_metadata.write().await;
+        let metadata = scenes_metadata
+            .get_mut(&payload.scene)
+            .ok_or(StatusCode::NOT_FOUND)?;
+        let volumes = &mut metadata.settings.frozen_volumes;
+        volumes.retain(|volume| volume.uuid != payload.volume);
+        if let Some(aabb) = payload.aabb {
+            volumes.push(FrozenVolume {
+                uuid: payload.volume,
+                aabb,
+                since_step,
+            });
+        }
+        volumes.clone()
+    };
+
+    let runners: Vec<_> = state
+        .data
+        .runners
+        .lock()
+        .await
+        .per_node
+        .get(&payload.scene)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .iter()
+        .map(|runner| runner.uuid)
+        .collect();
+
+    for runner in runners {
+        let message = RunnerMessage::SetFrozenVolumes {
+            scene: payload.scene,
+            volumes: volumes.clone(),
+        };
+        if let Err(e) = put_runner_message(&state.data.zenoh, runner, message).await {
+            error!("Failed to send the frozen volumes to runner {runner:?}: {e}");
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+    }
+
+    Ok(())
+}
+
+async fn body_input(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<BodyInputRequest>,
+) -> Result<(), StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+
+    let runners: Vec<_> = state
+        .data
+        .runners
+        .lock()
+        .await
+        .per_node
+        .get(&payload.scene)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .iter()
+        .map(|runner| runner.uuid)
+        .collect();
+
+    // NOTE: the body may change region before the input reaches it, so send it to all the
+    //       runners of the scene rather than to its current owner.
+    for runner in runners {
+        let message = RunnerMessage::BodyInput {
+            scene: payload.scene,
+            body: payload.body,
+            input: payload.input,
+        };
+        if let Err(e) = put_runner_message(&state.data.zenoh, runner, message).await {
+            error!("Failed to send the body input to runner {runner:?}: {e}");
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+    }
+
+    Ok(())
+}
+
+/// Despawns the bodies through the despawn stream of the scene, followed by all its runners.
+async fn remove_bodies(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<RemoveBodiesRequest>,
+) -> Result<(), StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+
+    let removed: HashSet<_> = payload.bodies.iter().copied().collect();
+    if let Err(e) = state
+        .data
+        .zenoh
+        .despawn_bodies(payload.scene, payload.bodies)
+        .await
+    {
+        error!("Failed to despawn bodies: {e}");
+        return Err(StatusCode::BAD_GATEWAY);
+    }
+
+    // The ids of the removed bodies can be inserted again.
+    state
+        .data
+        .body_owners
+        .write()
+        .await
+        .retain(|uuid, (scene, _)| *scene != payload.scene || !removed.contains(uuid));
+    if let Some(static_bodies) = state
+        .data
+        .static_bodies
+        .write()
+        .await
+        .get_mut(&payload.scene)
+    {
+        static_bodies.retain(|body| !removed.contains(&body.uuid));
+    }
+
+    Ok(())
+}
+
+async fn register_child(
+    State(state): State<AppState>,
+    _member: ClusterMember,
+    Negotiated(payload): Negotiated<RegisterChildRequest>,
+) {
+    let mut children = state.data.children.lock().await;
+    info!("Received child registration: {:?}", payload);
+
+    // A restarted child registers again, but keeps its place and scenes.
+    let mut children_endpoints = state.data.children_endpoints.lock().await;
+    let mut children_capabilities = state.data.children_capabilities.lock().await;
+    if let Some(id) = children_endpoints.iter().position(|c| *c == payload.child) {
+        children_capabilities[id] = payload.capabilities;
+        return;
+    }
+
+    let child_server =
+        AsyncPartitionnerServer::with_endpoint(payload.child.addr.clone(), payload.child.port)
+            .unwrap();
+    children.push(child_server);
+    children_endpoints.push(payload.child);
+    children_capabilities.push(payload.capabilities);
+}
+
+async fn start_stop(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<StartStopRequest>,
+) -> Result<(), StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+    start_stop_scene(&state, payload).await;
+    Ok(())
+}
+
+/// Starts or stops stepping `payload.scene`, on behalf of this partitionner.
+async fn start_stop_scene(state: &AppState, payload: StartStopRequest) {
+    let was_running = state.data.running.swap(payload.running, Ordering::SeqCst);
+
+    if payload.running && !was_running {
+        let scenes_ack = state.data.scenes_acks.read().await;
+        if let Some(scene_ack) = scenes_ack.get(&payload.scene) {
+            if scene_ack.step_id.load(Ordering::SeqCst)
+                <= scene_ack.step_limit.load(Ordering::SeqCst)
+            {
+                let step_id = scene_ack.step_id.load(Ordering::SeqCst);
+                drop(scenes_ack);
+                traced_step(
+                    state.clone(),
+                    StepCorrelation::root(payload.scene, step_id),
+                    StepRequest {
+                        scene: payload.scene,
+                        step_id,
+                    },
+                )
+                .await;
+            } else {
+                state.data.running.store(false, Ordering::SeqCst);
+            }
+        } else {
+            drop(scenes_ack);
+            traced_step(
+                state.clone(),
+                StepCorrelation::root(payload.scene, 1),
+                StepRequest {
+                    scene: payload.scene,
+                    step_id: 1,
+                },
+            )
+            .await;
+        };
+    }
+}
+
+/// Runs exactly `steps` steps of a paused scene, from its current step, unless its clients let
+/// it go further. Fails with `CONFLICT` if the scene is still running.
+async fn step_n(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<StepNRequest>,
+) -> Result<Json<StepNResponse>, StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+
+    if payload.steps == 0 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let scenes_acks = state.data.scenes_acks.read().await;
+    let scene_acks = scenes_acks
+        .get(&payload.scene)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    // NOTE: once stopped, `step_id` is the first step that wasn’t run.
+    let step_id = scene_acks.step_id.load(Ordering::SeqCst);
+    if state.data.running.load(Ordering::SeqCst)
+        && step_id <= scene_acks.step_limit.load(Ordering::SeqCst)
+    {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let step_limit = step_id
+        .checked_add(payload.steps - 1)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    scene_acks
+        .debug_step_limit
+        .store(step_limit, Ordering::SeqCst);
+    scene_acks.step_limit.store(step_limit, Ordering::SeqCst);
+    drop(scenes_acks);
+
+    start_stop_scene(
+        &state,
+        StartStopRequest {
+            scene: payload.scene,
+            running: true,
+        },
+    )
+    .await;
+
+    Ok(Json(StepNResponse { step_limit }))
+}
+
+async fn list_scenes(State(state): State<AppState>, tenant: Tenant) -> Json<SceneList> {
+    let scenes: Vec<_> = {
+        let scene_usages = state.data.scene_usages.read().await;
+        let runners = state.data.runners.lock().await;
+        runners
+            .per_node
+            .keys()
+            .filter(|scene| !runners.exited.contains(scene))
+            .filter(|scene| tenant.owns(&scene_usages, **scene))
+            .copied()
+            .collect()
+    };
+
+    let mut num_bodies: HashMap<_, _> = state
+        .data
+        .static_bodies
+        .read()
+        .await
+        .iter()
+        .map(|(scene, bodies)| (*scene, bodies.len()))
+        .collect();
+    for (scene, _) in state.data.body_owners.read().await.values() {
+        *num_bodies.entry(*scene).or_default() += 1;
+    }
+
+    let scenes_metadata = state.data.scenes_metadata.read().await;
+    let running = state.data.running.load(Ordering::SeqCst);
+    let mut scenes: Vec<_> = scenes
+        .into_iter()
+        .map(|uuid| {
+            let metadata = scenes_metadata.get(&uuid).cloned().unwrap_or_default();
+            SceneInfo {
+                uuid,
+                name: metadata.settings.name,
+                num_bodies: num_bodies.get(&uuid).copied().unwrap_or(0),
+                created_at: metadata.created_at,
+                running,
+            }
+        })
+        .collect();
+    scenes.sort_by_key(|scene| std::cmp::Reverse(scene.created_at));
+
+    Json(SceneList { scenes })
+}
+
+/// Maximum time waited for the regions to publish their part of a scene export.
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(30);
+
+async fn export_scene(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<ExportSceneRequest>,
+) -> Result<Json<SceneExport>, StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+    collect_scene_export(&state, payload.scene).await.map(Json)
+}
+
+/// Gathers the bodies and joints of `scene` from the child partitionners, or from the runners
+/// simulating its regions.
+async fn collect_scene_export(
+    state: &AppState,
+    scene: SceneUuid,
+) -> Result<SceneExport, StatusCode> {
+    let mut result = SceneExport::default();
+
+    if state.data.my_type == PartitionnerType::Master {
+        let children: Vec<_> = state.data.children.lock().await.iter().cloned().collect();
+
+        for child_partitionner in children {
+            match child_partitionner.export_scene(scene).await {
+                Ok(export) => result.merge(export),
+                Err(e) => {
+                    error!("Failed to export scene from child partitionner: {e}");
+                    return Err(StatusCode::BAD_GATEWAY);
+                }
+            }
+        }
+
+        return Ok(result);
+    }
+
+    let (regions, runners): (HashSet<_>, HashSet<_>) = state
+        .data
+        .assigned_runners
+        .iter()
+        .filter(|entry| entry.key().0 == scene)
+        .map(|entry| (entry.key().1, *entry.value()))
+        .unzip();
+
+    // Subscribe before asking the runners, so we don’t miss any answer.
+    let request = Uuid::new_v4();
+    let subscriber = state
+        .data
+        .zenoh
+        .session
+        .declare_subscriber(scene_export_key_expr(request))
+        .res_async()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    for runner in runners {
+        let message = RunnerMessage::Export {
+            scene: scene,
+            request,
+        };
+        if let Err(e) = put_runner_message(&state.data.zenoh, runner, message).await {
+            error!("Failed to ask runner {runner:?} for a scene export: {e}");
+        }
+    }
+
+    let deadline = Instant::now() + EXPORT_TIMEOUT;
+    let mut num_received = 0;
+
+    while num_received < regions.len() {
+        let Ok(sample) = tokio::time::timeout_at(deadline, subscriber.recv_async()).await else {
+            warn!(
+                "Only {}/{} regions of {:?} answered the export request.",
+                num_received,
+                regions.len(),
+                scene
+            );
+            break;
+        };
+        let Ok(sample) = sample else { break };
+        match deserialize_auto(&sample.value.payload.contiguous()) {
+            Ok(export) => result.merge(export),
+            Err(e) => error!("Failed to decode region export: {e}"),
+        }
+        num_received += 1;
+    }
+
+    // The static bodies haven’t been sent to any runner if there is no dynamic body yet.
+    if let Some(static_bodies) = state.data.static_bodies.read().await.get(&scene) {
+        result.merge(SceneExport {
+            bodies: static_bodies.clone(),
+            ..SceneExport::default()
+        });
+    }
+
+    Ok(result)
+}
+
+/// Maximum time waited for the runners to answer a profiling query.
+const PROFILE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Queries the profile of the last step of every region of `scene` from its runners.
+async fn query_region_profiles(
+    state: &AppState,
+    scene: SceneUuid,
+) -> Result<Vec<RegionProfile>, StatusCode> {
+    let replies = state
+        .data
+        .zenoh
+        .session
+        .get(scene_profile_key_expr(scene))
+        .consolidation(ConsolidationMode::None)
+        .timeout(PROFILE_TIMEOUT)
+        .res()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut profiles = vec![];
+    while let Ok(reply) = replies.recv_async().await {
+        let Ok(sample) = reply.sample else { continue };
+        match deserialize_auto::<RegionProfile>(&sample.value.payload.contiguous()) {
+            Ok(profile) => profiles.push(profile),
+            Err(e) => error!("Failed to decode region profile: {e}"),
+        }
+    }
+
+    Ok(profiles)
+}
+
+/// Gathers the timings of the last step of every region of the scene, slowest first.
+///
+/// The runners are queried directly through zenoh, so any partitionner can answer.
+async fn profile_scene(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<ProfileSceneRequest>,
+) -> Result<Json<SceneProfile>, StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+
+    let mut result = SceneProfile {
+        regions: query_region_profiles(&state, payload.scene).await?,
+    };
+
+    // Ignore the time spent waiting for the step to start, that’s the other regions’ fault.
+    result.regions.sort_by(|a, b| {
+        let busy = |p: &RegionProfile| p.timings.loop_time - p.timings.waiting_acks;
+        busy(b).total_cmp(&busy(a))
+    });
+    Ok(Json(result))
+}
+
+/// Sums the energy, momentum and bodies of the last step of every region of the scene, and
+/// adds its static bodies, shared by the regions.
+async fn scene_stats(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<SceneStatsRequest>,
+) -> Result<Json<SceneStats>, StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+
+    let profiles = query_region_profiles(&state, payload.scene).await?;
+    let mut result = SceneStats {
+        num_regions: profiles.len(),
+        min_step_id: profiles.iter().map(|p| p.step_id).min().unwrap_or(0),
+        max_step_id: profiles.iter().map(|p| p.step_id).max().unwrap_or(0),
+        ..Default::default()
+    };
+    for profile in &profiles {
+        result.total.merge(&profile.stats);
+        result.bodies.merge(&profile.bodies);
+        result.regions.push(RegionBodyCounts {
+            region: profile.region,
+            bodies: profile.bodies,
+        });
+    }
+    result
+        .regions
+        .sort_by_key(|region| std::cmp::Reverse(region.bodies.total()));
+
+    if let Some(static_bodies) = state.data.static_bodies.read().await.get(&payload.scene) {
+        for body in static_bodies {
+            result.bodies.add(body.cold.body_type, false);
+        }
+    }
+
+    Ok(Json(result))
+}
+
+/// The step of the scene, its hot regions as last checked by `hot_spot_loop`, the latest
+/// violations found by `consistency_loop`, and its force generators.
+async fn scene_status(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<SceneStatusRequest>,
+) -> Result<Json<SceneStatus>, StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+
+    let step_id = state
+        .data
+        .scenes_acks
+        .read()
+        .await
+        .get(&payload.scene)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .step_id
+        .load(Ordering::SeqCst);
+    let hot_regions = state
+        .data
+        .hot_regions
+        .read()
+        .await
+        .get(&payload.scene)
+        .cloned()
+        .unwrap_or_default();
+
+    let violations = state
+        .data
+        .violations
+        .read()
+        .await
+        .get(&payload.scene)
+        .map(|violations| violations.iter().copied().collect())
+        .unwrap_or_default();
+
+    let force_generators = state
+        .data
+        .scenes_metadata
+        .read()
+        .await
+        .get(&payload.scene)
+        .map(|metadata| metadata.settings.force_generators.clone())
+        .unwrap_or_default();
+
+    Ok(Json(SceneStatus {
+        step_id,
+        running: state.data.running.load(Ordering::SeqCst),
+        hot_regions,
+        violations,
+        force_generators,
+    }))
+}
+
+/// Forwards the complete client objects of a region, as stored by its runner.
+async fn full_sync(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<FullSyncRequest>,
+) -> Result<bytes::Bytes, StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+
+    let key = payload.region.runner_full_client_objects_key(payload.scene);
+    let replies = state
+        .data
+        .zenoh
+        .session
+        .get(&key)
+        .res()
+        .await
+        .map_err(|e| {
+            error!(
+                "Failed to query the client objects of {:?}: {e}",
+                payload.region
+            );
+            StatusCode::BAD_GATEWAY
+        })?;
+    let reply = replies
+        .recv_async()
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let sample = reply.sample.map_err(|_| StatusCode::BAD_GATEWAY)?;
+    Ok(sample.value.payload.contiguous().into_owned().into())
+}
+
+async fn body_owner(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<BodyOwnerRequest>,
+) -> Result<Json<BodyOwner>, StatusCode> {
+    let (scene, region) = state
+        .data
+        .body_owners
+        .read()
+        .await
+        .get(&payload.body)
+        .copied()
+        .ok_or(StatusCode::NOT_FOUND)?;
+    tenant.check_scene(&state, scene).await?;
+    let runner = state
+        .data
+        .assigned_runners
+        .get(&(scene, region))
+        .map(|runner| *runner)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(BodyOwner {
+        scene,
+        region,
+        runner,
+    }))
+}
+
+/// Keeps track of the region simulating each body, from the updates published by the runners.
+fn body_ownership_loop(state: AppState) {
+    tokio::spawn(async move {
+        let subscriber = match state
+            .data
+            .zenoh
+            .session
+            .declare_subscriber(body_ownership_key_expr())
+            .res()
+            .await
+        {
+            Ok(subscriber) => subscriber,
+            Err(e) => {
+                error!("Failed to subscribe to the body ownership updates: {e}");
+                return;
+            }
+        };
+
+        while let Ok(sample) = subscriber.recv_async().await {
+            let update: BodyOwnershipUpdate =
+                match deserialize_auto(&sample.value.payload.contiguous()) {
+                    Ok(update) => update,
+                    Err(e) => {
+                        error!("Failed to deserialize body ownership update: {e}");
+                        continue;
+                    }
+                };
+
+            if state
+                .data
+                .runners
+                .lock()
+                .await
+                .exited
+                .contains(&update.scene)
+            {
+                continue;
+            }
+
+            let mut body_owners = state.data.body_owners.write().await;
+            for body in update.bodies {
+                body_owners.insert(body, (update.scene, update.region));
+            }
+            // The rejected bodies can be inserted again, once fixed.
+            for rejected in update.rejected {
+                warn!(
+                    "Body {:?} rejected by region {:?} of scene {:?}: {:?}.",
+                    rejected.uuid, update.region, update.scene, rejected.reason
+                );
+                body_owners.remove(&rejected.uuid);
+            }
+        }
+    });
+}
+
+/// Records the points published by the runners for the traced bodies.
+fn trajectory_loop(state: AppState) {
+    tokio::spawn(async move {
+        let subscriber = match state
+            .data
+            .zenoh
+            .session
+            .declare_subscriber(trajectory_key_expr())
+            .res()
+            .await
+        {
+            Ok(subscriber) => subscriber,
+            Err(e) => {
+                error!("Failed to subscribe to the trajectory updates: {e}");
+                return;
+            }
+        };
+
+        while let Ok(sample) = subscriber.recv_async().await {
+            let update: TrajectoryUpdate =
+                match deserialize_auto(&sample.value.payload.contiguous()) {
+                    Ok(update) => update,
+                    Err(e) => {
+                        error!("Failed to deserialize trajectory update: {e}");
+                        continue;
+                    }
+                };
+
+            let mut trajectories = state.data.trajectories.write().await;
+            // Ignore the updates of removed scenes.
+            let Some(scene_trajectories) = trajectories.get_mut(&update.scene) else {
+                continue;
+            };
+            for (body, point) in update.points {
+                let points = scene_trajectories.entry(body).or_default();
+                points.push_back(point);
+                if points.len() > MAX_TRAJECTORY_LEN {
+                    points.pop_front();
+                }
+            }
+        }
+    });
+}
+
+/// The regions of the scene, with the bodies and the last active step of those whose runners
+/// answered the profile query.
+async fn list_regions(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<ListRegionsRequest>,
+) -> Result<Json<RegionList>, StatusCode> {
+    if !tenant.owns(&*state.data.scene_usages.read().await, payload.scene) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let bounds: Vec<_> = state
+        .data
+        .assigned_runners
+        .iter()
+        .map(|entry| *entry.key())
+        .filter(|(scene, _)| *scene == payload.scene)
+        .map(|(_, region)| region)
+        .collect();
+    let activity = query_region_profiles(&state, payload.scene)
+        .await?
+        .into_iter()
+        .filter(|profile| bounds.contains(&profile.region))
+        .map(|profile| RegionActivity {
+            region: profile.region,
+            step_id: profile.step_id,
+            bodies: profile.bodies,
+            last_active_step: profile.last_active_step,
+        })
+        .collect();
+
+    Ok(Json(RegionList { bounds, activity }))
+}
+
+async fn insert_objects(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<InsertObjectsRequest>,
+) -> Result<(), StatusCode> {
+    tenant.add_bodies(
+        &mut *state.data.scene_usages.write().await,
+        payload.scene,
+        payload.bodies.len(),
+    )?;
+    insert_bodies(&state, payload.scene, payload.bodies).await
+}
+
+async fn begin_insert(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<BeginInsertRequest>,
+) -> Result<Json<BeginInsertResponse>, StatusCode> {
+    tenant.add_bodies(
+        &mut *state.data.scene_usages.write().await,
+        payload.scene,
+        payload.total,
+    )?;
+
+    let (uuid, _, _) = start_insert_session(&state, payload.scene, payload.total).await;
+    Ok(Json(BeginInsertResponse { session: uuid }))
+}
+
+/// Starts a session inserting `total` bodies in `scene`, chunk by chunk.
+///
+/// The returned task completes once all the bodies were inserted, or with the status of the
+/// first chunk that failed. The session is removed either way, only the final progress of
+/// the complete ones is kept, see [`insert_session_expiry_loop`].
+async fn start_insert_session(
+    state: &AppState,
+    scene: SceneUuid,
+    total: usize,
+) -> (Uuid, Arc<InsertSession>, JoinHandle<Result<(), StatusCode>>) {
+    let uuid = Uuid::new_v4();
+    tracing::info!(
+        "Starting insertion session {:?} of {} objects.",
+        uuid,
+        total
+    );
+
+    // The bounded channel provides backpressure: `insert_chunk` won’t reply
+    // until there is room for its chunk.
+    let (chunks_snd, chunks_rcv) = async_channel::bounded(MAX_PENDING_INSERT_CHUNKS);
+    let session = Arc::new(InsertSession {
+        scene,
+        total,
+        received: AtomicUsize::new(0),
+        inserted: Arc::new(AtomicUsize::new(0)),
+        last_chunk: std::sync::Mutex::new(Instant::now()),
+        chunks_snd,
+    });
+    state
+        .data
+        .insert_sessions
+        .write()
+        .await
+        .insert(uuid, session.clone());
+
+    let state = state.clone();
+    let inserted = session.inserted.clone();
+    let task = tokio::spawn(async move {
+        let result = loop {
+            if inserted.load(Ordering::SeqCst) >= total {
+                break Ok(());
+            }
+
+            let Ok(chunk) = chunks_rcv.recv().await else {
+                // The session expired, or its scene was removed.
+                break Err(StatusCode::GONE);
+            };
+            let len = chunk.len();
+            if let Err(status) = insert_bodies(&state, scene, chunk).await {
+                break Err(status);
+            }
+
+            inserted.fetch_add(len, Ordering::SeqCst);
+        };
+
+        let session = state.data.insert_sessions.write().await.remove(&uuid);
+        match result {
+            Ok(()) => {
+                tracing::info!("Insertion session {:?} complete.", uuid);
+                if let Some(session) = session {
+                    state
+                        .data
+                        .complete_inserts
+                        .write()
+                        .await
+                        .insert(uuid, (scene, session.progress(), Instant::now()));
+                }
+            }
+            Err(status) => tracing::error!("Insertion session {:?} failed: {status}", uuid),
+        }
+
+        result
+    });
+
+    (uuid, session, task)
+}
+
+/// Drops the insertion sessions that received no chunk for [`INSERT_SESSION_TIMEOUT`], which
+/// stops their task, and forgets the progress of the complete ones after as long.
+fn insert_session_expiry_loop(state: AppState) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(INSERT_SESSION_TIMEOUT / 10).await;
+
+            state
+                .data
+                .insert_sessions
+                .write()
+                .await
+                .retain(|uuid, session| {
+                    let expired =
+                        session.last_chunk.lock().unwrap().elapsed() >= INSERT_SESSION_TIMEOUT;
+                    if expired {
+                        warn!("Dropping the idle insertion session {:?}.", uuid);
+                    }
+                    !expired
+                });
+            state
+                .data
+                .complete_inserts
+                .write()
+                .await
+                .retain(|_, (_, _, completed_at)| completed_at.elapsed() < INSERT_SESSION_TIMEOUT);
+        }
+    });
+}
+
+async fn insert_chunk(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<InsertChunkRequest>,
+) -> Result<Json<InsertProgress>, StatusCode> {
+    let session = state
+        .data
+        .insert_sessions
+        .read()
+        .await
+        .get(&payload.session)
+        .cloned()
+        .ok_or(StatusCode::NOT_FOUND)?;
+    tenant.check_scene(&state, session.scene).await?;
+    session.touch();
+
+    // Only the announced total counted against the quota of the tenant. A rejected chunk
+    // isn’t counted, so the next ones can still fit.
+    let len = payload.bodies.len();
+    session
+        .received
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |received| {
+            (received + len <= session.total).then_some(received + len)
+        })
+        .map_err(|_| StatusCode::PAYLOAD_TOO_LARGE)?;
+
+    session
+        .chunks_snd
+        .send(payload.bodies)
+        .await
+        .map_err(|_| StatusCode::GONE)?;
+
+    // NOTE: the chunk is only queued, see `InsertProgress::received`.
+    Ok(Json(session.progress()))
+}
+
+async fn insert_progress(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<InsertProgressRequest>,
+) -> Result<Json<InsertProgress>, StatusCode> {
+    let session = state
+        .data
+        .insert_sessions
+        .read()
+        .await
+        .get(&payload.session)
+        .map(|session| (session.scene, session.progress()));
+    let (scene, progress) = match session {
+        Some(session) => session,
+        None => state
+            .data
+            .complete_inserts
+            .read()
+            .await
+            .get(&payload.session)
+            .map(|(scene, progress, _)| (*scene, *progress))
+            .ok_or(StatusCode::NOT_FOUND)?,
+    };
+    tenant.check_scene(&state, scene).await?;
+    Ok(Json(progress))
+}
+
+async fn spawn_templates(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<SpawnTemplatesRequest>,
+) -> Result<(), StatusCode> {
+    let num_bodies = payload
+        .templates
+        .iter()
+        .try_fold(0usize, |total, template| {
+            total.checked_add(template.num_bodies()?)
+        })
+        .filter(|num_bodies| *num_bodies <= CONFIG.max_template_bodies)
+        .ok_or(StatusCode::PAYLOAD_TOO_LARGE)?;
+
+    // Each template gets its own generator, so the one of the scene isn’t locked during the
+    // insertion.
+    let template_rngs: Vec<_> = {
+        let mut rngs = state.data.scenes_rngs.lock().await;
+        let rng = rngs.get_mut(&payload.scene).ok_or(StatusCode::NOT_FOUND)?;
+        payload
+            .templates
+            .iter()
+            .map(|_| StdRng::seed_from_u64(rng.gen()))
+            .collect()
+    };
+
+    tenant.add_bodies(
+        &mut *state.data.scene_usages.write().await,
+        payload.scene,
+        num_bodies,
+    )?;
+
+    // The bodies are generated chunk by chunk, as they are inserted, so the large templates
+    // don’t have to fit in memory.
+    let (_, session, task) = start_insert_session(&state, payload.scene, num_bodies).await;
+
+    'templates: for (template, rng) in payload.templates.iter().zip(template_rngs) {
+        tracing::info!(
+            "Spawning {} bodies from template {:?}.",
+            template.num_bodies().unwrap_or_default(),
+            template
+        );
+
+        let mut bodies = template.bodies(rng);
+        loop {
+            let chunk: Vec<_> = bodies.by_ref().take(TEMPLATE_CHUNK_SIZE).collect();
+            if chunk.is_empty() {
+                break;
+            }
+
+            session.touch();
+            session.received.fetch_add(chunk.len(), Ordering::SeqCst);
+            if session.chunks_snd.send(chunk).await.is_err() {
+                // The insertion failed, its task reports why.
+                break 'templates;
+            }
+        }
+    }
+
+    drop(session);
+    task.await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
+
+/// The share of the scenes given to each child: a node with more cores gets a larger part.
+async fn children_weights(state: &AppState) -> Vec<Real> {
+    let capabilities = state.data.children_capabilities.lock().await;
+    capabilities.iter().map(|c| c.weight()).collect()
+}
+
+/// Splits the domain of `scene` between the children again on its first insertion, so each
+/// gets a share of its bodies rather than of its volume.
+///
+/// No region was assigned yet, so the bounds can still change. The regions are assigned to the
+/// child containing their center, so the bodies are counted at the center of their region.
+async fn balance_children_bounds(
+    state: &AppState,
+    scene: SceneUuid,
+    region_to_objects: &HashMap<SimulationBounds, Vec<BodyAssignment>>,
+) {
+    if region_to_objects.is_empty() {
+        return;
+    }
+
+    let weights = children_weights(state).await;
+    let mut geometries = state.data.scenes_geometries.write().await;
+    let Some(geometry) = geometries.get_mut(&scene) else {
+        return;
+    };
+    if !geometry.provisional {
+        return;
+    }
+    geometry.provisional = false;
+
+    // The children registered after the scene was created don’t simulate it.
+    if geometry.children_bounds.len() <= 1 || geometry.children_bounds.len() != weights.len() {
+        return;
+    }
+
+    let bodies: Vec<_> = region_to_objects
+        .iter()
+        .map(|(region, bodies)| (region.aabb().center(), bodies.len()))
+        .collect();
+    let domain = geometry
+        .children_bounds
+        .iter()
+        .copied()
+        .reduce(|a, b| a.merged(&b))
+        .unwrap();
+    geometry.children_bounds = subdivide_domain(domain, &weights, &bodies);
+    info!(
+        "Balanced the children bounds of scene {:?} on {} bodies.",
+        scene,
+        bodies.iter().map(|(_, count)| count).sum::<usize>()
+    );
+}
 
-            // FIXME: wait for the runner to be ready.
-            tokio::time::sleep(Duration::from_secs(1)).await;
-
-            locked_runners.per_node.insert(payload.scene, vec![runner]);
+async fn insert_bodies(
+    state: &AppState,
+    scene: SceneUuid,
+    bodies: Vec<BodyAssignment>,
+) -> Result<(), StatusCode> {
+    tracing::info!("Inserting {} objects.", bodies.len());
 
-            CreateSceneResponse { runner: uuid }
+    if state.data.runners.lock().await.exited.contains(&scene) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut locked_static_bodies = state.data.static_bodies.write().await;
+    let static_bodies = locked_static_bodies.entry(scene).or_insert_with(|| vec![]);
+
+    // NOTE: procedural generators run again insert bodies with the same deterministic ids,
+    //       see `SceneUuid::body_uuid`. Skip the bodies the scene already has.
+    let bodies = {
+        let body_owners = state.data.body_owners.read().await;
+        let mut known: HashSet<_> = static_bodies.iter().map(|body| body.uuid).collect();
+        let num_bodies = bodies.len();
+        let bodies: Vec<_> = bodies
+            .into_iter()
+            .filter(|body| {
+                body_owners.get(&body.uuid).map(|(s, _)| *s) != Some(scene)
+                    && known.insert(body.uuid)
+            })
+            .collect();
+        if bodies.len() != num_bodies {
+            info!(
+                "Skipped {} bodies already in the scene {:?}.",
+                num_bodies - bodies.len(),
+                scene
+            );
         }
+        bodies
     };
 
-    info!("Done creating scene {:?}", payload.scene);
-    Json(response)
-}
-
-async fn remove_scene(State(state): State<AppState>, Json(payload): Json<RemoveSceneRequest>) {
-    info!("Removing scene: {:?}", payload.scene.0);
-    let mut runners = state.data.runners.lock().await;
-    let runners = &mut *runners;
-    runners.exited.insert(payload.scene);
-
-    if let Some(node_runners) = runners.per_node.remove(&payload.scene) {
-        for mut runner in node_runners {
-            info!("Exiting runner: {:?}", runner.uuid);
+    let (dynamic_bodies, mut new_static_bodies): (Vec<_>, Vec<_>) = bodies
+        .into_iter()
+        .partition(|body| body.cold.moves_between_regions());
+    static_bodies.append(&mut new_static_bodies);
 
-            put_runner_message(&state.data.zenoh, runner.uuid, RunnerMessage::Exit)
-                .await
-                .unwrap();
+    // Group objects by island, so touching bodies end up in the same region.
+    let region_to_objects = group_islands_by_region(dynamic_bodies);
+    balance_children_bounds(state, scene, &region_to_objects).await;
 
-            if let Some(child) = runner.process.take() {
-                runners.to_remove.send(child).await.unwrap();
+    {
+        let mut body_owners = state.data.body_owners.write().await;
+        for (region, bodies) in &region_to_objects {
+            for body in bodies {
+                body_owners.insert(body.uuid, (scene, *region));
             }
         }
     }
-    runners
-        .assigned
-        .retain(|(scene, _), _| *scene != payload.scene);
 
-    let children = state.data.children.lock().await;
+    // Send objects to runners.
+    for (region, bodies) in region_to_objects {
+        let runner = assign_runner(
+            State(state.clone()),
+            ClusterMember,
+            Negotiated(AssignRunnerRequest { scene, region }),
+        )
+        .await?;
 
-    for child_partitionner in children.iter() {
-        child_partitionner
-            .remove_scene(payload.scene)
-            .await
-            .unwrap();
+        tracing::info!("Inserting {} objects to {}", bodies.len(), runner.uuid);
+
+        // Send message to the runner.
+        let messages = [
+            RunnerMessage::AssignIsland {
+                scene,
+                region,
+                bodies,
+                impulse_joints: vec![],
+            },
+            RunnerMessage::AssignStaticBodies {
+                scene,
+                bodies: static_bodies.clone(),
+            },
+            RunnerMessage::SyncClientObjects { scene },
+        ];
+        for message in messages {
+            if let Err(e) = put_runner_message(&state.data.zenoh, runner.uuid, message).await {
+                error!("Failed to send the bodies to runner {:?}: {e}", runner.uuid);
+                return Err(StatusCode::BAD_GATEWAY);
+            }
+        }
     }
+
+    Ok(())
 }
 
-async fn register_child(State(state): State<AppState>, Json(payload): Json<RegisterChildRequest>) {
-    let mut children = state.data.children.lock().await;
-    info!("Received child registration: {:?}", payload);
-    let child_server =
-        AsyncPartitionnerServer::with_endpoint(payload.child.addr, payload.child.port).unwrap();
-    children.push(child_server);
+async fn insert_particles(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<InsertParticlesRequest>,
+) -> Result<(), StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
+
+    assign_particles(&state, payload.scene, payload.particles).await
 }
 
-async fn start_stop(State(state): State<AppState>, Json(payload): Json<StartStopRequest>) {
-    let was_running = state.data.running.swap(payload.running, Ordering::SeqCst);
+/// Sends fluid particles to the runners of the regions they are in.
+async fn assign_particles(
+    state: &AppState,
+    scene: SceneUuid,
+    particles: Vec<FluidParticle>,
+) -> Result<(), StatusCode> {
+    tracing::info!("Inserting {} particles.", particles.len());
 
-    if payload.running && !was_running {
-        let scenes_ack = state.data.scenes_acks.read().await;
-        if let Some(scene_ack) = scenes_ack.get(&payload.scene) {
-            if scene_ack.step_id.load(Ordering::SeqCst)
-                < scene_ack.step_limit.load(Ordering::SeqCst)
-            {
-                let step_id = scene_ack.step_id.load(Ordering::SeqCst);
-                drop(scenes_ack);
-                step(
-                    State(state.clone()),
-                    Json(StepRequest {
-                        scene: payload.scene,
-                        step_id,
-                    }),
-                )
-                .await;
-            } else {
-                state.data.running.store(false, Ordering::SeqCst);
+    if state.data.runners.lock().await.exited.contains(&scene) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut region_to_particles: HashMap<_, Vec<_>> = HashMap::new();
+    for particle in particles {
+        let region =
+            SimulationBounds::from_point(particle.position, SimulationBounds::DEFAULT_WIDTH);
+        region_to_particles
+            .entry(region)
+            .or_default()
+            .push(particle);
+    }
+
+    let static_bodies = state
+        .data
+        .static_bodies
+        .read()
+        .await
+        .get(&scene)
+        .cloned()
+        .unwrap_or_default();
+
+    for (region, particles) in region_to_particles {
+        let runner = assign_runner(
+            State(state.clone()),
+            ClusterMember,
+            Negotiated(AssignRunnerRequest { scene, region }),
+        )
+        .await?;
+
+        // The region may be new, with a runner that doesn’t know the static bodies yet.
+        let messages = [
+            RunnerMessage::AssignStaticBodies {
+                scene,
+                bodies: static_bodies.clone(),
+            },
+            RunnerMessage::AssignParticles {
+                scene,
+                region,
+                particles,
+            },
+        ];
+        for message in messages {
+            if let Err(e) = put_runner_message(&state.data.zenoh, runner.uuid, message).await {
+                error!("Failed to send particles to runner {:?}: {e}", runner.uuid);
+                return Err(StatusCode::BAD_GATEWAY);
             }
-        } else {
-            drop(scenes_ack);
-            step(
-                State(state.clone()),
-                Json(StepRequest {
-                    scene: payload.scene,
-                    step_id: 1,
-                }),
-            )
-            .await;
-        };
+        }
     }
+
+    Ok(())
 }
 
-async fn list_scenes(State(state): State<AppState>) -> Json<SceneList> {
-    let runners = state.data.runners.lock().await;
+/// Sends the joints between inserted bodies to the regions simulating either of their bodies.
+///
+/// A joint between two regions is simulated by both, against a ghost of the other body.
+async fn assign_joints(
+    state: &AppState,
+    scene: SceneUuid,
+    joints: Vec<ImpulseJointAssignment>,
+) -> Result<(), StatusCode> {
+    tracing::info!("Inserting {} joints.", joints.len());
 
-    Json(SceneList {
-        scenes: runners.per_node.keys().cloned().collect(),
-    })
+    let mut region_to_joints: HashMap<_, Vec<_>> = HashMap::new();
+    {
+        let body_owners = state.data.body_owners.read().await;
+        for joint in joints {
+            let regions: HashSet<_> = [joint.body1, joint.body2]
+                .iter()
+                .filter_map(|body| body_owners.get(body))
+                .filter(|(body_scene, _)| *body_scene == scene)
+                .map(|(_, region)| *region)
+                .collect();
+            for region in regions {
+                region_to_joints.entry(region).or_default().push(joint);
+            }
+        }
+    }
+
+    for (region, impulse_joints) in region_to_joints {
+        let runner = assign_runner(
+            State(state.clone()),
+            ClusterMember,
+            Negotiated(AssignRunnerRequest { scene, region }),
+        )
+        .await?;
+
+        let message = RunnerMessage::AssignIsland {
+            scene,
+            region,
+            bodies: vec![],
+            impulse_joints,
+        };
+        if let Err(e) = put_runner_message(&state.data.zenoh, runner.uuid, message).await {
+            error!("Failed to send joints to runner {:?}: {e}", runner.uuid);
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+    }
+
+    Ok(())
 }
 
-async fn list_regions(
+async fn insert_vehicles(
     State(state): State<AppState>,
-    Json(payload): Json<ListRegionsRequest>,
-) -> Json<RegionList> {
-    let runners = state.data.runners.lock().await;
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<InsertVehiclesRequest>,
+) -> Result<(), StatusCode> {
+    tenant.check_scene(&state, payload.scene).await?;
 
-    Json(RegionList {
-        bounds: runners
-            .assigned
-            .iter()
-            .filter(|((scene, _), _)| *scene == payload.scene)
-            .map(|((_, region), _)| *region)
-            .collect(),
-    })
+    assign_vehicles(&state, payload.scene, payload.vehicles).await
 }
 
-async fn insert_objects(
-    State(state): State<AppState>,
-    Json(payload): Json<InsertObjectsRequest>,
+/// Sends vehicles to the runners of the regions their chassis are in.
+async fn assign_vehicles(
+    state: &AppState,
+    scene: SceneUuid,
+    vehicles: Vec<VehicleAssignment>,
 ) -> Result<(), StatusCode> {
-    log::info!("Inserting {} objects.", payload.bodies.len());
+    tracing::info!("Inserting {} vehicles.", vehicles.len());
 
-    if state
-        .data
-        .runners
-        .lock()
-        .await
-        .exited
-        .contains(&payload.scene)
-    {
+    if state.data.runners.lock().await.exited.contains(&scene) {
         return Err(StatusCode::BAD_REQUEST);
     }
 
-    let mut locked_static_bodies = state.data.static_bodies.write().await;
-    let static_bodies = locked_static_bodies
-        .entry(payload.scene)
-        .or_insert_with(|| vec![]);
-
-    let mut region_to_objects = HashMap::new();
-
-    // Group object by region.
-    for body in payload.bodies {
-        // TODO: not calculating islands here will induce a 1-step delay between the
-        //       time the objects are simulated and the time they become aware of any
-        //       potential island merge across the boundary.
-        let aabb = body.cold.shape.compute_aabb(&body.warm.position);
-
-        if body.cold.body_type.is_dynamic() {
-            let region = SimulationBounds::from_aabb(&aabb, SimulationBounds::DEFAULT_WIDTH);
-            region_to_objects
-                .entry(region)
-                .or_insert_with(Vec::new)
-                .push(body);
-        } else {
-            static_bodies.push(body);
+    let mut region_to_vehicles: HashMap<_, Vec<_>> = HashMap::new();
+    for vehicle in vehicles {
+        let region = SimulationBounds::from_point(
+            vehicle.chassis.warm.position.translation.vector.into(),
+            SimulationBounds::DEFAULT_WIDTH,
+        );
+        region_to_vehicles.entry(region).or_default().push(vehicle);
+    }
+
+    {
+        let mut body_owners = state.data.body_owners.write().await;
+        for (region, vehicles) in &region_to_vehicles {
+            for vehicle in vehicles {
+                body_owners.insert(vehicle.chassis.uuid, (scene, *region));
+            }
         }
     }
 
-    // Send objects to runners.
-    for (region, bodies) in region_to_objects {
+    let static_bodies = state
+        .data
+        .static_bodies
+        .read()
+        .await
+        .get(&scene)
+        .cloned()
+        .unwrap_or_default();
+
+    for (region, vehicles) in region_to_vehicles {
         let runner = assign_runner(
             State(state.clone()),
-            Json(AssignRunnerRequest {
-                scene: payload.scene,
-                region,
-            }),
+            ClusterMember,
+            Negotiated(AssignRunnerRequest { scene, region }),
         )
         .await?;
 
-        log::info!("Inserting {} objects to {}", bodies.len(), runner.uuid);
-
-        // Send message to the runner.
-        let message = RunnerMessage::AssignIsland {
-            scene: payload.scene,
-            region,
-            bodies,
-            impulse_joints: vec![],
-        };
-        put_runner_message(&state.data.zenoh, runner.uuid, message)
-            .await
-            .unwrap();
-        let message = RunnerMessage::AssignStaticBodies {
-            bodies: static_bodies.clone(),
-        };
-        put_runner_message(&state.data.zenoh, runner.uuid, message)
-            .await
-            .unwrap();
-        put_runner_message(
-            &state.data.zenoh,
-            runner.uuid,
-            RunnerMessage::SyncClientObjects,
-        )
-        .await
-        .unwrap();
+        // The region may be new, with a runner that doesn’t know the static bodies yet.
+        let messages = [
+            RunnerMessage::AssignStaticBodies {
+                scene,
+                bodies: static_bodies.clone(),
+            },
+            RunnerMessage::AssignVehicles {
+                scene,
+                region,
+                vehicles,
+            },
+            RunnerMessage::SyncClientObjects { scene },
+        ];
+        for message in messages {
+            if let Err(e) = put_runner_message(&state.data.zenoh, runner.uuid, message).await {
+                error!("Failed to send vehicles to runner {:?}: {e}", runner.uuid);
+                return Err(StatusCode::BAD_GATEWAY);
+            }
+        }
     }
 
     Ok(())
@@ -768,40 +3087,38 @@ async fn insert_objects(
 
 async fn runner_initialized(
     State(state): State<AppState>,
-    Json(payload): Json<RunnerInitializedRequest>,
+    _member: ClusterMember,
+    Negotiated(payload): Negotiated<RunnerInitializedRequest>,
 ) {
-    // let mut runners = state.data.runners.lock().await;
-    //
-    // if let Some(runner) = runners.uninitialized.remove(&payload.uuid) {
-    //     log::info!("Runner {:?} acked initialization.", payload.uuid);
-    //     runners.pending.push(runner);
-    //     state.data.num_uninitialized.fetch_sub(1, Ordering::SeqCst);
-    // }
+    let mut runners = state.data.runners.lock().await;
+
+    if let Some(runner) = runners.uninitialized.remove(&payload.uuid) {
+        tracing::info!("Runner {:?} acked initialization.", payload.uuid);
+        runners.idle.push(runner);
+        state.data.runner_initialized.notify_waiters();
+    }
 }
 
 async fn assign_runner(
     State(state): State<AppState>,
-    Json(payload): Json<AssignRunnerRequest>,
+    _member: ClusterMember,
+    Negotiated(payload): Negotiated<AssignRunnerRequest>,
 ) -> Result<Json<AssignRunnerResponse>, StatusCode> {
-    // TODO: this basically makes this endpoint operate completely sequentially.
-    //       How could we avoid this?
-    let _lock_guard = state.data.assign_runner_lock.lock().await;
-
-    let mut runners = state.data.runners.lock().await;
-
-    if runners.exited.contains(&payload.scene) {
-        return Err(StatusCode::BAD_REQUEST);
-    }
-
-    if let Some(runner_uuid) = runners.assigned.get(&(payload.scene, payload.region)) {
-        return Ok(Json(AssignRunnerResponse {
+    let key = (payload.scene, payload.region);
+    let response = |uuid| {
+        Json(AssignRunnerResponse {
             scene: payload.scene,
             region: payload.region,
-            uuid: *runner_uuid,
-        }));
+            uuid,
+        })
+    };
+
+    // Most regions are already assigned: answer without taking any lock.
+    if let Some(runner_uuid) = state.data.assigned_runners.get(&key) {
+        return Ok(response(*runner_uuid));
     }
 
-    log::info!(
+    tracing::info!(
         "No runner assigned to region {:?}::{:?} yet.",
         payload.scene,
         payload.region,
@@ -811,15 +3128,13 @@ async fn assign_runner(
     match state.data.my_type {
         PartitionnerType::Master | PartitionnerType::Dev => {
             // This is a master partitionner, assign to one of its children.
-            let children_bounds = state
-                .data
-                .scenes_geometries
-                .read()
-                .await
-                .get(&payload.scene)
-                .unwrap()
-                .children_bounds
-                .clone();
+            let (children_bounds, redirects) = {
+                let geometries = state.data.scenes_geometries.read().await;
+                let geometry = geometries
+                    .get(&payload.scene)
+                    .ok_or(StatusCode::BAD_REQUEST)?;
+                (geometry.children_bounds.clone(), geometry.redirects.clone())
+            };
             let new_region_center = payload.region.aabb().center();
 
             let mut child_id = usize::MAX;
@@ -847,23 +3162,42 @@ async fn assign_runner(
                 }
             }
 
-            let uuid = runners.per_node[&payload.scene][child_id].uuid;
-            runners
-                .assigned
-                .insert((payload.scene, payload.region), uuid);
+            // The children whose regions all moved to another one give it their new regions.
+            for _ in 0..redirects.len() {
+                match redirects.get(&child_id) {
+                    Some(to) => child_id = *to,
+                    None => break,
+                }
+            }
+
+            // Only the insertion itself is done under the `runners` lock, so it can’t race
+            // with `remove_scene`. Concurrent assignments of the same region agree on the
+            // runner of the first one inserted.
+            let runners = state.data.runners.lock().await;
 
-            log::info!(
-                "Assigned region {:?}::{:?} to runner {:?}.",
-                payload.scene,
-                payload.region,
-                uuid
-            );
+            if runners.exited.contains(&payload.scene) {
+                return Err(StatusCode::BAD_REQUEST);
+            }
 
-            Ok(Json(AssignRunnerResponse {
-                scene: payload.scene,
-                region: payload.region,
-                uuid,
-            }))
+            let uuid = runners
+                .per_node
+                .get(&payload.scene)
+                .ok_or(StatusCode::BAD_REQUEST)?[child_id]
+                .uuid;
+
+            match state.data.assigned_runners.entry(key) {
+                Entry::Occupied(entry) => Ok(response(*entry.get())),
+                Entry::Vacant(entry) => {
+                    entry.insert(uuid);
+                    tracing::info!(
+                        "Assigned region {:?}::{:?} to runner {:?}.",
+                        payload.scene,
+                        payload.region,
+                        uuid
+                    );
+                    Ok(response(uuid))
+                }
+            }
         }
         PartitionnerType::Runner => {
             unreachable!()
@@ -871,46 +3205,245 @@ async fn assign_runner(
     }
 }
 
-fn init_log() {
-    let mut builder = env_logger::Builder::from_default_env();
-    builder.filter_level(log::LevelFilter::Info);
-    builder.init();
+async fn spawn_runner(state: &AppState) -> anyhow::Result<Runner> {
+    let uuid = Uuid::new_v4();
+    tracing::info!(
+        "Spawning new runner: {:?}, with the {:?} launcher.",
+        uuid,
+        CONFIG.runner_launcher
+    );
+    let mut args = vec![
+        "--uuid".to_string(),
+        format!("{}", uuid.to_u128_le()),
+        "--time-origin".to_string(),
+        format!("{}", state.data.time_origin),
+    ];
+
+    if state.data.my_type == PartitionnerType::Dev {
+        args.push("--dev".to_string());
+    }
+
+    let process = state.data.launcher.launch(uuid, args).await?;
+    Ok(Runner {
+        process: Some(process),
+        uuid,
+        is_new: true,
+    })
+}
+
+/// Keeps an idle runner ready (or being initialized) so the first scene of this node is
+/// created without paying for the process spawn latency.
+///
+/// All the scenes of a node, and all their regions, are hosted by the runner adopted by the
+/// first scene, so that is the only runner ever taken from the pool: `pool_size` is capped to
+/// one, and new regions never spawn a runner. Once the host is adopted, the idle runners left
+/// are shut down.
+fn warm_pool_loop(state: AppState, pool_size: u32) {
+    tokio::spawn(async move {
+        loop {
+            let (num_missing, surplus) = {
+                let mut runners = state.data.runners.lock().await;
+                let target = if runners.host.is_some() {
+                    0
+                } else {
+                    pool_size.min(1) as usize
+                };
+                let num_warm = runners.idle.len() + runners.uninitialized.len();
+                let num_surplus = runners.idle.len().saturating_sub(target);
+                let surplus: Vec<_> = runners.idle.drain(..num_surplus).collect();
+                (target.saturating_sub(num_warm), surplus)
+            };
+
+            for runner in surplus {
+                shut_down_idle_runner(&state, runner).await;
+            }
+
+            // NOTE: the runners are spawned without holding the `runners` lock, `docker run`
+            //       and `kubectl run` can take a while.
+            for _ in 0..num_missing {
+                match spawn_runner(&state).await {
+                    Ok(runner) => {
+                        let mut runners = state.data.runners.lock().await;
+                        runners.uninitialized.insert(runner.uuid, runner);
+                    }
+                    Err(e) => {
+                        error!("Failed to spawn a runner for the warm pool: {e}");
+                        break;
+                    }
+                }
+            }
+
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    });
+}
+
+/// Asks an idle runner no scene can adopt to exit, killing it if it can’t be reached.
+async fn shut_down_idle_runner(state: &AppState, runner: Runner) {
+    info!("Shutting down the surplus idle runner {:?}.", runner.uuid);
+    let shut_down =
+        put_runner_message(&state.data.zenoh, runner.uuid, RunnerMessage::Shutdown).await;
+
+    let Some(process) = runner.process else {
+        return;
+    };
+
+    if let Err(e) = shut_down {
+        let uuid = runner.uuid;
+        warn!("Failed to shut down the idle runner {uuid:?}, killing it: {e}");
+        if let Err(e) = process.kill().await {
+            error!("Failed to kill the idle runner {:?}: {e}", runner.uuid);
+        }
+    }
+
+    let to_remove = state.data.runners.lock().await.to_remove.clone();
+    let _ = to_remove.send(process).await;
 }
 
-fn runner_stopped_child_wait_loop(to_remove: Receiver<Child>) {
-    while let Ok(mut child) = to_remove.recv_blocking() {
-        if let Err(e) = child.wait() {
+async fn runner_stopped_child_wait_loop(to_remove: Receiver<Arc<dyn RunnerHandle>>) {
+    while let Ok(child) = to_remove.recv().await {
+        if let Err(e) = child.wait().await {
             println!("Error waiting for runner to exit: {e}");
         }
     }
 }
 
 fn input_handling_loop(state: AppState) {
+    let client_timeout = Duration::from_millis(CONFIG.client_timeout_ms);
+
     tokio::spawn(async move {
-        while let Ok(inputs) = state.data.inputs_rcv.recv().await {
-            let scenes_acks = state.data.scenes_acks.read().await;
-            if let Some(scene_acks) = scenes_acks.get(&inputs.scene) {
-                let new_step_limit = inputs.step_id / NUM_INTERNAL_STEPS + 2;
-                // println!(
-                //     "################# Sim state: {} (old limit: {}, step id: {}) ################",
-                //     new_step_limit,
-                //     scene_acks.step_limit.load(Ordering::SeqCst),
-                //     scene_acks.step_id.load(Ordering::SeqCst)
-                // );
-
-                scene_acks
-                    .step_limit
-                    .fetch_max(new_step_limit, Ordering::SeqCst);
-
-                drop(scenes_acks);
-                start_stop(
-                    State(state.clone()),
-                    Json(StartStopRequest {
-                        scene: inputs.scene,
-                        running: true,
-                    }),
-                )
-                .await;
+        loop {
+            // Wake up regularly to forget about the clients that went away.
+            let _ =
+                tokio::time::timeout(client_timeout / 2, state.data.inputs_notify.notified()).await;
+            // NOTE: the inputs received while the previous ones were handled are coalesced.
+            let inputs = std::mem::take(&mut *state.data.pending_inputs.lock().await);
+
+            for ((scene, client), step_id) in inputs {
+                handle_client_input(&state, scene, client, step_id).await;
+            }
+
+            expire_clients(&state, client_timeout).await;
+        }
+    });
+}
+
+/// Records the progress of `client`, and lets the simulation of `scene` run a few steps
+/// ahead of its clients, as selected by the `STEP_LIMIT_POLICY`.
+async fn handle_client_input(state: &AppState, scene: SceneUuid, client: Uuid, step_id: u64) {
+    let scenes_acks = state.data.scenes_acks.read().await;
+    if let Some(scene_acks) = scenes_acks.get(&scene) {
+        let now = Instant::now();
+        let mut clients = scene_acks.clients.lock().await;
+        clients.update(client, step_id, now);
+        let new_step_limit = clients.step_limit(
+            CONFIG.step_limit_policy,
+            now,
+            Duration::from_millis(CONFIG.client_timeout_ms),
+        );
+        drop(clients);
+
+        if let Some(new_step_limit) = new_step_limit {
+            scene_acks.set_clients_step_limit(new_step_limit);
+        }
+
+        drop(scenes_acks);
+        start_stop_scene(
+            state,
+            StartStopRequest {
+                scene,
+                running: true,
+            },
+        )
+        .await;
+    }
+}
+
+/// Forgets the clients that sent no input for `timeout`, and updates the step limits of their
+/// scenes accordingly.
+///
+/// The step limit of a scene without any active client is left as is.
+async fn expire_clients(state: &AppState, timeout: Duration) {
+    let now = Instant::now();
+    let mut resumed = vec![];
+
+    for (scene, scene_acks) in state.data.scenes_acks.read().await.iter() {
+        let mut clients = scene_acks.clients.lock().await;
+        if !clients.expire(now, timeout) {
+            continue;
+        }
+
+        if let Some(new_step_limit) = clients.step_limit(CONFIG.step_limit_policy, now, timeout) {
+            if new_step_limit > scene_acks.set_clients_step_limit(new_step_limit) {
+                resumed.push(*scene);
+            }
+        }
+    }
+
+    // The slowest client went away, the others may be waiting for the next steps.
+    for scene in resumed {
+        start_stop_scene(
+            state,
+            StartStopRequest {
+                scene,
+                running: true,
+            },
+        )
+        .await;
+    }
+}
+
+/// Handles the `step`, `ack` and `client_input` messages published on zenoh, like their
+/// REST endpoints would.
+fn control_plane_loop(state: AppState) {
+    tokio::spawn(async move {
+        let subscriber = match state
+            .data
+            .zenoh
+            .session
+            .declare_subscriber(control_key_expr())
+            .res()
+            .await
+        {
+            Ok(subscriber) => subscriber,
+            Err(e) => {
+                error!("Failed to subscribe to the control plane: {e}");
+                return;
+            }
+        };
+
+        while let Ok(sample) = subscriber.recv_async().await {
+            let key = sample.key_expr.as_str();
+            let payload = sample.value.payload.contiguous();
+            let my_type = state.data.my_type;
+
+            // NOTE: the messages are handled in their own task so a step waiting for its
+            //       pacing doesn’t delay the acks of the other scenes.
+            if key == control_step_key() && my_type == PartitionnerType::Runner {
+                match deserialize_auto::<Traced<StepRequest>>(&payload) {
+                    Ok(request) => {
+                        let correlation = request.correlation.unwrap_or_else(|| {
+                            StepCorrelation::root(request.message.scene, request.message.step_id)
+                        });
+                        tokio::spawn(traced_step(state.clone(), correlation, request.message));
+                    }
+                    Err(e) => error!("Failed to deserialize step request: {e}"),
+                }
+            } else if key == control_ack_key() && my_type == PartitionnerType::Master {
+                match deserialize_auto::<Traced<AckRequest>>(&payload) {
+                    Ok(request) => {
+                        let correlation = request.correlation.unwrap_or_else(|| {
+                            StepCorrelation::root(request.message.scene, request.message.step_id)
+                        });
+                        tokio::spawn(traced_ack(state.clone(), correlation, request.message));
+                    }
+                    Err(e) => error!("Failed to deserialize ack request: {e}"),
+                }
+            } else if key == control_client_input_key() && my_type != PartitionnerType::Runner {
+                match deserialize_auto::<ClientInputRequest>(&payload) {
+                    Ok(request) => state.push_client_input(request).await,
+                    Err(e) => error!("Failed to deserialize client input: {e}"),
+                }
             }
         }
     });
@@ -952,18 +3485,11 @@ pub async fn put_runner_message(
     uuid: Uuid,
     message: RunnerMessage,
 ) -> anyhow::Result<()> {
-    let message_str = serialize(&message)?;
-    // FIXME: declare the publisher only once.
-    //        The problem is that the publisher’s lifetime depends
-    //        on both the zenoh session, and the zenoh key, lifetimes.
-    let zenoh_key = runner_zenoh_commands_key(uuid);
-    let publisher = zenoh
-        .session
-        .declare_publisher(&zenoh_key)
-        .congestion_control(CongestionControl::Block)
-        .res()
+    let correlation = match &message {
+        RunnerMessage::Step { scene, step_id } => Some(StepCorrelation::current(*scene, *step_id)),
+        _ => None,
+    };
+    zenoh
+        .send_runner_command(uuid, Traced::new(correlation, message))
         .await
-        .unwrap();
-    publisher.put(message_str).res().await.unwrap();
-    Ok(())
 }
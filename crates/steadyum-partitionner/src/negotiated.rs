@@ -0,0 +1,48 @@
+use axum::async_trait;
+use axum::body::{Bytes, HttpBody};
+use axum::extract::FromRequest;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{BoxError, Json};
+use serde::de::DeserializeOwned;
+use steadyum_api_types::partitionner::BINCODE_CONTENT_TYPE;
+use steadyum_api_types::serialization::deserialize;
+
+/// Request body extractor accepting either JSON, or bincode if the request’s content type
+/// is [`BINCODE_CONTENT_TYPE`].
+pub struct Negotiated<T>(pub T);
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B> for Negotiated<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let is_bincode = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .map(|content_type| content_type == BINCODE_CONTENT_TYPE)
+            .unwrap_or(false);
+
+        if is_bincode {
+            let bytes = Bytes::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            let value = deserialize(&bytes)
+                .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()).into_response())?;
+            Ok(Negotiated(value))
+        } else {
+            let Json(value) = Json::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            Ok(Negotiated(value))
+        }
+    }
+}
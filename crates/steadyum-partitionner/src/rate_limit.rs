@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often the buckets idle for that long are dropped. A bucket idle for more than a second
+/// is full again, so dropping it changes nothing.
+const EVICTION_PERIOD: Duration = Duration::from_secs(10);
+
+/// A token bucket per key, e.g., per client, each accepting `rate` requests per second on
+/// average, and bursts of up to `rate` requests.
+pub struct RateLimiter<K> {
+    rate: f64,
+    buckets: Mutex<Buckets<K>>,
+}
+
+struct Buckets<K> {
+    by_key: HashMap<K, Bucket>,
+    last_eviction: Instant,
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    /// A limiter accepting `rate` requests per second for each key, or any number of them if
+    /// `rate` is zero.
+    pub fn new(rate: u32) -> Self {
+        Self {
+            rate: rate as f64,
+            buckets: Mutex::new(Buckets {
+                by_key: HashMap::new(),
+                last_eviction: Instant::now(),
+            }),
+        }
+    }
+
+    /// Returns `true` if the request of `key` can be handled, `false` if it must be rejected.
+    pub fn try_acquire(&self, key: K) -> bool {
+        if self.rate == 0.0 {
+            return true;
+        }
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let now = Instant::now();
+
+        if now.duration_since(buckets.last_eviction) >= EVICTION_PERIOD {
+            buckets
+                .by_key
+                .retain(|_, bucket| now.duration_since(bucket.last_refill) < EVICTION_PERIOD);
+            buckets.last_eviction = now;
+        }
+
+        let bucket = buckets.by_key.entry(key).or_insert(Bucket {
+            tokens: self.rate,
+            last_refill: now,
+        });
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.rate);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
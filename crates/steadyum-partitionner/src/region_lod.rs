@@ -0,0 +1,160 @@
+//! Steps the regions of a scene far from every viewer with cheaper solver options.
+
+use crate::{put_runner_message, AppState};
+use std::collections::{HashMap, HashSet};
+use steadyum_api_types::messages::{
+    ClientInterest, RunnerMessage, CLIENT_INTEREST_REFRESH_PERIOD, CLIENT_INTEREST_TIMEOUT,
+};
+use steadyum_api_types::partitionner::SceneUuid;
+use steadyum_api_types::serialization::deserialize_auto;
+use steadyum_api_types::simulation::SimulationBounds;
+use steadyum_api_types::zenoh::client_interests_key_expr;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+use zenoh::prelude::r#async::AsyncResolve;
+use zenoh::prelude::SplitBuffer;
+
+/// The far regions of a scene last sent to its runners.
+#[derive(PartialEq)]
+struct FarRegions {
+    regions: HashSet<SimulationBounds>,
+    runners: HashSet<Uuid>,
+}
+
+/// Tracks the views of the viewers of every scene, and tells the runners of the scenes with a
+/// [`RegionLod`](steadyum_api_types::partitionner::RegionLod) which of their regions are far
+/// from all of them.
+///
+/// The far regions of a scene are sent again whenever they, or the runners of the scene,
+/// change. The regions assigned since the last update are simulated with the full solver
+/// options until the next one.
+pub fn region_lod_loop(state: AppState) {
+    tokio::spawn(async move {
+        let subscriber = match state
+            .data
+            .zenoh
+            .session
+            .declare_subscriber(client_interests_key_expr())
+            .res()
+            .await
+        {
+            Ok(subscriber) => subscriber,
+            Err(e) => {
+                error!("Failed to subscribe to the client interests: {e}");
+                return;
+            }
+        };
+
+        let mut interests: HashMap<(SceneUuid, Uuid), (ClientInterest, Instant)> = HashMap::new();
+        let mut sent: HashMap<SceneUuid, FarRegions> = HashMap::new();
+        let mut refresh = tokio::time::interval(CLIENT_INTEREST_REFRESH_PERIOD);
+
+        loop {
+            tokio::select! {
+                sample = subscriber.recv_async() => {
+                    let Ok(sample) = sample else { break };
+                    let Some(key) = interest_key(sample.key_expr.as_str()) else {
+                        continue;
+                    };
+                    match deserialize_auto(&sample.value.payload.contiguous()) {
+                        Ok(interest) => {
+                            interests.insert(key, (interest, Instant::now()));
+                        }
+                        Err(e) => error!("Failed to deserialize client interest: {e}"),
+                    }
+                }
+                _ = refresh.tick() => {
+                    interests.retain(|_, (_, last_seen)| {
+                        last_seen.elapsed() < CLIENT_INTEREST_TIMEOUT
+                    });
+                    send_far_regions(&state, &interests, &mut sent).await;
+                }
+            }
+        }
+    });
+}
+
+/// The scene and the client of an interest, at the end of its key.
+fn interest_key(key: &str) -> Option<(SceneUuid, Uuid)> {
+    let mut chunks = key.rsplit('/');
+    let client = Uuid::parse_str(chunks.next()?).ok()?;
+    let scene = Uuid::parse_str(chunks.next()?).ok()?;
+    Some((SceneUuid(scene), client))
+}
+
+async fn send_far_regions(
+    state: &AppState,
+    interests: &HashMap<(SceneUuid, Uuid), (ClientInterest, Instant)>,
+    sent: &mut HashMap<SceneUuid, FarRegions>,
+) {
+    let lods: Vec<_> = state
+        .data
+        .scenes_metadata
+        .read()
+        .await
+        .iter()
+        .filter_map(|(scene, metadata)| Some((*scene, metadata.settings.region_lod?)))
+        .collect();
+    let mut scene_runners: HashMap<SceneUuid, HashSet<Uuid>> = {
+        let runners = state.data.runners.lock().await;
+        lods.iter()
+            .filter(|(scene, _)| !runners.exited.contains(scene))
+            .filter_map(|(scene, _)| {
+                let scene_runners = runners.per_node.get(scene)?;
+                Some((*scene, scene_runners.iter().map(|r| r.uuid).collect()))
+            })
+            .collect()
+    };
+    sent.retain(|scene, _| scene_runners.contains_key(scene));
+
+    for (scene, lod) in lods {
+        let Some(runners) = scene_runners.remove(&scene) else {
+            continue;
+        };
+        let views: Vec<_> = interests
+            .iter()
+            .filter(|((interest_scene, _), _)| *interest_scene == scene)
+            .map(|(_, (interest, _))| interest.view)
+            .collect();
+        let regions: HashSet<_> = state
+            .data
+            .assigned_runners
+            .iter()
+            .map(|entry| *entry.key())
+            .filter(|(region_scene, region)| {
+                *region_scene == scene && lod.is_far(region, views.iter())
+            })
+            .map(|(_, region)| region)
+            .collect();
+
+        let far_regions = FarRegions { regions, runners };
+        if sent.get(&scene) == Some(&far_regions) {
+            continue;
+        }
+
+        info!(
+            "{} far regions in scene {scene:?}, seen by {} viewers.",
+            far_regions.regions.len(),
+            views.len()
+        );
+        let mut all_sent = true;
+        for runner in &far_regions.runners {
+            let message = RunnerMessage::SetFarRegions {
+                scene,
+                regions: far_regions.regions.iter().copied().collect(),
+            };
+            if let Err(e) = put_runner_message(&state.data.zenoh, *runner, message).await {
+                warn!("Failed to send the far regions to runner {runner:?}: {e}");
+                all_sent = false;
+            }
+        }
+
+        // Retried at the next refresh.
+        if all_sent {
+            sent.insert(scene, far_regions);
+        } else {
+            sent.remove(&scene);
+        }
+    }
+}
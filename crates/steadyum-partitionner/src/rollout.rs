@@ -0,0 +1,268 @@
+//! The executables served by the master to the updaters of the nodes, and their staged
+//! rollout.
+
+use crate::negotiated::Negotiated;
+use crate::AppState;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use steadyum_api_types::env::CONFIG;
+use steadyum_api_types::partitionner::{
+    GetExesDeltaRequest, GetExesDeltaResponse, GetExesResponse, UpdateCheckRequest,
+    UpdateCheckResponse,
+};
+use steadyum_api_types::serialization::serialize;
+use tokio::sync::OnceCell;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+/// The partitionner executable served to the updaters, next to the master.
+const PARTITIONNER_EXE: &str = "steadyum-partitionner";
+/// Number of versions served before the current one that the updaters can get deltas from.
+const MAX_DELTA_BASES: usize = 4;
+
+/// The executables served to the updaters, reloaded whenever they change on disk.
+#[derive(Default)]
+pub struct Release {
+    /// The modification times of the runner and partitionner executables of `exes`, and of their
+    /// signatures.
+    modified: Option<Vec<Option<SystemTime>>>,
+    exes: Option<Arc<GetExesResponse>>,
+    /// The versions served before, most recent last.
+    previous: Vec<Arc<GetExesResponse>>,
+    /// The serialized deltas to `exes` from each previous version, computed once on demand.
+    deltas: HashMap<String, Arc<OnceCell<bytes::Bytes>>>,
+}
+
+impl Release {
+    pub async fn current(&mut self) -> anyhow::Result<Arc<GetExesResponse>> {
+        let runner_signature = signature_path(&CONFIG.runner_exe);
+        let partitionner_signature = signature_path(PARTITIONNER_EXE);
+        let mut modified = vec![];
+        for path in [
+            CONFIG.runner_exe.as_str(),
+            PARTITIONNER_EXE,
+            runner_signature.as_str(),
+            partitionner_signature.as_str(),
+        ] {
+            let metadata = tokio::fs::metadata(path).await;
+            modified.push(metadata.and_then(|m| m.modified()).ok());
+        }
+
+        if let Some(exes) = &self.exes {
+            if self.modified.as_ref() == Some(&modified) {
+                return Ok(exes.clone());
+            }
+        }
+
+        let runner = tokio::fs::read(&CONFIG.runner_exe).await?;
+        let partitionner = tokio::fs::read(PARTITIONNER_EXE).await?;
+        let version = Some(CONFIG.release_version.clone()).filter(|v| !v.is_empty());
+        let mut exes = GetExesResponse::new(runner, partitionner, version);
+        exes.runner_signature = read_signature(&runner_signature).await;
+        exes.partitionner_signature = read_signature(&partitionner_signature).await;
+        if !exes.is_signed() {
+            warn!("The executables aren’t signed, only updaters in dev mode will run them.");
+        }
+        let exes = Arc::new(exes);
+        info!("Serving version {} of the executables.", exes.version);
+
+        if let Some(old) = self.exes.take() {
+            if old.version != exes.version {
+                self.previous.retain(|prev| prev.version != exes.version);
+                self.previous.push(old);
+                if self.previous.len() > MAX_DELTA_BASES {
+                    self.previous.remove(0);
+                }
+                self.deltas.clear();
+            }
+        }
+
+        self.modified = Some(modified);
+        self.exes = Some(exes.clone());
+        Ok(exes)
+    }
+
+    /// The delta from the version `from` to the current one, if `from` was served before.
+    async fn delta(&mut self, from: &str) -> anyhow::Result<Option<ReleaseDelta>> {
+        let exes = self.current().await?;
+        let Some(base) = self.previous.iter().find(|prev| prev.version == from) else {
+            return Ok(None);
+        };
+        let serialized = self.deltas.entry(from.to_string()).or_default().clone();
+        Ok(Some(ReleaseDelta {
+            base: base.clone(),
+            exes,
+            serialized,
+        }))
+    }
+}
+
+/// The delta between two versions, computed by the first updater asking for it.
+struct ReleaseDelta {
+    base: Arc<GetExesResponse>,
+    exes: Arc<GetExesResponse>,
+    serialized: Arc<OnceCell<bytes::Bytes>>,
+}
+
+/// The signature of the executable `path`, written next to it by `steadyum-ctl sign`.
+fn signature_path(path: &str) -> String {
+    format!("{path}.sig")
+}
+
+async fn read_signature(path: &str) -> Option<String> {
+    let signature = tokio::fs::read_to_string(path).await.ok()?;
+    Some(signature.trim().to_string())
+}
+
+/// Updates `ROLLOUT_BATCH_SIZE` nodes at a time to the latest version, and only admits the
+/// next ones once the updated nodes answer their heartbeats again.
+///
+/// If an updated node doesn’t within `ROLLOUT_HEALTH_TIMEOUT_SECS`, the rollout halts until a
+/// new version is served.
+#[derive(Default)]
+pub struct Rollout {
+    version: String,
+    /// The nodes allowed to update that aren’t healthy on the new version yet, and since when.
+    pending: HashMap<String, Instant>,
+    halted: bool,
+}
+
+impl Rollout {
+    /// The version the node of `request` should run, given the `latest` one.
+    pub fn target(&mut self, latest: &str, request: &UpdateCheckRequest) -> String {
+        if self.version != latest {
+            info!("Rolling out version {latest}.");
+            *self = Rollout {
+                version: latest.to_string(),
+                ..Rollout::default()
+            };
+        }
+
+        let batch_size = CONFIG.rollout_batch_size;
+        let node = &request.node;
+        let Some(current) = &request.version else {
+            // A new node has no previous version to keep running.
+            return latest.to_string();
+        };
+
+        if batch_size == 0 {
+            return latest.to_string();
+        }
+
+        if current == latest {
+            if request.healthy && self.pending.remove(node).is_some() {
+                info!("Node {node} is healthy on version {latest}.");
+            }
+            self.check_timeouts();
+            return latest.to_string();
+        }
+
+        let admitted = self.pending.contains_key(node);
+        if self.halted || (!admitted && self.pending.len() >= batch_size) {
+            return current.clone();
+        }
+
+        if !admitted {
+            info!("Updating node {node} from version {current} to {latest}.");
+            self.pending.insert(node.clone(), Instant::now());
+        }
+        latest.to_string()
+    }
+
+    fn check_timeouts(&mut self) {
+        let timeout = Duration::from_secs(CONFIG.rollout_health_timeout_secs);
+        let late: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, since)| since.elapsed() > timeout)
+            .map(|(node, _)| node)
+            .collect();
+
+        if !self.halted && !late.is_empty() {
+            error!(
+                "Halting the rollout of version {}: nodes {late:?} aren’t healthy after the update.",
+                self.version
+            );
+            self.halted = true;
+        }
+    }
+}
+
+async fn current_release(state: &AppState) -> Result<Arc<GetExesResponse>, StatusCode> {
+    state
+        .data
+        .release
+        .lock()
+        .await
+        .current()
+        .await
+        .map_err(|e| {
+            error!("Failed to read the executables: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+pub async fn get_exes(State(state): State<AppState>) -> Result<bytes::Bytes, StatusCode> {
+    info!("Getting exes.");
+    let exes = current_release(&state).await?;
+    let result = serialize(&*exes).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(result.into())
+}
+
+/// The current executables as deltas from a version served before, so only what changed is
+/// downloaded.
+///
+/// Answers `NOT_FOUND` if that version isn’t known, the updater then downloads the whole
+/// executables with [`get_exes`].
+pub async fn get_exes_delta(
+    State(state): State<AppState>,
+    Negotiated(payload): Negotiated<GetExesDeltaRequest>,
+) -> Result<bytes::Bytes, StatusCode> {
+    let delta = state.data.release.lock().await.delta(&payload.from).await;
+    let ReleaseDelta {
+        base,
+        exes,
+        serialized,
+    } = match delta {
+        Ok(Some(delta)) => delta,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to read the executables: {e}");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let result = serialized
+        .get_or_try_init(move || async move {
+            info!(
+                "Computing the delta from version {} to {}.",
+                base.version, exes.version
+            );
+            let delta = tokio::task::spawn_blocking(move || {
+                serialize(&GetExesDeltaResponse::new(&base, &exes)?)
+            })
+            .await
+            .map_err(anyhow::Error::from)??;
+            Ok::<_, anyhow::Error>(bytes::Bytes::from(delta))
+        })
+        .await
+        .map_err(|e| {
+            error!("Failed to compute the delta: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(result.clone())
+}
+
+pub async fn update_check(
+    State(state): State<AppState>,
+    Negotiated(payload): Negotiated<UpdateCheckRequest>,
+) -> Result<Json<UpdateCheckResponse>, StatusCode> {
+    let latest = current_release(&state).await?.version.clone();
+    let version = state.data.rollout.lock().await.target(&latest, &payload);
+    Ok(Json(UpdateCheckResponse { version }))
+}
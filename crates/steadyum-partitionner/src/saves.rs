@@ -0,0 +1,236 @@
+//! Named saves of the scenes, stored in the state store so they can be shared by everyone
+//! using the cluster, or by every user of the same tenant.
+
+use crate::negotiated::Negotiated;
+use crate::tenants::Tenant;
+use crate::{
+    assign_joints, assign_particles, assign_vehicles, collect_scene_export, create_scene,
+    insert_bodies, unix_time, AppState,
+};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use steadyum_api_types::objects::{SceneSaveInfo, SceneSaveList};
+use steadyum_api_types::partitionner::{
+    is_valid_save_name, CreateSceneRequest, CreateSceneResponse, LoadSaveRequest, SaveSceneRequest,
+    SceneSave, SceneUuid,
+};
+use steadyum_api_types::rapier::parry::bounding_volume::BoundingVolume;
+use steadyum_api_types::store::{scene_save_key, SCENE_SAVES_KEY};
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// The request recreating `scene` as it is now, i.e., with its current time scale and solver
+/// options.
+async fn scene_creation_request(state: &AppState, scene: SceneUuid) -> Option<CreateSceneRequest> {
+    let metadata = state
+        .data
+        .scenes_metadata
+        .read()
+        .await
+        .get(&scene)
+        .cloned()?;
+    let bounds = state
+        .data
+        .scenes_geometries
+        .read()
+        .await
+        .get(&scene)?
+        .children_bounds
+        .iter()
+        .copied()
+        .reduce(|a, b| a.merged(&b))?;
+    let client_objects_encoding = *state
+        .data
+        .client_objects_encodings
+        .read()
+        .await
+        .get(&scene)?;
+
+    let mut settings = metadata.settings;
+    if let Some(scene_acks) = state.data.scenes_acks.read().await.get(&scene) {
+        settings.time_scale = *scene_acks.time_scale.read().await;
+    }
+
+    Some(CreateSceneRequest {
+        scene,
+        bounds,
+        client_objects_encoding,
+        seed: metadata.seed,
+        settings,
+    })
+}
+
+async fn read_saves(state: &AppState) -> Result<SceneSaveList, StatusCode> {
+    state
+        .data
+        .store
+        .get_value(SCENE_SAVES_KEY)
+        .await
+        .map(Option::unwrap_or_default)
+        .map_err(|e| {
+            error!("Failed to read the scene saves: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+pub async fn save_scene(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<SaveSceneRequest>,
+) -> Result<Json<SceneSaveInfo>, StatusCode> {
+    if !is_valid_save_name(&payload.name) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if !tenant.owns(&*state.data.scene_usages.read().await, payload.scene) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let scene = scene_creation_request(&state, payload.scene)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let export = collect_scene_export(&state, payload.scene).await?;
+
+    info!(
+        "Saving {} bodies of scene {:?} as {:?}.",
+        export.bodies.len(),
+        payload.scene,
+        payload.name
+    );
+
+    let info = SceneSaveInfo {
+        name: payload.name,
+        scene: payload.scene,
+        num_bodies: export.bodies.len(),
+        saved_at: unix_time(),
+        tenant: tenant.name().map(str::to_string),
+    };
+    let save = SceneSave {
+        info: info.clone(),
+        scene,
+        export,
+    };
+
+    let _guard = state.data.saves_lock.lock().await;
+    let mut saves = read_saves(&state).await?;
+    // The saves of all the tenants share the same names.
+    if saves
+        .saves
+        .iter()
+        .any(|save| save.name == info.name && !tenant.owns_save(save))
+    {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let store = &state.data.store;
+    store
+        .put_value(&scene_save_key(&info.name), &save)
+        .await
+        .map_err(|e| {
+            error!("Failed to store the scene save {:?}: {e}", info.name);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    saves.saves.retain(|save| save.name != info.name);
+    saves.saves.push(info.clone());
+    store
+        .put_value(SCENE_SAVES_KEY, &saves)
+        .await
+        .map_err(|e| {
+            error!("Failed to store the scene saves: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(info))
+}
+
+pub async fn load_save(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<LoadSaveRequest>,
+) -> Result<Json<CreateSceneResponse>, StatusCode> {
+    if !is_valid_save_name(&payload.name) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let save: SceneSave = state
+        .data
+        .store
+        .get_value(&scene_save_key(&payload.name))
+        .await
+        .map_err(|e| {
+            error!("Failed to read the scene save {:?}: {e}", payload.name);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    // The saves of the other tenants are hidden, like unknown saves.
+    if !tenant.owns_save(&save.info) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    info!(
+        "Loading the save {:?} of scene {:?} as scene {:?}.",
+        payload.name, save.info.scene, payload.scene
+    );
+
+    let request = CreateSceneRequest {
+        scene: payload.scene,
+        ..save.scene
+    };
+    let response = create_scene(State(state.clone()), tenant.clone(), Negotiated(request)).await?;
+
+    // The body uuids are global, so every scene loaded from the same save needs its own.
+    let mut bodies = save.export.bodies;
+    let mut new_uuids = HashMap::new();
+    for body in &mut bodies {
+        let uuid = Uuid::new_v4();
+        new_uuids.insert(body.uuid, uuid);
+        body.uuid = uuid;
+    }
+    let joints: Vec<_> = save
+        .export
+        .impulse_joints
+        .into_iter()
+        .filter_map(|mut joint| {
+            joint.body1 = *new_uuids.get(&joint.body1)?;
+            joint.body2 = *new_uuids.get(&joint.body2)?;
+            Some(joint)
+        })
+        .collect();
+
+    tenant.add_bodies(
+        &mut *state.data.scene_usages.write().await,
+        payload.scene,
+        bodies.len(),
+    )?;
+    insert_bodies(&state, payload.scene, bodies).await?;
+    if !joints.is_empty() {
+        assign_joints(&state, payload.scene, joints).await?;
+    }
+    if !save.export.particles.is_empty() {
+        assign_particles(&state, payload.scene, save.export.particles).await?;
+    }
+    let mut vehicles = save.export.vehicles;
+    if !vehicles.is_empty() {
+        for vehicle in &mut vehicles {
+            vehicle.chassis.uuid = Uuid::new_v4();
+        }
+        assign_vehicles(&state, payload.scene, vehicles).await?;
+    }
+
+    Ok(response)
+}
+
+pub async fn list_saves(
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<Json<SceneSaveList>, StatusCode> {
+    let mut saves = read_saves(&state).await?;
+    saves.saves.retain(|save| tenant.owns_save(save));
+    saves
+        .saves
+        .sort_by_key(|save| std::cmp::Reverse(save.saved_at));
+    Ok(Json(saves))
+}
@@ -0,0 +1,86 @@
+use crate::{insert_bodies, AppState};
+use steadyum_api_types::messages::{DespawnBodies, SpawnBodies};
+use steadyum_api_types::serialization::deserialize_auto;
+use steadyum_api_types::zenoh::{despawn_key_expr, spawn_key_expr};
+use tracing::{error, warn};
+use zenoh::prelude::r#async::AsyncResolve;
+use zenoh::prelude::SplitBuffer;
+
+/// Handles what the runners can’t in the spawn and despawn streams of the clients.
+///
+/// The static bodies, and the dynamic bodies spawned in regions not simulated yet, are inserted
+/// as if they were sent to the insertion endpoint. Despawned bodies are forgotten.
+pub fn spawn_loop(state: AppState) {
+    tokio::spawn(async move {
+        let spawn_subscriber = state
+            .data
+            .zenoh
+            .session
+            .declare_subscriber(spawn_key_expr())
+            .res()
+            .await;
+        let despawn_subscriber = state
+            .data
+            .zenoh
+            .session
+            .declare_subscriber(despawn_key_expr())
+            .res()
+            .await;
+        let (spawn_subscriber, despawn_subscriber) = match (spawn_subscriber, despawn_subscriber) {
+            (Ok(spawn), Ok(despawn)) => (spawn, despawn),
+            (Err(e), _) | (_, Err(e)) => {
+                error!("Failed to subscribe to the spawn streams: {e}");
+                return;
+            }
+        };
+
+        loop {
+            tokio::select! {
+                sample = spawn_subscriber.recv_async() => {
+                    let Ok(sample) = sample else { break };
+                    match deserialize_auto(&sample.value.payload.contiguous()) {
+                        Ok(spawn) => insert_missed_bodies(&state, spawn).await,
+                        Err(e) => error!("Failed to deserialize spawned bodies: {e}"),
+                    }
+                }
+                sample = despawn_subscriber.recv_async() => {
+                    let Ok(sample) = sample else { break };
+                    let despawn: DespawnBodies =
+                        match deserialize_auto(&sample.value.payload.contiguous()) {
+                            Ok(despawn) => despawn,
+                            Err(e) => {
+                                error!("Failed to deserialize despawned bodies: {e}");
+                                continue;
+                            }
+                        };
+                    let mut body_owners = state.data.body_owners.write().await;
+                    for body in despawn.bodies {
+                        body_owners.remove(&body);
+                    }
+                }
+            }
+        }
+    });
+}
+
+async fn insert_missed_bodies(state: &AppState, spawn: SpawnBodies) {
+    let is_simulated = state
+        .data
+        .assigned_runners
+        .contains_key(&(spawn.scene, spawn.region));
+    let mut bodies = spawn.bodies;
+
+    // The runner simulating the region already took the dynamic bodies.
+    if is_simulated {
+        bodies.retain(|body| !body.cold.moves_between_regions());
+    }
+
+    if !bodies.is_empty() {
+        if let Err(e) = insert_bodies(state, spawn.scene, bodies).await {
+            warn!(
+                "Failed to insert the bodies spawned in {:?}: {e}",
+                spawn.region
+            );
+        }
+    }
+}
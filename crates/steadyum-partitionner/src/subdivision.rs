@@ -0,0 +1,69 @@
+//! Splits the domain of a scene between the children of the master partitionner.
+
+use steadyum_api_types::rapier::math::{Point, Real};
+use steadyum_api_types::rapier::parry::bounding_volume::Aabb;
+
+/// Splits `domain` in one part per weight, k-d style: each cut is along the largest horizontal
+/// axis, and leaves on each side a share of the `bodies` proportional to the weights of its
+/// parts. Without bodies, the volume is shared instead.
+///
+/// The `bodies` are points, each standing for a number of bodies.
+pub fn subdivide_domain(
+    domain: Aabb,
+    weights: &[Real],
+    bodies: &[(Point<Real>, usize)],
+) -> Vec<Aabb> {
+    if weights.len() <= 1 {
+        return vec![domain];
+    }
+
+    let (left, right) = weights.split_at(weights.len() / 2);
+    let left_weight: Real = left.iter().sum();
+    let right_weight: Real = right.iter().sum();
+    let ratio = left_weight / (left_weight + right_weight);
+
+    let axis = domain.extents().xz().imax() * 2;
+    let split = split_coordinate(&domain, axis, ratio, bodies);
+    let mut left_domain = domain;
+    let mut right_domain = domain;
+    left_domain.maxs[axis] = split;
+    right_domain.mins[axis] = split;
+
+    let (left_bodies, right_bodies): (Vec<_>, Vec<_>) =
+        bodies.iter().copied().partition(|(pt, _)| pt[axis] < split);
+    let mut subdivisions = subdivide_domain(left_domain, left, &left_bodies);
+    subdivisions.extend(subdivide_domain(right_domain, right, &right_bodies));
+    subdivisions
+}
+
+/// The coordinate along `axis` leaving `ratio` of the bodies below it.
+fn split_coordinate(
+    domain: &Aabb,
+    axis: usize,
+    ratio: Real,
+    bodies: &[(Point<Real>, usize)],
+) -> Real {
+    let total: usize = bodies.iter().map(|(_, count)| count).sum();
+    if total == 0 {
+        return domain.mins[axis] + domain.extents()[axis] * ratio;
+    }
+
+    let mut sorted = bodies.to_vec();
+    sorted.sort_by(|a, b| a.0[axis].total_cmp(&b.0[axis]));
+
+    let target = total as Real * ratio;
+    let mut below = 0;
+    for (i, (pt, count)) in sorted.iter().enumerate() {
+        below += count;
+        if below as Real >= target {
+            // Cut halfway to the next point.
+            let next = sorted
+                .get(i + 1)
+                .map(|(next, _)| next[axis])
+                .unwrap_or(domain.maxs[axis]);
+            return ((pt[axis] + next) / 2.0).clamp(domain.mins[axis], domain.maxs[axis]);
+        }
+    }
+
+    domain.maxs[axis]
+}
@@ -0,0 +1,259 @@
+//! Tenants sharing a cluster, identified by the API key of their requests, and the
+//! cluster-internal requests, identified by the `CLUSTER_KEY`.
+
+use crate::{AppState, PartitionnerType};
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use steadyum_api_types::env::CONFIG;
+use steadyum_api_types::objects::SceneSaveInfo;
+use steadyum_api_types::partitionner::{SceneUuid, API_KEY_HEADER, CLUSTER_KEY_HEADER};
+
+/// A tenant of the `TENANTS_FILE`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TenantConfig {
+    pub name: String,
+    pub api_key: String,
+    #[serde(default)]
+    pub quotas: TenantQuotas,
+}
+
+/// The limits of a tenant, over all its scenes. `None` means unlimited.
+#[derive(Copy, Clone, Debug, Default, Deserialize)]
+pub struct TenantQuotas {
+    pub max_scenes: Option<usize>,
+    /// Counts the bodies inserted into the scenes, removing bodies doesn’t free the quota.
+    pub max_bodies: Option<usize>,
+    /// Each scene is simulated by one runner per child partitionner.
+    pub max_runners: Option<usize>,
+}
+
+/// The tenants of the `TENANTS_FILE`, by API key.
+#[derive(Default)]
+pub struct Tenants {
+    by_api_key: HashMap<String, Arc<TenantConfig>>,
+}
+
+impl Tenants {
+    /// Reads the JSON array of tenants of the file at `path`, or none if `path` is empty.
+    ///
+    /// Fails without a `CLUSTER_KEY`, which would leave the cluster-internal endpoints open.
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        if path.is_empty() {
+            return Ok(Self::default());
+        }
+
+        if CONFIG.cluster_key.is_empty() {
+            anyhow::bail!("TENANTS_FILE requires a CLUSTER_KEY.");
+        }
+
+        let tenants: Vec<TenantConfig> = serde_json::from_slice(&std::fs::read(path)?)?;
+        Ok(Self {
+            by_api_key: tenants
+                .into_iter()
+                .map(|tenant| (tenant.api_key.clone(), Arc::new(tenant)))
+                .collect(),
+        })
+    }
+
+    /// Without tenants, every request is accepted.
+    pub fn is_enabled(&self) -> bool {
+        !self.by_api_key.is_empty()
+    }
+}
+
+/// What a scene counts against the quotas of its tenant.
+#[derive(Clone, Debug)]
+pub struct SceneUsage {
+    pub tenant: String,
+    pub runners: usize,
+    pub bodies: usize,
+}
+
+/// The tenant a request was sent by, from its [`API_KEY_HEADER`].
+///
+/// Without tenants, or for the cluster-internal requests, the requests are unrestricted. A
+/// child partitionner is only reached by the master and the runners, so it only accepts the
+/// cluster-internal requests.
+#[derive(Clone, Debug)]
+pub struct Tenant(Option<Arc<TenantConfig>>);
+
+impl Tenant {
+    pub fn unrestricted() -> Self {
+        Self(None)
+    }
+
+    /// Is this request sent by the master partitionner, or to a cluster without tenants?
+    pub fn is_unrestricted(&self) -> bool {
+        self.0.is_none()
+    }
+
+    /// The name of this tenant, none if unrestricted.
+    pub fn name(&self) -> Option<&str> {
+        self.0.as_ref().map(|config| config.name.as_str())
+    }
+
+    /// Can this tenant see and load `save`?
+    pub fn owns_save(&self, save: &SceneSaveInfo) -> bool {
+        self.is_unrestricted() || save.tenant.as_deref() == self.name()
+    }
+
+    /// Fails with `NOT_FOUND`, like for an unknown scene, unless this tenant owns `scene`.
+    pub async fn check_scene(&self, state: &AppState, scene: SceneUuid) -> Result<(), StatusCode> {
+        if self.owns(&*state.data.scene_usages.read().await, scene) {
+            Ok(())
+        } else {
+            Err(StatusCode::NOT_FOUND)
+        }
+    }
+
+    /// Can this tenant see and modify `scene`?
+    pub fn owns(&self, usages: &HashMap<SceneUuid, SceneUsage>, scene: SceneUuid) -> bool {
+        match &self.0 {
+            None => true,
+            Some(config) => usages
+                .get(&scene)
+                .map(|usage| usage.tenant == config.name)
+                .unwrap_or(false),
+        }
+    }
+
+    /// Records the new `scene`, simulated by `runners` runners, if it fits in the quotas.
+    pub fn add_scene(
+        &self,
+        usages: &mut HashMap<SceneUuid, SceneUsage>,
+        scene: SceneUuid,
+        runners: usize,
+    ) -> Result<(), StatusCode> {
+        let Some(config) = &self.0 else {
+            return Ok(());
+        };
+
+        let (num_scenes, num_runners) = usages
+            .values()
+            .filter(|usage| usage.tenant == config.name)
+            .fold((0, 0), |(num_scenes, num_runners), usage| {
+                (num_scenes + 1, num_runners + usage.runners)
+            });
+
+        if exceeds(config.quotas.max_scenes, num_scenes + 1)
+            || exceeds(config.quotas.max_runners, num_runners + runners)
+        {
+            return Err(StatusCode::FORBIDDEN);
+        }
+
+        usages.insert(
+            scene,
+            SceneUsage {
+                tenant: config.name.clone(),
+                runners,
+                bodies: 0,
+            },
+        );
+        Ok(())
+    }
+
+    /// Counts `num_bodies` more bodies in `scene`, if they fit in the quotas.
+    pub fn add_bodies(
+        &self,
+        usages: &mut HashMap<SceneUuid, SceneUsage>,
+        scene: SceneUuid,
+        num_bodies: usize,
+    ) -> Result<(), StatusCode> {
+        let Some(config) = &self.0 else {
+            return Ok(());
+        };
+
+        if !self.owns(usages, scene) {
+            return Err(StatusCode::NOT_FOUND);
+        }
+
+        let total: usize = usages
+            .values()
+            .filter(|usage| usage.tenant == config.name)
+            .map(|usage| usage.bodies)
+            .sum();
+
+        if exceeds(config.quotas.max_bodies, total + num_bodies) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+
+        if let Some(usage) = usages.get_mut(&scene) {
+            usage.bodies += num_bodies;
+        }
+        Ok(())
+    }
+}
+
+fn exceeds(max: Option<usize>, value: usize) -> bool {
+    max.map(|max| value > max).unwrap_or(false)
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Tenant {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let tenants = &state.data.tenants;
+
+        if state.data.my_type == PartitionnerType::Runner {
+            return ClusterMember::from_request_parts(parts, state)
+                .await
+                .map(|_| Self::unrestricted());
+        }
+
+        if !tenants.is_enabled() || is_cluster_member(parts) {
+            return Ok(Self::unrestricted());
+        }
+
+        let api_key = parts
+            .headers
+            .get(API_KEY_HEADER)
+            .and_then(|api_key| api_key.to_str().ok())
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        let config = tenants
+            .by_api_key
+            .get(api_key)
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        Ok(Self(Some(config.clone())))
+    }
+}
+
+/// A request sent by a partitionner or a runner of the cluster, with the `CLUSTER_KEY` in its
+/// [`CLUSTER_KEY_HEADER`].
+///
+/// Every request is a cluster member without `CLUSTER_KEY`.
+#[derive(Copy, Clone, Debug)]
+pub struct ClusterMember;
+
+fn is_cluster_member(parts: &Parts) -> bool {
+    CONFIG.cluster_key.is_empty()
+        || parts
+            .headers
+            .get(CLUSTER_KEY_HEADER)
+            .map(|cluster_key| cluster_key.as_bytes() == CONFIG.cluster_key.as_bytes())
+            .unwrap_or(false)
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClusterMember {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        if is_cluster_member(parts) {
+            Ok(Self)
+        } else {
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+}
@@ -0,0 +1,466 @@
+//! Moves regions from a child partitionner to another while the simulation runs, e.g., to
+//! decommission a node or to take load off it.
+
+use crate::negotiated::Negotiated;
+use crate::tenants::Tenant;
+use crate::{put_runner_message, traced_step, AppState, PartitionnerType, EXPORT_TIMEOUT};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use steadyum_api_types::messages::RunnerMessage;
+use steadyum_api_types::partitionner::{
+    DrainNodeRequest, DrainNodeResponse, SceneExport, SceneUuid, StepRequest,
+    TransferRegionsRequest, TransferRegionsResponse,
+};
+use steadyum_api_types::rapier::parry::bounding_volume::{Aabb, BoundingVolume};
+use steadyum_api_types::rapier::parry::query::PointQuery;
+use steadyum_api_types::rapier::parry::shape::Cuboid;
+use steadyum_api_types::serialization::deserialize_auto;
+use steadyum_api_types::simulation::SimulationBounds;
+use steadyum_api_types::telemetry::StepCorrelation;
+use steadyum_api_types::zenoh::scene_export_key_expr;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+use zenoh::prelude::r#async::AsyncResolve;
+use zenoh::prelude::SplitBuffer;
+
+/// Maximum time waited for the children to ack the step in progress before moving regions.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+const DRAIN_POLL_PERIOD: Duration = Duration::from_millis(10);
+
+/// Holds back the steps of a scene while its regions move.
+#[derive(Default)]
+pub struct Drain {
+    pub active: bool,
+    /// The step that should have started during the drain, started once it ends.
+    pub withheld_step: Option<u64>,
+}
+
+/// Holds back the next steps of `scene`, and waits for the children to ack the current one.
+async fn drain(state: &AppState, scene: SceneUuid) -> Result<(), StatusCode> {
+    {
+        let scenes_acks = state.data.scenes_acks.read().await;
+        let scene_acks = scenes_acks.get(&scene).ok_or(StatusCode::NOT_FOUND)?;
+        let mut drain = scene_acks.drain.lock().await;
+        if drain.active {
+            // Another transfer is in progress.
+            return Err(StatusCode::CONFLICT);
+        }
+        drain.active = true;
+    }
+
+    let deadline = Instant::now() + DRAIN_TIMEOUT;
+    loop {
+        // Don’t hold the lock while sleeping, the acks need it.
+        let remaining = match state.data.scenes_acks.read().await.get(&scene) {
+            Some(scene_acks) => scene_acks.acks.lock().await.remaining(),
+            None => return Err(StatusCode::NOT_FOUND),
+        };
+
+        if remaining == 0 {
+            return Ok(());
+        }
+
+        if Instant::now() > deadline {
+            warn!(
+                "Step of {:?} still waiting for {} acks, giving up the transfer.",
+                scene, remaining
+            );
+            resume(state, scene).await;
+            return Err(StatusCode::GATEWAY_TIMEOUT);
+        }
+
+        tokio::time::sleep(DRAIN_POLL_PERIOD).await;
+    }
+}
+
+/// Lets `scene` step again, starting the step withheld while it was drained.
+async fn resume(state: &AppState, scene: SceneUuid) {
+    let withheld_step = {
+        let scenes_acks = state.data.scenes_acks.read().await;
+        let Some(scene_acks) = scenes_acks.get(&scene) else {
+            return;
+        };
+        let mut drain = scene_acks.drain.lock().await;
+        drain.active = false;
+        drain.withheld_step.take()
+    };
+
+    if let Some(step_id) = withheld_step {
+        tokio::spawn(traced_step(
+            state.clone(),
+            StepCorrelation::root(scene, step_id),
+            StepRequest { scene, step_id },
+        ));
+    }
+}
+
+/// Moves regions of a scene from a child partitionner to another.
+///
+/// The scene stops stepping during the transfer. Each region is exported by its runner, which
+/// then forwards the islands still sent to it, and imported by the runner of the other child.
+pub async fn transfer_regions(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<TransferRegionsRequest>,
+) -> Result<Json<TransferRegionsResponse>, StatusCode> {
+    if state.data.my_type != PartitionnerType::Master {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if !tenant.owns(&*state.data.scene_usages.read().await, payload.scene) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let scene = payload.scene;
+    let (from, to) = {
+        let children_endpoints = state.data.children_endpoints.lock().await;
+        let position = |child| children_endpoints.iter().position(|c| c == child);
+        match (position(&payload.from), position(&payload.to)) {
+            (Some(from), Some(to)) if from != to => (from, to),
+            _ => return Err(StatusCode::BAD_REQUEST),
+        }
+    };
+    let (from_runner, to_runner) = {
+        let runners = state.data.runners.lock().await;
+        let node_runners = runners.per_node.get(&scene).ok_or(StatusCode::NOT_FOUND)?;
+        match (node_runners.get(from), node_runners.get(to)) {
+            (Some(from), Some(to)) => (from.uuid, to.uuid),
+            // The child registered after the scene was created.
+            _ => return Err(StatusCode::BAD_REQUEST),
+        }
+    };
+
+    drain(&state, scene).await?;
+
+    if payload.regions.is_empty() {
+        // The new regions of `from` go to `to` too.
+        if let Some(geometry) = state.data.scenes_geometries.write().await.get_mut(&scene) {
+            geometry.redirects.insert(from, to);
+            geometry.redirects.remove(&to);
+        }
+    }
+
+    let regions: Vec<_> = state
+        .data
+        .assigned_runners
+        .iter()
+        .filter(|entry| entry.key().0 == scene && *entry.value() == from_runner)
+        .map(|entry| entry.key().1)
+        .filter(|region| payload.regions.is_empty() || payload.regions.contains(region))
+        .collect();
+
+    info!(
+        "Transferring {} regions of {:?} from child {} to {}.",
+        regions.len(),
+        scene,
+        from,
+        to
+    );
+    let result = hand_over_regions(&state, scene, regions, from_runner, to_runner).await;
+    resume(&state, scene).await;
+
+    Ok(Json(TransferRegionsResponse { regions: result? }))
+}
+
+/// Moves the `regions` of the runner `from_runner` to `to_runner`.
+async fn hand_over_regions(
+    state: &AppState,
+    scene: SceneUuid,
+    regions: Vec<SimulationBounds>,
+    from_runner: Uuid,
+    to_runner: Uuid,
+) -> Result<Vec<SimulationBounds>, StatusCode> {
+    if regions.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let zenoh = &state.data.zenoh;
+    let send = |runner: Uuid, message: RunnerMessage| async move {
+        put_runner_message(zenoh, runner, message)
+            .await
+            .map_err(|e| {
+                error!("Failed to message runner {runner:?}: {e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })
+    };
+
+    let static_bodies = state
+        .data
+        .static_bodies
+        .read()
+        .await
+        .get(&scene)
+        .cloned()
+        .unwrap_or_default();
+    let message = RunnerMessage::AssignStaticBodies {
+        scene,
+        bodies: static_bodies,
+    };
+    send(to_runner, message).await?;
+
+    let mut moved = vec![];
+
+    for region in regions {
+        // Subscribe before asking the runner, so we don’t miss its answer.
+        let request = Uuid::new_v4();
+        let subscriber = zenoh
+            .session
+            .declare_subscriber(scene_export_key_expr(request))
+            .res_async()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let message = RunnerMessage::HandOver {
+            scene,
+            region,
+            request,
+            target: to_runner,
+        };
+        send(from_runner, message).await?;
+
+        let export: SceneExport =
+            match tokio::time::timeout(EXPORT_TIMEOUT, subscriber.recv_async()).await {
+                Ok(Ok(sample)) => {
+                    deserialize_auto(&sample.value.payload.contiguous()).map_err(|e| {
+                        error!("Failed to decode region export: {e}");
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    })?
+                }
+                _ => {
+                    error!(
+                        "Region {:?}::{:?} wasn’t handed over, its bodies may be lost.",
+                        scene, region
+                    );
+                    return Err(StatusCode::BAD_GATEWAY);
+                }
+            };
+
+        // The static bodies were sent already.
+        let bodies = export
+            .bodies
+            .into_iter()
+            .filter(|body| body.cold.moves_between_regions())
+            .collect();
+        let message = RunnerMessage::TakeOver {
+            scene,
+            region,
+            bodies,
+            impulse_joints: export.impulse_joints,
+        };
+        send(to_runner, message).await?;
+        if !export.particles.is_empty() {
+            let message = RunnerMessage::AssignParticles {
+                scene,
+                region,
+                particles: export.particles,
+            };
+            send(to_runner, message).await?;
+        }
+        if !export.vehicles.is_empty() {
+            let message = RunnerMessage::AssignVehicles {
+                scene,
+                region,
+                vehicles: export.vehicles,
+            };
+            send(to_runner, message).await?;
+        }
+        send(to_runner, RunnerMessage::SyncClientObjects { scene }).await?;
+
+        state
+            .data
+            .assigned_runners
+            .insert((scene, region), to_runner);
+        info!(
+            "Moved region {:?}::{:?} to runner {:?}.",
+            scene, region, to_runner
+        );
+        moved.push(region);
+    }
+
+    Ok(moved)
+}
+
+/// The child taking the regions of the child `id` when it leaves: the one with the closest
+/// bounds.
+fn heir(children_bounds: &[Aabb], id: usize) -> Option<usize> {
+    let center = children_bounds.get(id)?.center();
+    let distance = |child: &Aabb| {
+        let child_cuboid = Cuboid::new(child.half_extents());
+        child_cuboid.distance_to_point(&child.center().into(), &center, true)
+    };
+    children_bounds
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != id)
+        .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+        .map(|(i, _)| i)
+}
+
+/// Moves all the regions of a child partitionner to the other children, then deregisters it,
+/// so its node can be stopped without stopping the simulations.
+///
+/// Every scene stops stepping until the child is deregistered. The regions of each scene go to
+/// the child whose part of the scene is the closest, and that child takes over its part of the
+/// scene too.
+pub async fn drain_node(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Negotiated(payload): Negotiated<DrainNodeRequest>,
+) -> Result<Json<DrainNodeResponse>, StatusCode> {
+    // Draining a node moves the regions of every tenant.
+    if !tenant.is_unrestricted() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    match state.data.my_type {
+        PartitionnerType::Master => {}
+        PartitionnerType::Runner => {
+            // The master deregistered us, and our runners exited all their scenes.
+            info!("Decommissioned by the master partitionner.");
+            state.data.decommissioned.store(true, Ordering::SeqCst);
+            return Ok(Json(DrainNodeResponse { regions: 0 }));
+        }
+        PartitionnerType::Dev => return Err(StatusCode::BAD_REQUEST),
+    }
+
+    let (id, child_server) = {
+        let children = state.data.children.lock().await;
+        let children_endpoints = state.data.children_endpoints.lock().await;
+        let id = children_endpoints
+            .iter()
+            .position(|c| *c == payload.child)
+            .ok_or(StatusCode::NOT_FOUND)?;
+        if children.len() <= 1 {
+            // Nobody to move the regions to.
+            return Err(StatusCode::CONFLICT);
+        }
+        (id, children[id].clone())
+    };
+
+    let scenes: Vec<_> = state
+        .data
+        .runners
+        .lock()
+        .await
+        .per_node
+        .keys()
+        .copied()
+        .collect();
+    let mut drained = vec![];
+    let mut num_moved = 0;
+
+    for scene in scenes {
+        if let Err(e) = drain(&state, scene).await {
+            resume_all(&state, &drained).await;
+            return Err(e);
+        }
+
+        let heir = state
+            .data
+            .scenes_geometries
+            .read()
+            .await
+            .get(&scene)
+            .and_then(|geometry| heir(&geometry.children_bounds, id));
+        drained.push((scene, heir));
+        let Some(heir) = heir else {
+            continue;
+        };
+
+        let runners = {
+            let runners = state.data.runners.lock().await;
+            let node_runners = runners.per_node.get(&scene);
+            node_runners.and_then(|r| Some((r.get(id)?.uuid, r.get(heir)?.uuid)))
+        };
+        let Some((from_runner, to_runner)) = runners else {
+            continue;
+        };
+
+        let regions: Vec<_> = state
+            .data
+            .assigned_runners
+            .iter()
+            .filter(|entry| entry.key().0 == scene && *entry.value() == from_runner)
+            .map(|entry| entry.key().1)
+            .collect();
+        info!(
+            "Moving {} regions of {:?} from child {} to {}.",
+            regions.len(),
+            scene,
+            id,
+            heir
+        );
+
+        match hand_over_regions(&state, scene, regions, from_runner, to_runner).await {
+            Ok(moved) => num_moved += moved.len(),
+            Err(e) => {
+                resume_all(&state, &drained).await;
+                return Err(e);
+            }
+        }
+    }
+
+    deregister_child(&state, id, &drained).await;
+
+    // With the zenoh control plane, the child would keep acking the steps.
+    if let Err(e) = child_server.drain_node(payload.child.clone()).await {
+        warn!("Failed to notify the decommissioned child: {e}");
+    }
+
+    resume_all(&state, &drained).await;
+    info!(
+        "Child {}:{} drained, {} regions moved.",
+        payload.child.addr, payload.child.port, num_moved
+    );
+    Ok(Json(DrainNodeResponse { regions: num_moved }))
+}
+
+async fn resume_all(state: &AppState, scenes: &[(SceneUuid, Option<usize>)]) {
+    for (scene, _) in scenes {
+        resume(state, *scene).await;
+    }
+}
+
+/// Removes the child `id`, whose regions moved to the given heir in each scene.
+async fn deregister_child(state: &AppState, id: usize, heirs: &[(SceneUuid, Option<usize>)]) {
+    let mut runners = state.data.runners.lock().await;
+    state.data.children.lock().await.remove(id);
+    state.data.children_endpoints.lock().await.remove(id);
+    state.data.children_capabilities.lock().await.remove(id);
+
+    // The indices of the children after `id` shift.
+    let shift = |child: usize| if child > id { child - 1 } else { child };
+    let mut geometries = state.data.scenes_geometries.write().await;
+
+    for (scene, heir) in heirs {
+        if let Some(node_runners) = runners.per_node.get_mut(scene) {
+            if id < node_runners.len() {
+                let runner = node_runners.remove(id);
+                let message = RunnerMessage::Exit { scene: *scene };
+                if let Err(e) = put_runner_message(&state.data.zenoh, runner.uuid, message).await {
+                    warn!("Failed to stop runner {:?}: {e}", runner.uuid);
+                }
+            }
+        }
+
+        let (Some(geometry), Some(heir)) = (geometries.get_mut(scene), *heir) else {
+            continue;
+        };
+
+        // The heir takes the part of the scene of the child, so it gets its new regions.
+        let bounds = geometry.children_bounds.remove(id);
+        let heir = shift(heir);
+        geometry.children_bounds[heir].merge(&bounds);
+        geometry.redirects = geometry
+            .redirects
+            .iter()
+            .filter(|(from, _)| **from != id)
+            .map(|(from, to)| (shift(*from), if *to == id { heir } else { shift(*to) }))
+            .filter(|(from, to)| from != to)
+            .collect();
+    }
+}
@@ -0,0 +1,507 @@
+//! Python bindings for scripting a steadyum cluster.
+//!
+//! The `Cluster` class wraps a [`SteadyumClient`]. Its methods block until the partitionner
+//! replies, releasing the GIL meanwhile. Bodies and poses are exchanged as numpy arrays with
+//! one row per body.
+
+use numpy::ndarray::{Array1, Array2};
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyReadonlyArray1, PyReadonlyArray2};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use steadyum_api_types::characters::CharacterParams;
+use steadyum_api_types::messages::BodyAssignment;
+use steadyum_api_types::objects::{ColdBodyObject, WarmBodyObject};
+use steadyum_api_types::partitionner::{SceneSettings, SceneUuid, SimulationParams, TimeScale};
+use steadyum_api_types::rapier::geometry::{Ball, Cuboid, SharedShape};
+use steadyum_api_types::rapier::math::{Isometry, Point, Real, Vector, DIM};
+use steadyum_api_types::rapier::prelude::{Aabb, ColliderBuilder, RigidBodyBuilder, RigidBodyType};
+use steadyum_api_types::region_db::AsyncPartitionnerServer;
+use steadyum_api_types::serialization::ObjectSetEncoding;
+use steadyum_client::SteadyumClient;
+use uuid::Uuid;
+
+/// Number of bodies sent to the partitionner per request by `Cluster.insert_bodies`.
+const INSERT_CHUNK_SIZE: usize = 1024;
+
+/// Number of columns of the rotations returned by `Cluster.fetch_poses`: an angle in 2D, a
+/// `(x, y, z, w)` quaternion in 3D.
+#[cfg(feature = "dim2")]
+const ROTATION_DIM: usize = 1;
+#[cfg(feature = "dim3")]
+const ROTATION_DIM: usize = 4;
+
+#[pymodule]
+fn steadyum(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<Cluster>()?;
+    Ok(())
+}
+
+/// A connection to the partitionner of a cluster.
+#[pyclass]
+struct Cluster {
+    client: SteadyumClient,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl Cluster {
+    fn block_on<T: Send>(
+        &self,
+        py: Python,
+        task: impl std::future::Future<Output = anyhow::Result<T>> + Send,
+    ) -> PyResult<T> {
+        Ok(py.allow_threads(|| self.runtime.block_on(task))?)
+    }
+}
+
+#[pymethods]
+impl Cluster {
+    /// Connects to the partitionner at `addr:port`, or to the one configured by the
+    /// `PARTITIONNER_ADDR` and `PARTITIONNER_PORT` environment variables.
+    ///
+    /// The requests are sent with `api_key`, or the `API_KEY` environment variable, to
+    /// identify the tenant of the cluster they belong to.
+    #[new]
+    #[pyo3(signature = (addr = None, port = None, api_key = None))]
+    fn new(addr: Option<String>, port: Option<u16>, api_key: Option<String>) -> PyResult<Self> {
+        let partitionner = match addr {
+            Some(addr) => {
+                let port = port.unwrap_or(steadyum_api_types::env::CONFIG.partitionner_port);
+                AsyncPartitionnerServer::with_endpoint(addr, port)?
+            }
+            None => AsyncPartitionnerServer::new()?,
+        };
+        let partitionner = match api_key {
+            Some(api_key) => partitionner.api_key(api_key),
+            None => partitionner,
+        };
+        let client = SteadyumClient::from_partitionner(partitionner);
+        let runtime = tokio::runtime::Runtime::new()?;
+        Ok(Self { client, runtime })
+    }
+
+    /// Creates a scene spanning the box from `mins` to `maxs`, and returns its uuid.
+    ///
+    /// `name` is only displayed in the scene lists.
+    #[pyo3(signature = (mins, maxs, seed = 0, quantized = false, name = String::new()))]
+    fn create_scene(
+        &self,
+        py: Python,
+        mins: Vec<Real>,
+        maxs: Vec<Real>,
+        seed: u64,
+        quantized: bool,
+        name: String,
+    ) -> PyResult<String> {
+        let bounds = Aabb::new(point(&mins)?, point(&maxs)?);
+        let encoding = if quantized {
+            ObjectSetEncoding::Quantized
+        } else {
+            ObjectSetEncoding::Full
+        };
+        let scene = SceneUuid(Uuid::new_v4());
+        let settings = SceneSettings {
+            name,
+            ..SceneSettings::default()
+        };
+        self.block_on(
+            py,
+            self.client
+                .create_scene(scene, bounds, encoding, seed, settings),
+        )?;
+        Ok(scene.0.to_string())
+    }
+
+    fn list_scenes(&self, py: Python) -> PyResult<Vec<String>> {
+        let list = self.block_on(py, self.client.list_scenes())?;
+        Ok(list
+            .scenes
+            .iter()
+            .map(|scene| scene.uuid.0.to_string())
+            .collect())
+    }
+
+    fn remove_scene(&self, py: Python, scene: &str) -> PyResult<()> {
+        self.block_on(py, self.client.remove_scene(scene_uuid(scene)?))
+    }
+
+    /// Saves the current state of `scene` under `name` on the cluster, replacing any previous
+    /// save of that name.
+    fn save_scene(&self, py: Python, scene: &str, name: String) -> PyResult<()> {
+        self.block_on(py, self.client.save_scene(scene_uuid(scene)?, name))?;
+        Ok(())
+    }
+
+    /// Creates a new scene from the save `name`, and returns its uuid.
+    fn load_save(&self, py: Python, name: String) -> PyResult<String> {
+        let scene = SceneUuid(Uuid::new_v4());
+        self.block_on(py, self.client.load_save(name, scene))?;
+        Ok(scene.0.to_string())
+    }
+
+    /// The names of the scene saves, most recent first.
+    fn list_saves(&self, py: Python) -> PyResult<Vec<String>> {
+        let list = self.block_on(py, self.client.list_saves())?;
+        Ok(list.saves.into_iter().map(|save| save.name).collect())
+    }
+
+    /// Inserts one body per row of `positions`, and returns their uuids.
+    ///
+    /// `sizes` are the radii of the balls, or the half-widths of the cubes. `shape` is `"ball"`
+    /// or `"cuboid"`, `body_type` is `"dynamic"`, `"fixed"` or `"kinematic"`.
+    ///
+    /// With `keys`, one per row, the uuids are derived from the keys: inserting the same keys
+    /// again doesn’t duplicate the bodies.
+    #[pyo3(signature = (
+        scene,
+        positions,
+        sizes,
+        shape = "ball",
+        body_type = "dynamic",
+        density = 1.0,
+        velocities = None,
+        keys = None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn insert_bodies(
+        &self,
+        py: Python,
+        scene: &str,
+        positions: PyReadonlyArray2<Real>,
+        sizes: PyReadonlyArray1<Real>,
+        shape: &str,
+        body_type: &str,
+        density: Real,
+        velocities: Option<PyReadonlyArray2<Real>>,
+        keys: Option<Vec<String>>,
+    ) -> PyResult<Vec<String>> {
+        let scene = scene_uuid(scene)?;
+        let positions = positions.as_array();
+        let sizes = sizes.as_array();
+        let velocities = velocities.as_ref().map(|velocities| velocities.as_array());
+        let body_type = match body_type {
+            "dynamic" => RigidBodyType::Dynamic,
+            "fixed" => RigidBodyType::Fixed,
+            "kinematic" => RigidBodyType::KinematicVelocityBased,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "Unknown body type: {body_type}."
+                )))
+            }
+        };
+
+        if positions.ncols() != DIM || sizes.len() != positions.nrows() {
+            return Err(PyValueError::new_err(format!(
+                "Expected {DIM} columns of positions and one size per position."
+            )));
+        }
+        if let Some(velocities) = &velocities {
+            if velocities.dim() != positions.dim() {
+                return Err(PyValueError::new_err("Expected one velocity per position."));
+            }
+        }
+        if let Some(keys) = &keys {
+            if keys.len() != positions.nrows() {
+                return Err(PyValueError::new_err("Expected one key per position."));
+            }
+        }
+
+        let mut bodies = Vec::with_capacity(positions.nrows());
+        for (i, (position, size)) in positions.rows().into_iter().zip(sizes.iter()).enumerate() {
+            let shape = match shape {
+                "ball" => SharedShape::new(Ball::new(*size)),
+                "cuboid" => SharedShape::new(Cuboid::new(Vector::repeat(*size))),
+                _ => return Err(PyValueError::new_err(format!("Unknown shape: {shape}."))),
+            };
+            let linvel = velocities
+                .as_ref()
+                .map(|velocities| Vector::from_iterator(velocities.row(i).iter().copied()))
+                .unwrap_or_else(Vector::zeros);
+            let body = RigidBodyBuilder::new(body_type)
+                .translation(Vector::from_iterator(position.iter().copied()))
+                .linvel(linvel)
+                .build();
+            let collider = ColliderBuilder::new(shape).density(density).build();
+            bodies.push(BodyAssignment {
+                uuid: match &keys {
+                    Some(keys) => scene.body_uuid(&keys[i]),
+                    None => Uuid::new_v4(),
+                },
+                warm: WarmBodyObject::from_body(&body, 0),
+                cold: ColdBodyObject::from_body_collider(&body, &collider),
+                epoch: 0,
+            });
+        }
+
+        let uuids = bodies.iter().map(|body| body.uuid.to_string()).collect();
+        self.block_on(
+            py,
+            self.client
+                .insert_bodies_chunked(scene, bodies, INSERT_CHUNK_SIZE, |_| {}),
+        )?;
+        Ok(uuids)
+    }
+
+    /// Starts or pauses the simulation of `scene`.
+    fn set_running(&self, py: Python, scene: &str, running: bool) -> PyResult<()> {
+        self.block_on(py, self.client.set_running(scene_uuid(scene)?, running))
+    }
+
+    /// Slows down (below 1) or speeds up (above 1) the simulation of `scene` compared to real
+    /// time, if the cluster paces its steps.
+    fn set_time_scale(&self, py: Python, scene: &str, time_scale: f32) -> PyResult<()> {
+        let time_scale = TimeScale(time_scale);
+        if !time_scale.is_valid() {
+            return Err(PyValueError::new_err(format!(
+                "The time scale must be between {} and {}.",
+                TimeScale::MIN.0,
+                TimeScale::MAX.0
+            )));
+        }
+        self.block_on(
+            py,
+            self.client.set_time_scale(scene_uuid(scene)?, time_scale),
+        )
+    }
+
+    /// Changes the solver options of `scene`, from its next step. Each count must be between 1
+    /// and 100.
+    #[pyo3(signature = (
+        scene,
+        max_velocity_iterations = 4,
+        max_velocity_friction_iterations = 8,
+        max_stabilization_iterations = 1,
+        substeps = 1
+    ))]
+    fn set_simulation_params(
+        &self,
+        py: Python,
+        scene: &str,
+        max_velocity_iterations: usize,
+        max_velocity_friction_iterations: usize,
+        max_stabilization_iterations: usize,
+        substeps: usize,
+    ) -> PyResult<()> {
+        let params = SimulationParams {
+            max_velocity_iterations,
+            max_velocity_friction_iterations,
+            max_stabilization_iterations,
+            substeps,
+        };
+        if !params.is_valid() {
+            return Err(PyValueError::new_err(format!(
+                "The iteration counts and substeps must be between 1 and {}.",
+                SimulationParams::MAX_ITERATIONS
+            )));
+        }
+        self.block_on(
+            py,
+            self.client
+                .set_simulation_params(scene_uuid(scene)?, params),
+        )
+    }
+
+    /// Suspends the bodies of `scene` inside the box from `mins` to `maxs`, and returns the uuid
+    /// of the frozen volume.
+    fn freeze_volume(
+        &self,
+        py: Python,
+        scene: &str,
+        mins: Vec<Real>,
+        maxs: Vec<Real>,
+    ) -> PyResult<String> {
+        let aabb = Aabb::new(point(&mins)?, point(&maxs)?);
+        let volume = self.block_on(py, self.client.freeze_volume(scene_uuid(scene)?, aabb))?;
+        Ok(volume.to_string())
+    }
+
+    /// Resumes the bodies suspended by `freeze_volume`.
+    fn unfreeze_volume(&self, py: Python, scene: &str, volume: &str) -> PyResult<()> {
+        let volume = Uuid::parse_str(volume)
+            .map_err(|e| PyValueError::new_err(format!("Invalid volume uuid: {e}.")))?;
+        self.block_on(py, self.client.unfreeze_volume(scene_uuid(scene)?, volume))
+    }
+
+    /// Inserts a capsule character at `position` in `scene`, and returns its uuid.
+    #[pyo3(signature = (scene, position, radius = 0.3, half_height = 0.6))]
+    fn create_character(
+        &self,
+        py: Python,
+        scene: &str,
+        position: Vec<Real>,
+        radius: Real,
+        half_height: Real,
+    ) -> PyResult<String> {
+        let params = CharacterParams {
+            radius,
+            half_height,
+            ..Default::default()
+        };
+        let position = Isometry::from(point(&position)?.coords);
+        let character = self.block_on(
+            py,
+            self.client
+                .create_character(scene_uuid(scene)?, params, position),
+        )?;
+        Ok(character.to_string())
+    }
+
+    /// Sets the velocity at which `character` walks, until the next call.
+    fn move_character(
+        &self,
+        py: Python,
+        scene: &str,
+        character: &str,
+        velocity: Vec<Real>,
+    ) -> PyResult<()> {
+        let velocity = point(&velocity)?.coords;
+        self.block_on(
+            py,
+            self.client
+                .move_character(scene_uuid(scene)?, body_uuid(character)?, velocity),
+        )
+    }
+
+    /// Runs `steps` steps of the paused `scene` from its current step, and returns the last step
+    /// that will be run. Fails if the scene is running.
+    #[pyo3(signature = (scene, steps = 1))]
+    fn step(&self, py: Python, scene: &str, steps: u64) -> PyResult<u64> {
+        let response = self.block_on(py, self.client.step_n(scene_uuid(scene)?, steps))?;
+        Ok(response.step_limit)
+    }
+
+    /// Lets the simulation of `scene` run up to the step `step_id`.
+    ///
+    /// The cluster only runs a few steps ahead of its clients, this is how a script makes it
+    /// progress without displaying anything.
+    fn step_until(&self, py: Python, scene: &str, step_id: u64) -> PyResult<()> {
+        self.block_on(py, self.client.client_input(scene_uuid(scene)?, step_id))
+    }
+
+    /// The current poses of the bodies of `scene`, as a tuple of their uuids, their positions,
+    /// their rotations and the steps these poses were computed at.
+    #[allow(clippy::type_complexity)]
+    fn fetch_poses<'py>(
+        &self,
+        py: Python<'py>,
+        scene: &str,
+    ) -> PyResult<(
+        Vec<String>,
+        &'py PyArray2<Real>,
+        &'py PyArray2<Real>,
+        &'py PyArray1<u64>,
+    )> {
+        let export = self.block_on(py, self.client.export_scene(scene_uuid(scene)?))?;
+        let chassis = export.vehicles.iter().map(|vehicle| &vehicle.chassis);
+        let num_bodies = export.bodies.len() + export.vehicles.len();
+        let mut uuids = Vec::with_capacity(num_bodies);
+        let mut positions = Vec::with_capacity(num_bodies * DIM);
+        let mut rotations = Vec::with_capacity(num_bodies * ROTATION_DIM);
+        let mut timestamps = Vec::with_capacity(num_bodies);
+
+        for body in export.bodies.iter().chain(chassis) {
+            uuids.push(body.uuid.to_string());
+            positions.extend(body.warm.position.translation.vector.iter().copied());
+            push_rotation(&mut rotations, &body.warm.position);
+            timestamps.push(body.warm.timestamp);
+        }
+
+        let positions =
+            Array2::from_shape_vec((num_bodies, DIM), positions).map_err(anyhow::Error::from)?;
+        let rotations = Array2::from_shape_vec((num_bodies, ROTATION_DIM), rotations)
+            .map_err(anyhow::Error::from)?;
+        Ok((
+            uuids,
+            positions.into_pyarray(py),
+            rotations.into_pyarray(py),
+            Array1::from(timestamps).into_pyarray(py),
+        ))
+    }
+
+    /// Starts, or stops if `traced` is false, recording the trajectories of `bodies`.
+    #[pyo3(signature = (scene, bodies, traced = true))]
+    fn trace_bodies(
+        &self,
+        py: Python,
+        scene: &str,
+        bodies: Vec<String>,
+        traced: bool,
+    ) -> PyResult<()> {
+        let bodies = bodies
+            .iter()
+            .map(|body| body_uuid(body))
+            .collect::<PyResult<_>>()?;
+        self.block_on(
+            py,
+            self.client.trace_bodies(scene_uuid(scene)?, bodies, traced),
+        )
+    }
+
+    /// The trajectory recorded for `body`, as a tuple of its positions, rotations and the steps
+    /// they were computed at.
+    #[allow(clippy::type_complexity)]
+    fn fetch_trajectory<'py>(
+        &self,
+        py: Python<'py>,
+        scene: &str,
+        body: &str,
+    ) -> PyResult<(&'py PyArray2<Real>, &'py PyArray2<Real>, &'py PyArray1<u64>)> {
+        let body = body_uuid(body)?;
+        let trajectories =
+            self.block_on(py, self.client.trajectories(scene_uuid(scene)?, vec![body]))?;
+        let points = trajectories
+            .bodies
+            .into_iter()
+            .next()
+            .map(|trajectory| trajectory.points)
+            .unwrap_or_default();
+        let num_points = points.len();
+        let mut positions = Vec::with_capacity(num_points * DIM);
+        let mut rotations = Vec::with_capacity(num_points * ROTATION_DIM);
+        let mut timestamps = Vec::with_capacity(num_points);
+
+        for point in &points {
+            positions.extend(point.position.translation.vector.iter().copied());
+            push_rotation(&mut rotations, &point.position);
+            timestamps.push(point.timestamp);
+        }
+
+        let positions =
+            Array2::from_shape_vec((num_points, DIM), positions).map_err(anyhow::Error::from)?;
+        let rotations = Array2::from_shape_vec((num_points, ROTATION_DIM), rotations)
+            .map_err(anyhow::Error::from)?;
+        Ok((
+            positions.into_pyarray(py),
+            rotations.into_pyarray(py),
+            Array1::from(timestamps).into_pyarray(py),
+        ))
+    }
+}
+
+fn scene_uuid(scene: &str) -> PyResult<SceneUuid> {
+    Uuid::parse_str(scene)
+        .map(SceneUuid)
+        .map_err(|e| PyValueError::new_err(format!("Invalid scene uuid: {e}.")))
+}
+
+fn body_uuid(body: &str) -> PyResult<Uuid> {
+    Uuid::parse_str(body).map_err(|e| PyValueError::new_err(format!("Invalid body uuid: {e}.")))
+}
+
+fn point(coords: &[Real]) -> PyResult<Point<Real>> {
+    if coords.len() != DIM {
+        return Err(PyValueError::new_err(format!(
+            "Expected {DIM} coordinates."
+        )));
+    }
+    Ok(Point::from_slice(coords))
+}
+
+#[cfg(feature = "dim2")]
+fn push_rotation(rotations: &mut Vec<Real>, pose: &Isometry<Real>) {
+    rotations.push(pose.rotation.angle());
+}
+
+#[cfg(feature = "dim3")]
+fn push_rotation(rotations: &mut Vec<Real>, pose: &Isometry<Real>) {
+    rotations.extend(pose.rotation.coords.iter().copied());
+}
@@ -0,0 +1,111 @@
+//! The kinematic characters simulated by the region, moved with Rapier’s character controller.
+
+use crate::runner::SimulationState;
+use crate::watch::MAIN_GROUP;
+use rapier::control::{CharacterAutostep, CharacterLength, KinematicCharacterController};
+use rapier::prelude::*;
+use steadyum_api_types::characters::CharacterParams;
+
+/// A character simulated by the region, see [`CharacterParams`].
+pub struct RegionCharacter {
+    pub params: CharacterParams,
+    /// The velocity of the last movement intent of its client.
+    pub velocity: Vector<Real>,
+    /// The velocity gained falling since the character left the ground.
+    pub fall_velocity: Vector<Real>,
+    pub grounded: bool,
+}
+
+impl RegionCharacter {
+    /// A character entering the region with the velocity `linvel`.
+    ///
+    /// The walking velocity is recovered from `linvel`, so the character keeps walking after
+    /// crossing a border, until the next intent of its client reaches its new region.
+    pub fn new(params: CharacterParams, linvel: Vector<Real>, gravity: &Vector<Real>) -> Self {
+        let down = gravity.try_normalize(1.0e-6).unwrap_or_else(Vector::zeros);
+        let fall_velocity = down * linvel.dot(&down).max(0.0);
+        Self {
+            params,
+            velocity: linvel - fall_velocity,
+            fall_velocity,
+            grounded: false,
+        }
+    }
+
+    fn controller(&self, up: UnitVector<Real>) -> KinematicCharacterController {
+        KinematicCharacterController {
+            up,
+            offset: CharacterLength::Absolute(self.params.offset),
+            slide: true,
+            autostep: self.params.autostep_height.map(|height| CharacterAutostep {
+                max_height: CharacterLength::Absolute(height),
+                min_width: CharacterLength::Absolute(self.params.radius),
+                ..Default::default()
+            }),
+            max_slope_climb_angle: self.params.max_slope_climb_angle,
+            min_slope_slide_angle: self.params.min_slope_slide_angle,
+            snap_to_ground: self.params.snap_to_ground.map(CharacterLength::Absolute),
+        }
+    }
+}
+
+/// Moves the characters of the region by their velocity for `dt` seconds, sliding along the
+/// colliders of the region and the ones mirrored from the neighbors.
+///
+/// The query pipeline must be up to date. The new positions are reached during the next step.
+pub fn move_characters(sim_state: &mut SimulationState, dt: Real) {
+    // NOTE: the handles of the removed characters, or of the ones that left the region, can be
+    //       reused by other bodies.
+    let bodies = &sim_state.bodies;
+    let watched_objects = &sim_state.watched_objects;
+    sim_state.characters.retain(|handle, _| {
+        bodies
+            .get(*handle)
+            .map(|body| body.is_kinematic())
+            .unwrap_or(false)
+            && !watched_objects.contains_key(handle)
+    });
+    if sim_state.characters.is_empty() {
+        return;
+    }
+
+    let up = UnitVector::try_new(-sim_state.gravity, 1.0e-6).unwrap_or_else(Vector::y_axis);
+    let mut next_positions = vec![];
+
+    for (handle, character) in &mut sim_state.characters {
+        let body = &sim_state.bodies[*handle];
+        let collider = &sim_state.colliders[body.colliders()[0]];
+
+        if character.grounded {
+            character.fall_velocity = Vector::zeros();
+        } else {
+            character.fall_velocity += sim_state.gravity * dt;
+        }
+
+        // Only the main colliders, not the watch colliders.
+        let filter = QueryFilter::new()
+            .exclude_sensors()
+            .exclude_rigid_body(*handle)
+            .groups(InteractionGroups::new(Group::ALL, MAIN_GROUP));
+        let movement = character.controller(up).move_shape(
+            dt,
+            &sim_state.bodies,
+            &sim_state.colliders,
+            &sim_state.query_pipeline,
+            collider.shape(),
+            body.position(),
+            (character.velocity + character.fall_velocity) * dt,
+            filter,
+            |_| {},
+        );
+        character.grounded = movement.grounded;
+
+        let mut position = *body.position();
+        position.translation.vector += movement.translation;
+        next_positions.push((*handle, position));
+    }
+
+    for (handle, position) in next_positions {
+        sim_state.bodies[handle].set_next_kinematic_position(position);
+    }
+}
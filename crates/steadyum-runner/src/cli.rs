@@ -6,8 +6,8 @@ use uuid::Uuid;
 pub struct CliArgs {
     #[arg(long)]
     pub uuid: u128,
-    #[arg(long)]
-    pub scene_uuid: u128,
+    /// Simulation time, in milliseconds, of the first step. This offsets the time at which
+    /// the kinematic animations are evaluated.
     #[arg(long, default_value_t = 0)]
     pub time_origin: u64,
     #[arg(short, long, default_value_t = false)]
@@ -18,7 +18,4 @@ impl CliArgs {
     pub fn typed_uuid(&self) -> Uuid {
         Uuid::from_u128_le(self.uuid)
     }
-    pub fn typed_scene_uuid(&self) -> Uuid {
-        Uuid::from_u128_le(self.scene_uuid)
-    }
 }
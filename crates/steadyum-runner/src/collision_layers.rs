@@ -0,0 +1,59 @@
+use crate::watch::{MAIN_GROUP, WATCH_GROUP};
+use rapier::prelude::*;
+use steadyum_api_types::collision::CollisionMatrix;
+
+/// Member of every body without layer, so they aren’t filtered out by the layered bodies.
+const UNLAYERED_GROUP: Group = Group::GROUP_3;
+/// The group of the first collision layer. The layer `i` is the group `i + FIRST_LAYER_GROUP`.
+const FIRST_LAYER_GROUP: u32 = 3;
+
+fn layer_group(layer: u32) -> Group {
+    Group::from_bits_truncate(1 << (layer + FIRST_LAYER_GROUP))
+}
+
+fn interacting_groups(matrix: &CollisionMatrix, layer: u32) -> Group {
+    Group::from_bits_truncate(matrix.interacting_layers(layer) << FIRST_LAYER_GROUP)
+}
+
+/// Layers unknown to the collision matrix are ignored.
+fn known_layer(matrix: &CollisionMatrix, layer: Option<u32>) -> Option<u32> {
+    layer.filter(|layer| (*layer as usize) < matrix.layers.len().min(CollisionMatrix::MAX_LAYERS))
+}
+
+/// The collision groups of the main collider of a body on `layer`.
+pub fn collision_groups(matrix: &CollisionMatrix, layer: Option<u32>) -> InteractionGroups {
+    match known_layer(matrix, layer) {
+        None => InteractionGroups::all(),
+        Some(layer) => InteractionGroups::new(
+            MAIN_GROUP | layer_group(layer),
+            WATCH_GROUP | UNLAYERED_GROUP | interacting_groups(matrix, layer),
+        ),
+    }
+}
+
+/// The collision groups of the colliders the watch colliders must not detect: the sensors and
+/// the bodies mirrored from the neighbors.
+pub fn unwatched_collision_groups(
+    matrix: &CollisionMatrix,
+    layer: Option<u32>,
+) -> InteractionGroups {
+    match known_layer(matrix, layer) {
+        None => InteractionGroups::new(!WATCH_GROUP, !WATCH_GROUP),
+        Some(layer) => InteractionGroups::new(
+            MAIN_GROUP | layer_group(layer),
+            UNLAYERED_GROUP | interacting_groups(matrix, layer),
+        ),
+    }
+}
+
+/// The layer of a body, recovered from the collision groups of its main collider.
+pub fn collision_layer(collider: &Collider) -> Option<u32> {
+    let memberships = collider.collision_groups().memberships;
+
+    if memberships.contains(UNLAYERED_GROUP) {
+        return None;
+    }
+
+    let layers = memberships.bits() >> FIRST_LAYER_GROUP;
+    (layers != 0).then(|| layers.trailing_zeros())
+}
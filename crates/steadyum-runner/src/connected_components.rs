@@ -1,6 +1,7 @@
 use crate::runner::SimulationState;
 use rapier::parry::bounding_volume::BoundingVolume;
 use rapier::prelude::*;
+use rayon::prelude::*;
 use std::collections::HashSet;
 
 #[derive(Clone)]
@@ -25,8 +26,54 @@ impl Default for ConnectedComponent {
     }
 }
 
-pub fn calculate_connected_components(
+impl ConnectedComponent {
+    /// Are all the bodies of this component still sleeping, and all its joints still there?
+    ///
+    /// Contacts and joints starting or stopping wake the bodies up, and so do the bodies removed
+    /// from the region, so the membership of such a component didn’t change since it was
+    /// computed. Its bodies didn’t move either.
+    fn is_still_sleeping(&self, sim_state: &SimulationState) -> bool {
+        self.bodies.iter().all(|handle| {
+            sim_state
+                .bodies
+                .get(*handle)
+                .map(|body| body.is_dynamic() && body.is_sleeping())
+                .unwrap_or(false)
+        }) && self
+            .joints
+            .iter()
+            .all(|(_, _, _, joint)| sim_state.impulse_joints.get(*joint).is_some())
+    }
+}
+
+/// The AABB swept by the colliders of `bodies` during the next `num_steps_run` steps.
+fn swept_aabb(
     sim_state: &SimulationState,
+    bodies: &[RigidBodyHandle],
+    num_steps_run: usize,
+) -> Aabb {
+    let mut swept_aabb = Aabb::new_invalid();
+
+    for handle in bodies {
+        let body = &sim_state.bodies[*handle];
+        let predicted_pos = body
+            .predict_position_using_velocity_and_forces(sim_state.params.dt * num_steps_run as f32);
+
+        for collider_handle in body.colliders() {
+            let collider = &sim_state.colliders[*collider_handle];
+            swept_aabb.merge(&collider.compute_swept_aabb(&predicted_pos));
+        }
+    }
+
+    swept_aabb
+}
+
+/// Computes the groups of dynamic bodies connected by contacts or joints.
+///
+/// The components whose bodies were all sleeping the last time are reused as long as they
+/// stay asleep, so only the contact graph of the awake bodies is walked again.
+pub fn calculate_connected_components(
+    sim_state: &mut SimulationState,
     num_steps_run: usize,
 ) -> Vec<ConnectedComponent> {
     let mut visited = HashSet::new();
@@ -35,9 +82,22 @@ pub fn calculate_connected_components(
     let mut connected_bodies = vec![];
     let mut connected_joints = vec![];
     let mut result = vec![];
-    let mut swept_aabb = Aabb::new_invalid();
+    let mut new_components = vec![];
+
+    let sleeping_components = std::mem::take(&mut sim_state.sleeping_components);
+    for cc in sleeping_components {
+        if cc.is_still_sleeping(sim_state) {
+            visited.extend(cc.bodies.iter().copied());
+            visited_joints.extend(cc.joints.iter().map(|(_, _, _, joint)| *joint));
+            result.push(cc);
+        }
+    }
 
     for (handle, _body) in sim_state.bodies.iter() {
+        if visited.contains(&handle) {
+            continue;
+        }
+
         stack.push(handle);
 
         while let Some(body_handle) = stack.pop() {
@@ -53,13 +113,7 @@ pub fn calculate_connected_components(
             visited.insert(body_handle);
             connected_bodies.push(body_handle);
 
-            for collider_handle in sim_state.bodies[body_handle].colliders() {
-                let collider = &sim_state.colliders[*collider_handle];
-                let predicted_pos = body.predict_position_using_velocity_and_forces(
-                    sim_state.params.dt * num_steps_run as f32,
-                );
-                swept_aabb.merge(&collider.compute_swept_aabb(&predicted_pos));
-
+            for collider_handle in body.colliders() {
                 for contact in sim_state.narrow_phase.contacts_with(*collider_handle) {
                     let other_collider_handle = if contact.collider1 == *collider_handle {
                         contact.collider2
@@ -89,13 +143,37 @@ pub fn calculate_connected_components(
         let cc = ConnectedComponent {
             bodies: std::mem::replace(&mut connected_bodies, vec![]),
             joints: std::mem::replace(&mut connected_joints, vec![]),
-            swept_aabb,
+            swept_aabb: Aabb::new_invalid(),
         };
 
         if !cc.bodies.is_empty() || !cc.joints.is_empty() {
-            result.push(cc);
+            new_components.push(cc);
         }
     }
 
+    // The characters are kinematic, so they aren’t part of the components of the bodies they
+    // touch, but they still migrate from region to region.
+    for (handle, _) in sim_state.bodies.iter() {
+        if sim_state.characters.contains_key(&handle) {
+            new_components.push(ConnectedComponent {
+                bodies: vec![handle],
+                ..Default::default()
+            });
+        }
+    }
+
+    // The walk is sequential, but the motion of the components can be predicted in parallel.
+    let sim_state_ref = &*sim_state;
+    new_components.par_iter_mut().for_each(|cc| {
+        cc.swept_aabb = swept_aabb(sim_state_ref, &cc.bodies, num_steps_run);
+    });
+    result.append(&mut new_components);
+
+    sim_state.sleeping_components = result
+        .iter()
+        .filter(|cc| !cc.bodies.is_empty() && cc.is_still_sleeping(sim_state))
+        .cloned()
+        .collect();
+
     result
 }
@@ -0,0 +1,72 @@
+//! The bodies leaving the simulation domain of the scene, see [`SimulationDomain`].
+
+use crate::runner::SimulationState;
+use crate::spawn::despawn_body;
+use rapier::parry::query::PointQuery;
+use rapier::prelude::*;
+use steadyum_api_types::messages::SimulationEvent;
+use steadyum_api_types::partitionner::{OutOfDomainPolicy, SimulationDomain};
+
+/// Applies the policy of the domain of the scene to the bodies of the region with their center
+/// outside of it, and reports them.
+///
+/// The bodies frozen by the policy are suspended by [`crate::freeze::update_frozen_bodies`]
+/// before the next step, and aren’t reported again.
+pub fn enforce_domain(sim_state: &mut SimulationState) -> Vec<SimulationEvent> {
+    let Some(domain) = sim_state.settings.domain else {
+        return vec![];
+    };
+
+    let mut escaped = vec![];
+    for (handle, body) in sim_state.bodies.iter() {
+        // NOTE: the watched bodies are handled by the region simulating them.
+        if sim_state.watched_objects.contains_key(&handle) || !body.is_enabled() {
+            continue;
+        }
+        let is_character = sim_state.characters.contains_key(&handle);
+        if !body.is_dynamic() && !is_character {
+            continue;
+        }
+        if domain
+            .aabb
+            .contains_local_point(&Point::from(*body.translation()))
+        {
+            continue;
+        }
+        if let Some(uuid) = sim_state.body2uuid.get(&handle) {
+            escaped.push((*uuid, handle, is_character));
+        }
+    }
+
+    // NOTE: despawn in a consistent order so the next bodies get the same handles.
+    escaped.sort_by_key(|(uuid, ..)| *uuid);
+
+    let mut events = vec![];
+    for (uuid, handle, is_character) in escaped {
+        match domain.policy {
+            OutOfDomainPolicy::Despawn => despawn_body(sim_state, uuid),
+            OutOfDomainPolicy::Freeze if is_character => despawn_body(sim_state, uuid),
+            OutOfDomainPolicy::Freeze => {}
+            OutOfDomainPolicy::Teleport(position) => {
+                let body = &mut sim_state.bodies[handle];
+                body.set_position(position, true);
+                body.set_linvel(Vector::zeros(), true);
+                body.set_angvel(Default::default(), true);
+                if let Some(character) = sim_state.characters.get_mut(&handle) {
+                    character.fall_velocity = Vector::zeros();
+                }
+            }
+        }
+        events.push(SimulationEvent::LeftDomain { body: uuid });
+    }
+
+    events
+}
+
+/// The volume outside of which the dynamic bodies are frozen, if the domain of the scene
+/// freezes the bodies leaving it.
+pub fn freezing_domain(domain: Option<&SimulationDomain>) -> Option<Aabb> {
+    domain
+        .filter(|domain| domain.policy == OutOfDomainPolicy::Freeze)
+        .map(|domain| domain.aabb)
+}
@@ -0,0 +1,280 @@
+//! The particle fluid simulated by each region, see [`FluidParams`].
+//!
+//! The fluid is a simple SPH (Smoothed-Particle Hydrodynamics) with the kernels of Müller et
+//! al. 2003. The particles close to the borders are sent to the neighbors with the watch sets,
+//! so the fluid stays continuous across the regions.
+
+use crate::neighbors::Neighbors;
+use crate::runner::SimulationState;
+use crate::watch::MAIN_GROUP;
+use crate::AppState;
+use rapier::parry::query::PointQuery;
+use rapier::prelude::*;
+use std::collections::HashMap;
+use steadyum_api_types::fluids::{FluidParams, FluidParticle};
+use steadyum_api_types::messages::RunnerMessage;
+use steadyum_api_types::region_db::AsyncPartitionnerServer;
+use steadyum_api_types::simulation::SimulationBounds;
+
+/// The fluid particles simulated by a region.
+#[derive(Default)]
+pub struct RegionFluid {
+    pub particles: Vec<FluidParticle>,
+    /// The particles assigned to the region, inserted at the beginning of the next step.
+    pub pending: Vec<FluidParticle>,
+}
+
+impl RegionFluid {
+    /// Inserts the particles assigned to the region since the last step.
+    pub fn insert_pending(&mut self) {
+        self.particles.append(&mut self.pending);
+    }
+}
+
+/// The particles of a fluid, hashed into cells as wide as the smoothing radius.
+struct ParticleGrid {
+    cell_width: Real,
+    cells: HashMap<[i32; DIM], Vec<usize>>,
+}
+
+impl ParticleGrid {
+    fn new(particles: &[FluidParticle], cell_width: Real) -> Self {
+        let mut cells: HashMap<_, Vec<_>> = HashMap::new();
+        for (i, particle) in particles.iter().enumerate() {
+            cells
+                .entry(cell(&particle.position, cell_width))
+                .or_default()
+                .push(i);
+        }
+        Self { cell_width, cells }
+    }
+
+    /// The particles in the cell of `point` and the adjacent ones.
+    fn neighbors(&self, point: &Point<Real>) -> impl Iterator<Item = usize> + '_ {
+        let center = cell(point, self.cell_width);
+        (0..3usize.pow(DIM as u32)).flat_map(move |k| {
+            let key: [i32; DIM] =
+                std::array::from_fn(|i| center[i] + (k / 3usize.pow(i as u32) % 3) as i32 - 1);
+            self.cells.get(&key).into_iter().flatten().copied()
+        })
+    }
+}
+
+fn cell(point: &Point<Real>, cell_width: Real) -> [i32; DIM] {
+    std::array::from_fn(|i| (point[i] / cell_width).floor() as i32)
+}
+
+/// The factors of the density, pressure and viscosity kernels for the smoothing radius `h`.
+#[cfg(feature = "dim2")]
+fn kernel_factors(h: Real) -> (Real, Real, Real) {
+    let pi = std::f32::consts::PI as Real;
+    (
+        4.0 / (pi * h.powi(8)),
+        -30.0 / (pi * h.powi(5)),
+        40.0 / (pi * h.powi(5)),
+    )
+}
+
+/// The factors of the density, pressure and viscosity kernels for the smoothing radius `h`.
+#[cfg(feature = "dim3")]
+fn kernel_factors(h: Real) -> (Real, Real, Real) {
+    let pi = std::f32::consts::PI as Real;
+    (
+        315.0 / (64.0 * pi * h.powi(9)),
+        -45.0 / (pi * h.powi(6)),
+        45.0 / (pi * h.powi(6)),
+    )
+}
+
+/// Moves the fluid particles of the region by `dt`, and resolves their contacts with the
+/// colliders.
+///
+/// The `watched` particles of the neighbors contribute to the densities and forces, but aren’t
+/// moved. The query pipeline must be up to date.
+pub fn step_fluid(sim_state: &mut SimulationState, watched: &[FluidParticle], dt: Real) {
+    let Some(params) = sim_state.settings.fluid else {
+        return;
+    };
+    if sim_state.fluid.particles.is_empty() {
+        return;
+    }
+
+    let h = params.smoothing_radius();
+    let mass = params.particle_mass();
+    let (density_factor, pressure_factor, viscosity_factor) = kernel_factors(h);
+
+    let all: Vec<_> = sim_state
+        .fluid
+        .particles
+        .iter()
+        .chain(watched)
+        .copied()
+        .collect();
+    let grid = ParticleGrid::new(&all, h);
+
+    let densities: Vec<Real> = all
+        .iter()
+        .map(|particle| {
+            grid.neighbors(&particle.position)
+                .map(|j| (all[j].position - particle.position).norm_squared())
+                .filter(|r2| *r2 < h * h)
+                .map(|r2| mass * density_factor * (h * h - r2).powi(3))
+                .sum()
+        })
+        .collect();
+    let pressures: Vec<Real> = densities
+        .iter()
+        .map(|density| params.stiffness * (density - params.rest_density).max(0.0))
+        .collect();
+
+    let accelerations: Vec<_> = (0..sim_state.fluid.particles.len())
+        .map(|i| {
+            let particle = &all[i];
+            let mut acceleration = sim_state.gravity;
+            for generator in &sim_state.settings.force_generators {
+                acceleration += generator.acceleration(&particle.position, &particle.velocity);
+            }
+
+            for j in grid.neighbors(&particle.position) {
+                let diff = particle.position - all[j].position;
+                let r = diff.norm();
+                if j == i || r >= h || r == 0.0 {
+                    continue;
+                }
+
+                let dir = diff / r;
+                let pressure = mass * (pressures[i] + pressures[j]) / (2.0 * densities[j])
+                    * pressure_factor
+                    * (h - r).powi(2);
+                let viscosity = params.viscosity * mass / densities[j] * viscosity_factor * (h - r);
+                acceleration -= dir * pressure / densities[i];
+                acceleration += (all[j].velocity - particle.velocity) * viscosity / densities[i];
+            }
+
+            acceleration
+        })
+        .collect();
+
+    for (particle, acceleration) in sim_state.fluid.particles.iter_mut().zip(accelerations) {
+        particle.velocity += acceleration * dt;
+        particle.position += particle.velocity * dt;
+    }
+
+    resolve_collider_contacts(sim_state, &params);
+}
+
+/// Pushes the particles out of the colliders, and the dynamic bodies back.
+fn resolve_collider_contacts(sim_state: &mut SimulationState, params: &FluidParams) {
+    let mass = params.particle_mass();
+    // Only the main colliders, not the watch colliders.
+    let filter = QueryFilter::new()
+        .exclude_sensors()
+        .groups(InteractionGroups::new(Group::ALL, MAIN_GROUP));
+    let mut impulses = vec![];
+
+    for particle in &mut sim_state.fluid.particles {
+        let Some((handle, proj)) = sim_state.query_pipeline.project_point(
+            &sim_state.bodies,
+            &sim_state.colliders,
+            &particle.position,
+            false,
+            filter,
+        ) else {
+            continue;
+        };
+
+        let delta = particle.position - proj.point;
+        let dist = delta.norm();
+        if dist == 0.0 || (!proj.is_inside && dist >= params.particle_radius) {
+            continue;
+        }
+
+        let normal = if proj.is_inside {
+            -delta / dist
+        } else {
+            delta / dist
+        };
+        particle.position = proj.point + normal * params.particle_radius;
+
+        let parent = sim_state.colliders[handle].parent();
+        let body_velocity = parent
+            .map(|parent| sim_state.bodies[parent].velocity_at_point(&particle.position))
+            .unwrap_or_else(Vector::zeros);
+        let normal_velocity = (particle.velocity - body_velocity).dot(&normal);
+
+        if normal_velocity < 0.0 {
+            let dv = normal * -normal_velocity;
+            particle.velocity += dv;
+            if let Some(parent) = parent {
+                impulses.push((parent, -dv * mass, particle.position));
+            }
+        }
+    }
+
+    for (handle, impulse, point) in impulses {
+        let body = &mut sim_state.bodies[handle];
+        if body.is_dynamic() {
+            body.apply_impulse_at_point(impulse, point, true);
+        }
+    }
+}
+
+/// The particles close enough to the borders of the region to interact with the particles of
+/// the neighbors, sent with the watch set.
+pub fn border_particles(sim_state: &SimulationState) -> Vec<FluidParticle> {
+    let Some(params) = sim_state.settings.fluid else {
+        return vec![];
+    };
+
+    let inner = sim_state
+        .sim_bounds
+        .aabb()
+        .tightened(params.smoothing_radius());
+    sim_state
+        .fluid
+        .particles
+        .iter()
+        .filter(|particle| !inner.contains_local_point(&particle.position))
+        .copied()
+        .collect()
+}
+
+/// Hands the particles that left the region off to the regions they are in now.
+pub async fn send_escaped_particles(
+    app: &AppState,
+    sim_state: &mut SimulationState,
+    neighbors: &mut Neighbors<'_>,
+    db_context: &AsyncPartitionnerServer,
+) -> anyhow::Result<()> {
+    let aabb = sim_state.sim_bounds.aabb();
+    let mut escaped: HashMap<_, Vec<_>> = HashMap::new();
+    sim_state.fluid.particles.retain(|particle| {
+        if aabb.contains_local_point(&particle.position) {
+            return true;
+        }
+
+        let region =
+            SimulationBounds::from_point(particle.position, SimulationBounds::DEFAULT_WIDTH);
+        escaped.entry(region).or_default().push(*particle);
+        false
+    });
+
+    if escaped.is_empty() {
+        return Ok(());
+    }
+
+    neighbors
+        .spawn_neighbors(app, db_context, sim_state.scene, escaped.keys().copied())
+        .await;
+
+    for (region, particles) in escaped {
+        let message = RunnerMessage::AssignParticles {
+            scene: sim_state.scene,
+            region,
+            particles,
+        };
+        neighbors.fetch_neighbor(region).send(&message).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,37 @@
+//! The force generators of the scene, applied to the bodies of the region at each internal
+//! step.
+
+use crate::runner::SimulationState;
+use rapier::prelude::*;
+
+/// Applies the force fields of the scene to the awake dynamic bodies simulated by the region,
+/// for a step of `dt` seconds.
+pub fn apply_force_generators(sim_state: &mut SimulationState, dt: Real) {
+    let generators = &sim_state.settings.force_generators;
+    if generators.is_empty() {
+        return;
+    }
+
+    for (handle, body) in sim_state.bodies.iter_mut() {
+        // NOTE: the watched bodies are pushed by the region simulating them.
+        if !body.is_dynamic()
+            || body.is_sleeping()
+            || !body.is_enabled()
+            || sim_state.watched_objects.contains_key(&handle)
+        {
+            continue;
+        }
+
+        let position = *body.center_of_mass();
+        let linvel = *body.linvel();
+        let acceleration: Vector<Real> = generators
+            .iter()
+            .map(|generator| generator.acceleration(&position, &linvel))
+            .sum();
+        if acceleration != Vector::zeros() {
+            // NOTE: applied as an impulse so the locked axes are respected, and the forces of
+            //       the body don’t need to be reset after the step.
+            body.apply_impulse(acceleration * body.mass() * dt, false);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! The bodies inside the frozen volumes of the scene, suspended until their volume is unfrozen.
+
+use crate::domain::freezing_domain;
+use crate::runner::SimulationState;
+use rapier::parry::query::PointQuery;
+use rapier::prelude::*;
+
+/// Freezes the dynamic bodies of the region with their center inside a frozen volume of the
+/// scene, or outside of its domain if it freezes the bodies leaving it, and unfreezes those
+/// that no longer are.
+///
+/// Frozen bodies are put to sleep and disabled, so the steps skip them entirely. They are
+/// still mirrored by the neighbors through the watch sets.
+pub fn update_frozen_bodies(sim_state: &mut SimulationState) {
+    let step_id = sim_state.step_id;
+    let volumes: Vec<_> = sim_state
+        .settings
+        .frozen_volumes
+        .iter()
+        .filter(|volume| step_id >= volume.since_step)
+        .map(|volume| volume.aabb)
+        .collect();
+    let domain = freezing_domain(sim_state.settings.domain.as_ref());
+
+    for (handle, body) in sim_state.bodies.iter_mut() {
+        // NOTE: the watched bodies are frozen by the region simulating them.
+        if !body.is_dynamic() || sim_state.watched_objects.contains_key(&handle) {
+            continue;
+        }
+
+        let center = Point::from(*body.translation());
+        let frozen = volumes
+            .iter()
+            .any(|aabb| aabb.contains_local_point(&center))
+            || domain.is_some_and(|aabb| !aabb.contains_local_point(&center));
+        if frozen == !body.is_enabled() {
+            continue;
+        }
+
+        if frozen {
+            body.set_linvel(Vector::zeros(), false);
+            body.set_angvel(AngVector::zeros(), false);
+            body.sleep();
+            body.set_enabled(false);
+        } else {
+            body.set_enabled(true);
+            body.wake_up(true);
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use crate::runner::SimulationState;
+use crate::watch::WatchedObject;
+use rapier::prelude::*;
+use std::collections::HashMap;
+use steadyum_api_types::messages::ImpulseJointAssignment;
+use steadyum_api_types::objects::{WarmBodyObject, WatchedObjects};
+use steadyum_api_types::simulation::SimulationBounds;
+use uuid::Uuid;
+
+/// Kinematic copy of a body simulated by another region, attached by a joint to a body
+/// of this region.
+///
+/// The ghost follows the state of the actual body, as published by its region through the
+/// watch channel, so the joint can be solved approximately on both sides.
+pub struct JointGhost {
+    pub handle: RigidBodyHandle,
+    /// The region simulating the actual body, as far as we know.
+    pub region: SimulationBounds,
+}
+
+/// Adds the joints for which at least one endpoint is simulated by this region.
+///
+/// Endpoints simulated by other regions are replaced by ghosts. Joints without any local
+/// endpoint are kept pending until one of their bodies is assigned to this region.
+pub fn resolve_pending_joints(sim_state: &mut SimulationState, watch_iteration_id: usize) {
+    let mut pending = std::mem::take(&mut sim_state.pending_joints);
+    pending.sort_by_key(|joint| (joint.body1, joint.body2));
+
+    for joint in pending {
+        let local1 = sim_state.uuid2body.get(&joint.body1).copied();
+        let local2 = sim_state.uuid2body.get(&joint.body2).copied();
+
+        let (handle1, handle2) = match (local1, local2) {
+            (Some(handle1), Some(handle2)) => (handle1, handle2),
+            (Some(handle1), None) => {
+                let handle2 = ghost_for(sim_state, &joint, handle1, false, watch_iteration_id);
+                (handle1, handle2)
+            }
+            (None, Some(handle2)) => {
+                let handle1 = ghost_for(sim_state, &joint, handle2, true, watch_iteration_id);
+                (handle1, handle2)
+            }
+            (None, None) => {
+                sim_state.pending_joints.push(joint);
+                continue;
+            }
+        };
+
+        sim_state
+            .impulse_joints
+            .insert(handle1, handle2, joint.joint, true);
+    }
+}
+
+/// Gets or creates the ghost of the endpoint of `joint` that isn’t `local`.
+///
+/// A new ghost is placed where the joint would be satisfied, until the actual state of
+/// the body is read from the watch set of its region.
+fn ghost_for(
+    sim_state: &mut SimulationState,
+    joint: &ImpulseJointAssignment,
+    local: RigidBodyHandle,
+    ghost_is_body1: bool,
+    watch_iteration_id: usize,
+) -> RigidBodyHandle {
+    let uuid = if ghost_is_body1 {
+        joint.body1
+    } else {
+        joint.body2
+    };
+
+    if let Some(ghost) = sim_state.joint_ghosts.get(&uuid) {
+        return ghost.handle;
+    }
+
+    let (local_frame, ghost_frame) = if ghost_is_body1 {
+        (joint.joint.local_frame2, joint.joint.local_frame1)
+    } else {
+        (joint.joint.local_frame1, joint.joint.local_frame2)
+    };
+    let position = sim_state.bodies[local].position() * local_frame * ghost_frame.inverse();
+    let region = SimulationBounds::from_point(
+        position.translation.vector.into(),
+        SimulationBounds::DEFAULT_WIDTH,
+    );
+
+    let handle = sim_state
+        .bodies
+        .insert(RigidBodyBuilder::kinematic_velocity_based().position(position));
+    sim_state
+        .watched_objects
+        .insert(handle, WatchedObject::new(region, watch_iteration_id));
+    sim_state
+        .joint_ghosts
+        .insert(uuid, JointGhost { handle, region });
+    handle
+}
+
+/// Updates the ghosts from the latest state published by the regions of their actual body.
+///
+/// Ghosts that are no longer attached to any joint are removed.
+pub fn update_joint_ghosts(
+    sim_state: &mut SimulationState,
+    watched: &[(WatchedObjects, SimulationBounds)],
+    watch_iteration_id: usize,
+) {
+    let joint_bodies: HashMap<Uuid, (&WarmBodyObject, SimulationBounds)> = watched
+        .iter()
+        .flat_map(|(objs, region)| {
+            objs.joint_bodies
+                .iter()
+                .map(move |(uuid, warm)| (*uuid, (warm, *region)))
+        })
+        .collect();
+
+    let SimulationState {
+        bodies,
+        islands,
+        colliders,
+        impulse_joints,
+        multibody_joints,
+        watched_objects,
+        joint_ghosts,
+        ..
+    } = sim_state;
+
+    let mut orphans = vec![];
+
+    joint_ghosts.retain(|uuid, ghost| {
+        if impulse_joints
+            .attached_joints(ghost.handle)
+            .next()
+            .is_none()
+        {
+            orphans.push(ghost.handle);
+            return false;
+        }
+
+        if let Some((warm, region)) = joint_bodies.get(uuid) {
+            if let Some(body) = bodies.get_mut(ghost.handle) {
+                body.set_position(warm.position, true);
+                body.set_linvel(warm.linvel, true);
+                body.set_angvel(warm.angvel, true);
+            }
+
+            ghost.region = *region;
+            watched_objects.insert(
+                ghost.handle,
+                WatchedObject::new(*region, watch_iteration_id),
+            );
+        }
+
+        true
+    });
+
+    // NOTE: remove the orphans in a consistent order so the next bodies get the same handles.
+    orphans.sort_by_key(|handle| handle.into_raw_parts());
+    for handle in orphans {
+        bodies.remove(
+            handle,
+            islands,
+            colliders,
+            impulse_joints,
+            multibody_joints,
+            true,
+        );
+        watched_objects.remove(&handle);
+    }
+}
+
+/// Replaces the ghost of `uuid` by the actual body, now simulated by this region.
+pub fn replace_joint_ghost(sim_state: &mut SimulationState, uuid: Uuid, handle: RigidBodyHandle) {
+    let Some(ghost) = sim_state.joint_ghosts.remove(&uuid) else {
+        return;
+    };
+
+    let joints: Vec<_> = sim_state
+        .impulse_joints
+        .attached_joints(ghost.handle)
+        .map(|(rb1, rb2, _, joint)| (rb1, rb2, joint.data))
+        .collect();
+
+    sim_state.bodies.remove(
+        ghost.handle,
+        &mut sim_state.islands,
+        &mut sim_state.colliders,
+        &mut sim_state.impulse_joints,
+        &mut sim_state.multibody_joints,
+        true,
+    );
+    sim_state.watched_objects.remove(&ghost.handle);
+
+    for (rb1, rb2, joint) in joints {
+        let rb1 = if rb1 == ghost.handle { handle } else { rb1 };
+        let rb2 = if rb2 == ghost.handle { handle } else { rb2 };
+        sim_state.impulse_joints.insert(rb1, rb2, joint, true);
+    }
+}
+
+/// The regions simulating the actual bodies of the ghosts.
+pub fn joint_ghost_regions(sim_state: &SimulationState) -> Vec<SimulationBounds> {
+    let mut regions: Vec<_> = sim_state
+        .joint_ghosts
+        .values()
+        .map(|ghost| ghost.region)
+        .filter(|region| *region != sim_state.sim_bounds)
+        .collect();
+    regions.sort();
+    regions.dedup();
+    regions
+}
+
+/// The state of the bodies of this region attached by a joint to a body of another region.
+///
+/// This is published with the watch set, so the other region can update its ghosts.
+pub fn joint_bodies_watch_data(sim_state: &SimulationState) -> Vec<(Uuid, WarmBodyObject)> {
+    let mut result = vec![];
+
+    for ghost in sim_state.joint_ghosts.values() {
+        for (rb1, rb2, _, _) in sim_state.impulse_joints.attached_joints(ghost.handle) {
+            let other = if rb1 == ghost.handle { rb2 } else { rb1 };
+            if let (Some(body), Some(uuid)) =
+                (sim_state.bodies.get(other), sim_state.body2uuid.get(&other))
+            {
+                result.push((*uuid, WarmBodyObject::from_body(body, sim_state.step_id)));
+            }
+        }
+    }
+
+    result
+}
+
+/// Identifies the bodies of `joints` by their uuids, including the bodies mirrored by ghosts.
+///
+/// Joints attached to a body without uuid are skipped.
+pub fn joint_assignments(
+    sim_state: &SimulationState,
+    joints: &[(RigidBodyHandle, RigidBodyHandle, GenericJoint)],
+) -> Vec<ImpulseJointAssignment> {
+    let uuid = |handle: RigidBodyHandle| {
+        sim_state.body2uuid.get(&handle).copied().or_else(|| {
+            sim_state
+                .joint_ghosts
+                .iter()
+                .find(|(_, ghost)| ghost.handle == handle)
+                .map(|(uuid, _)| *uuid)
+        })
+    };
+
+    joints
+        .iter()
+        .filter_map(|(rb1, rb2, joint)| {
+            Some(ImpulseJointAssignment {
+                body1: uuid(*rb1)?,
+                body2: uuid(*rb2)?,
+                joint: *joint,
+            })
+        })
+        .collect()
+}
@@ -3,40 +3,68 @@ extern crate rapier2d as rapier;
 #[cfg(feature = "dim3")]
 extern crate rapier3d as rapier;
 
+mod characters;
 mod cli;
+mod collision_layers;
 mod connected_components;
+mod domain;
+mod fluids;
+mod force_generators;
+mod freeze;
+mod joints;
 mod neighbors;
+mod projectiles;
 mod region_assignment;
 mod runner;
+#[cfg(feature = "scripting")]
+mod scripting;
+mod sensors;
+mod spawn;
+mod static_bodies;
 mod storage;
+mod vehicles;
 mod watch;
 
 use crate::cli::CliArgs;
+use crate::static_bodies::SceneStaticBodies;
 use crate::storage::{
-    start_storage_thread_for_client_objects, start_storage_thread_for_watched_objects,
+    start_client_interests_thread, start_profile_queryable_thread,
+    start_storage_thread_for_client_objects, start_storage_thread_for_particles,
+    start_storage_thread_for_watched_objects,
 };
 use crate::watch::WatchedObject;
 use async_channel::{Receiver, Sender};
 use clap::Parser;
 use dashmap::DashMap;
 use futures::FutureExt;
-use log::info;
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread::{JoinHandle, Thread};
-use steadyum_api_types::messages::{BodyAssignment, RunnerMessage};
+use std::time::Instant;
+use steadyum_api_types::env::CONFIG;
+use steadyum_api_types::messages::{ClientInterest, RunnerMessage, CLIENT_INTEREST_TIMEOUT};
 use steadyum_api_types::objects::{ClientBodyObjectSet, WatchedObjects};
-use steadyum_api_types::partitionner::SceneUuid;
+use steadyum_api_types::partitionner::{
+    RegionProfile, SceneSettings, SceneUuid, NUM_INTERNAL_STEPS,
+};
 use steadyum_api_types::region_db::AsyncPartitionnerServer;
-use steadyum_api_types::serialization::deserialize;
+use steadyum_api_types::serialization::{
+    deserialize_auto, ObjectSetEncoding, QuantizedParticleSet,
+};
 use steadyum_api_types::simulation::SimulationBounds;
-use steadyum_api_types::zenoh::{runner_zenoh_commands_key, ZenohContext};
+use steadyum_api_types::store::{open_state_store, scene_prefix, StateStore};
+use steadyum_api_types::telemetry::{StepCorrelation, Traced};
+use steadyum_api_types::zenoh::{
+    runner_zenoh_commands_key, RunnerCommand, RunnerCommandDedup, ZenohContext,
+};
 use tokio::sync::RwLock;
+use tracing::{info, warn, Instrument, Span};
 use uuid::Uuid;
 use zenoh::config::WhatAmI;
 use zenoh::prelude::r#async::AsyncResolve;
-use zenoh::prelude::Reliability;
 use zenoh::prelude::SplitBuffer;
+use zenoh::sample::Sample;
 
 pub struct RegionThread {
     thread: JoinHandle<anyhow::Result<()>>,
@@ -46,37 +74,89 @@ pub struct RegionThread {
 pub struct RegionState {
     pub uuid: Uuid, // Used for logs.
     pub app: Arc<AppState>,
+    pub scene: SceneUuid,
+    pub scene_state: Arc<SceneState>,
     pub reg_rcv: Receiver<RunnerMessage>,
     pub bounds: SimulationBounds,
 }
 
 impl RegionState {
     pub fn step_id(&self) -> u64 {
-        self.app.step_id.load(Ordering::SeqCst)
+        self.scene_state.step_id.load(Ordering::SeqCst)
     }
 }
 
+/// State shared by all the regions of a single scene hosted by this runner.
+#[derive(Default)]
+pub struct SceneState {
+    pub step_id: AtomicU64,
+    pub static_bodies: RwLock<SceneStaticBodies>,
+    pub client_objects_encoding: RwLock<ObjectSetEncoding>,
+    pub settings: RwLock<SceneSettings>,
+    /// The WASM module run by the regions at each step, empty if the scene has no script.
+    pub script: RwLock<Arc<Vec<u8>>>,
+    /// The span of the current step, parent of the spans of the regions.
+    pub step_span: RwLock<Option<Span>>,
+    /// The bodies whose trajectories are published after each step.
+    pub traced_bodies: RwLock<HashSet<Uuid>>,
+    /// The regions far from every viewer, see [`SceneSettings::region_lod`].
+    pub far_regions: RwLock<HashSet<SimulationBounds>>,
+}
+
 pub struct AppState {
-    pub scene: SceneUuid,
     pub uuid: Uuid,
     pub zenoh: ZenohContext,
-    pub regions: DashMap<SimulationBounds, RegionThread>,
-    pub step_id: AtomicU64,
+    pub scenes: DashMap<SceneUuid, Arc<SceneState>>,
+    pub regions: DashMap<(SceneUuid, SimulationBounds), RegionThread>,
     pub main_thread_snd: Sender<RunnerMessage>,
-    pub pending_acks: AtomicU64,
     pub main_partitionner: AsyncPartitionnerServer,
     pub local_partitionner: AsyncPartitionnerServer,
-    pub static_bodies: RwLock<Vec<BodyAssignment>>,
-    pub watch_sets: DashMap<SimulationBounds, WatchedObjects>,
-    pub client_object_sets: DashMap<SimulationBounds, ClientBodyObjectSet>,
+    /// Simulation time, in seconds, of the first step.
+    pub time_origin: f32,
+    pub watch_sets: DashMap<(SceneUuid, SimulationBounds), WatchedObjects>,
+    pub client_object_sets: DashMap<(SceneUuid, SimulationBounds), ClientBodyObjectSet>,
+    pub particle_sets: DashMap<(SceneUuid, SimulationBounds), QuantizedParticleSet>,
+    /// Where the watch sets, client objects and particles above are also written, if it isn’t
+    /// local, so they outlive this runner.
+    pub store: Arc<dyn StateStore>,
+    /// The views of the viewers of each scene, with the last time they were refreshed.
+    pub client_interests: DashMap<(SceneUuid, Uuid), (ClientInterest, Instant)>,
+    /// The timings of the last step of each region, for the profiling queries.
+    pub region_profiles: DashMap<(SceneUuid, SimulationBounds), RegionProfile>,
+    /// The runner each region handed over by this runner moved to.
+    pub handed_over: DashMap<(SceneUuid, SimulationBounds), Uuid>,
     pub exit: AtomicBool,
 }
 
+impl AppState {
+    /// Gets the state of `scene`, creating it if this is the first time this runner sees it.
+    pub fn scene(&self, scene: SceneUuid) -> Arc<SceneState> {
+        self.scenes.entry(scene).or_default().clone()
+    }
+
+    pub fn hosts_scene(&self, scene: SceneUuid) -> bool {
+        self.scenes.contains_key(&scene)
+    }
+
+    /// Every how many auxiliary steps `region` must report its objects to the clients looking
+    /// at it, or `None` if no client is looking at it.
+    pub fn client_report_period(&self, scene: SceneUuid, region: &SimulationBounds) -> Option<u64> {
+        self.client_interests
+            .iter()
+            .filter_map(|entry| {
+                let (interest, last_seen) = entry.value();
+                (entry.key().0 == scene
+                    && last_seen.elapsed() < CLIENT_INTEREST_TIMEOUT
+                    && region.intersects_aabb(&interest.view))
+                .then(|| interest.report_period(region))
+            })
+            .min()
+    }
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> anyhow::Result<()> {
-    let mut builder = env_logger::Builder::new();
-    builder.filter_level(log::LevelFilter::Info);
-    builder.init();
+    steadyum_api_types::telemetry::init("steadyum-runner")?;
     let args = CliArgs::parse();
 
     let zenoh = ZenohContext::new(
@@ -93,100 +173,369 @@ async fn main() -> anyhow::Result<()> {
     let (main_thread_snd, main_thread_rcv) = async_channel::unbounded();
 
     let state = Arc::new(AppState {
-        scene: SceneUuid(args.typed_scene_uuid()),
         uuid: args.typed_uuid(),
         zenoh,
+        scenes: DashMap::new(),
         regions: DashMap::new(),
-        step_id: AtomicU64::new(0),
         main_thread_snd,
-        pending_acks: AtomicU64::new(0),
         main_partitionner: AsyncPartitionnerServer::new()?,
         local_partitionner: AsyncPartitionnerServer::local()?,
-        static_bodies: RwLock::new(vec![]),
+        time_origin: args.time_origin as f32 / 1000.0,
         watch_sets: DashMap::new(),
         client_object_sets: DashMap::new(),
+        particle_sets: DashMap::new(),
+        store: open_state_store(&CONFIG).await?,
+        client_interests: DashMap::new(),
+        region_profiles: DashMap::new(),
+        handed_over: DashMap::new(),
         exit: AtomicBool::new(false),
     });
 
     start_storage_thread_for_watched_objects(state.clone());
     start_storage_thread_for_client_objects(state.clone());
-    main_messages_loop(state, main_thread_rcv).await
+    start_storage_thread_for_particles(state.clone());
+    start_client_interests_thread(state.clone());
+    start_profile_queryable_thread(state.clone());
+    let result = main_messages_loop(state, main_thread_rcv).await;
+    steadyum_api_types::telemetry::shutdown();
+    result
 }
 
-async fn main_messages_loop(
-    state: Arc<AppState>,
-    main_thread_rcv: Receiver<RunnerMessage>,
-) -> anyhow::Result<()> {
+/// Receives the commands sent to this runner, acknowledges them, and passes the new ones to
+/// `commands_snd`.
+///
+/// The commands are acknowledged on their own task, so the senders don’t time out and send
+/// them again while the main loop dispatches a slow one.
+async fn commands_loop(state: Arc<AppState>, commands_snd: Sender<Traced<RunnerMessage>>) {
     let runner_zenoh_key = runner_zenoh_commands_key(state.uuid);
-    let runner_zenoh_commands_queue = state
+    let runner_zenoh_commands_queue = match state
         .zenoh
         .session
-        .declare_subscriber(&runner_zenoh_key)
-        .reliability(Reliability::Reliable)
+        .declare_queryable(&runner_zenoh_key)
         .res_async()
         .await
-        .map_err(|e| anyhow::anyhow!("{e}"))?;
-    let mut pending_acks = 0;
+    {
+        Ok(queryable) => queryable,
+        Err(e) => {
+            tracing::error!("Failed to declare the runner commands queryable: {e}");
+            return;
+        }
+    };
+    let mut dedup = RunnerCommandDedup::default();
+
+    // We are ready to receive commands, let the partitionner know so it
+    // can move us to its pool of idle runners.
+    if let Err(e) = state
+        .local_partitionner
+        .put_runner_initialized(state.uuid)
+        .await
+    {
+        tracing::error!("Failed to notify the partitionner of the runner initialization: {e}");
+    }
+
+    while let Ok(query) = runner_zenoh_commands_queue.recv_async().await {
+        let Some(value) = query.value() else {
+            warn!("Ignoring a runner command without payload.");
+            continue;
+        };
+        let command: RunnerCommand = match deserialize_auto(&value.payload.contiguous()) {
+            Ok(command) => command,
+            Err(e) => {
+                warn!("Ignoring an invalid runner command: {e}");
+                continue;
+            }
+        };
+
+        // Acknowledge duplicates too: the previous ack may have been lost.
+        let ack = Sample::new(query.key_expr().clone(), Vec::<u8>::new());
+        if let Err(e) = query.reply(Ok(ack)).res_async().await {
+            warn!("Failed to acknowledge a runner command: {e}");
+        }
+
+        if dedup.accept(&command) && commands_snd.send(command.message).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Sends the commands of the regions handed over to their new runner, in order.
+///
+/// Waiting for the new runner to acknowledge them in the main loop would stall the commands
+/// of every scene of this runner.
+async fn forwarding_loop(
+    state: Arc<AppState>,
+    forwards_rcv: Receiver<(Uuid, Traced<RunnerMessage>)>,
+) {
+    while let Ok((target, command)) = forwards_rcv.recv().await {
+        if let Err(e) = state.zenoh.send_runner_command(target, command).await {
+            warn!("Failed to forward a command to runner {target:?}: {e}");
+        }
+    }
+}
+
+async fn main_messages_loop(
+    state: Arc<AppState>,
+    main_thread_rcv: Receiver<RunnerMessage>,
+) -> anyhow::Result<()> {
+    let (commands_snd, commands_rcv) = async_channel::unbounded();
+    let (forwards_snd, forwards_rcv) = async_channel::unbounded();
+    tokio::spawn(commands_loop(state.clone(), commands_snd));
+    tokio::spawn(forwarding_loop(state.clone(), forwards_rcv));
+    let mut pending_acks = HashMap::new();
 
     loop {
-        let message: RunnerMessage = futures::select_biased! {
+        let (message, correlation) = futures::select_biased! {
             message = main_thread_rcv.recv().fuse() => {
-                message?
+                (message?, None)
             },
-            sample = runner_zenoh_commands_queue.recv_async() => {
-                let sample = sample?;
-                let payload = sample.value.payload.contiguous();
-                deserialize(&payload)?
+            command = commands_rcv.recv().fuse() => {
+                let command = command?;
+                (command.message, command.correlation)
             }
         };
 
         match message {
-            RunnerMessage::Ack => {
-                assert!(pending_acks > 0);
-                pending_acks -= 1;
+            RunnerMessage::Adopt {
+                scene,
+                client_objects_encoding,
+                settings,
+            } => {
+                info!("Runner {:?} adopted scene {:?}.", state.uuid, scene);
+                let scene_state = state.scene(scene);
+                *scene_state.client_objects_encoding.write().await = client_objects_encoding;
+                *scene_state.settings.write().await = settings;
+            }
+            RunnerMessage::SetScript { scene, module } => {
+                if cfg!(not(feature = "scripting")) && !module.is_empty() {
+                    warn!("Ignoring the script of scene {scene:?}: scripting is disabled.");
+                    continue;
+                }
+
+                info!(
+                    "Loading a {} bytes script for scene {:?}.",
+                    module.len(),
+                    scene
+                );
+                *state.scene(scene).script.write().await = Arc::new(module);
+            }
+            RunnerMessage::SetTimeScale { scene, time_scale } => {
+                info!("Time scale of scene {:?} set to {}.", scene, time_scale.0);
+                state.scene(scene).settings.write().await.time_scale = time_scale;
+            }
+            RunnerMessage::SetSimulationParams { scene, params } => {
+                info!("Solver options of scene {:?} set to {:?}.", scene, params);
+                state.scene(scene).settings.write().await.simulation_params = params;
+            }
+            RunnerMessage::SetFrozenVolumes { scene, volumes } => {
+                info!("{} frozen volumes in scene {:?}.", volumes.len(), scene);
+                state.scene(scene).settings.write().await.frozen_volumes = volumes;
+            }
+            RunnerMessage::SetForceGenerators { scene, generators } => {
+                info!(
+                    "{} force generators in scene {:?}.",
+                    generators.len(),
+                    scene
+                );
+                state.scene(scene).settings.write().await.force_generators = generators;
+            }
+            RunnerMessage::SetFarRegions { scene, regions } => {
+                *state.scene(scene).far_regions.write().await = regions.into_iter().collect();
+            }
+            RunnerMessage::TraceBodies {
+                scene,
+                bodies,
+                traced,
+            } => {
+                let scene_state = state.scene(scene);
+                let mut traced_bodies = scene_state.traced_bodies.write().await;
+                if traced {
+                    traced_bodies.extend(bodies);
+                } else {
+                    for body in &bodies {
+                        traced_bodies.remove(body);
+                    }
+                }
+            }
+            RunnerMessage::Ack {
+                scene,
+                region,
+                step_id,
+            } => {
+                let Some((pending_step_id, pending_regions, step_span)) =
+                    pending_acks.get_mut(&scene)
+                else {
+                    // The scene was removed while its regions were stepping.
+                    continue;
+                };
+
+                if *pending_step_id != step_id {
+                    warn!(
+                        "Ignoring ack of step {} from {:?}, expected step {}.",
+                        step_id, region, pending_step_id
+                    );
+                    continue;
+                }
 
-                if pending_acks == 0 {
+                if !pending_regions.remove(&region) {
+                    warn!(
+                        "Ignoring duplicate ack of step {} from {:?}.",
+                        step_id, region
+                    );
+                    continue;
+                }
+
+                if pending_regions.is_empty() {
                     // TODO: hit the main partitionner directly?
-                    state.local_partitionner.ack(state.scene).await?;
+                    ack_scene(&state, scene, step_id)
+                        .instrument(step_span.clone())
+                        .await;
                 }
             }
-            RunnerMessage::AssignStaticBodies { mut bodies } => {
+            RunnerMessage::AssignStaticBodies { scene, bodies } => {
                 // info!("Adding static bodies: {}", bodies.len());
-                state.static_bodies.write().await.append(&mut bodies);
+                let scene_state = state.scene(scene);
+                let timestamp = scene_state.step_id.load(Ordering::SeqCst) * NUM_INTERNAL_STEPS;
+                scene_state
+                    .static_bodies
+                    .write()
+                    .await
+                    .append(bodies, timestamp);
+            }
+            RunnerMessage::AssignIsland { scene, region, .. }
+            | RunnerMessage::AssignParticles { scene, region, .. }
+            | RunnerMessage::AssignVehicles { scene, region, .. } => {
+                // The neighbors may not know yet that the region moved.
+                if let Some(target) = state.handed_over.get(&(scene, region)).map(|t| *t) {
+                    forwards_snd
+                        .send((target, Traced::new(correlation, message)))
+                        .await?;
+                    continue;
+                }
+
+                let region_thread = state
+                    .regions
+                    .entry((scene, region))
+                    .or_insert_with(|| spawn_region(state.clone(), scene, region));
+                region_thread.reg_snd.send(message).await?;
+            }
+            RunnerMessage::HandOver {
+                scene,
+                region,
+                target,
+                ..
+            } => {
+                info!(
+                    "Handing region {:?}::{:?} over to runner {:?}.",
+                    scene, region, target
+                );
+                state.handed_over.insert((scene, region), target);
+                state.region_profiles.remove(&(scene, region));
+                if let Some((_, region_thread)) = state.regions.remove(&(scene, region)) {
+                    region_thread.reg_snd.send(message).await?;
+                }
             }
-            RunnerMessage::AssignIsland { region, .. } => {
+            RunnerMessage::TakeOver {
+                scene,
+                region,
+                bodies,
+                impulse_joints,
+            } => {
+                info!("Taking region {:?}::{:?} over.", scene, region);
+                state.handed_over.remove(&(scene, region));
                 let region_thread = state
                     .regions
-                    .entry(region)
-                    .or_insert_with(|| spawn_region(state.clone(), region));
+                    .entry((scene, region))
+                    .or_insert_with(|| spawn_region(state.clone(), scene, region));
+                let message = RunnerMessage::AssignIsland {
+                    scene,
+                    region,
+                    bodies,
+                    impulse_joints,
+                };
                 region_thread.reg_snd.send(message).await?;
             }
-            RunnerMessage::Step { step_id } => {
-                state.step_id.store(step_id, Ordering::SeqCst);
+            RunnerMessage::Step { scene, step_id } => {
+                let step_span = correlation
+                    .unwrap_or_else(|| StepCorrelation::root(scene, step_id))
+                    .span("runner.step");
+                let scene_state = state.scene(scene);
+                scene_state.step_id.store(step_id, Ordering::SeqCst);
+                *scene_state.step_span.write().await = Some(step_span.clone());
 
-                pending_acks = state.regions.len();
-                for runner in state.regions.iter() {
-                    runner.reg_snd.send(RunnerMessage::Step { step_id }).await?;
+                let mut regions = HashSet::new();
+                for runner in state.regions.iter().filter(|r| r.key().0 == scene) {
+                    runner
+                        .reg_snd
+                        .send(RunnerMessage::Step { scene, step_id })
+                        .await?;
+                    regions.insert(runner.key().1);
                 }
 
                 // If we don’t have any active runner, ack right away.
-                if pending_acks == 0 {
+                if regions.is_empty() {
                     // TODO: hit the main partitionner directly?
-                    state.local_partitionner.ack(state.scene).await?;
+                    ack_scene(&state, scene, step_id)
+                        .instrument(step_span.clone())
+                        .await;
                 }
+
+                pending_acks.insert(scene, (step_id, regions, step_span));
             }
-            RunnerMessage::SyncClientObjects => {
-                for runner in state.regions.iter() {
+            RunnerMessage::SyncClientObjects { scene } => {
+                for runner in state.regions.iter().filter(|r| r.key().0 == scene) {
+                    runner
+                        .reg_snd
+                        .send(RunnerMessage::SyncClientObjects { scene })
+                        .await?;
+                }
+            }
+            RunnerMessage::Export { scene, request } => {
+                for runner in state.regions.iter().filter(|r| r.key().0 == scene) {
                     runner
                         .reg_snd
-                        .send(RunnerMessage::SyncClientObjects)
+                        .send(RunnerMessage::Export { scene, request })
                         .await?;
                 }
             }
-            RunnerMessage::Exit => {
+            RunnerMessage::BodyInput { scene, .. } => {
+                for runner in state.regions.iter().filter(|r| r.key().0 == scene) {
+                    runner.reg_snd.send(message.clone()).await?;
+                }
+            }
+            RunnerMessage::Exit { scene } => {
+                info!("Removing scene {:?} from this runner.", scene);
+                let to_exit: Vec<_> = state
+                    .regions
+                    .iter()
+                    .filter(|r| r.key().0 == scene)
+                    .map(|r| *r.key())
+                    .collect();
+                for key in to_exit {
+                    if let Some((_, runner)) = state.regions.remove(&key) {
+                        runner.reg_snd.send(RunnerMessage::Exit { scene }).await?;
+                    }
+                }
+
+                state.scenes.remove(&scene);
+                state.watch_sets.retain(|(s, _), _| *s != scene);
+                state.client_object_sets.retain(|(s, _), _| *s != scene);
+                state.particle_sets.retain(|(s, _), _| *s != scene);
+                if !state.store.is_local() {
+                    if let Err(e) = state.store.remove_prefix(&scene_prefix(scene)).await {
+                        warn!("Failed to remove the stored state of scene {scene:?}: {e}");
+                    }
+                }
+                state.client_interests.retain(|(s, _), _| *s != scene);
+                state.region_profiles.retain(|(s, _), _| *s != scene);
+                state.handed_over.retain(|(s, _), _| *s != scene);
+                pending_acks.remove(&scene);
+            }
+            RunnerMessage::Ping => {}
+            RunnerMessage::Shutdown => {
                 state.exit.store(true, Ordering::SeqCst);
                 for runner in state.regions.iter() {
-                    runner.reg_snd.send(RunnerMessage::Exit).await?;
+                    let scene = runner.key().0;
+                    runner.reg_snd.send(RunnerMessage::Exit { scene }).await?;
                 }
                 break;
             }
@@ -196,17 +545,33 @@ async fn main_messages_loop(
     Ok(())
 }
 
-fn spawn_region(app: Arc<AppState>, region: SimulationBounds) -> RegionThread {
+async fn ack_scene(state: &AppState, scene: SceneUuid, step_id: u64) {
+    if let Err(e) = state
+        .local_partitionner
+        .ack(scene, step_id, state.uuid)
+        .await
+    {
+        tracing::error!("Failed to ack step {} of scene {:?}: {e}", step_id, scene);
+    }
+}
+
+fn spawn_region(app: Arc<AppState>, scene: SceneUuid, region: SimulationBounds) -> RegionThread {
     let (reg_snd, reg_rcv) = async_channel::unbounded();
     let uuid = Uuid::new_v4();
+    let scene_state = app.scene(scene);
     let reg_state = RegionState {
         uuid,
         app,
+        scene,
+        scene_state,
         reg_rcv,
         bounds: region,
     };
 
-    info!("Spawning thread {:?} for region {:?}.", uuid, region);
+    info!(
+        "Spawning thread {:?} for region {:?}::{:?}.",
+        uuid, scene, region
+    );
 
     let thread = std::thread::spawn(move || {
         let runtime = tokio::runtime::Builder::new_current_thread()
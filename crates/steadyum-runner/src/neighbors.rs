@@ -6,32 +6,28 @@ use steadyum_api_types::messages::{RunnerMessage, PARTITIONNER_QUEUE};
 use steadyum_api_types::partitionner::SceneUuid;
 use steadyum_api_types::region_db::AsyncPartitionnerServer;
 use steadyum_api_types::simulation::SimulationBounds;
-use steadyum_api_types::zenoh::{runner_zenoh_commands_key, ZenohContext};
+use steadyum_api_types::telemetry::Traced;
+use steadyum_api_types::zenoh::ZenohContext;
 use uuid::Uuid;
-use zenoh::prelude::r#async::*;
 // use zenoh::prelude::SplitBuffer;
-use zenoh::publication::Publisher;
 // use zenoh::sample::Sample;
 use crate::AppState;
-use steadyum_api_types::serialization::{deserialize, serialize};
+use steadyum_api_types::serialization::deserialize;
 use zenoh::subscriber::Subscriber;
 
 pub enum NeighborRunner<'a> {
     Local { sender: Sender<RunnerMessage> },
-    Remote { queue: Publisher<'a>, uuid: Uuid },
+    Remote { zenoh: &'a ZenohContext, uuid: Uuid },
 }
 
 impl<'a> NeighborRunner<'a> {
     pub async fn send(&self, message: &RunnerMessage) -> anyhow::Result<()> {
         match self {
             Self::Local { sender } => Ok(sender.send(message.clone()).await?),
-            Self::Remote { queue, .. } => {
-                let data = serialize(message)?;
-                Ok(queue
-                    .put(data)
-                    .res()
+            Self::Remote { zenoh, uuid } => {
+                zenoh
+                    .send_runner_command(*uuid, Traced::new(None, message.clone()))
                     .await
-                    .map_err(|e| anyhow::anyhow!("{e}"))?)
             }
         }
     }
@@ -60,7 +56,7 @@ impl<'a> Neighbors<'a> {
         for region in regions {
             match self.runners.entry(region) {
                 Entry::Vacant(entry) => {
-                    if let Some(runner) = app.regions.get(&region) {
+                    if let Some(runner) = app.regions.get(&(scene, region)) {
                         // The region already exists locally.
                         entry.insert(NeighborRunner::Local {
                             sender: app.main_thread_snd.clone(),
@@ -77,16 +73,10 @@ impl<'a> Neighbors<'a> {
                         });
                     } else {
                         // The region exists on another node.
-                        let zenoh_key = runner_zenoh_commands_key(uuid);
-                        let queue = self
-                            .zenoh
-                            .session
-                            .declare_publisher(zenoh_key)
-                            .congestion_control(CongestionControl::Block)
-                            .res()
-                            .await
-                            .unwrap();
-                        entry.insert(NeighborRunner::Remote { queue, uuid });
+                        entry.insert(NeighborRunner::Remote {
+                            zenoh: self.zenoh,
+                            uuid,
+                        });
                     }
                 }
                 Entry::Occupied(_) => {}
@@ -0,0 +1,125 @@
+//! The projectiles simulated by the region, removed after their lifetime or their first hit.
+
+use crate::runner::SimulationState;
+use crate::spawn::despawn_body;
+use crate::watch::WATCH_GROUP;
+use rapier::prelude::*;
+use std::collections::HashSet;
+use steadyum_api_types::messages::SimulationEvent;
+use steadyum_api_types::projectiles::ProjectileParams;
+use uuid::Uuid;
+
+/// A projectile simulated by the region, see [`ProjectileParams`].
+pub struct RegionProjectile {
+    pub despawn_on_hit: bool,
+    /// The step at which the projectile is removed.
+    pub expires_at: u64,
+}
+
+impl RegionProjectile {
+    pub fn new(params: ProjectileParams, step_id: u64) -> Self {
+        Self {
+            despawn_on_hit: params.despawn_on_hit,
+            expires_at: step_id + params.lifetime,
+        }
+    }
+
+    /// The parameters of the projectile handed off, or exported, at the step `step_id`.
+    pub fn params(&self, step_id: u64) -> ProjectileParams {
+        ProjectileParams {
+            lifetime: self.expires_at.saturating_sub(step_id),
+            despawn_on_hit: self.despawn_on_hit,
+        }
+    }
+}
+
+/// Reports the hits of the projectiles of the region during the last physics steps, and
+/// removes the projectiles that expired, or hit something and must be despawned.
+pub fn update_projectiles(
+    sim_state: &mut SimulationState,
+    collision_events: &[CollisionEvent],
+) -> Vec<SimulationEvent> {
+    // NOTE: the handles of the removed projectiles, or of the ones that left the region, can
+    //       be reused by other bodies.
+    let bodies = &sim_state.bodies;
+    let watched_objects = &sim_state.watched_objects;
+    sim_state
+        .projectiles
+        .retain(|handle, _| bodies.contains(*handle) && !watched_objects.contains_key(handle));
+    if sim_state.projectiles.is_empty() {
+        return vec![];
+    }
+
+    let mut events = vec![];
+    let mut despawned = HashSet::new();
+
+    for event in collision_events {
+        if !event.started() || event.sensor() {
+            continue;
+        }
+
+        // NOTE: the colliders removed since the last step are gone already.
+        let (Some(co1), Some(co2)) = (
+            sim_state.colliders.get(event.collider1()),
+            sim_state.colliders.get(event.collider2()),
+        ) else {
+            continue;
+        };
+
+        // The watch colliders touching a projectile don’t hit it.
+        if [co1, co2]
+            .iter()
+            .any(|co| co.collision_groups().memberships == WATCH_GROUP)
+        {
+            continue;
+        }
+
+        for (projectile, other) in [(co1, co2), (co2, co1)] {
+            let Some(projectile_handle) = projectile.parent() else {
+                continue;
+            };
+            let Some(region_projectile) = sim_state.projectiles.get(&projectile_handle) else {
+                continue;
+            };
+            let Some(projectile_uuid) = sim_state.body2uuid.get(&projectile_handle) else {
+                continue;
+            };
+            // A projectile despawned on hit only reports its first one.
+            if despawned.contains(projectile_uuid) {
+                continue;
+            }
+
+            events.push(SimulationEvent::ProjectileHit {
+                projectile: *projectile_uuid,
+                body: other
+                    .parent()
+                    .and_then(|handle| sim_state.body2uuid.get(&handle))
+                    .copied(),
+            });
+
+            if region_projectile.despawn_on_hit {
+                despawned.insert(*projectile_uuid);
+            }
+        }
+    }
+
+    for (handle, projectile) in &sim_state.projectiles {
+        if projectile.expires_at <= sim_state.step_id {
+            if let Some(uuid) = sim_state.body2uuid.get(handle) {
+                despawned.insert(*uuid);
+            }
+        }
+    }
+
+    // NOTE: despawn in a consistent order so the next bodies get the same handles.
+    let mut despawned: Vec<Uuid> = despawned.into_iter().collect();
+    despawned.sort();
+    for uuid in despawned {
+        if let Some(handle) = sim_state.uuid2body.get(&uuid).copied() {
+            sim_state.projectiles.remove(&handle);
+        }
+        despawn_body(sim_state, uuid);
+    }
+
+    events
+}
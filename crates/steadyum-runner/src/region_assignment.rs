@@ -1,19 +1,21 @@
+use crate::collision_layers::collision_layer;
 use crate::connected_components::ConnectedComponent;
+use crate::joints::joint_assignments;
 use crate::neighbors::Neighbors;
-use crate::runner::{QueryableWatchedObjects, SimulationState};
+use crate::runner::{GhostBody, QueryableWatchedObjects, SimulationState};
+use crate::vehicles::vehicle_assignments;
 use crate::watch::WatchedObject;
 use crate::AppState;
 use futures::{stream, StreamExt, TryStreamExt};
 use rapier::parry::bounding_volume::BoundingVolume;
 use rapier::parry::partitioning::Qbvh;
+use rapier::parry::query::PointQuery;
 use rapier::prelude::*;
 use std::collections::{HashMap, HashSet};
 use steadyum_api_types::messages::{BodyAssignment, RunnerMessage};
 use steadyum_api_types::objects::{ColdBodyObject, WarmBodyObject};
 use steadyum_api_types::region_db::AsyncPartitionnerServer;
-use steadyum_api_types::simulation::SimulationBounds;
-
-const MIN_SENDBACK_DELAY: u128 = 50;
+use steadyum_api_types::simulation::{SimulationBounds, WatchMode};
 
 #[derive(Default)]
 pub struct RegionAssignments {
@@ -21,6 +23,9 @@ pub struct RegionAssignments {
     pub bodies_to_reassign: HashMap<SimulationBounds, Vec<RigidBodyHandle>>,
     /// Set of rigid-body that should change region.
     pub reassigned_bodies: HashSet<RigidBodyHandle>, // TODO: coarena?
+    /// Joints attached to the bodies changing region.
+    pub joints_to_reassign:
+        HashMap<SimulationBounds, Vec<(RigidBodyHandle, RigidBodyHandle, GenericJoint)>>,
 }
 
 pub fn calculate_region_assignments(
@@ -66,19 +71,37 @@ pub fn calculate_region_assignments(
                             SimulationBounds::from_aabb(&aabb, SimulationBounds::DEFAULT_WIDTH);
 
                         if body_region < sim_state.sim_bounds {
-                            if body.user_data < MIN_SENDBACK_DELAY {
+                            let hysteresis = sim_state.settings.migration_hysteresis;
+                            if body.user_data < hysteresis.delay as u128 {
                                 sim_state.bodies[*handle].user_data += 1;
                                 return sim_state.sim_bounds;
                             }
+
+                            let past_border = sim_state
+                                .sim_bounds
+                                .aabb()
+                                .distance_to_local_point(&aabb.maxs, true);
+                            if past_border < hysteresis.border_distance {
+                                return sim_state.sim_bounds;
+                            }
                         }
 
                         body_region
                     } else {
-                        watch_intersections
+                        let watched_region = watch_intersections
                             .drain(..)
                             .map(|i| watched_objects.objects[i].0)
                             .max()
-                            .unwrap()
+                            .unwrap();
+
+                        // When every neighbor is watched, the bodies touching across a border
+                        // are seen by both regions: the greater one keeps them, so they never
+                        // end up simulated twice.
+                        if sim_state.settings.watch_mode == WatchMode::All {
+                            watched_region.max(sim_state.sim_bounds)
+                        } else {
+                            watched_region
+                        }
                     }
                 });
 
@@ -124,6 +147,15 @@ pub fn calculate_region_assignments(
                     .filter(|h| !sim_state.watched_objects.contains_key(&h))
                     .copied(),
             );
+            result
+                .joints_to_reassign
+                .entry(*cc_region)
+                .or_insert_with(Vec::new)
+                .extend(
+                    cc.joints
+                        .iter()
+                        .map(|(rb1, rb2, joint, _)| (*rb1, *rb2, *joint)),
+                );
         }
     }
 
@@ -168,24 +200,58 @@ pub async fn apply_and_send_region_assignments(
 
                 let neighbor = neighbors.fetch_neighbor(*new_region);
 
-                let body_assignments = handles
+                let body_assignments: Vec<_> = handles
                     .iter()
                     .map(|handle| {
                         let body = &sim_state.bodies[*handle];
                         let collider = &sim_state.colliders[body.colliders()[0]];
                         let uuid = sim_state.body2uuid[handle];
                         let warm = WarmBodyObject::from_body(body, sim_state.step_id);
-                        let cold = ColdBodyObject::from_body_collider(body, collider);
-                        BodyAssignment { uuid, warm, cold }
+                        let mut cold = ColdBodyObject::from_body_collider(body, collider);
+                        cold.collision_layer = collision_layer(collider);
+                        if let Some(user_data) = sim_state.body2user_data.get(handle.0) {
+                            cold.user_data = user_data.clone();
+                        }
+                        cold.character = sim_state
+                            .characters
+                            .get(handle)
+                            .map(|character| character.params);
+                        cold.projectile = sim_state
+                            .projectiles
+                            .get(handle)
+                            .map(|projectile| projectile.params(sim_state.step_id));
+                        BodyAssignment {
+                            uuid,
+                            warm,
+                            cold,
+                            epoch: sim_state.epoch(*handle) + 1,
+                        }
                     })
                     .collect();
 
+                // The vehicles are handed off as one unit, with their chassis.
+                let (chassis, body_assignments): (Vec<_>, Vec<_>) = body_assignments
+                    .into_iter()
+                    .partition(|body| sim_state.vehicles.contains_key(&body.uuid));
+                if !chassis.is_empty() {
+                    let message = RunnerMessage::AssignVehicles {
+                        scene: sim_state.scene,
+                        region: *new_region,
+                        vehicles: vehicle_assignments(sim_state, chassis),
+                    };
+                    neighbor.send(&message).await?;
+                }
+
                 // Switch region.
                 let message = RunnerMessage::AssignIsland {
-                    scene: app_state.scene,
+                    scene: sim_state.scene,
                     region: *new_region,
                     bodies: body_assignments,
-                    impulse_joints: vec![],
+                    impulse_joints: assignments
+                        .joints_to_reassign
+                        .get(new_region)
+                        .map(|joints| joint_assignments(sim_state, joints))
+                        .unwrap_or_default(),
                 };
 
                 neighbor.send(&message).await?;
@@ -194,21 +260,30 @@ pub async fn apply_and_send_region_assignments(
             .await?;
     }
 
-    for handle in assignments
-        .bodies_to_reassign
-        .values()
-        .flat_map(|handles| handles.iter())
+    // NOTE: remove the bodies in a consistent order so the next bodies get the same handles.
+    let mut regions: Vec<_> = assignments.bodies_to_reassign.iter().collect();
+    regions.sort_by_key(|(region, _)| **region);
+
+    for (new_region, handle) in regions
+        .into_iter()
+        .flat_map(|(region, handles)| handles.iter().map(move |h| (*region, *h)))
     {
+        // Keep reporting the body to clients until its new region does.
+        if let Some(ghost) = GhostBody::new(sim_state, handle, new_region) {
+            sim_state.ghosts.insert(ghost.object.uuid, ghost);
+        }
+
         sim_state.bodies.remove(
-            *handle,
+            handle,
             &mut sim_state.islands,
             &mut sim_state.colliders,
             &mut sim_state.impulse_joints,
             &mut sim_state.multibody_joints,
             true,
         );
-        if let Some(uuid) = sim_state.body2uuid.remove(handle) {
+        if let Some(uuid) = sim_state.body2uuid.remove(&handle) {
             sim_state.uuid2body.remove(&uuid);
+            sim_state.vehicles.remove(&uuid);
         }
     }
 
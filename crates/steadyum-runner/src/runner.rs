@@ -1,45 +1,222 @@
+use crate::characters::{move_characters, RegionCharacter};
 use crate::cli::CliArgs;
-use crate::connected_components::calculate_connected_components;
+use crate::collision_layers::{collision_groups, collision_layer, unwatched_collision_groups};
+use crate::connected_components::{calculate_connected_components, ConnectedComponent};
+use crate::domain::enforce_domain;
+use crate::fluids::{send_escaped_particles, step_fluid, RegionFluid};
+use crate::force_generators::apply_force_generators;
+use crate::freeze::update_frozen_bodies;
+use crate::joints::{
+    joint_assignments, replace_joint_ghost, resolve_pending_joints, update_joint_ghosts, JointGhost,
+};
 use crate::neighbors::Neighbors;
+use crate::projectiles::{update_projectiles, RegionProjectile};
 use crate::region_assignment::{
     apply_and_send_region_assignments, calculate_region_assignments, RegionAssignments,
 };
+#[cfg(feature = "scripting")]
+use crate::scripting::RegionScript;
+use crate::sensors::intersection_events;
+use crate::spawn::SpawnSubscriptions;
+use crate::static_bodies::{report_static_bodies, update_static_bodies, RegionStaticBodies};
+use crate::vehicles::{update_vehicles, vehicle_assignments, RegionVehicle};
 use crate::watch::{
-    compute_watch_data, init_watched_neighbors, read_watched_objects, WatchedObject, MAIN_GROUP,
-    WATCH_GROUP,
+    compute_watch_data, init_watched_neighbors, joint_watched_neighbors, read_watched_objects,
+    update_watched_bodies, watch_collider, WatchSubscriptions, WatchedObject,
 };
 use crate::{AppState, RegionState};
 use futures::TryFutureExt;
-use log::info;
 use rapier::data::Coarena;
-use rapier::parry::partitioning::Qbvh;
+use rapier::parry::partitioning::{Qbvh, QbvhUpdateWorkspace};
 use rapier::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::time::Duration;
+use steadyum_api_types::admission::RejectedBody;
+use steadyum_api_types::collision::CollisionMatrix;
 use steadyum_api_types::kinematic::KinematicAnimations;
-use steadyum_api_types::messages::{BodyAssignment, RunnerMessage};
+use steadyum_api_types::messages::{
+    BodyAssignment, BodyInput, BodyOwnershipUpdate, ImpulseJointAssignment, RegionEvents,
+    RunnerMessage, SimulationEvent,
+};
 use steadyum_api_types::objects::{
-    ClientBodyObject, ClientBodyObjectSet, ColdBodyObject, WarmBodyObject, WatchedObjects,
+    ClientBodyObject, ClientBodyObjectSet, ClientColdBodyObject, ClientGhostBodyObject,
+    ColdBodyObject, RegionClientBodyObjectSet, WarmBodyObject, WatchedObjects,
+};
+use steadyum_api_types::partitionner::{
+    BodyCounts, PhysicsStats, RegionProfile, SceneExport, SceneSettings, SceneUuid, StepTimings,
+    NUM_INTERNAL_STEPS,
+};
+use steadyum_api_types::region_db::{AsyncPartitionnerServer, PartitionnerError};
+use steadyum_api_types::serialization::{
+    deserialize, serialize, serialize_region_client_objects, serialize_watched_objects,
+    EncodedClientBodyObjectSet, QuantizedParticleSet, STREAMED_OBJECT_SET_ENCODING,
 };
-use steadyum_api_types::partitionner::{SceneUuid, NUM_INTERNAL_STEPS};
-use steadyum_api_types::region_db::AsyncPartitionnerServer;
-use steadyum_api_types::serialization::{deserialize, serialize};
 use steadyum_api_types::simulation::SimulationBounds;
-use steadyum_api_types::zenoh::{runner_zenoh_commands_key, ZenohContext};
+use steadyum_api_types::store::{client_objects_key, particles_key, watch_set_key};
+use steadyum_api_types::trajectory::{TrajectoryPoint, TrajectoryUpdate};
+use steadyum_api_types::zenoh::{
+    body_ownership_key, client_objects_stream_key, events_key, put_serialized,
+    runner_zenoh_commands_key, scene_export_key, trajectory_key, watch_stream_key, ZenohContext,
+};
+use tracing::{debug, info, info_span, warn};
 use uuid::Uuid;
 use zenoh::config::WhatAmI;
 use zenoh::prelude::r#async::AsyncResolve;
 use zenoh::prelude::SplitBuffer;
 use zenoh::subscriber::Reliability;
 
+/// The bodies watched from the neighbor regions, in a Qbvh updated incrementally from one
+/// step to the next.
 pub struct QueryableWatchedObjects {
     pub qbvh: Qbvh<usize>,
+    /// The region and AABB of each leaf of `qbvh`. The free leaves aren’t in `qbvh`.
     pub objects: Vec<(SimulationBounds, Aabb)>,
+    leaves: HashMap<(SimulationBounds, Uuid), usize>,
+    free_leaves: Vec<usize>,
+    workspace: QbvhUpdateWorkspace,
+}
+
+impl Default for QueryableWatchedObjects {
+    fn default() -> Self {
+        let mut qbvh = Qbvh::new();
+        // Start with a root, so the tree can be queried before any body is watched.
+        qbvh.clear_and_rebuild(std::iter::empty(), 0.0);
+        Self {
+            qbvh,
+            objects: vec![],
+            leaves: HashMap::new(),
+            free_leaves: vec![],
+            workspace: QbvhUpdateWorkspace::default(),
+        }
+    }
+}
+
+impl QueryableWatchedObjects {
+    /// Replaces the watched bodies by the ones of `watched`.
+    ///
+    /// Only the leaves of the bodies that moved, appeared, or left the watch sets are updated,
+    /// instead of rebuilding the whole tree.
+    pub fn update(&mut self, watched: &[(WatchedObjects, SimulationBounds)]) {
+        let mut seen = HashSet::new();
+
+        for (objs, region) in watched {
+            for object in &objs.objects {
+                let key = (*region, object.uuid);
+                seen.insert(key);
+
+                let leaf = match self.leaves.get(&key) {
+                    Some(leaf) if self.objects[*leaf].1 == object.aabb => continue,
+                    Some(leaf) => *leaf,
+                    None => {
+                        let leaf = self.free_leaves.pop().unwrap_or_else(|| {
+                            self.objects.push((*region, object.aabb));
+                            self.objects.len() - 1
+                        });
+                        self.leaves.insert(key, leaf);
+                        leaf
+                    }
+                };
+
+                self.objects[leaf] = (*region, object.aabb);
+                self.qbvh.pre_update_or_insert(leaf);
+            }
+        }
+
+        self.leaves.retain(|key, leaf| {
+            if seen.contains(key) {
+                true
+            } else {
+                self.qbvh.remove(*leaf);
+                self.free_leaves.push(*leaf);
+                false
+            }
+        });
+
+        let objects = &self.objects;
+        self.qbvh
+            .refit(0.0, &mut self.workspace, |leaf| objects[*leaf].1);
+        self.qbvh.rebalance(0.0, &mut self.workspace);
+    }
+}
+
+/// Number of steps a body handed off to another region is still reported to clients.
+const NUM_GHOST_STEPS: u32 = 2;
+/// Bodies that moved less than this since they were last streamed to the clients aren’t
+/// streamed again.
+const CLIENT_MOTION_THRESHOLD: Real = 1.0e-3;
+/// Every how many streamed sets all the bodies are streamed, for the clients that just joined.
+const CLIENT_KEYFRAME_PERIOD: u32 = 10;
+
+/// A body handed off to another region, still reported to clients for a few steps.
+pub struct GhostBody {
+    pub object: ClientBodyObject,
+    pub new_region: SimulationBounds,
+    pub handoff_timestamp: u64,
+    pub steps_left: u32,
+}
+
+impl GhostBody {
+    /// Creates the ghost of a body about to leave the region, if it was ever sent to clients.
+    pub fn new(
+        sim_state: &SimulationState,
+        handle: RigidBodyHandle,
+        new_region: SimulationBounds,
+    ) -> Option<Self> {
+        let body = sim_state.bodies.get(handle)?;
+        let uuid = *sim_state.body2uuid.get(&handle)?;
+        let attrs = sim_state.bodies_attributes.get(handle.0)?;
+        let (shape_hash, _) = attrs.shape_hash?;
+        let timestamp = sim_state.step_id * NUM_INTERNAL_STEPS;
+        let warm = WarmBodyObject::from_body(body, timestamp);
+
+        Some(Self {
+            object: ClientBodyObject {
+                uuid,
+                position: warm.position,
+                linvel: warm.linvel,
+                angvel: warm.angvel,
+                shape_hash,
+                sleep_start_frame: None,
+                epoch: attrs.epoch,
+            },
+            new_region,
+            handoff_timestamp: timestamp,
+            steps_left: NUM_GHOST_STEPS,
+        })
+    }
+
+    /// The ghost at `timestamp`, extrapolated from its velocities at the time of the handoff.
+    fn client_object(&self, timestamp: u64, dt: Real) -> ClientGhostBodyObject {
+        let elapsed = timestamp.saturating_sub(self.handoff_timestamp) as Real * dt;
+        let mut object = self.object;
+        object.position = Isometry::from_parts(
+            (object.position.translation.vector + object.linvel * elapsed).into(),
+            Rotation::new(object.angvel * elapsed) * object.position.rotation,
+        );
+
+        ClientGhostBodyObject {
+            object,
+            new_region: self.new_region,
+        }
+    }
 }
 
 #[derive(Default, Clone, Copy)]
 pub struct BodyAttributes {
     pub sleep_step_id: Option<u64>,
+    /// The shape hash of the body, and the timestamp at which it was first sent to clients.
+    pub shape_hash: Option<(u64, u64)>,
+    /// See [`BodyAssignment::epoch`].
+    pub epoch: u32,
+}
+
+impl SimulationState {
+    /// The ownership epoch of the body `handle`.
+    pub fn epoch(&self, handle: RigidBodyHandle) -> u32 {
+        self.bodies_attributes
+            .get(handle.0)
+            .map_or(0, |attrs| attrs.epoch)
+    }
 }
 
 #[derive(Default)]
@@ -60,27 +237,24 @@ pub struct SimulationState {
     pub ccd_solver: CCDSolver,
     pub physics_pipeline: PhysicsPipeline,
     pub body2animations: Coarena<KinematicAnimations>,
+    pub body2user_data: Coarena<Vec<u8>>,
+    pub settings: SceneSettings,
     pub body2uuid: HashMap<RigidBodyHandle, Uuid>,
     pub uuid2body: HashMap<Uuid, RigidBodyHandle>,
     pub sim_bounds: SimulationBounds,
     pub watched_objects: HashMap<RigidBodyHandle, WatchedObject>,
     pub bodies_attributes: Coarena<BodyAttributes>,
-}
-
-#[derive(Copy, Clone, Debug, Default)]
-struct MainLoopTimings {
-    pub num_bodies: usize,
-    pub waiting_acks: f32,
-    pub read_watch_sets: f32,
-    pub apply_watch_sets: f32,
-    pub resolve_assignments: f32,
-    pub message_processing: f32,
-    pub simulation_step: f32,
-    pub connected_components: f32,
-    pub data_and_watch_list: f32,
-    pub release_reassign: f32,
-    pub ack: f32,
-    pub loop_time: f32,
+    pub ghosts: HashMap<Uuid, GhostBody>,
+    /// Joints waiting for one of their bodies to be assigned to this region.
+    pub pending_joints: Vec<ImpulseJointAssignment>,
+    pub joint_ghosts: HashMap<Uuid, JointGhost>,
+    /// The connected components of sleeping bodies, reused until one of them wakes up.
+    pub sleeping_components: Vec<ConnectedComponent>,
+    pub static_bodies: RegionStaticBodies,
+    pub fluid: RegionFluid,
+    pub characters: HashMap<RigidBodyHandle, RegionCharacter>,
+    pub vehicles: HashMap<Uuid, RegionVehicle>,
+    pub projectiles: HashMap<RigidBodyHandle, RegionProjectile>,
 }
 
 pub async fn run_simulation(reg_state: RegionState) -> anyhow::Result<()> {
@@ -88,8 +262,11 @@ pub async fn run_simulation(reg_state: RegionState) -> anyhow::Result<()> {
     let mut neighbors = Neighbors::new(&reg_state.app.zenoh);
     let mut sim_state = SimulationState::default();
     sim_state.sim_bounds = reg_state.bounds;
-    sim_state.scene = reg_state.app.scene;
-    sim_state.gravity = Vector::y() * (-9.81);
+    sim_state.scene = reg_state.scene;
+    sim_state.settings = reg_state.scene_state.settings.read().await.clone();
+    sim_state.gravity = sim_state.settings.gravity.unwrap_or(Vector::y() * (-9.81));
+    // Needed for the broad-phase, narrow-phase and solver timings of the step profiles.
+    sim_state.physics_pipeline.counters.enable();
 
     // Subscribe to command queue.
     let mut watch_iteration_id = 0;
@@ -98,7 +275,77 @@ pub async fn run_simulation(reg_state: RegionState) -> anyhow::Result<()> {
      * Wait for region assignment (blocking).
      */
     let mut pending_assignments = vec![];
-    let mut static_bodies_added = 0;
+
+    // Publisher pushing our client objects to anyone streaming them.
+    let client_objects_publisher = reg_state
+        .app
+        .zenoh
+        .session
+        .declare_publisher(client_objects_stream_key(
+            sim_state.scene,
+            &sim_state.sim_bounds,
+        ))
+        .res_async()
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+
+    // Publisher pushing our watch set to the neighbors watching us.
+    let watch_publisher = reg_state
+        .app
+        .zenoh
+        .session
+        .declare_publisher(watch_stream_key(sim_state.scene, &sim_state.sim_bounds))
+        .res_async()
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+    // Publisher telling the partitionner which bodies this region started simulating.
+    let ownership_publisher = reg_state
+        .app
+        .zenoh
+        .session
+        .declare_publisher(body_ownership_key(sim_state.scene, &sim_state.sim_bounds))
+        .res_async()
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+    // Publisher streaming the events of this region, e.g., the sensor intersections.
+    let events_publisher = reg_state
+        .app
+        .zenoh
+        .session
+        .declare_publisher(events_key(sim_state.scene, &sim_state.sim_bounds))
+        .res_async()
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+    // Publisher streaming the poses of the traced bodies to the partitionner.
+    let trajectory_publisher = reg_state
+        .app
+        .zenoh
+        .session
+        .declare_publisher(trajectory_key(sim_state.scene, &sim_state.sim_bounds))
+        .res_async()
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+    let (collision_snd, collision_rcv) = rapier::crossbeam::channel::unbounded();
+    let (contact_force_snd, _contact_force_rcv) = rapier::crossbeam::channel::unbounded();
+    let event_handler = ChannelEventCollector::new(collision_snd, contact_force_snd);
+    let mut watch_subscriptions = WatchSubscriptions::new(&reg_state.app.zenoh, sim_state.scene);
+    let spawn_subscriptions =
+        SpawnSubscriptions::new(&reg_state.app.zenoh, sim_state.scene, sim_state.sim_bounds)
+            .await?;
+    #[cfg(feature = "scripting")]
+    let mut region_script = RegionScript::default();
+    // The step the neighbors computed the watch sets we read during this step.
+    let mut previous_step_id = 0;
+    // The watch set sent again to the neighbors when the auxiliary work is skipped.
+    let mut last_watched = WatchedObjects::default();
+    // The version of the last watch set pushed with its bodies.
+    let mut pushed_watch_version = None;
+    let mut client_motion = ClientMotionFilter::default();
+    // The bodies of the region at the end of the last step where one of them moved, or was
+    // added or removed, and that step.
+    let mut last_active_body_counts = BodyCounts::default();
+    let mut last_active_step = 0;
+    let mut queryable_watched_objects = QueryableWatchedObjects::default();
 
     /*
      * Main runner loop.
@@ -107,19 +354,22 @@ pub async fn run_simulation(reg_state: RegionState) -> anyhow::Result<()> {
         &reg_state.app,
         &reg_state.app.main_partitionner,
         &mut neighbors,
+        sim_state.scene,
         sim_state.sim_bounds,
+        sim_state.settings.watch_mode,
     )
     .await;
 
     'stop: while !sim_state.killed {
-        let mut timings = MainLoopTimings::default();
+        let mut timings = StepTimings::default();
         let loop_time = std::time::Instant::now();
 
         let t0 = std::time::Instant::now();
 
         // Process messages.
         while let Ok(message) = reg_state.reg_rcv.recv().await {
-            if let RunnerMessage::Step { step_id } = &message {
+            if let RunnerMessage::Step { step_id, .. } = &message {
+                previous_step_id = sim_state.step_id;
                 sim_state.step_id = *step_id;
                 break;
             }
@@ -138,18 +388,16 @@ pub async fn run_simulation(reg_state: RegionState) -> anyhow::Result<()> {
             }
         }
 
-        // Add any missing static body.
+        // Covers the step of this region, up to its ack.
+        let step_span = match &*reg_state.scene_state.step_span.read().await {
+            Some(parent) => info_span!(parent: parent, "region.step", region = ?reg_state.bounds),
+            None => info_span!("region.step", region = ?reg_state.bounds),
+        };
+
+        // Add the static bodies our bodies are getting close to, and remove the far ones.
         {
-            let static_bodies_in_scene = reg_state.app.static_bodies.read().await;
-            if static_bodies_in_scene.len() > static_bodies_added {
-                // info!(
-                //     "Adding static bodies to simulation: {}",
-                //     static_bodies_in_scene.len() - static_bodies_added
-                // );
-                pending_assignments
-                    .extend_from_slice(&static_bodies_in_scene[static_bodies_added..]);
-                static_bodies_added = static_bodies_in_scene.len();
-            }
+            let static_bodies = reg_state.scene_state.static_bodies.read().await;
+            update_static_bodies(&mut sim_state, &static_bodies, &mut pending_assignments);
         }
 
         // info!(
@@ -161,43 +409,103 @@ pub async fn run_simulation(reg_state: RegionState) -> anyhow::Result<()> {
 
         let t0 = std::time::Instant::now();
         watch_iteration_id += 1;
-        let watched: Vec<(WatchedObjects, SimulationBounds)> =
-            read_watched_objects(&reg_state.app, &watched_neighbors).await;
+        let joint_neighbors = joint_watched_neighbors(
+            &reg_state.app,
+            &reg_state.app.main_partitionner,
+            &mut neighbors,
+            &sim_state,
+            &watched_neighbors,
+        )
+        .await;
+        let watched: Vec<(WatchedObjects, SimulationBounds)> = read_watched_objects(
+            &reg_state.app,
+            sim_state.scene,
+            &mut watch_subscriptions,
+            previous_step_id,
+            watched_neighbors.iter().chain(&joint_neighbors),
+        )
+        .await;
         timings.read_watch_sets = t0.elapsed().as_secs_f32();
 
         let t0 = std::time::Instant::now();
-        let mut watched_objects_tree = Qbvh::new();
-
-        let all_watched_objects: Vec<_> = watched
-            .iter()
-            .flat_map(|(objs, region)| objs.objects.iter().map(|o| (*region, o.1)))
-            .collect();
-        watched_objects_tree.clear_and_rebuild(
-            all_watched_objects
-                .iter()
-                .enumerate()
-                .map(|(i, (_, aabb))| (i, *aabb)),
-            0.0,
-        );
-
-        let queryable_watched_objects = QueryableWatchedObjects {
-            qbvh: watched_objects_tree,
-            objects: all_watched_objects,
-        };
+        queryable_watched_objects.update(&watched);
+        update_watched_bodies(&mut sim_state, &watched, watch_iteration_id);
         timings.apply_watch_sets = t0.elapsed().as_secs_f32();
 
         let t0 = std::time::Instant::now();
-        resolve_pending_assignments(&mut sim_state, &mut pending_assignments);
+        spawn_subscriptions.read_spawned(&mut pending_assignments);
+        spawn_subscriptions.remove_despawned(&mut sim_state);
+        #[cfg(feature = "scripting")]
+        {
+            let module = reg_state.scene_state.script.read().await.clone();
+            region_script.run(module, &mut sim_state, &mut pending_assignments);
+        }
+        let (new_bodies, rejected_bodies) =
+            resolve_pending_assignments(&mut sim_state, &mut pending_assignments);
+        if !new_bodies.is_empty() || !rejected_bodies.is_empty() {
+            let update = BodyOwnershipUpdate {
+                scene: sim_state.scene,
+                region: sim_state.sim_bounds,
+                bodies: new_bodies,
+                rejected: rejected_bodies.clone(),
+            };
+            if let Err(e) = steadyum_api_types::zenoh::put(&ownership_publisher, &update).await {
+                tracing::error!("Failed to publish the body ownership update: {e}");
+            }
+        }
+        resolve_pending_joints(&mut sim_state, watch_iteration_id);
+        update_joint_ghosts(&mut sim_state, &watched, watch_iteration_id);
         timings.resolve_assignments = t0.elapsed().as_secs_f32();
 
         let mut region_assignments = RegionAssignments::default();
 
         let t0 = std::time::Instant::now();
 
-        for sub_step_id in 0..NUM_INTERNAL_STEPS {
+        // Read at each step, the volumes can be frozen and unfrozen, and the force generators
+        // replaced, while the scene runs.
+        {
+            let settings = reg_state.scene_state.settings.read().await;
+            sim_state
+                .settings
+                .frozen_volumes
+                .clone_from(&settings.frozen_volumes);
+            sim_state
+                .settings
+                .force_generators
+                .clone_from(&settings.force_generators);
+        }
+        update_frozen_bodies(&mut sim_state);
+
+        // Read at each step, the solver options can change while the scene runs, and the
+        // region can get closer to, or further from, the viewers.
+        let simulation_params = {
+            let settings = reg_state.scene_state.settings.read().await;
+            let far_regions = reg_state.scene_state.far_regions.read().await;
+            match settings.region_lod {
+                Some(lod) if far_regions.contains(&sim_state.sim_bounds) => lod.far_params,
+                _ => settings.simulation_params,
+            }
+        };
+        let substep_params = simulation_params.substep_params(&sim_state.params);
+
+        // The particles of the neighbors close to our borders, as of their previous step.
+        let watched_particles: Vec<_> = watched
+            .iter()
+            .flat_map(|(objs, _)| objs.particles.iter().copied())
+            .collect();
+        sim_state.fluid.insert_pending();
+        // The fluid particles, the characters and the wheels of the vehicles collide with the
+        // colliders through the query pipeline.
+        let update_queries = sim_state.settings.fluid.is_some()
+            || !sim_state.characters.is_empty()
+            || !sim_state.vehicles.is_empty();
+
+        for sub_step_id in 0..NUM_INTERNAL_STEPS * simulation_params.substeps as u64 {
+            apply_force_generators(&mut sim_state, substep_params.dt);
+            update_vehicles(&mut sim_state, substep_params.dt);
             sim_state.physics_pipeline.step(
                 &sim_state.gravity,
-                &sim_state.params,
+                &substep_params,
                 &mut sim_state.islands,
                 &mut sim_state.broad_phase,
                 &mut sim_state.narrow_phase,
@@ -206,51 +514,164 @@ pub async fn run_simulation(reg_state: RegionState) -> anyhow::Result<()> {
                 &mut sim_state.impulse_joints,
                 &mut sim_state.multibody_joints,
                 &mut sim_state.ccd_solver,
-                None,
-                &(),
+                update_queries.then_some(&mut sim_state.query_pipeline),
                 &(),
+                &event_handler,
             );
+            step_fluid(&mut sim_state, &watched_particles, substep_params.dt);
+            move_characters(&mut sim_state, substep_params.dt);
+
+            let counters = &sim_state.physics_pipeline.counters;
+            timings.broad_phase += counters.broad_phase_time() as f32 / 1000.0;
+            timings.narrow_phase += counters.narrow_phase_time() as f32 / 1000.0;
+            timings.solver += counters.solver_time() as f32 / 1000.0;
 
-            let current_physics_time = (reg_state.step_id() * NUM_INTERNAL_STEPS + sub_step_id + 1)
-                as Real
-                * sim_state.params.dt;
+            let current_physics_time = (reg_state.step_id() * NUM_INTERNAL_STEPS) as Real
+                * sim_state.params.dt
+                + (sub_step_id + 1) as Real * substep_params.dt
+                + reg_state.app.time_origin;
 
             // Update animations.
             for (handle, animations) in sim_state.body2animations.iter() {
-                if animations.linear.is_none() && animations.angular.is_none() {
+                if animations.is_empty() {
                     // Nothing to animate.
                     continue;
                 }
 
                 // println!("Animating: {:?}.", handle);
                 if let Some(rb) = sim_state.bodies.get_mut(RigidBodyHandle(handle)) {
-                    let new_pos = animations.eval(current_physics_time, *rb.position());
-                    // TODO: what if it’s a velocity-based kinematic body?
-                    // println!("prev: {:?}, new: {:?}", rb.position(), new_pos);
-                    rb.set_next_kinematic_position(new_pos);
+                    if rb.body_type() == RigidBodyType::KinematicVelocityBased {
+                        let (linvel, angvel) = animations.eval_velocities(current_physics_time);
+                        rb.set_linvel(linvel, true);
+                        rb.set_angvel(angvel, true);
+                    } else {
+                        let new_pos = animations.eval(current_physics_time, *rb.position());
+                        // println!("prev: {:?}, new: {:?}", rb.position(), new_pos);
+                        rb.set_next_kinematic_position(new_pos);
+                    }
                 }
             }
         }
 
         timings.simulation_step = t0.elapsed().as_secs_f32();
 
-        let num_steps_run = NUM_INTERNAL_STEPS;
+        let collision_events: Vec<_> = collision_rcv.try_iter().collect();
+        let mut events = intersection_events(&sim_state, &collision_events);
+        events.extend(update_projectiles(&mut sim_state, &collision_events));
+        events.extend(enforce_domain(&mut sim_state));
+        events.extend(
+            rejected_bodies
+                .iter()
+                .map(|rejected| SimulationEvent::BodyRejected {
+                    body: rejected.uuid,
+                    reason: rejected.reason,
+                }),
+        );
+        if !events.is_empty() {
+            let region_events = RegionEvents {
+                region: sim_state.sim_bounds,
+                step_id: sim_state.step_id,
+                events,
+            };
+            if let Err(e) = steadyum_api_types::zenoh::put(&events_publisher, &region_events).await
+            {
+                tracing::error!("Failed to publish the region events: {e}");
+            }
+        }
+
+        let points = {
+            let traced_bodies = reg_state.scene_state.traced_bodies.read().await;
+            trajectory_points(&sim_state, &traced_bodies)
+        };
+        if !points.is_empty() {
+            let update = TrajectoryUpdate {
+                scene: sim_state.scene,
+                region: sim_state.sim_bounds,
+                points,
+            };
+            if let Err(e) = steadyum_api_types::zenoh::put(&trajectory_publisher, &update).await {
+                tracing::error!("Failed to publish the trajectories: {e}");
+            }
+        }
+
+        // The auxiliary work only runs every few steps on huge regions, predicting the motion of
+        // the bodies over the whole period. In between, the bodies stay in this region and the
+        // neighbors get the previous watch set again.
+        let auxiliary_work_period = sim_state.settings.auxiliary_work_period();
+        let run_auxiliary_work = sim_state.step_id % auxiliary_work_period == 0;
+        timings.auxiliary_work_skipped = !run_auxiliary_work;
+        let num_steps_run = NUM_INTERNAL_STEPS * auxiliary_work_period;
 
         let t0 = std::time::Instant::now();
 
-        let connected_components =
-            calculate_connected_components(&sim_state, num_steps_run as usize);
-        region_assignments = calculate_region_assignments(
-            &mut sim_state,
-            connected_components,
-            &queryable_watched_objects,
-        );
+        if run_auxiliary_work {
+            let connected_components =
+                calculate_connected_components(&mut sim_state, num_steps_run as usize);
+            region_assignments = calculate_region_assignments(
+                &mut sim_state,
+                connected_components,
+                &queryable_watched_objects,
+            );
+        }
         timings.connected_components = t0.elapsed().as_secs_f32();
 
         let t0 = std::time::Instant::now();
 
-        let client_objects = compute_client_objects(&mut sim_state, &[]);
-        let watched = compute_watch_data(&sim_state, num_steps_run as usize, &region_assignments);
+        // Don’t bother building the client objects if no one is looking at this region, and
+        // build them less often if all the clients looking at it are far away.
+        let client_report_period = reg_state
+            .app
+            .client_report_period(sim_state.scene, &sim_state.sim_bounds);
+        let has_interested_client = client_report_period.is_some();
+        let report_client_objects = client_report_period
+            .map(|period| sim_state.step_id % (auxiliary_work_period * period) == 0)
+            .unwrap_or(false);
+        // The characters are streamed at every step, between the reports of the whole region,
+        // so the clients see them move with the lowest latency.
+        let report_characters =
+            has_interested_client && !report_client_objects && !sim_state.characters.is_empty();
+        let client_objects = if report_client_objects {
+            let mut client_objects = compute_client_objects(&mut sim_state, &[]);
+            let static_bodies = reg_state.scene_state.static_bodies.read().await;
+            report_static_bodies(
+                &sim_state,
+                &static_bodies,
+                &mut client_objects.objects,
+                &mut client_objects.cold_objects,
+            );
+            Some(client_objects)
+        } else {
+            None
+        };
+        let physics_stats = compute_physics_stats(&sim_state);
+        let body_counts = compute_body_counts(&sim_state);
+        if body_counts.dynamic > body_counts.sleeping
+            || body_counts.kinematic > 0
+            || body_counts != last_active_body_counts
+        {
+            last_active_body_counts = body_counts;
+            last_active_step = sim_state.step_id;
+        }
+        sim_state.ghosts.retain(|_, ghost| {
+            ghost.steps_left = ghost.steps_left.saturating_sub(1);
+            ghost.steps_left > 0
+        });
+        let watched = if run_auxiliary_work {
+            let mut watched =
+                compute_watch_data(&sim_state, num_steps_run as usize, &region_assignments);
+            watched.version = if watched.same_bodies(&last_watched) {
+                last_watched.version
+            } else {
+                last_watched.version + 1
+            };
+            last_watched = watched;
+            last_watched.clone()
+        } else {
+            WatchedObjects {
+                step_id: sim_state.step_id,
+                ..last_watched.clone()
+            }
+        };
 
         timings.data_and_watch_list = t0.elapsed().as_secs_f32();
 
@@ -265,19 +686,128 @@ pub async fn run_simulation(reg_state: RegionState) -> anyhow::Result<()> {
             //     sim_state.sim_bounds,
             //     watched.objects.len()
             // );
-            reg_state
-                .app
-                .watch_sets
-                .insert(sim_state.sim_bounds, watched);
-            reg_state
-                .app
-                .client_object_sets
-                .insert(sim_state.sim_bounds, client_objects);
+            let time_scale = reg_state.scene_state.settings.read().await.time_scale;
+            // NOTE: only stream the cold objects that changed during this step. Subscribers
+            //       that missed some can still query the full set from the storage.
+            //       The full set is stored, but only the bodies that moved are streamed.
+            //       They are streamed as plain-old-data with the `pod-framing` feature, the
+            //       partitionner re-encodes them for the viewers.
+            let streamed_set = if let Some(client_objects) = &client_objects {
+                let (objects, partial) =
+                    client_motion.filter(&client_objects.objects, &character_uuids(&sim_state));
+                Some(ClientBodyObjectSet {
+                    timestamp: client_objects.timestamp,
+                    objects,
+                    cold_objects: client_objects
+                        .cold_objects
+                        .iter()
+                        .filter(|cold| cold.timestamp >= client_objects.timestamp)
+                        .cloned()
+                        .collect(),
+                    ghosts: client_objects.ghosts.clone(),
+                    partial,
+                })
+            } else if report_characters {
+                Some(compute_character_client_objects(&sim_state))
+            } else {
+                None
+            };
+            let streamed = streamed_set.map(|set| RegionClientBodyObjectSet {
+                region: sim_state.sim_bounds,
+                set: EncodedClientBodyObjectSet::encode(
+                    set,
+                    &sim_state.sim_bounds,
+                    STREAMED_OBJECT_SET_ENCODING,
+                ),
+                time_scale,
+                stats: physics_stats,
+            });
+
+            // NOTE: the bodies of a watch set are only pushed once. The neighbors that missed
+            //       them query the full set from the storage.
+            let unchanged_watched =
+                (pushed_watch_version == Some(watched.version)).then(|| watched.unchanged());
+            pushed_watch_version = Some(watched.version);
+
+            // The watch set and the client objects are the largest payloads of the step.
+            let (watch_payload, streamed_payload) = rayon::join(
+                || serialize_watched_objects(unchanged_watched.as_ref().unwrap_or(&watched)),
+                || streamed.as_ref().map(serialize_region_client_objects),
+            );
+
+            let pushed = match watch_payload {
+                Ok(data) => put_serialized(&watch_publisher, data).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = pushed {
+                tracing::error!("Failed to push the watch set: {e}");
+            }
+            let app = &reg_state.app;
+            let region_key = (sim_state.scene, sim_state.sim_bounds);
+            let remote_store = !app.store.is_local();
+            if remote_store {
+                let watch_key = watch_set_key(sim_state.scene, &sim_state.sim_bounds);
+                if let Err(e) = app.store.put_value(&watch_key, &watched).await {
+                    tracing::error!("Failed to store the watch set: {e}");
+                }
+            }
+            app.watch_sets.insert(region_key, watched);
+
+            let streamed = match streamed_payload {
+                Some(Ok(data)) => put_serialized(&client_objects_publisher, data).await,
+                Some(Err(e)) => Err(e),
+                None => Ok(()),
+            };
+            if let Err(e) = streamed {
+                tracing::error!("Failed to stream client objects: {e}");
+            }
+
+            let client_key = client_objects_key(sim_state.scene, &sim_state.sim_bounds);
+            if let Some(client_objects) = client_objects {
+                if remote_store {
+                    if let Err(e) = app.store.put_value(&client_key, &client_objects).await {
+                        tracing::error!("Failed to store client objects: {e}");
+                    }
+                }
+                app.client_object_sets.insert(region_key, client_objects);
+            } else if !has_interested_client {
+                app.client_object_sets.remove(&region_key);
+                if remote_store {
+                    if let Err(e) = app.store.remove(&client_key).await {
+                        tracing::error!("Failed to remove client objects: {e}");
+                    }
+                }
+            }
+
+            if let Some(fluid) = &sim_state.settings.fluid {
+                let particles_key = particles_key(sim_state.scene, &sim_state.sim_bounds);
+                if report_client_objects {
+                    let particles = QuantizedParticleSet::encode(
+                        &sim_state.fluid.particles,
+                        fluid.particle_radius,
+                        &sim_state.sim_bounds,
+                        sim_state.step_id * NUM_INTERNAL_STEPS,
+                    );
+                    if remote_store {
+                        if let Err(e) = app.store.put_value(&particles_key, &particles).await {
+                            tracing::error!("Failed to store the fluid particles: {e}");
+                        }
+                    }
+                    app.particle_sets.insert(region_key, particles);
+                } else if !has_interested_client {
+                    app.particle_sets.remove(&region_key);
+                    if remote_store {
+                        if let Err(e) = app.store.remove(&particles_key).await {
+                            tracing::error!("Failed to remove the fluid particles: {e}");
+                        }
+                    }
+                }
+            }
 
             /*
              * Send objects to adjacent regions if assignment changed.
              */
-            apply_and_send_region_assignments(
+            if let Err(e) = apply_and_send_region_assignments(
                 &reg_state.app,
                 &mut sim_state,
                 &region_assignments,
@@ -285,7 +815,25 @@ pub async fn run_simulation(reg_state: RegionState) -> anyhow::Result<()> {
                 &reg_state.app.main_partitionner,
             )
             .await
-            .unwrap();
+            {
+                // Without a partitionner, the bodies leaving the region can’t be handed off.
+                if PartitionnerError::is_gone_error(&e) {
+                    return Err(e);
+                }
+                // The bodies are kept, and reassigned again at the next step.
+                tracing::error!("Failed to hand the bodies off: {e}");
+            }
+
+            if let Err(e) = send_escaped_particles(
+                &reg_state.app,
+                &mut sim_state,
+                &mut neighbors,
+                &reg_state.app.main_partitionner,
+            )
+            .await
+            {
+                tracing::error!("Failed to hand the fluid particles off: {e}");
+            }
         }
 
         timings.release_reassign = t0.elapsed().as_secs_f32();
@@ -297,12 +845,29 @@ pub async fn run_simulation(reg_state: RegionState) -> anyhow::Result<()> {
         reg_state
             .app
             .main_thread_snd
-            .send(RunnerMessage::Ack)
+            .send(RunnerMessage::Ack {
+                scene: sim_state.scene,
+                region: reg_state.bounds,
+                step_id: sim_state.step_id,
+            })
             .await?;
         timings.ack = t0.elapsed().as_secs_f32();
 
         timings.loop_time = loop_time.elapsed().as_secs_f32();
         timings.num_bodies = sim_state.bodies.len();
+        step_span.in_scope(|| debug!(?timings, "Region step done."));
+        reg_state.app.region_profiles.insert(
+            (sim_state.scene, reg_state.bounds),
+            RegionProfile {
+                region: reg_state.bounds,
+                runner: reg_state.app.uuid,
+                step_id: sim_state.step_id,
+                timings,
+                stats: physics_stats,
+                bodies: body_counts,
+                last_active_step,
+            },
+        );
 
         // info!("Runner {my_uuid} timings: {:?}", timings);
     }
@@ -313,29 +878,86 @@ pub async fn run_simulation(reg_state: RegionState) -> anyhow::Result<()> {
 }
 
 fn make_builders(
+    collision_matrix: &CollisionMatrix,
     cold_object: &ColdBodyObject,
     warm_object: WarmBodyObject,
 ) -> (RigidBodyBuilder, ColliderBuilder) {
-    let body = RigidBodyBuilder::new(cold_object.body_type)
+    let mut body = RigidBodyBuilder::new(cold_object.body_type)
         .position(warm_object.position)
         .linvel(warm_object.linvel)
         .angvel(warm_object.angvel);
-    let collider = ColliderBuilder::new(cold_object.shape.clone()).density(cold_object.density);
+    let mut collider = ColliderBuilder::new(cold_object.shape.clone())
+        .density(cold_object.density)
+        .collision_groups(collision_groups(
+            collision_matrix,
+            cold_object.collision_layer,
+        ));
+    if cold_object.is_sensor {
+        // Sensors only intersect the main colliders: the watch colliders attached to every body
+        // must not trigger them.
+        collider = collider
+            .sensor(true)
+            .collision_groups(unwatched_collision_groups(
+                collision_matrix,
+                cold_object.collision_layer,
+            ))
+            .active_events(ActiveEvents::COLLISION_EVENTS)
+            // Static and kinematic sensors must detect the kinematic bodies too.
+            .active_collision_types(ActiveCollisionTypes::all());
+    }
+    if cold_object.projectile.is_some() {
+        // Projectiles are small and fast, and their hits are reported.
+        body = body.ccd_enabled(true);
+        collider = collider.active_events(ActiveEvents::COLLISION_EVENTS);
+    }
     (body, collider)
 }
 
+/// Inserts the assigned bodies that can be simulated at the current step.
+///
+/// Returns the uuids of the dynamic bodies that just started being simulated by this region,
+/// and the bodies refused by its admission policy.
 fn resolve_pending_assignments(
     sim_state: &mut SimulationState,
     pending_assignments: &mut Vec<BodyAssignment>,
-) {
-    pending_assignments.retain(|data| {
+) -> (Vec<Uuid>, Vec<RejectedBody>) {
+    let mut new_bodies = vec![];
+    let mut rejected_bodies = vec![];
+
+    // NOTE: the assignments from different neighbors arrive in any order. Sort them so
+    //       bodies are always inserted in the same order, and given the same handles.
+    pending_assignments.sort_by_key(|data| data.uuid);
+    pending_assignments.retain_mut(|data| {
         if data.warm.timestamp > sim_state.step_id {
             println!("{} > {}", data.warm.timestamp, sim_state.step_id);
             // This body lives in the future, we can’t simulate it for now.
             return true;
         }
 
+        if let Err(reason) = sim_state.settings.admission.admit(data) {
+            warn!("Rejecting body {:?}: {reason:?}.", data.uuid);
+            if !sim_state.uuid2body.contains_key(&data.uuid) {
+                sim_state.vehicles.remove(&data.uuid);
+            }
+            rejected_bodies.push(RejectedBody {
+                uuid: data.uuid,
+                reason,
+            });
+            return false;
+        }
+
         if let Some(handle) = sim_state.uuid2body.get(&data.uuid) {
+            if !sim_state.watched_objects.contains_key(handle)
+                && sim_state.epoch(*handle) >= data.epoch
+            {
+                // We already simulate this body, e.g., the assignment was delivered twice.
+                warn!(
+                    "Discarding a stale copy of body {:?} (epoch {}).",
+                    data.uuid, data.epoch
+                );
+                return false;
+            }
+
             sim_state.bodies.remove(
                 *handle,
                 &mut sim_state.islands,
@@ -344,46 +966,62 @@ fn resolve_pending_assignments(
                 &mut sim_state.multibody_joints,
                 true,
             );
+            // This might have been the mirror of the body, watched from its previous region.
             sim_state.watched_objects.remove(handle);
+            sim_state.body2uuid.remove(handle);
         }
 
-        let (body, collider) = make_builders(&data.cold, data.warm);
-        let watch_shape_radius = collider.shape.compute_local_bounding_sphere().radius * 1.1;
+        let (body, collider) =
+            make_builders(&sim_state.settings.collision_matrix, &data.cold, data.warm);
+        let watch_collider = watch_collider(&collider.shape, &sim_state.settings.watch_margins);
         let body_handle = sim_state.bodies.insert(body);
         sim_state
             .colliders
             .insert_with_parent(collider, body_handle, &mut sim_state.bodies);
-        let watch_collider = ColliderBuilder::ball(watch_shape_radius)
-            .density(0.0)
-            .collision_groups(InteractionGroups::new(
-                // We don’t care about watched objects intersecting each others.
-                WATCH_GROUP,
-                MAIN_GROUP,
-            ))
-            // Watched objects don’t generate forces.
-            .solver_groups(InteractionGroups::none());
         sim_state
             .colliders
             .insert_with_parent(watch_collider, body_handle, &mut sim_state.bodies);
         sim_state.body2uuid.insert(body_handle, data.uuid.clone());
         sim_state.uuid2body.insert(data.uuid, body_handle);
+        sim_state
+            .bodies_attributes
+            .ensure_element_exist(body_handle.0, BodyAttributes::default());
+        let attrs = sim_state.bodies_attributes.get_mut(body_handle.0).unwrap();
+        attrs.epoch = data.epoch;
         sim_state
             .body2animations
             .insert(body_handle.0, data.cold.animations.clone());
+        sim_state
+            .body2user_data
+            .insert(body_handle.0, data.cold.user_data.clone());
+        match data.cold.character {
+            Some(params) => {
+                let character = RegionCharacter::new(params, data.warm.linvel, &sim_state.gravity);
+                sim_state.characters.insert(body_handle, character);
+            }
+            None => {
+                sim_state.characters.remove(&body_handle);
+            }
+        }
+        match data.cold.projectile {
+            Some(params) => {
+                let projectile = RegionProjectile::new(params, sim_state.step_id);
+                sim_state.projectiles.insert(body_handle, projectile);
+            }
+            None => {
+                sim_state.projectiles.remove(&body_handle);
+            }
+        }
+        replace_joint_ghost(sim_state, data.uuid, body_handle);
 
-        // for data in impulse_joints {
-        //     if let (Some(handle1), Some(handle2)) = (
-        //         sim_state.uuid2body.get(&data.body1),
-        //         sim_state.uuid2body.get(&data.body2),
-        //     ) {
-        //         sim_state
-        //             .impulse_joints
-        //             .insert(*handle1, *handle2, data.joint, true);
-        //     }
-        // }
+        if data.cold.moves_between_regions() {
+            new_bodies.push(data.uuid);
+        }
 
         false
     });
+
+    (new_bodies, rejected_bodies)
 }
 
 async fn process_message(
@@ -394,7 +1032,7 @@ async fn process_message(
     pending_assignments: &mut Vec<BodyAssignment>,
 ) -> anyhow::Result<()> {
     match message {
-        RunnerMessage::Exit => {
+        RunnerMessage::Exit { .. } => {
             sim_state.killed = true;
         }
         RunnerMessage::AssignIsland {
@@ -409,26 +1047,238 @@ async fn process_message(
             //     impulse_joints.len()
             // );
             pending_assignments.append(&mut bodies);
+            sim_state.pending_joints.extend(impulse_joints);
+        }
+        RunnerMessage::AssignVehicles { vehicles, .. } => {
+            for vehicle in vehicles {
+                let region_vehicle = RegionVehicle::new(vehicle.params, vehicle.controls);
+                sim_state
+                    .vehicles
+                    .insert(vehicle.chassis.uuid, region_vehicle);
+                pending_assignments.push(vehicle.chassis);
+            }
+        }
+        RunnerMessage::AssignParticles { particles, .. } => {
+            if sim_state.settings.fluid.is_some() {
+                sim_state.fluid.pending.extend(particles);
+            } else {
+                warn!(
+                    "Discarding {} particles: the scene has no fluid.",
+                    particles.len()
+                );
+            }
         }
-        RunnerMessage::SyncClientObjects => {
+        RunnerMessage::SyncClientObjects { .. } => {
             let client_objects = compute_client_objects(sim_state, &pending_assignments);
+            if !app.store.is_local() {
+                let key = client_objects_key(sim_state.scene, &sim_state.sim_bounds);
+                app.store.put_value(&key, &client_objects).await?;
+            }
             app.client_object_sets
-                .insert(sim_state.sim_bounds, client_objects);
+                .insert((sim_state.scene, sim_state.sim_bounds), client_objects);
+        }
+        RunnerMessage::Export { request, .. } => {
+            let export = compute_scene_export(sim_state, &pending_assignments);
+            let key = scene_export_key(request, &sim_state.sim_bounds);
+            app.zenoh.put(&key, &export).await?;
+        }
+        RunnerMessage::HandOver { request, .. } => {
+            // The master gives the bodies to the new runner of the region.
+            let export = compute_scene_export(sim_state, &pending_assignments);
+            let key = scene_export_key(request, &sim_state.sim_bounds);
+            app.zenoh.put(&key, &export).await?;
+            sim_state.killed = true;
         }
-        RunnerMessage::AssignStaticBodies { .. }
-        | RunnerMessage::Ack
-        | RunnerMessage::Step { .. } => unreachable!(),
+        RunnerMessage::BodyInput { body, input, .. } => apply_body_input(sim_state, body, input),
+        RunnerMessage::Adopt { .. }
+        | RunnerMessage::AssignStaticBodies { .. }
+        | RunnerMessage::SetScript { .. }
+        | RunnerMessage::SetTimeScale { .. }
+        | RunnerMessage::SetSimulationParams { .. }
+        | RunnerMessage::SetFrozenVolumes { .. }
+        | RunnerMessage::SetForceGenerators { .. }
+        | RunnerMessage::SetFarRegions { .. }
+        | RunnerMessage::TraceBodies { .. }
+        | RunnerMessage::Ack { .. }
+        | RunnerMessage::TakeOver { .. }
+        | RunnerMessage::Step { .. }
+        | RunnerMessage::Ping
+        | RunnerMessage::Shutdown => unreachable!(),
     }
 
     Ok(())
 }
 
+/// The bodies, joints, fluid particles and vehicles simulated by this region, including the ones
+/// waiting to be inserted, but excluding the bodies watched from other regions.
+fn compute_scene_export(sim_state: &SimulationState, pending: &[BodyAssignment]) -> SceneExport {
+    let mut bodies = vec![];
+
+    for (handle, body) in sim_state.bodies.iter() {
+        if sim_state.watched_objects.contains_key(&handle) {
+            continue;
+        }
+
+        let Some(uuid) = sim_state.body2uuid.get(&handle) else {
+            continue;
+        };
+        let collider = &sim_state.colliders[body.colliders()[0]];
+        let mut cold = ColdBodyObject::from_body_collider(body, collider);
+        cold.collision_layer = collision_layer(collider);
+        if let Some(animations) = sim_state.body2animations.get(handle.0) {
+            cold.animations = animations.clone();
+        }
+        if let Some(user_data) = sim_state.body2user_data.get(handle.0) {
+            cold.user_data = user_data.clone();
+        }
+        cold.character = sim_state
+            .characters
+            .get(&handle)
+            .map(|character| character.params);
+        cold.projectile = sim_state
+            .projectiles
+            .get(&handle)
+            .map(|projectile| projectile.params(sim_state.step_id));
+
+        bodies.push(BodyAssignment {
+            uuid: *uuid,
+            warm: WarmBodyObject::from_body(body, sim_state.step_id),
+            cold,
+            epoch: sim_state.epoch(handle),
+        });
+    }
+
+    bodies.extend_from_slice(pending);
+
+    // The chassis are exported with their vehicle.
+    let (chassis, bodies): (Vec<_>, Vec<_>) = bodies
+        .into_iter()
+        .partition(|body| sim_state.vehicles.contains_key(&body.uuid));
+    let vehicles = vehicle_assignments(sim_state, chassis);
+
+    let joints: Vec<_> = sim_state
+        .impulse_joints
+        .iter()
+        .map(|(_, joint)| (joint.body1, joint.body2, joint.data))
+        .collect();
+    let mut impulse_joints = joint_assignments(sim_state, &joints);
+    impulse_joints.extend_from_slice(&sim_state.pending_joints);
+
+    SceneExport {
+        bodies,
+        impulse_joints,
+        particles: sim_state
+            .fluid
+            .particles
+            .iter()
+            .chain(&sim_state.fluid.pending)
+            .copied()
+            .collect(),
+        vehicles,
+    }
+}
+
+/// Applies the input of a client to the body `uuid`, if it is simulated by this region.
+fn apply_body_input(sim_state: &mut SimulationState, uuid: Uuid, input: BodyInput) {
+    let Some(handle) = sim_state.uuid2body.get(&uuid).copied() else {
+        return;
+    };
+
+    // The region simulating the body applies the input to the original.
+    if sim_state.watched_objects.contains_key(&handle) {
+        return;
+    }
+
+    let step_duration = sim_state.params.dt * NUM_INTERNAL_STEPS as Real;
+    let body = &mut sim_state.bodies[handle];
+
+    match input {
+        // NOTE: the force is applied as the equivalent impulse, so it doesn’t need to be reset
+        //       after the step.
+        BodyInput::Force(force) => body.apply_impulse(force * step_duration, true),
+        BodyInput::Teleport(position) => {
+            body.set_position(position, true);
+            body.set_linvel(Vector::zeros(), true);
+            body.set_angvel(Default::default(), true);
+        }
+        BodyInput::Freeze(frozen) => {
+            body.lock_translations(frozen, true);
+            body.lock_rotations(frozen, true);
+            if frozen {
+                body.set_linvel(Vector::zeros(), true);
+                body.set_angvel(Default::default(), true);
+            }
+        }
+        BodyInput::Move(velocity) => {
+            if let Some(character) = sim_state.characters.get_mut(&handle) {
+                character.velocity = velocity;
+            }
+        }
+        BodyInput::Drive(controls) => {
+            if let Some(vehicle) = sim_state.vehicles.get_mut(&uuid) {
+                vehicle.controls = controls;
+                body.wake_up(true);
+            }
+        }
+    }
+}
+
+/// The energy and momentum of the bodies simulated by this region, ghosts excluded.
+fn compute_physics_stats(sim_state: &SimulationState) -> PhysicsStats {
+    let mut stats = PhysicsStats::default();
+    for (handle, body) in sim_state.bodies.iter() {
+        if !sim_state.watched_objects.contains_key(&handle) {
+            stats.add_body(body);
+        }
+    }
+    stats
+}
+
+/// The bodies simulated by this region, ghosts and static bodies excluded.
+fn compute_body_counts(sim_state: &SimulationState) -> BodyCounts {
+    let mut counts = BodyCounts::default();
+    for (handle, body) in sim_state.bodies.iter() {
+        if !sim_state.watched_objects.contains_key(&handle)
+            && (body.is_dynamic() || sim_state.characters.contains_key(&handle))
+        {
+            counts.add_body(body);
+        }
+    }
+    counts
+}
+
+/// The poses of the traced bodies simulated by this region, ghosts excluded.
+fn trajectory_points(
+    sim_state: &SimulationState,
+    traced_bodies: &HashSet<Uuid>,
+) -> Vec<(Uuid, TrajectoryPoint)> {
+    let timestamp = sim_state.step_id * NUM_INTERNAL_STEPS;
+    traced_bodies
+        .iter()
+        .filter_map(|uuid| {
+            let handle = *sim_state.uuid2body.get(uuid)?;
+            if sim_state.watched_objects.contains_key(&handle) {
+                return None;
+            }
+            let body = sim_state.bodies.get(handle)?;
+            let point = TrajectoryPoint {
+                timestamp,
+                position: *body.position(),
+                linvel: *body.linvel(),
+                angvel: body.angvel().clone(),
+            };
+            Some((*uuid, point))
+        })
+        .collect()
+}
+
 fn compute_client_objects(
     sim_state: &mut SimulationState,
     pending: &[BodyAssignment],
 ) -> ClientBodyObjectSet {
     let timestamp = sim_state.step_id * NUM_INTERNAL_STEPS;
     let mut objects = vec![];
+    let mut cold_objects = vec![];
 
     for (handle, body) in sim_state.bodies.iter() {
         if !sim_state.watched_objects.contains_key(&handle) {
@@ -448,29 +1298,183 @@ fn compute_client_objects(
                 attrs.sleep_step_id = None;
             }
 
+            let collider = &sim_state.colliders[body.colliders()[0]];
+            let shape = collider.shared_shape();
+            let (shape_hash, cold_timestamp) = *attrs.shape_hash.get_or_insert_with(|| {
+                (
+                    ClientColdBodyObject::compute_shape_hash(shape, body.body_type()),
+                    timestamp,
+                )
+            });
+
             let client_object = ClientBodyObject {
                 uuid,
                 position: warm_object.position,
-                shape: sim_state.colliders[body.colliders()[0]]
-                    .shared_shape()
-                    .clone(),
-                body_type: body.body_type(),
+                linvel: warm_object.linvel,
+                angvel: warm_object.angvel,
+                shape_hash,
                 sleep_start_frame: attrs.sleep_step_id,
+                epoch: attrs.epoch,
+            };
+            let cold_object = ClientColdBodyObject {
+                uuid,
+                shape_hash,
+                timestamp: cold_timestamp,
+                shape: shape.clone(),
+                body_type: body.body_type(),
+                user_data: sim_state
+                    .body2user_data
+                    .get(handle.0)
+                    .cloned()
+                    .unwrap_or_default(),
+                is_sensor: collider.is_sensor(),
             };
             objects.push(client_object);
+            cold_objects.push(cold_object);
         }
     }
 
     for pending in pending {
+        let shape_hash =
+            ClientColdBodyObject::compute_shape_hash(&pending.cold.shape, pending.cold.body_type);
         let client_object = ClientBodyObject {
             uuid: pending.uuid,
             position: pending.warm.position,
+            linvel: pending.warm.linvel,
+            angvel: pending.warm.angvel,
+            shape_hash,
+            sleep_start_frame: None,
+            epoch: pending.epoch,
+        };
+        let cold_object = ClientColdBodyObject {
+            uuid: pending.uuid,
+            shape_hash,
+            timestamp,
             shape: pending.cold.shape.clone(),
             body_type: pending.cold.body_type,
-            sleep_start_frame: None,
+            user_data: pending.cold.user_data.clone(),
+            is_sensor: pending.cold.is_sensor,
         };
         objects.push(client_object);
+        cold_objects.push(cold_object);
+    }
+
+    // NOTE: a ghost might have come back to this region already.
+    let mut ghosts: Vec<_> = sim_state
+        .ghosts
+        .values()
+        .filter(|ghost| !sim_state.uuid2body.contains_key(&ghost.object.uuid))
+        .map(|ghost| ghost.client_object(timestamp, sim_state.params.dt))
+        .collect();
+    ghosts.sort_by_key(|ghost| ghost.object.uuid);
+
+    ClientBodyObjectSet {
+        timestamp,
+        objects,
+        cold_objects,
+        ghosts,
+        partial: false,
     }
+}
+
+/// The uuids of the characters simulated by this region.
+fn character_uuids(sim_state: &SimulationState) -> HashSet<Uuid> {
+    sim_state
+        .characters
+        .keys()
+        .filter_map(|handle| sim_state.body2uuid.get(handle).copied())
+        .collect()
+}
+
+/// The characters simulated by this region, streamed as a partial set between two reports of
+/// all its bodies.
+fn compute_character_client_objects(sim_state: &SimulationState) -> ClientBodyObjectSet {
+    let timestamp = sim_state.step_id * NUM_INTERNAL_STEPS;
+    let objects = sim_state
+        .characters
+        .keys()
+        .filter_map(|handle| {
+            let body = sim_state.bodies.get(*handle)?;
+            let attrs = sim_state.bodies_attributes.get(handle.0)?;
+            // NOTE: the characters not reported with the rest of the region yet are skipped,
+            //       the clients don’t know their shape.
+            let (shape_hash, _) = attrs.shape_hash?;
+            let warm_object = WarmBodyObject::from_body(body, timestamp);
+            Some(ClientBodyObject {
+                uuid: *sim_state.body2uuid.get(handle)?,
+                position: warm_object.position,
+                linvel: warm_object.linvel,
+                angvel: warm_object.angvel,
+                shape_hash,
+                sleep_start_frame: None,
+                epoch: attrs.epoch,
+            })
+        })
+        .collect();
+
+    ClientBodyObjectSet {
+        timestamp,
+        objects,
+        cold_objects: vec![],
+        ghosts: vec![],
+        partial: true,
+    }
+}
+
+/// Drops the bodies that barely moved from the client objects streamed by a region.
+#[derive(Default)]
+struct ClientMotionFilter {
+    /// The bodies as they were last streamed.
+    published: HashMap<Uuid, ClientBodyObject>,
+    sets_since_keyframe: u32,
+}
+
+impl ClientMotionFilter {
+    /// The `objects` to stream, and whether some were dropped.
+    ///
+    /// Every [`CLIENT_KEYFRAME_PERIOD`] sets, all the objects are streamed. Otherwise, only the
+    /// ones that moved more than [`CLIENT_MOTION_THRESHOLD`], fell asleep or woke up since they
+    /// were last streamed are, and the `characters`.
+    fn filter(
+        &mut self,
+        objects: &[ClientBodyObject],
+        characters: &HashSet<Uuid>,
+    ) -> (Vec<ClientBodyObject>, bool) {
+        let keyframe = self.sets_since_keyframe == 0;
+        self.sets_since_keyframe = (self.sets_since_keyframe + 1) % CLIENT_KEYFRAME_PERIOD;
+
+        if keyframe {
+            self.published = objects
+                .iter()
+                .map(|object| (object.uuid, *object))
+                .collect();
+            return (objects.to_vec(), false);
+        }
+
+        let moved: Vec<_> = objects
+            .iter()
+            .filter(|object| {
+                characters.contains(&object.uuid)
+                    || self
+                        .published
+                        .get(&object.uuid)
+                        .map(|published| moved_significantly(published, object))
+                        .unwrap_or(true)
+            })
+            .copied()
+            .collect();
+        for object in &moved {
+            self.published.insert(object.uuid, *object);
+        }
+
+        (moved, true)
+    }
+}
 
-    ClientBodyObjectSet { timestamp, objects }
+fn moved_significantly(before: &ClientBodyObject, after: &ClientBodyObject) -> bool {
+    let translation = after.position.translation.vector - before.position.translation.vector;
+    before.sleep_start_frame.is_some() != after.sleep_start_frame.is_some()
+        || before.shape_hash != after.shape_hash
+        || translation.norm() > CLIENT_MOTION_THRESHOLD
+        || before.position.rotation.angle_to(&after.position.rotation) > CLIENT_MOTION_THRESHOLD
 }
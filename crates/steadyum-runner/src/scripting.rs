@@ -0,0 +1,337 @@
+use crate::runner::SimulationState;
+use crate::spawn::despawn_body;
+use rapier::prelude::*;
+use std::sync::Arc;
+use steadyum_api_types::messages::BodyAssignment;
+use uuid::Uuid;
+use wasmtime::{
+    Caller, Config, Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+    TypedFunc,
+};
+
+/// Fuel given to a script at each step, bounding the time it can take.
+const STEP_FUEL: u64 = 100_000_000;
+/// The maximum memory a script can allocate.
+const MAX_SCRIPT_MEMORY: usize = 64 * 1024 * 1024;
+/// The module of the functions imported by the scripts.
+const IMPORT_MODULE: &str = "steadyum";
+
+/// A body simulated by the region, as seen by the script during a step.
+struct ScriptBody {
+    uuid: Uuid,
+    handle: RigidBodyHandle,
+    position: Isometry<Real>,
+    linvel: Vector<Real>,
+}
+
+/// A change requested by the script, applied once it returns.
+enum ScriptCommand {
+    ApplyImpulse(RigidBodyHandle, Vector<Real>),
+    AddForce(RigidBodyHandle, Vector<Real>),
+    SetLinvel(RigidBodyHandle, Vector<Real>),
+    Despawn(Uuid),
+    Spawn(BodyAssignment),
+}
+
+struct ScriptState {
+    limits: StoreLimits,
+    bodies: Vec<ScriptBody>,
+    commands: Vec<ScriptCommand>,
+}
+
+/// The script of a scene, instantiated for a single region.
+///
+/// The WASM module exports its `memory` and a `step(step_id: u64)` function, called before each
+/// physics step. It doesn’t have access to anything but the bodies simulated by its region,
+/// designated by their index in `0..body_count()`, through these functions of the `steadyum`
+/// module (vectors are `DIM` little-endian `f32` in the memory of the script):
+/// - `body_count() -> u32`
+/// - `body_uuid(body: u32, out: u32)`: writes the 16 bytes of the body uuid at `out`.
+/// - `body_position(body: u32, out: u32)`: writes the translation, then the rotation angle (2D)
+///   or the `[i, j, k, w]` quaternion (3D).
+/// - `body_linvel(body: u32, out: u32)`
+/// - `apply_impulse(body: u32, impulse: u32)`
+/// - `add_force(body: u32, force: u32)`: the force is only applied during the next step.
+/// - `set_linvel(body: u32, linvel: u32)`
+/// - `despawn(body: u32)`
+/// - `spawn(json: u32, len: u32)`: spawns the dynamic `BodyAssignment` serialized as JSON.
+struct StepScript {
+    store: Store<ScriptState>,
+    step: TypedFunc<u64, ()>,
+    /// The bodies forced during the last step, to reset their forces.
+    forced_bodies: Vec<RigidBodyHandle>,
+}
+
+impl StepScript {
+    fn new(module: &[u8]) -> anyhow::Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        // Keep the simulation deterministic, whatever the platform running the script.
+        config.cranelift_nan_canonicalization(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::new(&engine, module)?;
+        let mut linker = Linker::new(&engine);
+        define_imports(&mut linker)?;
+
+        let state = ScriptState {
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MAX_SCRIPT_MEMORY)
+                .build(),
+            bodies: vec![],
+            commands: vec![],
+        };
+        let mut store = Store::new(&engine, state);
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(STEP_FUEL)?;
+        let instance = linker.instantiate(&mut store, &module)?;
+        let step = instance.get_typed_func::<u64, ()>(&mut store, "step")?;
+
+        Ok(Self {
+            store,
+            step,
+            forced_bodies: vec![],
+        })
+    }
+
+    fn step(
+        &mut self,
+        sim_state: &mut SimulationState,
+        pending_assignments: &mut Vec<BodyAssignment>,
+    ) -> anyhow::Result<()> {
+        for handle in self.forced_bodies.drain(..) {
+            if let Some(body) = sim_state.bodies.get_mut(handle) {
+                body.reset_forces(false);
+            }
+        }
+
+        let state = self.store.data_mut();
+        state.bodies.clear();
+        state.commands.clear();
+
+        for (handle, body) in sim_state.bodies.iter() {
+            if sim_state.watched_objects.contains_key(&handle) {
+                continue;
+            }
+
+            let Some(uuid) = sim_state.body2uuid.get(&handle) else {
+                continue;
+            };
+
+            state.bodies.push(ScriptBody {
+                uuid: *uuid,
+                handle,
+                position: *body.position(),
+                linvel: *body.linvel(),
+            });
+        }
+
+        self.store.set_fuel(STEP_FUEL)?;
+        self.step.call(&mut self.store, sim_state.step_id)?;
+
+        for command in std::mem::take(&mut self.store.data_mut().commands) {
+            match command {
+                ScriptCommand::ApplyImpulse(handle, impulse) => {
+                    if let Some(body) = sim_state.bodies.get_mut(handle) {
+                        body.apply_impulse(impulse, true);
+                    }
+                }
+                ScriptCommand::AddForce(handle, force) => {
+                    if let Some(body) = sim_state.bodies.get_mut(handle) {
+                        body.add_force(force, true);
+                        self.forced_bodies.push(handle);
+                    }
+                }
+                ScriptCommand::SetLinvel(handle, linvel) => {
+                    if let Some(body) = sim_state.bodies.get_mut(handle) {
+                        body.set_linvel(linvel, true);
+                    }
+                }
+                ScriptCommand::Despawn(uuid) => despawn_body(sim_state, uuid),
+                ScriptCommand::Spawn(mut body) => {
+                    body.warm.timestamp = sim_state.step_id;
+                    pending_assignments.push(body);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The script run by a region, reloaded whenever the script of its scene changes.
+#[derive(Default)]
+pub struct RegionScript {
+    module: Arc<Vec<u8>>,
+    script: Option<StepScript>,
+}
+
+impl RegionScript {
+    /// Runs `module` on the bodies of the region, before the physics step.
+    pub fn run(
+        &mut self,
+        module: Arc<Vec<u8>>,
+        sim_state: &mut SimulationState,
+        pending_assignments: &mut Vec<BodyAssignment>,
+    ) {
+        if !Arc::ptr_eq(&self.module, &module) {
+            self.script = None;
+
+            if !module.is_empty() {
+                match StepScript::new(&module) {
+                    Ok(script) => self.script = Some(script),
+                    Err(e) => tracing::error!("Failed to load the script: {e}"),
+                }
+            }
+
+            self.module = module;
+        }
+
+        if let Some(script) = &mut self.script {
+            if let Err(e) = script.step(sim_state, pending_assignments) {
+                tracing::error!("The script failed at step {}: {e}", sim_state.step_id);
+            }
+        }
+    }
+}
+
+fn define_imports(linker: &mut Linker<ScriptState>) -> anyhow::Result<()> {
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "body_count",
+        |caller: Caller<'_, ScriptState>| caller.data().bodies.len() as u32,
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "body_uuid",
+        |mut caller: Caller<'_, ScriptState>, body: u32, out: u32| {
+            let uuid = script_body(&caller, body)?.uuid;
+            write_bytes(&mut caller, out, uuid.as_bytes())
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "body_position",
+        |mut caller: Caller<'_, ScriptState>, body: u32, out: u32| {
+            let position = script_body(&caller, body)?.position;
+            let mut reals: Vec<Real> = position.translation.vector.iter().copied().collect();
+            #[cfg(feature = "dim2")]
+            reals.push(position.rotation.angle());
+            #[cfg(feature = "dim3")]
+            reals.extend(position.rotation.coords.iter().copied());
+            write_reals(&mut caller, out, &reals)
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "body_linvel",
+        |mut caller: Caller<'_, ScriptState>, body: u32, out: u32| {
+            let linvel = script_body(&caller, body)?.linvel;
+            write_reals(&mut caller, out, linvel.as_slice())
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "apply_impulse",
+        |mut caller: Caller<'_, ScriptState>, body: u32, impulse: u32| {
+            let handle = script_body(&caller, body)?.handle;
+            let impulse = read_vector(&mut caller, impulse)?;
+            push_command(&mut caller, ScriptCommand::ApplyImpulse(handle, impulse));
+            anyhow::Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "add_force",
+        |mut caller: Caller<'_, ScriptState>, body: u32, force: u32| {
+            let handle = script_body(&caller, body)?.handle;
+            let force = read_vector(&mut caller, force)?;
+            push_command(&mut caller, ScriptCommand::AddForce(handle, force));
+            anyhow::Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "set_linvel",
+        |mut caller: Caller<'_, ScriptState>, body: u32, linvel: u32| {
+            let handle = script_body(&caller, body)?.handle;
+            let linvel = read_vector(&mut caller, linvel)?;
+            push_command(&mut caller, ScriptCommand::SetLinvel(handle, linvel));
+            anyhow::Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "despawn",
+        |mut caller: Caller<'_, ScriptState>, body: u32| {
+            let uuid = script_body(&caller, body)?.uuid;
+            push_command(&mut caller, ScriptCommand::Despawn(uuid));
+            anyhow::Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "spawn",
+        |mut caller: Caller<'_, ScriptState>, json: u32, len: u32| {
+            let mut bytes = vec![0; len as usize];
+            memory(&mut caller)?.read(&caller, json as usize, &mut bytes)?;
+            let body: BodyAssignment = serde_json::from_slice(&bytes)?;
+
+            // Static bodies must be known by every region, so they go through the partitionner.
+            if !body.cold.body_type.is_dynamic() {
+                anyhow::bail!("scripts can only spawn dynamic bodies");
+            }
+
+            push_command(&mut caller, ScriptCommand::Spawn(body));
+            anyhow::Ok(())
+        },
+    )?;
+    Ok(())
+}
+
+fn script_body<'a>(
+    caller: &'a Caller<'_, ScriptState>,
+    body: u32,
+) -> anyhow::Result<&'a ScriptBody> {
+    caller
+        .data()
+        .bodies
+        .get(body as usize)
+        .ok_or_else(|| anyhow::anyhow!("invalid body index: {body}"))
+}
+
+fn push_command(caller: &mut Caller<'_, ScriptState>, command: ScriptCommand) {
+    caller.data_mut().commands.push(command);
+}
+
+fn memory(caller: &mut Caller<'_, ScriptState>) -> anyhow::Result<Memory> {
+    caller
+        .get_export("memory")
+        .and_then(|export| export.into_memory())
+        .ok_or_else(|| anyhow::anyhow!("the script doesn’t export its memory"))
+}
+
+fn write_bytes(caller: &mut Caller<'_, ScriptState>, out: u32, bytes: &[u8]) -> anyhow::Result<()> {
+    memory(caller)?.write(&mut *caller, out as usize, bytes)?;
+    Ok(())
+}
+
+fn write_reals(
+    caller: &mut Caller<'_, ScriptState>,
+    out: u32,
+    reals: &[Real],
+) -> anyhow::Result<()> {
+    let bytes: Vec<u8> = reals.iter().flat_map(|x| x.to_le_bytes()).collect();
+    write_bytes(caller, out, &bytes)
+}
+
+fn read_vector(caller: &mut Caller<'_, ScriptState>, ptr: u32) -> anyhow::Result<Vector<Real>> {
+    let mut bytes = [0; DIM * 4];
+    memory(caller)?.read(&*caller, ptr as usize, &mut bytes)?;
+
+    let mut vector = Vector::zeros();
+    for (i, chunk) in bytes.chunks_exact(4).enumerate() {
+        vector[i] = Real::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+
+    Ok(vector)
+}
@@ -0,0 +1,58 @@
+use crate::runner::SimulationState;
+use rapier::prelude::*;
+use steadyum_api_types::messages::SimulationEvent;
+
+/// Converts the collision events generated by the last physics steps into intersection events.
+///
+/// Intersections with the bodies mirrored from a neighbor are only reported by dynamic sensors:
+/// static and kinematic sensors exist in every region, so the region simulating the body
+/// already reports them.
+pub fn intersection_events(
+    sim_state: &SimulationState,
+    collision_events: &[CollisionEvent],
+) -> Vec<SimulationEvent> {
+    let mut events = vec![];
+
+    for event in collision_events {
+        if !event.sensor() {
+            continue;
+        }
+
+        // NOTE: the colliders removed since the last step are gone already.
+        let (Some(co1), Some(co2)) = (
+            sim_state.colliders.get(event.collider1()),
+            sim_state.colliders.get(event.collider2()),
+        ) else {
+            continue;
+        };
+
+        for (sensor, other) in [(co1, co2), (co2, co1)] {
+            let (Some(sensor_handle), Some(other_handle)) = (sensor.parent(), other.parent())
+            else {
+                continue;
+            };
+
+            if !sensor.is_sensor()
+                || (sim_state.watched_objects.contains_key(&other_handle)
+                    && !sim_state.bodies[sensor_handle].is_dynamic())
+            {
+                continue;
+            }
+
+            let (Some(sensor_uuid), Some(body_uuid)) = (
+                sim_state.body2uuid.get(&sensor_handle),
+                sim_state.body2uuid.get(&other_handle),
+            ) else {
+                continue;
+            };
+
+            events.push(SimulationEvent::Intersection {
+                sensor: *sensor_uuid,
+                body: *body_uuid,
+                started: event.started(),
+            });
+        }
+    }
+
+    events
+}
@@ -0,0 +1,104 @@
+use crate::runner::SimulationState;
+use steadyum_api_types::messages::{BodyAssignment, DespawnBodies, SpawnBodies};
+use steadyum_api_types::partitionner::SceneUuid;
+use steadyum_api_types::serialization::deserialize_auto;
+use steadyum_api_types::simulation::SimulationBounds;
+use steadyum_api_types::zenoh::{despawn_key, spawn_key, ZenohContext};
+use uuid::Uuid;
+use zenoh::prelude::r#async::AsyncResolve;
+use zenoh::prelude::SplitBuffer;
+use zenoh::subscriber::FlumeSubscriber;
+
+/// Subscriptions to the bodies spawned and despawned by the clients, bypassing the partitionner.
+pub struct SpawnSubscriptions<'a> {
+    spawn: FlumeSubscriber<'a>,
+    despawn: FlumeSubscriber<'a>,
+}
+
+impl<'a> SpawnSubscriptions<'a> {
+    pub async fn new(
+        zenoh: &'a ZenohContext,
+        scene: SceneUuid,
+        region: SimulationBounds,
+    ) -> anyhow::Result<Self> {
+        let spawn = zenoh
+            .session
+            .declare_subscriber(spawn_key(scene, &region))
+            .res_async()
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+        let despawn = zenoh
+            .session
+            .declare_subscriber(despawn_key(scene))
+            .res_async()
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+        Ok(Self { spawn, despawn })
+    }
+
+    /// Queues the dynamic bodies spawned since the last call for insertion.
+    ///
+    /// The static bodies are inserted by the partitionner, so every region gets them.
+    pub fn read_spawned(&self, pending_assignments: &mut Vec<BodyAssignment>) {
+        while let Ok(sample) = self.spawn.try_recv() {
+            let spawn: SpawnBodies = match deserialize_auto(&sample.value.payload.contiguous()) {
+                Ok(spawn) => spawn,
+                Err(e) => {
+                    tracing::error!("Failed to deserialize spawned bodies: {e}");
+                    continue;
+                }
+            };
+
+            pending_assignments.extend(
+                spawn
+                    .bodies
+                    .into_iter()
+                    .filter(|body| body.cold.moves_between_regions()),
+            );
+        }
+    }
+
+    /// Removes the bodies despawned since the last call, if they are simulated by this region.
+    pub fn remove_despawned(&self, sim_state: &mut SimulationState) {
+        while let Ok(sample) = self.despawn.try_recv() {
+            let despawn: DespawnBodies = match deserialize_auto(&sample.value.payload.contiguous())
+            {
+                Ok(despawn) => despawn,
+                Err(e) => {
+                    tracing::error!("Failed to deserialize despawned bodies: {e}");
+                    continue;
+                }
+            };
+
+            for uuid in despawn.bodies {
+                despawn_body(sim_state, uuid);
+            }
+        }
+    }
+}
+
+/// Removes the body `uuid`, if it is simulated by this region.
+pub fn despawn_body(sim_state: &mut SimulationState, uuid: Uuid) {
+    sim_state.static_bodies.despawn(uuid);
+
+    let Some(handle) = sim_state.uuid2body.get(&uuid).copied() else {
+        return;
+    };
+
+    // Mirrors are removed once their region stops reporting them.
+    if sim_state.watched_objects.contains_key(&handle) {
+        return;
+    }
+    sim_state.vehicles.remove(&uuid);
+
+    sim_state.bodies.remove(
+        handle,
+        &mut sim_state.islands,
+        &mut sim_state.colliders,
+        &mut sim_state.impulse_joints,
+        &mut sim_state.multibody_joints,
+        true,
+    );
+    sim_state.body2uuid.remove(&handle);
+    sim_state.uuid2body.remove(&uuid);
+}
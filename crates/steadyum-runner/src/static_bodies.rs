@@ -0,0 +1,204 @@
+//! The static bodies of the scenes, shared by all the regions of this runner.
+
+use crate::runner::SimulationState;
+use rapier::parry::bounding_volume::BoundingVolume;
+use rapier::parry::partitioning::Qbvh;
+use rapier::prelude::*;
+use std::collections::{BTreeMap, HashSet};
+use steadyum_api_types::messages::BodyAssignment;
+use steadyum_api_types::objects::{ClientBodyObject, ClientColdBodyObject};
+use steadyum_api_types::partitionner::NUM_INTERNAL_STEPS;
+use uuid::Uuid;
+
+/// Number of steps a fixed body stays in the sets of a region after the bodies of the region
+/// moved away from it.
+const FIXED_BODY_LIFETIME: u64 = 100;
+
+/// The static bodies of a scene, shared by all the regions of this runner simulating it.
+///
+/// The fixed bodies, e.g., the terrain, are only inserted in the sets of the regions whose
+/// bodies come close to them, instead of every region keeping a copy of all of them. The
+/// kinematic bodies follow their animations anywhere, every region inserts them.
+pub struct SceneStaticBodies {
+    pub bodies: Vec<BodyAssignment>,
+    uuids: HashSet<Uuid>,
+    /// The AABBs of the fixed bodies of `bodies`, by index.
+    fixed: Qbvh<usize>,
+    kinematic: Vec<usize>,
+    /// The objects reported to the clients for each body of `bodies`.
+    client_objects: Vec<(ClientBodyObject, ClientColdBodyObject)>,
+}
+
+impl Default for SceneStaticBodies {
+    fn default() -> Self {
+        let mut fixed = Qbvh::new();
+        // Start with a root, so the tree can be queried before any body is added.
+        fixed.clear_and_rebuild(std::iter::empty(), 0.0);
+        Self {
+            bodies: vec![],
+            uuids: HashSet::new(),
+            fixed,
+            kinematic: vec![],
+            client_objects: vec![],
+        }
+    }
+}
+
+impl SceneStaticBodies {
+    /// Adds the bodies of `bodies` this runner doesn’t know yet, received at `timestamp`.
+    pub fn append(&mut self, bodies: Vec<BodyAssignment>, timestamp: u64) {
+        for body in bodies {
+            if !self.uuids.insert(body.uuid) {
+                // The partitionner sends all the static bodies of the scene each time.
+                continue;
+            }
+
+            if body.cold.body_type != RigidBodyType::Fixed {
+                self.kinematic.push(self.bodies.len());
+            }
+
+            let shape_hash =
+                ClientColdBodyObject::compute_shape_hash(&body.cold.shape, body.cold.body_type);
+            let client_object = ClientBodyObject {
+                uuid: body.uuid,
+                position: body.warm.position,
+                linvel: body.warm.linvel,
+                angvel: body.warm.angvel,
+                shape_hash,
+                sleep_start_frame: Some(timestamp),
+                epoch: body.epoch,
+            };
+            let cold_object = ClientColdBodyObject {
+                uuid: body.uuid,
+                shape_hash,
+                timestamp,
+                shape: body.cold.shape.clone(),
+                body_type: body.cold.body_type,
+                user_data: body.cold.user_data.clone(),
+                is_sensor: body.cold.is_sensor,
+            };
+            self.client_objects.push((client_object, cold_object));
+            self.bodies.push(body);
+        }
+
+        let bodies = &self.bodies;
+        self.fixed.clear_and_rebuild(
+            bodies
+                .iter()
+                .enumerate()
+                .filter(|(_, body)| body.cold.body_type == RigidBodyType::Fixed)
+                .map(|(i, body)| (i, body.cold.shape.compute_aabb(&body.warm.position))),
+            0.0,
+        );
+    }
+}
+
+/// The static bodies of its scene a region inserted in its sets.
+#[derive(Default)]
+pub struct RegionStaticBodies {
+    /// The last step each fixed body was close to a body of the region, by index in
+    /// [`SceneStaticBodies::bodies`]. The kinematic bodies are never removed.
+    inserted: BTreeMap<usize, u64>,
+    /// The static bodies despawned from the region, never inserted again.
+    despawned: HashSet<Uuid>,
+}
+
+impl RegionStaticBodies {
+    /// Marks `uuid` as despawned, so it isn’t inserted again if it is a static body.
+    pub fn despawn(&mut self, uuid: Uuid) {
+        self.despawned.insert(uuid);
+    }
+}
+
+/// Queues the static bodies the bodies of the region may reach during the next step, and
+/// removes the fixed bodies none of them came close to for [`FIXED_BODY_LIFETIME`] steps.
+pub fn update_static_bodies(
+    sim_state: &mut SimulationState,
+    scene_bodies: &SceneStaticBodies,
+    pending: &mut Vec<BodyAssignment>,
+) {
+    let step_id = sim_state.step_id;
+    let region = &mut sim_state.static_bodies;
+    region
+        .despawned
+        .retain(|uuid| scene_bodies.uuids.contains(uuid));
+
+    let mut close = vec![];
+    for (handle, body) in sim_state.bodies.iter() {
+        if body.is_fixed() || sim_state.watched_objects.contains_key(&handle) {
+            continue;
+        }
+
+        let predicted_pos = body.predict_position_using_velocity_and_forces(
+            sim_state.params.dt * NUM_INTERNAL_STEPS as Real,
+        );
+        let collider = &sim_state.colliders[body.colliders()[0]];
+        let margins = &sim_state.settings.watch_margins;
+        let margin = margins.absolute + body.linvel().norm() * margins.velocity_scaled;
+        let aabb = collider.compute_swept_aabb(&predicted_pos).loosened(margin);
+        scene_bodies.fixed.intersect_aabb(&aabb, &mut close);
+    }
+
+    let close = close
+        .into_iter()
+        .map(|i| (i, step_id))
+        .chain(scene_bodies.kinematic.iter().map(|i| (*i, u64::MAX)));
+    for (i, last_close) in close {
+        let body = &scene_bodies.bodies[i];
+        if region.despawned.contains(&body.uuid) {
+            continue;
+        }
+
+        if region.inserted.insert(i, last_close).is_none() {
+            pending.push(body.clone());
+        }
+    }
+
+    // NOTE: remove the bodies in a consistent order so the next bodies get the same handles.
+    region.inserted.retain(|i, last_close| {
+        if step_id <= last_close.saturating_add(FIXED_BODY_LIFETIME) {
+            return true;
+        }
+
+        let uuid = scene_bodies.bodies[*i].uuid;
+        let Some(handle) = sim_state.uuid2body.remove(&uuid) else {
+            // Still waiting to be inserted.
+            return pending.iter().any(|pending| pending.uuid == uuid);
+        };
+        sim_state.bodies.remove(
+            handle,
+            &mut sim_state.islands,
+            &mut sim_state.colliders,
+            &mut sim_state.impulse_joints,
+            &mut sim_state.multibody_joints,
+            false,
+        );
+        sim_state.body2uuid.remove(&handle);
+        false
+    });
+}
+
+/// Adds to `objects` and `cold_objects` the fixed bodies intersecting the region that aren’t
+/// inserted in its sets, so the clients still see all of them.
+pub fn report_static_bodies(
+    sim_state: &SimulationState,
+    scene_bodies: &SceneStaticBodies,
+    objects: &mut Vec<ClientBodyObject>,
+    cold_objects: &mut Vec<ClientColdBodyObject>,
+) {
+    let mut intersecting = vec![];
+    scene_bodies
+        .fixed
+        .intersect_aabb(&sim_state.sim_bounds.aabb(), &mut intersecting);
+    intersecting.sort_unstable();
+
+    for i in intersecting {
+        let (object, cold_object) = &scene_bodies.client_objects[i];
+        if !sim_state.uuid2body.contains_key(&object.uuid)
+            && !sim_state.static_bodies.despawned.contains(&object.uuid)
+        {
+            objects.push(*object);
+            cold_objects.push(cold_object.clone());
+        }
+    }
+}
@@ -1,13 +1,31 @@
 use crate::{runner, AppState};
-use log::{error, info};
+use rapier::math::{Isometry, Real};
+use rapier::parry::query::PointQuery;
+use rapier::prelude::Aabb;
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Instant;
+use steadyum_api_types::messages::ClientInterest;
 use steadyum_api_types::objects::{ClientBodyObjectSet, WatchedObjects};
-use steadyum_api_types::serialization::serialize;
-use steadyum_api_types::simulation::SimulationBounds;
+use steadyum_api_types::partitionner::SceneUuid;
+use steadyum_api_types::serialization::{
+    deserialize_auto, serialize_client_objects, serialize_compressed, serialize_watched_objects,
+    Codec, ObjectSetEncoding,
+};
+use steadyum_api_types::simulation::{parse_aabb_param, SimulationBounds};
+use steadyum_api_types::zenoh::{
+    client_interests_key_expr, region_profile_key, region_profiles_key_expr,
+};
+use tracing::{error, info};
+use uuid::Uuid;
 use zenoh::prelude::r#async::AsyncResolve;
+use zenoh::prelude::{KeyExpr, SplitBuffer};
 use zenoh::sample::Sample;
 
+/// The maximum number of bodies per reply to a client objects query. Larger sets are split.
+const CLIENT_OBJECTS_CHUNK_SIZE: usize = 4096;
+
 pub fn start_storage_thread_for_watched_objects(app: Arc<AppState>) {
     let _ = std::thread::spawn(move || {
         let runtime = tokio::runtime::Builder::new_current_thread()
@@ -39,19 +57,23 @@ pub async fn listen_storage_queries_for_watched_objects(app: &AppState) {
         let Ok(query) = query else { break };
         let selector = query.selector();
         // println!(">> [Queryable ] Received Query '{}'", query.selector());
-        let params = selector.parameters();
-        let Some(region) = SimulationBounds::from_str(params) else {
+        let mut params = selector.parameters().split('&');
+        let Some(scene) = params.next().and_then(|s| Uuid::parse_str(s).ok()) else {
             continue;
         };
+        let Some(region) = params.next().and_then(SimulationBounds::from_str) else {
+            continue;
+        };
+        // The version of the watch set the querier already has the bodies of.
+        let known_version = params.next().and_then(|v| v.parse::<u64>().ok());
         // println!(">>>>> [Queryable ] Region {:?} was queried.", region);
-        let data = app
-            .watch_sets
-            .get(&region)
-            .map(|obj| {
-                // info!("Answering {} watched objects.", obj.value().objects.len());
-                serialize(obj.value()).unwrap()
-            })
-            .unwrap_or_else(|| serialize(&WatchedObjects::default()).unwrap());
+        let data = match app.watch_sets.get(&(SceneUuid(scene), region)) {
+            Some(watched) if Some(watched.version) == known_version => {
+                serialize_watched_objects(&watched.unchanged()).unwrap()
+            }
+            Some(watched) => serialize_compressed(watched.value(), Codec::Lz4).unwrap(),
+            None => serialize_compressed(&WatchedObjects::default(), Codec::Lz4).unwrap(),
+        };
 
         let sample = Sample::new(query.key_expr().clone(), data);
 
@@ -76,7 +98,9 @@ pub fn start_storage_thread_for_client_objects(app: Arc<AppState>) {
 pub async fn listen_storage_queries_for_client_objects(app: &AppState) {
     // NOTE: we only need a queryable to expose access to the body sets.
     //       Inserting data into the body set is done entirely locally.
-    let key_expr = format!("steadyum/client_bodies/{:?}", app.scene.0);
+    //       The scene is the last chunk of the key, so a single queryable serves
+    //       all the scenes hosted by this runner.
+    let key_expr = "steadyum/client_bodies/*".to_string();
 
     info!("Starting bodies storage: {}", key_expr);
 
@@ -95,6 +119,22 @@ pub async fn listen_storage_queries_for_client_objects(app: &AppState) {
         let selector = query.selector();
         // println!(">> [Queryable ] Received Query '{}'", query.selector());
 
+        let Some(scene) = query
+            .key_expr()
+            .as_str()
+            .rsplit('/')
+            .next()
+            .and_then(|s| Uuid::parse_str(s).ok())
+            .map(SceneUuid)
+        else {
+            continue;
+        };
+
+        if !app.hosts_scene(scene) {
+            // Let the runner hosting this scene answer.
+            continue;
+        }
+
         let mut params = selector.parameters().split('&');
         let Some(region_str) = params.next() else {
             continue;
@@ -111,24 +151,172 @@ pub async fn listen_storage_queries_for_client_objects(app: &AppState) {
         let Ok(step_id) = u64::from_str(step_id_str) else {
             continue;
         };
+        // A full sync also gets the bodies that were already asleep.
+        let mut full = false;
+        let mut view = None;
+        for param in params {
+            if param == "full" {
+                full = true;
+            } else if let Some(aabb) = param.strip_prefix("view=") {
+                view = parse_aabb_param(aabb);
+            }
+        }
 
         // println!(">>>>> [Queryable ] Region {:?} was queried.", region);
-        let data = app
+        let scene_state = app
+            .scenes
+            .get(&scene)
+            .map(|scene_state| scene_state.clone());
+        let encoding = match scene_state {
+            Some(scene_state) => *scene_state.client_objects_encoding.read().await,
+            None => ObjectSetEncoding::Full,
+        };
+        let mut object_set = app
             .client_object_sets
-            .get(&region)
-            .map(|obj| serialize(&filter_object_set(step_id, obj.value())).unwrap())
-            .unwrap_or_else(|| serialize(&ClientBodyObjectSet::default()).unwrap());
+            .get(&(scene, region))
+            .map(|obj| {
+                if full {
+                    obj.value().clone()
+                } else {
+                    filter_object_set(step_id, obj.value())
+                }
+            })
+            .unwrap_or_default();
+        if let Some(view) = &view {
+            filter_object_set_view(&mut object_set, view);
+        }
 
-        let sample = Sample::new(query.key_expr().clone(), data);
+        for chunk in chunk_object_set(object_set, CLIENT_OBJECTS_CHUNK_SIZE) {
+            let data = serialize_client_objects(chunk, &region, encoding).unwrap();
+            let sample = Sample::new(query.key_expr().clone(), data);
+
+            if let Err(e) = query.reply(Ok(sample)).res().await {
+                error!("Error replying to client objects query: {e}");
+                break;
+            }
+        }
+    }
+
+    info!("Exiting storage loop.")
+}
+
+pub fn start_storage_thread_for_particles(app: Arc<AppState>) {
+    let _ = std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(listen_storage_queries_for_particles(&app))
+    });
+}
+
+pub async fn listen_storage_queries_for_particles(app: &AppState) {
+    // NOTE: like the client objects, the scene is the last chunk of the key.
+    let key_expr = "steadyum/particles/*".to_string();
 
+    info!("Starting particles storage: {}", key_expr);
+
+    let queryable = app
+        .zenoh
+        .session
+        .declare_queryable(&key_expr)
+        .complete(true)
+        .res()
+        .await
+        .unwrap();
+
+    while !app.exit.load(Ordering::SeqCst) {
+        let query = queryable.recv_async().await;
+        let Ok(query) = query else { break };
+
+        let Some(scene) = query
+            .key_expr()
+            .as_str()
+            .rsplit('/')
+            .next()
+            .and_then(|s| Uuid::parse_str(s).ok())
+            .map(SceneUuid)
+        else {
+            continue;
+        };
+
+        if !app.hosts_scene(scene) {
+            // Let the runner hosting this scene answer.
+            continue;
+        }
+
+        let Some(region) = SimulationBounds::from_str(query.selector().parameters()) else {
+            continue;
+        };
+
+        // The region has no fluid, or no client looked at it yet.
+        let Some(particles) = app.particle_sets.get(&(scene, region)) else {
+            continue;
+        };
+        let data = serialize_compressed(particles.value(), Codec::Lz4).unwrap();
+        drop(particles);
+
+        let sample = Sample::new(query.key_expr().clone(), data);
         if let Err(e) = query.reply(Ok(sample)).res().await {
-            error!("Error replying to client objects query: {e}");
+            error!("Error replying to particles query: {e}");
         }
     }
 
     info!("Exiting storage loop.")
 }
 
+/// Keeps the bodies of `object_set` with their center inside `view`, and their cold data.
+fn filter_object_set_view(object_set: &mut ClientBodyObjectSet, view: &Aabb) {
+    let in_view = |pos: &Isometry<Real>| view.contains_local_point(&pos.translation.vector.into());
+    object_set.objects.retain(|obj| in_view(&obj.position));
+    object_set
+        .ghosts
+        .retain(|ghost| in_view(&ghost.object.position));
+
+    let uuids: HashSet<_> = object_set.objects.iter().map(|obj| obj.uuid).collect();
+    object_set
+        .cold_objects
+        .retain(|cold| uuids.contains(&cold.uuid));
+}
+
+/// Splits `object_set` into sets of at most `chunk_size` bodies, each with the cold data of
+/// its bodies, so the replies for dense regions stay small.
+fn chunk_object_set(
+    mut object_set: ClientBodyObjectSet,
+    chunk_size: usize,
+) -> Vec<ClientBodyObjectSet> {
+    if object_set.objects.len() <= chunk_size {
+        return vec![object_set];
+    }
+
+    let mut cold_objects: HashMap<_, _> = object_set
+        .cold_objects
+        .drain(..)
+        .map(|cold| (cold.uuid, cold))
+        .collect();
+    let mut ghosts = std::mem::take(&mut object_set.ghosts);
+    let mut chunks: Vec<_> = object_set
+        .objects
+        .chunks(chunk_size)
+        .map(|objects| ClientBodyObjectSet {
+            timestamp: object_set.timestamp,
+            objects: objects.to_vec(),
+            cold_objects: objects
+                .iter()
+                .filter_map(|obj| cold_objects.remove(&obj.uuid))
+                .collect(),
+            ghosts: std::mem::take(&mut ghosts),
+            partial: object_set.partial,
+        })
+        .collect();
+
+    // The cold data of bodies not listed, if any, is sent with the last chunk.
+    if let Some(last) = chunks.last_mut() {
+        last.cold_objects.extend(cold_objects.into_values());
+    }
+    chunks
+}
+
 fn filter_object_set(step_id: u64, object_set: &ClientBodyObjectSet) -> ClientBodyObjectSet {
     let mut result = object_set.clone();
     let mut max_sleep_frame = 0;
@@ -143,6 +331,9 @@ fn filter_object_set(step_id: u64, object_set: &ClientBodyObjectSet) -> ClientBo
         }
     });
 
+    // Only send the cold data the client didn’t get with the previous frames.
+    result.cold_objects.retain(|obj| obj.timestamp >= step_id);
+
     // println!(
     //     ">>>>>>>>>>>>> RESULT: {} vs  {}, {} (max sleep frame: {})",
     //     step_id,
@@ -152,3 +343,123 @@ fn filter_object_set(step_id: u64, object_set: &ClientBodyObjectSet) -> ClientBo
     // );
     result
 }
+
+pub fn start_client_interests_thread(app: Arc<AppState>) {
+    let _ = std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(listen_client_interests(&app))
+    });
+}
+
+pub async fn listen_client_interests(app: &AppState) {
+    let key_expr = client_interests_key_expr();
+
+    info!("Listening to client interests: {}", key_expr);
+
+    let subscriber = app
+        .zenoh
+        .session
+        .declare_subscriber(&key_expr)
+        .res()
+        .await
+        .unwrap();
+
+    while !app.exit.load(Ordering::SeqCst) {
+        let Ok(sample) = subscriber.recv_async().await else {
+            break;
+        };
+
+        // The key ends with `{scene}/{client}`.
+        let mut chunks = sample.key_expr.as_str().rsplit('/');
+        let Some(client) = chunks.next().and_then(|s| Uuid::parse_str(s).ok()) else {
+            continue;
+        };
+        let Some(scene) = chunks
+            .next()
+            .and_then(|s| Uuid::parse_str(s).ok())
+            .map(SceneUuid)
+        else {
+            continue;
+        };
+
+        if !app.hosts_scene(scene) {
+            continue;
+        }
+
+        let Ok(interest) = deserialize_auto::<ClientInterest>(&sample.value.payload.contiguous())
+        else {
+            continue;
+        };
+
+        app.client_interests
+            .insert((scene, client), (interest, Instant::now()));
+    }
+
+    info!("Exiting client interests loop.")
+}
+
+pub fn start_profile_queryable_thread(app: Arc<AppState>) {
+    let _ = std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(listen_profile_queries(&app))
+    });
+}
+
+/// Answers the profiling queries of the partitionner with the timings of the last step of
+/// every region of the queried scene hosted by this runner.
+pub async fn listen_profile_queries(app: &AppState) {
+    let key_expr = region_profiles_key_expr();
+
+    info!("Starting profile queryable: {}", key_expr);
+
+    let queryable = app
+        .zenoh
+        .session
+        .declare_queryable(&key_expr)
+        .res()
+        .await
+        .unwrap();
+
+    while !app.exit.load(Ordering::SeqCst) {
+        let Ok(query) = queryable.recv_async().await else {
+            break;
+        };
+
+        // The key ends with `{scene}/*`.
+        let Some(scene) = query
+            .key_expr()
+            .as_str()
+            .rsplit('/')
+            .nth(1)
+            .and_then(|s| Uuid::parse_str(s).ok())
+            .map(SceneUuid)
+        else {
+            continue;
+        };
+
+        let profiles: Vec<_> = app
+            .region_profiles
+            .iter()
+            .filter(|entry| entry.key().0 == scene)
+            .map(|entry| *entry.value())
+            .collect();
+
+        for profile in profiles {
+            let key = KeyExpr::try_from(region_profile_key(scene, &profile.region)).unwrap();
+            let data = serialize_compressed(&profile, Codec::Lz4).unwrap();
+            let sample = Sample::new(key, data);
+
+            if let Err(e) = query.reply(Ok(sample)).res().await {
+                error!("Error replying to profile query: {e}");
+            }
+        }
+    }
+
+    info!("Exiting profile queryable loop.")
+}
@@ -0,0 +1,130 @@
+//! The ray-cast vehicles simulated by the region, see [`VehicleParams`].
+
+use crate::runner::SimulationState;
+#[cfg(feature = "dim3")]
+use crate::watch::MAIN_GROUP;
+#[cfg(feature = "dim3")]
+use rapier::control::{DynamicRayCastVehicleController, WheelTuning};
+use rapier::prelude::*;
+use steadyum_api_types::messages::{BodyAssignment, VehicleAssignment};
+use steadyum_api_types::vehicles::{VehicleControls, VehicleParams};
+
+/// A vehicle simulated by the region, indexed by the uuid of its chassis.
+pub struct RegionVehicle {
+    pub params: VehicleParams,
+    pub controls: VehicleControls,
+    /// Built once the chassis is inserted, and again if its handle changes.
+    #[cfg(feature = "dim3")]
+    controller: Option<DynamicRayCastVehicleController>,
+}
+
+impl RegionVehicle {
+    pub fn new(params: VehicleParams, controls: VehicleControls) -> Self {
+        Self {
+            params,
+            controls,
+            #[cfg(feature = "dim3")]
+            controller: None,
+        }
+    }
+}
+
+#[cfg(feature = "dim3")]
+fn vehicle_controller(
+    params: &VehicleParams,
+    chassis: RigidBodyHandle,
+) -> DynamicRayCastVehicleController {
+    let mut controller = DynamicRayCastVehicleController::new(chassis);
+    for wheel in &params.wheels {
+        let tuning = WheelTuning {
+            suspension_stiffness: wheel.suspension_stiffness,
+            max_suspension_travel: wheel.max_suspension_travel,
+            friction_slip: wheel.friction_slip,
+            ..WheelTuning::default()
+        };
+        controller.add_wheel(
+            wheel.connection,
+            wheel.direction,
+            wheel.axle,
+            wheel.suspension_rest_length,
+            wheel.radius,
+            &tuning,
+        );
+    }
+    controller
+}
+
+/// Casts the wheels of the vehicles of the region, and applies the forces of their suspensions,
+/// engines and brakes to their chassis, for a step of `dt` seconds.
+///
+/// The query pipeline must be up to date.
+#[cfg(feature = "dim3")]
+pub fn update_vehicles(sim_state: &mut SimulationState, dt: Real) {
+    for (uuid, vehicle) in &mut sim_state.vehicles {
+        // NOTE: the chassis may still be waiting to be inserted.
+        let Some(handle) = sim_state.uuid2body.get(uuid).copied() else {
+            continue;
+        };
+        if sim_state.watched_objects.contains_key(&handle) {
+            continue;
+        }
+
+        if vehicle.controller.as_ref().map(|c| c.chassis) != Some(handle) {
+            vehicle.controller = Some(vehicle_controller(&vehicle.params, handle));
+        }
+        let Some(controller) = &mut vehicle.controller else {
+            continue;
+        };
+
+        let params = &vehicle.params;
+        let controls = &vehicle.controls;
+        for (wheel, wheel_params) in controller.wheels_mut().iter_mut().zip(&params.wheels) {
+            wheel.engine_force = if wheel_params.driven {
+                controls.throttle * params.max_engine_force
+            } else {
+                0.0
+            };
+            wheel.steering = if wheel_params.steered {
+                controls.steering * params.max_steering_angle
+            } else {
+                0.0
+            };
+            wheel.brake = controls.brake * params.max_brake;
+        }
+
+        // Only the main colliders, not the watch colliders.
+        let filter = QueryFilter::new()
+            .exclude_sensors()
+            .exclude_rigid_body(handle)
+            .groups(InteractionGroups::new(Group::ALL, MAIN_GROUP));
+        controller.update_vehicle(
+            dt,
+            &mut sim_state.bodies,
+            &sim_state.colliders,
+            &sim_state.query_pipeline,
+            filter,
+        );
+    }
+}
+
+/// Rapier’s ray-cast vehicles are 3D only: in 2D, the chassis are simulated as plain bodies.
+#[cfg(feature = "dim2")]
+pub fn update_vehicles(_sim_state: &mut SimulationState, _dt: Real) {}
+
+/// The vehicles of the region with the given `chassis`, to hand off or export.
+pub fn vehicle_assignments(
+    sim_state: &SimulationState,
+    chassis: Vec<BodyAssignment>,
+) -> Vec<VehicleAssignment> {
+    chassis
+        .into_iter()
+        .filter_map(|chassis| {
+            let vehicle = sim_state.vehicles.get(&chassis.uuid)?;
+            Some(VehicleAssignment {
+                chassis,
+                params: vehicle.params.clone(),
+                controls: vehicle.controls,
+            })
+        })
+        .collect()
+}
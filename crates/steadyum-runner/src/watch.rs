@@ -1,3 +1,6 @@
+use crate::collision_layers::{collision_layer, unwatched_collision_groups};
+use crate::fluids::border_particles;
+use crate::joints::{joint_bodies_watch_data, joint_ghost_regions};
 use crate::neighbors::NeighborRunner;
 use crate::neighbors::Neighbors;
 use crate::region_assignment::RegionAssignments;
@@ -7,16 +10,21 @@ use futures::stream::FuturesUnordered;
 use futures::{stream, StreamExt, TryStreamExt};
 use rapier::parry::bounding_volume::{BoundingSphere, BoundingVolume};
 use rapier::prelude::*;
-use std::collections::HashMap;
-use steadyum_api_types::objects::{ClientBodyObject, WarmBodyObject, WatchedObjects};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use steadyum_api_types::objects::{
+    ClientBodyObject, WarmBodyObject, WatchedBodyObject, WatchedObjects,
+};
 use steadyum_api_types::partitionner::SceneUuid;
 use steadyum_api_types::region_db::AsyncPartitionnerServer;
-use steadyum_api_types::serialization::deserialize;
-use steadyum_api_types::simulation::SimulationBounds;
-use steadyum_api_types::zenoh::ZenohContext;
+use steadyum_api_types::serialization::deserialize_watched_objects;
+use steadyum_api_types::simulation::{SimulationBounds, WatchMargins, WatchMode};
+use steadyum_api_types::zenoh::{watch_stream_key, ZenohContext};
+use tracing::warn;
 use uuid::Uuid;
 use zenoh::prelude::r#async::AsyncResolve;
 use zenoh::prelude::SplitBuffer;
+use zenoh::subscriber::FlumeSubscriber;
 use zenoh::Session;
 
 pub const WATCH_GROUP: Group = Group::GROUP_1;
@@ -36,8 +44,6 @@ impl WatchedObject {
     }
 }
 
-pub type WatchedNeighbors = [WatchedNeighbor; 3];
-
 pub enum WatchedNeighbor {
     Local {
         bounds: SimulationBounds,
@@ -48,35 +54,175 @@ pub enum WatchedNeighbor {
     },
 }
 
+impl WatchedNeighbor {
+    fn new(neighbors: &Neighbors, bounds: SimulationBounds) -> Self {
+        match neighbors.fetch_neighbor(bounds) {
+            NeighborRunner::Local { .. } => WatchedNeighbor::Local { bounds },
+            NeighborRunner::Remote { uuid, .. } => {
+                // tracing::info!("Found remote neighbor watch: {:?}/{:?}", uuid, bounds);
+                WatchedNeighbor::Remote {
+                    uuid: *uuid,
+                    bounds,
+                }
+            }
+        }
+    }
+
+    fn bounds(&self) -> SimulationBounds {
+        match self {
+            Self::Local { bounds } | Self::Remote { bounds, .. } => *bounds,
+        }
+    }
+}
+
 pub async fn init_watched_neighbors(
     app: &AppState,
     db: &AsyncPartitionnerServer,
     neighbors: &mut Neighbors<'_>,
+    scene: SceneUuid,
     bounds: SimulationBounds,
-) -> WatchedNeighbors {
-    let watched_regions = [0, 1, 2].map(|i| {
-        let mut shift = [0; 3];
-        shift[i] = 1;
-        bounds.relative_neighbor(shift)
-    });
+    mode: WatchMode,
+) -> Vec<WatchedNeighbor> {
+    let watched_regions = match mode {
+        WatchMode::Greater => [0, 1, 2]
+            .map(|i| {
+                let mut shift = [0; 3];
+                shift[i] = 1;
+                bounds.relative_neighbor(shift)
+            })
+            .to_vec(),
+        WatchMode::All => bounds.all_neighbors().to_vec(),
+    };
     neighbors
-        .spawn_neighbors(app, db, app.scene, watched_regions.into_iter())
+        .spawn_neighbors(app, db, scene, watched_regions.iter().copied())
         .await;
-    watched_regions.map(|bounds| match neighbors.fetch_neighbor(bounds) {
-        NeighborRunner::Local { .. } => WatchedNeighbor::Local { bounds },
-        NeighborRunner::Remote { uuid, .. } => {
-            // log::info!("Found remote neighbor watch: {:?}/{:?}", uuid, bounds);
-            WatchedNeighbor::Remote {
-                uuid: *uuid,
-                bounds,
+    watched_regions
+        .into_iter()
+        .map(|bounds| WatchedNeighbor::new(neighbors, bounds))
+        .collect()
+}
+
+/// The regions to watch, in addition to `watched_neighbors`, for the state of the bodies
+/// attached by joints to bodies of this region.
+pub async fn joint_watched_neighbors(
+    app: &AppState,
+    db: &AsyncPartitionnerServer,
+    neighbors: &mut Neighbors<'_>,
+    sim_state: &SimulationState,
+    watched_neighbors: &[WatchedNeighbor],
+) -> Vec<WatchedNeighbor> {
+    let regions: Vec<_> = joint_ghost_regions(sim_state)
+        .into_iter()
+        .filter(|region| watched_neighbors.iter().all(|nbh| nbh.bounds() != *region))
+        .collect();
+    neighbors
+        .spawn_neighbors(app, db, sim_state.scene, regions.iter().copied())
+        .await;
+    regions
+        .into_iter()
+        .map(|bounds| WatchedNeighbor::new(neighbors, bounds))
+        .collect()
+}
+
+/// Subscriptions to the watch sets pushed by the remote neighbors after each of their steps.
+///
+/// This removes the query round trip from the critical path of the step. Watch sets that
+/// didn’t arrive in time are still queried by [`read_watched_objects`].
+///
+/// The neighbors only push the bodies of their watch sets when they changed, the latest
+/// bodies received from each neighbor are kept for the next steps.
+pub struct WatchSubscriptions<'a> {
+    zenoh: &'a ZenohContext,
+    scene: SceneUuid,
+    subscribers: HashMap<SimulationBounds, FlumeSubscriber<'a>>,
+    latest: HashMap<SimulationBounds, WatchedObjects>,
+}
+
+impl<'a> WatchSubscriptions<'a> {
+    pub fn new(zenoh: &'a ZenohContext, scene: SceneUuid) -> Self {
+        Self {
+            zenoh,
+            scene,
+            subscribers: HashMap::new(),
+            latest: HashMap::new(),
+        }
+    }
+
+    async fn subscribe(&mut self, region: SimulationBounds) {
+        if self.subscribers.contains_key(&region) {
+            return;
+        }
+
+        match self
+            .zenoh
+            .session
+            .declare_subscriber(watch_stream_key(self.scene, &region))
+            .res_async()
+            .await
+        {
+            Ok(subscriber) => {
+                self.subscribers.insert(region, subscriber);
+            }
+            Err(e) => tracing::error!("Failed to subscribe to the watch set of {region:?}: {e}"),
+        }
+    }
+
+    /// The watch set pushed by `region` after the step `step_id`, if it was received already.
+    fn take(&mut self, region: SimulationBounds, step_id: u64) -> Option<WatchedObjects> {
+        if let Some(subscriber) = self.subscribers.get(&region) {
+            while let Ok(sample) = subscriber.try_recv() {
+                let payload = sample.value.payload.contiguous();
+                match deserialize_watched_objects(&payload) {
+                    Ok(data) => {
+                        record_watch_set(&mut self.latest, region, data);
+                    }
+                    Err(e) => tracing::error!("Failed to decode the watch set of {region:?}: {e}"),
+                }
             }
         }
-    })
+
+        let latest = self.latest.get(&region)?;
+        (latest.step_id == step_id).then(|| latest.clone())
+    }
+
+    /// The version of the latest watch set with bodies received from `region`.
+    fn known_version(&self, region: SimulationBounds) -> Option<u64> {
+        self.latest.get(&region).map(|latest| latest.version)
+    }
+}
+
+/// Records the watch set `data` received from `region` in `latest`, returns it with its bodies
+/// if they are known.
+fn record_watch_set(
+    latest: &mut HashMap<SimulationBounds, WatchedObjects>,
+    region: SimulationBounds,
+    data: WatchedObjects,
+) -> Option<&WatchedObjects> {
+    if !data.unchanged {
+        latest.insert(region, data);
+        return latest.get(&region);
+    }
+
+    match latest.get_mut(&region) {
+        Some(known) if known.version == data.version => {
+            known.step_id = data.step_id;
+            Some(known)
+        }
+        _ => {
+            // We missed the bodies of this version, they will be queried.
+            latest.remove(&region);
+            None
+        }
+    }
 }
 
+/// Reads the watch sets computed by `watched_neighbors` after the step `step_id`.
 pub async fn read_watched_objects(
     app: &AppState,
-    watched_neighbors: &WatchedNeighbors,
+    scene: SceneUuid,
+    subscriptions: &mut WatchSubscriptions<'_>,
+    step_id: u64,
+    watched_neighbors: impl IntoIterator<Item = &WatchedNeighbor>,
 ) -> Vec<(WatchedObjects, SimulationBounds)> {
     let mut result = vec![];
     let mut fetch_from_remote_futs = FuturesUnordered::new();
@@ -86,18 +232,26 @@ pub async fn read_watched_objects(
     for nbh in watched_neighbors {
         match nbh {
             WatchedNeighbor::Local { bounds } => {
-                // log::info!("Querying local watch region: {:?}", bounds);
-                let Some(watched) = app.watch_sets.get(bounds) else {
+                // tracing::info!("Querying local watch region: {:?}", bounds);
+                let Some(watched) = app.watch_sets.get(&(scene, *bounds)) else {
                     continue;
                 };
                 result.push((watched.clone(), *bounds));
             }
             WatchedNeighbor::Remote { uuid, bounds } => {
-                let watch_key = bounds.watch_kvs_key(*uuid);
+                subscriptions.subscribe(*bounds).await;
+
+                if let Some(watched) = subscriptions.take(*bounds, step_id) {
+                    result.push((watched, *bounds));
+                    continue;
+                }
+
+                let known_version = subscriptions.known_version(*bounds);
+                let watch_key = bounds.watch_kvs_key(scene, *uuid, known_version);
                 let bounds = *bounds;
 
                 let fetch_data_fut = async move {
-                    // log::info!("Querying watch key: {}", watch_key);
+                    // tracing::info!("Querying watch key: {}", watch_key);
                     let data = app.zenoh.session.get(watch_key).res_async().await;
                     (bounds, data)
                 };
@@ -118,21 +272,27 @@ pub async fn read_watched_objects(
     drop(snd);
 
     while let Ok((nbh, replies)) = rcv.recv().await {
-        // log::info!("Found reply from {:?}.", nbh);
+        // tracing::info!("Found reply from {:?}.", nbh);
         let Ok(replies) = replies else { continue };
         let Ok(reply) = replies.recv() else { continue }; // NOTE: there should be only one reply.
         let Ok(sample) = reply.sample else { continue };
         let payload = sample.value.payload.contiguous();
-        let data: WatchedObjects = deserialize(&payload).unwrap();
-        // log::info!(
+        let data = deserialize_watched_objects(&payload).unwrap();
+        // tracing::info!(
         //     "Reply from {:?} conatined {} objects.",
         //     nbh,
         //     data.objects.len()
         // );
 
-        result.push((data, nbh));
+        match record_watch_set(&mut subscriptions.latest, nbh, data) {
+            Some(data) => result.push((data.clone(), nbh)),
+            None => warn!("The watch set of {nbh:?} changed while being queried."),
+        }
     }
 
+    // NOTE: the replies arrive in any order. Sort them so the watched bodies are always
+    //       mirrored in the same order.
+    result.sort_by_key(|(_, bounds)| *bounds);
     result
 }
 
@@ -141,27 +301,172 @@ pub fn compute_watch_data(
     num_steps_run: usize,
     reassignments: &RegionAssignments,
 ) -> WatchedObjects {
-    let mut objects = vec![];
     let my_region_aabb = sim_state.sim_bounds.aabb();
+    let handles: Vec<_> = sim_state
+        .bodies
+        .iter()
+        .filter(|(handle, body)| {
+            (body.is_dynamic() || sim_state.characters.contains_key(handle))
+                && !sim_state.watched_objects.contains_key(handle)
+                && !reassignments.reassigned_bodies.contains(handle)
+        })
+        .map(|(handle, _)| handle)
+        .collect();
 
-    for (handle, body) in sim_state.bodies.iter() {
-        if body.is_dynamic()
-            && !sim_state.watched_objects.contains_key(&handle)
-            && !reassignments.reassigned_bodies.contains(&handle)
-        {
-            let uuid = sim_state.body2uuid[&handle].clone();
+    // NOTE: the order of the handles is kept, so the watch set is the same whatever the
+    //       number of threads.
+    let objects = handles
+        .par_iter()
+        .filter_map(|handle| {
+            let body = &sim_state.bodies[*handle];
+            let uuid = sim_state.body2uuid[handle].clone();
             let predicted_pos = body.predict_position_using_velocity_and_forces(
                 sim_state.params.dt * num_steps_run as f32,
             );
 
-            let aabb = sim_state.colliders[body.colliders()[0]].compute_swept_aabb(&predicted_pos);
+            let collider = &sim_state.colliders[body.colliders()[0]];
+            let margins = &sim_state.settings.watch_margins;
+            let margin = margins.absolute + body.linvel().norm() * margins.velocity_scaled;
+            let aabb = collider.compute_swept_aabb(&predicted_pos).loosened(margin);
+
+            // NOTE: object fully inside the region are not part of the watch set. Sensors
+            //       aren’t either since they don’t affect the bodies of the neighbors.
+            (!collider.is_sensor() && !my_region_aabb.contains(&aabb)).then(|| WatchedBodyObject {
+                uuid,
+                aabb,
+                warm: WarmBodyObject::from_body(body, sim_state.step_id),
+                shape: collider.shared_shape().clone(),
+                collision_layer: collision_layer(collider),
+                epoch: sim_state.epoch(*handle),
+            })
+        })
+        .collect();
+
+    WatchedObjects {
+        step_id: sim_state.step_id,
+        version: 0,
+        unchanged: false,
+        objects,
+        joint_bodies: joint_bodies_watch_data(sim_state),
+        particles: border_particles(sim_state),
+    }
+}
 
-            // NOTE: object fully inside the region are not part of the watch set.
-            if !my_region_aabb.contains(&aabb) {
-                objects.push((uuid, aabb));
+/// The collider attached to every simulated body to find the bodies close to it.
+///
+/// It only detects the main colliders of the bodies simulated by this region: the watched
+/// bodies mirrored from the neighbors are part of the [`MAIN_GROUP`] but don’t accept
+/// interactions from the [`WATCH_GROUP`].
+pub fn watch_collider(shape: &SharedShape, margins: &WatchMargins) -> ColliderBuilder {
+    ColliderBuilder::ball(margins.watch_radius(shape))
+        .density(0.0)
+        .collision_groups(InteractionGroups::new(
+            // We don’t care about watched objects intersecting each others.
+            WATCH_GROUP,
+            MAIN_GROUP,
+        ))
+        // Watched objects don’t generate forces.
+        .solver_groups(InteractionGroups::none())
+}
+
+/// Mirrors the bodies watched from the neighbor regions as kinematic bodies with their
+/// actual shape and velocities, so the bodies of this region collide with them at the
+/// region borders.
+///
+/// Mirrors no longer part of any watch set are removed.
+pub fn update_watched_bodies(
+    sim_state: &mut SimulationState,
+    watched: &[(WatchedObjects, SimulationBounds)],
+    watch_iteration_id: usize,
+) {
+    let mut stale = vec![];
+
+    for (objects, region) in watched {
+        for object in &objects.objects {
+            match sim_state.uuid2body.get(&object.uuid) {
+                Some(handle) if sim_state.watched_objects.contains_key(handle) => {
+                    let body = &mut sim_state.bodies[*handle];
+                    body.set_position(object.warm.position, true);
+                    body.set_linvel(object.warm.linvel, true);
+                    body.set_angvel(object.warm.angvel, true);
+                    sim_state
+                        .watched_objects
+                        .insert(*handle, WatchedObject::new(*region, watch_iteration_id));
+                }
+                // The body was just assigned to this region, unless the neighbor simulates a more
+                // recent copy of it.
+                Some(handle) => {
+                    if object.epoch > sim_state.epoch(*handle) {
+                        stale.push((*handle, object.uuid, object.epoch));
+                    }
+                }
+                None => {
+                    let body = RigidBodyBuilder::kinematic_velocity_based()
+                        .position(object.warm.position)
+                        .linvel(object.warm.linvel)
+                        .angvel(object.warm.angvel);
+                    let collider = ColliderBuilder::new(object.shape.clone()).collision_groups(
+                        unwatched_collision_groups(
+                            &sim_state.settings.collision_matrix,
+                            object.collision_layer,
+                        ),
+                    );
+                    let handle = sim_state.bodies.insert(body);
+                    sim_state
+                        .colliders
+                        .insert_with_parent(collider, handle, &mut sim_state.bodies);
+                    sim_state.body2uuid.insert(handle, object.uuid);
+                    sim_state.uuid2body.insert(object.uuid, handle);
+                    sim_state
+                        .watched_objects
+                        .insert(handle, WatchedObject::new(*region, watch_iteration_id));
+                }
             }
         }
     }
 
-    WatchedObjects { objects }
+    // The neighbor owns these bodies, they will be mirrored from the next watch sets.
+    for (handle, uuid, epoch) in stale {
+        warn!("Discarding a stale copy of body {uuid:?}, owned by a neighbor at epoch {epoch}.");
+        sim_state.bodies.remove(
+            handle,
+            &mut sim_state.islands,
+            &mut sim_state.colliders,
+            &mut sim_state.impulse_joints,
+            &mut sim_state.multibody_joints,
+            true,
+        );
+        sim_state.body2uuid.remove(&handle);
+        sim_state.uuid2body.remove(&uuid);
+    }
+
+    let joint_ghosts: HashSet<_> = sim_state
+        .joint_ghosts
+        .values()
+        .map(|ghost| ghost.handle)
+        .collect();
+    let mut outdated: Vec<_> = sim_state
+        .watched_objects
+        .iter()
+        .filter(|(handle, watched)| {
+            watched.watch_iteration_id != watch_iteration_id && !joint_ghosts.contains(handle)
+        })
+        .map(|(handle, _)| *handle)
+        .collect();
+    outdated.sort_by_key(|handle| handle.into_raw_parts());
+
+    for handle in outdated {
+        sim_state.bodies.remove(
+            handle,
+            &mut sim_state.islands,
+            &mut sim_state.colliders,
+            &mut sim_state.impulse_joints,
+            &mut sim_state.multibody_joints,
+            true,
+        );
+        sim_state.watched_objects.remove(&handle);
+        if let Some(uuid) = sim_state.body2uuid.remove(&handle) {
+            sim_state.uuid2body.remove(&uuid);
+        }
+    }
 }
@@ -0,0 +1,14 @@
+#[derive(clap::Parser, Debug, Clone)]
+#[command(author, version, about, long_about = None)]
+pub struct CliArgs {
+    /// Identifies this node in the staged rollouts of the master. Defaults to its local IP.
+    #[arg(long)]
+    pub node_id: Option<String>,
+    /// Keep running this version, whatever the master serves. It must have been downloaded
+    /// before, or be the one the master serves.
+    #[arg(long)]
+    pub pin_version: Option<String>,
+    /// Run executables that aren’t signed with `RELEASE_PUBLIC_KEY`, e.g., local builds.
+    #[arg(long)]
+    pub dev: bool,
+}
@@ -1,11 +1,52 @@
+mod cli;
+
+use crate::cli::CliArgs;
+use clap::Parser;
 use log::{error, info, warn};
+use std::path::PathBuf;
 use std::process::{Child, Command};
 use std::time::Duration;
-use steadyum_api_types::region_db::AsyncPartitionnerServer;
+use steadyum_api_types::env::CONFIG;
+use steadyum_api_types::partitionner::{is_valid_version, GetExesResponse};
+use steadyum_api_types::region_db::{AsyncPartitionnerServer, PartitionnerError};
+
+/// The directory the executables of each version are downloaded into.
+const VERSIONS_DIR: &str = "versions";
 
 #[derive(Default)]
 struct AppState {
     partitionner: Option<Child>,
+    /// The version of the executables run by `partitionner`.
+    version: Option<String>,
+}
+
+fn version_dir(version: &str) -> PathBuf {
+    PathBuf::from(VERSIONS_DIR).join(version)
+}
+
+/// The most recently downloaded version, if any.
+async fn latest_downloaded_version() -> anyhow::Result<Option<String>> {
+    let mut latest = None;
+    let Ok(mut entries) = tokio::fs::read_dir(VERSIONS_DIR).await else {
+        return Ok(None);
+    };
+
+    while let Some(entry) = entries.next_entry().await? {
+        let Some(version) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        // Skips the unfinished downloads.
+        if !is_valid_version(&version) {
+            continue;
+        }
+
+        let modified = entry.metadata().await?.modified()?;
+        if latest.as_ref().map_or(true, |(t, _)| modified > *t) {
+            latest = Some((modified, version));
+        }
+    }
+
+    Ok(latest.map(|(_, version)| version))
 }
 
 impl AppState {
@@ -22,24 +63,184 @@ impl AppState {
                 info!("Successfully shutdown partitionner.");
             }
         }
+        self.version = None;
 
         Ok(())
     }
 
-    fn spawn_partitionner(&mut self) {
+    fn spawn_partitionner(&mut self, version: &str) {
         assert!(self.partitionner.is_none());
-        let mut args = vec!["--runner".to_string()];
-        match Command::new("./partitionner").args(args).spawn() {
-            Ok(child) => self.partitionner = Some(child),
+        let dir = version_dir(version);
+        let args = vec!["--runner".to_string()];
+        match Command::new(dir.join("partitionner"))
+            .args(args)
+            .env("RUNNER_EXE", dir.join("runner"))
+            .spawn()
+        {
+            Ok(child) => {
+                info!("Running version {version}.");
+                self.partitionner = Some(child);
+                self.version = Some(version.to_string());
+            }
             Err(e) => error!("Could not start partitionner: {}", e),
         }
     }
+
+    /// Runs the version the master asks for, or the pinned one, downloading it if needed.
+    async fn update(
+        &mut self,
+        main_partitionner: &AsyncPartitionnerServer,
+        args: &CliArgs,
+        node_id: &str,
+    ) -> anyhow::Result<()> {
+        let healthy = match &self.partitionner {
+            Some(_) => AsyncPartitionnerServer::local()?.heartbeat().await.is_ok(),
+            None => false,
+        };
+        let response = main_partitionner
+            .update_check(node_id.to_string(), self.version.clone(), healthy)
+            .await?;
+        let target = args.pin_version.clone().unwrap_or(response.version);
+
+        if self.partitionner.is_some() && self.version.as_ref() == Some(&target) {
+            return Ok(());
+        }
+
+        if !is_valid_version(&target) {
+            anyhow::bail!("Invalid version: {target:?}.");
+        }
+
+        if !version_dir(&target).exists() {
+            // Request the partitionner+runner executables.
+            let base = match self.version.clone() {
+                Some(version) => Some(version),
+                None => latest_downloaded_version().await?,
+            };
+            let exes = download(main_partitionner, base).await?;
+            if exes.version != target {
+                warn!(
+                    "Version {target} isn’t downloaded, and the master serves {}.",
+                    exes.version
+                );
+                return Ok(());
+            }
+            check_signatures(&exes, args.dev)?;
+            info!("Retrieved version {target}.");
+            store_exes(&exes).await?;
+        }
+
+        // Checked again before every start, in case the downloaded executables were modified.
+        check_signatures(&load_exes(&target).await?, args.dev)?;
+
+        self.kill_partitionner().await?;
+        self.spawn_partitionner(&target);
+        Ok(())
+    }
+}
+
+/// Downloads the executables served by the master, as deltas from the ones of `base` if the
+/// master still has that version, or whole otherwise.
+async fn download(
+    main_partitionner: &AsyncPartitionnerServer,
+    base: Option<String>,
+) -> anyhow::Result<GetExesResponse> {
+    if let Some(base) = base {
+        match download_delta(main_partitionner, &base).await {
+            Ok(exes) => return Ok(exes),
+            Err(e) => warn!("Couldn’t update from version {base}: {e}. Downloading it whole."),
+        }
+    }
+
+    let exes = main_partitionner.get_exes().await?;
+    exes.verify()?;
+    Ok(exes)
+}
+
+async fn download_delta(
+    main_partitionner: &AsyncPartitionnerServer,
+    base: &str,
+) -> anyhow::Result<GetExesResponse> {
+    let delta = main_partitionner.get_exes_delta(base.to_string()).await?;
+    let exes = delta.apply(&load_exes(base).await?)?;
+    exes.verify()?;
+    info!(
+        "Patched version {base} into {}, with {} bytes of deltas.",
+        exes.version,
+        delta.runner.delta.len() + delta.partitionner.delta.len()
+    );
+    Ok(exes)
+}
+
+/// Refuses the executables not signed with `RELEASE_PUBLIC_KEY`, unless in `dev` mode.
+fn check_signatures(exes: &GetExesResponse, dev: bool) -> anyhow::Result<()> {
+    if CONFIG.release_public_key.is_empty() {
+        if !dev {
+            anyhow::bail!(
+                "No RELEASE_PUBLIC_KEY to check the executables with, outside of dev mode."
+            );
+        }
+    } else if exes.is_signed() || !dev {
+        return exes.verify_signatures(&CONFIG.release_public_key);
+    }
+
+    warn!(
+        "Running version {} without checking its signatures, in dev mode.",
+        exes.version
+    );
+    Ok(())
+}
+
+/// Reads the executables of a downloaded version.
+async fn load_exes(version: &str) -> anyhow::Result<GetExesResponse> {
+    let dir = version_dir(version);
+    let runner = tokio::fs::read(dir.join("runner")).await?;
+    let partitionner = tokio::fs::read(dir.join("partitionner")).await?;
+    let mut exes = GetExesResponse::new(runner, partitionner, Some(version.to_string()));
+    exes.runner_signature = tokio::fs::read_to_string(dir.join("runner.sig")).await.ok();
+    exes.partitionner_signature = tokio::fs::read_to_string(dir.join("partitionner.sig"))
+        .await
+        .ok();
+    Ok(exes)
+}
+
+/// Writes the executables of `exes` into the directory of their version.
+async fn store_exes(exes: &GetExesResponse) -> anyhow::Result<()> {
+    // Download next to the final directory, so an interrupted download isn’t mistaken for a
+    // complete one. Versions can’t start with a dot.
+    let dir = version_dir(&exes.version);
+    let tmp_dir = PathBuf::from(VERSIONS_DIR).join(format!(".{}.download", exes.version));
+    tokio::fs::create_dir_all(&tmp_dir).await?;
+    tokio::fs::write(tmp_dir.join("partitionner"), &exes.partitionner).await?;
+    tokio::fs::write(tmp_dir.join("runner"), &exes.runner).await?;
+    if let Some(signature) = &exes.runner_signature {
+        tokio::fs::write(tmp_dir.join("runner.sig"), signature).await?;
+    }
+    if let Some(signature) = &exes.partitionner_signature {
+        tokio::fs::write(tmp_dir.join("partitionner.sig"), signature).await?;
+    }
+
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        for exe in ["partitionner", "runner"] {
+            tokio::fs::set_permissions(tmp_dir.join(exe), std::fs::Permissions::from_mode(0o755))
+                .await?;
+        }
+    }
+
+    tokio::fs::rename(tmp_dir, dir).await?;
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     init_log();
 
+    let args = CliArgs::parse();
+    let node_id = match &args.node_id {
+        Some(node_id) => node_id.clone(),
+        None => local_ip_address::local_ip()?.to_string(),
+    };
     let mut state = AppState::default();
     let main_partitionner = AsyncPartitionnerServer::new()?;
 
@@ -47,40 +248,13 @@ async fn main() -> anyhow::Result<()> {
         // Send heartbeat.
         match main_partitionner.heartbeat().await {
             Ok(()) => {
-                if state.partitionner.is_none() {
-                    // Request the partitionner+runner executables.
-                    match main_partitionner.get_exes().await {
-                        Ok(exes) => {
-                            info!("Retireving runner/partitionner executables.");
-                            tokio::fs::write("partitionner", exes.partitionner)
-                                .await
-                                .unwrap();
-                            tokio::fs::write("runner", exes.runner).await.unwrap();
-
-                            #[cfg(target_family = "unix")]
-                            {
-                                use std::os::unix::fs::PermissionsExt;
-                                tokio::fs::set_permissions(
-                                    "partitionner",
-                                    std::fs::Permissions::from_mode(755),
-                                )
-                                .await
-                                .unwrap();
-                                tokio::fs::set_permissions(
-                                    "runner",
-                                    std::fs::Permissions::from_mode(755),
-                                )
-                                .await
-                                .unwrap();
-                            }
-
-                            info!("Retireved runners/partitioners. Spawning partitionner.");
-                            state.spawn_partitionner();
-                        }
-                        Err(e) => error!("Couldn’t retrieve executables: {e}"),
-                    }
+                if let Err(e) = state.update(&main_partitionner, &args, &node_id).await {
+                    error!("Couldn’t update the node: {e}");
                 }
             }
+            Err(e) if !PartitionnerError::is_gone_error(&e) => {
+                warn!("Heartbeat failed: {e}. Trying again later.");
+            }
             Err(e) => {
                 // Partitionner didn’t respond, kill the local instance.
                 // NOTE: this is probably too aggressive for any production usage,